@@ -0,0 +1,241 @@
+//! Generates a method of `Client` for every operation of `openapi.json`
+
+use serde_json::Value;
+use std::fmt::Write;
+use std::{env, fs, path::Path};
+
+const KEYWORDS: &[&str] = &[
+    "as", "fn", "impl", "in", "match", "mod", "ref", "type", "use",
+];
+
+fn main() {
+    println!("cargo:rerun-if-changed=openapi.json");
+    let document: Value = serde_json::from_str(
+        &fs::read_to_string("openapi.json").expect("Failed to read openapi.json"),
+    )
+    .expect("Invalid openapi.json");
+
+    let mut methods = String::new();
+    let mut ids = Vec::new();
+    let paths = document["paths"]
+        .as_object()
+        .expect("No paths in openapi.json");
+    for (path, item) in paths {
+        for (method, operation) in item.as_object().into_iter().flatten() {
+            let id = operation["operationId"]
+                .as_str()
+                .unwrap_or_else(|| panic!("{} {} has no operationId", method, path));
+            ids.push(id.to_string());
+            methods.push_str(&generate(&document, path, method, operation));
+        }
+    }
+
+    let mut out = String::from("impl Client {\n");
+    out.push_str(&methods);
+    out.push_str("}\n\n/// `operationId` of every operation the client covers\n");
+    writeln!(out, "pub const OPERATIONS: &[&str] = &{:?};", ids).unwrap();
+    let target = Path::new(&env::var("OUT_DIR").unwrap()).join("operations.rs");
+    fs::write(target, out).expect("Failed to write the generated client");
+}
+
+/// One `async fn` sending the operation and decoding its answer
+fn generate(document: &Value, path: &str, method: &str, operation: &Value) -> String {
+    let mut out = String::new();
+    let mut docs: Vec<&str> = Vec::new();
+    for key in ["summary", "description"] {
+        if let Some(text) = operation[key].as_str() {
+            if !docs.is_empty() {
+                docs.push("");
+            }
+            docs.extend(text.lines());
+        }
+    }
+    for line in docs {
+        writeln!(
+            out,
+            "    ///{}{}",
+            if line.is_empty() { "" } else { " " },
+            line
+        )
+        .unwrap();
+    }
+    writeln!(out, "    ///\n    /// `{} {}`", method.to_uppercase(), path).unwrap();
+
+    let mut args = Vec::new();
+    let mut build = String::new();
+    let mut url = format!("{:?}", path);
+    let mut segments = String::new();
+    let mut query = Vec::new();
+    for parameter in operation["parameters"].as_array().into_iter().flatten() {
+        let parameter = resolve(document, parameter);
+        let name = parameter["name"].as_str().unwrap();
+        let ident = identifier(name);
+        let required = parameter["required"].as_bool().unwrap_or(false);
+        let kind = match parameter["schema"]["type"].as_str() {
+            Some("integer") => "u64",
+            Some("boolean") => "bool",
+            _ => "&str",
+        };
+        match parameter["in"].as_str() {
+            Some("path") => {
+                args.push(format!("{}: &str", ident));
+                url = url.replace(&format!("{{{}}}", name), "{}");
+                write!(segments, ", segment({})", ident).unwrap();
+            }
+            Some("header") => {
+                args.push(format!("{}: &str", ident));
+                writeln!(
+                    build,
+                    "        let request = request.header({:?}, {});",
+                    name, ident
+                )
+                .unwrap();
+            }
+            _ => {
+                match required {
+                    true => args.push(format!("{}: {}", ident, kind)),
+                    false => args.push(format!("{}: Option<{}>", ident, kind)),
+                }
+                query.push((name.to_string(), ident, required));
+            }
+        }
+    }
+    if !query.is_empty() {
+        let required: Vec<String> = query
+            .iter()
+            .filter(|(_, _, required)| *required)
+            .map(|(name, ident, _)| format!("({:?}, {}.to_string())", name, ident))
+            .collect();
+        let optional = query.iter().filter(|(_, _, required)| !required);
+        writeln!(
+            build,
+            "        let {}query: Vec<(&str, String)> = vec![{}];",
+            if optional.clone().next().is_some() {
+                "mut "
+            } else {
+                ""
+            },
+            required.join(", ")
+        )
+        .unwrap();
+        for (name, ident, _) in optional {
+            writeln!(
+                build,
+                "        if let Some(value) = {} {{\n            query.push(({:?}, value.to_string()));\n        }}",
+                ident, name
+            )
+            .unwrap();
+        }
+        build.push_str("        let request = request.query(&query);\n");
+    }
+    let body_types = content_types(&operation["requestBody"]);
+    if body_types.iter().any(|t| t == "application/json") {
+        args.push("body: &serde_json::Value".to_string());
+        build.push_str("        let request = request.json(body);\n");
+    } else if let Some(content_type) = body_types.first() {
+        args.push("body: Vec<u8>".to_string());
+        if content_type != "*/*" {
+            writeln!(
+                build,
+                "        let request = request.header(\"Content-Type\", {:?});",
+                content_type
+            )
+            .unwrap();
+        }
+        build.push_str("        let request = request.body(body);\n");
+    }
+
+    let answers: Vec<String> = operation["responses"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(status, _)| status.starts_with('2'))
+        .flat_map(|(_, response)| content_types(response))
+        .collect();
+    let (output, decode) = if answers.iter().any(|t| t == "application/json") {
+        ("serde_json::Value", "response.json().await?")
+    } else if answers.iter().any(|t| t.starts_with("text/")) {
+        ("String", "response.text().await?")
+    } else if !answers.is_empty() {
+        ("Vec<u8>", "response.bytes().await?.to_vec()")
+    } else {
+        ("()", "")
+    };
+
+    let authenticated = operation["security"]
+        .as_array()
+        .is_some_and(|s| !s.is_empty());
+    let mut signature = vec!["&self".to_string()];
+    signature.extend(args);
+    writeln!(
+        out,
+        "    pub async fn {}({}) -> Result<{}> {{",
+        identifier(operation["operationId"].as_str().unwrap()),
+        signature.join(", "),
+        output
+    )
+    .unwrap();
+    let url = match segments.is_empty() {
+        true => url,
+        false => format!("&format!({}{})", url, segments),
+    };
+    writeln!(
+        out,
+        "        let request = self.request(reqwest::Method::{}, {}, {});",
+        method.to_uppercase(),
+        url,
+        authenticated
+    )
+    .unwrap();
+    out.push_str(&build);
+    match output {
+        "()" => out.push_str("        self.send(request).await?;\n        Ok(())\n"),
+        _ => writeln!(
+            out,
+            "        let response = self.send(request).await?;\n        Ok({})",
+            decode
+        )
+        .unwrap(),
+    }
+    out.push_str("    }\n\n");
+    out
+}
+
+/// Follow a `$ref` into the components
+fn resolve<'a>(document: &'a Value, value: &'a Value) -> &'a Value {
+    match value["$ref"].as_str() {
+        Some(reference) => reference
+            .trim_start_matches("#/")
+            .split('/')
+            .fold(document, |value, key| &value[key]),
+        None => value,
+    }
+}
+
+fn content_types(value: &Value) -> Vec<String> {
+    value["content"]
+        .as_object()
+        .map(|content| content.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// `apiStatus` and `X-Webhook-Timestamp` as Rust names, `api_status` and `x_webhook_timestamp`
+fn identifier(name: &str) -> String {
+    let mut ident = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            if !ident.is_empty() && !ident.ends_with('_') {
+                ident.push('_');
+            }
+            ident.push(c.to_ascii_lowercase());
+        } else if c.is_ascii_alphanumeric() {
+            ident.push(c);
+        } else if !ident.ends_with('_') {
+            ident.push('_');
+        }
+    }
+    match KEYWORDS.contains(&ident.as_str()) {
+        true => format!("r#{}", ident),
+        false => ident,
+    }
+}
@@ -0,0 +1,138 @@
+//! Client of the foundry-watcher admin API.
+//!
+//! The methods of [`Client`] are generated by `build.rs` from `openapi.json`, the document the
+//! server describes its handlers with. The server's tests fail when the document no longer
+//! matches them, so both are changed in the same commit.
+//!
+//! ```no_run
+//! # async fn run() -> foundry_watcher_client::Result<()> {
+//! let client = foundry_watcher_client::Client::new("http://localhost:4445")
+//!     .with_token(std::env::var("ADMIN_API_TOKEN").ok());
+//! let status = client.api_status().await?;
+//! println!("{}", status["world"]);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+/// Answer of an operation, JSON answers are returned as they were sent
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The request could not be sent or the answer not be read
+    Http(reqwest::Error),
+    /// The server refused the request, e.g. `401` for a missing `ADMIN_API_TOKEN`
+    Status { status: u16, body: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(e) => write!(f, "{}", e),
+            Self::Status { status, body } => write!(f, "HTTP {}: {}", status, body),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Http(e)
+    }
+}
+
+/// The admin API of one instance, on its `ADMIN_API_PORT`
+#[derive(Debug, Clone)]
+pub struct Client {
+    base_url: String,
+    token: Option<String>,
+    http: reqwest::Client,
+}
+
+impl Client {
+    /// Client of the admin API at `base_url`, e.g. `http://localhost:4445`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: None,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// `ADMIN_API_TOKEN` of the instance, the operations below `/api` need it
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    fn request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        authenticated: bool,
+    ) -> reqwest::RequestBuilder {
+        let request = self
+            .http
+            .request(method, format!("{}{}", self.base_url, path));
+        match (&self.token, authenticated) {
+            (Some(token), true) => request.bearer_auth(token),
+            _ => request,
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        Err(Error::Status {
+            status: status.as_u16(),
+            body: response.text().await.unwrap_or_default(),
+        })
+    }
+}
+
+/// Percent-encode a value placed in the path, e.g. a webhook name
+fn segment(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+include!(concat!(env!("OUT_DIR"), "/operations.rs"));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_operation_of_the_document_is_generated() {
+        let document: serde_json::Value =
+            serde_json::from_str(include_str!("../openapi.json")).unwrap();
+        let operations: usize = document["paths"]
+            .as_object()
+            .unwrap()
+            .values()
+            .map(|item| item.as_object().unwrap().len())
+            .sum();
+        assert_eq!(OPERATIONS.len(), operations);
+        assert!(OPERATIONS.contains(&"apiStatus"));
+    }
+
+    #[test]
+    fn path_values_are_encoded() {
+        assert_eq!(segment("nightly-backup"), "nightly-backup");
+        assert_eq!(segment("a b/c"), "a%20b%2Fc");
+    }
+}
@@ -0,0 +1,149 @@
+## Einrichtungsseite, Einträge dürfen HTML enthalten
+
+setup-title = Foundry VTT Installation
+setup-description = Foundry VTT Installationsprogramm
+setup-instructions = Anleitung
+setup-option-url = Option 1: Über eine URL herunterladen
+setup-step-login = Melde dich bei Foundry VTT an:
+setup-step-profile = Klicke oben rechts auf dein Profil
+setup-step-purchased = Klicke auf „Purchased Content“
+setup-step-version = Stelle <strong>„Download Version“</strong> auf die gewünschte Version
+setup-step-os = Stelle <strong>„Operating System“</strong> auf <strong>linux/nodejs</strong>
+setup-step-timed-url = Klicke auf „Timed URL“
+setup-step-paste = Füge den Link in das Eingabefeld unten ein
+setup-option-upload = Option 2: ZIP-Datei direkt hochladen
+setup-step-select = Wähle die Foundry VTT ZIP-Datei auf deinem Gerät aus
+setup-step-drop = Oder ziehe die Datei in den Upload-Bereich
+setup-tab-url = Download per URL
+setup-tab-file = Datei hochladen
+setup-url-label = URL hier einfügen
+setup-download = 🚀 Herunterladen
+setup-drop-here = Foundry VTT ZIP-Datei hierher ziehen
+setup-or = oder
+setup-choose-file = Datei auswählen
+setup-upload = 📤 Hochladen
+setup-methods-label = Installationsart
+setup-file-label = Foundry VTT ZIP-Datei
+
+## Skripte der Einrichtungsseite, Variablen setzt der Browser ein
+
+setup-js-started = Vorgang gestartet. Der Server beendet sich, sobald er abgeschlossen ist.
+setup-js-request-failed = Die Anfrage ist fehlgeschlagen. Bitte versuche es erneut.
+setup-js-enter-url = Bitte gib eine URL ein.
+setup-js-select-file = Bitte wähle eine Datei zum Hochladen aus.
+setup-js-error = Ein Fehler ist aufgetreten: { $message }
+setup-js-unknown-error = Unbekannter Fehler
+setup-js-complete = Installation abgeschlossen! Warte 30 Sekunden, bis der Server beendet ist...
+setup-js-event-error = Fehler beim Verarbeiten eines Server-Ereignisses. Bitte versuche es erneut.
+setup-js-connection-lost = Verbindung zum Server verloren. Bitte versuche es erneut.
+setup-js-no-response = Der Server hat nicht rechtzeitig geantwortet. Bitte versuche es erneut.
+setup-js-checking = Suche Foundry VTT ({ $attempts }/{ $max })...
+setup-js-ready = Foundry VTT ist bereit! Weiterleitung...
+setup-js-license-ready = Die Lizenzseite von Foundry VTT ist bereit! Weiterleitung...
+setup-js-not-ready = Foundry VTT ist noch nicht bereit. Nächster Versuch in { $seconds } Sekunden...
+setup-js-max-attempts = Maximale Anzahl an Versuchen erreicht. Bitte lade die Seite manuell neu.
+setup-js-needs-time = Foundry VTT braucht eventuell länger zum Starten. Lade die Seite gleich neu.
+setup-js-retrying = Verbindung fehlgeschlagen. Neuer Versuch in { $seconds } Sekunden...
+setup-js-unreachable = Foundry VTT ist nach mehreren Versuchen nicht erreichbar. Bitte lade die Seite manuell neu.
+setup-js-couldnt-connect = Keine Verbindung zu Foundry VTT. Lade die Seite gleich neu.
+setup-js-shutdown-complete = Der Server wurde beendet. Starte Weiterleitung...
+setup-js-redirecting = Starte Weiterleitung...
+
+## Fortschrittsseite für Browser ohne JavaScript
+
+progress-title = Foundry VTT wird installiert
+progress-label = Installationsfortschritt
+progress-running = Installation läuft
+progress-waiting = Warte auf den Start der Installation...
+progress-running-hint = Diese Seite lädt sich alle paar Sekunden neu.
+progress-complete = Installation abgeschlossen
+progress-complete-hint = Foundry VTT startet, diese Seite öffnet es in etwa 30 Sekunden. <a href="/">Foundry VTT jetzt öffnen</a>
+progress-error = Installation fehlgeschlagen
+progress-error-hint = <a href="/">Zurück zum Installer</a>
+
+## Doctor
+
+doctor-writable = { $path } beschreibbar
+doctor-missing = Verzeichnis existiert nicht
+doctor-mount-volume = Binde ein Volume unter { $path } ein
+doctor-can-write = Dateien können angelegt werden
+doctor-can-write-windows = Dateien können auf einem Windows-Bind-Mount angelegt werden
+doctor-windows-slow = Foundry ist auf über Docker Desktop geteilten Windows-Laufwerken langsam: viele kleine Dateien und keine Änderungsereignisse. Verwende für { $path } ein benanntes Volume oder ein Verzeichnis im WSL2-Dateisystem
+doctor-read-only = { $path } ist schreibgeschützt eingebunden, entferne `:ro` vom Volume
+doctor-check-host = Prüfe Besitzer und Rechte des Host-Verzeichnisses, das unter { $path } eingebunden ist
+doctor-owner = { $path } gehört uid { $owner }, der Container läuft aber als uid { $uid }. Führe auf dem Host `chown -R { $uid }:{ $gid } <Host-Pfad>` aus
+doctor-owner-rootless = { $path } gehört { $owner }, der Container läuft aber als uid { $uid } in einem User-Namespace{ $host }. Führe auf dem Host `podman unshare chown -R { $uid }:{ $gid } <Host-Pfad>` aus oder starte den Container mit `--userns=keep-id`
+doctor-owner-unmapped = einem Host-Benutzer, der nicht in den Container abgebildet ist
+doctor-owner-uid = uid { $uid }
+doctor-host-uid = { " " }(Host-uid { $uid })
+doctor-selinux-label = SELinux ist aktiv und { $path } hat das Label { $label }, in das Container nicht schreiben dürfen. Ergänze `:z` (geteilt) oder `:Z` (privat) am Volume, z. B. `-v /host/dir:{ $path }:Z`.
+doctor-selinux-private = SELinux ist aktiv und { $path } wurde privat für einen anderen Container umgelabelt ({ $label }). Verwende `:z` statt `:Z`, wenn sich mehrere Container das Volume teilen.
+doctor-apparmor = Das AppArmor-Profil { $profile } hat den Zugriff auf { $path } verweigert. Erlaube den Pfad im Profil oder teste mit `--security-opt apparmor=unconfined`.
+doctor-kernel-log = Kernel-Log: { $line }
+doctor-foundry-config = Foundry-Konfiguration
+doctor-foundry-config-ok = keine bekannten Fehlkonfigurationen
+doctor-foundry-config-lints = { $count } Einstellungen prüfen
+doctor-lint-hostname = APPLICATION_HOST ist `{ $host }`, Foundry erwartet einen reinen Hostnamen und baut daraus kaputte Einladungslinks. Setze `APPLICATION_HOST={ $bare }`
+doctor-lint-options-hostname = options.json setzt den Hostnamen `{ $host }`, der zugunsten von APPLICATION_HOST ignoriert wird. Entferne ihn oder ändere ihn zu `{ $bare }`
+doctor-lint-route-prefix-url = options.json setzt routePrefix `{ $prefix }`, Foundry erwartet einen Pfad wie `foundry` und liefert das Spiel unter `/foundry/` aus. Entferne Schema und Host
+doctor-lint-route-prefix = options.json setzt routePrefix `{ $prefix }`, Foundry antwortet also nur unter `/{ $prefix }/`, JOIN_URL `{ $url }` beginnt aber nicht mit diesem Pfad. Ergänze `/{ $prefix }` in JOIN_URL und lass den Reverse Proxy den Pfad unverändert weiterleiten
+doctor-lint-proxy-ssl-port = options.json setzt proxyPort 80, der Container startet Foundry aber mit `--proxySSL`, Browser versuchen also HTTPS auf Port 80. Setze proxyPort auf 443 oder entferne ihn
+doctor-lint-upnp = options.json aktiviert UPnP, das der Container mit `--noupnp` abschaltet: Portweiterleitungen aus einem Container heraus erreichen den Router nicht. Leite den Port am Router oder Reverse Proxy weiter und entferne `upnp`
+doctor-lint-language = options.json setzt die Sprache `{ $language }`, kein installiertes System oder Modul bringt aber `{ $lang }` mit und Foundry fällt auf Englisch zurück. Installiere ein Übersetzungsmodul für `{ $lang }` oder setze die Sprache auf `en.core`
+doctor-capability = Optionale Funktion { $name }
+doctor-capability-configured = eingerichtet, noch nicht benutzt
+doctor-capability-active = funktioniert
+doctor-capability-failed = eingerichtet, funktioniert aber nicht
+doctor-failed = { $failed } von { $total } Prüfungen fehlgeschlagen
+
+## Fehlersuche
+
+troubleshoot-ask-problem = Was funktioniert nicht?
+troubleshoot-ask-choice = Auswahl 1-{ $count }:
+troubleshoot-ask-world = Welche Welt lädt nicht?
+troubleshoot-problem-unreachable = Der Server ist nicht erreichbar
+troubleshoot-problem-world = Eine Welt lädt nicht
+troubleshoot-problem-permissions = Fehler „Permission denied“
+troubleshoot-problem-other = Etwas anderes, alles prüfen
+troubleshoot-port = Foundry auf Port { $port }
+troubleshoot-port-world = antwortet, Welt { $world } ist aktiv
+troubleshoot-port-setup = antwortet mit dem Setup-Bildschirm
+troubleshoot-port-published = Foundry antwortet im Container. Verbindet sich der Browser trotzdem nicht, veröffentliche den Port mit `-p { $port }:{ $port }` und prüfe Reverse Proxy und Firewall
+troubleshoot-port-closed = auf dem Port lauscht nichts
+troubleshoot-port-not-running = Foundry läuft nicht, `docker logs <container>` zeigt, warum es beendet wurde oder nicht startete
+troubleshoot-port-installer = der Installer wartet auf ein Foundry-Release, öffne ihn im Browser, um eines zu installieren
+troubleshoot-port-not-foundry = der Port ist offen, aber Foundry antwortet nicht: { $error }
+troubleshoot-port-starting = Foundry startet eventuell noch oder hängt, siehe `docker logs <container>` und `GET /healthz`
+troubleshoot-join-url = JOIN_URL { $url }
+troubleshoot-join-status = antwortet mit { $status }
+troubleshoot-join-proxy = Der Reverse Proxy antwortet, erreicht Foundry aber nicht. Richte ihn auf Port { $port } dieses Containers
+troubleshoot-join-unreachable = Die Adresse antwortet im Container nicht. Prüfe den DNS-Eintrag und den Reverse Proxy und teste von einem anderen Gerät: viele Router leiten die eigene öffentliche Adresse nicht zurück
+troubleshoot-worlds = Welten
+troubleshoot-no-world = keine Welt gewählt, prüfe die Welt, die nicht lädt, mit `--world <id>`
+troubleshoot-world-manifest = world.json von { $world }
+troubleshoot-world-manifest-ok = { $title }, System { $system }, zuletzt mit Foundry { $core } geöffnet
+troubleshoot-world-restore = Stelle die Welt mit `foundry-watcher backup restore --world { $world } <snapshot>` aus einem Snapshot wieder her
+troubleshoot-world-system = System { $system }
+troubleshoot-world-system-installed = installiert
+troubleshoot-world-system-missing = nicht installiert
+troubleshoot-world-install-system = Installiere das System { $system } im Setup-Bildschirm oder mit INSTALL_SYSTEMS, ohne es startet Foundry die Welt nicht
+troubleshoot-world-version = Foundry-Version
+troubleshoot-world-version-ok = die Welt wurde zuletzt mit { $core } geöffnet, { $installed } ist installiert
+troubleshoot-world-version-newer = die Welt wurde zuletzt mit { $core } geöffnet, neuer als das installierte { $installed }
+troubleshoot-world-version-install = Eine migrierte Welt öffnet sich in einer älteren Generation nicht. Installiere Foundry { $core } oder stelle einen Snapshot von vor der Migration wieder her
+troubleshoot-world-version-older = die Welt wurde zuletzt mit { $core } geöffnet, Foundry { $installed } migriert sie beim Start
+troubleshoot-world-version-migrate = Erstelle vor dem Start einen Snapshot mit `foundry-watcher backup create`, die Migration lässt sich nicht rückgängig machen
+troubleshoot-world-lock = Wartungssperre
+troubleshoot-world-locked = gesperrt seit { $since }
+troubleshoot-world-unlock = Spieler sehen den Wartungshinweis, `foundry-watcher world unlock { $world }` hebt ihn auf
+troubleshoot-world-databases = Welt-Datenbanken
+troubleshoot-world-problems = { $count } Probleme gefunden
+troubleshoot-world-log = Foundry-Log
+troubleshoot-world-errors = { $count } Fehler in den letzten { $hours } Stunden
+troubleshoot-world-bisect = Lädt die Welt weiterhin nicht, findet `foundry-watcher bisect-modules --world { $world }` das Modul, das sie kaputt macht
+troubleshoot-summary = Füge das in das Issue ein:
+troubleshoot-summary-problem = Problem: { $problem }
+
+## Wartung
+
+maintenance-locked = Diese Welt ist wegen Wartungsarbeiten gesperrt, bitte schau später wieder vorbei.
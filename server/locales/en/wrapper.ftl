@@ -0,0 +1,149 @@
+## Setup page, entries may contain HTML
+
+setup-title = Foundry VTT Installer
+setup-description = Foundry VTT Content Downloader
+setup-instructions = Instructions
+setup-option-url = Option 1: Download using URL
+setup-step-login = Log into Foundry VTT at
+setup-step-profile = Click your profile in the upper right hand corner
+setup-step-purchased = Click purchased content
+setup-step-version = Change <strong>"Download Version"</strong> to desired version
+setup-step-os = Change <strong>"Operating System"</strong> to <strong>linux/nodejs</strong>
+setup-step-timed-url = Click time URL
+setup-step-paste = Paste it into the input field below
+setup-option-upload = Option 2: Upload ZIP file directly
+setup-step-select = Select the Foundry VTT ZIP file from your device
+setup-step-drop = Or drag and drop the file into the upload area
+setup-tab-url = URL Download
+setup-tab-file = File Upload
+setup-url-label = Paste URL here
+setup-download = 🚀 Download
+setup-drop-here = Drag & drop your Foundry VTT zip file here
+setup-or = or
+setup-choose-file = Choose File
+setup-upload = 📤 Upload
+setup-methods-label = Installation method
+setup-file-label = Foundry VTT ZIP file
+
+## Setup page scripts, variables are filled in by the browser
+
+setup-js-started = Process initiated. The server will exit once the operation is complete.
+setup-js-request-failed = Failed to process request. Please try again.
+setup-js-enter-url = Please enter a URL.
+setup-js-select-file = Please select a file to upload.
+setup-js-error = An error occurred: { $message }
+setup-js-unknown-error = Unknown error
+setup-js-complete = Installation complete! Waiting 30 seconds for server to shut down...
+setup-js-event-error = Error processing server event. Please try again.
+setup-js-connection-lost = Lost connection to server. Please try again.
+setup-js-no-response = Server did not respond in time. Please try again.
+setup-js-checking = Checking for Foundry VTT ({ $attempts }/{ $max })...
+setup-js-ready = Foundry VTT is ready! Redirecting...
+setup-js-license-ready = Foundry VTT license page is ready! Redirecting...
+setup-js-not-ready = Foundry VTT not ready yet. Next check in { $seconds } seconds...
+setup-js-max-attempts = Max redirect attempts reached. Please try manually refreshing the page.
+setup-js-needs-time = Foundry VTT might need more time to start. Try refreshing this page in a moment.
+setup-js-retrying = Connection attempt failed. Retrying in { $seconds } seconds...
+setup-js-unreachable = Unable to connect to Foundry VTT after multiple attempts. Please refresh manually.
+setup-js-couldnt-connect = Couldn't connect to Foundry VTT. Try refreshing this page in a moment.
+setup-js-shutdown-complete = Server shutdown period complete. Starting redirect attempts...
+setup-js-redirecting = Beginning redirect attempts...
+
+## Progress page shown to browsers without JavaScript
+
+progress-title = Installing Foundry VTT
+progress-label = Installation progress
+progress-running = Installation in progress
+progress-waiting = Waiting for the installation to start...
+progress-running-hint = This page reloads every few seconds.
+progress-complete = Installation complete
+progress-complete-hint = Foundry VTT is starting, this page opens it in about 30 seconds. <a href="/">Open Foundry VTT now</a>
+progress-error = Installation failed
+progress-error-hint = <a href="/">Back to the installer</a>
+
+## Doctor
+
+doctor-writable = { $path } writable
+doctor-missing = Directory does not exist
+doctor-mount-volume = Mount a volume at { $path }
+doctor-can-write = Files can be created
+doctor-can-write-windows = Files can be created on a Windows bind mount
+doctor-windows-slow = Foundry is slow on Windows drives shared through Docker Desktop: many small files and no file change events. Use a named volume or a directory inside the WSL2 file system for { $path }
+doctor-read-only = { $path } is mounted read-only, remove `:ro` from the volume
+doctor-check-host = Check the ownership and mode of the host directory mounted at { $path }
+doctor-owner = { $path } is owned by uid { $owner } but the container runs as uid { $uid }, run `chown -R { $uid }:{ $gid } <host path>` on the host
+doctor-owner-rootless = { $path } is owned by { $owner } but the container runs as uid { $uid } in a user namespace{ $host }. Run `podman unshare chown -R { $uid }:{ $gid } <host path>` on the host, or start the container with `--userns=keep-id`
+doctor-owner-unmapped = a host user that is not mapped into the container
+doctor-owner-uid = uid { $uid }
+doctor-host-uid = { " " }(host uid { $uid })
+doctor-selinux-label = SELinux is enforcing and { $path } is labeled { $label }, which containers may not write. Add `:z` (shared) or `:Z` (private) to the volume, e.g. `-v /host/dir:{ $path }:Z`.
+doctor-selinux-private = SELinux is enforcing and { $path } was relabeled privately for another container ({ $label }). Use `:z` instead of `:Z` when several containers share the volume.
+doctor-apparmor = AppArmor profile { $profile } denied access to { $path }. Allow the path in the profile or test with `--security-opt apparmor=unconfined`.
+doctor-kernel-log = Kernel log: { $line }
+doctor-foundry-config = Foundry configuration
+doctor-foundry-config-ok = no known misconfigurations
+doctor-foundry-config-lints = { $count } settings to look at
+doctor-lint-hostname = APPLICATION_HOST is `{ $host }`, Foundry expects a bare hostname and builds broken invitation links from it. Set `APPLICATION_HOST={ $bare }`
+doctor-lint-options-hostname = options.json sets hostname `{ $host }`, which is ignored in favor of APPLICATION_HOST. Remove it or change it to `{ $bare }`
+doctor-lint-route-prefix-url = options.json sets routePrefix `{ $prefix }`, Foundry expects a path such as `foundry` and serves the game under `/foundry/`. Remove the scheme and host
+doctor-lint-route-prefix = options.json sets routePrefix `{ $prefix }`, so Foundry only answers under `/{ $prefix }/`, but JOIN_URL `{ $url }` does not start with that path. Add `/{ $prefix }` to JOIN_URL and make the reverse proxy forward the path unchanged
+doctor-lint-proxy-ssl-port = options.json sets proxyPort 80, but the container runs Foundry with `--proxySSL`, so browsers try HTTPS on port 80. Set proxyPort to 443 or remove it
+doctor-lint-upnp = options.json enables UPnP, which the container turns off with `--noupnp`: port forwarding from inside a container does not reach the router. Forward the port on the router or the reverse proxy and remove `upnp`
+doctor-lint-language = options.json sets the language `{ $language }`, but no installed system or module provides `{ $lang }` and Foundry falls back to English. Install a translation module for `{ $lang }` or set language to `en.core`
+doctor-capability = Optional feature { $name }
+doctor-capability-configured = configured, not used yet
+doctor-capability-active = working
+doctor-capability-failed = configured, but not working
+doctor-failed = { $failed } of { $total } checks failed
+
+## Troubleshoot
+
+troubleshoot-ask-problem = What is going wrong?
+troubleshoot-ask-choice = Choose 1-{ $count }:
+troubleshoot-ask-world = Which world does not load?
+troubleshoot-problem-unreachable = I can't reach the server
+troubleshoot-problem-world = A world won't load
+troubleshoot-problem-permissions = Permission denied errors
+troubleshoot-problem-other = Something else, check everything
+troubleshoot-port = Foundry on port { $port }
+troubleshoot-port-world = answers, world { $world } is active
+troubleshoot-port-setup = answers on the setup screen
+troubleshoot-port-published = Foundry answers inside the container. If the browser still cannot connect, publish the port with `-p { $port }:{ $port }` and check the reverse proxy and firewall
+troubleshoot-port-closed = nothing listens on the port
+troubleshoot-port-not-running = Foundry is not running, `docker logs <container>` shows why it stopped or did not start
+troubleshoot-port-installer = the installer waits for a Foundry release, open it in the browser to install one
+troubleshoot-port-not-foundry = the port is open, but Foundry does not answer: { $error }
+troubleshoot-port-starting = Foundry may still be starting or hang, see `docker logs <container>` and `GET /healthz`
+troubleshoot-join-url = JOIN_URL { $url }
+troubleshoot-join-status = answers with { $status }
+troubleshoot-join-proxy = The reverse proxy answers but cannot reach Foundry, point it at port { $port } of this container
+troubleshoot-join-unreachable = The address does not answer from inside the container. Check the DNS record and the reverse proxy, and try from another device: many routers do not route a host's own public address back to it
+troubleshoot-worlds = Worlds
+troubleshoot-no-world = no world chosen, pass `--world <id>` to check the one that does not load
+troubleshoot-world-manifest = world.json of { $world }
+troubleshoot-world-manifest-ok = { $title }, system { $system }, last opened with Foundry { $core }
+troubleshoot-world-restore = Restore the world from a snapshot with `foundry-watcher backup restore --world { $world } <snapshot>`
+troubleshoot-world-system = System { $system }
+troubleshoot-world-system-installed = installed
+troubleshoot-world-system-missing = not installed
+troubleshoot-world-install-system = Install the system { $system } in the setup screen or with INSTALL_SYSTEMS, Foundry cannot launch the world without it
+troubleshoot-world-version = Foundry version
+troubleshoot-world-version-ok = the world was last opened with { $core }, { $installed } is installed
+troubleshoot-world-version-newer = the world was last opened with { $core }, newer than the installed { $installed }
+troubleshoot-world-version-install = A migrated world does not open in an older generation. Install Foundry { $core }, or restore a snapshot taken before the migration
+troubleshoot-world-version-older = the world was last opened with { $core }, Foundry { $installed } migrates it on launch
+troubleshoot-world-version-migrate = Take a snapshot with `foundry-watcher backup create` before launching it, the migration cannot be undone
+troubleshoot-world-lock = Maintenance lock
+troubleshoot-world-locked = locked since { $since }
+troubleshoot-world-unlock = Players see the maintenance notice, `foundry-watcher world unlock { $world }` lifts it
+troubleshoot-world-databases = World databases
+troubleshoot-world-problems = { $count } problems found
+troubleshoot-world-log = Foundry log
+troubleshoot-world-errors = { $count } errors in the last { $hours } hours
+troubleshoot-world-bisect = If the world still does not load, `foundry-watcher bisect-modules --world { $world }` finds the module that breaks it
+troubleshoot-summary = Paste this into the issue:
+troubleshoot-summary-problem = Problem: { $problem }
+
+## Maintenance
+
+maintenance-locked = This world is locked for maintenance, please check back later.
@@ -0,0 +1,368 @@
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ring::rand::SystemRandom;
+use ring::signature::{
+    ECDSA_P256_SHA256_ASN1_SIGNING, ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, KeyPair,
+};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, PoisonError};
+use tokio::time::{Duration, sleep};
+use tracing::{debug, info};
+
+/// Production directory of Let's Encrypt
+pub const LETS_ENCRYPT: &str = "https://acme-v02.api.letsencrypt.org/directory";
+
+/// Attempts to wait for a challenge or an order, two seconds apart
+const POLL_ATTEMPTS: u32 = 60;
+
+/// Key authorizations of pending http-01 challenges by token
+static CHALLENGES: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// Answer to `/.well-known/acme-challenge/<token>` while the challenge is pending
+pub fn challenge_response(token: &str) -> Option<String> {
+    CHALLENGES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(token)
+        .cloned()
+}
+
+/// Orders certificates from an ACME CA like Let's Encrypt (RFC 8555), proving control of the
+/// domain with http-01 challenges answered on port 80
+pub struct AcmeClient {
+    pub directory: String,
+    pub email: String,
+    /// PKCS#8 P-256 key of the account, see [`generate_key`]
+    pub account_key: Vec<u8>,
+}
+
+/// A new P-256 key in PKCS#8, for the account or a certificate
+pub fn generate_key() -> Result<Vec<u8>> {
+    let pkcs8 =
+        EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &SystemRandom::new())
+            .map_err(|_| anyhow!("Failed to generate a key"))?;
+    Ok(pkcs8.as_ref().to_vec())
+}
+
+impl AcmeClient {
+    /// Order a certificate for `domain`, answering its PEM chain and its PKCS#8 key
+    pub async fn order(&self, domain: &str) -> Result<(String, Vec<u8>)> {
+        let rng = SystemRandom::new();
+        let key =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &self.account_key, &rng)
+                .map_err(|_| anyhow!("The ACME account key is invalid"))?;
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+        let directory: Value = http
+            .get(&self.directory)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .context("The ACME directory is not JSON")?;
+        let endpoint = |name: &str| {
+            directory[name]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| anyhow!("The ACME directory has no {}", name))
+        };
+        let mut session = Session {
+            http,
+            key,
+            rng,
+            new_nonce: endpoint("newNonce")?,
+            nonce: None,
+            kid: None,
+        };
+
+        let (account, _) = session
+            .post(
+                &endpoint("newAccount")?,
+                Some(json!({
+                    "termsOfServiceAgreed": true,
+                    "contact": [format!("mailto:{}", self.email)],
+                })),
+            )
+            .await
+            .context("Failed to register the ACME account")?;
+        session.kid = Some(account.ok_or_else(|| anyhow!("The CA answered no account URL"))?);
+
+        let (order_url, order) = session
+            .post(
+                &endpoint("newOrder")?,
+                Some(json!({ "identifiers": [{ "type": "dns", "value": domain }] })),
+            )
+            .await
+            .context("Failed to order the certificate")?;
+        let order_url = order_url.ok_or_else(|| anyhow!("The CA answered no order URL"))?;
+        for authorization in order["authorizations"].as_array().into_iter().flatten() {
+            let url = authorization.as_str().unwrap_or_default();
+            session.authorize(url).await?;
+        }
+
+        let certificate_key = generate_key()?;
+        let csr = csr(domain, &certificate_key)?;
+        let finalize = order["finalize"]
+            .as_str()
+            .ok_or_else(|| anyhow!("The order has no finalize URL"))?;
+        session
+            .post(
+                finalize,
+                Some(json!({ "csr": URL_SAFE_NO_PAD.encode(csr) })),
+            )
+            .await
+            .context("Failed to finalize the order")?;
+        let order = session.poll(&order_url, "processing").await?;
+        let certificate = order["certificate"]
+            .as_str()
+            .ok_or_else(|| anyhow!("The order is {} without a certificate", order["status"]))?;
+        let chain = session.download(certificate).await?;
+        info!("🔐 Issued a certificate for {}", domain);
+        Ok((chain, certificate_key))
+    }
+}
+
+/// Requests of one order, each signed with the account key and carrying a fresh nonce
+struct Session {
+    http: reqwest::Client,
+    key: EcdsaKeyPair,
+    rng: SystemRandom,
+    new_nonce: String,
+    nonce: Option<String>,
+    /// Account URL, requests before the account exists carry the public key instead
+    kid: Option<String>,
+}
+
+impl Session {
+    /// POST `payload` as a JWS, `None` for a POST-as-GET, answering the `Location` and the body
+    async fn post(&mut self, url: &str, payload: Option<Value>) -> Result<(Option<String>, Value)> {
+        let response = self.send(url, payload).await?;
+        let location = response
+            .headers()
+            .get("location")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let body = response.json().await.unwrap_or(Value::Null);
+        Ok((location, body))
+    }
+
+    async fn send(&mut self, url: &str, payload: Option<Value>) -> Result<reqwest::Response> {
+        // A nonce the CA no longer accepts is retried once with the new one it answered
+        let mut retried = false;
+        loop {
+            let nonce = match self.nonce.take() {
+                Some(nonce) => nonce,
+                None => self.fresh_nonce().await?,
+            };
+            let body = self.sign(url, &nonce, payload.as_ref())?;
+            let response = self
+                .http
+                .post(url)
+                .header("content-type", "application/jose+json")
+                .body(body)
+                .send()
+                .await?;
+            self.nonce = replay_nonce(&response);
+            if response.status().is_success() {
+                return Ok(response);
+            }
+            let problem: Value = response.json().await.unwrap_or(Value::Null);
+            let kind = problem["type"].as_str().unwrap_or_default();
+            if kind.ends_with(":badNonce") && !retried {
+                retried = true;
+                continue;
+            }
+            return Err(anyhow!(
+                "{} {}",
+                kind,
+                problem["detail"].as_str().unwrap_or_default()
+            ));
+        }
+    }
+
+    async fn fresh_nonce(&self) -> Result<String> {
+        let response = self.http.head(&self.new_nonce).send().await?;
+        replay_nonce(&response).ok_or_else(|| anyhow!("The CA answered no nonce"))
+    }
+
+    /// Flattened JWS of `payload` with ES256
+    fn sign(&self, url: &str, nonce: &str, payload: Option<&Value>) -> Result<String> {
+        let mut protected = json!({ "alg": "ES256", "nonce": nonce, "url": url });
+        match &self.kid {
+            Some(kid) => protected["kid"] = json!(kid),
+            None => protected["jwk"] = jwk(&self.key),
+        }
+        let protected = URL_SAFE_NO_PAD.encode(protected.to_string());
+        let payload = payload
+            .map(|payload| URL_SAFE_NO_PAD.encode(payload.to_string()))
+            .unwrap_or_default();
+        let signature = self
+            .key
+            .sign(&self.rng, format!("{}.{}", protected, payload).as_bytes())
+            .map_err(|_| anyhow!("Failed to sign the ACME request"))?;
+        Ok(json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": URL_SAFE_NO_PAD.encode(signature.as_ref()),
+        })
+        .to_string())
+    }
+
+    /// Answer the http-01 challenge of an authorization and wait until the CA checked it
+    async fn authorize(&mut self, url: &str) -> Result<()> {
+        let (_, authorization) = self.post(url, None).await?;
+        if authorization["status"] == "valid" {
+            return Ok(());
+        }
+        let domain = authorization["identifier"]["value"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let challenge = authorization["challenges"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|challenge| challenge["type"] == "http-01")
+            .ok_or_else(|| anyhow!("The CA offers no http-01 challenge for {}", domain))?;
+        let token = challenge["token"]
+            .as_str()
+            .ok_or_else(|| anyhow!("The challenge has no token"))?
+            .to_string();
+        let challenge_url = challenge["url"].as_str().unwrap_or_default().to_string();
+
+        let thumbprint = URL_SAFE_NO_PAD.encode(Sha256::digest(jwk(&self.key).to_string()));
+        let challenges = || CHALLENGES.lock().unwrap_or_else(PoisonError::into_inner);
+        challenges().insert(token.clone(), format!("{}.{}", token, thumbprint));
+        debug!("Answering the http-01 challenge for {}", domain);
+        let checked = async {
+            self.post(&challenge_url, Some(json!({}))).await?;
+            self.poll(url, "pending").await
+        }
+        .await;
+        challenges().remove(&token);
+
+        let authorization = checked?;
+        if authorization["status"] != "valid" {
+            let error = authorization["challenges"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find_map(|challenge| challenge["error"]["detail"].as_str())
+                .unwrap_or("no reason given");
+            return Err(anyhow!(
+                "{} could not be validated, is port 80 reachable from the internet? {}",
+                domain,
+                error
+            ));
+        }
+        Ok(())
+    }
+
+    /// POST-as-GET `url` until its status is no longer `waiting` (or `pending`)
+    async fn poll(&mut self, url: &str, waiting: &str) -> Result<Value> {
+        for _ in 0..POLL_ATTEMPTS {
+            let (_, body) = self.post(url, None).await?;
+            let status = body["status"].as_str().unwrap_or_default();
+            if status != waiting && status != "pending" {
+                return Ok(body);
+            }
+            sleep(Duration::from_secs(2)).await;
+        }
+        Err(anyhow!("The CA did not answer in time"))
+    }
+
+    async fn download(&mut self, url: &str) -> Result<String> {
+        let response = self.send(url, None).await?;
+        Ok(response.text().await?)
+    }
+}
+
+fn replay_nonce(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get("replay-nonce")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Public account key as a JWK, the members in the order its thumbprint needs
+fn jwk(key: &EcdsaKeyPair) -> Value {
+    // Uncompressed point: 0x04, then 32 bytes each of x and y
+    let point = key.public_key().as_ref();
+    json!({
+        "crv": "P-256",
+        "kty": "EC",
+        "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+        "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+    })
+}
+
+/// PKCS#10 request for `domain`, signed with the certificate key
+fn csr(domain: &str, pkcs8: &[u8]) -> Result<Vec<u8>> {
+    const EC_PUBLIC_KEY: &[u8] = &[0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+    const PRIME256V1: &[u8] = &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+    const ECDSA_WITH_SHA256: &[u8] = &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+    const COMMON_NAME: &[u8] = &[0x06, 0x03, 0x55, 0x04, 0x03];
+    const EXTENSION_REQUEST: &[u8] = &[
+        0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x0e,
+    ];
+    const SUBJECT_ALT_NAME: &[u8] = &[0x06, 0x03, 0x55, 0x1d, 0x11];
+
+    let rng = SystemRandom::new();
+    let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8, &rng)
+        .map_err(|_| anyhow!("The certificate key is invalid"))?;
+    let bit_string = |bytes: &[u8]| der(0x03, &[&[0u8][..], bytes].concat());
+
+    let subject = seq(&[der(
+        0x31,
+        &seq(&[COMMON_NAME.to_vec(), der(0x0c, domain.as_bytes())]),
+    )]);
+    let public_key = seq(&[
+        seq(&[EC_PUBLIC_KEY.to_vec(), PRIME256V1.to_vec()]),
+        bit_string(key.public_key().as_ref()),
+    ]);
+    // The CA only looks at the subject alternative names
+    let names = seq(&[der(0x82, domain.as_bytes())]);
+    let extensions = seq(&[seq(&[SUBJECT_ALT_NAME.to_vec(), der(0x04, &names)])]);
+    let attributes = der(
+        0xa0,
+        &seq(&[EXTENSION_REQUEST.to_vec(), der(0x31, &extensions)]),
+    );
+    let info = seq(&[vec![0x02, 0x01, 0x00], subject, public_key, attributes]);
+    let signature = key
+        .sign(&rng, &info)
+        .map_err(|_| anyhow!("Failed to sign the certificate request"))?;
+    Ok(seq(&[
+        info,
+        seq(&[ECDSA_WITH_SHA256.to_vec()]),
+        bit_string(signature.as_ref()),
+    ]))
+}
+
+/// DER element of `tag` around `content`
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut element = vec![tag];
+    match content.len() {
+        length @ 0..0x80 => element.push(length as u8),
+        length => {
+            let bytes: Vec<u8> = length
+                .to_be_bytes()
+                .into_iter()
+                .skip_while(|b| *b == 0)
+                .collect();
+            element.push(0x80 | bytes.len() as u8);
+            element.extend(bytes);
+        }
+    }
+    element.extend_from_slice(content);
+    element
+}
+
+fn seq(parts: &[Vec<u8>]) -> Vec<u8> {
+    der(0x30, &parts.concat())
+}
@@ -0,0 +1,268 @@
+use actix_web::{App, HttpResponse, HttpServer, Responder, web};
+use tracing::info;
+use tracing_actix_web::TracingLogger;
+use utoipa::OpenApi;
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+
+use crate::api::{self, ApiConfig};
+use crate::backup::{self, BackupService};
+use crate::changelog;
+use crate::config::AppConfig;
+use crate::diskspace;
+use crate::health::{self, HealthConfig};
+#[cfg(feature = "full")]
+use crate::hostmigrate;
+use crate::instances::{self, RoutingConfig};
+use crate::join::{self, JoinConfig};
+use crate::logs;
+#[cfg(feature = "full")]
+use crate::metrics;
+use crate::moduleguard;
+use crate::proxy;
+#[cfg(feature = "full")]
+use crate::replicate;
+use crate::restore::RestoreService;
+use crate::tempfiles;
+use crate::updater::Updater;
+use crate::uptime;
+#[cfg(feature = "full")]
+use crate::webhooks::{self, WebhookConfig};
+
+/// Description of the routes below for dashboards and generated clients, built from the
+/// annotations of the handlers. The routes of the `full` profile are added from
+/// `IntegrationDoc`.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "foundry-watcher admin API",
+        description = "Probes, statistics and webhooks of the wrapper, served on ADMIN_API_PORT next to Foundry.",
+        version = "1"
+    ),
+    paths(
+        health::livez,
+        health::healthz,
+        health::readyz,
+        changelog::show,
+        proxy::connections,
+        join::qr_png,
+        uptime::show,
+        instances::show,
+        logs::query,
+        moduleguard::show,
+        diskspace::show,
+        tempfiles::show,
+        backup::list,
+        openapi,
+        api::status,
+        api::restart,
+        api::announce,
+        api::pause,
+        api::unpause,
+        api::backup,
+        api::restore,
+        api::update,
+        api::logs_tail,
+        api::options,
+        api::apply_options,
+        api::world_locks,
+        api::lock_world,
+        api::unlock_world,
+    ),
+    modifiers(&TokenAuth)
+)]
+pub struct ApiDoc;
+
+/// Routes the `minimal` profile leaves out: metrics, webhooks and replication
+#[cfg(feature = "full")]
+#[derive(OpenApi)]
+#[openapi(paths(
+    metrics::show,
+    webhooks::handle_hook,
+    replicate::manifest,
+    replicate::upload_status,
+    replicate::upload_chunk,
+    replicate::commit,
+    replicate::remove,
+    hostmigrate::receive_manifest,
+))]
+struct IntegrationDoc;
+
+impl ApiDoc {
+    /// The document of the routes this build serves
+    pub fn build() -> utoipa::openapi::OpenApi {
+        let doc = <Self as OpenApi>::openapi();
+        #[cfg(feature = "full")]
+        let doc = doc.merge_from(IntegrationDoc::openapi());
+        doc
+    }
+}
+
+/// The `ADMIN_API_TOKEN` the routes below `/api` require
+struct TokenAuth;
+
+impl utoipa::Modify for TokenAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        let mut scheme = Http::new(HttpAuthScheme::Bearer);
+        scheme.description = Some("ADMIN_API_TOKEN".to_string());
+        components.add_security_scheme("token", SecurityScheme::Http(scheme));
+    }
+}
+
+/// Start the admin API on its own port, it keeps running while Foundry owns the main port
+pub async fn start_admin_server(config: &AppConfig, port: u16) -> std::io::Result<()> {
+    let health_config = web::Data::new(HealthConfig {
+        foundry_port: config.server_port,
+    });
+
+    if config.admin_api_token.is_none() {
+        info!("The lifecycle endpoints below /api are disabled, ADMIN_API_TOKEN is not set");
+    }
+    let api_config = web::Data::new(ApiConfig {
+        token: config.admin_api_token.clone(),
+        backup: BackupService::from_config(config),
+        target_dir: config.target_dir.clone(),
+        foundry_port: config.server_port,
+        config_apply: config.config_apply,
+        updater: Updater::from_config(config),
+        #[cfg(feature = "full")]
+        scan_excludes: config.scan_excludes.clone(),
+        restore: RestoreService::from_config(config),
+    });
+
+    let join_config = web::Data::new(JoinConfig {
+        url: config.join_url.clone(),
+    });
+
+    let routing_config = web::Data::new(RoutingConfig {
+        foundry_port: config.server_port,
+        instances: config.instances.clone(),
+    });
+
+    let integrations = integration_routes(config);
+
+    let server = HttpServer::new(move || {
+        App::new()
+            .wrap(TracingLogger::default())
+            .app_data(health_config.clone())
+            .app_data(join_config.clone())
+            .app_data(api_config.clone())
+            .app_data(routing_config.clone())
+            .route("/livez", web::get().to(health::livez))
+            .route("/healthz", web::get().to(health::healthz))
+            .route("/readyz", web::get().to(health::readyz))
+            .route("/changelog", web::get().to(changelog::show))
+            .route("/connections", web::get().to(proxy::dashboard))
+            .route("/join.png", web::get().to(join::qr_png))
+            .route("/uptime", web::get().to(uptime::show))
+            .route("/disabled-modules", web::get().to(moduleguard::show))
+            .configure(integrations.clone())
+            .route("/openapi.json", web::get().to(openapi))
+            .service(
+                web::scope("/api")
+                    .route("/status", web::get().to(api::status))
+                    .route("/tempfiles", web::get().to(tempfiles::show))
+                    .route("/disk", web::get().to(diskspace::show))
+                    .route("/instances", web::get().to(instances::show))
+                    .route("/backups", web::get().to(backup::list))
+                    .route("/connections", web::get().to(proxy::connections))
+                    .route("/restart", web::post().to(api::restart))
+                    .route("/announce", web::post().to(api::announce))
+                    .route("/pause", web::post().to(api::pause))
+                    .route("/unpause", web::post().to(api::unpause))
+                    .route("/backup", web::post().to(api::backup))
+                    .route("/restore", web::post().to(api::restore))
+                    .route("/update", web::post().to(api::update))
+                    .route("/logs", web::get().to(logs::query))
+                    .route("/logs/tail", web::get().to(api::logs_tail))
+                    .route("/options", web::get().to(api::options))
+                    .route("/options/apply", web::post().to(api::apply_options))
+                    .route("/worlds/locks", web::get().to(api::world_locks))
+                    .route("/worlds/{id}/lock", web::post().to(api::lock_world))
+                    .route("/worlds/{id}/lock", web::delete().to(api::unlock_world))
+                    .configure(replication_routes),
+            )
+    })
+    // A single worker is plenty for occasional admin requests
+    .workers(1)
+    .disable_signals()
+    .bind((config.server_host.clone(), port))?
+    .run();
+
+    info!("Admin API listening on {}:{}", config.server_host, port);
+    tokio::spawn(server);
+    Ok(())
+}
+
+/// This document
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    operation_id = "openapi",
+    responses((status = 200, description = "OpenAPI document", body = Object))
+)]
+async fn openapi() -> impl Responder {
+    HttpResponse::Ok().json(ApiDoc::build())
+}
+
+/// Metrics and webhooks of the `full` profile, the `minimal` one serves neither
+#[cfg(feature = "full")]
+fn integration_routes(
+    config: &AppConfig,
+) -> impl Fn(&mut web::ServiceConfig) + Clone + Send + use<> {
+    let webhook_config = web::Data::new(WebhookConfig::from_config(config));
+    move |cfg| {
+        cfg.app_data(webhook_config.clone())
+            .route("/metrics", web::get().to(metrics::show))
+            .route("/hooks/{name}", web::post().to(webhooks::handle_hook));
+    }
+}
+
+#[cfg(not(feature = "full"))]
+fn integration_routes(_: &AppConfig) -> impl Fn(&mut web::ServiceConfig) + Clone + Send + use<> {
+    |_| {}
+}
+
+/// Replication and host migration below `/api`, left out of the `minimal` profile
+#[cfg(feature = "full")]
+fn replication_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/sync/manifest", web::get().to(replicate::manifest))
+        .route(
+            "/sync/uploads/{sha256}",
+            web::get().to(replicate::upload_status),
+        )
+        .route(
+            "/sync/uploads/{sha256}",
+            web::put().to(replicate::upload_chunk),
+        )
+        .route("/sync/files", web::post().to(replicate::commit))
+        .route("/sync/files", web::delete().to(replicate::remove))
+        .route(
+            "/migrate/manifest",
+            web::post().to(hostmigrate::receive_manifest),
+        );
+}
+
+#[cfg(not(feature = "full"))]
+fn replication_routes(_: &mut web::ServiceConfig) {}
+
+#[cfg(all(test, feature = "full"))]
+mod tests {
+    use super::*;
+
+    /// The client crate is generated from `client/openapi.json`, so it has to follow the
+    /// handlers. `UPDATE_OPENAPI=1 cargo test` writes the current document there.
+    #[test]
+    fn client_document_matches_the_handlers() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../client/openapi.json");
+        let document = ApiDoc::build().to_pretty_json().unwrap() + "\n";
+        if std::env::var_os("UPDATE_OPENAPI").is_some() {
+            std::fs::write(path, &document).unwrap();
+        }
+        let committed = std::fs::read_to_string(path).unwrap_or_default();
+        assert!(
+            committed == document,
+            "client/openapi.json is outdated, run `UPDATE_OPENAPI=1 cargo test`"
+        );
+    }
+}
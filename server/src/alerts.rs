@@ -0,0 +1,234 @@
+use anyhow::{Result, anyhow};
+use nix::sys::statvfs::statvfs;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use tokio::time::{Duration, Instant, interval};
+use tracing::{debug, info, warn};
+
+use crate::notify::{self, Notification, Severity};
+use crate::status;
+use crate::utils::paths;
+
+/// Window restarts are counted in for `restarts>N`
+const RESTART_WINDOW: Duration = Duration::from_secs(3600);
+/// New log output read per file and check, a runaway log must not fill the memory
+const MAX_READ: u64 = 4 * 1024 * 1024;
+
+/// What makes an alert rule fire
+#[derive(Debug, Clone)]
+pub enum Condition {
+    /// A new line in Foundry's log files contains the text, compared case-insensitively
+    LogContains(String),
+    /// Foundry restarted more often than this within the last hour
+    Restarts(u32),
+    /// The file system of `DATA_DIR` is fuller than this percentage
+    DiskPercent(f64),
+}
+
+impl Condition {
+    /// Parse `log:<text>`, `restarts>N` or `disk>N`
+    pub fn parse(spec: &str) -> Result<Self> {
+        if let Some(text) = spec.strip_prefix("log:") {
+            if text.trim().is_empty() {
+                return Err(anyhow!("log: needs the text to look for"));
+            }
+            return Ok(Self::LogContains(text.trim().to_lowercase()));
+        }
+        let (metric, threshold) = spec
+            .split_once('>')
+            .ok_or_else(|| anyhow!("expected log:<text>, restarts>N or disk>N"))?;
+        let threshold = threshold.trim();
+        match metric.trim() {
+            "restarts" => Ok(Self::Restarts(threshold.parse().map_err(|_| {
+                anyhow!("restarts needs a whole number, got {}", threshold)
+            })?)),
+            "disk" => Ok(Self::DiskPercent(
+                threshold
+                    .trim_end_matches('%')
+                    .parse()
+                    .map_err(|_| anyhow!("disk needs a percentage, got {}", threshold))?,
+            )),
+            other => Err(anyhow!("unknown metric {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AlertRule {
+    pub name: String,
+    pub condition: Condition,
+}
+
+/// Built-in alerting for tables without a monitoring stack: rules over Foundry's logs and a
+/// few metrics notify when they start firing and again when they are resolved
+#[derive(Clone)]
+pub struct AlertEngine {
+    pub rules: Vec<AlertRule>,
+    /// Least time between two notifications of a rule, and how long a log rule stays firing
+    pub cooldown: Duration,
+    pub interval: Duration,
+}
+
+#[derive(Default)]
+struct RuleState {
+    firing: bool,
+    last_notified: Option<Instant>,
+    last_match: Option<Instant>,
+}
+
+impl AlertEngine {
+    pub async fn run(self) {
+        let logs_dir = PathBuf::from(&*paths::DATA_DIR).join("Logs");
+        // Only output written from now on is looked at
+        let mut tail = LogTail::default();
+        tail.read_new(&logs_dir);
+
+        let mut states: BTreeMap<String, RuleState> = BTreeMap::new();
+        let mut restarts: VecDeque<(Instant, u32)> = VecDeque::new();
+        let mut ticker = interval(self.interval);
+        loop {
+            ticker.tick().await;
+            let now = Instant::now();
+            let lines = tail.read_new(&logs_dir);
+            restarts.push_back((now, status::current().restarts));
+            while restarts
+                .front()
+                .is_some_and(|(at, _)| now.duration_since(*at) > RESTART_WINDOW)
+            {
+                restarts.pop_front();
+            }
+            let recent_restarts = match (restarts.front(), restarts.back()) {
+                (Some((_, first)), Some((_, last))) => last.saturating_sub(*first),
+                _ => 0,
+            };
+
+            for rule in &self.rules {
+                let state = states.entry(rule.name.clone()).or_default();
+                let (matched, detail) = match &rule.condition {
+                    Condition::LogContains(text) => {
+                        match lines.iter().find(|line| line.to_lowercase().contains(text)) {
+                            Some(line) => (true, line.trim().to_string()),
+                            None => (false, String::new()),
+                        }
+                    }
+                    Condition::Restarts(max) => (
+                        recent_restarts > *max,
+                        format!("{} restarts within the last hour", recent_restarts),
+                    ),
+                    Condition::DiskPercent(max) => match disk_used_percent() {
+                        Some(used) => (used > *max, format!("{:.1}% of the disk used", used)),
+                        None => (false, String::new()),
+                    },
+                };
+                if matched {
+                    state.last_match = Some(now);
+                }
+                // A log line is an event, the rule stays firing until the cooldown passed quietly
+                let active = match rule.condition {
+                    Condition::LogContains(_) => state
+                        .last_match
+                        .is_some_and(|at| now.duration_since(at) < self.cooldown),
+                    _ => matched,
+                };
+                let cooled_down = state
+                    .last_notified
+                    .is_none_or(|at| now.duration_since(at) >= self.cooldown);
+
+                if matched && cooled_down {
+                    notify::send(Notification {
+                        severity: Severity::Critical,
+                        title: format!("Alert {}", rule.name),
+                        message: detail,
+                    });
+                    state.last_notified = Some(now);
+                    state.firing = true;
+                } else if state.firing && !active {
+                    notify::send(Notification {
+                        severity: Severity::Warning,
+                        title: format!("Alert {} resolved", rule.name),
+                        message: "The condition no longer holds.".to_string(),
+                    });
+                    state.firing = false;
+                } else if matched {
+                    debug!("Alert {} still firing, in cooldown", rule.name);
+                }
+            }
+        }
+    }
+}
+
+/// Lines appended to the log files since the last read, rotation starts a file over
+#[derive(Default)]
+struct LogTail {
+    offsets: BTreeMap<PathBuf, u64>,
+    /// The first read only records where the files end
+    initialized: bool,
+}
+
+impl LogTail {
+    fn read_new(&mut self, dir: &Path) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            // Files in a directory created later are all new
+            self.initialized = true;
+            return Vec::new();
+        };
+        let mut lines = Vec::new();
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().is_none_or(|extension| extension != "log") {
+                continue;
+            }
+            let Ok(len) = path.metadata().map(|m| m.len()) else {
+                continue;
+            };
+            let known = self.offsets.contains_key(&path);
+            let offset = self.offsets.get(&path).copied().unwrap_or(0);
+            let start = if offset > len { 0 } else { offset };
+            // Files seen for the first time are only read when they appeared while watching
+            if known || self.initialized {
+                match read_from(&path, start.max(len.saturating_sub(MAX_READ)), len) {
+                    Ok(text) => lines.extend(text.lines().map(str::to_string)),
+                    Err(e) => warn!("Failed to read {}: {}", path.display(), e),
+                }
+            }
+            self.offsets.insert(path, len);
+        }
+        self.initialized = true;
+        lines
+    }
+}
+
+fn read_from(path: &Path, start: u64, end: u64) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut buffer = Vec::new();
+    file.take(end - start).read_to_end(&mut buffer)?;
+    Ok(String::from_utf8_lossy(&buffer).to_string())
+}
+
+/// Used share of the file system holding `DATA_DIR`, like `df` reports it
+fn disk_used_percent() -> Option<f64> {
+    let stat = statvfs(paths::DATA_DIR.as_str()).ok()?;
+    let used = stat.blocks().saturating_sub(stat.blocks_free());
+    let usable = used + stat.blocks_available();
+    (usable > 0).then(|| used as f64 * 100.0 / usable as f64)
+}
+
+/// Parse the configured `name=condition` rules, skipping invalid ones
+pub fn parse_rules(rules: &BTreeMap<String, String>) -> Vec<AlertRule> {
+    rules
+        .iter()
+        .filter_map(|(name, spec)| match Condition::parse(spec) {
+            Ok(condition) => Some(AlertRule {
+                name: name.clone(),
+                condition,
+            }),
+            Err(e) => {
+                warn!("⚠️ Ignoring alert rule {}: {}", name, e);
+                None
+            }
+        })
+        .inspect(|rule| info!("Alert rule {}: {:?}", rule.name, rule.condition))
+        .collect()
+}
@@ -0,0 +1,798 @@
+use actix_web::{HttpRequest, HttpResponse, Responder, http::header, web};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{error, info, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::backup::{BackupRecord, BackupService, BackupTrigger};
+use crate::capabilities::{self, Capability};
+use crate::gamesocket;
+use crate::inhibit;
+use crate::launch;
+use crate::logs::{self, LogEntry};
+use crate::notify::{self, Notification, Severity};
+use crate::options::{self, ApplyMode, OptionsDiff};
+use crate::release::{ReleaseDownload, ReleaseSource};
+use crate::restore::{self, RestoreOptions, RestoreService};
+use crate::state::WorldLock;
+use crate::status;
+use crate::tasks;
+use crate::updater::{self, Updater};
+use crate::utils::{installed_foundry_version, paths};
+use crate::worlds::{self, WorldService};
+
+const DEFAULT_TAIL: usize = 100;
+const MAX_TAIL: usize = 1000;
+
+/// Set while `POST /api/update` installs a release, a second update is refused meanwhile
+static UPDATING: AtomicBool = AtomicBool::new(false);
+
+/// What the lifecycle endpoints below `/api` need, requests carry `Authorization: Bearer <token>`
+pub struct ApiConfig {
+    pub token: Option<String>,
+    pub backup: BackupService,
+    pub target_dir: String,
+    pub foundry_port: u16,
+    pub config_apply: ApplyMode,
+    /// Installs releases once they are kept side by side, and holds the account updates to a
+    /// version are downloaded with
+    pub updater: Updater,
+    /// Skipped when indexing the data directory for `foundry-watcher sync`
+    #[cfg(feature = "full")]
+    pub scan_excludes: Vec<String>,
+    pub restore: RestoreService,
+}
+
+/// What happened, the body of most answers below `/api`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Message {
+    pub message: String,
+}
+
+impl Message {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+/// Response for requests without the `ADMIN_API_TOKEN`, `None` lets the request through
+pub fn reject(req: &HttpRequest, config: &ApiConfig) -> Option<HttpResponse> {
+    let Some(token) = &config.token else {
+        return Some(HttpResponse::Forbidden().json(Message::new(
+            "The lifecycle API is disabled, set ADMIN_API_TOKEN",
+        )));
+    };
+    let given = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    // Comparing digests takes the same time wherever the tokens differ
+    if Sha256::digest(given.trim()) != Sha256::digest(token) {
+        warn!("Rejected {} {}: invalid token", req.method(), req.path());
+        return Some(
+            HttpResponse::Unauthorized()
+                .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+                .json(Message::new("Invalid token")),
+        );
+    }
+    None
+}
+
+/// The Foundry process, the active world and the wrapper's subsystems
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = Status)]
+pub struct ApiStatus {
+    pub installing: bool,
+    pub running: bool,
+    pub pid: Option<u32>,
+    /// Installed Foundry version
+    pub version: Option<String>,
+    pub restarts: u32,
+    /// Since Foundry was last started
+    pub uptime_seconds: Option<u64>,
+    /// Active world
+    pub world: Option<String>,
+    /// Connected players, missing when Foundry does not answer
+    pub players: Option<u32>,
+    /// Names of the connected users, read from Foundry's socket
+    pub player_names: Option<Vec<String>>,
+    /// A release is being installed
+    pub updating: bool,
+    /// Whether each subsystem is healthy
+    pub subsystems: BTreeMap<String, bool>,
+    pub capabilities: BTreeMap<String, Capability>,
+}
+
+/// The Foundry process, the active world and the wrapper's subsystems
+#[utoipa::path(
+    get,
+    path = "/api/status",
+    operation_id = "apiStatus",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "Current status", body = ApiStatus),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+    )
+)]
+pub async fn status(req: HttpRequest, config: web::Data<ApiConfig>) -> impl Responder {
+    if let Some(rejected) = reject(&req, &config) {
+        return rejected;
+    }
+    let foundry = status::current();
+    let server = match foundry.running {
+        true => status::fetch_server_status(config.foundry_port).await.ok(),
+        false => None,
+    };
+    let subsystems = tasks::subsystems()
+        .into_iter()
+        .map(|(name, state)| (name.to_string(), state.healthy))
+        .collect();
+    // Names come from Foundry's socket, the status endpoint only counts the players
+    let player_names = match (&server, gamesocket::client()) {
+        (Some(server), Some(client)) if server.users > 0 => client.connected_users().await.ok(),
+        _ => None,
+    };
+    HttpResponse::Ok().json(ApiStatus {
+        installing: foundry.installing,
+        running: foundry.running,
+        pid: foundry.pid,
+        version: foundry.version.or_else(installed_foundry_version),
+        restarts: foundry.restarts,
+        uptime_seconds: foundry
+            .started_at
+            .filter(|_| foundry.running)
+            .map(|started_at| started_at.elapsed().as_secs()),
+        world: server.as_ref().and_then(|s| s.world.clone()),
+        players: server.as_ref().map(|s| s.users),
+        player_names,
+        updating: UPDATING.load(Ordering::SeqCst),
+        subsystems,
+        capabilities: capabilities::all(),
+    })
+}
+
+/// Restart the Foundry process, connected players are warned in the chat for
+/// `RESTART_WARNING` seconds with `GAME_USER` and then disconnected
+#[utoipa::path(
+    post,
+    path = "/api/restart",
+    operation_id = "restart",
+    security(("token" = [])),
+    responses(
+        (status = 202, description = "Restarting", body = Message),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 409, description = "Foundry is not running", body = Message),
+    )
+)]
+pub async fn restart(req: HttpRequest, config: web::Data<ApiConfig>) -> impl Responder {
+    if let Some(rejected) = reject(&req, &config) {
+        return rejected;
+    }
+    if !status::current().running {
+        return HttpResponse::Conflict().json(Message::new("Foundry is not running"));
+    }
+    info!("🔄 Restart requested through the admin API");
+    tokio::spawn(launch::restart_after_warning());
+    HttpResponse::Accepted().json(Message::new("Restarting Foundry"))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AnnounceRequest {
+    /// Plain text, shown as written
+    message: String,
+}
+
+/// Post a message to the chat of the active world as `GAME_USER`
+#[utoipa::path(
+    post,
+    path = "/api/announce",
+    operation_id = "announce",
+    security(("token" = [])),
+    request_body = AnnounceRequest,
+    responses(
+        (status = 200, description = "Announced", body = Message),
+        (status = 400, description = "GAME_USER is not set or the message is empty", body = Message),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 409, description = "Foundry's socket refused the message", body = Message),
+    )
+)]
+pub async fn announce(
+    req: HttpRequest,
+    config: web::Data<ApiConfig>,
+    body: web::Json<AnnounceRequest>,
+) -> impl Responder {
+    if let Some(rejected) = reject(&req, &config) {
+        return rejected;
+    }
+    let Some(client) = gamesocket::client().filter(|client| client.user.is_some()) else {
+        return HttpResponse::BadRequest().json(Message::new(
+            "Announcements need GAME_USER, a Gamemaster of the world",
+        ));
+    };
+    let message = body.into_inner().message;
+    if message.trim().is_empty() {
+        return HttpResponse::BadRequest().json(Message::new("The message is empty"));
+    }
+    match client.announce(message.trim()).await {
+        Ok(()) => {
+            info!("📣 Announced through the admin API: {}", message.trim());
+            HttpResponse::Ok().json(Message::new("Announced"))
+        }
+        Err(e) => HttpResponse::Conflict().json(Message::new(format!("{:#}", e))),
+    }
+}
+
+/// Pause the game of the active world for all players as `GAME_USER`
+#[utoipa::path(
+    post,
+    path = "/api/pause",
+    operation_id = "pause",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "Paused", body = Message),
+        (status = 400, description = "GAME_USER is not set", body = Message),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 409, description = "Foundry's socket refused the pause", body = Message),
+    )
+)]
+pub async fn pause(req: HttpRequest, config: web::Data<ApiConfig>) -> impl Responder {
+    set_paused(&req, &config, true).await
+}
+
+/// Unpause the game of the active world as `GAME_USER`
+#[utoipa::path(
+    post,
+    path = "/api/unpause",
+    operation_id = "unpause",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "Unpaused", body = Message),
+        (status = 400, description = "GAME_USER is not set", body = Message),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 409, description = "Foundry's socket refused the unpause", body = Message),
+    )
+)]
+pub async fn unpause(req: HttpRequest, config: web::Data<ApiConfig>) -> impl Responder {
+    set_paused(&req, &config, false).await
+}
+
+async fn set_paused(req: &HttpRequest, config: &ApiConfig, paused: bool) -> HttpResponse {
+    if let Some(rejected) = reject(req, config) {
+        return rejected;
+    }
+    let Some(client) = gamesocket::client().filter(|client| client.user.is_some()) else {
+        return HttpResponse::BadRequest().json(Message::new(
+            "Pausing needs GAME_USER, a Gamemaster of the world",
+        ));
+    };
+    match client.set_paused(paused).await {
+        Ok(()) => {
+            let message = if paused { "Paused" } else { "Unpaused" };
+            info!("⏯️ {} the game through the admin API", message);
+            HttpResponse::Ok().json(Message::new(message))
+        }
+        Err(e) => HttpResponse::Conflict().json(Message::new(format!("{:#}", e))),
+    }
+}
+
+/// Take a snapshot and answer its catalog entry once it is written
+#[utoipa::path(
+    post,
+    path = "/api/backup",
+    operation_id = "backup",
+    security(("token" = [])),
+    responses(
+        (status = 201, description = "The new snapshot", body = BackupRecord),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 500, description = "The snapshot failed", body = Message),
+    )
+)]
+pub async fn backup(req: HttpRequest, config: web::Data<ApiConfig>) -> impl Responder {
+    if let Some(rejected) = reject(&req, &config) {
+        return rejected;
+    }
+    let service = config.backup.clone();
+    match tokio::task::spawn_blocking(move || service.create(BackupTrigger::Api)).await {
+        Ok(Ok(summary)) => HttpResponse::Created().json(summary.record),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(Message::new(format!("{:#}", e))),
+        Err(e) => HttpResponse::InternalServerError().json(Message::new(e.to_string())),
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RestoreRequest {
+    /// File name of the snapshot, as `GET /api/backups` lists it
+    file: Option<String>,
+    /// Newest snapshot taken at or before this RFC 3339 time, or this long ago like `2h`,
+    /// instead of a file
+    at: Option<String>,
+    /// Only restore this world
+    world: Option<String>,
+    /// Restore a snapshot of a newer Foundry anyway
+    #[serde(default)]
+    force: bool,
+    #[serde(default)]
+    install_core: bool,
+    #[serde(default)]
+    no_backup: bool,
+    #[serde(default)]
+    reproduce: bool,
+    /// Restore even while players are connected, they are warned like before a restart
+    #[serde(default)]
+    disconnect: bool,
+}
+
+/// What a restore put in place
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RestoreResult {
+    pub file: String,
+    pub world: Option<String>,
+    /// Snapshot of the data before the restore
+    pub previous: Option<String>,
+    pub installed_core: Option<String>,
+    pub environment: Vec<String>,
+}
+
+/// Stop Foundry, restore a snapshot or one world of it and start Foundry again, answered once
+/// it is done
+///
+/// Foundry keeps running when an inactive world is restored.
+#[utoipa::path(
+    post,
+    path = "/api/restore",
+    operation_id = "restore",
+    security(("token" = [])),
+    request_body = RestoreRequest,
+    responses(
+        (status = 200, description = "What was restored", body = RestoreResult),
+        (status = 400, description = "Neither a file nor a time, or no snapshot at that time", body = Message),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 409, description = "Players are connected or the restore failed", body = Message),
+    )
+)]
+pub async fn restore(
+    req: HttpRequest,
+    config: web::Data<ApiConfig>,
+    body: web::Json<RestoreRequest>,
+) -> impl Responder {
+    if let Some(rejected) = reject(&req, &config) {
+        return rejected;
+    }
+    let body = body.into_inner();
+    let file = match (body.file, body.at) {
+        (Some(file), None) => file,
+        (None, Some(at)) => {
+            let record = logs::parse_since(&at)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Invalid at {}, send an RFC 3339 time or e.g. 2h", at)
+                })
+                .and_then(restore::snapshot_at);
+            match record {
+                Ok(record) => record.file,
+                Err(e) => {
+                    return HttpResponse::BadRequest().json(Message::new(format!("{:#}", e)));
+                }
+            }
+        }
+        _ => {
+            return HttpResponse::BadRequest()
+                .json(Message::new("Send either a file or a time to restore to"));
+        }
+    };
+
+    // Restoring an inactive world leaves the active one and its players alone
+    let server = status::fetch_server_status(config.foundry_port).await.ok();
+    let stop = status::current().running
+        && (body.world.is_none()
+            || server
+                .as_ref()
+                .is_some_and(|s| s.active && s.world == body.world));
+    if stop
+        && !body.disconnect
+        && let Some(server) = &server
+        && server.users > 0
+    {
+        return HttpResponse::Conflict().json(Message::new(format!(
+            "{} players are connected, send disconnect to restore anyway",
+            server.users
+        )));
+    }
+
+    info!("♻️ Restore of {} requested through the admin API", file);
+    let hold = match stop {
+        true => {
+            if let Some(client) = gamesocket::client() {
+                client.warn_restart().await;
+            }
+            Some(launch::pause().await)
+        }
+        false => None,
+    };
+    let options = RestoreOptions {
+        world: body.world,
+        force: body.force,
+        install_core: body.install_core,
+        no_backup: body.no_backup,
+        reproduce: body.reproduce,
+    };
+    let restored = config.restore.restore(&file, options).await;
+    // Foundry starts again with the restored data, or the old one when the restore failed
+    drop(hold);
+    match restored {
+        Ok(summary) => HttpResponse::Ok().json(RestoreResult {
+            file: summary.record.file,
+            world: summary.world,
+            previous: summary.previous.and_then(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+            }),
+            installed_core: summary.installed_core,
+            environment: summary.environment,
+        }),
+        Err(e) => {
+            error!("❌ Restore of {} failed: {:#}", file, e);
+            HttpResponse::Conflict().json(Message::new(format!("{:#}", e)))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateRequest {
+    /// Release downloaded with the `FOUNDRY_USERNAME` account, e.g. `13.345`
+    version: Option<String>,
+    /// Timed download link from the purchased licenses page instead
+    url: Option<String>,
+    /// Update even while players are connected
+    #[serde(default)]
+    force: bool,
+}
+
+/// Install another Foundry release in the background and restart into it
+#[utoipa::path(
+    post,
+    path = "/api/update",
+    operation_id = "update",
+    security(("token" = [])),
+    request_body = UpdateRequest,
+    responses(
+        (status = 200, description = "The release is installed already", body = Message),
+        (status = 202, description = "Installing", body = Message),
+        (status = 400, description = "Neither a version nor a url, or no account to download with", body = Message),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 409, description = "Players are connected or an update is running", body = Message),
+    )
+)]
+pub async fn update(
+    req: HttpRequest,
+    config: web::Data<ApiConfig>,
+    body: web::Json<UpdateRequest>,
+) -> impl Responder {
+    if let Some(rejected) = reject(&req, &config) {
+        return rejected;
+    }
+    let body = body.into_inner();
+    let source = match (body.version, body.url) {
+        (Some(version), None) => {
+            if installed_foundry_version().as_deref() == Some(version.trim()) {
+                return HttpResponse::Ok().json(Message::new(format!(
+                    "Foundry {} is installed already",
+                    version.trim()
+                )));
+            }
+            let Some((username, password)) = config.updater.account.clone() else {
+                return HttpResponse::BadRequest().json(Message::new(
+                    "Updating to a version needs FOUNDRY_USERNAME and FOUNDRY_PASSWORD, \
+                                send a download url instead",
+                ));
+            };
+            ReleaseSource::Account {
+                username,
+                password,
+                version: version.trim().to_string(),
+            }
+        }
+        (None, Some(url)) => ReleaseSource::Url(url),
+        _ => {
+            return HttpResponse::BadRequest().json(Message::new("Send either a version or a url"));
+        }
+    };
+
+    if !body.force
+        && let Ok(server) = status::fetch_server_status(config.foundry_port).await
+        && server.users > 0
+    {
+        return HttpResponse::Conflict().json(Message::new(format!(
+            "{} players are connected, send force to update anyway",
+            server.users
+        )));
+    }
+    if UPDATING.swap(true, Ordering::SeqCst) {
+        return HttpResponse::Conflict().json(Message::new("An update is running already"));
+    }
+
+    let download = ReleaseDownload {
+        source,
+        // A pinned checksum belongs to FOUNDRY_VERSION, not to this release
+        sha256: None,
+        metadata: config.updater.metadata.clone(),
+    };
+    let target_dir = config.target_dir.clone();
+    let updater = config.updater.clone();
+    info!("⬆️ Update requested through the admin API");
+    // The download is not Send, it runs on the worker of the admin API
+    actix_web::rt::spawn(async move {
+        let installed = if updater::side_by_side() {
+            updater.install(download.source).await.map(|_| ())
+        } else {
+            match inhibit::acquire("Foundry update") {
+                Ok(_inhibitor) => download.install(&target_dir).await,
+                Err(e) => Err(e),
+            }
+        };
+        match installed {
+            Ok(()) => launch::restart_after_warning().await,
+            Err(e) => {
+                error!("❌ Update failed: {:#}", e);
+                notify::send(Notification {
+                    severity: Severity::Critical,
+                    title: "Update failed".to_string(),
+                    message: format!("{:#}", e),
+                });
+            }
+        }
+        UPDATING.store(false, Ordering::SeqCst);
+    });
+    HttpResponse::Accepted().json(Message::new(
+        "Installing the release, Foundry restarts once it is in place",
+    ))
+}
+
+/// The last and the proposed changes to `options.json`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OptionsReport {
+    pub mode: ApplyMode,
+    pub last: Option<OptionsDiff>,
+    pub proposed: Option<OptionsDiff>,
+}
+
+/// What the last start changed in `Config/options.json`, and the proposal waiting for
+/// confirmation with `CONFIG_APPLY=manual`
+#[utoipa::path(
+    get,
+    path = "/api/options",
+    operation_id = "options",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The last and the proposed changes", body = OptionsReport),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 500, description = "The proposal could not be read", body = Message),
+    )
+)]
+pub async fn options(req: HttpRequest, config: web::Data<ApiConfig>) -> impl Responder {
+    if let Some(rejected) = reject(&req, &config) {
+        return rejected;
+    }
+    match web::block(options::proposal).await {
+        Ok(Ok(proposed)) => HttpResponse::Ok().json(OptionsReport {
+            mode: config.config_apply,
+            last: options::last_diff(),
+            proposed,
+        }),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(Message::new(format!("{:#}", e))),
+        Err(e) => HttpResponse::InternalServerError().json(Message::new(e.to_string())),
+    }
+}
+
+/// Move the proposed `options.json` into place, Foundry reads it on its next start
+#[utoipa::path(
+    post,
+    path = "/api/options/apply",
+    operation_id = "applyOptions",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The applied changes", body = OptionsDiff),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 409, description = "No changes are proposed", body = Message),
+        (status = 500, description = "The proposal could not be applied", body = Message),
+    )
+)]
+pub async fn apply_options(req: HttpRequest, config: web::Data<ApiConfig>) -> impl Responder {
+    if let Some(rejected) = reject(&req, &config) {
+        return rejected;
+    }
+    match web::block(options::apply_proposal).await {
+        Ok(Ok(Some(diff))) => {
+            info!("⚙️ Proposed options confirmed through the admin API");
+            HttpResponse::Ok().json(diff)
+        }
+        Ok(Ok(None)) => {
+            HttpResponse::Conflict().json(Message::new("No changes to options.json are proposed"))
+        }
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(Message::new(format!("{:#}", e))),
+        Err(e) => HttpResponse::InternalServerError().json(Message::new(e.to_string())),
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TailQuery {
+    /// Number of entries
+    #[param(minimum = 1, maximum = 1000, default = 100)]
+    lines: Option<usize>,
+}
+
+/// The newest entries of Foundry's log files
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LogTail {
+    pub entries: Vec<LogEntry>,
+}
+
+/// The newest entries of Foundry's log files, oldest first
+#[utoipa::path(
+    get,
+    path = "/api/logs/tail",
+    operation_id = "tailLogs",
+    security(("token" = [])),
+    params(TailQuery),
+    responses(
+        (status = 200, description = "The entries", body = LogTail),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 500, description = "The log files could not be read", body = Message),
+    )
+)]
+pub async fn logs_tail(
+    req: HttpRequest,
+    config: web::Data<ApiConfig>,
+    query: web::Query<TailQuery>,
+) -> impl Responder {
+    if let Some(rejected) = reject(&req, &config) {
+        return rejected;
+    }
+    let lines = query.lines.unwrap_or(DEFAULT_TAIL).clamp(1, MAX_TAIL);
+    match web::block(move || logs::tail(lines)).await {
+        Ok(Ok(entries)) => HttpResponse::Ok().json(LogTail { entries }),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(Message::new(format!("{:#}", e))),
+        Err(e) => HttpResponse::InternalServerError().json(Message::new(e.to_string())),
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct LockRequest {
+    /// Notice shown on the join screen, a translated maintenance notice when left out
+    message: Option<String>,
+}
+
+/// Lock a world for maintenance, players joining through the proxy are refused with the notice
+#[utoipa::path(
+    post,
+    path = "/api/worlds/{id}/lock",
+    operation_id = "lockWorld",
+    security(("token" = [])),
+    params(("id" = String, Path, description = "Id of the world")),
+    request_body = LockRequest,
+    responses(
+        (status = 200, description = "Locked", body = Message),
+        (status = 400, description = "Not a valid world id", body = Message),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 404, description = "The world does not exist", body = Message),
+    )
+)]
+pub async fn lock_world(
+    req: HttpRequest,
+    path: web::Path<String>,
+    config: web::Data<ApiConfig>,
+    body: web::Json<LockRequest>,
+) -> impl Responder {
+    if let Some(rejected) = reject(&req, &config) {
+        return rejected;
+    }
+    let world = path.into_inner();
+    if let Err(e) = worlds::validate_id(&world) {
+        return HttpResponse::BadRequest().json(Message::new(format!("{:#}", e)));
+    }
+    if !paths::WORLDS_DIR.join(&world).join("world.json").is_file() {
+        return HttpResponse::NotFound().json(Message::new(format!("World {} not found", world)));
+    }
+    let message = body
+        .into_inner()
+        .message
+        .filter(|message| !message.trim().is_empty())
+        .unwrap_or_else(worlds::default_lock_message);
+    let id = world.clone();
+    match web::block(move || WorldService::lock(&id, &message)).await {
+        Ok(Ok(())) => HttpResponse::Ok().json(Message::new(format!(
+            "Locked world {}, restart the world for players to see it",
+            world
+        ))),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(Message::new(format!("{:#}", e))),
+        Err(e) => HttpResponse::InternalServerError().json(Message::new(e.to_string())),
+    }
+}
+
+/// Unlock a world and restore its description
+#[utoipa::path(
+    delete,
+    path = "/api/worlds/{id}/lock",
+    operation_id = "unlockWorld",
+    security(("token" = [])),
+    params(("id" = String, Path, description = "Id of the world")),
+    responses(
+        (status = 200, description = "Unlocked", body = Message),
+        (status = 400, description = "Not a valid world id", body = Message),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 404, description = "The world is not locked", body = Message),
+    )
+)]
+pub async fn unlock_world(
+    req: HttpRequest,
+    path: web::Path<String>,
+    config: web::Data<ApiConfig>,
+) -> impl Responder {
+    if let Some(rejected) = reject(&req, &config) {
+        return rejected;
+    }
+    let world = path.into_inner();
+    if let Err(e) = worlds::validate_id(&world) {
+        return HttpResponse::BadRequest().json(Message::new(format!("{:#}", e)));
+    }
+    let id = world.clone();
+    let unlocked = web::block(move || {
+        let locked = WorldService::locks()?
+            .iter()
+            .any(|(locked, _)| *locked == id);
+        locked.then(|| WorldService::unlock(&id)).transpose()
+    })
+    .await;
+    match unlocked {
+        Ok(Ok(Some(()))) => {
+            HttpResponse::Ok().json(Message::new(format!("Unlocked world {}", world)))
+        }
+        Ok(Ok(None)) => {
+            HttpResponse::NotFound().json(Message::new(format!("World {} is not locked", world)))
+        }
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(Message::new(format!("{:#}", e))),
+        Err(e) => HttpResponse::InternalServerError().json(Message::new(e.to_string())),
+    }
+}
+
+/// Locked worlds by id, as `world locks --output json` prints them
+#[utoipa::path(
+    get,
+    path = "/api/worlds/locks",
+    operation_id = "worldLocks",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "Locks by world id", body = BTreeMap<String, WorldLock>),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+    )
+)]
+pub async fn world_locks(req: HttpRequest, config: web::Data<ApiConfig>) -> impl Responder {
+    if let Some(rejected) = reject(&req, &config) {
+        return rejected;
+    }
+    match web::block(WorldService::locks).await {
+        Ok(Ok(locks)) => HttpResponse::Ok().json(locks.into_iter().collect::<BTreeMap<_, _>>()),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(Message::new(format!("{:#}", e))),
+        Err(e) => HttpResponse::InternalServerError().json(Message::new(e.to_string())),
+    }
+}
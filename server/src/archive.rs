@@ -0,0 +1,256 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+use zip::write::ZipWriter;
+
+use crate::backup;
+use crate::config::AppConfig;
+use crate::extractor::ExtractorService;
+use crate::objectstore::{ObjectStoreConfig, S3Storage};
+use crate::permissions::{self, FileKind};
+use crate::restore;
+use crate::scan::ScanService;
+use crate::state::WrapperState;
+use crate::storage::Storage;
+use crate::tempfiles;
+use crate::utils::paths;
+use crate::worlds::{self, WorldService};
+
+/// Directory below `BACKUP_DIR` the archived worlds are kept in
+const ARCHIVE_DIR: &str = "archives";
+/// Directory of the bucket prefix the archives are uploaded to, next to those of the snapshots
+const REMOTE_DIR: &str = "archive";
+pub const STAGING_DIR: &str = ".unarchive-staging";
+
+/// Catalog entry of a world moved out of the data directory, kept in the wrapper state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedWorld {
+    /// File name in `BACKUP_DIR/archives`, also when only the bucket holds it
+    pub file: String,
+    pub title: String,
+    pub archived: String,
+    /// Size of the archive
+    pub bytes: u64,
+    pub files: usize,
+    pub core_version: Option<String>,
+    pub system: Option<String>,
+    /// Key of the copy in the `BACKUP_S3_BUCKET`, the local archive is removed once it is there
+    #[serde(default)]
+    pub remote: Option<String>,
+}
+
+/// Moves finished campaigns out of `Data/worlds` into `BACKUP_DIR/archives`, or into the
+/// backup bucket when there is one, and brings them back.
+///
+/// Archived worlds no longer take space in the data directory, in snapshots or on the setup
+/// screen, and no longer slow down scans and backups.
+pub struct ArchiveService {
+    pub s3: Option<ObjectStoreConfig>,
+    pub foundry_port: u16,
+}
+
+impl ArchiveService {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            s3: config.backup_s3.clone(),
+            foundry_port: config.server_port,
+        }
+    }
+
+    /// Zip the world, keep the archive locally or upload it, and remove the world. The world
+    /// stays in place when any of it fails.
+    pub async fn archive(&self, world: &str) -> Result<ArchivedWorld> {
+        let manifest = world_manifest(&paths::WORLDS_DIR.join(world))
+            .with_context(|| format!("World {} not found", world))?;
+        if WrapperState::load()?.archived_worlds.contains_key(world) {
+            return Err(anyhow!(
+                "An archive of world {} exists already, unarchive it first",
+                world
+            ));
+        }
+        worlds::refuse_active(self.foundry_port, world, "archiving it").await?;
+
+        let id = world.to_string();
+        let s3 = self.s3.clone();
+        let record =
+            tokio::task::spawn_blocking(move || write_archive(&id, &manifest, s3)).await??;
+
+        let mut state = WrapperState::load()?;
+        state
+            .archived_worlds
+            .insert(world.to_string(), record.clone());
+        state.save()?;
+        WorldService::remove(world)?;
+        info!(
+            "📦 Archived world {} to {}",
+            world,
+            record.remote.as_deref().unwrap_or(&record.file)
+        );
+        Ok(record)
+    }
+
+    /// Put an archived world back into `Data/worlds`, fetching it from the bucket when only
+    /// the bucket has it. The copy in the bucket is left to its lifecycle rules.
+    pub async fn unarchive(&self, world: &str) -> Result<ArchivedWorld> {
+        let record = WrapperState::load()?
+            .archived_worlds
+            .remove(world)
+            .ok_or_else(|| anyhow!("World {} is not archived, see `world archives`", world))?;
+        let target = paths::WORLDS_DIR.join(world);
+        if target.exists() {
+            return Err(anyhow!(
+                "{} exists, move it away before unarchiving",
+                target.display()
+            ));
+        }
+
+        let path = archive_dir().join(&record.file);
+        if !path.exists() {
+            let (Some(s3), Some(_)) = (self.s3.clone(), &record.remote) else {
+                return Err(anyhow!("{} is missing", path.display()));
+            };
+            let file = record.file.clone();
+            tokio::task::spawn_blocking(move || download(s3, &file)).await??;
+        }
+        let checked = path.clone();
+        tokio::task::spawn_blocking(move || restore::verify_archive(&checked))
+            .await?
+            .with_context(|| format!("{} is damaged, the world stays archived", record.file))?;
+
+        let staging = PathBuf::from(&*paths::DATA_DIR).join(STAGING_DIR);
+        remove_if_exists(&staging)?;
+        tempfiles::track(&staging, "unarchive staging");
+        // Nobody follows the progress of an unarchive
+        let (event_tx, _) = broadcast::channel(16);
+        let extracted = ExtractorService::extract_zip(
+            path.to_string_lossy().to_string(),
+            staging.to_string_lossy().to_string(),
+            event_tx,
+        )
+        .await
+        .with_context(|| format!("Failed to extract {}", record.file));
+        let unpacked = staging.join("Data").join("worlds").join(world);
+        let moved = extracted.and_then(|()| {
+            if !unpacked.join("world.json").is_file() {
+                return Err(anyhow!("{} has no world {}", record.file, world));
+            }
+            permissions::create_dir_all(&paths::WORLDS_DIR)?;
+            fs::rename(&unpacked, &target)
+                .with_context(|| format!("Failed to move {} into place", target.display()))
+        });
+        let _ = remove_if_exists(&staging);
+        moved?;
+
+        let mut state = WrapperState::load()?;
+        state.archived_worlds.remove(world);
+        state.save()?;
+        if let Err(e) = fs::remove_file(&path) {
+            warn!("Failed to remove {}: {}", path.display(), e);
+        }
+        info!("📦 Unarchived world {} from {}", world, record.file);
+        Ok(record)
+    }
+}
+
+/// Archived worlds by id
+pub fn catalog() -> Result<BTreeMap<String, ArchivedWorld>> {
+    Ok(WrapperState::load()?.archived_worlds)
+}
+
+fn write_archive(
+    world: &str,
+    manifest: &serde_json::Value,
+    s3: Option<ObjectStoreConfig>,
+) -> Result<ArchivedWorld> {
+    let dir = archive_dir();
+    permissions::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    let archived = Utc::now();
+    let file = format!("{}-{}.zip", world, archived.format("%Y%m%d-%H%M%S"));
+    let path = dir.join(&file);
+    let partial = dir.join(format!(".{}.partial", file));
+    tempfiles::track(&partial, "partial world archive");
+
+    let entries = ScanService::walk(&paths::WORLDS_DIR.join(world), &[])?;
+    let (files, _) = (|| -> Result<(usize, u64)> {
+        let output = permissions::create(&partial, FileKind::Secret)
+            .with_context(|| format!("Failed to create {}", partial.display()))?;
+        let mut zip = ZipWriter::new(io::BufWriter::new(output));
+        let written = backup::add_files(&mut zip, &entries)?;
+        zip.finish()?;
+        restore::verify_archive(&partial)?;
+        Ok(written)
+    })()
+    .inspect_err(|_| {
+        let _ = fs::remove_file(&partial);
+    })?;
+    fs::rename(&partial, &path)
+        .with_context(|| format!("Failed to move {} into place", path.display()))?;
+
+    let mut record = ArchivedWorld {
+        file: file.clone(),
+        title: manifest["title"].as_str().unwrap_or(world).to_string(),
+        archived: archived.to_rfc3339(),
+        bytes: fs::metadata(&path)?.len(),
+        files,
+        core_version: manifest["coreVersion"].as_str().map(str::to_string),
+        system: manifest["system"].as_str().map(str::to_string),
+        remote: None,
+    };
+    if let Some(s3) = s3 {
+        let storage = S3Storage::new(s3.clone(), archive_dir())?;
+        let target = remote_path(&file);
+        // The world is not removed without a copy in the bucket
+        storage
+            .upload_file(&target, &path)
+            .with_context(|| format!("Failed to upload {} to {}", file, s3.bucket))?;
+        record.remote = Some(storage.key(&target)?);
+        if let Err(e) = fs::remove_file(&path) {
+            warn!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+    Ok(record)
+}
+
+fn download(s3: ObjectStoreConfig, file: &str) -> Result<()> {
+    let storage = S3Storage::new(s3.clone(), archive_dir())?;
+    let contents = storage
+        .read(&remote_path(file))
+        .with_context(|| format!("Failed to download {} from {}", file, s3.bucket))?;
+    let path = archive_dir().join(file);
+    let partial = archive_dir().join(format!(".{}.partial", file));
+    permissions::create_dir_all(&archive_dir())?;
+    tempfiles::track(&partial, "partial world archive");
+    permissions::write(&partial, contents, FileKind::Secret)
+        .with_context(|| format!("Failed to write {}", partial.display()))?;
+    fs::rename(&partial, &path)
+        .with_context(|| format!("Failed to move {} into place", path.display()))
+}
+
+fn world_manifest(dir: &Path) -> Result<serde_json::Value> {
+    let contents = fs::read_to_string(dir.join("world.json"))?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_dir_all(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn remote_path(file: &str) -> PathBuf {
+    archive_dir().join(REMOTE_DIR).join(file)
+}
+
+fn archive_dir() -> PathBuf {
+    PathBuf::from(&*paths::BACKUP_DIR).join(ARCHIVE_DIR)
+}
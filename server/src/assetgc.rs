@@ -0,0 +1,257 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+use crate::config::AppConfig;
+use crate::permissions;
+use crate::rewrite::{self, percent_decode};
+use crate::scan::{ScanEntry, ScanService};
+use crate::utils::paths;
+
+/// Directories of `Data` that are never collected: packages reference their own files by
+/// relative path and worlds hold the databases
+const NOT_COLLECTED: &[&str] = &["/worlds/", "/systems/", "/modules/"];
+
+/// What `ASSET_GC` does at startup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GcMode {
+    /// Only log the unreferenced files
+    Report,
+    /// Also move them to the quarantine
+    Collect,
+}
+
+/// Files of `Data` no world links to
+#[derive(Debug, Default)]
+pub struct GcReport {
+    /// Paths relative to `Data`
+    pub unreferenced: Vec<String>,
+    pub bytes: u64,
+    /// Files skipped because they changed within the minimum age
+    pub recent: usize,
+    /// Where the files were moved to when collecting
+    pub quarantine: Option<PathBuf>,
+}
+
+/// Finds uploaded assets that no document, compendium pack or world manifest refers to
+/// anymore, e.g. maps of a deleted scene.
+///
+/// Files are moved to `BACKUP_DIR/asset-gc/<UTC time>/` instead of deleted, so a reference
+/// the scan missed costs a move back rather than the file.
+pub struct AssetCollector {
+    pub excludes: Vec<String>,
+    /// Files modified more recently may belong to a document that is not saved yet
+    pub min_age: Duration,
+}
+
+impl AssetCollector {
+    pub fn from_config(config: &AppConfig, min_age: Duration) -> Self {
+        Self {
+            excludes: config.scan_excludes.clone(),
+            min_age,
+        }
+    }
+
+    /// List the unreferenced files, and move them to the quarantine with `collect`
+    pub fn run(&self, collect: bool) -> Result<GcReport> {
+        let references = collect_references()?;
+        let data_dir = PathBuf::from(&*paths::DATA_DIR).join("Data");
+        let mut excludes: Vec<String> = NOT_COLLECTED.iter().map(|dir| dir.to_string()).collect();
+        excludes.extend(self.excludes.iter().cloned());
+
+        let mut report = GcReport::default();
+        let now = SystemTime::now();
+        let mut candidates: Vec<(String, ScanEntry)> = Vec::new();
+        for entry in ScanService::walk(&data_dir, &excludes)? {
+            let Ok(relative) = entry.path.strip_prefix(&data_dir) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            if references.contains(&relative) {
+                continue;
+            }
+            let modified = fs::metadata(&entry.path).and_then(|m| m.modified());
+            let age = modified.map(|m| now.duration_since(m).unwrap_or_default());
+            if age.is_ok_and(|age| age < self.min_age) {
+                report.recent += 1;
+                continue;
+            }
+            candidates.push((relative, entry));
+        }
+        candidates.sort_by(|a, b| a.0.cmp(&b.0));
+
+        if collect && !candidates.is_empty() {
+            let quarantine = PathBuf::from(&*paths::BACKUP_DIR)
+                .join("asset-gc")
+                .join(chrono::Utc::now().format("%Y%m%d-%H%M%S").to_string());
+            for (relative, entry) in &candidates {
+                move_file(&entry.path, &quarantine.join(relative))
+                    .with_context(|| format!("Failed to move {} aside", relative))?;
+            }
+            info!(
+                "🧹 Moved {} unreferenced assets to {}",
+                candidates.len(),
+                quarantine.display()
+            );
+            report.quarantine = Some(quarantine);
+        }
+        report.bytes = candidates.iter().map(|(_, entry)| entry.size).sum();
+        report.unreferenced = candidates.into_iter().map(|(path, _)| path).collect();
+        Ok(report)
+    }
+}
+
+/// Every path below `Data` that a world refers to, in the form `Data` relative paths are
+/// compared in
+fn collect_references() -> Result<HashSet<String>> {
+    let mut references = HashSet::new();
+    for world in rewrite::world_ids(&[])? {
+        let mut record = |text: &str| references.extend(referenced_paths(text));
+        rewrite::visit_world(&world, &mut record)
+            .with_context(|| format!("Failed to read the documents of world {}", world))?;
+
+        // Background images and the like of the setup screen live in the manifest
+        let manifest = paths::WORLDS_DIR.join(&world).join("world.json");
+        match fs::read_to_string(&manifest).map(|text| serde_json::from_str::<Value>(&text)) {
+            Ok(Ok(manifest)) => rewrite::visit_strings(&manifest, &mut record),
+            _ => warn!("⚠️ Could not read {}", manifest.display()),
+        }
+    }
+    Ok(references)
+}
+
+/// The paths a document string may refer to. Besides whole paths like `img` these are `src`
+/// attributes and CSS `url()`s in HTML and the JSON encoded values of module settings, so the
+/// text is split at quotes, brackets and line breaks, but not at spaces, which file names
+/// may contain.
+fn referenced_paths(text: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    for token in text.split(['"', '\'', '<', '>', '(', ')', '\\', '\n', '\r', '\t']) {
+        let mut path = token.trim();
+        // Links to this server's own URL, e.g. pasted into a journal
+        if let Some((_, rest)) = path.split_once("://") {
+            path = rest.find('/').map(|slash| &rest[slash..]).unwrap_or("");
+        }
+        let path = path
+            .split(['?', '#'])
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches('/');
+        if path.is_empty() {
+            continue;
+        }
+        if let Some(decoded) = percent_decode(path).filter(|decoded| decoded != path) {
+            paths.push(decoded);
+        }
+        paths.push(path.to_string());
+    }
+    paths
+}
+
+/// Move `from` to `to`, copying when the backup directory is on another volume
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        permissions::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            fs::copy(from, to)?;
+            fs::remove_file(from)?;
+            Ok(())
+        }
+        result => Ok(result?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusty_leveldb::compressor::{CompressorId, SnappyCompressor};
+    use rusty_leveldb::{DB, Options};
+
+    #[test]
+    fn whole_paths_are_references() {
+        assert_eq!(
+            referenced_paths("assets/maps/cave.webp"),
+            vec!["assets/maps/cave.webp"]
+        );
+        assert_eq!(
+            referenced_paths("/assets/maps/cave.webp?v=2"),
+            vec!["assets/maps/cave.webp"]
+        );
+    }
+
+    #[test]
+    fn encoded_paths_are_decoded() {
+        assert_eq!(
+            referenced_paths("assets/my%20map.webp"),
+            vec!["assets/my map.webp", "assets/my%20map.webp"]
+        );
+        assert!(referenced_paths("assets/my map.webp").contains(&"assets/my map.webp".into()));
+    }
+
+    #[test]
+    fn html_and_css_references_are_found() {
+        let html = r#"<p><img src="assets/a.png"> <span style="background: url('assets/b.jpg')"></span></p>"#;
+        let paths = referenced_paths(html);
+        assert!(paths.contains(&"assets/a.png".to_string()));
+        assert!(paths.contains(&"assets/b.jpg".to_string()));
+    }
+
+    #[test]
+    fn json_encoded_settings_and_urls_are_found() {
+        let setting = r#"{\"logo\":\"assets/logo.svg\"}"#;
+        assert!(referenced_paths(setting).contains(&"assets/logo.svg".to_string()));
+        let url = "https://vtt.example.com/assets/handout.pdf#page=2";
+        assert!(referenced_paths(url).contains(&"assets/handout.pdf".to_string()));
+    }
+
+    #[test]
+    fn documents_are_read_from_nedb_and_leveldb() {
+        let world = |id: &str| {
+            let dir = paths::WORLDS_DIR.join(id);
+            fs::create_dir_all(dir.join("data")).unwrap();
+            fs::write(
+                dir.join("world.json"),
+                r#"{"id":"world","background":"assets/setup/title.webp"}"#,
+            )
+            .unwrap();
+            dir
+        };
+
+        let nedb = world("asset-gc-nedb");
+        fs::write(
+            nedb.join("data").join("scenes.db"),
+            "{\"_id\":\"a\",\"background\":{\"src\":\"assets/maps/cave.webp\"}}\n",
+        )
+        .unwrap();
+
+        let leveldb = world("asset-gc-leveldb");
+        let options = Options {
+            compressor: SnappyCompressor::ID,
+            ..Options::default()
+        };
+        let mut db = DB::open(leveldb.join("data").join("journal"), options).unwrap();
+        db.put(
+            b"!journal.pages!b.c",
+            br#"{"_id":"c","text":{"content":"<img src=\"assets/handouts/map%20key.png\">"}}"#,
+        )
+        .unwrap();
+        db.flush().unwrap();
+        drop(db);
+
+        let references = collect_references();
+        // Only this test writes to the data directory of the tests
+        fs::remove_dir_all(&*paths::DATA_DIR).unwrap();
+        let references = references.unwrap();
+        assert!(references.contains("assets/maps/cave.webp"));
+        assert!(references.contains("assets/handouts/map key.png"));
+        assert!(references.contains("assets/setup/title.webp"));
+    }
+}
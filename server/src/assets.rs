@@ -0,0 +1,114 @@
+use actix_web::http::header;
+use actix_web::{HttpResponse, Responder, web};
+use anyhow::{Result, anyhow};
+use std::path::Path;
+use tracing::{debug, error};
+
+/// A file of the setup UI compiled into the binary
+pub struct Asset {
+    pub name: &'static str,
+    pub content_type: &'static str,
+    pub contents: &'static str,
+}
+
+/// The setup UI, so the wrapper runs from a single file in scratch and distroless images.
+/// Message catalogs are embedded by `i18n` the same way.
+static ASSETS: &[Asset] = &[
+    Asset {
+        name: "index.html",
+        content_type: "text/html; charset=utf-8",
+        contents: include_str!("../static/index.html"),
+    },
+    Asset {
+        name: "progress.html",
+        content_type: "text/html; charset=utf-8",
+        contents: include_str!("../static/progress.html"),
+    },
+    Asset {
+        name: "index.css",
+        content_type: "text/css; charset=utf-8",
+        contents: include_str!("../static/index.css"),
+    },
+    Asset {
+        name: "index.js",
+        content_type: "text/javascript; charset=utf-8",
+        contents: include_str!("../static/index.js"),
+    },
+];
+
+/// The embedded asset called `name`
+pub fn get(name: &str) -> Option<&'static Asset> {
+    ASSETS.iter().find(|asset| asset.name == name)
+}
+
+/// Contents of `name`, read from `override_dir` when one is configured
+pub async fn load(name: &str, override_dir: Option<&str>) -> Result<String> {
+    match override_dir {
+        Some(dir) => {
+            let path = Path::new(dir).join(name);
+            tokio::fs::read_to_string(&path)
+                .await
+                .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))
+        }
+        None => get(name)
+            .map(|asset| asset.contents.to_string())
+            .ok_or_else(|| anyhow!("{} is not embedded", name)),
+    }
+}
+
+/// Serve an embedded asset, unknown names fall through to the 404 handler
+pub async fn serve(name: web::Path<String>) -> impl Responder {
+    match get(&name) {
+        Some(asset) => HttpResponse::Ok()
+            .content_type(asset.content_type)
+            .insert_header((header::CACHE_CONTROL, "no-cache"))
+            .body(asset.contents),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+/// Check that every local `href` and `src` of the embedded files is embedded as well, so
+/// nothing the UI needs lives outside the binary
+pub fn verify() -> Result<()> {
+    let mut missing = Vec::new();
+    for asset in ASSETS {
+        for reference in local_references(asset.contents) {
+            if get(reference).is_none() {
+                missing.push(format!("{} (referenced by {})", reference, asset.name));
+            }
+        }
+    }
+    if missing.is_empty() {
+        debug!("All {} embedded assets are self-contained", ASSETS.len());
+        Ok(())
+    } else {
+        for reference in &missing {
+            error!("Asset not embedded: {}", reference);
+        }
+        Err(anyhow!("{} assets are not embedded", missing.len()))
+    }
+}
+
+/// Relative file references in HTML attributes, skipping URLs, anchors and server routes
+fn local_references(contents: &str) -> Vec<&str> {
+    let mut references = Vec::new();
+    for attribute in ["href=\"", "src=\""] {
+        let mut rest = contents;
+        while let Some(start) = rest.find(attribute) {
+            rest = &rest[start + attribute.len()..];
+            let Some(end) = rest.find('"') else {
+                break;
+            };
+            let reference = &rest[..end];
+            // Absolute paths are routes of the installer or of Foundry itself
+            if !reference.is_empty()
+                && !reference.contains("://")
+                && !reference.starts_with(['/', '#', '{'])
+            {
+                references.push(reference);
+            }
+            rest = &rest[end..];
+        }
+    }
+    references
+}
@@ -0,0 +1,95 @@
+use chrono::Local;
+use tokio::time::{Duration, Instant, sleep};
+use tracing::{debug, info, warn};
+
+use crate::gamesocket;
+use crate::launch;
+use crate::notify::{self, Notification, Severity};
+use crate::schedule::CronSchedule;
+use crate::status;
+
+/// Returns an active world nobody plays to the setup screen, so players still logged in
+/// cannot change it between sessions. With `GAME_USER` the world is shut down directly,
+/// otherwise Foundry is restarted, which only lands on the setup screen when no world is
+/// launched on start.
+#[derive(Clone)]
+pub struct AutoReturn {
+    /// Return once no player was connected for this long
+    pub idle: Option<Duration>,
+    /// Return at these times when no player is connected
+    pub schedule: Option<CronSchedule>,
+    pub foundry_port: u16,
+    pub interval: Duration,
+}
+
+impl AutoReturn {
+    pub async fn run(self) {
+        let mut empty_since: Option<Instant> = None;
+        let mut next = self.next_scheduled();
+        loop {
+            sleep(self.interval).await;
+            let due = next.is_some_and(|next| Local::now() >= next);
+            if due {
+                next = self.next_scheduled();
+            }
+
+            let server = match status::fetch_server_status(self.foundry_port).await {
+                Ok(server) if server.active => server,
+                // On the setup screen already, or Foundry does not answer
+                _ => {
+                    empty_since = None;
+                    continue;
+                }
+            };
+            let world = server.world.unwrap_or_default();
+            if server.users > 0 {
+                if due {
+                    info!(
+                        "{} players are connected to {}, not returning to the setup screen",
+                        server.users, world
+                    );
+                }
+                empty_since = None;
+                continue;
+            }
+
+            let empty_for = empty_since.get_or_insert_with(Instant::now).elapsed();
+            let reason = if due {
+                Some("at the time of RETURN_TO_SETUP_SCHEDULE".to_string())
+            } else {
+                self.idle
+                    .filter(|idle| empty_for >= *idle)
+                    .map(|idle| format!("after {} minutes without players", idle.as_secs() / 60))
+            };
+            let Some(reason) = reason else {
+                debug!("{} has no players for {}s", world, empty_for.as_secs());
+                continue;
+            };
+            notify::send(Notification {
+                severity: Severity::Info,
+                title: "World returned to the setup screen".to_string(),
+                message: format!("{} was shut down {}.", world, reason),
+            });
+            // Shutting the world down keeps Foundry running, the restart is the fallback
+            match gamesocket::client().filter(|client| client.user.is_some()) {
+                Some(client) => {
+                    if let Err(e) = client.shut_down().await {
+                        warn!(
+                            "⚠️ Failed to shut {} down, restarting Foundry: {:#}",
+                            world, e
+                        );
+                        launch::request_restart();
+                    }
+                }
+                None => launch::request_restart(),
+            }
+            empty_since = None;
+        }
+    }
+
+    fn next_scheduled(&self) -> Option<chrono::DateTime<Local>> {
+        self.schedule
+            .as_ref()
+            .and_then(|schedule| schedule.next_after(Local::now()))
+    }
+}
@@ -0,0 +1,692 @@
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::time::{Duration, sleep};
+use tracing::{debug, info, warn};
+use utoipa::{IntoParams, ToSchema};
+use zip::CompressionMethod;
+use zip::write::{SimpleFileOptions, ZipWriter};
+
+use crate::api::{self, ApiConfig, Message};
+use crate::capabilities;
+use crate::config::AppConfig;
+use crate::diskspace;
+use crate::environment;
+use crate::hooks::{self, HookEvent};
+use crate::inhibit;
+use crate::jobs;
+use crate::jobstore;
+#[cfg(feature = "full")]
+use crate::metrics;
+use crate::notify::{self, Notification, Severity};
+use crate::objectstore::{ObjectStoreConfig, S3Storage};
+use crate::packages::installed_packages;
+use crate::permissions::{self, FileKind};
+use crate::restoretest::RestoreTestResult;
+use crate::scan::{ScanEntry, ScanService};
+use crate::schedule::CronSchedule;
+use crate::state::WrapperState;
+use crate::status;
+use crate::tempfiles;
+use crate::utils::paths;
+use crate::worlds::WorldService;
+
+/// Directory below `BACKUP_DIR` the snapshots are written to
+const SNAPSHOT_DIR: &str = "snapshots";
+const PREFIX: &str = "foundry-";
+const TIMESTAMP: &str = "%Y%m%d-%H%M%S";
+/// Top-level directories of `DATA_DIR` a snapshot contains, logs and wrapper state are left out
+pub const INCLUDED: [&str; 2] = ["Config", "Data"];
+
+/// Which snapshots survive pruning, all of them when neither rule is set.
+///
+/// A snapshot is kept when either rule keeps it, and the newest one is never removed.
+#[derive(Debug, Clone, Default)]
+pub struct Retention {
+    pub keep_last: Option<usize>,
+    pub keep_days: Option<u64>,
+}
+
+/// A snapshot in `BACKUP_DIR/snapshots`
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub path: PathBuf,
+    pub created: DateTime<Utc>,
+}
+
+/// What started a backup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupTrigger {
+    Schedule,
+    /// `backup create` on the command line
+    Manual,
+    Discord,
+    /// `POST /api/backup` or a `backup` webhook on the admin API
+    Api,
+    /// Before the worlds are migrated to a newer Foundry generation
+    Migration,
+}
+
+/// Catalog entry of a snapshot, kept in the wrapper state
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BackupRecord {
+    /// File name in `BACKUP_DIR/snapshots`
+    pub file: String,
+    #[schema(format = DateTime)]
+    pub created: String,
+    /// Size of the archive
+    pub bytes: u64,
+    pub files: usize,
+    pub duration_ms: u64,
+    pub trigger: BackupTrigger,
+    pub worlds: Vec<String>,
+    /// Installed Foundry version at the time, restores into another one may break the worlds
+    pub foundry_version: Option<String>,
+    /// Installed game systems and their versions, keyed by id
+    #[serde(default)]
+    pub systems: BTreeMap<String, String>,
+    /// Key of the uploaded copy in the `BACKUP_S3_BUCKET`
+    #[serde(default)]
+    pub remote: Option<String>,
+    #[serde(default)]
+    pub restore_test: Option<RestoreTestResult>,
+}
+
+/// A snapshot that failed, when and why
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedBackup {
+    pub at: String,
+    pub error: String,
+}
+
+/// Outcome of the newest snapshot attempt
+#[cfg(feature = "full")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupResult {
+    /// When the snapshot was taken or failed, RFC 3339
+    pub at: String,
+    pub succeeded: bool,
+}
+
+#[derive(Debug)]
+pub struct BackupSummary {
+    pub path: PathBuf,
+    pub record: BackupRecord,
+    pub removed: Vec<PathBuf>,
+}
+
+/// Zip snapshots of the worlds, packages and configuration in the data directory
+#[derive(Debug, Clone)]
+pub struct BackupService {
+    /// `SCAN_EXCLUDE` globs, matched relative to `DATA_DIR`
+    pub excludes: Vec<String>,
+    pub retention: Retention,
+    /// Bucket every snapshot is uploaded to after it was written
+    pub s3: Option<ObjectStoreConfig>,
+}
+
+impl BackupService {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            excludes: config.scan_excludes.clone(),
+            retention: Retention {
+                keep_last: config.backup_keep_last,
+                keep_days: config.backup_keep_days,
+            },
+            s3: config.backup_s3.clone(),
+        }
+    }
+
+    /// Write a snapshot of `DATA_DIR` and prune old ones afterwards.
+    ///
+    /// The archive is written under a temporary name first, an interrupted backup never
+    /// looks like a complete one.
+    pub fn create(&self, trigger: BackupTrigger) -> Result<BackupSummary> {
+        let _inhibitor = inhibit::acquire("backup")?;
+        let summary = self.write_snapshot(trigger);
+        #[cfg(feature = "full")]
+        metrics::backup_finished(summary.is_ok());
+        if let Err(e) = record_result(summary.as_ref().err()) {
+            warn!("⚠️ Failed to record the backup result: {:#}", e);
+        }
+        summary
+    }
+
+    fn write_snapshot(&self, trigger: BackupTrigger) -> Result<BackupSummary> {
+        // A failing hook is reported, the snapshot is still worth more than none
+        if let Err(e) = hooks::run_blocking(HookEvent::PreBackup, json!({ "trigger": trigger })) {
+            warn!("⚠️ {:#}", e);
+        }
+        let started = Instant::now();
+        let mut created = Utc::now();
+        let entries = snapshot_entries(&self.excludes)?;
+
+        let dir = snapshot_dir();
+        permissions::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        // Names have a resolution of seconds, two snapshots within one must not collide
+        while dir
+            .join(format!("{}{}.zip", PREFIX, created.format(TIMESTAMP)))
+            .exists()
+        {
+            created += chrono::Duration::seconds(1);
+        }
+        let name = format!("{}{}.zip", PREFIX, created.format(TIMESTAMP));
+        let path = dir.join(&name);
+        let partial = dir.join(format!(".{}.partial", name));
+        tempfiles::track(&partial, "partial backup");
+
+        let file = permissions::create(&partial, FileKind::Secret)
+            .with_context(|| format!("Failed to create {}", partial.display()))?;
+        let mut zip = ZipWriter::new(io::BufWriter::new(file));
+        let (files, bytes) = add_files(&mut zip, &entries)?;
+        if let Some(manifest) = environment::capture_or_warn(&entries) {
+            zip.start_file(environment::MANIFEST, SimpleFileOptions::default())?;
+            zip.write_all(&manifest)?;
+        }
+        zip.finish()?;
+        fs::rename(&partial, &path)
+            .with_context(|| format!("Failed to move {} into place", path.display()))?;
+        info!(
+            "💾 Backed up {} files ({} MB) to {}",
+            files,
+            bytes / (1024 * 1024),
+            path.display()
+        );
+
+        let packages = installed_packages();
+        let mut record = BackupRecord {
+            file: name.clone(),
+            created: created.to_rfc3339(),
+            bytes: fs::metadata(&path)?.len(),
+            files,
+            duration_ms: started.elapsed().as_millis() as u64,
+            trigger,
+            worlds: WorldService::ids().unwrap_or_default(),
+            foundry_version: packages.get("core").cloned(),
+            systems: packages
+                .iter()
+                .filter_map(|(key, version)| {
+                    Some((key.strip_prefix("system:")?.to_string(), version.clone()))
+                })
+                .collect(),
+            remote: None,
+            restore_test: None,
+        };
+        // Cataloged before the upload, a restart while uploading continues it at startup
+        let mut state = WrapperState::load()?;
+        state.backups.retain(|known| known.file != record.file);
+        state.backups.push(record.clone());
+        state.save()?;
+
+        if let Some(s3) = &self.s3 {
+            match upload(s3, &path, trigger) {
+                Ok(key) => {
+                    info!("Uploaded backup to {}/{}", s3.bucket, key);
+                    capabilities::active("s3-backup");
+                    record_upload(&name, &key)?;
+                    record.remote = Some(key);
+                }
+                // The local snapshot is complete, only the off-site copy is missing
+                Err(e) => upload_failed(&name, s3, &e),
+            }
+        }
+
+        let removed = self.prune()?;
+        Ok(BackupSummary {
+            path,
+            record,
+            removed,
+        })
+    }
+
+    /// Remove the snapshots the retention rules do not keep
+    pub fn prune(&self) -> Result<Vec<PathBuf>> {
+        let Retention {
+            keep_last,
+            keep_days,
+        } = self.retention;
+        if keep_last.is_none() && keep_days.is_none() {
+            return Ok(Vec::new());
+        }
+        let now = Utc::now();
+        let mut snapshots = snapshots()?;
+        // Newest first
+        snapshots.reverse();
+        let mut removed = Vec::new();
+        for (i, snapshot) in snapshots.iter().enumerate().skip(1) {
+            let by_count = keep_last.is_some_and(|last| i < last);
+            let by_age = keep_days.is_some_and(|days| {
+                now.signed_duration_since(snapshot.created).num_days() < days as i64
+            });
+            if by_count || by_age {
+                continue;
+            }
+            match fs::remove_file(&snapshot.path) {
+                Ok(()) => {
+                    debug!("Removed backup {}", snapshot.path.display());
+                    removed.push(snapshot.path.clone());
+                }
+                Err(e) => warn!("Failed to remove {}: {}", snapshot.path.display(), e),
+            }
+        }
+        if !removed.is_empty() {
+            info!("Removed {} old backups", removed.len());
+            let mut state = WrapperState::load()?;
+            state
+                .backups
+                .retain(|record| !removed.iter().any(|path| path.ends_with(&record.file)));
+            state.save()?;
+        }
+        Ok(removed)
+    }
+}
+
+/// Remember a failed snapshot, or forget the last failure once one succeeds
+fn record_result(error: Option<&anyhow::Error>) -> Result<()> {
+    let mut state = WrapperState::load()?;
+    let failed = error.map(|e| FailedBackup {
+        at: Utc::now().to_rfc3339(),
+        error: format!("{:#}", e),
+    });
+    if failed.is_none() && state.failed_backup.is_none() {
+        return Ok(());
+    }
+    state.failed_backup = failed;
+    state.save()
+}
+
+/// The newest snapshot or the failure after it, `None` before the first snapshot
+#[cfg(feature = "full")]
+pub fn last_result() -> Result<Option<BackupResult>> {
+    let failed = WrapperState::load()?.failed_backup;
+    let newest = catalog(None)?.pop();
+    Ok(pick_last_result(
+        newest.map(|record| record.created),
+        failed.map(|failed| failed.at),
+    ))
+}
+
+#[cfg(feature = "full")]
+fn pick_last_result(succeeded: Option<String>, failed: Option<String>) -> Option<BackupResult> {
+    let newer = |a: &str, b: &str| match (
+        DateTime::parse_from_rfc3339(a),
+        DateTime::parse_from_rfc3339(b),
+    ) {
+        (Ok(a), Ok(b)) => a > b,
+        _ => a > b,
+    };
+    match (succeeded, failed) {
+        (Some(succeeded), Some(failed)) if newer(&succeeded, &failed) => Some(BackupResult {
+            at: succeeded,
+            succeeded: true,
+        }),
+        (_, Some(failed)) => Some(BackupResult {
+            at: failed,
+            succeeded: false,
+        }),
+        (Some(succeeded), None) => Some(BackupResult {
+            at: succeeded,
+            succeeded: true,
+        }),
+        (None, None) => None,
+    }
+}
+
+/// Catalog of the snapshots still on disk, oldest first, only those containing `world` when
+/// given. Snapshots taken before the catalog existed are listed with what their file tells.
+pub fn catalog(world: Option<&str>) -> Result<Vec<BackupRecord>> {
+    let records = WrapperState::load()?.backups;
+    let mut catalog: Vec<BackupRecord> = snapshots()?
+        .into_iter()
+        .filter_map(|snapshot| {
+            let file = snapshot.path.file_name()?.to_string_lossy().to_string();
+            let bytes = fs::metadata(&snapshot.path).ok()?.len();
+            Some(
+                records
+                    .iter()
+                    .find(|record| record.file == file)
+                    .cloned()
+                    .unwrap_or_else(|| BackupRecord {
+                        file,
+                        created: snapshot.created.to_rfc3339(),
+                        bytes,
+                        files: 0,
+                        duration_ms: 0,
+                        trigger: BackupTrigger::Manual,
+                        worlds: Vec::new(),
+                        foundry_version: None,
+                        systems: BTreeMap::new(),
+                        remote: None,
+                        restore_test: None,
+                    }),
+            )
+        })
+        .filter(|record| world.is_none_or(|world| record.worlds.iter().any(|w| w == world)))
+        .collect();
+    catalog.sort_by(|a, b| a.created.cmp(&b.created));
+    Ok(catalog)
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CatalogQuery {
+    /// Only snapshots containing this world
+    world: Option<String>,
+}
+
+/// Catalog of the snapshots in `BACKUP_DIR/snapshots`, oldest first
+#[utoipa::path(
+    get,
+    path = "/api/backups",
+    operation_id = "backups",
+    params(CatalogQuery),
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "One entry per snapshot", body = Vec<BackupRecord>),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 500, description = "The catalog could not be read", body = String, content_type = "text/plain"),
+    )
+)]
+pub async fn list(
+    req: HttpRequest,
+    config: web::Data<ApiConfig>,
+    query: web::Query<CatalogQuery>,
+) -> impl Responder {
+    if let Some(rejected) = api::reject(&req, &config) {
+        return rejected;
+    }
+    let world = query.into_inner().world;
+    match web::block(move || catalog(world.as_deref())).await {
+        Ok(Ok(catalog)) => HttpResponse::Ok().json(catalog),
+        Ok(Err(e)) => HttpResponse::InternalServerError().body(format!("{:#}", e)),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Upload a snapshot below `<trigger>/` of the bucket prefix, so lifecycle rules of the bucket
+/// can expire scheduled snapshots while keeping those taken by hand. Answers the key.
+fn upload(config: &ObjectStoreConfig, path: &Path, trigger: BackupTrigger) -> io::Result<String> {
+    let directory = match trigger {
+        BackupTrigger::Schedule => "schedule",
+        BackupTrigger::Manual => "manual",
+        BackupTrigger::Discord => "discord",
+        BackupTrigger::Api => "api",
+        BackupTrigger::Migration => "migration",
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::other("The snapshot has no file name"))?;
+    let root = snapshot_dir();
+    let storage = S3Storage::new(config.clone(), root.clone())?;
+    let target = root.join(directory).join(name);
+    storage.upload_file(&target, path)?;
+    storage.key(&target)
+}
+
+fn upload_failed(name: &str, s3: &ObjectStoreConfig, error: &dyn std::fmt::Display) {
+    capabilities::failed("s3-backup", error);
+    notify::send(Notification {
+        severity: Severity::Critical,
+        title: "Backup upload failed".to_string(),
+        message: format!("{} was not uploaded to {}: {}", name, s3.bucket, error),
+    });
+}
+
+/// Note the key of the uploaded copy in the catalog entry of snapshot `name`
+fn record_upload(name: &str, key: &str) -> Result<()> {
+    let mut state = WrapperState::load()?;
+    if let Some(record) = state.backups.iter_mut().find(|record| record.file == name) {
+        record.remote = Some(key.to_string());
+        state.save()?;
+    }
+    Ok(())
+}
+
+/// Continue the uploads of snapshots a restart of the container interrupted, with the parts
+/// the bucket accepted before the restart
+pub async fn resume_uploads(s3: ObjectStoreConfig) {
+    let dir = snapshot_dir();
+    let pending = jobstore::pending_uploads()
+        .into_iter()
+        .filter(|(_, job)| job.bucket == s3.bucket && job.source.starts_with(&dir));
+    for (key, job) in pending {
+        let name = job
+            .source
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let config = s3.clone();
+        let uploaded = jobs::run("backup upload", move || -> io::Result<Option<String>> {
+            let storage = S3Storage::new(config, snapshot_dir())?;
+            // Pruned meanwhile, nothing is left to upload
+            if !job.source.exists() {
+                storage.abort(&key, &job.upload_id);
+                jobstore::record(|store| {
+                    store.uploads.remove(&key);
+                });
+                return Ok(None);
+            }
+            storage.upload_file_as(&key, &job.source)?;
+            Ok(Some(key))
+        })
+        .await;
+        match uploaded {
+            Ok(Ok(Some(key))) => {
+                info!("Uploaded backup to {}/{} after the restart", s3.bucket, key);
+                if let Err(e) = record_upload(&name, &key) {
+                    warn!("Failed to catalog the upload of {}: {:#}", name, e);
+                }
+            }
+            Ok(Ok(None)) => debug!("Dropped the upload of the removed backup {}", name),
+            Ok(Err(e)) => upload_failed(&name, &s3, &e),
+            Err(e) => upload_failed(&name, &s3, &e),
+        }
+    }
+}
+
+/// Files of `DATA_DIR` a snapshot contains, sorted by path
+pub fn snapshot_entries(excludes: &[String]) -> Result<Vec<ScanEntry>> {
+    let data_dir = PathBuf::from(&*paths::DATA_DIR);
+    let mut entries: Vec<_> = ScanService::walk(&data_dir, excludes)?
+        .into_iter()
+        .filter(|entry| {
+            entry.path.strip_prefix(&data_dir).is_ok_and(|relative| {
+                relative
+                    .components()
+                    .next()
+                    .is_some_and(|first| INCLUDED.iter().any(|dir| first.as_os_str() == *dir))
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// Add `entries` to `zip` by their path relative to `DATA_DIR`, answering the number of files
+/// and bytes written
+pub fn add_files<W: Write + io::Seek>(
+    zip: &mut ZipWriter<W>,
+    entries: &[ScanEntry],
+) -> Result<(usize, u64)> {
+    let data_dir = PathBuf::from(&*paths::DATA_DIR);
+    let mut bytes = 0;
+    let mut files = 0;
+    for entry in entries {
+        let relative = entry.path.strip_prefix(&data_dir)?;
+        let mut source = match fs::File::open(&entry.path) {
+            Ok(source) => source,
+            // Foundry may delete a file while the snapshot is written, e.g. a compacted log
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", relative.display()));
+            }
+        };
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(entry.size >= u32::MAX as u64);
+        zip.start_file(relative.to_string_lossy(), options)?;
+        bytes += io::copy(&mut source, zip)
+            .with_context(|| format!("Failed to back up {}", relative.display()))?;
+        files += 1;
+    }
+    Ok((files, bytes))
+}
+
+pub fn snapshot_dir() -> PathBuf {
+    PathBuf::from(&*paths::BACKUP_DIR).join(SNAPSHOT_DIR)
+}
+
+/// Complete snapshots, oldest first
+pub fn snapshots() -> Result<Vec<Snapshot>> {
+    let dir = snapshot_dir();
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots: Vec<Snapshot> = fs::read_dir(&dir)
+        .with_context(|| format!("Failed to list {}", dir.display()))?
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let created = snapshot_time(&path)?;
+            Some(Snapshot { path, created })
+        })
+        .collect();
+    snapshots.sort_by_key(|snapshot| snapshot.created);
+    Ok(snapshots)
+}
+
+fn snapshot_time(path: &Path) -> Option<DateTime<Utc>> {
+    let name = path.file_name()?.to_str()?;
+    let stamp = name.strip_prefix(PREFIX)?.strip_suffix(".zip")?;
+    NaiveDateTime::parse_from_str(stamp, TIMESTAMP)
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+/// Takes the snapshots at the times of `BACKUP_SCHEDULE`
+#[derive(Clone)]
+pub struct BackupScheduler {
+    pub service: BackupService,
+    pub schedule: CronSchedule,
+    /// Wait until no player is connected, so no session writes to the worlds meanwhile
+    pub when_idle: bool,
+    pub foundry_port: u16,
+}
+
+impl BackupScheduler {
+    pub async fn run(self) {
+        loop {
+            let Some(next) = self.schedule.next_after(Local::now()) else {
+                warn!("⚠️ BACKUP_SCHEDULE never matches, no backups are taken");
+                return;
+            };
+            debug!("Next backup at {}", next);
+            let wait = (next - Local::now()).to_std().unwrap_or_default();
+            sleep(wait).await;
+
+            if self.when_idle && !self.wait_until_idle().await {
+                warn!(
+                    "⚠️ Skipping the backup of {}, players stayed connected",
+                    next
+                );
+                continue;
+            }
+            // A snapshot written to a full disk could leave no room for the worlds to save
+            if diskspace::critical() {
+                warn!(
+                    "⚠️ Skipping the backup of {}, disk space is critically low",
+                    next
+                );
+                continue;
+            }
+            let service = self.service.clone();
+            match jobs::run("backup", move || service.create(BackupTrigger::Schedule)).await {
+                Ok(Ok(summary)) => notify::send(Notification {
+                    severity: Severity::Info,
+                    title: "Backup completed".to_string(),
+                    message: format!(
+                        "{} with {} files, {:.1} MiB, written in {:.1} seconds.",
+                        summary.record.file,
+                        summary.record.files,
+                        summary.record.bytes as f64 / (1024.0 * 1024.0),
+                        summary.record.duration_ms as f64 / 1000.0
+                    ),
+                }),
+                Ok(Err(e)) => notify::send(Notification {
+                    severity: Severity::Critical,
+                    title: "Backup failed".to_string(),
+                    message: format!("{:#}", e),
+                }),
+                Err(e) => warn!("{}", e),
+            }
+        }
+    }
+
+    /// Whether Foundry became idle before the next scheduled backup is due
+    async fn wait_until_idle(&self) -> bool {
+        let next = self.schedule.next_after(Local::now());
+        loop {
+            if !status::current().running {
+                return true;
+            }
+            match status::fetch_server_status(self.foundry_port).await {
+                Ok(server) if server.users > 0 => {
+                    debug!("{} players connected, postponing the backup", server.users)
+                }
+                // Nobody can play while Foundry does not answer
+                _ => return true,
+            }
+            if next.is_some_and(|next| Local::now() + chrono::Duration::minutes(1) >= next) {
+                return false;
+            }
+            sleep(Duration::from_secs(60)).await;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "full"))]
+mod tests {
+    use super::*;
+
+    fn result(at: &str, succeeded: bool) -> Option<BackupResult> {
+        Some(BackupResult {
+            at: at.to_string(),
+            succeeded,
+        })
+    }
+
+    #[test]
+    fn newest_attempt_is_the_last_result() {
+        let snapshot = Some("2026-10-14T02:00:00+00:00".to_string());
+        let before = Some("2026-10-13T02:00:00+00:00".to_string());
+        let after = Some("2026-10-14T04:00:00+02:00".to_string());
+        assert_eq!(pick_last_result(None, None), None);
+        assert_eq!(
+            pick_last_result(snapshot.clone(), None),
+            result("2026-10-14T02:00:00+00:00", true)
+        );
+        assert_eq!(
+            pick_last_result(snapshot.clone(), before),
+            result("2026-10-14T02:00:00+00:00", true)
+        );
+        // Equal instants in other offsets count as the failure coming last
+        assert_eq!(
+            pick_last_result(snapshot, after.clone()),
+            result("2026-10-14T04:00:00+02:00", false)
+        );
+        assert_eq!(
+            pick_last_result(None, after),
+            result("2026-10-14T04:00:00+02:00", false)
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::{Result, anyhow, bail};
+use serde_json::{Map, Value};
+use tracing::{info, warn};
+
+use crate::db::Collection;
+use crate::smoke::SmokeTest;
+
+const MODULE_CONFIGURATION_KEY: &str = "core.moduleConfiguration";
+
+/// Finds the module that breaks loading a world by booting it with halves of the module set
+pub struct ModuleBisector {
+    pub smoke_test: SmokeTest,
+    pub script_path: String,
+}
+
+impl ModuleBisector {
+    /// Bisect the enabled modules of the world, returning the culprit if one was found.
+    ///
+    /// The original module configuration is always written back, even when a step fails.
+    pub async fn run(&self) -> Result<Option<String>> {
+        let world = self.smoke_test.world.clone();
+        let original = read_module_configuration(&world)?;
+        let enabled: Vec<String> = original
+            .iter()
+            .filter(|(_, active)| active.as_bool().unwrap_or(false))
+            .map(|(id, _)| id.clone())
+            .collect();
+        info!(
+            "Bisecting {} enabled modules of world {}",
+            enabled.len(),
+            world
+        );
+
+        let result = self.bisect(&original, enabled).await;
+
+        if let Err(e) = write_module_configuration(&world, &original) {
+            warn!("❌ Failed to restore the module configuration: {}", e);
+            return Err(e);
+        }
+        info!(
+            "Restored the original module configuration of world {}",
+            world
+        );
+        result
+    }
+
+    async fn bisect(
+        &self,
+        original: &Map<String, Value>,
+        enabled: Vec<String>,
+    ) -> Result<Option<String>> {
+        if self.loads_with(original, &enabled).await? {
+            info!("World loads with all modules enabled, nothing to bisect");
+            return Ok(None);
+        }
+        if !self.loads_with(original, &[]).await? {
+            bail!("World fails to load even with every module disabled, modules are not the cause");
+        }
+
+        // The culprit is always within `candidates`, halve it until one module is left
+        let mut candidates = enabled;
+        while candidates.len() > 1 {
+            let second_half = candidates.split_off(candidates.len() / 2);
+            info!(
+                "Testing {} modules, {} remaining candidates",
+                candidates.len(),
+                candidates.len() + second_half.len()
+            );
+            if self.loads_with(original, &candidates).await? {
+                candidates = second_half;
+            }
+        }
+
+        Ok(candidates.pop())
+    }
+
+    /// Boot the world with only `modules` of the originally enabled set active
+    async fn loads_with(&self, original: &Map<String, Value>, modules: &[String]) -> Result<bool> {
+        let mut configuration = original.clone();
+        for (id, active) in configuration.iter_mut() {
+            if active.as_bool().unwrap_or(false) {
+                *active = Value::Bool(modules.contains(id));
+            }
+        }
+        write_module_configuration(&self.smoke_test.world, &configuration)?;
+
+        let outcome = self.smoke_test.run(&self.script_path).await?;
+        info!("  {} modules active: {}", modules.len(), outcome.reason);
+        if let Some(first_error) = outcome.errors.first() {
+            info!("  first error: {}", first_error);
+        }
+        Ok(outcome.passed)
+    }
+}
+
+/// Find the settings document holding the world's module activation map
+fn find_configuration_document(settings: &mut Collection) -> Result<Option<Value>> {
+    Ok(settings
+        .documents()?
+        .into_iter()
+        .find(|doc| doc["key"] == MODULE_CONFIGURATION_KEY))
+}
+
+pub fn read_module_configuration(world: &str) -> Result<Map<String, Value>> {
+    let mut settings = Collection::open(world, "settings")?;
+    let document = find_configuration_document(&mut settings)?
+        .ok_or_else(|| anyhow!("World {} has no module configuration yet", world))?;
+
+    // Setting values are stored as JSON encoded strings
+    let value = document["value"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Module configuration of world {} is not a string", world))?;
+    match serde_json::from_str(value)? {
+        Value::Object(map) => Ok(map),
+        _ => Err(anyhow!(
+            "Module configuration of world {} is not an object",
+            world
+        )),
+    }
+}
+
+pub fn write_module_configuration(world: &str, configuration: &Map<String, Value>) -> Result<()> {
+    // Reopen for every write, Foundry needs the database lock while a smoke test runs
+    let mut settings = Collection::open(world, "settings")?;
+    let mut document = find_configuration_document(&mut settings)?
+        .ok_or_else(|| anyhow!("World {} has no module configuration yet", world))?;
+    document["value"] = Value::String(serde_json::to_string(configuration)?);
+    settings.put(&document)
+}
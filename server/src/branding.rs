@@ -0,0 +1,164 @@
+use anyhow::{Context, Result};
+use serde_json::{Map, Value, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+use crate::db::Collection;
+use crate::permissions::{self, FileKind};
+use crate::scan::ScanService;
+use crate::settings::SettingsService;
+use crate::utils::paths;
+use crate::worlds::WorldService;
+
+/// Directory below `Data` the branding files are copied to
+const TARGET_DIR: &str = "branding";
+
+/// A font file registered in Foundry's font settings of every world
+#[derive(Debug, Clone)]
+pub struct CustomFont {
+    pub family: String,
+    /// Path as Foundry serves it, relative to `Data`, e.g. `branding/Cinzel.woff2`
+    pub path: String,
+    pub weight: u32,
+}
+
+/// Parse `Family=path[@weight]` entries separated by commas
+pub fn parse_fonts(entries: &str) -> Vec<CustomFont> {
+    entries
+        .split(',')
+        .filter_map(|entry| {
+            let (family, file) = entry.trim().split_once('=')?;
+            let (path, weight) = match file.rsplit_once('@') {
+                Some((path, weight)) => (path, weight.trim().parse().ok()?),
+                None => (file, 400),
+            };
+            Some(CustomFont {
+                family: family.trim().to_string(),
+                path: path.trim().trim_start_matches('/').to_string(),
+                weight,
+            })
+        })
+        .filter(|font| !font.family.is_empty() && !font.path.is_empty())
+        .collect()
+}
+
+/// Places the fonts and UI assets of a branded table setup in the data directory before
+/// Foundry starts, so the setup can be reproduced from the container configuration alone
+pub struct Branding {
+    /// Copied to `Data/branding`, e.g. a mounted directory with fonts, logos and backgrounds
+    pub source_dir: Option<PathBuf>,
+    pub fonts: Vec<CustomFont>,
+}
+
+impl Branding {
+    /// Copy the files and register the fonts in every world. Worlds created later get them on
+    /// the next start of the container.
+    pub fn apply(&self) -> Result<()> {
+        let data_dir = PathBuf::from(&*paths::DATA_DIR).join("Data");
+        if let Some(source) = &self.source_dir {
+            let copied = copy_changed(source, &data_dir.join(TARGET_DIR))?;
+            info!(
+                "🎨 Placed {} changed branding files from {} in Data/{}",
+                copied,
+                source.display(),
+                TARGET_DIR
+            );
+        }
+        if self.fonts.is_empty() {
+            return Ok(());
+        }
+
+        for font in &self.fonts {
+            if !data_dir.join(&font.path).is_file() {
+                warn!(
+                    "⚠️ Font file {} of {} does not exist in Data",
+                    font.path, font.family
+                );
+            }
+        }
+        for world in WorldService::ids()? {
+            if !Collection::exists(&world, "settings") {
+                debug!(
+                    "World {} has no settings yet, registering fonts later",
+                    world
+                );
+                continue;
+            }
+            if let Err(e) = self.register_fonts(&world) {
+                warn!(
+                    "Failed to register the custom fonts in world {}: {:#}",
+                    world, e
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the definitions of the configured families in `core.fonts`, keeping fonts
+    /// added in Foundry
+    fn register_fonts(&self, world_id: &str) -> Result<()> {
+        // Foundry stores setting values JSON encoded
+        let current = SettingsService::get(world_id, "core.fonts")?
+            .and_then(|value| value.as_str().and_then(|v| serde_json::from_str(v).ok()))
+            .unwrap_or_else(|| Value::Object(Map::new()));
+        let mut fonts = match current {
+            Value::Object(fonts) => fonts,
+            _ => Map::new(),
+        };
+
+        let before = fonts.clone();
+        for family in self.families() {
+            let files: Vec<Value> = self
+                .fonts
+                .iter()
+                .filter(|font| font.family == family)
+                .map(|font| json!({"urls": [font.path], "weight": font.weight, "style": "normal"}))
+                .collect();
+            fonts.insert(family.to_string(), json!({"editor": true, "fonts": files}));
+        }
+        if fonts == before {
+            return Ok(());
+        }
+        SettingsService::set(
+            world_id,
+            "core.fonts",
+            Value::String(serde_json::to_string(&fonts)?),
+        )?;
+        info!(
+            "🔤 Registered {} custom font families in world {}",
+            self.families().len(),
+            world_id
+        );
+        Ok(())
+    }
+
+    fn families(&self) -> Vec<&str> {
+        let mut families: Vec<&str> = self.fonts.iter().map(|f| f.family.as_str()).collect();
+        families.sort();
+        families.dedup();
+        families
+    }
+}
+
+/// Copy the files of `source` to `target` whose contents differ, returning how many
+fn copy_changed(source: &Path, target: &Path) -> Result<usize> {
+    let mut copied = 0;
+    for entry in ScanService::walk(source, &[])? {
+        let relative = entry.path.strip_prefix(source)?;
+        let destination = target.join(relative);
+        let contents = fs::read(&entry.path)
+            .with_context(|| format!("Failed to read {}", entry.path.display()))?;
+        if fs::read(&destination).is_ok_and(|existing| existing == contents) {
+            continue;
+        }
+        if let Some(parent) = destination.parent() {
+            permissions::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        permissions::write(&destination, contents, FileKind::Regular)
+            .with_context(|| format!("Failed to write {}", destination.display()))?;
+        copied += 1;
+    }
+    Ok(copied)
+}
@@ -0,0 +1,119 @@
+use anyhow::{Result, anyhow};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+use crate::configfile;
+use crate::utils::paths;
+
+/// `OFFLINE=true`: nothing is downloaded, releases and packages install from `CACHE_DIR`
+pub fn offline() -> bool {
+    configfile::settings().offline
+}
+
+/// Fail in offline mode, `what` tells which download was refused
+pub fn ensure_online(what: &str) -> Result<()> {
+    if !offline() {
+        return Ok(());
+    }
+    let hint = match &*paths::CACHE_DIR {
+        Some(dir) => format!("it is not cached in {}", dir.display()),
+        None => "CACHE_DIR is not set".to_string(),
+    };
+    Err(anyhow!(
+        "OFFLINE is set and {}, cannot download {}",
+        hint,
+        what
+    ))
+}
+
+/// Key of an entry below `CACHE_DIR`, e.g. `releases/12.331.zip` for `["releases", "12.331"]`
+/// and `zip`. Parts are reduced to characters safe in file names.
+pub fn key(parts: &[&str], extension: &str) -> String {
+    let parts: Vec<String> = parts
+        .iter()
+        .map(|part| {
+            part.chars()
+                .map(|c| match c {
+                    'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+                    _ => '_',
+                })
+                .collect::<String>()
+                .trim_start_matches('.')
+                .to_string()
+        })
+        .collect();
+    format!("{}.{}", parts.join("/"), extension)
+}
+
+fn entry(key: &str) -> Option<PathBuf> {
+    paths::CACHE_DIR.as_ref().map(|dir| dir.join(key))
+}
+
+/// Put the cached file of `key` at `dest`, answering whether the cache held it
+pub async fn restore(key: &str, dest: &Path) -> Result<bool> {
+    let Some(cached) = entry(key).filter(|path| path.is_file()) else {
+        return Ok(false);
+    };
+    let _ = tokio::fs::remove_file(dest).await;
+    // A link costs nothing when both are on one volume
+    if tokio::fs::hard_link(&cached, dest).await.is_err() {
+        tokio::fs::copy(&cached, dest).await?;
+    }
+    info!("📦 Using {} from the download cache", key);
+    Ok(true)
+}
+
+/// Keep `src` as the entry of `key`. The cache only saves downloads, failures are logged.
+pub async fn store(key: &str, src: &Path) {
+    let Some(cached) = entry(key) else {
+        return;
+    };
+    // Written next to the entry and renamed, an interrupted copy is never used
+    let partial = cached.with_extension("partial");
+    let stored = async {
+        if let Some(parent) = cached.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let _ = tokio::fs::remove_file(&partial).await;
+        if tokio::fs::hard_link(src, &partial).await.is_err() {
+            tokio::fs::copy(src, &partial).await?;
+        }
+        tokio::fs::rename(&partial, &cached).await
+    }
+    .await;
+    match stored {
+        Ok(()) => debug!("Cached {} as {}", src.display(), key),
+        Err(e) => warn!("Failed to cache {} as {}: {}", src.display(), key, e),
+    }
+}
+
+/// Drop the entry of `key`, e.g. once its archive turned out to be broken
+pub async fn evict(key: &str) {
+    if let Some(cached) = entry(key)
+        && tokio::fs::remove_file(&cached).await.is_ok()
+    {
+        warn!("⚠️ Removed {} from the download cache", key);
+    }
+}
+
+/// Contents of a small entry, e.g. a package manifest
+pub fn read(key: &str) -> Option<Vec<u8>> {
+    fs::read(entry(key)?).ok()
+}
+
+/// Keep `contents` as the entry of `key`, failures are logged
+pub fn write(key: &str, contents: &[u8]) {
+    let Some(cached) = entry(key) else {
+        return;
+    };
+    let partial = cached.with_extension("partial");
+    let written = cached
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&partial, contents))
+        .and_then(|()| fs::rename(&partial, &cached));
+    if let Err(e) = written {
+        warn!("Failed to cache {}: {}", key, e);
+    }
+}
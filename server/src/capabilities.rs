@@ -0,0 +1,132 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::permissions::{self, FileKind};
+use crate::utils::paths;
+
+/// How far an optional feature that was configured got
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CapabilityState {
+    /// Set up, but not used yet, e.g. no backup was uploaded since the start
+    Configured,
+    /// The last attempt worked
+    Active,
+    /// Set up wrong, or the last attempt failed, see the reason
+    Failed,
+}
+
+impl CapabilityState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Configured => "configured",
+            Self::Active => "active",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// An optional feature that was configured
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Capability {
+    pub state: CapabilityState,
+    /// Why a failed feature is not working
+    pub reason: Option<String>,
+    /// When the state last changed
+    #[schema(format = DateTime)]
+    pub since: String,
+}
+
+/// Optional features of this run by name, `s3-backup`, `tls`, `email`, ... Only what was
+/// configured is listed, so a feature that is missing here was not set up at all.
+static REGISTRY: Mutex<BTreeMap<&'static str, Capability>> = Mutex::new(BTreeMap::new());
+
+/// Only the wrapper records its capabilities, a management command uploading a backup must
+/// not replace them
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+/// Record the capabilities of this run for `doctor`, called once at startup
+pub fn init() {
+    RECORDING.store(true, Ordering::Relaxed);
+    // Replaces what an earlier run recorded
+    persist(&REGISTRY.lock().unwrap_or_else(PoisonError::into_inner));
+}
+
+/// `name` is set up and waits for its first use
+pub fn configured(name: &'static str) {
+    set(name, CapabilityState::Configured, None);
+}
+
+/// `name` just worked
+pub fn active(name: &'static str) {
+    set(name, CapabilityState::Active, None);
+}
+
+/// `name` does not work, `reason` tells the user why
+pub fn failed(name: &'static str, reason: impl Display) {
+    set(name, CapabilityState::Failed, Some(format!("{:#}", reason)));
+}
+
+/// Every capability of this run by name
+pub fn all() -> BTreeMap<String, Capability> {
+    REGISTRY
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .map(|(name, capability)| (name.to_string(), capability.clone()))
+        .collect()
+}
+
+/// The capabilities the running wrapper recorded, for commands running in a process of their
+/// own like `doctor`
+pub fn recorded() -> Option<BTreeMap<String, Capability>> {
+    let contents = fs::read_to_string(path()).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn set(name: &'static str, state: CapabilityState, reason: Option<String>) {
+    let mut registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+    if registry
+        .get(name)
+        .is_some_and(|known| known.state == state && known.reason == reason)
+    {
+        return;
+    }
+    debug!("Capability {} is {}", name, state.as_str());
+    registry.insert(
+        name,
+        Capability {
+            state,
+            reason,
+            since: Utc::now().to_rfc3339(),
+        },
+    );
+    if RECORDING.load(Ordering::Relaxed) {
+        persist(&registry);
+    }
+}
+
+fn persist(registry: &BTreeMap<&'static str, Capability>) {
+    let written = permissions::create_dir_all(&paths::WRAPPER_STATE_DIR).and_then(|()| {
+        permissions::write(
+            &path(),
+            serde_json::to_string_pretty(registry).unwrap_or_default(),
+            FileKind::Regular,
+        )
+    });
+    if let Err(e) = written {
+        debug!("Failed to record the capabilities: {}", e);
+    }
+}
+
+fn path() -> PathBuf {
+    paths::WRAPPER_STATE_DIR.join("capabilities.json")
+}
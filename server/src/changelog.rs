@@ -0,0 +1,220 @@
+use actix_web::{HttpResponse, Responder};
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::PathBuf;
+use tracing::{debug, info, warn};
+
+use crate::permissions::{self, FileKind};
+use crate::resilience::{self, Service};
+use crate::utils::{installed_foundry_version, paths};
+
+/// Release notes of installed Foundry versions, kept in `DATA_DIR/.wrapper/changelog`
+pub struct Changelog;
+
+impl Changelog {
+    /// Fetch the release notes of the installed version unless they are stored already.
+    ///
+    /// `url` may contain `{version}`. Failures are only logged, an air-gapped server simply
+    /// starts without release notes and tries again on the next boot.
+    pub async fn refresh(url: &str) {
+        let Some(version) = installed_foundry_version() else {
+            return;
+        };
+        if Self::path(&version).exists() {
+            return;
+        }
+
+        match Self::fetch(&url.replace("{version}", &version)).await {
+            Ok(notes) => match Self::store(&version, &notes) {
+                Ok(()) => info!(
+                    "📰 Release notes of Foundry {} saved, show them with `foundry-watcher changelog`",
+                    version
+                ),
+                Err(e) => warn!("Failed to save the release notes of {}: {}", version, e),
+            },
+            Err(e) => warn!(
+                "Could not fetch the release notes of Foundry {}: {}",
+                version, e
+            ),
+        }
+    }
+
+    /// Stored release notes of `version`, the installed version by default
+    pub fn read(version: Option<&str>) -> Result<(String, String)> {
+        let version = match version {
+            Some(version) => version.to_string(),
+            None => installed_foundry_version().context("No Foundry release is installed")?,
+        };
+        let path = Self::path(&version);
+        let notes = fs::read_to_string(&path).with_context(|| {
+            format!(
+                "No release notes stored for Foundry {}, they are fetched when it starts",
+                version
+            )
+        })?;
+        Ok((version, notes))
+    }
+
+    /// Versions with stored release notes
+    pub fn versions() -> Vec<String> {
+        let Ok(entries) = fs::read_dir(Self::dir()) else {
+            return Vec::new();
+        };
+        let mut versions: Vec<String> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                name.strip_suffix(".md").map(|v| v.to_string())
+            })
+            .collect();
+        versions.sort();
+        versions
+    }
+
+    async fn fetch(url: &str) -> Result<String> {
+        debug!("Fetching release notes from {}", url);
+        let client = reqwest::Client::new();
+        let html = resilience::call(Service::ReleaseNotes, || async {
+            Ok::<_, anyhow::Error>(
+                client
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?,
+            )
+        })
+        .await?;
+        let notes = html_to_text(release_section(&html));
+        if notes.is_empty() {
+            anyhow::bail!("{} contains no release notes", url);
+        }
+        Ok(notes)
+    }
+
+    fn store(version: &str, notes: &str) -> Result<()> {
+        permissions::create_dir_all(&Self::dir())?;
+        let path = Self::path(version);
+        let tmp_path = path.with_extension("md.tmp");
+        permissions::write(&tmp_path, notes, FileKind::Regular)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    fn dir() -> PathBuf {
+        paths::WRAPPER_STATE_DIR.join("changelog")
+    }
+
+    fn path(version: &str) -> PathBuf {
+        Self::dir().join(format!("{}.md", version))
+    }
+}
+
+/// Release notes of the installed Foundry version
+#[utoipa::path(
+    get,
+    path = "/changelog",
+    operation_id = "changelog",
+    responses(
+        (status = 200, description = "Release notes as plain text", body = String, content_type = "text/plain"),
+        (status = 404, description = "No release notes are stored for the installed version", body = String, content_type = "text/plain"),
+    )
+)]
+pub async fn show() -> impl Responder {
+    match Changelog::read(None) {
+        Ok((version, notes)) => HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .body(format!("Foundry VTT {}\n\n{}\n", version, notes)),
+        Err(e) => HttpResponse::NotFound().body(e.to_string()),
+    }
+}
+
+/// The release page's article, or its body when the page has no article element
+fn release_section(html: &str) -> &str {
+    for (open, close) in [
+        ("<article", "</article>"),
+        ("<main", "</main>"),
+        ("<body", "</body>"),
+    ] {
+        if let Some(start) = html.find(open)
+            && let Some(end) = html[start..].find(close)
+        {
+            return &html[start..start + end];
+        }
+    }
+    html
+}
+
+/// Plain text with Markdown style headings and list items, good enough for a terminal
+fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = rest[start + 1..start + end].to_ascii_lowercase();
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default();
+        rest = &rest[start + end + 1..];
+
+        // Skip scripts and styles including their content
+        if !tag.starts_with('/') && (name == "script" || name == "style") {
+            let close = format!("</{}", name);
+            rest = rest
+                .find(&close)
+                .map(|i| &rest[i..])
+                .and_then(|r| r.find('>').map(|i| &r[i + 1..]))
+                .unwrap_or_default();
+            continue;
+        }
+        match (tag.starts_with('/'), name) {
+            (false, "h1" | "h2" | "h3" | "h4") => text.push_str("\n\n## "),
+            (false, "li") => text.push_str("\n- "),
+            (_, "p" | "div" | "ul" | "ol" | "h1" | "h2" | "h3" | "h4") => text.push_str("\n\n"),
+            (_, "br") => text.push('\n'),
+            _ => {}
+        }
+    }
+
+    let text = text
+        .replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ");
+
+    // Collapse the whitespace of the markup, keeping single blank lines between blocks
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        match lines.last_mut() {
+            // Markup often puts the heading or item text on a line of its own
+            Some(last) if (last == "##" || last == "-") && !line.is_empty() => {
+                last.push(' ');
+                last.push_str(&line);
+            }
+            Some(last) if line.is_empty() && (last.is_empty() || last == "##" || last == "-") => {}
+            None if line.is_empty() => {}
+            _ => {
+                // List items stay together
+                let n = lines.len();
+                if line.starts_with('-')
+                    && n >= 2
+                    && lines[n - 1].is_empty()
+                    && lines[n - 2].starts_with("- ")
+                {
+                    lines.pop();
+                }
+                lines.push(line);
+            }
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
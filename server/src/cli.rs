@@ -0,0 +1,1446 @@
+use anyhow::{Context, Result, anyhow};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::archive::{self, ArchiveService};
+use crate::assetgc::AssetCollector;
+use crate::backup::{self, BackupService, BackupTrigger};
+use crate::bisect::ModuleBisector;
+use crate::changelog::Changelog;
+use crate::completions::{self, Shell};
+use crate::config::AppConfig;
+use crate::doctor::Doctor;
+use crate::dump::{self, DumpFormat, DumpService};
+use crate::export::ExportService;
+use crate::gamesocket::GameClient;
+use crate::hostfs;
+#[cfg(feature = "full")]
+use crate::hostmigrate::HostPush;
+use crate::hostmigrate::{self, HostVerify};
+use crate::i18n;
+use crate::inspector::Inspector;
+use crate::jobstore::JobStore;
+use crate::journal;
+use crate::logs;
+use crate::migrate::{AssetMigration, S3Target};
+use crate::options;
+use crate::packages;
+use crate::permissions::{self, FileKind};
+use crate::recording::Replay;
+#[cfg(feature = "full")]
+use crate::replicate::Replication;
+use crate::report::{CampaignReport, ReportFormat};
+use crate::restore::{self, RestoreOptions, RestoreService};
+use crate::restoretest::RestoreTest;
+use crate::rewrite::PathRewrite;
+#[cfg(feature = "full")]
+use crate::secrets;
+use crate::selftest;
+use crate::settings::{SettingEntry, SettingsService};
+use crate::smoke::SmokeTest;
+use crate::standby;
+use crate::state::WrapperState;
+use crate::statesync::StateSync;
+use crate::status;
+use crate::transfer::WorldTransfer;
+use crate::troubleshoot::{Problem, Troubleshooting};
+use crate::updater::Updater;
+use crate::utils::{installed_foundry_version, paths};
+use crate::worlds::{self, WorldService};
+
+/// Launcher and management tool for the Foundry VTT container.
+///
+/// Without a subcommand the installer and Foundry itself are started.
+#[derive(Parser)]
+#[command(name = "foundry-watcher", version, about)]
+pub struct Cli {
+    /// Output of the management commands, `json` for scripts. Only the commands listing or
+    /// checking something support `json`, their fields are only ever added to.
+    #[arg(long, global = true, value_enum, default_value_t = Output::Text)]
+    pub output: Output,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    Text,
+    Json,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Manage worlds in the data directory
+    World {
+        #[command(subcommand)]
+        action: WorldCommand,
+    },
+    /// Control the game of the active world as `GAME_USER`, e.g. when the Gamemaster's browser
+    /// is gone mid-session
+    Game {
+        #[command(subcommand)]
+        action: GameCommand,
+    },
+    /// Transfer module settings between worlds or instances.
+    ///
+    /// Stop the running server first, the world database can only be opened by one process.
+    Settings {
+        #[command(subcommand)]
+        action: SettingsCommand,
+    },
+    /// Export the actors and journals of a world to Markdown and JSON under `BACKUP_DIR/exports`
+    Export {
+        /// Id of the world (its directory name)
+        world: String,
+    },
+    /// Zip snapshots of the data directory in `BACKUP_DIR/snapshots`
+    Backup {
+        #[command(subcommand)]
+        action: BackupCommand,
+    },
+    /// Read world documents for analytics and custom tooling
+    Db {
+        #[command(subcommand)]
+        action: DbCommand,
+    },
+    /// Move user assets between storages and clean up those no world uses
+    Assets {
+        #[command(subcommand)]
+        action: AssetsCommand,
+    },
+    /// Fix asset paths in world documents
+    Paths {
+        #[command(subcommand)]
+        action: PathsCommand,
+    },
+    /// Capture diagnostics of the running Foundry process under `DATA_DIR/diagnostics`
+    Diagnostics {
+        #[command(subcommand)]
+        action: DiagnosticsCommand,
+    },
+    /// Find the module that breaks loading a world by booting it with halves of its modules.
+    ///
+    /// Stop the running server first, the world database can only be opened by one process.
+    BisectModules {
+        /// Id of the world (its directory name)
+        #[arg(long)]
+        world: String,
+        /// Port for the headless Foundry instances
+        #[arg(long, default_value_t = 30001)]
+        port: u16,
+        /// Seconds to wait for the world to load in each round
+        #[arg(long, default_value_t = 180)]
+        timeout: u64,
+    },
+    /// Show the installed Foundry version, the active world and the wrapper's state, without
+    /// the admin API
+    Status,
+    /// List the installed core, systems and modules with their versions
+    Packages,
+    /// List the interrupted transfers continued on the next start and the temporary files
+    /// left for cleanup
+    Jobs,
+    /// Check the container setup and explain how to fix problems, e.g. unwritable volumes
+    Doctor,
+    /// Exercise the installer, supervisor, config renderer and backup against throwaway fixtures,
+    /// e.g. to verify a custom build. The volumes are not touched.
+    SelfTest,
+    /// Walk through a common failure, can't reach the server, a world won't load or permission
+    /// denied, and print a summary to paste into an issue
+    Troubleshoot {
+        /// Skip the question what is going wrong
+        #[arg(long, value_enum)]
+        problem: Option<Problem>,
+        /// World that does not load, asked for when there are several
+        #[arg(long)]
+        world: Option<String>,
+    },
+    /// Review changes of `FOUNDRY_*` variables to `Config/options.json`
+    Options {
+        #[command(subcommand)]
+        action: OptionsCommand,
+    },
+    /// Show the release notes of the installed Foundry version, fetched when it first started
+    Changelog {
+        /// Show the notes of another stored version instead
+        version: Option<String>,
+        /// List the versions with stored release notes
+        #[arg(long)]
+        list: bool,
+    },
+    /// Install the newest Foundry release of `AUTO_UPDATE_CHANNEL` next to the installed one and
+    /// switch to it once it booted. Restart the container afterwards.
+    Update {
+        /// Switch to this version instead, e.g. back to the previous one
+        #[arg(long)]
+        version: Option<String>,
+    },
+    /// Replay the messages a player sent in a recording of `PROXY_RECORD_DIR` against a fresh
+    /// instance, printing what Foundry answers
+    Replay {
+        /// Recording written by the proxy
+        recording: PathBuf,
+        /// Websocket URL of the instance
+        #[arg(long, default_value = "ws://localhost:30000")]
+        url: String,
+        /// Session cookie of a user on the instance, replaces the recorded session
+        #[arg(long)]
+        session: Option<String>,
+    },
+    /// Take over the bucket of stateless mode: wake a waiting standby, or let an instance a
+    /// standby replaced sync again. The previous holder stops uploading.
+    Promote,
+    /// Push the changes of the data directory to the admin API of another instance, e.g. to
+    /// move to a new host or keep a copy at home. An interrupted run continues where it stopped.
+    #[cfg(feature = "full")]
+    Sync {
+        /// Admin API of the other instance, e.g. `https://home.example.com:8081`
+        #[arg(long)]
+        to: String,
+        /// ADMIN_API_TOKEN of the other instance, defaults to SYNC_TOKEN
+        #[arg(long)]
+        token: Option<String>,
+        /// Also delete files the other instance has and this one does not
+        #[arg(long)]
+        delete: bool,
+        /// Only list what would be sent and deleted
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Move this installation to a new host: pack or push the data directory, the wrapper state
+    /// and what the old host looked like, then verify the result on the new host
+    MigrateHost {
+        #[command(subcommand)]
+        action: MigrateHostCommand,
+    },
+    /// Print the completion script of a shell, the image installs them already
+    Completions { shell: Shell },
+    /// Write man pages for the launcher and each subcommand
+    Man {
+        /// Directory the pages are written to
+        #[arg(long, default_value = ".")]
+        out_dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum MigrateHostCommand {
+    /// Write `Config`, `Data` and the wrapper state to one archive in `BACKUP_DIR/migrations`,
+    /// to copy to the new host. Stop the running server first for consistent world databases.
+    Pack {
+        /// File to write instead
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Push the data directory and the wrapper state to the admin API of the new instance,
+    /// which stays on its setup screen meanwhile. An interrupted run continues where it stopped.
+    #[cfg(feature = "full")]
+    Push {
+        /// Admin API of the new instance, e.g. `https://new-host.example.com:8081`
+        #[arg(long)]
+        to: String,
+        /// ADMIN_API_TOKEN of the new instance, defaults to SYNC_TOKEN
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Replace `Config` and `Data` with those of an archive written by `pack`
+    Unpack {
+        archive: PathBuf,
+        /// Replace worlds that are in the data directory already
+        #[arg(long)]
+        force: bool,
+    },
+    /// Compare this host with the old one and boot each world on `RESTORE_TEST_PORT`.
+    ///
+    /// Foundry must not run meanwhile, e.g. verify from a one-off container on the volumes.
+    Verify,
+}
+
+#[derive(Subcommand)]
+pub enum BackupCommand {
+    /// Take a snapshot now and prune old ones by `BACKUP_KEEP_LAST` and `BACKUP_KEEP_DAYS`
+    Create,
+    /// List the snapshots with their worlds and the Foundry version they were taken with
+    List {
+        /// Only snapshots containing this world
+        #[arg(long)]
+        world: Option<String>,
+        /// Print the catalog as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Replace `Config` and `Data` with a snapshot, after taking one of the current data.
+    ///
+    /// Return to the setup screen first and restart the container afterwards. Snapshots taken
+    /// with a newer Foundry than the installed one are refused. `POST /api/restore` of the
+    /// admin API stops and starts Foundry on its own.
+    Restore {
+        /// File name of the snapshot, as `backup list` shows it
+        #[arg(required_unless_present = "at")]
+        file: Option<String>,
+        /// Restore the newest snapshot taken at or before this RFC 3339 time, or this long ago,
+        /// e.g. `2h` or `1d`
+        #[arg(long, conflicts_with = "file")]
+        at: Option<String>,
+        /// Only restore this world, Foundry may stay on the setup screen or in another world
+        #[arg(long)]
+        world: Option<String>,
+        /// Install the Foundry version of the snapshot first, needs FOUNDRY_USERNAME and
+        /// FOUNDRY_PASSWORD
+        #[arg(long)]
+        install_core: bool,
+        /// Restore a snapshot of a newer Foundry anyway
+        #[arg(long)]
+        force: bool,
+        /// Do not snapshot the current data first
+        #[arg(long)]
+        no_backup: bool,
+        /// Set up the environment the snapshot was taken with first: its exact Foundry version,
+        /// and its systems and modules reinstalled where their files differ
+        #[arg(long)]
+        reproduce: bool,
+    },
+    /// Restore a snapshot into a throwaway data directory and boot each of its worlds on
+    /// `RESTORE_TEST_PORT`, like `RESTORE_TEST_SCHEDULE` does
+    Test {
+        /// File name of the snapshot, defaults to the newest one
+        file: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum WorldCommand {
+    /// List the worlds of the data directory
+    List {
+        /// Print the worlds as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Zip a world with `world.json` at the top, to import it in another container or install
+    /// it in Foundry. Return to the setup screen first if it is active.
+    Export {
+        /// Id of the world (its directory name)
+        world: String,
+        /// File to write, defaults to `BACKUP_DIR/exports/<id>-<UTC time>.zip`
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Put the world of a zip into the data directory, after checking its `world.json`
+    Import {
+        /// Zip of the world, as `world export` or Foundry's own world packages make them
+        archive: PathBuf,
+        /// Import the world under this id instead of the one in its `world.json`
+        #[arg(long)]
+        id: Option<String>,
+        /// Replace a world with the same id
+        #[arg(long)]
+        force: bool,
+    },
+    /// Copy a world to a new id, e.g. to try a new module on a copy of the campaign
+    Duplicate {
+        /// Id of the world (its directory name)
+        world: String,
+        /// Id of the copy
+        target: String,
+        /// Title of the copy, defaults to the title of the world with ` (copy)` added
+        #[arg(long)]
+        title: Option<String>,
+    },
+    /// Lock a world for maintenance, showing a notice on its join screen
+    Lock {
+        /// Id of the world (its directory name)
+        world: String,
+        /// Notice shown to players while the world is locked, defaults to a notice in
+        /// `WRAPPER_LANGUAGE`
+        #[arg(long)]
+        message: Option<String>,
+    },
+    /// Lift a maintenance lock and restore the world description
+    Unlock {
+        /// Id of the world (its directory name)
+        world: String,
+    },
+    /// List all locked worlds
+    Locks,
+    /// Move a finished world out of the data directory into `BACKUP_DIR/archives`, or into
+    /// `BACKUP_S3_BUCKET` when it is set. Return to the setup screen first if it is active.
+    Archive {
+        /// Id of the world (its directory name)
+        world: String,
+    },
+    /// Put an archived world back into the data directory
+    Unarchive {
+        /// Id of the world (its directory name)
+        world: String,
+    },
+    /// List the archived worlds
+    Archives {
+        /// Print the catalog as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum GameCommand {
+    /// Pause the game for all players
+    Pause,
+    /// Unpause the game
+    Unpause,
+    /// Post a message to the chat of the world
+    Broadcast { message: String },
+}
+
+#[derive(Subcommand)]
+pub enum OptionsCommand {
+    /// Show the changes proposed with `CONFIG_APPLY=manual` as a diff
+    Diff,
+    /// Write the proposed changes to `options.json`, Foundry reads them on its next start
+    Apply,
+}
+
+#[derive(Subcommand)]
+pub enum SettingsCommand {
+    /// Export the module settings of a world as JSON
+    Export {
+        /// Id of the world (its directory name)
+        world: String,
+        /// Only export settings of this module, can be repeated
+        #[arg(long = "module")]
+        modules: Vec<String>,
+        /// File to write to instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Import module settings exported from another world
+    Import {
+        /// Id of the world (its directory name)
+        world: String,
+        /// File created by `settings export`
+        #[arg(long)]
+        input: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DbCommand {
+    /// Dump the documents of a world, with their embedded documents inlined.
+    ///
+    /// Stop the running server first, the world database can only be opened by one process.
+    Dump {
+        /// Id of the world (its directory name)
+        #[arg(long)]
+        world: String,
+        #[arg(long, value_enum, default_value_t = DumpFormat::Jsonl)]
+        format: DumpFormat,
+        /// Collection to dump, can be repeated, defaults to actors, items, scenes and messages
+        #[arg(long = "collection")]
+        collections: Vec<String>,
+        /// File to write to instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Campaign statistics from the chat log: sessions, dice rolls and the most active players.
+    ///
+    /// Stop the running server first, the world database can only be opened by one process.
+    Report {
+        /// Id of the world (its directory name)
+        #[arg(long)]
+        world: String,
+        #[arg(long, value_enum, default_value_t = ReportFormat::Markdown)]
+        format: ReportFormat,
+        /// File to write to instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AssetsCommand {
+    /// Upload the files of `Data` outside worlds, systems and modules to the S3 bucket of
+    /// Foundry's `awsConfig` and point the world documents at the bucket.
+    ///
+    /// Stop the running server and take a backup first, the world databases are rewritten in
+    /// place. Local files are kept.
+    MigrateToS3 {
+        /// Bucket of the S3 configuration, defaults to its first one
+        #[arg(long)]
+        bucket: Option<String>,
+        /// Public URL of the bucket, defaults to the AWS or endpoint URL of the bucket
+        #[arg(long)]
+        base_url: Option<String>,
+        /// World to rewrite, can be repeated, defaults to all worlds
+        #[arg(long = "world")]
+        worlds: Vec<String>,
+        /// Rewritten links to request from the bucket afterwards
+        #[arg(long, default_value_t = 20)]
+        verify: usize,
+    },
+    /// Download the objects of the S3 bucket the world documents link to into `Data/assets`
+    /// and point the documents at the local copies, the inverse of `migrate-to-s3`.
+    ///
+    /// Stop the running server and take a backup first, the world databases are rewritten in
+    /// place. The bucket is left as is.
+    MigrateToLocal {
+        /// Bucket of the S3 configuration, defaults to its first one
+        #[arg(long)]
+        bucket: Option<String>,
+        /// Public URL of the bucket the documents link to, defaults to the AWS or endpoint URL
+        /// of the bucket
+        #[arg(long)]
+        base_url: Option<String>,
+        /// World to rewrite, can be repeated, defaults to all worlds
+        #[arg(long = "world")]
+        worlds: Vec<String>,
+    },
+    /// List the files of `Data` outside worlds, systems and modules that no world document,
+    /// compendium pack or world manifest refers to.
+    ///
+    /// With `--collect` they are moved to `BACKUP_DIR/asset-gc/<UTC time>/`, delete that
+    /// folder once the worlds look right. Files matching `SCAN_EXCLUDE` are kept.
+    Gc {
+        /// Move the unreferenced files to the quarantine folder
+        #[arg(long)]
+        collect: bool,
+        /// Keep files modified within this many hours, they may belong to unsaved documents
+        #[arg(long, default_value_t = 24)]
+        min_age_hours: u64,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum PathsCommand {
+    /// Replace the start of asset paths in world documents, e.g. after moving `assets/old/` to
+    /// `assets/new/`. Whole paths like token images and `src` attributes in HTML are replaced.
+    ///
+    /// Stop the running server first, the world databases are rewritten in place. They are
+    /// copied to `BACKUP_DIR/path-rewrites` before.
+    Rewrite {
+        /// Prefix of the paths to replace
+        #[arg(long)]
+        from: String,
+        /// Prefix to replace it with
+        #[arg(long)]
+        to: String,
+        /// World to rewrite, can be repeated, defaults to all worlds
+        #[arg(long = "world")]
+        worlds: Vec<String>,
+        /// Only show what would be replaced
+        #[arg(long)]
+        dry_run: bool,
+        /// Skip copying the databases to `BACKUP_DIR` first
+        #[arg(long)]
+        no_backup: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DiagnosticsCommand {
+    /// Write a heap snapshot, Foundry pauses while it is taken
+    HeapSnapshot,
+    /// Record a CPU profile
+    CpuProfile {
+        /// Seconds to record for
+        #[arg(long, default_value_t = 30)]
+        seconds: u64,
+    },
+}
+
+/// Commands with a `--output json` form
+fn supports_json(command: &Command) -> bool {
+    matches!(
+        command,
+        Command::Status
+            | Command::Packages
+            | Command::Jobs
+            | Command::Doctor
+            | Command::Backup {
+                action: BackupCommand::List { .. }
+            }
+            | Command::World {
+                action: WorldCommand::List { .. }
+                    | WorldCommand::Locks
+                    | WorldCommand::Archives { .. }
+            }
+    )
+}
+
+/// Run a management subcommand to completion
+pub async fn run(command: Command, output: Output) -> Result<()> {
+    let json_output = output == Output::Json;
+    if json_output && !supports_json(&command) {
+        return Err(anyhow!(
+            "--output json is supported by status, packages, jobs, doctor, backup list, world list, world locks and world archives"
+        ));
+    }
+    match command {
+        Command::World { action } => match action {
+            WorldCommand::List { json } => {
+                let worlds = WorldService::list()?;
+                if json || json_output {
+                    println!("{}", serde_json::to_string_pretty(&worlds)?);
+                    return Ok(());
+                }
+                if worlds.is_empty() {
+                    println!("No worlds in {}", paths::WORLDS_DIR.display());
+                }
+                for world in &worlds {
+                    println!(
+                        "{}\t{}\t{}\tFoundry {}\t{:.1} MB{}",
+                        world.id,
+                        world.title,
+                        world.system.as_deref().unwrap_or("unknown"),
+                        world.core_version.as_deref().unwrap_or("unknown"),
+                        world.bytes as f64 / 1_048_576.0,
+                        if world.locked { "\tlocked" } else { "" }
+                    );
+                }
+            }
+            WorldCommand::Export { world, output } => {
+                let exported = WorldTransfer::from_config(&AppConfig::from_env())
+                    .export(&world, output)
+                    .await?;
+                println!(
+                    "Exported world {} ({} files, {:.1} MB) to {}",
+                    world,
+                    exported.files,
+                    exported.bytes as f64 / 1_048_576.0,
+                    exported.path.display()
+                );
+            }
+            WorldCommand::Import { archive, id, force } => {
+                let imported = WorldTransfer::from_config(&AppConfig::from_env())
+                    .import(&archive, id.as_deref(), force)
+                    .await?;
+                println!(
+                    "{} world {} ({})",
+                    if imported.replaced {
+                        "Replaced"
+                    } else {
+                        "Imported"
+                    },
+                    imported.id,
+                    imported.title
+                );
+            }
+            WorldCommand::Duplicate {
+                world,
+                target,
+                title,
+            } => {
+                worlds::validate_id(&target)?;
+                let config = AppConfig::from_env();
+                worlds::refuse_active(config.server_port, &world, "duplicating it").await?;
+                let title = match title {
+                    Some(title) => title,
+                    None => {
+                        let summary = WorldService::list()?
+                            .into_iter()
+                            .find(|summary| summary.id == world)
+                            .ok_or_else(|| anyhow!("World {} not found", world))?;
+                        format!("{} (copy)", summary.title)
+                    }
+                };
+                WorldService::duplicate(&world, &target, &title)?;
+                println!("Duplicated world {} to {} ({})", world, target, title);
+            }
+            WorldCommand::Lock { world, message } => {
+                let message = message.unwrap_or_else(worlds::default_lock_message);
+                WorldService::lock(&world, &message)?;
+                println!(
+                    "Locked world {}, restart the world for players to see it",
+                    world
+                );
+                if AppConfig::from_env().proxy_port.is_none() {
+                    println!(
+                        "Only joins through the proxy are refused, set PROXY_PORT and publish it instead of Foundry's port"
+                    );
+                }
+            }
+            WorldCommand::Unlock { world } => {
+                WorldService::unlock(&world)?;
+                println!("Unlocked world {}", world);
+            }
+            WorldCommand::Locks => {
+                let locks = WorldService::locks()?;
+                if json_output {
+                    let locks: BTreeMap<_, _> = locks.into_iter().collect();
+                    println!("{}", serde_json::to_string_pretty(&locks)?);
+                    return Ok(());
+                }
+                if locks.is_empty() {
+                    println!("No worlds are locked");
+                }
+                for (world, lock) in locks {
+                    println!(
+                        "{}\tlocked since {}\t{}",
+                        world, lock.locked_at, lock.message
+                    );
+                }
+            }
+            WorldCommand::Archive { world } => {
+                let record = ArchiveService::from_config(&AppConfig::from_env())
+                    .archive(&world)
+                    .await?;
+                println!(
+                    "Archived world {} ({} files, {:.1} MB) to {}",
+                    world,
+                    record.files,
+                    record.bytes as f64 / 1_048_576.0,
+                    record.remote.as_deref().unwrap_or(&record.file)
+                );
+            }
+            WorldCommand::Unarchive { world } => {
+                ArchiveService::from_config(&AppConfig::from_env())
+                    .unarchive(&world)
+                    .await?;
+                println!("Unarchived world {}", world);
+            }
+            WorldCommand::Archives { json } => {
+                let catalog = archive::catalog()?;
+                if json || json_output {
+                    println!("{}", serde_json::to_string_pretty(&catalog)?);
+                    return Ok(());
+                }
+                if catalog.is_empty() {
+                    println!("No archived worlds");
+                }
+                for (world, record) in &catalog {
+                    println!(
+                        "{}\t{}\t{}\t{:.1} MB\tFoundry {}\t{}",
+                        world,
+                        record.title,
+                        record.archived,
+                        record.bytes as f64 / 1_048_576.0,
+                        record.core_version.as_deref().unwrap_or("unknown"),
+                        record.remote.as_deref().unwrap_or(&record.file)
+                    );
+                }
+            }
+        },
+        Command::Game { action } => {
+            let client = GameClient::from_config(&AppConfig::from_env());
+            if client.user.is_none() {
+                return Err(anyhow!("Set GAME_USER, a Gamemaster of the world"));
+            }
+            match action {
+                GameCommand::Pause => {
+                    client.set_paused(true).await?;
+                    println!("Paused the game");
+                }
+                GameCommand::Unpause => {
+                    client.set_paused(false).await?;
+                    println!("Unpaused the game");
+                }
+                GameCommand::Broadcast { message } => {
+                    if message.trim().is_empty() {
+                        return Err(anyhow!("The message is empty"));
+                    }
+                    client.announce(message.trim()).await?;
+                    println!("Posted the message to the chat");
+                }
+            }
+        }
+        Command::Settings { action } => match action {
+            SettingsCommand::Export {
+                world,
+                modules,
+                output,
+            } => {
+                let entries = SettingsService::export(&world, &modules)?;
+                let json = serde_json::to_string_pretty(&entries)?;
+                match output {
+                    Some(path) => {
+                        // Module settings often hold API keys
+                        permissions::write(&path, json, FileKind::Secret)
+                            .with_context(|| format!("Failed to write {}", path.display()))?;
+                        println!("Exported {} settings to {}", entries.len(), path.display());
+                    }
+                    None => println!("{}", json),
+                }
+            }
+            SettingsCommand::Import { world, input } => {
+                let contents = fs::read_to_string(&input)
+                    .map(|c| hostfs::normalize_text(&c))
+                    .with_context(|| format!("Failed to read {}", input.display()))?;
+                let entries: Vec<SettingEntry> = serde_json::from_str(&contents)
+                    .with_context(|| format!("Invalid settings export {}", input.display()))?;
+                let count = SettingsService::import(&world, &entries)?;
+                println!("Imported {} settings into world {}", count, world);
+            }
+        },
+        Command::Export { world } => {
+            let target = ExportService::export_world(&world)?;
+            println!("Exported world {} to {}", world, target.display());
+        }
+        Command::Backup { action } => match action {
+            BackupCommand::Create => {
+                let service = BackupService::from_config(&AppConfig::from_env());
+                // Uploads to BACKUP_S3_BUCKET block on the runtime
+                let summary =
+                    tokio::task::spawn_blocking(move || service.create(BackupTrigger::Manual))
+                        .await??;
+                println!(
+                    "Backed up {} files ({} bytes) to {}",
+                    summary.record.files,
+                    summary.record.bytes,
+                    summary.path.display()
+                );
+                for path in summary.removed {
+                    println!("Removed {}", path.display());
+                }
+            }
+            BackupCommand::List { world, json } => {
+                let catalog = backup::catalog(world.as_deref())?;
+                if json || json_output {
+                    println!("{}", serde_json::to_string_pretty(&catalog)?);
+                    return Ok(());
+                }
+                if catalog.is_empty() {
+                    println!("No backups");
+                }
+                for record in &catalog {
+                    println!(
+                        "{}	{}	{:.1} MB	{:?}	Foundry {}	{}",
+                        record.file,
+                        record.created,
+                        record.bytes as f64 / 1_048_576.0,
+                        record.trigger,
+                        record.foundry_version.as_deref().unwrap_or("unknown"),
+                        record.worlds.join(", ")
+                    );
+                }
+            }
+            BackupCommand::Restore {
+                file,
+                at,
+                world,
+                install_core,
+                force,
+                no_backup,
+                reproduce,
+            } => {
+                // clap requires one of them
+                let file = match file {
+                    Some(file) => file,
+                    None => {
+                        let at = at.unwrap_or_default();
+                        let time = logs::parse_since(&at).ok_or_else(|| {
+                            anyhow!("Invalid --at {}, expected an RFC 3339 time or e.g. 2h", at)
+                        })?;
+                        restore::snapshot_at(time)?.file
+                    }
+                };
+                let options = RestoreOptions {
+                    world,
+                    force,
+                    install_core,
+                    no_backup,
+                    reproduce,
+                };
+                let summary = RestoreService::from_config(&AppConfig::from_env())
+                    .restore(&file, options)
+                    .await?;
+                if let Some(previous) = &summary.previous {
+                    println!("Backed up the current data to {}", previous.display());
+                }
+                if let Some(version) = &summary.installed_core {
+                    println!("Installed Foundry {}", version);
+                }
+                for note in &summary.environment {
+                    println!("{}", note);
+                }
+                match &summary.world {
+                    Some(world) => println!("Restored {} from {}", world, summary.record.file),
+                    None => println!(
+                        "Restored {}, restart the container to load it",
+                        summary.record.file
+                    ),
+                }
+            }
+            BackupCommand::Test { file } => {
+                let (file, result) = RestoreTest::from_config(&AppConfig::from_env())
+                    .run(file.as_deref())
+                    .await?;
+                if !result.passed {
+                    return Err(anyhow!("{} does not restore: {}", file, result.reason));
+                }
+                println!("{} restores: {}", file, result.reason);
+            }
+        },
+        Command::Db { action } => match action {
+            DbCommand::Dump {
+                world,
+                format,
+                collections,
+                output,
+            } => {
+                let collections = if collections.is_empty() {
+                    dump::DEFAULT_COLLECTIONS
+                        .iter()
+                        .map(|name| name.to_string())
+                        .collect()
+                } else {
+                    collections
+                };
+                match output {
+                    Some(path) => {
+                        let file = permissions::create(&path, FileKind::Regular)
+                            .with_context(|| format!("Failed to create {}", path.display()))?;
+                        let mut out = io::BufWriter::new(file);
+                        let count = DumpService::dump(&world, &collections, format, &mut out)?;
+                        println!("Dumped {} documents to {}", count, path.display());
+                    }
+                    None => {
+                        DumpService::dump(&world, &collections, format, &mut io::stdout().lock())?;
+                    }
+                }
+            }
+            DbCommand::Report {
+                world,
+                format,
+                output,
+            } => {
+                let report = CampaignReport::build(&world)?.render(format);
+                match output {
+                    Some(path) => {
+                        permissions::write(&path, report, FileKind::Regular)
+                            .with_context(|| format!("Failed to write {}", path.display()))?;
+                        println!("Saved the report of world {} to {}", world, path.display());
+                    }
+                    None => print!("{}", report),
+                }
+            }
+        },
+        Command::Assets { action } => match action {
+            AssetsCommand::MigrateToS3 {
+                bucket,
+                base_url,
+                worlds,
+                verify,
+            } => {
+                let migration = AssetMigration {
+                    target: S3Target::from_foundry_config(bucket, base_url)?,
+                    worlds,
+                    sample: verify,
+                    foundry_port: AppConfig::from_env().server_port,
+                };
+                let summary = migration.upload_to_s3().await?;
+                println!(
+                    "Uploaded {} files ({:.1} MB) and rewrote {} documents",
+                    summary.files,
+                    summary.bytes as f64 / 1_048_576.0,
+                    summary.documents
+                );
+                if !summary.broken.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "{} of {} sampled links do not load, check the bucket's public read access. The local files are still in place",
+                        summary.broken.len(),
+                        summary.verified
+                    ));
+                }
+                println!("{} sampled links load from the bucket", summary.verified);
+            }
+            AssetsCommand::MigrateToLocal {
+                bucket,
+                base_url,
+                worlds,
+            } => {
+                let migration = AssetMigration {
+                    target: S3Target::from_foundry_config(bucket, base_url)?,
+                    worlds,
+                    sample: 0,
+                    foundry_port: AppConfig::from_env().server_port,
+                };
+                let summary = migration.download_to_local().await?;
+                println!(
+                    "Downloaded {} files ({:.1} MB) into Data/assets and rewrote {} documents",
+                    summary.files,
+                    summary.bytes as f64 / 1_048_576.0,
+                    summary.documents
+                );
+                if !summary.broken.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "{} objects could not be downloaded, the documents still link to them in the bucket",
+                        summary.broken.len()
+                    ));
+                }
+            }
+            AssetsCommand::Gc {
+                collect,
+                min_age_hours,
+            } => {
+                let collector = AssetCollector::from_config(
+                    &AppConfig::from_env(),
+                    Duration::from_secs(min_age_hours * 3600),
+                );
+                let report = tokio::task::spawn_blocking(move || collector.run(collect)).await??;
+                for path in &report.unreferenced {
+                    println!("{}", path);
+                }
+                let size = report.bytes as f64 / 1_048_576.0;
+                match &report.quarantine {
+                    Some(quarantine) => println!(
+                        "Moved {} unreferenced files ({:.1} MB) to {}",
+                        report.unreferenced.len(),
+                        size,
+                        quarantine.display()
+                    ),
+                    None => println!(
+                        "{} unreferenced files ({:.1} MB), pass --collect to move them aside",
+                        report.unreferenced.len(),
+                        size
+                    ),
+                }
+                if report.recent > 0 {
+                    println!(
+                        "{} unreferenced files changed within the last {} hours were kept",
+                        report.recent, min_age_hours
+                    );
+                }
+            }
+        },
+        Command::Paths { action } => match action {
+            PathsCommand::Rewrite {
+                from,
+                to,
+                worlds,
+                dry_run,
+                no_backup,
+            } => {
+                let summary = PathRewrite {
+                    from,
+                    to,
+                    worlds,
+                    dry_run,
+                    backup: !no_backup,
+                    foundry_port: AppConfig::from_env().server_port,
+                }
+                .run()
+                .await?;
+                for ((path, replaced), count) in &summary.paths {
+                    println!("{} → {} ({}×)", path, replaced, count);
+                }
+                for backup in &summary.backups {
+                    println!("Backed up to {}", backup.display());
+                }
+                if dry_run {
+                    println!("Would rewrite {} documents", summary.documents);
+                } else {
+                    println!("Rewrote {} documents", summary.documents);
+                }
+                if !summary.missing.is_empty() {
+                    eprintln!(
+                        "⚠️ {} new paths have no file in Data:",
+                        summary.missing.len()
+                    );
+                    for path in &summary.missing {
+                        eprintln!("   {}", path);
+                    }
+                }
+            }
+        },
+        Command::Diagnostics { action } => {
+            let mut inspector = Inspector::attach().await?;
+            let path = match action {
+                DiagnosticsCommand::HeapSnapshot => inspector.heap_snapshot().await?,
+                DiagnosticsCommand::CpuProfile { seconds } => {
+                    inspector.cpu_profile(Duration::from_secs(seconds)).await?
+                }
+            };
+            println!("Saved {}", path.display());
+        }
+        Command::BisectModules {
+            world,
+            port,
+            timeout,
+        } => {
+            let bisector = ModuleBisector {
+                smoke_test: SmokeTest {
+                    world: world.clone(),
+                    data_dir: paths::DATA_DIR.clone(),
+                    port,
+                    timeout: Duration::from_secs(timeout),
+                    background: false,
+                },
+                script_path: paths::resolve_foundry_script_path()
+                    .to_string_lossy()
+                    .to_string(),
+            };
+            match bisector.run().await? {
+                Some(module) => println!("Module '{}' breaks loading world {}", module, world),
+                None => println!("World {} loads fine with all modules enabled", world),
+            }
+        }
+        Command::Status => {
+            let config = AppConfig::from_env();
+            let server = status::fetch_server_status(config.server_port).await.ok();
+            let state = WrapperState::load()?;
+            let last_backup = backup::catalog(None)?.pop();
+            let foundry_version = installed_foundry_version();
+            let world = server
+                .as_ref()
+                .filter(|server| server.active)
+                .and_then(|server| server.world.clone());
+            let operation = journal::pending();
+            if json_output {
+                let status = json!({
+                    "wrapper_version": env!("CARGO_PKG_VERSION"),
+                    "foundry_version": foundry_version,
+                    "reachable": server.is_some(),
+                    "world": world,
+                    "players": server.as_ref().map(|server| server.users),
+                    "operation": operation,
+                    "last_backup": last_backup.map(|record| json!({
+                        "file": record.file,
+                        "created": record.created,
+                    })),
+                    "locked_worlds": state.world_locks.keys().collect::<Vec<_>>(),
+                    "archived_worlds": state.archived_worlds.keys().collect::<Vec<_>>(),
+                });
+                println!("{}", serde_json::to_string_pretty(&status)?);
+                return Ok(());
+            }
+            match &foundry_version {
+                Some(version) => println!("Foundry {} is installed", version),
+                None => println!("Foundry is not installed"),
+            }
+            match (&server, &world) {
+                (Some(server), Some(world)) => {
+                    println!("World {} is active with {} players", world, server.users)
+                }
+                (Some(_), None) => println!("Foundry is on the setup screen"),
+                (None, _) => println!("Foundry does not answer on port {}", config.server_port),
+            }
+            if let Some(operation) = operation {
+                println!("Journal records the {}", operation);
+            }
+            match last_backup {
+                Some(record) => println!("Last backup {} from {}", record.file, record.created),
+                None => println!("No backups"),
+            }
+            if !state.world_locks.is_empty() {
+                let locked: Vec<_> = state.world_locks.keys().cloned().collect();
+                println!("Locked worlds: {}", locked.join(", "));
+            }
+            if !state.archived_worlds.is_empty() {
+                println!("{} archived worlds", state.archived_worlds.len());
+            }
+        }
+        Command::Packages => {
+            let packages: Vec<_> = packages::installed_packages()
+                .into_iter()
+                .map(|(key, version)| {
+                    let (kind, id) = key.split_once(':').unwrap_or((&key, &key));
+                    (kind.to_string(), id.to_string(), version)
+                })
+                .collect();
+            if json_output {
+                let packages: Vec<_> = packages
+                    .iter()
+                    .map(|(kind, id, version)| json!({"kind": kind, "id": id, "version": version}))
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&packages)?);
+                return Ok(());
+            }
+            if packages.is_empty() {
+                println!("Nothing is installed");
+            }
+            for (kind, id, version) in &packages {
+                println!("{}\t{}\t{}", kind, id, version);
+            }
+        }
+        Command::Jobs => {
+            let store = JobStore::load()?;
+            let downloaded = |path: &str| fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            if json_output {
+                let downloads: Vec<_> = store
+                    .downloads
+                    .iter()
+                    .map(|(path, download)| {
+                        json!({
+                            "path": path,
+                            "url": download.url,
+                            "bytes": downloaded(path),
+                            "total": download.total,
+                            "started": download.started,
+                        })
+                    })
+                    .collect();
+                let uploads: Vec<_> = store
+                    .uploads
+                    .iter()
+                    .map(|(key, upload)| {
+                        json!({
+                            "key": key,
+                            "bucket": upload.bucket,
+                            "source": upload.source,
+                            "parts": upload.parts.len(),
+                            "total": upload.size,
+                            "started": upload.started,
+                        })
+                    })
+                    .collect();
+                let temporary: Vec<_> = store
+                    .temporary
+                    .iter()
+                    .map(|(path, file)| {
+                        json!({
+                            "path": path,
+                            "purpose": file.purpose,
+                            "created": file.created,
+                        })
+                    })
+                    .collect();
+                let jobs = json!({
+                    "downloads": downloads,
+                    "uploads": uploads,
+                    "temporary": temporary,
+                });
+                println!("{}", serde_json::to_string_pretty(&jobs)?);
+                return Ok(());
+            }
+            if store.downloads.is_empty() && store.uploads.is_empty() && store.temporary.is_empty()
+            {
+                println!("No jobs");
+            }
+            for (path, download) in &store.downloads {
+                println!(
+                    "download\t{}\t{:.1} of {:.1} MB\tsince {}",
+                    path,
+                    downloaded(path) as f64 / 1_048_576.0,
+                    download.total as f64 / 1_048_576.0,
+                    download.started
+                );
+            }
+            for (key, upload) in &store.uploads {
+                println!(
+                    "upload\t{}/{}\t{} parts\tsince {}",
+                    upload.bucket,
+                    key,
+                    upload.parts.len(),
+                    upload.started
+                );
+            }
+            for (path, file) in &store.temporary {
+                println!(
+                    "temporary\t{}\t{}\tsince {}",
+                    path, file.purpose, file.created
+                );
+            }
+        }
+        Command::Doctor => {
+            let checks = Doctor::run();
+            let failed = checks.iter().filter(|check| !check.ok).count();
+            if json_output {
+                let report = json!({ "ok": failed == 0, "checks": checks });
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                for check in &checks {
+                    println!("{} {}: {}", check.icon(), check.name, check.detail);
+                    for hint in &check.hints {
+                        println!("   → {}", hint);
+                    }
+                }
+            }
+            if failed > 0 {
+                anyhow::bail!(i18n::tr_args(
+                    "doctor-failed",
+                    &[
+                        ("failed", &failed.to_string()),
+                        ("total", &checks.len().to_string())
+                    ],
+                ));
+            }
+        }
+        Command::SelfTest => {
+            if let Some(root) = selftest::fixture() {
+                return selftest::run_in_fixture(&root).await;
+            }
+            let checks = selftest::run().await?;
+            for check in &checks {
+                let icon = if check.passed { "✅" } else { "❌" };
+                println!("{} {}: {}", icon, check.name, check.detail);
+            }
+            let failed = checks.iter().filter(|check| !check.passed).count();
+            if failed > 0 {
+                anyhow::bail!("{} of {} self-test checks failed", failed, checks.len());
+            }
+        }
+        Command::Troubleshoot { problem, world } => {
+            let troubleshooting = Troubleshooting::run(problem, world).await?;
+            println!(
+                "\n{}\n{}",
+                i18n::tr("troubleshoot-summary"),
+                troubleshooting.summary()
+            );
+        }
+        Command::Options { action } => match action {
+            OptionsCommand::Diff => match options::proposal()? {
+                Some(diff) => print!("{}", diff.diff),
+                None => println!("No changes to options.json are proposed"),
+            },
+            OptionsCommand::Apply => match options::apply_proposal()? {
+                Some(diff) => {
+                    print!("{}", diff.diff);
+                    println!("Applied, restart the container for Foundry to read them");
+                }
+                None => println!("No changes to options.json are proposed"),
+            },
+        },
+        Command::Changelog { version, list } => {
+            if list {
+                for version in Changelog::versions() {
+                    println!("{}", version);
+                }
+            } else {
+                let (version, notes) = Changelog::read(version.as_deref())?;
+                println!("Foundry VTT {}\n\n{}", version, notes);
+            }
+        }
+        Command::Update { version } => {
+            let updater = Updater::from_config(&AppConfig::from_env());
+            let switched = match version {
+                Some(version) => updater.install_version(&version).await?,
+                None => updater.update().await?,
+            };
+            match switched {
+                Some(version) => {
+                    println!(
+                        "Switched to Foundry {}, restart the container to run it",
+                        version
+                    )
+                }
+                None => println!(
+                    "Foundry {} is up to date",
+                    installed_foundry_version().unwrap_or_default()
+                ),
+            }
+        }
+        Command::Replay {
+            recording,
+            url,
+            session,
+        } => {
+            Replay {
+                recording,
+                url,
+                session,
+            }
+            .run()
+            .await?;
+        }
+        Command::Promote => {
+            let sync = StateSync::from_config(&AppConfig::from_env())
+                .ok_or_else(|| anyhow!("STATE_SYNC_BUCKET is not set up"))?;
+            let epoch =
+                tokio::task::spawn_blocking(move || standby::promote(&sync.remote)).await??;
+            println!(
+                "Holding epoch {}, a waiting standby starts Foundry now",
+                epoch
+            );
+        }
+        #[cfg(feature = "full")]
+        Command::Sync {
+            to,
+            token,
+            delete,
+            dry_run,
+        } => {
+            let token = token
+                .or_else(|| secrets::from_env("SYNC_TOKEN"))
+                .ok_or_else(|| anyhow!("Pass --token or set SYNC_TOKEN"))?;
+            let replication = Replication {
+                target: to,
+                token,
+                excludes: AppConfig::from_env().scan_excludes,
+                delete,
+                dry_run,
+            };
+            let summary = replication.run().await?;
+            println!(
+                "{} {} files ({:.1} MB), {} deleted, {} unchanged",
+                if dry_run { "Would send" } else { "Sent" },
+                summary.uploaded,
+                summary.bytes as f64 / 1_048_576.0,
+                summary.deleted,
+                summary.unchanged
+            );
+        }
+        Command::MigrateHost { action } => match action {
+            MigrateHostCommand::Pack { out } => {
+                let excludes = AppConfig::from_env().scan_excludes;
+                let (path, files, bytes) =
+                    tokio::task::spawn_blocking(move || hostmigrate::pack(out, &excludes))
+                        .await??;
+                println!(
+                    "Packed {} files ({:.1} MB) into {}, copy it to the new host and run `migrate-host unpack` there",
+                    files,
+                    bytes as f64 / 1_048_576.0,
+                    path.display()
+                );
+            }
+            #[cfg(feature = "full")]
+            MigrateHostCommand::Push { to, token } => {
+                let token = token
+                    .or_else(|| secrets::from_env("SYNC_TOKEN"))
+                    .ok_or_else(|| anyhow!("Pass --token or set SYNC_TOKEN"))?;
+                let push = HostPush {
+                    replication: Replication {
+                        target: to,
+                        token,
+                        excludes: AppConfig::from_env().scan_excludes,
+                        delete: false,
+                        dry_run: false,
+                    },
+                };
+                let manifest = push.run().await?;
+                println!(
+                    "Pushed {} worlds, run `migrate-host verify` on the new host",
+                    manifest.worlds.len()
+                );
+            }
+            MigrateHostCommand::Unpack { archive, force } => {
+                let manifest = hostmigrate::unpack(&archive, force).await?;
+                println!(
+                    "Unpacked {} worlds of {} from {}, run `migrate-host verify` next",
+                    manifest.worlds.len(),
+                    manifest.source,
+                    manifest.created
+                );
+            }
+            MigrateHostCommand::Verify => {
+                let report = HostVerify::from_config(&AppConfig::from_env())
+                    .run()
+                    .await?;
+                for note in &report.notes {
+                    println!("note: {}", note);
+                }
+                for problem in &report.problems {
+                    println!("problem: {}", problem);
+                }
+                if !report.problems.is_empty() {
+                    return Err(anyhow!(
+                        "The new host is not ready, {} problems",
+                        report.problems.len()
+                    ));
+                }
+                println!(
+                    "All {} worlds loaded, the new host is ready to take over",
+                    report.worlds
+                );
+            }
+        },
+        Command::Completions { shell } => print!("{}", completions::generate(shell)),
+        Command::Man { out_dir } => {
+            let pages = completions::write_man_pages(&out_dir)?;
+            println!("Wrote {} man pages to {}", pages.len(), out_dir.display());
+        }
+    }
+    Ok(())
+}
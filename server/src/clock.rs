@@ -0,0 +1,107 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use std::time::{Instant, SystemTime};
+use tokio::time::{Duration, sleep};
+use tracing::{info, warn};
+
+use crate::notify::{self, Notification, Severity};
+use crate::resilience::{self, Service};
+
+/// Skew at which TLS validation and Foundry's timed download URLs start failing
+const SKEW_THRESHOLD: Duration = Duration::from_secs(120);
+/// How often the wall clock is compared against the monotonic clock
+const JUMP_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Compares the container clock against the `Date` header of an HTTP server and watches for
+/// time jumps, e.g. a Raspberry Pi without RTC getting its time from NTP late
+#[derive(Clone)]
+pub struct ClockMonitor {
+    pub check_url: String,
+}
+
+impl ClockMonitor {
+    pub async fn run(self) {
+        self.check_skew().await;
+
+        let mut wall = SystemTime::now();
+        let mut monotonic = Instant::now();
+        loop {
+            sleep(JUMP_CHECK_INTERVAL).await;
+            let now_wall = SystemTime::now();
+            let now_monotonic = Instant::now();
+
+            // Both clocks advance equally unless the wall clock was set
+            let elapsed = now_monotonic.duration_since(monotonic).as_secs_f64();
+            let wall_elapsed = match now_wall.duration_since(wall) {
+                Ok(d) => d.as_secs_f64(),
+                Err(e) => -e.duration().as_secs_f64(),
+            };
+            let jump = wall_elapsed - elapsed;
+            if jump.abs() > SKEW_THRESHOLD.as_secs_f64() {
+                warn!(
+                    "⏰ System clock jumped {} by {:.0} seconds, schedules keep their intervals",
+                    if jump < 0.0 { "backwards" } else { "forwards" },
+                    jump.abs()
+                );
+                self.check_skew().await;
+            }
+            wall = now_wall;
+            monotonic = now_monotonic;
+        }
+    }
+
+    async fn check_skew(&self) {
+        match measure_skew(&self.check_url).await {
+            Ok(skew) if skew.unsigned_abs() > SKEW_THRESHOLD.as_secs() => {
+                let direction = if skew > 0 { "ahead" } else { "behind" };
+                notify::send(Notification {
+                    severity: Severity::Warning,
+                    title: "Container clock is wrong".to_string(),
+                    message: format!(
+                        "The system clock is {} seconds {} of {}. Timed download URLs and TLS connections will fail, fix the host's time synchronisation.",
+                        skew.abs(),
+                        direction,
+                        self.check_url
+                    ),
+                });
+            }
+            Ok(skew) => info!(
+                "System clock is within {} seconds of {}",
+                skew.abs(),
+                self.check_url
+            ),
+            Err(e) => warn!("Could not check the system clock: {}", e),
+        }
+    }
+}
+
+/// Seconds the local clock is ahead of the server at `url`, negative when behind
+async fn measure_skew(url: &str) -> Result<i64> {
+    let client = reqwest::Client::new();
+
+    let sent = Utc::now();
+    let response = resilience::call(Service::Clock, || async {
+        Ok::<_, anyhow::Error>(client.head(url).send().await?)
+    })
+    .await
+    .map_err(|e| {
+        anyhow!(
+            "Request to {} failed: {} (a clock far off also breaks TLS certificate validation)",
+            url,
+            e
+        )
+    })?;
+    let received = Utc::now();
+
+    let date = response
+        .headers()
+        .get(reqwest::header::DATE)
+        .and_then(|d| d.to_str().ok())
+        .ok_or_else(|| anyhow!("{} did not send a Date header", url))?;
+    let server_time = DateTime::parse_from_rfc2822(date)
+        .with_context(|| format!("Invalid Date header {}", date))?;
+
+    // The server stamped the response somewhere in between, assume the middle
+    let local_time = sent + (received - sent) / 2;
+    Ok((local_time - server_time.with_timezone(&Utc)).num_seconds())
+}
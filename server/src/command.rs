@@ -0,0 +1,209 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::time::{Duration, Instant, timeout};
+use tracing::debug;
+
+/// Time a command gets unless the caller sets its own
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A short-lived external command whose output is collected, e.g. `dmesg` or a webhook script.
+/// Long-running children like Foundry itself are spawned directly.
+#[derive(Debug, Clone)]
+pub struct CommandRunner {
+    program: OsString,
+    args: Vec<OsString>,
+    env: Vec<(OsString, OsString)>,
+    current_dir: Option<PathBuf>,
+    stdin: Option<Vec<u8>>,
+    timeout: Duration,
+}
+
+/// What a finished command answered
+#[derive(Debug, Clone)]
+pub struct CommandResult {
+    pub status: ExitStatus,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration: Duration,
+}
+
+#[derive(Debug)]
+pub enum CommandError {
+    /// The command could not be started, e.g. it does not exist or is not executable
+    Spawn { command: String, source: io::Error },
+    /// The command ran longer than its timeout and was killed
+    Timeout { command: String, after: Duration },
+    /// The command exited with a non-zero status, its output is kept
+    Failed {
+        command: String,
+        result: CommandResult,
+    },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Spawn { command, source } => write!(f, "Failed to run {}: {}", command, source),
+            Self::Timeout { command, after } => {
+                write!(f, "{} did not finish within {:?}", command, after)
+            }
+            Self::Failed { command, result } => {
+                write!(f, "{} exited with {}", command, result.status)?;
+                // The last line usually says what went wrong
+                match result.stderr.trim().lines().last() {
+                    Some(line) => write!(f, ": {}", line),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for CommandError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Spawn { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl CommandRunner {
+    pub fn new(program: impl Into<OsString>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            env: Vec::new(),
+            current_dir: None,
+            stdin: None,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Set a variable on top of the launcher's environment
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Written to the command's standard input, which is closed afterwards
+    pub fn stdin(mut self, input: impl Into<Vec<u8>>) -> Self {
+        self.stdin = Some(input.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The command line for logs and errors
+    pub fn describe(&self) -> String {
+        std::iter::once(&self.program)
+            .chain(&self.args)
+            .map(|part| part.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Run the command to completion, killing it once the timeout passed. A non-zero exit is
+    /// an error carrying the output.
+    pub async fn run(self) -> Result<CommandResult, CommandError> {
+        let command = self.describe();
+        debug!("Running command: {}", command);
+        let mut process = Command::new(&self.program);
+        process
+            .args(&self.args)
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .stdin(match self.stdin {
+                Some(_) => Stdio::piped(),
+                None => Stdio::null(),
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(dir) = &self.current_dir {
+            process.current_dir(dir);
+        }
+
+        let started = Instant::now();
+        let mut child = process.spawn().map_err(|source| CommandError::Spawn {
+            command: command.clone(),
+            source,
+        })?;
+        let stdin = child.stdin.take();
+        let finished = async {
+            // Dropping stdin closes it, commands reading until EOF go on then
+            if let (Some(input), Some(mut stdin)) = (self.stdin, stdin) {
+                // A command exiting without reading its input is judged by its status instead
+                let _ = stdin.write_all(&input).await;
+            }
+            child.wait_with_output().await
+        };
+        let output = match timeout(self.timeout, finished).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(source)) => return Err(CommandError::Spawn { command, source }),
+            Err(_) => {
+                return Err(CommandError::Timeout {
+                    command,
+                    after: self.timeout,
+                });
+            }
+        };
+        let result = CommandResult {
+            status: output.status,
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            duration: started.elapsed(),
+        };
+        debug!(
+            "Command {} exited with {} after {:?}",
+            command, result.status, result.duration
+        );
+        if !result.status.success() {
+            return Err(CommandError::Failed { command, result });
+        }
+        Ok(result)
+    }
+
+    /// [`run`](Self::run) for the synchronous startup checks. The command runs on a thread of
+    /// its own, so this also works on a thread of the runtime.
+    pub fn run_blocking(self) -> Result<CommandResult, CommandError> {
+        let command = self.describe();
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .map_err(|source| CommandError::Spawn {
+                            command: command.clone(),
+                            source,
+                        })?
+                        .block_on(self.run())
+                })
+                .join()
+                .unwrap_or_else(|_| {
+                    Err(CommandError::Spawn {
+                        command: command.clone(),
+                        source: io::Error::other("the command thread panicked"),
+                    })
+                })
+        })
+    }
+}
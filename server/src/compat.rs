@@ -0,0 +1,74 @@
+use tracing::warn;
+
+use crate::configfile::Settings;
+
+/// A variable that was renamed, or is named differently by another popular image
+pub struct Alias {
+    pub old: &'static str,
+    pub new: &'static str,
+    /// Where the old name comes from, shown in the deprecation warning
+    pub origin: &'static str,
+}
+
+const FELDDY: &str = "felddy/foundryvtt";
+const EARLIER: &str = "earlier versions of this image";
+
+/// Old names read in place of the new ones while those are not set, the first that is set
+/// is used. The `_FILE` form of a secret follows its variable.
+pub const ALIASES: &[Alias] = &[
+    Alias {
+        old: "FOUNDRY_ADMIN_KEY",
+        new: "ADMIN_KEY",
+        origin: FELDDY,
+    },
+    Alias {
+        old: "CONTAINER_CACHE",
+        new: "CACHE_DIR",
+        origin: FELDDY,
+    },
+    Alias {
+        old: "FOUNDRY_MINIFY_STATIC_FILES",
+        new: "FOUNDRY_COMPRESS_STATIC",
+        origin: FELDDY,
+    },
+    Alias {
+        old: "MINIFY_STATIC_FILES",
+        new: "FOUNDRY_COMPRESS_STATIC",
+        origin: EARLIER,
+    },
+];
+
+/// Old names standing in for `name`, in the order they are tried
+pub fn old_names(name: &str) -> Vec<String> {
+    let (base, suffix) = match name.strip_suffix("_FILE") {
+        Some(base) => (base, "_FILE"),
+        None => (name, ""),
+    };
+    ALIASES
+        .iter()
+        .filter(|alias| alias.new == base)
+        .map(|alias| format!("{}{}", alias.old, suffix))
+        .collect()
+}
+
+/// Warn about every old name that is set, once at startup, so compose files can be updated
+/// before the names are dropped
+pub fn warn_deprecated(settings: &Settings) {
+    for alias in ALIASES {
+        for suffix in ["", "_FILE"] {
+            let old = format!("{}{}", alias.old, suffix);
+            if settings.var_exact(&old).is_err() {
+                continue;
+            }
+            let new = format!("{}{}", alias.new, suffix);
+            if settings.var_exact(&new).is_ok() {
+                warn!("⚠️ {} is ignored, {} is set as well", old, new);
+            } else {
+                warn!(
+                    "⚠️ {} is deprecated ({}), rename it to {}",
+                    old, alias.origin, new
+                );
+            }
+        }
+    }
+}
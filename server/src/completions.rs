@@ -0,0 +1,522 @@
+use anyhow::{Context, Result};
+use clap::{ArgAction, CommandFactory, ValueEnum};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::cli::Cli;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// A subcommand as the generators see it, hidden commands and arguments are left out
+struct Node {
+    /// Names from the binary down to this command
+    path: Vec<String>,
+    about: String,
+    long_about: String,
+    version: Option<String>,
+    args: Vec<Arg>,
+    children: Vec<Node>,
+}
+
+struct Arg {
+    long: Option<String>,
+    short: Option<char>,
+    positional: bool,
+    takes_value: bool,
+    value_name: String,
+    /// Values clap accepts, completed instead of file names
+    values: Vec<String>,
+    help: String,
+    required: bool,
+    multiple: bool,
+    default: Option<String>,
+}
+
+impl Node {
+    fn root() -> Self {
+        let mut command = Cli::command();
+        // Adds the generated `--help` and `--version` for introspection
+        command.build();
+        Self::from_command(&command, Vec::new())
+    }
+
+    fn from_command(command: &clap::Command, parent: Vec<String>) -> Self {
+        let mut path = parent;
+        path.push(command.get_name().to_string());
+        let args = command
+            .get_arguments()
+            .filter(|arg| !arg.is_hide_set())
+            .map(|arg| Arg {
+                long: arg.get_long().map(str::to_string),
+                short: arg.get_short(),
+                positional: arg.is_positional(),
+                takes_value: arg.get_action().takes_values(),
+                value_name: arg
+                    .get_value_names()
+                    .and_then(|names| names.first())
+                    .map(|name| name.to_string())
+                    .unwrap_or_else(|| arg.get_id().to_string().to_uppercase()),
+                values: arg
+                    .get_possible_values()
+                    .iter()
+                    .filter(|value| !value.is_hide_set())
+                    .map(|value| value.get_name().to_string())
+                    .collect(),
+                help: arg.get_help().map(|h| h.to_string()).unwrap_or_default(),
+                required: arg.is_required_set(),
+                multiple: matches!(arg.get_action(), ArgAction::Append)
+                    || arg.get_num_args().is_some_and(|n| n.max_values() > 1),
+                default: arg
+                    .get_default_values()
+                    .first()
+                    .map(|v| v.to_string_lossy().to_string()),
+            })
+            .collect();
+        let children = command
+            .get_subcommands()
+            .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+            .map(|sub| Self::from_command(sub, path.clone()))
+            .collect();
+        Self {
+            about: command
+                .get_about()
+                .map(|a| a.to_string())
+                .unwrap_or_default(),
+            long_about: command
+                .get_long_about()
+                .or(command.get_about())
+                .map(|a| a.to_string())
+                .unwrap_or_default(),
+            version: command.get_version().map(str::to_string),
+            path,
+            args,
+            children,
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.path.last().map(String::as_str).unwrap_or_default()
+    }
+
+    /// Every command below and including this one, parents first
+    fn walk(&self) -> Vec<&Node> {
+        let mut nodes = vec![self];
+        for child in &self.children {
+            nodes.extend(child.walk());
+        }
+        nodes
+    }
+
+    fn options(&self) -> impl Iterator<Item = &Arg> {
+        self.args.iter().filter(|arg| !arg.positional)
+    }
+
+    fn positionals(&self) -> impl Iterator<Item = &Arg> {
+        self.args.iter().filter(|arg| arg.positional)
+    }
+}
+
+impl Arg {
+    /// `-s` and `--long` of an option
+    fn flags(&self) -> Vec<String> {
+        self.short
+            .map(|s| format!("-{}", s))
+            .into_iter()
+            .chain(self.long.as_ref().map(|l| format!("--{}", l)))
+            .collect()
+    }
+}
+
+/// Completion script of `shell` for the whole command tree
+pub fn generate(shell: Shell) -> String {
+    let root = Node::root();
+    match shell {
+        Shell::Bash => bash(&root),
+        Shell::Zsh => zsh(&root),
+        Shell::Fish => fish(&root),
+    }
+}
+
+fn bash(root: &Node) -> String {
+    let function = format!("_{}", root.name().replace('-', "_"));
+    let key = |node: &Node| node.path.join("__");
+    let mut out = String::new();
+    let _ = writeln!(out, "{}() {{", function);
+    out.push_str("    local cur prev path word i opts\n");
+    out.push_str("    cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    out.push_str("    prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    let _ = writeln!(out, "    path=\"{}\"", root.name());
+    out.push_str("    for ((i = 1; i < COMP_CWORD; i++)); do\n");
+    out.push_str("        word=\"${COMP_WORDS[i]}\"\n");
+    out.push_str("        case \"${path},${word}\" in\n");
+    for node in root.walk() {
+        for child in &node.children {
+            let _ = writeln!(
+                out,
+                "            \"{},{}\") path=\"{}\" ;;",
+                key(node),
+                child.name(),
+                key(child)
+            );
+        }
+    }
+    out.push_str("        esac\n    done\n\n    case \"${path}\" in\n");
+    for node in root.walk() {
+        let _ = writeln!(out, "        \"{}\")", key(node));
+        let valued: Vec<&Arg> = node.options().filter(|arg| arg.takes_value).collect();
+        if !valued.is_empty() {
+            out.push_str("            case \"${prev}\" in\n");
+            for arg in valued {
+                // Without known values the `default` fallback completes file names
+                let reply = match arg.values.is_empty() {
+                    true => "COMPREPLY=()".to_string(),
+                    false => format!(
+                        "COMPREPLY=($(compgen -W \"{}\" -- \"${{cur}}\"))",
+                        arg.values.join(" ")
+                    ),
+                };
+                let _ = writeln!(
+                    out,
+                    "                {}) {}; return 0 ;;",
+                    arg.flags().join("|"),
+                    reply
+                );
+            }
+            out.push_str("            esac\n");
+        }
+        let words: Vec<String> = node
+            .children
+            .iter()
+            .map(|child| child.name().to_string())
+            .chain(node.options().flat_map(Arg::flags))
+            .chain(node.positionals().flat_map(|arg| arg.values.clone()))
+            .collect();
+        let _ = writeln!(
+            out,
+            "            opts=\"{}\"\n            ;;",
+            words.join(" ")
+        );
+    }
+    out.push_str("    esac\n");
+    out.push_str("    COMPREPLY=($(compgen -W \"${opts}\" -- \"${cur}\"))\n}\n\n");
+    let _ = writeln!(
+        out,
+        "complete -F {} -o bashdefault -o default {}",
+        function,
+        root.name()
+    );
+    out
+}
+
+fn zsh(root: &Node) -> String {
+    let function = |node: &Node| format!("_{}", node.path.join("__").replace('-', "_"));
+    let mut out = format!("#compdef {}\n", root.name());
+    for node in root.walk() {
+        let _ = writeln!(out, "\n{}() {{", function(node));
+        let mut specs: Vec<String> = node.options().flat_map(zsh_option).collect();
+        for (index, arg) in node.positionals().enumerate() {
+            let optional = if arg.required { "" } else { ":" };
+            let multiple = if arg.multiple { "*" } else { "" };
+            let position = if arg.multiple {
+                String::new()
+            } else {
+                (index + 1).to_string()
+            };
+            specs.push(format!(
+                "'{}{}:{}{} -- {}:{}'",
+                multiple,
+                position,
+                optional,
+                arg.value_name.to_lowercase(),
+                zsh_escape(&arg.help),
+                zsh_action(arg)
+            ));
+        }
+        if node.children.is_empty() {
+            out.push_str("    _arguments -s \\\n");
+            for spec in &specs {
+                let _ = writeln!(out, "        {} \\", spec);
+            }
+            out.push_str("        && return 0\n}\n");
+            continue;
+        }
+        out.push_str("    local context curcontext=\"$curcontext\" state line\n");
+        out.push_str("    _arguments -C \\\n");
+        for spec in &specs {
+            let _ = writeln!(out, "        {} \\", spec);
+        }
+        out.push_str("        '1: :->commands' \\\n        '*:: :->args'\n");
+        out.push_str("    case $state in\n        commands)\n            local commands\n");
+        out.push_str("            commands=(\n");
+        for child in &node.children {
+            let _ = writeln!(
+                out,
+                "                '{}:{}'",
+                child.name(),
+                zsh_escape(&child.about)
+            );
+        }
+        out.push_str("            )\n            _describe -t commands 'command' commands\n");
+        out.push_str("            ;;\n        args)\n            case $line[1] in\n");
+        for child in &node.children {
+            let _ = writeln!(
+                out,
+                "                {}) {} ;;",
+                child.name(),
+                function(child)
+            );
+        }
+        out.push_str("            esac\n            ;;\n    esac\n}\n");
+    }
+    let _ = writeln!(out, "\n{} \"$@\"", function(root));
+    out
+}
+
+fn zsh_option(arg: &Arg) -> Vec<String> {
+    let help = zsh_escape(&arg.help);
+    let value = match arg.takes_value {
+        true => format!(":{}:{}", arg.value_name.to_lowercase(), zsh_action(arg)),
+        false => String::new(),
+    };
+    let repeat = if arg.multiple { "*" } else { "" };
+    arg.flags()
+        .into_iter()
+        .map(|flag| format!("'{}{}[{}]{}'", repeat, flag, help, value))
+        .collect()
+}
+
+fn zsh_action(arg: &Arg) -> String {
+    match arg.values.is_empty() {
+        true => "_files".to_string(),
+        false => format!("({})", arg.values.join(" ")),
+    }
+}
+
+/// First line of a help text, safe inside single quotes and `[...]` of `_arguments`
+fn zsh_escape(text: &str) -> String {
+    first_line(text)
+        .replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn fish(root: &Node) -> String {
+    let bin = root.name();
+    let mut out = String::new();
+    for node in root.walk() {
+        // The subcommands typed so far, fish has no notion of nesting
+        let below = &node.path[1..];
+        let seen: Vec<String> = below
+            .iter()
+            .map(|name| format!("__fish_seen_subcommand_from {}", name))
+            .collect();
+        let condition = match seen.is_empty() {
+            true => "__fish_use_subcommand".to_string(),
+            false => seen.join("; and "),
+        };
+        let children: Vec<&str> = node.children.iter().map(Node::name).collect();
+        let choosing = match (seen.is_empty(), children.is_empty()) {
+            (true, _) => condition.clone(),
+            (false, true) => condition.clone(),
+            (false, false) => format!(
+                "{}; and not __fish_seen_subcommand_from {}",
+                condition,
+                children.join(" ")
+            ),
+        };
+        for child in &node.children {
+            let _ = writeln!(
+                out,
+                "complete -c {} -n \"{}\" -f -a {} -d '{}'",
+                bin,
+                choosing,
+                child.name(),
+                fish_escape(&child.about)
+            );
+        }
+        for arg in node.options() {
+            let mut line = format!("complete -c {} -n \"{}\"", bin, condition);
+            if let Some(short) = arg.short {
+                let _ = write!(line, " -s {}", short);
+            }
+            if let Some(long) = &arg.long {
+                let _ = write!(line, " -l {}", long);
+            }
+            if arg.takes_value {
+                line.push_str(" -r");
+                match arg.values.is_empty() {
+                    true => line.push_str(" -F"),
+                    false => {
+                        let _ = write!(line, " -f -a \"{}\"", arg.values.join(" "));
+                    }
+                }
+            }
+            let _ = writeln!(line, " -d '{}'", fish_escape(&arg.help));
+            out.push_str(&line);
+        }
+        for arg in node.positionals().filter(|arg| !arg.values.is_empty()) {
+            let _ = writeln!(
+                out,
+                "complete -c {} -n \"{}\" -f -a \"{}\"",
+                bin,
+                choosing,
+                arg.values.join(" ")
+            );
+        }
+    }
+    out
+}
+
+fn fish_escape(text: &str) -> String {
+    first_line(text).replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn first_line(text: &str) -> &str {
+    text.trim().lines().next().unwrap_or_default().trim()
+}
+
+/// Write a man page for the binary and each subcommand to `dir`, e.g.
+/// `foundry-watcher-backup-restore.1`
+pub fn write_man_pages(dir: &Path) -> Result<Vec<String>> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let root = Node::root();
+    let version = root.version.clone().unwrap_or_default();
+    let mut written = Vec::new();
+    for node in root.walk() {
+        let file = format!("{}.1", node.path.join("-"));
+        let path = dir.join(&file);
+        fs::write(&path, man_page(node, &version))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        written.push(file);
+    }
+    Ok(written)
+}
+
+fn man_page(node: &Node, version: &str) -> String {
+    let name = node.path.join("-");
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        ".TH {} 1 \"\" \"{} {}\"",
+        roff(&name.to_uppercase()),
+        roff(&node.path[0]),
+        roff(version)
+    );
+    out.push_str(".SH NAME\n");
+    let _ = writeln!(out, "{} \\- {}", roff(&name), roff(first_line(&node.about)));
+
+    out.push_str(".SH SYNOPSIS\n");
+    let mut synopsis = format!("\\fB{}\\fR", roff(&node.path.join(" ")));
+    if node.options().next().is_some() {
+        synopsis.push_str(" [\\fIOPTIONS\\fR]");
+    }
+    for arg in node.positionals() {
+        let value = format!("\\fI{}\\fR", roff(&arg.value_name));
+        let value = if arg.multiple {
+            format!("{}...", value)
+        } else {
+            value
+        };
+        match arg.required {
+            true => synopsis.push_str(&format!(" {}", value)),
+            false => synopsis.push_str(&format!(" [{}]", value)),
+        }
+    }
+    if !node.children.is_empty() {
+        synopsis.push_str(" \\fICOMMAND\\fR");
+    }
+    let _ = writeln!(out, "{}", synopsis);
+
+    if !node.long_about.is_empty() {
+        out.push_str(".SH DESCRIPTION\n");
+        out.push_str(&paragraphs(&node.long_about));
+    }
+
+    let positionals: Vec<&Arg> = node.positionals().collect();
+    if !positionals.is_empty() {
+        out.push_str(".SH ARGUMENTS\n");
+        for arg in positionals {
+            let _ = writeln!(out, ".TP\n\\fI{}\\fR", roff(&arg.value_name));
+            out.push_str(&arg_help(arg));
+        }
+    }
+
+    let options: Vec<&Arg> = node.options().collect();
+    if !options.is_empty() {
+        out.push_str(".SH OPTIONS\n");
+        for arg in options {
+            let mut flags = arg
+                .flags()
+                .iter()
+                .map(|flag| format!("\\fB{}\\fR", roff(flag)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            if arg.takes_value {
+                let _ = write!(flags, " \\fI{}\\fR", roff(&arg.value_name));
+            }
+            let _ = writeln!(out, ".TP\n{}", flags);
+            out.push_str(&arg_help(arg));
+        }
+    }
+
+    if !node.children.is_empty() {
+        out.push_str(".SH COMMANDS\n");
+        for child in &node.children {
+            let _ = writeln!(out, ".TP\n\\fB{}\\fR", roff(child.name()));
+            let _ = writeln!(
+                out,
+                "{}\n.br\nSee \\fB{}\\fR(1).",
+                roff(first_line(&child.about)),
+                roff(&child.path.join("-"))
+            );
+        }
+    }
+
+    if node.path.len() > 1 {
+        out.push_str(".SH SEE ALSO\n");
+        let parent = node.path[..node.path.len() - 1].join("-");
+        let _ = writeln!(out, "\\fB{}\\fR(1)", roff(&parent));
+    }
+    out
+}
+
+fn arg_help(arg: &Arg) -> String {
+    let mut help = roff(arg.help.trim());
+    if !arg.values.is_empty() {
+        let _ = write!(help, "\n.br\nOne of: {}", roff(&arg.values.join(", ")));
+    }
+    if let Some(default) = arg.default.as_ref().filter(|_| arg.takes_value) {
+        let _ = write!(help, "\n.br\nDefault: {}", roff(default));
+    }
+    help.push('\n');
+    help
+}
+
+/// Paragraphs of a help text separated by blank lines
+fn paragraphs(text: &str) -> String {
+    text.trim()
+        .split("\n\n")
+        .map(|paragraph| format!("{}\n", roff(paragraph.trim())))
+        .collect::<Vec<_>>()
+        .join(".PP\n")
+}
+
+/// Escape text for roff, lines starting with a dot or quote would be read as requests
+fn roff(text: &str) -> String {
+    text.replace('\\', "\\e")
+        .replace('-', "\\-")
+        .lines()
+        .map(|line| match line.starts_with(['.', '\'']) {
+            true => format!("\\&{}", line),
+            false => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
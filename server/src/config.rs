@@ -1,58 +1,1262 @@
+use crate::acme;
+use crate::assetgc::GcMode;
+use crate::branding::{self, CustomFont};
+use crate::configfile;
+use crate::gate::AuthGate;
+use crate::headers::SecurityHeaders;
+use crate::hostfs;
+use crate::instances::{self, Instance};
+use crate::jobs::{IoClass, JobLimits};
+use crate::objectstore::ObjectStoreConfig;
+use crate::options::{ApplyMode, FoundryOptions};
+use crate::release::{PublisherMetadata, ReleaseDownload, ReleaseSource};
+use crate::schedule::CronSchedule;
+use crate::secrets;
+use crate::standby::StandbyConfig;
+use crate::supervisor::RestartPolicy;
+use crate::tls::CertificateSource;
 use crate::utils::paths;
+use chrono::NaiveTime;
+use std::collections::BTreeMap;
 use std::env;
+use std::fs;
 
 pub struct AppConfig {
-    pub static_files_dir: String,
+    /// Directory the setup UI is served from instead of the copy embedded in the binary
+    pub static_files_dir: Option<String>,
+    /// Written to Foundry's `Config/options.json` on every start
+    pub foundry_options: FoundryOptions,
+    /// Manifest URLs of packages installed before Foundry starts
+    pub install_modules: Vec<String>,
+    pub install_systems: Vec<String>,
+    pub config_apply: ApplyMode,
     pub server_port: u16,
     pub server_host: String,
     pub target_dir: String,
     pub foundry_args: Vec<String>,
     pub foundry_script: String,
+    pub scan_excludes: Vec<String>,
+    pub report_data_usage: bool,
+    pub asset_gc: Option<GcMode>,
+    pub warm_start: bool,
+    pub smoke_test_world: Option<String>,
+    pub smoke_test_port: u16,
+    pub smoke_test_timeout: u64,
+    pub smoke_test_canary: bool,
+    pub export_worlds: Vec<String>,
+    pub export_interval_hours: u64,
+    /// Scheduled exports kept per world, all of them when unset
+    pub export_keep_last: Option<usize>,
+    pub admin_api_port: Option<u16>,
+    pub healthcheck_port: Option<u16>,
+    pub admin_api_token: Option<String>,
+    #[cfg(feature = "full")]
+    pub webhook_secret: Option<String>,
+    pub webhooks: BTreeMap<String, String>,
+    pub alert_rules: BTreeMap<String, String>,
+    #[cfg(feature = "full")]
+    pub alert_cooldown_minutes: u64,
+    pub backup_schedule: Option<CronSchedule>,
+    pub backup_keep_last: Option<usize>,
+    pub backup_keep_days: Option<u64>,
+    pub backup_when_idle: bool,
+    pub background_jobs: JobLimits,
+    pub backup_s3: Option<ObjectStoreConfig>,
+    pub restore_test_schedule: Option<CronSchedule>,
+    pub restore_test_port: u16,
+    pub return_to_setup_after: Option<u64>,
+    pub return_to_setup_schedule: Option<CronSchedule>,
+    /// Minutes without connections to Foundry after which it is stopped
+    pub idle_shutdown_minutes: Option<u64>,
+    /// Stop the container instead of waiting for the next connection
+    pub idle_shutdown_exit: bool,
+    pub discord_bot_token: Option<String>,
+    #[cfg(feature = "full")]
+    pub discord_allowed_role: Option<String>,
+    #[cfg(feature = "full")]
+    pub discord_prefix: String,
+    pub mqtt_host: Option<String>,
+    #[cfg(feature = "full")]
+    pub mqtt_port: u16,
+    #[cfg(feature = "full")]
+    pub mqtt_username: Option<String>,
+    #[cfg(feature = "full")]
+    pub mqtt_password: Option<String>,
+    #[cfg(feature = "full")]
+    pub mqtt_topic_prefix: String,
+    #[cfg(feature = "full")]
+    pub mqtt_discovery_prefix: String,
+    pub smtp_host: Option<String>,
+    #[cfg(feature = "full")]
+    pub smtp_port: u16,
+    #[cfg(feature = "full")]
+    pub smtp_tls: String,
+    #[cfg(feature = "full")]
+    pub smtp_username: Option<String>,
+    #[cfg(feature = "full")]
+    pub smtp_password: Option<String>,
+    #[cfg(feature = "full")]
+    pub smtp_from: Option<String>,
+    #[cfg(feature = "full")]
+    pub smtp_to: Vec<String>,
+    pub notify_webhook_url: Option<String>,
+    #[cfg(feature = "full")]
+    pub notify_webhook_format: String,
+    #[cfg(feature = "full")]
+    pub notify_webhook_min_severity: String,
+    pub notify_disk_free_mb: u64,
+    /// Free MiB below which the disk is critically low, scheduled backups and package installs
+    /// then wait, `0` disables
+    pub disk_critical_free_mb: u64,
+    /// Soft quota of the files in `DATA_DIR`, enforced like critically low disk space
+    pub data_dir_quota_mb: Option<u64>,
+    pub heartbeat_url: Option<String>,
+    #[cfg(feature = "full")]
+    pub heartbeat_failure_url: Option<String>,
+    #[cfg(feature = "full")]
+    pub heartbeat_interval: u64,
+    pub startup_timeout: Option<u64>,
+    /// Seconds between responsiveness checks of the running Foundry, `None` disables them
+    pub watchdog_interval: Option<u64>,
+    /// Failed responsiveness checks in a row after which Foundry is restarted
+    pub watchdog_failures: u32,
+    /// Free space `DATA_DIR` needs before Foundry is started, in MiB
+    pub preflight_min_free_mb: u64,
+    pub restart_policy: RestartPolicy,
+    pub max_restarts: Option<u32>,
+    pub shutdown_timeout: u64,
+    /// Seconds a stop waits for running backups, restores and updates
+    pub shutdown_inhibit_timeout: u64,
+    /// Seconds players are warned in the chat before the wrapper restarts Foundry
+    pub restart_warning: u64,
+    /// Disable the module the logs blame when `FOUNDRY_WORLD` fails to load
+    pub auto_disable_modules: bool,
+    /// Start a Foundry of a newer generation than the worlds, migrating them
+    pub allow_major_migration: bool,
+    pub clock_check_url: Option<String>,
+    pub release_notes_url: Option<String>,
+    pub foundry_release_path: Option<String>,
+    pub foundry_download: Option<ReleaseDownload>,
+    /// Written to `Config/license.json` when Foundry has no or another key stored
+    pub foundry_license_key: Option<String>,
+    pub auto_update: bool,
+    /// `FOUNDRY_USERNAME` and `FOUNDRY_PASSWORD`, updates download with them
+    pub foundry_account: Option<(String, String)>,
+    /// `GAME_USER` and `GAME_USER_PASSWORD`, the wrapper joins active worlds as this user
+    pub game_user: Option<(String, String)>,
+    pub update_channel: String,
+    pub update_check_url: String,
+    /// Where the size and checksum of every downloaded release are checked, `None` when
+    /// `RELEASE_METADATA_URL` is empty
+    pub release_metadata: Option<PublisherMetadata>,
+    pub proxy_port: Option<u16>,
+    pub proxy_latency_ms: u64,
+    pub proxy_jitter_ms: u64,
+    pub proxy_drop_percent: f64,
+    pub proxy_record_dir: Option<String>,
+    pub proxy_headers: SecurityHeaders,
+    pub proxy_max_connections_per_ip: u32,
+    pub proxy_header_timeout: u64,
+    pub proxy_auth: Option<AuthGate>,
+    /// Certificate the launcher terminates TLS with, `None` leaves TLS to a reverse proxy
+    pub tls: Option<CertificateSource>,
+    pub tls_port: u16,
+    /// Plain HTTP port for ACME challenges and the redirect to HTTPS
+    pub tls_http_port: Option<u16>,
+    pub join_url: String,
+    pub join_qr: bool,
+    pub state_sync: Option<ObjectStoreConfig>,
+    pub state_sync_interval: u64,
+    pub standby: Option<StandbyConfig>,
+    pub preload_scenes: bool,
+    #[cfg(feature = "full")]
+    pub preload_at: Vec<NaiveTime>,
+    pub branding_dir: Option<String>,
+    pub custom_fonts: Vec<CustomFont>,
+    pub settings_presets_dir: Option<String>,
+    /// Append the wrapper's log to `DATA_DIR/Logs/wrapper.log`
+    pub log_file: bool,
+    /// Size in MiB `wrapper.log` is rotated at
+    pub log_file_max_mb: u64,
+    /// Further Foundry instances next to the main one, from `INSTANCES`
+    pub instances: Vec<Instance>,
+    /// Seconds per attempt by service name, from `EXTERNAL_TIMEOUTS`
+    pub external_timeouts: Vec<(String, u64)>,
+    /// Seconds external calls may take in total before Foundry starts
+    pub external_startup_budget: u64,
+    /// Seconds external calls may take in total after Foundry stopped
+    pub external_shutdown_budget: u64,
+    /// Hours after which untouched temporary files and partial downloads are removed
+    pub temp_file_max_age: u64,
+    /// Cap Foundry's heap and background jobs on hosts with little memory
+    pub memory_guardrails: bool,
+    /// Append lifecycle events to `DATA_DIR/.wrapper/events.ndjson`
+    pub event_log: bool,
+    /// Start against a data directory a newer wrapper changed the files of
+    pub allow_state_downgrade: bool,
 }
 
 impl AppConfig {
     pub fn from_env() -> Self {
-        let static_files_dir =
-            env::var("STATIC_FILES_DIR").unwrap_or_else(|_| "static".to_string());
+        let static_files_dir = static_files_dir();
 
-        let server_port = env::var("SERVER_PORT")
-            .or_else(|_| env::var("APPLICATION_PORT"))
+        let server_port = configfile::var("SERVER_PORT")
+            .or_else(|_| configfile::var("APPLICATION_PORT"))
+            .or_else(|_| configfile::var("FOUNDRY_PORT"))
             .unwrap_or_else(|_| "4444".to_string())
             .parse::<u16>()
             .unwrap_or(4444);
 
-        let server_host = env::var("SERVER_HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
+        let server_host = configfile::var("SERVER_HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
 
         let target_dir = get_target_directory();
 
-        let foundry_host =
-            env::var("APPLICATION_HOST").unwrap_or("foundry.vtt".to_string());
+        let foundry_host = configfile::var("APPLICATION_HOST").unwrap_or("foundry.vtt".to_string());
 
-        let foundry_args = vec![
+        let foundry_options = foundry_options(server_port);
+        let config_apply = configfile::var("CONFIG_APPLY")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(|v| {
+                ApplyMode::parse(&v).unwrap_or_else(|| {
+                    tracing::warn!("⚠️ Ignoring CONFIG_APPLY, expected auto or manual: {}", v);
+                    ApplyMode::Auto
+                })
+            })
+            .unwrap_or(ApplyMode::Auto);
+
+        // Install newer releases of the channel on startup, next to the installed one
+        let auto_update = configfile::var("AUTO_UPDATE")
+            .map(|v| v.to_lowercase() == "true")
+            .unwrap_or(false);
+
+        // Command line options win over options.json, they are left out for keys set there
+        let mut foundry_args = vec![
             format!("--dataPath={}", *paths::DATA_DIR),
             format!("--port={}", server_port),
-            format!("--hostname={}", foundry_host),
-            "--noupnp".to_string(),
-            "--proxySSL".to_string(),
         ];
+        if foundry_options.hostname.is_none() {
+            foundry_args.push(format!("--hostname={}", foundry_host));
+        }
+        if foundry_options.upnp.is_none() {
+            foundry_args.push("--noupnp".to_string());
+        }
+        if foundry_options.proxy_ssl.is_none() {
+            foundry_args.push("--proxySSL".to_string());
+        }
+        // Foundry replacing its own files would undo the releases installed by the wrapper
+        if configfile::var("FOUNDRY_NO_UPDATE")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_lowercase() == "true")
+            .unwrap_or(auto_update)
+        {
+            foundry_args.push("--noupdate".to_string());
+        }
+
+        let foundry_script = paths::resolve_foundry_script_path()
+            .to_string_lossy()
+            .to_string();
+
+        // Comma separated globs skipped by every directory scan (disk usage, integrity, ...)
+        let scan_excludes = configfile::var("SCAN_EXCLUDE")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        // Walking all of DATA_DIR takes minutes on large volumes, so only on request
+        let report_data_usage = configfile::var("REPORT_DATA_USAGE")
+            .map(|v| v.to_lowercase() == "true")
+            .unwrap_or(false);
+
+        let asset_gc = match configfile::var("ASSET_GC").unwrap_or_default().trim() {
+            "report" => Some(GcMode::Report),
+            "collect" => Some(GcMode::Collect),
+            _ => None,
+        };
+
+        // Skip validation and diagnostics when nothing changed since the last successful boot
+        let warm_start = configfile::var("WARM_START")
+            .map(|v| v.to_lowercase() != "false")
+            .unwrap_or(true);
+
+        // World booted headlessly to verify core, system and module upgrades
+        let smoke_test_world = configfile::var("SMOKE_TEST_WORLD")
+            .ok()
+            .filter(|w| !w.is_empty());
+
+        let smoke_test_port = configfile::var("SMOKE_TEST_PORT")
+            .unwrap_or_else(|_| "30001".to_string())
+            .parse::<u16>()
+            .unwrap_or(30001);
+
+        let smoke_test_timeout = configfile::var("SMOKE_TEST_TIMEOUT")
+            .unwrap_or_else(|_| "180".to_string())
+            .parse::<u64>()
+            .unwrap_or(180);
+
+        // Run the smoke test against a throwaway copy so the live world is never opened
+        let smoke_test_canary = configfile::var("SMOKE_TEST_CANARY")
+            .map(|v| v.to_lowercase() == "true")
+            .unwrap_or(false);
+
+        // Worlds whose actors and journals are exported regularly, `all` exports every world
+        let export_worlds = configfile::var("EXPORT_WORLDS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        let export_interval_hours = configfile::var("EXPORT_INTERVAL_HOURS")
+            .unwrap_or_else(|_| "24".to_string())
+            .parse::<u64>()
+            .unwrap_or(24)
+            .max(1);
+        let export_keep_last = configfile::var("EXPORT_KEEP_LAST")
+            .ok()
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|n| *n > 0);
+
+        // The admin API is only started when a port is configured
+        let admin_api_port = configfile::var("ADMIN_API_PORT")
+            .ok()
+            .and_then(|p| p.parse::<u16>().ok());
+
+        // Bearer token of the lifecycle endpoints, they stay disabled without one
+        let admin_api_token = secrets::from_env("ADMIN_API_TOKEN");
+
+        // Probes on their own port, without the rest of the admin API
+        let healthcheck_port = configfile::var("HEALTHCHECK_PORT")
+            .ok()
+            .and_then(|p| p.parse::<u16>().ok());
+
+        #[cfg(feature = "full")]
+        let webhook_secret = secrets::from_env("WEBHOOK_SECRET");
+
+        // `name=action` pairs, e.g. `nightly=export:my-world,redeploy=restart`
+        let webhooks = configfile::var("WEBHOOKS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(name, action)| (name.trim().to_string(), action.trim().to_string()))
+            .collect();
+
+        // `name=condition` pairs, e.g. `db=log:Database failed,disk=disk>90,crashes=restarts>3`
+        let alert_rules = configfile::var("ALERT_RULES")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(name, rule)| (name.trim().to_string(), rule.trim().to_string()))
+            .collect();
+        #[cfg(feature = "full")]
+        let alert_cooldown_minutes = configfile::var("ALERT_COOLDOWN_MINUTES")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse::<u64>()
+            .unwrap_or(30)
+            .max(1);
+
+        // Zip snapshots of the data directory in `BACKUP_DIR/snapshots`, e.g. `30 3 * * *`
+        let backup_schedule = configfile::var("BACKUP_SCHEDULE")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .and_then(|schedule| match CronSchedule::parse(&schedule) {
+                Ok(schedule) => Some(schedule),
+                Err(e) => {
+                    tracing::warn!("Invalid BACKUP_SCHEDULE, no backups are taken: {}", e);
+                    None
+                }
+            });
+        let backup_keep_last = configfile::var("BACKUP_KEEP_LAST")
+            .ok()
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|n| *n > 0);
+        let backup_keep_days = configfile::var("BACKUP_KEEP_DAYS")
+            .ok()
+            .and_then(|n| n.parse::<u64>().ok())
+            .filter(|n| *n > 0);
+        let backup_when_idle = configfile::var("BACKUP_WHEN_IDLE")
+            .map(|v| v.to_lowercase() == "true")
+            .unwrap_or(false);
+        let background_jobs = background_jobs(server_port);
+        // Snapshots are uploaded to this bucket as well, e.g. for off-site copies
+        let backup_s3 = object_store_config("BACKUP_S3", "uploading backups");
+        // The newest snapshot is restored into a throwaway data directory and its worlds booted
+        let restore_test_schedule = configfile::var("RESTORE_TEST_SCHEDULE")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .and_then(|schedule| match CronSchedule::parse(&schedule) {
+                Ok(schedule) => Some(schedule),
+                Err(e) => {
+                    tracing::warn!("Invalid RESTORE_TEST_SCHEDULE, no test restores run: {}", e);
+                    None
+                }
+            });
+        let restore_test_port = configfile::var("RESTORE_TEST_PORT")
+            .unwrap_or_else(|_| "30002".to_string())
+            .parse::<u16>()
+            .unwrap_or(30002);
+
+        // Active worlds without players go back to the setup screen after these minutes
+        let return_to_setup_after = configfile::var("RETURN_TO_SETUP_AFTER")
+            .ok()
+            .and_then(|n| n.parse::<u64>().ok())
+            .filter(|n| *n > 0);
+        let return_to_setup_schedule = configfile::var("RETURN_TO_SETUP_SCHEDULE")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .and_then(|schedule| match CronSchedule::parse(&schedule) {
+                Ok(schedule) => Some(schedule),
+                Err(e) => {
+                    tracing::warn!("Invalid RETURN_TO_SETUP_SCHEDULE, it is ignored: {}", e);
+                    None
+                }
+            });
+        let idle_shutdown_minutes = configfile::var("IDLE_SHUTDOWN_MINUTES")
+            .ok()
+            .and_then(|n| n.parse::<u64>().ok())
+            .filter(|n| *n > 0);
+        let idle_shutdown_exit = configfile::var("IDLE_SHUTDOWN_EXIT")
+            .map(|v| v.to_lowercase() == "true")
+            .unwrap_or(false);
+
+        let discord_bot_token = secrets::from_env("DISCORD_BOT_TOKEN");
+        #[cfg(feature = "full")]
+        let discord_allowed_role = configfile::var("DISCORD_ALLOWED_ROLE")
+            .ok()
+            .filter(|r| !r.is_empty());
+        #[cfg(feature = "full")]
+        let discord_prefix =
+            configfile::var("DISCORD_PREFIX").unwrap_or_else(|_| "!foundry".to_string());
+
+        // MQTT state publishing is enabled by setting a broker host
+        let mqtt_host = configfile::var("MQTT_HOST").ok().filter(|h| !h.is_empty());
+        #[cfg(feature = "full")]
+        let mqtt_port = configfile::var("MQTT_PORT")
+            .unwrap_or_else(|_| "1883".to_string())
+            .parse::<u16>()
+            .unwrap_or(1883);
+        #[cfg(feature = "full")]
+        let mqtt_username = configfile::var("MQTT_USERNAME")
+            .ok()
+            .filter(|u| !u.is_empty());
+        #[cfg(feature = "full")]
+        let mqtt_password = secrets::from_env("MQTT_PASSWORD");
+        #[cfg(feature = "full")]
+        let mqtt_topic_prefix =
+            configfile::var("MQTT_TOPIC_PREFIX").unwrap_or_else(|_| "foundryvtt".to_string());
+        #[cfg(feature = "full")]
+        let mqtt_discovery_prefix = configfile::var("MQTT_DISCOVERY_PREFIX")
+            .unwrap_or_else(|_| "homeassistant".to_string());
+
+        // Email notifications for critical events, `starttls`, `tls` or `none`
+        let smtp_host = configfile::var("SMTP_HOST").ok().filter(|h| !h.is_empty());
+        #[cfg(feature = "full")]
+        let smtp_tls = configfile::var("SMTP_TLS")
+            .unwrap_or_else(|_| "starttls".to_string())
+            .to_lowercase();
+        #[cfg(feature = "full")]
+        let default_smtp_port = if smtp_tls == "tls" { 465 } else { 587 };
+        #[cfg(feature = "full")]
+        let smtp_port = configfile::var("SMTP_PORT")
+            .ok()
+            .and_then(|p| p.parse::<u16>().ok())
+            .unwrap_or(default_smtp_port);
+        #[cfg(feature = "full")]
+        let smtp_username = configfile::var("SMTP_USERNAME")
+            .ok()
+            .filter(|u| !u.is_empty());
+        #[cfg(feature = "full")]
+        let smtp_password = secrets::from_env("SMTP_PASSWORD");
+        #[cfg(feature = "full")]
+        let smtp_from = configfile::var("SMTP_FROM").ok().filter(|f| !f.is_empty());
+        #[cfg(feature = "full")]
+        let smtp_to = configfile::var("SMTP_TO")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        // Notifications posted to a webhook, `auto` picks Discord's format for Discord URLs
+        let notify_webhook_url = secrets::from_env("NOTIFY_WEBHOOK_URL");
+        #[cfg(feature = "full")]
+        let notify_webhook_format = configfile::var("NOTIFY_WEBHOOK_FORMAT")
+            .unwrap_or_else(|_| "auto".to_string())
+            .to_lowercase();
+        #[cfg(feature = "full")]
+        let notify_webhook_min_severity = configfile::var("NOTIFY_WEBHOOK_MIN_SEVERITY")
+            .unwrap_or_else(|_| "info".to_string())
+            .to_lowercase();
+        let notify_disk_free_mb = configfile::var("NOTIFY_DISK_FREE_MB")
+            .ok()
+            .and_then(|mb| mb.parse().ok())
+            .unwrap_or(1024);
+        let disk_critical_free_mb = configfile::var("DISK_CRITICAL_FREE_MB")
+            .ok()
+            .and_then(|mb| mb.parse().ok())
+            .unwrap_or(256);
+        let data_dir_quota_mb = configfile::var("DATA_DIR_QUOTA_MB")
+            .ok()
+            .and_then(|mb| mb.parse().ok())
+            .filter(|mb| *mb > 0);
+
+        // Push monitoring, the failure URL is pinged instead while Foundry is down
+        let heartbeat_url = configfile::var("HEARTBEAT_URL")
+            .ok()
+            .filter(|u| !u.is_empty());
+        #[cfg(feature = "full")]
+        let heartbeat_failure_url = configfile::var("HEARTBEAT_FAILURE_URL")
+            .ok()
+            .filter(|u| !u.is_empty());
+        #[cfg(feature = "full")]
+        let heartbeat_interval = configfile::var("HEARTBEAT_INTERVAL")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()
+            .unwrap_or(60)
+            .max(1);
+
+        // Seconds Foundry may take until it answers HTTP requests, 0 disables hang detection
+        let startup_timeout = configfile::var("STARTUP_TIMEOUT_SECONDS")
+            .unwrap_or_else(|_| "300".to_string())
+            .parse::<u64>()
+            .ok()
+            .filter(|t| *t > 0);
+        // Catches event loop stalls the process being alive hides, 0 disables the checks
+        let watchdog_interval = configfile::var("WATCHDOG_INTERVAL")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse::<u64>()
+            .ok()
+            .filter(|t| *t > 0);
+        let watchdog_failures = configfile::var("WATCHDOG_FAILURES")
+            .unwrap_or_else(|_| "4".to_string())
+            .parse::<u32>()
+            .unwrap_or(4)
+            .max(1);
+
+        // A full volume corrupts the world databases, 0 skips the check
+        let preflight_min_free_mb = configfile::var("PREFLIGHT_MIN_FREE_MB")
+            .unwrap_or_else(|_| "256".to_string())
+            .parse::<u64>()
+            .unwrap_or(256);
+
+        // Foundry is restarted with backoff after it exited, `MAX_RESTARTS` 0 allows any number
+        let restart_policy = configfile::var("RESTART_POLICY")
+            .ok()
+            .filter(|p| !p.is_empty())
+            .map(|policy| {
+                RestartPolicy::parse(&policy).unwrap_or_else(|| {
+                    tracing::warn!("Unknown RESTART_POLICY {}, restarting always", policy);
+                    RestartPolicy::Always
+                })
+            })
+            .unwrap_or(RestartPolicy::Always);
+        let max_restarts = configfile::var("MAX_RESTARTS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse::<u32>()
+            .ok()
+            .filter(|m| *m > 0);
+
+        // Seconds Foundry gets to save the world after SIGTERM, keep below `docker stop -t`
+        let shutdown_timeout = configfile::var("SHUTDOWN_TIMEOUT")
+            .unwrap_or_else(|_| "8".to_string())
+            .parse::<u64>()
+            .unwrap_or(8);
+        let shutdown_inhibit_timeout = configfile::var("SHUTDOWN_INHIBIT_TIMEOUT")
+            .unwrap_or_else(|_| "120".to_string())
+            .parse::<u64>()
+            .unwrap_or(120);
+        let restart_warning = configfile::var("RESTART_WARNING")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()
+            .unwrap_or(60);
+        let auto_disable_modules = configfile::var("AUTO_DISABLE_MODULES")
+            .map(|v| v.to_lowercase() == "true")
+            .unwrap_or(false);
+        let allow_major_migration = configfile::var("ALLOW_MAJOR_MIGRATION")
+            .map(|v| v.to_lowercase() == "true")
+            .unwrap_or(false);
+
+        // Server whose `Date` header the system clock is compared with, empty disables the check
+        let clock_check_url = Some(
+            configfile::var("CLOCK_CHECK_URL")
+                .unwrap_or_else(|_| "https://foundryvtt.com".to_string()),
+        )
+        .filter(|u| !u.is_empty());
 
-        let foundry_script = paths::FOUNDRY_SCRIPT_PATH.to_string_lossy().to_string();
+        // Release page of a Foundry version, `{version}` is replaced, empty disables fetching
+        let release_notes_url = Some(
+            configfile::var("RELEASE_NOTES_URL")
+                .unwrap_or_else(|_| "https://foundryvtt.com/releases/{version}".to_string()),
+        )
+        .filter(|u| !u.is_empty());
+
+        // Local release archive installed without the web installer, e.g. in air-gapped setups
+        let foundry_release_path = configfile::var("FOUNDRY_RELEASE_PATH")
+            .ok()
+            .filter(|p| !p.is_empty());
+
+        let foundry_license_key = secrets::from_env("FOUNDRY_LICENSE_KEY");
+        let update_channel = configfile::var("AUTO_UPDATE_CHANNEL")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| "stable".to_string());
+        let update_check_url = configfile::var("UPDATE_CHECK_URL")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| {
+                "https://foundryvtt.com/_api/release/latest?channel={channel}".to_string()
+            });
+        // Every downloaded release is checked against what foundryvtt.com states about it
+        let release_metadata = Some(
+            configfile::var("RELEASE_METADATA_URL")
+                .unwrap_or_else(|_| "https://foundryvtt.com/_api/release/{version}".to_string()),
+        )
+        .filter(|u| !u.is_empty())
+        .map(|url| PublisherMetadata {
+            url,
+            required: configfile::var("RELEASE_METADATA_REQUIRED")
+                .map(|v| v.to_lowercase() == "true")
+                .unwrap_or(false),
+        });
+        // Downloaded at first boot when nothing is installed, both links and accounts are secrets
+        let foundry_account =
+            secrets::from_env("FOUNDRY_USERNAME").zip(secrets::from_env("FOUNDRY_PASSWORD"));
+        // A Gamemaster of the worlds, Foundry users may have no password
+        let game_user = secrets::from_env("GAME_USER").map(|user| {
+            (
+                user,
+                secrets::from_env("GAME_USER_PASSWORD").unwrap_or_default(),
+            )
+        });
+        let foundry_download = match (secrets::from_env("FOUNDRY_RELEASE_URL"), &foundry_account) {
+            (Some(url), _) => Some(ReleaseSource::Url(url)),
+            (None, Some((username, password))) => {
+                match configfile::var("FOUNDRY_VERSION").ok().filter(|v| !v.is_empty()) {
+                    Some(version) => Some(ReleaseSource::Account {
+                        username: username.clone(),
+                        password: password.clone(),
+                        version,
+                    }),
+                    // The updater picks the release on its own
+                    None if auto_update => None,
+                    None => {
+                        tracing::warn!(
+                            "FOUNDRY_USERNAME needs FOUNDRY_VERSION, e.g. 12.331, to download Foundry"
+                        );
+                        None
+                    }
+                }
+            }
+            _ => None,
+        }
+        .map(|source| ReleaseDownload {
+            source,
+            sha256: configfile::var("FOUNDRY_RELEASE_SHA256")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            metadata: release_metadata.clone(),
+        });
+
+        // Players connect here instead of to Foundry's port when the proxy is enabled
+        let proxy_port = configfile::var("PROXY_PORT")
+            .ok()
+            .and_then(|p| p.parse::<u16>().ok());
+
+        // Development only, simulates bad connections on the game websockets of the proxy
+        let proxy_latency_ms = configfile::var("PROXY_LATENCY_MS")
+            .ok()
+            .and_then(|ms| ms.parse::<u64>().ok())
+            .unwrap_or(0);
+        let proxy_jitter_ms = configfile::var("PROXY_JITTER_MS")
+            .ok()
+            .and_then(|ms| ms.parse::<u64>().ok())
+            .unwrap_or(0);
+        let proxy_drop_percent = configfile::var("PROXY_DROP_PERCENT")
+            .ok()
+            .and_then(|p| p.parse::<f64>().ok())
+            .map(|p| p.clamp(0.0, 100.0))
+            .unwrap_or(0.0);
+        let proxy_record_dir = configfile::var("PROXY_RECORD_DIR")
+            .ok()
+            .filter(|d| !d.is_empty());
+
+        // Foundry runs behind a TLS terminating proxy (`--proxySSL`), so players use HTTPS
+        let join_url = configfile::var("JOIN_URL")
+            .ok()
+            .filter(|u| !u.is_empty())
+            .unwrap_or_else(|| format!("https://{}/join", foundry_host));
+        let join_qr = configfile::var("JOIN_QR")
+            .map(|v| v.to_lowercase() == "true")
+            .unwrap_or(false);
+
+        let proxy_headers = proxy_headers_config(&join_url);
+
+        // Enough for a reverse proxy in front of a full table, 0 disables the limit
+        let proxy_max_connections_per_ip = configfile::var("PROXY_MAX_CONNECTIONS_PER_IP")
+            .unwrap_or_else(|_| "128".to_string())
+            .parse::<u32>()
+            .unwrap_or(128);
+        let proxy_header_timeout = configfile::var("PROXY_HEADER_TIMEOUT")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse::<u64>()
+            .unwrap_or(10)
+            .max(1);
+
+        let proxy_auth = proxy_auth_config();
+
+        let tls = tls_config();
+        let tls_port = configfile::var("TLS_PORT")
+            .unwrap_or_else(|_| "443".to_string())
+            .parse::<u16>()
+            .unwrap_or(443);
+        // 0 turns the redirect off, certificates from ACME then cannot be validated
+        let tls_http_port = configfile::var("TLS_HTTP_PORT")
+            .unwrap_or_else(|_| "80".to_string())
+            .parse::<u16>()
+            .ok()
+            .filter(|port| *port != 0);
+        if matches!(tls, Some(CertificateSource::Acme { .. })) && tls_http_port.is_none() {
+            tracing::warn!("⚠️ TLS_HTTP_PORT is off, the ACME CA cannot validate TLS_DOMAIN");
+        }
+
+        let state_sync = object_store_config("STATE_SYNC", "state sync");
+        let state_sync_interval = configfile::var("STATE_SYNC_INTERVAL")
+            .unwrap_or_else(|_| "15".to_string())
+            .parse::<u64>()
+            .unwrap_or(15)
+            .max(1);
+
+        // Experimental warm standby, pulling what the primary syncs to the same bucket
+        let standby = configfile::var("STANDBY_PRIMARY_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .map(|primary_url| StandbyConfig {
+                primary_url,
+                auto_promote: configfile::var("STANDBY_PROMOTION")
+                    .is_ok_and(|mode| mode.eq_ignore_ascii_case("auto")),
+                failures: configfile::var("STANDBY_FAILURES")
+                    .unwrap_or_else(|_| "3".to_string())
+                    .parse::<u32>()
+                    .unwrap_or(3)
+                    .max(1),
+                poll_interval: configfile::var("STANDBY_POLL_INTERVAL")
+                    .unwrap_or_else(|_| "10".to_string())
+                    .parse::<u64>()
+                    .unwrap_or(10)
+                    .max(1),
+            });
+        let standby = match (standby, &state_sync) {
+            (Some(_), None) => {
+                tracing::warn!(
+                    "⚠️ STANDBY_PRIMARY_URL needs the bucket of STATE_SYNC_BUCKET, starting as a primary"
+                );
+                None
+            }
+            (standby, _) => standby,
+        };
+
+        // Load the active scene's images when a world launches and at these local times
+        let preload_at: Vec<NaiveTime> = configfile::var("PRELOAD_AT")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|t| NaiveTime::parse_from_str(t.trim(), "%H:%M").ok())
+            .collect();
+        let preload_scenes = configfile::var("PRELOAD_SCENES")
+            .map(|v| v.to_lowercase() == "true")
+            .unwrap_or(!preload_at.is_empty());
+
+        // Fonts and UI assets of a branded table, placed in `Data` before Foundry starts
+        let branding_dir = configfile::var("BRANDING_DIR")
+            .ok()
+            .filter(|d| !d.is_empty());
+        let custom_fonts =
+            branding::parse_fonts(&configfile::var("CUSTOM_FONTS").unwrap_or_default());
+
+        // Module settings bundles seeded into every world once
+        let settings_presets_dir = configfile::var("SETTINGS_PRESETS_DIR")
+            .ok()
+            .filter(|d| !d.is_empty());
+
+        let log_file = configfile::var("LOG_FILE")
+            .map(|v| v.to_lowercase() != "false")
+            .unwrap_or(true);
+        let log_file_max_mb = configfile::var("LOG_FILE_MAX_MB")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse::<u64>()
+            .unwrap_or(10);
+
+        // Further worlds with their own data directory and port, run next to the main one
+        let taken: Vec<u16> = [
+            Some(server_port),
+            admin_api_port,
+            healthcheck_port,
+            proxy_port,
+            tls.as_ref().map(|_| tls_port),
+            Some(smoke_test_port),
+            Some(restore_test_port),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let instances = instances::parse(&configfile::var("INSTANCES").unwrap_or_default(), &taken);
+
+        // Timeouts of single calls to third-party services, e.g. `s3:600,webhooks:5`
+        let external_timeouts = configfile::var("EXTERNAL_TIMEOUTS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .filter_map(|entry| {
+                let parsed = entry.split_once(':').and_then(|(name, seconds)| {
+                    Some((
+                        name.trim().to_lowercase(),
+                        seconds.trim().parse::<u64>().ok()?,
+                    ))
+                });
+                if parsed.is_none() {
+                    tracing::warn!(
+                        "⚠️ Ignoring {} in EXTERNAL_TIMEOUTS, expected service:seconds",
+                        entry
+                    );
+                }
+                parsed
+            })
+            .collect();
+        let external_startup_budget = configfile::var("EXTERNAL_STARTUP_BUDGET")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()
+            .unwrap_or(60);
+        let external_shutdown_budget = configfile::var("EXTERNAL_SHUTDOWN_BUDGET")
+            .unwrap_or_else(|_| "20".to_string())
+            .parse::<u64>()
+            .unwrap_or(20);
+        let event_log = configfile::var("EVENT_LOG")
+            .map(|v| v.to_lowercase() != "false")
+            .unwrap_or(true);
+        let allow_state_downgrade = configfile::var("ALLOW_STATE_DOWNGRADE")
+            .map(|v| v.to_lowercase() == "true")
+            .unwrap_or(false);
+        let memory_guardrails = configfile::var("MEMORY_GUARDRAILS")
+            .map(|v| v.to_lowercase() == "true")
+            .unwrap_or(false);
+        let temp_file_max_age = configfile::var("TEMP_FILE_MAX_AGE")
+            .unwrap_or_else(|_| "24".to_string())
+            .parse::<u64>()
+            .unwrap_or(24)
+            .max(1);
 
         Self {
             static_files_dir,
+            foundry_options,
+            install_modules: manifest_urls("INSTALL_MODULES"),
+            install_systems: manifest_urls("INSTALL_SYSTEMS"),
+            config_apply,
             server_port,
             server_host,
             target_dir,
             foundry_args,
             foundry_script,
+            scan_excludes,
+            report_data_usage,
+            asset_gc,
+            warm_start,
+            smoke_test_world,
+            smoke_test_port,
+            smoke_test_timeout,
+            smoke_test_canary,
+            export_worlds,
+            export_interval_hours,
+            export_keep_last,
+            admin_api_port,
+            healthcheck_port,
+            admin_api_token,
+            #[cfg(feature = "full")]
+            webhook_secret,
+            webhooks,
+            alert_rules,
+            #[cfg(feature = "full")]
+            alert_cooldown_minutes,
+            backup_schedule,
+            backup_keep_last,
+            backup_keep_days,
+            backup_when_idle,
+            background_jobs,
+            backup_s3,
+            restore_test_schedule,
+            restore_test_port,
+            return_to_setup_after,
+            return_to_setup_schedule,
+            idle_shutdown_minutes,
+            idle_shutdown_exit,
+            discord_bot_token,
+            #[cfg(feature = "full")]
+            discord_allowed_role,
+            #[cfg(feature = "full")]
+            discord_prefix,
+            mqtt_host,
+            #[cfg(feature = "full")]
+            mqtt_port,
+            #[cfg(feature = "full")]
+            mqtt_username,
+            #[cfg(feature = "full")]
+            mqtt_password,
+            #[cfg(feature = "full")]
+            mqtt_topic_prefix,
+            #[cfg(feature = "full")]
+            mqtt_discovery_prefix,
+            smtp_host,
+            #[cfg(feature = "full")]
+            smtp_port,
+            #[cfg(feature = "full")]
+            smtp_tls,
+            #[cfg(feature = "full")]
+            smtp_username,
+            #[cfg(feature = "full")]
+            smtp_password,
+            #[cfg(feature = "full")]
+            smtp_from,
+            #[cfg(feature = "full")]
+            smtp_to,
+            notify_webhook_url,
+            #[cfg(feature = "full")]
+            notify_webhook_format,
+            #[cfg(feature = "full")]
+            notify_webhook_min_severity,
+            notify_disk_free_mb,
+            disk_critical_free_mb,
+            data_dir_quota_mb,
+            heartbeat_url,
+            #[cfg(feature = "full")]
+            heartbeat_failure_url,
+            #[cfg(feature = "full")]
+            heartbeat_interval,
+            startup_timeout,
+            watchdog_interval,
+            watchdog_failures,
+            preflight_min_free_mb,
+            restart_policy,
+            max_restarts,
+            shutdown_timeout,
+            shutdown_inhibit_timeout,
+            restart_warning,
+            auto_disable_modules,
+            allow_major_migration,
+            clock_check_url,
+            release_notes_url,
+            foundry_release_path,
+            foundry_download,
+            foundry_license_key,
+            auto_update,
+            foundry_account,
+            game_user,
+            update_channel,
+            update_check_url,
+            release_metadata,
+            proxy_port,
+            proxy_latency_ms,
+            proxy_jitter_ms,
+            proxy_drop_percent,
+            proxy_record_dir,
+            proxy_headers,
+            proxy_max_connections_per_ip,
+            proxy_header_timeout,
+            proxy_auth,
+            tls,
+            tls_port,
+            tls_http_port,
+            join_url,
+            join_qr,
+            state_sync,
+            state_sync_interval,
+            standby,
+            preload_scenes,
+            #[cfg(feature = "full")]
+            preload_at,
+            branding_dir,
+            custom_fonts,
+            settings_presets_dir,
+            log_file,
+            log_file_max_mb,
+            instances,
+            external_timeouts,
+            external_startup_budget,
+            external_shutdown_budget,
+            temp_file_max_age,
+            memory_guardrails,
+            event_log,
+            allow_state_downgrade,
         }
     }
 }
 
+/// Headers the proxy adds, defaulting to the `public` mode when players join over HTTPS
+fn proxy_headers_config(join_url: &str) -> SecurityHeaders {
+    let default_mode = if join_url.starts_with("https://") {
+        "public"
+    } else {
+        "lan"
+    };
+    let mode = configfile::var("PROXY_HEADERS")
+        .map(|m| m.to_lowercase())
+        .unwrap_or_else(|_| default_mode.to_string());
+    let mut headers = SecurityHeaders::for_mode(&mode).unwrap_or_else(|| {
+        tracing::warn!(
+            "⚠️ Unknown PROXY_HEADERS mode '{}', using '{}'",
+            mode,
+            default_mode
+        );
+        SecurityHeaders::for_mode(default_mode).unwrap_or_default()
+    });
+
+    // Set to override the mode's value, set empty to leave the header to Foundry
+    for (name, value) in [
+        ("PROXY_HSTS", &mut headers.hsts),
+        ("PROXY_CSP", &mut headers.csp),
+        ("PROXY_FRAME_OPTIONS", &mut headers.frame_options),
+    ] {
+        if let Ok(configured) = configfile::var(name) {
+            *value = Some(configured).filter(|v| !v.is_empty());
+        }
+    }
+    headers.cors_origins = configfile::var("PROXY_CORS_ORIGINS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|o| o.trim().trim_end_matches('/').to_string())
+        .filter(|o| !o.is_empty())
+        .collect();
+    headers
+}
+
+/// Login the proxy asks for, a forward-auth header takes precedence over basic auth users
+fn proxy_auth_config() -> Option<AuthGate> {
+    let header = configfile::var("PROXY_AUTH_HEADER")
+        .ok()
+        .filter(|h| !h.is_empty());
+    let users = secrets::from_env("PROXY_AUTH_USERS");
+    match (header, users) {
+        (Some(header), users) => {
+            if users.is_some() {
+                tracing::warn!(
+                    "⚠️ PROXY_AUTH_HEADER and PROXY_AUTH_USERS are both set, only the header is checked"
+                );
+            }
+            Some(AuthGate::ForwardAuth { header })
+        }
+        (None, Some(users)) => Some(AuthGate::basic(&users)),
+        (None, None) => None,
+    }
+}
+
+/// Certificate for built-in TLS: files from `TLS_CERT` and `TLS_KEY`, or one ordered for
+/// `TLS_DOMAIN` from Let's Encrypt or the CA in `TLS_ACME_DIRECTORY`
+fn tls_config() -> Option<CertificateSource> {
+    let cert = configfile::var("TLS_CERT").ok().filter(|v| !v.is_empty());
+    let key = configfile::var("TLS_KEY").ok().filter(|v| !v.is_empty());
+    let domain = configfile::var("TLS_DOMAIN").ok().filter(|v| !v.is_empty());
+    match (cert, key, domain) {
+        (Some(cert), Some(key), _) => Some(CertificateSource::Files {
+            cert: cert.into(),
+            key: key.into(),
+        }),
+        (None, None, Some(domain)) => {
+            let Some(email) = configfile::var("TLS_ACME_EMAIL")
+                .ok()
+                .filter(|v| !v.is_empty())
+            else {
+                tracing::warn!("⚠️ TLS_DOMAIN needs TLS_ACME_EMAIL, TLS stays off");
+                return None;
+            };
+            Some(CertificateSource::Acme {
+                domain: domain.trim().to_lowercase(),
+                email,
+                directory: configfile::var("TLS_ACME_DIRECTORY")
+                    .ok()
+                    .filter(|v| !v.is_empty())
+                    .unwrap_or_else(|| acme::LETS_ENCRYPT.to_string()),
+            })
+        }
+        (None, None, None) => None,
+        _ => {
+            tracing::warn!("⚠️ TLS_CERT and TLS_KEY have to be set together, TLS stays off");
+            None
+        }
+    }
+}
+
+/// Manifest URLs of `name`, separated by commas, or the path of a file listing them one per
+/// line, e.g. as a YAML list. Blank lines and `#` comments are skipped.
+fn manifest_urls(name: &str) -> Vec<String> {
+    let Some(value) = configfile::var(name).ok().filter(|v| !v.trim().is_empty()) else {
+        return Vec::new();
+    };
+    if value.contains("://") {
+        return value
+            .split(',')
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+            .collect();
+    }
+    match fs::read_to_string(value.trim()) {
+        Ok(contents) => hostfs::normalize_text(&contents)
+            .lines()
+            .map(|line| line.split(" #").next().unwrap_or_default().trim())
+            .map(|line| line.strip_prefix('-').unwrap_or(line).trim())
+            .map(|line| line.trim_matches(['"', '\''].as_slice()))
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect(),
+        Err(e) => {
+            tracing::warn!("⚠️ Failed to read {} {}: {}", name, value, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Priority and concurrency of scheduled backups, test restores, exports and state syncs
+fn background_jobs(foundry_port: u16) -> JobLimits {
+    let nice = configfile::var("BACKGROUND_NICE")
+        .ok()
+        .and_then(|n| n.parse::<i32>().ok())
+        .unwrap_or(10)
+        .clamp(0, 19);
+    let io_class = configfile::var("BACKGROUND_IO_CLASS")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            IoClass::parse(&v).unwrap_or_else(|| {
+                tracing::warn!(
+                    "⚠️ Ignoring BACKGROUND_IO_CLASS, expected idle, best-effort or none: {}",
+                    v
+                );
+                IoClass::BestEffort
+            })
+        })
+        .unwrap_or(IoClass::BestEffort);
+    let concurrency = configfile::var("BACKGROUND_CONCURRENCY")
+        .ok()
+        .and_then(|n| n.parse::<usize>().ok())
+        .unwrap_or(1)
+        .max(1);
+    let pause_while_playing = configfile::var("BACKGROUND_WHEN_IDLE")
+        .map(|v| v.to_lowercase() == "true")
+        .unwrap_or(false);
+    JobLimits {
+        nice,
+        io_class,
+        concurrency,
+        pause_while_playing,
+        foundry_port,
+    }
+}
+
+/// Options of `Config/options.json` from `FOUNDRY_*`, invalid values are skipped with a warning
+fn foundry_options(server_port: u16) -> FoundryOptions {
+    fn text(name: &str) -> Option<String> {
+        configfile::var(name).ok().filter(|v| !v.trim().is_empty())
+    }
+    fn flag(name: &str) -> Option<bool> {
+        let value = text(name)?;
+        match value.trim().to_lowercase().as_str() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => {
+                tracing::warn!("⚠️ Ignoring {}, expected true or false: {}", name, value);
+                None
+            }
+        }
+    }
+    fn number(name: &str) -> Option<u16> {
+        let value = text(name)?;
+        value
+            .trim()
+            .parse()
+            .inspect_err(|_| tracing::warn!("⚠️ Ignoring {}, expected a port: {}", name, value))
+            .ok()
+    }
+
+    let port = number("FOUNDRY_PORT");
+    if port.is_some_and(|port| port != server_port) {
+        tracing::warn!(
+            "⚠️ FOUNDRY_PORT differs from SERVER_PORT, Foundry listens on {}",
+            server_port
+        );
+    }
+    FoundryOptions {
+        port: port.map(|_| server_port),
+        hostname: text("FOUNDRY_HOSTNAME"),
+        local_hostname: text("FOUNDRY_LOCAL_HOSTNAME"),
+        // Foundry wants the bare path, `/foundry/` as well as `foundry` are accepted here
+        route_prefix: text("FOUNDRY_ROUTE_PREFIX").map(|p| p.trim_matches('/').to_string()),
+        proxy_ssl: flag("FOUNDRY_PROXY_SSL"),
+        proxy_port: number("FOUNDRY_PROXY_PORT"),
+        upnp: flag("FOUNDRY_UPNP"),
+        language: text("FOUNDRY_LANGUAGE"),
+        world: text("FOUNDRY_WORLD"),
+        update_channel: text("FOUNDRY_UPDATE_CHANNEL"),
+        telemetry: flag("FOUNDRY_TELEMETRY"),
+        compress_static: flag("FOUNDRY_COMPRESS_STATIC"),
+        compress_socket: flag("FOUNDRY_COMPRESS_WEBSOCKET"),
+        ssl_cert: text("FOUNDRY_SSL_CERT"),
+        ssl_key: text("FOUNDRY_SSL_KEY"),
+    }
+}
+
+/// Bucket configured by `<name>_BUCKET`, `<name>_ENDPOINT`, `<name>_REGION`, `<name>_PREFIX`
+/// and the access keys, `feature` names what is disabled when the keys are missing
+fn object_store_config(name: &str, feature: &str) -> Option<ObjectStoreConfig> {
+    let bucket = configfile::var(format!("{}_BUCKET", name))
+        .ok()
+        .filter(|b| !b.is_empty())?;
+    let region =
+        configfile::var(format!("{}_REGION", name)).unwrap_or_else(|_| "us-east-1".to_string());
+    let endpoint = configfile::var(format!("{}_ENDPOINT", name))
+        .ok()
+        .filter(|e| !e.is_empty())
+        .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", region));
+    let (Some(access_key), Some(secret_key)) = (
+        secrets::from_env(&format!("{}_ACCESS_KEY_ID", name)),
+        secrets::from_env(&format!("{}_SECRET_ACCESS_KEY", name)),
+    ) else {
+        tracing::warn!(
+            "⚠️ {0}_BUCKET is set without {0}_ACCESS_KEY_ID and {0}_SECRET_ACCESS_KEY, {1} is disabled",
+            name,
+            feature
+        );
+        return None;
+    };
+    Some(ObjectStoreConfig {
+        endpoint,
+        bucket,
+        region,
+        prefix: configfile::var(format!("{}_PREFIX", name)).unwrap_or_default(),
+        access_key,
+        secret_key,
+    })
+}
+
+/// `STATIC_FILES_DIR` is only honoured by builds that may read files next to the binary
+#[cfg(not(feature = "self-contained"))]
+fn static_files_dir() -> Option<String> {
+    configfile::var("STATIC_FILES_DIR")
+        .ok()
+        .filter(|d| !d.is_empty())
+}
+
+#[cfg(feature = "self-contained")]
+fn static_files_dir() -> Option<String> {
+    if configfile::var("STATIC_FILES_DIR").is_ok() {
+        tracing::warn!("STATIC_FILES_DIR is ignored, this build only serves its embedded assets");
+    }
+    None
+}
+
 pub(crate) fn get_target_directory() -> String {
     // Check for TARGET_DIR first, then APPLICATION_DIR, then fallback
-    env::var("TARGET_DIR").unwrap_or_else(|_| {
-        env::var("APPLICATION_DIR").unwrap_or_else(|_| {
+    configfile::var("TARGET_DIR").unwrap_or_else(|_| {
+        configfile::var("APPLICATION_DIR").unwrap_or_else(|_| {
             let mut dir = env::current_dir().expect("Failed to get current directory");
             dir.push("tmp");
             // Make sure the directory exists
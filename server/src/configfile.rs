@@ -0,0 +1,781 @@
+use anyhow::{Context, Result, anyhow, bail};
+use std::collections::BTreeMap;
+use std::env::{self, VarError};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::compat;
+use crate::hostfs;
+
+/// Name of the config file in `DATA_DIR` read when `CONFIG_FILE` is not set
+pub const DEFAULT_FILE_NAME: &str = "container-config.toml";
+
+/// Everything the launcher is configured with: the environment, and below it the config file.
+///
+/// The directories every module works in are typed fields, `utils::paths` answers them. The
+/// options of single subsystems are read through `var` into `AppConfig`, so a setting works
+/// from either place.
+pub struct Settings {
+    /// Where the Foundry VTT application is installed
+    pub application_dir: String,
+    /// Data directory for user data
+    pub data_dir: String,
+    /// Directory for backups and exports, kept outside the data volume by default
+    pub backup_dir: String,
+    /// All worlds of the data volume, `Data/worlds` in `data_dir`
+    pub worlds_dir: PathBuf,
+    /// Diagnostic reports, heap snapshots and profiles of the Foundry process
+    pub diagnostics_dir: PathBuf,
+    /// Data directories of the `INSTANCES`, one per name
+    pub instances_dir: PathBuf,
+    /// Lifecycle hook scripts, one directory per event like `pre-start.d`
+    pub hooks_dir: PathBuf,
+    /// Where the wrapper keeps its own state, inside the data volume
+    pub wrapper_state_dir: PathBuf,
+    /// Downloaded release and package archives, kept for the next install when set
+    pub cache_dir: Option<PathBuf>,
+    /// Nothing is downloaded, releases and packages install from `cache_dir`
+    pub offline: bool,
+    /// The file values were read from, `None` without one
+    pub file: Option<PathBuf>,
+    /// Values of the file keyed by the environment variable they stand for
+    values: BTreeMap<String, String>,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Read the config file, called once at startup. A file that cannot be read or parsed stops
+/// the start, silently running with half of it would be worse.
+pub fn init() -> Result<&'static Settings> {
+    let _ = SETTINGS.set(Settings::load()?);
+    Ok(settings())
+}
+
+pub fn settings() -> &'static Settings {
+    // Only the environment when `init` did not run yet
+    SETTINGS.get_or_init(|| Settings::new(None, defaults()))
+}
+
+#[cfg(not(test))]
+fn defaults() -> BTreeMap<String, String> {
+    BTreeMap::new()
+}
+
+/// Tests work below a directory of their own instead of `/foundrydata`
+#[cfg(test)]
+fn defaults() -> BTreeMap<String, String> {
+    let data_dir = env::temp_dir().join(format!("foundry-watcher-test-{}", std::process::id()));
+    BTreeMap::from([(
+        "DATA_DIR".to_string(),
+        data_dir.to_string_lossy().to_string(),
+    )])
+}
+
+/// Value of a setting like `env::var` answers it, the environment wins over the file
+pub fn var(name: impl AsRef<str>) -> Result<String, VarError> {
+    settings().var(name.as_ref())
+}
+
+/// Every setting of the environment and the file, e.g. to fingerprint the configuration
+pub fn vars() -> Vec<(String, String)> {
+    settings().vars()
+}
+
+impl Settings {
+    fn load() -> Result<Self> {
+        let explicit = env::var("CONFIG_FILE").ok().filter(|path| !path.is_empty());
+        let path = explicit.clone().map(PathBuf::from).unwrap_or_else(|| {
+            PathBuf::from(env::var("DATA_DIR").unwrap_or_else(|_| "/foundrydata".to_string()))
+                .join(DEFAULT_FILE_NAME)
+        });
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => hostfs::normalize_text(&contents),
+            // Only a file named by CONFIG_FILE has to exist
+            Err(e) if e.kind() == ErrorKind::NotFound && explicit.is_none() => {
+                return Ok(Self::new(None, BTreeMap::new()));
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        let values =
+            parse(&path, &contents).with_context(|| format!("Invalid {}", path.display()))?;
+        Ok(Self::new(Some(path), values))
+    }
+
+    fn new(file: Option<PathBuf>, values: BTreeMap<String, String>) -> Self {
+        let mut settings = Self {
+            application_dir: String::new(),
+            data_dir: String::new(),
+            backup_dir: String::new(),
+            worlds_dir: PathBuf::new(),
+            diagnostics_dir: PathBuf::new(),
+            instances_dir: PathBuf::new(),
+            hooks_dir: PathBuf::new(),
+            wrapper_state_dir: PathBuf::new(),
+            cache_dir: None,
+            offline: false,
+            file,
+            values,
+        };
+        settings.application_dir = settings
+            .var("APPLICATION_DIR")
+            .unwrap_or_else(|_| "/foundryvtt".to_string());
+        settings.data_dir = settings
+            .var("DATA_DIR")
+            .unwrap_or_else(|_| "/foundrydata".to_string());
+        settings.backup_dir = settings
+            .var("BACKUP_DIR")
+            .unwrap_or_else(|_| "/foundrybackups".to_string());
+        let data_dir = PathBuf::from(&settings.data_dir);
+        settings.worlds_dir = data_dir.join("Data").join("worlds");
+        settings.diagnostics_dir = data_dir.join("diagnostics");
+        settings.instances_dir = data_dir.join("instances");
+        settings.hooks_dir = data_dir.join("hooks");
+        settings.wrapper_state_dir = data_dir.join(".wrapper");
+        settings.cache_dir = settings
+            .var("CACHE_DIR")
+            .ok()
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from);
+        settings.offline = settings
+            .var("OFFLINE")
+            .is_ok_and(|v| v.to_lowercase() == "true");
+        settings
+    }
+
+    /// Value of `name`, or of an old name of it while `name` itself is not set
+    pub fn var(&self, name: &str) -> Result<String, VarError> {
+        match self.var_exact(name) {
+            Err(VarError::NotPresent) => compat::old_names(name)
+                .iter()
+                .find_map(|old| self.var_exact(old).ok())
+                .ok_or(VarError::NotPresent),
+            found => found,
+        }
+    }
+
+    /// Value of exactly `name`, without its old names
+    pub fn var_exact(&self, name: &str) -> Result<String, VarError> {
+        match env::var(name) {
+            Err(VarError::NotPresent) => self.values.get(name).cloned().ok_or(VarError::NotPresent),
+            found => found,
+        }
+    }
+
+    pub fn vars(&self) -> Vec<(String, String)> {
+        let mut vars: BTreeMap<String, String> = self.values.clone();
+        vars.extend(env::vars());
+        vars.into_iter().collect()
+    }
+
+    /// Names the file sets that the environment overrides
+    pub fn overridden(&self) -> Vec<&str> {
+        self.values
+            .keys()
+            .filter(|name| env::var_os(name).is_some())
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// Values of a TOML file, or a YAML one by its extension, keyed by environment variable
+fn parse(path: &Path, contents: &str) -> Result<BTreeMap<String, String>> {
+    let yaml = path
+        .extension()
+        .is_some_and(|ext| ext == "yaml" || ext == "yml");
+    if yaml {
+        parse_yaml(contents)
+    } else {
+        parse_toml(contents)
+    }
+}
+
+/// `[backup]` with `keep_last = 7` stands for `BACKUP_KEEP_LAST`, like `backup.keep_last` or
+/// `BACKUP_KEEP_LAST` at the top does
+fn env_name(path: &[String]) -> String {
+    path.iter()
+        .map(|part| part.trim().to_uppercase().replace(['-', '.'], "_"))
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+fn insert(values: &mut BTreeMap<String, String>, path: &[String], value: String) -> Result<()> {
+    let name = env_name(path);
+    if values.insert(name.clone(), value).is_some() {
+        bail!("{} is set twice", name);
+    }
+    Ok(())
+}
+
+/// The part of TOML settings need: tables, dotted keys, strings, numbers, booleans and arrays
+/// of them. Arrays become comma separated lists like in the environment.
+fn parse_toml(contents: &str) -> Result<BTreeMap<String, String>> {
+    let mut parser = TomlParser {
+        chars: contents.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    parser
+        .document()
+        .map_err(|e| anyhow!("line {}: {}", parser.line, e))
+}
+
+struct TomlParser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl TomlParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.next();
+        }
+    }
+
+    /// Whitespace, newlines and comments, e.g. between the items of an array
+    fn skip_blank(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\n') => {
+                    self.next();
+                }
+                Some('#') => {
+                    while !matches!(self.peek(), None | Some('\n')) {
+                        self.next();
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<()> {
+        self.skip_spaces();
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.next();
+            }
+        }
+        match self.next() {
+            None | Some('\n') => Ok(()),
+            Some(c) => bail!("unexpected '{}' after the value", c),
+        }
+    }
+
+    fn document(&mut self) -> Result<BTreeMap<String, String>> {
+        let mut values = BTreeMap::new();
+        let mut table: Vec<String> = Vec::new();
+        loop {
+            self.skip_blank();
+            match self.peek() {
+                None => return Ok(values),
+                Some('[') => {
+                    self.next();
+                    if self.peek() == Some('[') {
+                        bail!("arrays of tables are not supported");
+                    }
+                    table = self.key()?;
+                    if self.peek() != Some(']') {
+                        bail!("expected ']' after the table name");
+                    }
+                    self.next();
+                    self.end_of_line()?;
+                }
+                Some(_) => {
+                    let mut path = table.clone();
+                    path.extend(self.key()?);
+                    if self.peek() != Some('=') {
+                        bail!("expected '=' after {}", path.join("."));
+                    }
+                    self.next();
+                    self.skip_spaces();
+                    let value = self.value()?;
+                    insert(&mut values, &path, value)?;
+                    self.end_of_line()?;
+                }
+            }
+        }
+    }
+
+    /// Bare, quoted and dotted keys, up to `=` or `]`
+    fn key(&mut self) -> Result<Vec<String>> {
+        let mut parts = Vec::new();
+        loop {
+            self.skip_spaces();
+            let part = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let mut part = String::new();
+                    while let Some(c) = self.peek()
+                        && (c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                    {
+                        part.push(c);
+                        self.next();
+                    }
+                    part
+                }
+            };
+            if part.is_empty() {
+                bail!("expected a key");
+            }
+            parts.push(part);
+            self.skip_spaces();
+            if self.peek() != Some('.') {
+                return Ok(parts);
+            }
+            self.next();
+        }
+    }
+
+    fn value(&mut self) -> Result<String> {
+        match self.peek() {
+            Some('[') => {
+                self.next();
+                let mut items = Vec::new();
+                loop {
+                    self.skip_blank();
+                    if self.peek() == Some(']') {
+                        self.next();
+                        return Ok(items.join(","));
+                    }
+                    if self.peek() == Some('[') {
+                        bail!("nested arrays are not supported");
+                    }
+                    items.push(self.value()?);
+                    self.skip_blank();
+                    match self.next() {
+                        Some(',') => {}
+                        Some(']') => return Ok(items.join(",")),
+                        _ => bail!("expected ',' or ']' in the array"),
+                    }
+                }
+            }
+            Some('{') => bail!("inline tables are not supported, use a [table]"),
+            Some('"') | Some('\'') if self.starts_with("\"\"\"") || self.starts_with("'''") => {
+                bail!("multi-line strings are not supported")
+            }
+            Some('"') => self.basic_string(),
+            Some('\'') => self.literal_string(),
+            _ => {
+                let mut token = String::new();
+                while let Some(c) = self.peek()
+                    && (c.is_ascii_alphanumeric() || "+-._:".contains(c))
+                {
+                    token.push(c);
+                    self.next();
+                }
+                if token.is_empty() {
+                    bail!("expected a value");
+                }
+                // 1_000 is a number, not a name
+                if token.starts_with(|c: char| c.is_ascii_digit() || c == '+' || c == '-') {
+                    token.retain(|c| c != '_');
+                }
+                Ok(token)
+            }
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String> {
+        self.next();
+        let mut value = String::new();
+        loop {
+            // Not consuming the newline, the error names the line the string started on
+            let Some(c) = self.peek().filter(|c| *c != '\n') else {
+                bail!("unterminated string");
+            };
+            self.next();
+            match c {
+                '"' => return Ok(value),
+                '\\' => value.push(match self.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('u') => {
+                        let hex: String = (0..4).filter_map(|_| self.next()).collect();
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| anyhow!("invalid escape \\u{}", hex))?
+                    }
+                    other => bail!("invalid escape \\{}", other.unwrap_or(' ')),
+                }),
+                c => value.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String> {
+        self.next();
+        let mut value = String::new();
+        loop {
+            let Some(c) = self.peek().filter(|c| *c != '\n') else {
+                bail!("unterminated string");
+            };
+            self.next();
+            match c {
+                '\'' => return Ok(value),
+                c => value.push(c),
+            }
+        }
+    }
+}
+
+/// The part of YAML settings need: nested mappings, scalars and lists of them, block (`- a`)
+/// or flow (`[a, b]`)
+fn parse_yaml(contents: &str) -> Result<BTreeMap<String, String>> {
+    let mut values = BTreeMap::new();
+    // Indentation and path of the mappings the current line may belong to
+    let mut parents: Vec<(usize, Vec<String>)> = Vec::new();
+    let mut lists: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for (index, raw) in contents.lines().enumerate() {
+        let line = strip_yaml_comment(raw);
+        let content = line.trim_start();
+        if content.is_empty() || content == "---" {
+            continue;
+        }
+        let fail = |message: String| anyhow!("line {}: {}", index + 1, message);
+        let indent = line.len() - content.len();
+        if line[..indent].contains('\t') {
+            return Err(fail("tabs are not allowed for indentation".to_string()));
+        }
+
+        if let Some(item) = content
+            .strip_prefix("- ")
+            .or((content == "-").then_some(""))
+        {
+            // Items may be indented like their key or deeper
+            while parents.last().is_some_and(|(level, _)| *level > indent) {
+                parents.pop();
+            }
+            let Some((_, path)) = parents.last() else {
+                return Err(fail("a list needs a key".to_string()));
+            };
+            if split_yaml_key(item).is_some() {
+                return Err(fail("lists of mappings are not supported".to_string()));
+            }
+            let item = yaml_scalar(item.trim()).map_err(|e| fail(e.to_string()))?;
+            lists.entry(env_name(path)).or_default().push(item);
+            continue;
+        }
+
+        let Some((key, value)) = split_yaml_key(content) else {
+            return Err(fail(format!("expected 'key: value', got '{}'", content)));
+        };
+        while parents.last().is_some_and(|(level, _)| *level >= indent) {
+            parents.pop();
+        }
+        let mut path = parents
+            .last()
+            .map(|(_, path)| path.clone())
+            .unwrap_or_default();
+        path.push(yaml_scalar(key).map_err(|e| fail(e.to_string()))?);
+
+        let value = value.trim();
+        if value.is_empty() {
+            // A mapping or a block list follows
+            parents.push((indent, path));
+        } else if let Some(flow) = value.strip_prefix('[') {
+            let Some(flow) = flow.strip_suffix(']') else {
+                return Err(fail("flow lists must end on their line".to_string()));
+            };
+            let items = flow
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(yaml_scalar)
+                .collect::<Result<Vec<_>>>()
+                .map_err(|e| fail(e.to_string()))?;
+            insert(&mut values, &path, items.join(",")).map_err(|e| fail(e.to_string()))?;
+        } else if value.starts_with('{') {
+            return Err(fail("flow mappings are not supported".to_string()));
+        } else if value.starts_with(['|', '>']) {
+            return Err(fail("multi-line strings are not supported".to_string()));
+        } else if value != "~" && value != "null" {
+            let value = yaml_scalar(value).map_err(|e| fail(e.to_string()))?;
+            insert(&mut values, &path, value).map_err(|e| fail(e.to_string()))?;
+        }
+    }
+
+    for (name, items) in lists {
+        if values.insert(name.clone(), items.join(",")).is_some() {
+            bail!("{} is set twice", name);
+        }
+    }
+    Ok(values)
+}
+
+/// A comment starts with `#` at the beginning or after whitespace, outside of quotes
+fn strip_yaml_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '#') if previous.is_whitespace() => return &line[..i],
+            _ => {}
+        }
+        previous = c;
+    }
+    line
+}
+
+/// `key: value` or `key:`, a colon inside a URL does not count
+fn split_yaml_key(content: &str) -> Option<(&str, &str)> {
+    content
+        .split_once(": ")
+        .or_else(|| content.strip_suffix(':').map(|key| (key, "")))
+}
+
+fn yaml_scalar(value: &str) -> Result<String> {
+    if value.starts_with('"') {
+        let mut parser = TomlParser {
+            chars: value.chars().collect(),
+            pos: 0,
+            line: 1,
+        };
+        let parsed = parser.basic_string()?;
+        if parser.pos != parser.chars.len() {
+            bail!("unexpected text after {}", value);
+        }
+        return Ok(parsed);
+    }
+    if let Some(inner) = value.strip_prefix('\'') {
+        let Some(inner) = inner.strip_suffix('\'') else {
+            bail!("unterminated string");
+        };
+        return Ok(inner.replace("''", "'"));
+    }
+    Ok(value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toml(contents: &str) -> BTreeMap<String, String> {
+        parse_toml(contents).unwrap()
+    }
+
+    fn yaml(contents: &str) -> BTreeMap<String, String> {
+        parse_yaml(contents).unwrap()
+    }
+
+    fn error(result: Result<BTreeMap<String, String>>) -> String {
+        format!("{:#}", result.unwrap_err())
+    }
+
+    #[test]
+    fn toml_tables_and_keys_become_variable_names() {
+        let values = toml(
+            "application_host = \"vtt.example.com\" # the public name\n\
+             \n\
+             [backup]\n\
+             keep-last = 7\n\
+             s3.bucket = 'games'\n\
+             \"keep_days\" = 30\n\
+             [smtp.tls]\n\
+             enabled = true\n",
+        );
+        let expected = [
+            ("APPLICATION_HOST", "vtt.example.com"),
+            ("BACKUP_KEEP_LAST", "7"),
+            ("BACKUP_S3_BUCKET", "games"),
+            ("BACKUP_KEEP_DAYS", "30"),
+            ("SMTP_TLS_ENABLED", "true"),
+        ];
+        assert_eq!(values.len(), expected.len());
+        for (name, value) in expected {
+            assert_eq!(values[name], value, "{}", name);
+        }
+    }
+
+    #[test]
+    fn toml_values_are_read_like_the_environment_writes_them() {
+        let values = toml(
+            "escaped = \"a\\tb\\n\\\"c\\\" \\u00e9\"\n\
+             literal = 'C:\\data'\n\
+             number = 1_000\n\
+             negative = -5\n\
+             version = 13.345\n\
+             name = my_world\n\
+             modules = [\n\
+             \x20   \"https://example.com/a.json\", # first\n\
+             \x20   'b',\n\
+             ]\n\
+             empty = []\n",
+        );
+        assert_eq!(values["ESCAPED"], "a\tb\n\"c\" é");
+        assert_eq!(values["LITERAL"], "C:\\data");
+        assert_eq!(values["NUMBER"], "1000");
+        assert_eq!(values["NEGATIVE"], "-5");
+        assert_eq!(values["VERSION"], "13.345");
+        assert_eq!(values["NAME"], "my_world");
+        assert_eq!(values["MODULES"], "https://example.com/a.json,b");
+        assert_eq!(values["EMPTY"], "");
+    }
+
+    #[test]
+    fn toml_errors_name_the_line() {
+        let cases = [
+            (
+                "a = 1\n[[servers]]\n",
+                "line 2: arrays of tables are not supported",
+            ),
+            ("a = { b = 1 }\n", "line 1: inline tables are not supported"),
+            (
+                "a = \"\"\"x\"\"\"\n",
+                "line 1: multi-line strings are not supported",
+            ),
+            ("a = [[1]]\n", "line 1: nested arrays are not supported"),
+            ("a = [1 2]\n", "line 1: expected ',' or ']' in the array"),
+            ("a = \"open\n", "line 1: unterminated string"),
+            ("a = 'open\n", "line 1: unterminated string"),
+            ("a = \"\\q\"\n", "line 1: invalid escape \\q"),
+            ("\n\na 1\n", "line 3: expected '=' after a"),
+            ("a = 1 2\n", "line 1: unexpected '2' after the value"),
+            ("a =\n", "line 1: expected a value"),
+            ("[backup\n", "line 1: expected ']' after the table name"),
+            ("= 1\n", "line 1: expected a key"),
+            ("a = 1\nA = 2\n", "line 2: A is set twice"),
+            (
+                "backup_keep_last = 1\n[backup]\nkeep_last = 2\n",
+                "BACKUP_KEEP_LAST is set twice",
+            ),
+        ];
+        for (contents, expected) in cases {
+            let message = error(parse_toml(contents));
+            assert!(message.contains(expected), "{:?}: {}", contents, message);
+        }
+    }
+
+    #[test]
+    fn yaml_mappings_and_lists_become_variable_names() {
+        let values = yaml(
+            "---\n\
+             application_host: vtt.example.com # the public name\n\
+             join_url: https://vtt.example.com/join\n\
+             backup:\n\
+             \x20 keep_last: 7\n\
+             \x20 s3:\n\
+             \x20   bucket: \"games # not a comment\"\n\
+             \x20 keep_days: ~\n\
+             install_modules:\n\
+             - https://example.com/a.json\n\
+             - 'it''s'\n\
+             smtp:\n\
+             \x20 to:\n\
+             \x20   - gm@example.com\n\
+             \x20 port: 587\n\
+             locales: [en, \"de\"]\n",
+        );
+        let expected = [
+            ("APPLICATION_HOST", "vtt.example.com"),
+            ("JOIN_URL", "https://vtt.example.com/join"),
+            ("BACKUP_KEEP_LAST", "7"),
+            ("BACKUP_S3_BUCKET", "games # not a comment"),
+            ("INSTALL_MODULES", "https://example.com/a.json,it's"),
+            ("SMTP_TO", "gm@example.com"),
+            ("SMTP_PORT", "587"),
+            ("LOCALES", "en,de"),
+        ];
+        assert_eq!(values.len(), expected.len(), "{:?}", values);
+        for (name, value) in expected {
+            assert_eq!(values[name], value, "{}", name);
+        }
+    }
+
+    #[test]
+    fn yaml_errors_name_the_line() {
+        let cases = [
+            (
+                "a:\n\tb: 1\n",
+                "line 2: tabs are not allowed for indentation",
+            ),
+            ("- a\n", "line 1: a list needs a key"),
+            (
+                "a:\n  - b: 1\n",
+                "line 2: lists of mappings are not supported",
+            ),
+            ("a: {b: 1}\n", "line 1: flow mappings are not supported"),
+            (
+                "a: |\n  text\n",
+                "line 1: multi-line strings are not supported",
+            ),
+            ("a: [1, 2\n", "line 1: flow lists must end on their line"),
+            ("a: \"open\n", "line 1: unterminated string"),
+            ("a: 'open\n", "line 1: unterminated string"),
+            (
+                "just text\n",
+                "line 1: expected 'key: value', got 'just text'",
+            ),
+            ("a: 1\na: 2\n", "line 2: A is set twice"),
+            ("a:\n  - 1\na: 2\n", "A is set twice"),
+        ];
+        for (contents, expected) in cases {
+            let message = error(parse_yaml(contents));
+            assert!(message.contains(expected), "{:?}: {}", contents, message);
+        }
+    }
+
+    #[test]
+    fn files_are_read_by_their_extension() {
+        let toml = parse(Path::new("container-config.toml"), "a = 1\n").unwrap();
+        assert_eq!(toml["A"], "1");
+        let yaml = parse(Path::new("container-config.yml"), "a: 1\n").unwrap();
+        assert_eq!(yaml["A"], "1");
+        assert!(parse(Path::new("container-config.yaml"), "a = 1\n").is_err());
+    }
+
+    #[test]
+    fn directories_are_derived_from_the_file() {
+        let values = BTreeMap::from([
+            ("DATA_DIR".to_string(), "/srv/foundry".to_string()),
+            ("CONFIGFILE_TEST_ONLY".to_string(), "set".to_string()),
+        ]);
+        let settings = Settings::new(None, values);
+        // The environment wins where it sets the directory itself
+        if env::var_os("DATA_DIR").is_none() {
+            assert_eq!(settings.data_dir, "/srv/foundry");
+            assert_eq!(
+                settings.worlds_dir,
+                PathBuf::from("/srv/foundry/Data/worlds")
+            );
+            assert_eq!(
+                settings.wrapper_state_dir,
+                PathBuf::from("/srv/foundry/.wrapper")
+            );
+        }
+        assert_eq!(settings.var("CONFIGFILE_TEST_ONLY").as_deref(), Ok("set"));
+        assert_eq!(
+            settings.var("CONFIGFILE_TEST_MISSING"),
+            Err(VarError::NotPresent)
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use anyhow::{Context, Result, anyhow};
+use rusty_leveldb::compressor::{CompressorId, SnappyCompressor};
+use rusty_leveldb::{DB, LdbIterator, Options};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::utils::paths;
+
+/// One document collection (actors, settings, ...) of a world's database.
+///
+/// Foundry v11+ stores each collection as a LevelDB directory under `data/<name>`, while v10
+/// and older use an append-only NeDB file at `data/<name>.db`. The database must not be opened
+/// by a running Foundry server at the same time.
+pub enum Collection {
+    LevelDb {
+        name: String,
+        db: Box<DB>,
+        /// Copied database files, removed again when the collection is dropped
+        _snapshot: Option<SnapshotDir>,
+    },
+    NeDb {
+        path: PathBuf,
+        documents: BTreeMap<String, Value>,
+    },
+}
+
+impl Collection {
+    /// Whether the world has a collection `name` in either format
+    pub fn exists(world_id: &str, name: &str) -> bool {
+        let data_dir = paths::WORLDS_DIR.join(world_id).join("data");
+        data_dir.join(name).is_dir() || data_dir.join(format!("{}.db", name)).is_file()
+    }
+
+    /// Open the collection `name` of the world `world_id`
+    pub fn open(world_id: &str, name: &str) -> Result<Self> {
+        Self::open_with(world_id, name, false)
+    }
+
+    /// Open a point in time copy of the collection, which also works while Foundry is running.
+    ///
+    /// LevelDB allows a single process only, so its files are copied aside first. Only read from
+    /// a snapshot, NeDB files are opened in place.
+    pub fn open_snapshot(world_id: &str, name: &str) -> Result<Self> {
+        Self::open_with(world_id, name, true)
+    }
+
+    /// Open a point in time copy of the compendium pack `name` of the world `world_id`, kept
+    /// below `packs` in the same formats as the collections
+    pub fn open_pack(world_id: &str, name: &str) -> Result<Self> {
+        let packs_dir = paths::WORLDS_DIR.join(world_id).join("packs");
+        Self::open_in(&packs_dir, world_id, name, true)
+    }
+
+    fn open_with(world_id: &str, name: &str, snapshot: bool) -> Result<Self> {
+        let data_dir = paths::WORLDS_DIR.join(world_id).join("data");
+        Self::open_in(&data_dir, world_id, name, snapshot)
+    }
+
+    fn open_in(data_dir: &Path, world_id: &str, name: &str, snapshot: bool) -> Result<Self> {
+        let leveldb_dir = data_dir.join(name);
+        if leveldb_dir.is_dir() {
+            let (open_dir, snapshot_dir) = if snapshot {
+                let dir = SnapshotDir::copy_of(&leveldb_dir, world_id)?;
+                (dir.0.clone(), Some(dir))
+            } else {
+                (leveldb_dir.clone(), None)
+            };
+            let options = Options {
+                create_if_missing: false,
+                // Match the compression of Foundry's LevelDB so compactions stay compatible
+                compressor: SnappyCompressor::ID,
+                ..Options::default()
+            };
+            let db = DB::open(&open_dir, options).map_err(|e| {
+                anyhow!(
+                    "Failed to open {} (is Foundry still running?): {}",
+                    leveldb_dir.display(),
+                    e
+                )
+            })?;
+            debug!("Opened LevelDB collection {}", open_dir.display());
+            return Ok(Self::LevelDb {
+                name: name.to_string(),
+                db: Box::new(db),
+                _snapshot: snapshot_dir,
+            });
+        }
+
+        let nedb_file = data_dir.join(format!("{}.db", name));
+        if nedb_file.is_file() {
+            let documents = read_nedb(&nedb_file)?;
+            debug!("Opened NeDB collection {}", nedb_file.display());
+            return Ok(Self::NeDb {
+                path: nedb_file,
+                documents,
+            });
+        }
+
+        Err(anyhow!(
+            "World {} has no '{}' collection in {}",
+            world_id,
+            name,
+            data_dir.display()
+        ))
+    }
+
+    /// All primary documents of the collection, embedded documents are not included
+    pub fn documents(&mut self) -> Result<Vec<Value>> {
+        match self {
+            Self::LevelDb { name, db, .. } => {
+                let prefix = format!("!{}!", name);
+                scan_prefix(db, &prefix)
+            }
+            Self::NeDb { documents, .. } => Ok(documents.values().cloned().collect()),
+        }
+    }
+
+    /// Embedded documents of `parent` stored under `field`, e.g. the `pages` of a journal entry
+    pub fn embedded(&mut self, field: &str, parent: &Value) -> Result<Vec<Value>> {
+        match self {
+            Self::LevelDb { name, db, .. } => {
+                // v11+ stores embedded documents as `!<collection>.<field>!<parent id>.<id>`
+                let parent_id = parent["_id"].as_str().unwrap_or_default();
+                let prefix = format!("!{}.{}!{}.", name, field, parent_id);
+                scan_prefix(db, &prefix)
+            }
+            // NeDB keeps embedded documents inline in their parent
+            Self::NeDb { .. } => Ok(parent[field]
+                .as_array()
+                .map(|docs| docs.iter().filter(|d| d.is_object()).cloned().collect())
+                .unwrap_or_default()),
+        }
+    }
+
+    /// Insert or replace a primary document, keyed by its `_id`
+    pub fn put(&mut self, document: &Value) -> Result<()> {
+        let id = document["_id"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Document has no _id"))?
+            .to_string();
+        let serialized = serde_json::to_string(document)?;
+
+        match self {
+            Self::LevelDb { name, db, .. } => {
+                let key = format!("!{}!{}", name, id);
+                db.put(key.as_bytes(), serialized.as_bytes())
+                    .map_err(|e| anyhow!("Failed to write {}: {}", key, e))?;
+                db.flush()
+                    .map_err(|e| anyhow!("Failed to flush LevelDB: {}", e))
+            }
+            Self::NeDb { path, documents } => {
+                // NeDB is append-only, the last line for an id wins when Foundry loads the file
+                let mut file = OpenOptions::new()
+                    .append(true)
+                    .open(&*path)
+                    .with_context(|| format!("Failed to open {}", path.display()))?;
+                writeln!(file, "{}", serialized)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                documents.insert(id, document.clone());
+                Ok(())
+            }
+        }
+    }
+
+    /// Pass every document, embedded ones included, to `change` and write back those it
+    /// reports as changed. Returns the number of changed documents, with `dry_run` nothing
+    /// is written.
+    pub fn rewrite(
+        &mut self,
+        dry_run: bool,
+        mut change: impl FnMut(&mut Value) -> bool,
+    ) -> Result<usize> {
+        match self {
+            Self::LevelDb { db, .. } => {
+                // The directory only holds this collection, its embedded collections included
+                let mut changed = Vec::new();
+                {
+                    let mut iter = db.new_iter().context("Failed to iterate LevelDB")?;
+                    iter.seek_to_first();
+                    let (mut key, mut value) = (Vec::new(), Vec::new());
+                    while iter.valid() && iter.current(&mut key, &mut value) {
+                        if key.starts_with(b"!")
+                            && let Ok(mut document) = serde_json::from_slice::<Value>(&value)
+                            && change(&mut document)
+                        {
+                            changed.push((key.clone(), serde_json::to_vec(&document)?));
+                        }
+                        iter.advance();
+                    }
+                }
+                if dry_run {
+                    return Ok(changed.len());
+                }
+                for (key, value) in &changed {
+                    db.put(key, value).map_err(|e| {
+                        anyhow!("Failed to write {}: {}", String::from_utf8_lossy(key), e)
+                    })?;
+                }
+                db.flush()
+                    .map_err(|e| anyhow!("Failed to flush LevelDB: {}", e))?;
+                Ok(changed.len())
+            }
+            Self::NeDb { documents, .. } => {
+                let mut changed = Vec::new();
+                for document in documents.values() {
+                    let mut document = document.clone();
+                    if change(&mut document) {
+                        changed.push(document);
+                    }
+                }
+                if !dry_run {
+                    for document in &changed {
+                        self.put(document)?;
+                    }
+                }
+                Ok(changed.len())
+            }
+        }
+    }
+}
+
+/// Temporary copy of a LevelDB directory
+pub struct SnapshotDir(PathBuf);
+
+impl SnapshotDir {
+    fn copy_of(source: &Path, world_id: &str) -> Result<Self> {
+        let kind = source
+            .parent()
+            .and_then(Path::file_name)
+            .unwrap_or_default()
+            .to_string_lossy();
+        let name = source.file_name().unwrap_or_default().to_string_lossy();
+        let dir = paths::WRAPPER_STATE_DIR.join("snapshots").join(format!(
+            "{}-{}-{}-{}",
+            world_id,
+            kind,
+            name,
+            std::process::id()
+        ));
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            // The lock file belongs to the running server
+            if entry.file_name() == "LOCK" || !entry.file_type()?.is_file() {
+                continue;
+            }
+            fs::copy(entry.path(), dir.join(entry.file_name()))
+                .with_context(|| format!("Failed to copy {}", entry.path().display()))?;
+        }
+        Ok(Self(dir))
+    }
+}
+
+impl Drop for SnapshotDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Generate a 16 character alphanumeric document id in the format Foundry uses
+pub fn new_document_id(seed: &str) -> String {
+    const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+    let nanos = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default();
+    let digest = Sha256::digest(format!("{}:{}", seed, nanos));
+    digest
+        .iter()
+        .take(16)
+        .map(|b| ALPHABET[*b as usize % ALPHABET.len()] as char)
+        .collect()
+}
+
+/// All documents whose LevelDB key starts with `prefix`
+fn scan_prefix(db: &mut DB, prefix: &str) -> Result<Vec<Value>> {
+    let mut iter = db.new_iter().context("Failed to iterate LevelDB")?;
+    iter.seek(prefix.as_bytes());
+
+    let mut documents = Vec::new();
+    let (mut key, mut value) = (Vec::new(), Vec::new());
+    while iter.valid() && iter.current(&mut key, &mut value) {
+        if !key.starts_with(prefix.as_bytes()) {
+            break;
+        }
+        documents
+            .push(serde_json::from_slice(&value).with_context(|| {
+                format!("Malformed document {}", String::from_utf8_lossy(&key))
+            })?);
+        iter.advance();
+    }
+    Ok(documents)
+}
+
+fn read_nedb(path: &PathBuf) -> Result<BTreeMap<String, Value>> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+
+    let mut documents = BTreeMap::new();
+    for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+        let document: Value = serde_json::from_str(line)
+            .with_context(|| format!("Malformed record in {}", path.display()))?;
+        let Some(id) = document["_id"].as_str().map(|id| id.to_string()) else {
+            continue;
+        };
+        if document["$$deleted"].as_bool().unwrap_or(false) {
+            documents.remove(&id);
+        } else {
+            documents.insert(id, document);
+        }
+    }
+    Ok(documents)
+}
@@ -0,0 +1,211 @@
+use anyhow::{Context, Result, anyhow};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use tokio::time::{Duration, Instant, interval_at, sleep};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{info, warn};
+
+use crate::backup::{BackupService, BackupTrigger};
+use crate::capabilities;
+use crate::launch;
+use crate::status;
+
+const GATEWAY_URL: &str = "wss://gateway.discord.gg/?v=10&encoding=json";
+const API_URL: &str = "https://discord.com/api/v10";
+
+/// GUILD_MESSAGES | MESSAGE_CONTENT
+const INTENTS: u64 = (1 << 9) | (1 << 15);
+
+/// Minimal Discord gateway client answering `!foundry <command>` messages
+#[derive(Clone)]
+pub struct DiscordBot {
+    pub token: String,
+    /// Role id required to use any command
+    pub allowed_role: String,
+    pub prefix: String,
+    pub backup: BackupService,
+}
+
+impl DiscordBot {
+    /// Stay connected to the gateway, reconnecting with a fresh session whenever it drops
+    pub async fn run(self) {
+        let mut backoff = Duration::from_secs(5);
+        loop {
+            let connected_at = Instant::now();
+            match self.session().await {
+                Ok(()) => info!("Discord gateway closed the connection, reconnecting"),
+                Err(e) => {
+                    warn!("⚠️ Discord bot disconnected: {}", e);
+                    capabilities::failed("discord", &e);
+                }
+            }
+            // Only back off further when connections keep failing right away
+            backoff = if connected_at.elapsed() > Duration::from_secs(60) {
+                Duration::from_secs(5)
+            } else {
+                (backoff * 2).min(Duration::from_secs(300))
+            };
+            sleep(backoff).await;
+        }
+    }
+
+    async fn session(&self) -> Result<()> {
+        let (socket, _) = connect_async(GATEWAY_URL)
+            .await
+            .context("Failed to connect to the Discord gateway")?;
+        let (mut write, mut read) = socket.split();
+
+        let hello = next_payload(&mut read).await?;
+        let heartbeat_ms = hello["d"]["heartbeat_interval"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("Gateway did not send a heartbeat interval"))?;
+
+        let identify = json!({
+            "op": 2,
+            "d": {
+                "token": self.token,
+                "intents": INTENTS,
+                "properties": { "os": "linux", "browser": "foundry-watcher", "device": "foundry-watcher" },
+            }
+        });
+        write.send(Message::text(identify.to_string())).await?;
+
+        let period = Duration::from_millis(heartbeat_ms);
+        let mut heartbeat = interval_at(Instant::now() + period, period);
+        let mut sequence = Value::Null;
+        loop {
+            tokio::select! {
+                _ = heartbeat.tick() => {
+                    let beat = json!({ "op": 1, "d": sequence });
+                    write.send(Message::text(beat.to_string())).await?;
+                }
+                payload = next_payload(&mut read) => {
+                    let payload = payload?;
+                    if !payload["s"].is_null() {
+                        sequence = payload["s"].clone();
+                    }
+                    match payload["op"].as_u64() {
+                        Some(0) if payload["t"] == "READY" => {
+                            info!("🤖 Discord bot connected as {}", payload["d"]["user"]["username"]);
+                            capabilities::active("discord");
+                        }
+                        Some(0) if payload["t"] == "MESSAGE_CREATE" => {
+                            self.handle_message(&payload["d"]).await;
+                        }
+                        // Heartbeat request, reconnect and invalid session
+                        Some(1) => {
+                            let beat = json!({ "op": 1, "d": sequence });
+                            write.send(Message::text(beat.to_string())).await?;
+                        }
+                        Some(7) | Some(9) => return Ok(()),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    async fn handle_message(&self, message: &Value) {
+        let content = message["content"].as_str().unwrap_or_default();
+        let Some(command) = content.strip_prefix(&self.prefix) else {
+            return;
+        };
+        if message["author"]["bot"].as_bool().unwrap_or(false) {
+            return;
+        }
+
+        let allowed = message["member"]["roles"]
+            .as_array()
+            .is_some_and(|roles| roles.iter().any(|r| r == self.allowed_role.as_str()));
+        let reply = if !allowed {
+            "⛔ You are not allowed to control this server".to_string()
+        } else {
+            info!(
+                "Discord command '{}' from {}",
+                command.trim(),
+                message["author"]["username"]
+            );
+            // The same actions the admin API offers, called directly
+            match command.trim() {
+                "status" => status::current().summary(),
+                "restart" => {
+                    tokio::spawn(launch::restart_after_warning());
+                    "🔄 Restarting Foundry".to_string()
+                }
+                "backup" => {
+                    // Snapshots of large worlds take a while, the gateway must keep its heartbeat
+                    let bot = self.clone();
+                    let channel = message["channel_id"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string();
+                    let message_id = message["id"].as_str().map(str::to_string);
+                    tokio::spawn(async move {
+                        let service = bot.backup.clone();
+                        let reply = match tokio::task::spawn_blocking(move || {
+                            service.create(BackupTrigger::Discord)
+                        })
+                        .await
+                        {
+                            Ok(Ok(summary)) => format!(
+                                "💾 Backed up {} files to {}",
+                                summary.record.files,
+                                summary.path.display()
+                            ),
+                            Ok(Err(e)) => format!("❌ Backup failed: {:#}", e),
+                            Err(e) => format!("❌ Backup failed: {}", e),
+                        };
+                        if let Err(e) = bot.reply(&channel, message_id.as_deref(), &reply).await {
+                            warn!("Failed to answer on Discord: {}", e);
+                        }
+                    });
+                    "💾 Taking a backup".to_string()
+                }
+                _ => format!(
+                    "Commands: `{0} status`, `{0} restart`, `{0} backup`",
+                    self.prefix
+                ),
+            }
+        };
+
+        let channel = message["channel_id"].as_str().unwrap_or_default();
+        if let Err(e) = self.reply(channel, message["id"].as_str(), &reply).await {
+            warn!("Failed to answer on Discord: {}", e);
+        }
+    }
+
+    async fn reply(&self, channel: &str, message_id: Option<&str>, content: &str) -> Result<()> {
+        let response = reqwest::Client::new()
+            .post(format!("{}/channels/{}/messages", API_URL, channel))
+            .header("Authorization", format!("Bot {}", self.token))
+            .json(&json!({
+                "content": content,
+                "message_reference": message_id.map(|id| json!({ "message_id": id })),
+            }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Discord answered {}", response.status()));
+        }
+        Ok(())
+    }
+}
+
+/// Read the next JSON payload, skipping pings and other non-text frames
+async fn next_payload<S>(read: &mut S) -> Result<Value>
+where
+    S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    loop {
+        match read.next().await {
+            Some(Ok(Message::Text(text))) => return Ok(serde_json::from_str(&text)?),
+            Some(Ok(Message::Close(frame))) => {
+                return Err(anyhow!("Gateway closed the connection: {:?}", frame));
+            }
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(e.into()),
+            None => return Err(anyhow!("Gateway connection ended")),
+        }
+    }
+}
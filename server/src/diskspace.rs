@@ -0,0 +1,221 @@
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use chrono::Utc;
+use nix::sys::statvfs::statvfs;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::{PoisonError, RwLock};
+use tokio::time::{Duration, sleep};
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+use crate::api::{self, ApiConfig, Message};
+use crate::notify::{self, Notification, Severity};
+use crate::scan::ScanService;
+use crate::utils::paths;
+
+/// Share of `DATA_DIR_QUOTA_MB` from which the data directory counts as running low
+const QUOTA_WARN_PERCENT: u64 = 90;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DiskLevel {
+    Ok,
+    /// Below `NOTIFY_DISK_FREE_MB`, or the data directory used most of its quota
+    Low,
+    /// Below `DISK_CRITICAL_FREE_MB`, or the data directory exceeds its quota. Scheduled
+    /// backups are skipped and packages are not installed.
+    Critical,
+}
+
+/// Outcome of the last check, served on `GET /api/disk`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DiskReport {
+    pub level: DiskLevel,
+    /// Space available to unprivileged processes on the volume of `DATA_DIR`
+    pub free_mb: Option<u64>,
+    /// Size of the files in `DATA_DIR`, without `SCAN_EXCLUDES`
+    pub data_dir_mb: Option<u64>,
+    pub quota_mb: Option<u64>,
+    #[schema(format = DateTime)]
+    pub checked_at: String,
+}
+
+impl DiskReport {
+    fn describe(&self) -> String {
+        let free = format!(
+            "{} MiB are free on the volume of DATA_DIR {}.",
+            self.free_mb.unwrap_or_default(),
+            *paths::DATA_DIR
+        );
+        match (self.data_dir_mb, self.quota_mb) {
+            (Some(used), Some(quota)) => {
+                format!(
+                    "DATA_DIR uses {} of its {} MiB quota, {}",
+                    used, quota, free
+                )
+            }
+            _ => free,
+        }
+    }
+}
+
+static LAST: RwLock<Option<DiskReport>> = RwLock::new(None);
+
+/// Outcome of the last check, `None` while the monitor is off
+pub fn last() -> Option<DiskReport> {
+    LAST.read().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+/// Whether the last check found the disk critically low, nothing that can wait should
+/// write to it then
+pub fn critical() -> bool {
+    LAST.read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .is_some_and(|report| report.level == DiskLevel::Critical)
+}
+
+/// Reports the usage of `DATA_DIR` and the free space of its volume. Warns when either runs
+/// low, and enforces a soft quota once it is critical, so a full disk does not break a world
+/// in the middle of a session. Each level is notified once, and again after it recovered.
+#[derive(Clone)]
+pub struct DiskSpaceMonitor {
+    pub min_free_mb: u64,
+    pub critical_free_mb: u64,
+    pub quota_mb: Option<u64>,
+    pub excludes: Vec<String>,
+    pub interval: Duration,
+}
+
+impl DiskSpaceMonitor {
+    pub async fn run(self) {
+        let mut notified = DiskLevel::Ok;
+        loop {
+            let report = self.check().await;
+            debug!("{}", report.describe());
+            if report.level != notified {
+                self.notify(&report, notified);
+                notified = report.level;
+            }
+            *LAST.write().unwrap_or_else(PoisonError::into_inner) = Some(report);
+            sleep(self.interval).await;
+        }
+    }
+
+    async fn check(&self) -> DiskReport {
+        let free_mb = free_mb();
+        // Only the quota needs the directory walked
+        let data_dir_mb = match self.quota_mb {
+            Some(_) => {
+                let excludes = self.excludes.clone();
+                tokio::task::spawn_blocking(move || {
+                    ScanService::disk_usage(Path::new(&*paths::DATA_DIR), &excludes)
+                })
+                .await
+                .ok()
+                .and_then(|usage| {
+                    usage
+                        .inspect_err(|e| warn!("Could not determine the usage of DATA_DIR: {}", e))
+                        .ok()
+                })
+                .map(|usage| usage.total_bytes / (1024 * 1024))
+            }
+            None => None,
+        };
+
+        let over = |percent: u64| {
+            self.quota_mb
+                .zip(data_dir_mb)
+                .is_some_and(|(quota, used)| used * 100 >= quota * percent)
+        };
+        let level = if free_mb.is_some_and(|free| free < self.critical_free_mb) || over(100) {
+            DiskLevel::Critical
+        } else if free_mb.is_some_and(|free| free < self.min_free_mb) || over(QUOTA_WARN_PERCENT) {
+            DiskLevel::Low
+        } else {
+            DiskLevel::Ok
+        };
+        DiskReport {
+            level,
+            free_mb,
+            data_dir_mb,
+            quota_mb: self.quota_mb,
+            checked_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    fn notify(&self, report: &DiskReport, previous: DiskLevel) {
+        let usage = report.describe();
+        let notification = match report.level {
+            DiskLevel::Critical => {
+                warn!(
+                    "🛑 Disk space is critically low, skipping scheduled backups and package installs: {}",
+                    usage
+                );
+                Notification {
+                    severity: Severity::Critical,
+                    title: "Disk space critically low".to_string(),
+                    message: format!(
+                        "{} Scheduled backups are skipped and packages are not installed until space is freed.",
+                        usage
+                    ),
+                }
+            }
+            DiskLevel::Low if previous == DiskLevel::Ok => {
+                warn!("⚠️ Disk space is low: {}", usage);
+                Notification {
+                    severity: Severity::Warning,
+                    title: "Disk space low".to_string(),
+                    message: format!("{} Worlds and backups fail to save once it is full.", usage),
+                }
+            }
+            // Back below critical, backups and installs resume
+            DiskLevel::Low => {
+                info!("Disk space is no longer critically low: {}", usage);
+                Notification {
+                    severity: Severity::Info,
+                    title: "Disk space no longer critical".to_string(),
+                    message: format!("{} Scheduled backups are taken again.", usage),
+                }
+            }
+            DiskLevel::Ok => {
+                info!("Disk space recovered: {}", usage);
+                Notification {
+                    severity: Severity::Info,
+                    title: "Disk space recovered".to_string(),
+                    message: usage,
+                }
+            }
+        };
+        notify::send(notification);
+    }
+}
+
+/// Space available to unprivileged processes, like `df` reports it
+fn free_mb() -> Option<u64> {
+    let stat = statvfs(paths::DATA_DIR.as_str()).ok()?;
+    Some(stat.blocks_available() * stat.fragment_size() / (1024 * 1024))
+}
+
+/// Last check of the free disk space and the `DATA_DIR` quota, taken every 5 minutes
+#[utoipa::path(
+    get,
+    path = "/api/disk",
+    operation_id = "disk",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "Disk usage", body = DiskReport),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 404, description = "Disk space is not checked", body = String, content_type = "text/plain"),
+    )
+)]
+pub async fn show(req: HttpRequest, config: web::Data<ApiConfig>) -> impl Responder {
+    if let Some(rejected) = api::reject(&req, &config) {
+        return rejected;
+    }
+    match last() {
+        Some(report) => HttpResponse::Ok().json(report),
+        None => HttpResponse::NotFound().body("Disk space is not checked"),
+    }
+}
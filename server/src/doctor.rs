@@ -0,0 +1,446 @@
+use nix::unistd::{getegid, geteuid};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::capabilities::{self, CapabilityState};
+use crate::command::CommandRunner;
+use crate::configfile;
+use crate::hostfs;
+use crate::i18n::{tr, tr_args};
+use crate::runtime::{OVERFLOW_UID, RuntimeInfo};
+use crate::utils::{find_executable, paths};
+
+/// Outcome of one doctor check, failed checks carry hints on how to fix them and passed
+/// checks may carry warnings
+#[derive(Serialize)]
+pub struct Check {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+    pub hints: Vec<String>,
+}
+
+impl Check {
+    /// `✅` for a passed check, `⚠️` for one with warnings and `❌` for a failed one
+    pub fn icon(&self) -> &'static str {
+        match (self.ok, self.hints.is_empty()) {
+            (true, true) => "✅",
+            (true, false) => "⚠️",
+            (false, _) => "❌",
+        }
+    }
+}
+
+/// Label types a container process may write to under the container SELinux policy
+const SELINUX_CONTAINER_TYPES: &[&str] = &["container_file_t", "svirt_sandbox_file_t"];
+
+pub struct Doctor;
+
+impl Doctor {
+    /// Check that every volume can be written to and Foundry's options make sense
+    pub fn run() -> Vec<Check> {
+        let mut checks = Self::volumes();
+        checks.push(Self::check_foundry_config());
+        checks.extend(Self::check_capabilities());
+        checks
+    }
+
+    /// Whether the application, data and backup volumes can be written to
+    pub fn volumes() -> Vec<Check> {
+        [
+            PathBuf::from(&*paths::APPLICATION_DIR),
+            PathBuf::from(&*paths::DATA_DIR),
+            PathBuf::from(&*paths::BACKUP_DIR),
+        ]
+        .iter()
+        .map(|path| Self::check_volume(path))
+        .collect()
+    }
+
+    /// The optional features the running wrapper recorded. One that is not working is a
+    /// warning, Foundry runs without it.
+    fn check_capabilities() -> Vec<Check> {
+        capabilities::recorded()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, capability)| Check {
+                name: tr_args("doctor-capability", &[("name", &name)]),
+                ok: true,
+                detail: tr(match capability.state {
+                    CapabilityState::Configured => "doctor-capability-configured",
+                    CapabilityState::Active => "doctor-capability-active",
+                    CapabilityState::Failed => "doctor-capability-failed",
+                }),
+                hints: capability.reason.into_iter().collect(),
+            })
+            .collect()
+    }
+
+    pub fn check_foundry_config() -> Check {
+        let lints = foundry_config_lints();
+        Check {
+            name: tr("doctor-foundry-config"),
+            ok: lints.iter().all(|lint| !lint.breaking),
+            detail: if lints.is_empty() {
+                tr("doctor-foundry-config-ok")
+            } else {
+                tr_args(
+                    "doctor-foundry-config-lints",
+                    &[("count", &lints.len().to_string())],
+                )
+            },
+            hints: lints.into_iter().map(|lint| lint.message).collect(),
+        }
+    }
+
+    pub fn check_volume(path: &Path) -> Check {
+        let display = path.display().to_string();
+        let name = tr_args("doctor-writable", &[("path", &display)]);
+        if !path.exists() {
+            return Check {
+                name,
+                ok: false,
+                detail: tr("doctor-missing"),
+                hints: vec![tr_args("doctor-mount-volume", &[("path", &display)])],
+            };
+        }
+        match probe_write(path) {
+            Ok(()) if hostfs::is_windows_mount(path) => Check {
+                name,
+                ok: true,
+                detail: tr("doctor-can-write-windows"),
+                hints: vec![tr_args("doctor-windows-slow", &[("path", &display)])],
+            },
+            Ok(()) => Check {
+                name,
+                ok: true,
+                detail: tr("doctor-can-write"),
+                hints: Vec::new(),
+            },
+            Err(e) => Check {
+                name,
+                ok: false,
+                detail: e.to_string(),
+                hints: explain_write_failure(path, &e),
+            },
+        }
+    }
+}
+
+/// A setting of Foundry that keeps players from connecting or does not do what it says, with
+/// the fix
+pub struct Lint {
+    pub message: String,
+    /// Players cannot connect like this, otherwise the setting is only ignored
+    pub breaking: bool,
+}
+
+/// Common misconfigurations of `Config/options.json` and `APPLICATION_HOST`.
+///
+/// Unless `FOUNDRY_HOSTNAME`, `FOUNDRY_UPNP` or `FOUNDRY_PROXY_SSL` set them in the options
+/// file, the wrapper passes `--hostname`, `--noupnp` and `--proxySSL` on the command line, which
+/// take precedence over the same keys there.
+pub fn foundry_config_lints() -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let mut lint = |breaking, message| lints.push(Lint { message, breaking });
+
+    if let Ok(host) = configfile::var("APPLICATION_HOST")
+        && let Some(bare) = bare_hostname(&host)
+    {
+        lint(
+            true,
+            tr_args("doctor-lint-hostname", &[("host", &host), ("bare", &bare)]),
+        );
+    }
+
+    let options_path = PathBuf::from(&*paths::DATA_DIR)
+        .join("Config")
+        .join("options.json");
+    let Some(options) = fs::read_to_string(&options_path)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<Value>(&contents).ok())
+    else {
+        return lints;
+    };
+
+    // Foundry wants the bare path, `foundry` serves the game under `/foundry/`
+    if let Some(prefix) = options["routePrefix"].as_str().filter(|p| !p.is_empty()) {
+        let bare = prefix.trim_matches('/');
+        if prefix.contains("://") {
+            lint(
+                true,
+                tr_args("doctor-lint-route-prefix-url", &[("prefix", prefix)]),
+            );
+        } else if let Ok(join_url) = configfile::var("JOIN_URL")
+            && let Ok(url) = reqwest::Url::parse(&join_url)
+            && !url.path().starts_with(&format!("/{}/", bare))
+        {
+            lint(
+                true,
+                tr_args(
+                    "doctor-lint-route-prefix",
+                    &[("prefix", bare), ("url", &join_url)],
+                ),
+            );
+        }
+    }
+    if options["proxyPort"].as_u64() == Some(80) {
+        lint(true, tr("doctor-lint-proxy-ssl-port"));
+    }
+    if options["upnp"].as_bool() == Some(true) && configfile::var("FOUNDRY_UPNP").is_err() {
+        lint(false, tr("doctor-lint-upnp"));
+    }
+    if let Some(host) = options["hostname"].as_str()
+        && configfile::var("FOUNDRY_HOSTNAME").is_err()
+        && let Some(bare) = bare_hostname(host)
+    {
+        lint(
+            false,
+            tr_args(
+                "doctor-lint-options-hostname",
+                &[("host", host), ("bare", &bare)],
+            ),
+        );
+    }
+    if let Some(language) = options["language"].as_str() {
+        // `de.lang-de` is the language `de` of the package `lang-de`, `en.core` ships with Foundry
+        let lang = language.split('.').next().unwrap_or(language);
+        if lang != "en" && !installed_languages().contains(lang) {
+            lint(
+                true,
+                tr_args(
+                    "doctor-lint-language",
+                    &[("language", language), ("lang", lang)],
+                ),
+            );
+        }
+    }
+    lints
+}
+
+/// `host` without scheme, path and port, nothing when it is bare already
+fn bare_hostname(host: &str) -> Option<String> {
+    let rest = host.split_once("://").map_or(host, |(_, rest)| rest);
+    let bare = rest.split(['/', ':']).next().unwrap_or(rest);
+    (bare != host).then(|| bare.to_string())
+}
+
+/// Languages provided by the installed systems and modules
+fn installed_languages() -> HashSet<String> {
+    let data = PathBuf::from(&*paths::DATA_DIR).join("Data");
+    let mut languages = HashSet::new();
+    for (dir, manifest) in [("systems", "system.json"), ("modules", "module.json")] {
+        let Ok(entries) = fs::read_dir(data.join(dir)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Some(package) = fs::read_to_string(entry.path().join(manifest))
+                .ok()
+                .and_then(|contents| serde_json::from_str::<Value>(&contents).ok())
+            else {
+                continue;
+            };
+            let declared = package["languages"].as_array().into_iter().flatten();
+            languages.extend(
+                declared
+                    .filter_map(|language| language["lang"].as_str())
+                    .map(str::to_string),
+            );
+        }
+    }
+    languages
+}
+
+/// Create and remove a file in `dir`, the only reliable way to know a write works
+pub fn probe_write(dir: &Path) -> io::Result<()> {
+    let probe = dir.join(format!(".write-probe-{}", std::process::id()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
+
+/// Actionable reasons why writing to `path` failed with `error`.
+///
+/// Permission errors on Fedora and RHEL hosts are usually SELinux labels, on Ubuntu AppArmor,
+/// neither shows up in the file mode.
+pub fn explain_write_failure(path: &Path, error: &io::Error) -> Vec<String> {
+    let display = path.display().to_string();
+    let mut hints = Vec::new();
+    if error.kind() == ErrorKind::ReadOnlyFilesystem {
+        hints.push(tr_args("doctor-read-only", &[("path", &display)]));
+        return hints;
+    }
+    if error.kind() != ErrorKind::PermissionDenied {
+        return hints;
+    }
+
+    hints.extend(selinux_hint(path));
+    hints.extend(apparmor_hint(path));
+
+    if let Ok(metadata) = fs::metadata(path) {
+        let uid = geteuid().as_raw();
+        let runtime = RuntimeInfo::detect();
+        if metadata.uid() != uid && uid != 0 {
+            hints.push(ownership_hint(path, metadata.uid(), &runtime));
+        }
+    }
+
+    if hints.is_empty() {
+        hints.push(tr_args("doctor-check-host", &[("path", &display)]));
+    }
+    hints
+}
+
+fn ownership_hint(path: &Path, owner: u32, runtime: &RuntimeInfo) -> String {
+    let path = path.display().to_string();
+    let uid = geteuid().as_raw().to_string();
+    let gid = getegid().as_raw().to_string();
+    if !runtime.rootless() {
+        let owner = owner.to_string();
+        return tr_args(
+            "doctor-owner",
+            &[
+                ("path", &path),
+                ("owner", &owner),
+                ("uid", &uid),
+                ("gid", &gid),
+            ],
+        );
+    }
+
+    // Plain `chown` on the host uses host ids, which differ from the ones seen in here
+    let owner = if owner == OVERFLOW_UID {
+        tr("doctor-owner-unmapped")
+    } else {
+        tr_args("doctor-owner-uid", &[("uid", &owner.to_string())])
+    };
+    let host = runtime
+        .host_uid(geteuid().as_raw())
+        .map(|host| tr_args("doctor-host-uid", &[("uid", &host.to_string())]))
+        .unwrap_or_default();
+    tr_args(
+        "doctor-owner-rootless",
+        &[
+            ("path", &path),
+            ("owner", &owner),
+            ("uid", &uid),
+            ("gid", &gid),
+            ("host", &host),
+        ],
+    )
+}
+
+fn selinux_hint(path: &Path) -> Option<String> {
+    let enforcing = fs::read_to_string("/sys/fs/selinux/enforce").ok()?;
+    if enforcing.trim() != "1" {
+        return None;
+    }
+
+    let display = path.display().to_string();
+    let label = selinux_label(path)?;
+    let label_type = label.split(':').nth(2).unwrap_or_default();
+    let denial = kernel_log_denial(path, "avc:  denied")
+        .map(|line| format!(" {}", tr_args("doctor-kernel-log", &[("line", &line)])))
+        .unwrap_or_default();
+
+    if !SELINUX_CONTAINER_TYPES.contains(&label_type) {
+        let hint = tr_args(
+            "doctor-selinux-label",
+            &[("path", &display), ("label", label_type)],
+        );
+        return Some(hint + &denial);
+    }
+
+    // Private `:Z` labels carry the categories of the container that relabeled them
+    let process_label = fs::read_to_string("/proc/self/attr/current").unwrap_or_default();
+    let level = |label: &str| {
+        label
+            .splitn(4, ':')
+            .nth(3)
+            .map(|l| l.trim_matches(char::from(0)).trim().to_string())
+    };
+    if level(&label) != level(&process_label) {
+        let hint = tr_args(
+            "doctor-selinux-private",
+            &[("path", &display), ("label", &label)],
+        );
+        return Some(hint + &denial);
+    }
+    None
+}
+
+fn apparmor_hint(path: &Path) -> Option<String> {
+    let enabled = fs::read_to_string("/sys/module/apparmor/parameters/enabled").ok()?;
+    if enabled.trim() != "Y" {
+        return None;
+    }
+    let profile = fs::read_to_string("/proc/self/attr/apparmor/current")
+        .or_else(|_| fs::read_to_string("/proc/self/attr/current"))
+        .unwrap_or_default()
+        .trim_matches(char::from(0))
+        .trim()
+        .to_string();
+    if profile.is_empty() || profile == "unconfined" {
+        return None;
+    }
+
+    let denial = kernel_log_denial(path, "apparmor=\"DENIED\"")?;
+    let display = path.display().to_string();
+    Some(format!(
+        "{} {}",
+        tr_args(
+            "doctor-apparmor",
+            &[("profile", &profile), ("path", &display)]
+        ),
+        tr_args("doctor-kernel-log", &[("line", &denial)])
+    ))
+}
+
+/// SELinux context of `path` like `user:role:type:level`, read from its extended attribute
+/// instead of `ls -Z`, which images without coreutils do not have
+fn selinux_label(path: &Path) -> Option<String> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut buffer = [0u8; 256];
+    // SAFETY: both strings are NUL terminated and the length matches the buffer
+    let len = unsafe {
+        nix::libc::lgetxattr(
+            c_path.as_ptr(),
+            c"security.selinux".as_ptr(),
+            buffer.as_mut_ptr().cast(),
+            buffer.len(),
+        )
+    };
+    if len <= 0 {
+        return None;
+    }
+    let label = String::from_utf8_lossy(&buffer[..len as usize]);
+    Some(label.trim_end_matches('\0').to_string())
+}
+
+/// Latest kernel log line containing `marker` that mentions `path`
+fn kernel_log_denial(path: &Path, marker: &str) -> Option<String> {
+    let name = path.file_name()?.to_string_lossy().to_string();
+    // Containers rarely get to read the kernel log, the host audit log is tried as well
+    let mut logs = Vec::new();
+    if find_executable("dmesg").is_some() {
+        let dmesg = CommandRunner::new("dmesg")
+            .timeout(Duration::from_secs(5))
+            .run_blocking();
+        logs.push(dmesg.map(|result| result.stdout).unwrap_or_default());
+    }
+    for file in ["/var/log/audit/audit.log", "/var/log/kern.log"] {
+        logs.push(fs::read_to_string(file).unwrap_or_default());
+    }
+    logs.iter()
+        .flat_map(|log| log.lines())
+        .rfind(|line| line.contains(marker) && line.contains(&name))
+        .map(|line| line.trim().to_string())
+}
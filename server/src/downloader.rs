@@ -1,5 +1,11 @@
+use crate::cache;
 use crate::events::ProgressEvent;
-use reqwest::Client;
+use crate::jobstore::{self, RangeDownload};
+use crate::tempfiles;
+use chrono::Utc;
+use reqwest::header::{CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE};
+use reqwest::{Client, Response, StatusCode};
+use std::path::Path;
 use tokio::fs;
 use tokio::sync::broadcast;
 use tracing::{debug, error, info};
@@ -8,31 +14,61 @@ pub struct DownloadService;
 
 impl DownloadService {
     /// Download file from `url` and write it to `save_path`, streaming the response.
+    ///
+    /// A download interrupted by a restart is continued where it stopped, as long as the
+    /// server answers range requests for the same file.
     pub async fn download_file_from_url(
         url: &str,
         save_path: &str,
         event_tx: broadcast::Sender<ProgressEvent>,
     ) -> Result<(), actix_web::Error> {
+        if cache::offline() {
+            return Err(actix_web::error::ErrorServiceUnavailable(format!(
+                "OFFLINE is set, {} is not downloaded",
+                url
+            )));
+        }
         info!("Starting download from URL: {}", url);
 
         let client = Client::new();
-        let mut resp = client.get(url).send().await.map_err(|e| {
-            error!("Request error: {}", e);
-            actix_web::error::ErrorInternalServerError(format!("Failed to send request: {}", e))
-        })?;
+        let partial = fs::metadata(save_path).await.map(|m| m.len()).unwrap_or(0);
+        let resumable = jobstore::download(save_path)
+            .filter(|job| partial > 0 && partial < job.total)
+            .and_then(|job| Some((job.etag.clone().or(job.last_modified.clone())?, job)));
 
-        // Check the response status
-        if !resp.status().is_success() {
-            let status = resp.status();
-            error!("Download request failed with status: {}", status);
-            return Err(actix_web::error::ErrorInternalServerError(format!(
-                "Download failed with status: {}",
-                status
-            )));
+        let mut resp = None;
+        let mut offset = 0;
+        if let Some((validator, job)) = &resumable {
+            let answer = client
+                .get(url)
+                .header(RANGE, format!("bytes={}-", partial))
+                .header(IF_RANGE, validator.as_str())
+                .send()
+                .await;
+            // 200 means the file changed since, it is downloaded from the start then
+            if let Ok(answer) = answer {
+                if answer.status() == StatusCode::PARTIAL_CONTENT
+                    && content_range(&answer) == Some((partial, job.total))
+                {
+                    info!(
+                        "Resuming the download at {} of {} MB",
+                        partial / (1024 * 1024),
+                        job.total / (1024 * 1024)
+                    );
+                    offset = partial;
+                    resp = Some(answer);
+                } else if answer.status() == StatusCode::OK {
+                    resp = Some(answer);
+                }
+            }
         }
+        let mut resp = match resp {
+            Some(resp) => resp,
+            None => send(client.get(url)).await?,
+        };
 
         // Get content length if available for progress calculation
-        let content_length = resp.content_length().unwrap_or(0);
+        let content_length = offset + resp.content_length().unwrap_or(0);
         if content_length > 0 {
             debug!("Content length: {} bytes", content_length);
             let _ = event_tx.send(ProgressEvent::new(
@@ -48,14 +84,22 @@ impl DownloadService {
             ));
         }
 
-        let mut out = fs::File::create(save_path).await.map_err(|e| {
+        tempfiles::track(Path::new(save_path), "download");
+        let out = match offset {
+            0 => fs::File::create(save_path).await,
+            _ => fs::OpenOptions::new().append(true).open(save_path).await,
+        };
+        let mut out = out.map_err(|e| {
             error!("Failed to create file: {}", e);
             actix_web::error::ErrorInternalServerError(format!("Failed to create file: {}", e))
         })?;
         info!("Saving downloaded file to: {}", save_path);
+        if offset == 0 {
+            record_download(url, save_path, &resp, content_length);
+        }
 
         // Use a buffer to track download progress
-        let mut downloaded: u64 = 0;
+        let mut downloaded: u64 = offset;
         while let Some(chunk) = resp.chunk().await.map_err(|e| {
             error!("Failed reading download stream: {}", e);
             actix_web::error::ErrorInternalServerError(format!(
@@ -110,7 +154,74 @@ impl DownloadService {
             }
         }
 
+        // A connection closed early still ends the stream without an error
+        if content_length > 0 && downloaded != content_length {
+            error!(
+                "Download truncated: {} of {} bytes",
+                downloaded, content_length
+            );
+            return Err(actix_web::error::ErrorInternalServerError(format!(
+                "Download incomplete: received {} of {} bytes",
+                downloaded, content_length
+            )));
+        }
+
+        jobstore::record(|store| {
+            store.downloads.remove(save_path);
+        });
         info!("Download completed successfully: {} bytes", downloaded);
         Ok(())
     }
 }
+
+async fn send(request: reqwest::RequestBuilder) -> Result<Response, actix_web::Error> {
+    let resp = request.send().await.map_err(|e| {
+        error!("Request error: {}", e);
+        actix_web::error::ErrorInternalServerError(format!("Failed to send request: {}", e))
+    })?;
+
+    // Check the response status
+    if !resp.status().is_success() {
+        let status = resp.status();
+        error!("Download request failed with status: {}", status);
+        return Err(actix_web::error::ErrorInternalServerError(format!(
+            "Download failed with status: {}",
+            status
+        )));
+    }
+    Ok(resp)
+}
+
+/// Remember a download that can be resumed, which needs its length and a validator
+fn record_download(url: &str, save_path: &str, resp: &Response, total: u64) {
+    let header = |name| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    // Weak ETags are not allowed in If-Range
+    let etag = header(ETAG).filter(|etag| !etag.starts_with("W/"));
+    let last_modified = header(LAST_MODIFIED);
+    if total == 0 || (etag.is_none() && last_modified.is_none()) {
+        return;
+    }
+    let job = RangeDownload {
+        url: url.to_string(),
+        total,
+        etag,
+        last_modified,
+        started: Utc::now().to_rfc3339(),
+    };
+    jobstore::record(|store| {
+        store.downloads.insert(save_path.to_string(), job);
+    });
+}
+
+/// Start and total length of a `Content-Range: bytes <start>-<end>/<total>` answer
+fn content_range(resp: &Response) -> Option<(u64, u64)> {
+    let range = resp.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    let (span, total) = range.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, _) = span.split_once('-')?;
+    Some((start.parse().ok()?, total.parse().ok()?))
+}
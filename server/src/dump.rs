@@ -0,0 +1,128 @@
+use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
+use serde_json::{Value, json};
+use std::io::Write;
+
+use crate::db::Collection;
+use crate::utils::paths;
+
+/// Collections dumped when none are given: actors, items, scenes and the chat log
+pub const DEFAULT_COLLECTIONS: &[&str] = &["actors", "items", "scenes", "messages"];
+
+/// Embedded collections inlined into their parent documents, as v10 and older store them
+const EMBEDDED: &[(&str, &[&str])] = &[
+    ("actors", &["items", "effects"]),
+    ("items", &["effects"]),
+    ("journal", &["pages"]),
+    ("combats", &["combatants"]),
+    ("cards", &["cards"]),
+    ("playlists", &["sounds"]),
+    ("tables", &["results"]),
+    (
+        "scenes",
+        &[
+            "tokens",
+            "notes",
+            "drawings",
+            "lights",
+            "sounds",
+            "templates",
+            "tiles",
+            "walls",
+            "regions",
+        ],
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DumpFormat {
+    /// One `{"collection": ..., "document": ...}` object per line
+    Jsonl,
+    /// `CREATE TABLE` and `INSERT` statements, one table per collection holding the documents
+    /// as JSON, e.g. for SQLite's JSON functions
+    Sql,
+}
+
+/// Reads world documents for analytics and custom tooling.
+///
+/// Opens the databases in place, so the world must not be running.
+pub struct DumpService;
+
+impl DumpService {
+    /// Primary documents of collection `name` with their embedded documents inlined, nothing
+    /// for a collection the world does not have yet
+    pub fn documents(world_id: &str, name: &str) -> Result<Vec<Value>> {
+        if !Collection::exists(world_id, name) {
+            return Ok(Vec::new());
+        }
+        let mut collection = Collection::open(world_id, name)?;
+        let mut documents = collection.documents()?;
+        let fields = EMBEDDED
+            .iter()
+            .find(|(collection, _)| *collection == name)
+            .map(|(_, fields)| *fields)
+            .unwrap_or_default();
+        for document in &mut documents {
+            for field in fields {
+                document[*field] = Value::Array(collection.embedded(field, document)?);
+            }
+        }
+        Ok(documents)
+    }
+
+    /// Write the `collections` of a world to `out`, returning the number of documents
+    pub fn dump(
+        world_id: &str,
+        collections: &[String],
+        format: DumpFormat,
+        out: &mut dyn Write,
+    ) -> Result<usize> {
+        if !paths::WORLDS_DIR
+            .join(world_id)
+            .join("world.json")
+            .is_file()
+        {
+            return Err(anyhow!("World {} does not exist", world_id));
+        }
+
+        let mut count = 0;
+        for name in collections {
+            let documents = Self::documents(world_id, name)
+                .with_context(|| format!("Failed to read collection {}", name))?;
+            if format == DumpFormat::Sql {
+                writeln!(
+                    out,
+                    "CREATE TABLE IF NOT EXISTS {} (id TEXT PRIMARY KEY, document TEXT NOT NULL);",
+                    sql_identifier(name)
+                )?;
+            }
+            for document in &documents {
+                match format {
+                    DumpFormat::Jsonl => writeln!(
+                        out,
+                        "{}",
+                        json!({ "collection": name, "document": document })
+                    )?,
+                    DumpFormat::Sql => writeln!(
+                        out,
+                        "INSERT INTO {} (id, document) VALUES ({}, {});",
+                        sql_identifier(name),
+                        sql_string(document["_id"].as_str().unwrap_or_default()),
+                        sql_string(&document.to_string())
+                    )?,
+                }
+            }
+            count += documents.len();
+        }
+        out.flush()?;
+        Ok(count)
+    }
+}
+
+fn sql_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn sql_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
@@ -0,0 +1,92 @@
+use anyhow::{Context, Result, anyhow};
+use futures_util::future::BoxFuture;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::config::AppConfig;
+use crate::notify::{Notification, Notifier, Severity};
+
+/// Sends notifications through an SMTP server
+#[derive(Clone)]
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl EmailNotifier {
+    /// Build the notifier from the `SMTP_*` settings, `None` when no SMTP host is configured
+    pub fn from_config(config: &AppConfig) -> Result<Option<Self>> {
+        let Some(host) = &config.smtp_host else {
+            return Ok(None);
+        };
+        let from = config
+            .smtp_from
+            .as_deref()
+            .ok_or_else(|| anyhow!("SMTP_FROM is not set"))?
+            .parse()
+            .context("Invalid SMTP_FROM address")?;
+        let to = config
+            .smtp_to
+            .iter()
+            .map(|address| {
+                address
+                    .parse()
+                    .with_context(|| format!("Invalid SMTP_TO address {}", address))
+            })
+            .collect::<Result<Vec<Mailbox>>>()?;
+        if to.is_empty() {
+            return Err(anyhow!("SMTP_TO is not set"));
+        }
+
+        let mut builder = match config.smtp_tls.as_str() {
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            other => return Err(anyhow!("Unknown SMTP_TLS mode '{}'", other)),
+        }
+        .port(config.smtp_port);
+        if let Some(username) = &config.smtp_username {
+            builder = builder.credentials(Credentials::new(
+                username.clone(),
+                config.smtp_password.clone().unwrap_or_default(),
+            ));
+        }
+
+        Ok(Some(Self {
+            transport: builder.build(),
+            from,
+            to,
+        }))
+    }
+
+    pub async fn send(&self, notification: &Notification) -> Result<()> {
+        let mut message = Message::builder()
+            .from(self.from.clone())
+            .subject(format!("[Foundry VTT] {}", notification.title));
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        let message = message.body(notification.message.clone())?;
+        self.transport
+            .send(message)
+            .await
+            .context("SMTP delivery failed")?;
+        Ok(())
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    fn min_severity(&self) -> Severity {
+        Severity::Critical
+    }
+
+    fn deliver<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.send(notification))
+    }
+}
@@ -0,0 +1,241 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+use zip::read::ZipArchive;
+use zip::result::ZipError;
+
+use crate::fingerprint;
+use crate::initialization::tool_version;
+use crate::packages::{self, installed_packages};
+use crate::scan::{ScanEntry, ScanService};
+use crate::utils::paths;
+
+/// Name of the manifest at the root of every snapshot, next to `Config` and `Data`
+pub const MANIFEST: &str = "environment.json";
+
+/// What a snapshot was taken with, so a restore can set up the same environment first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvironmentManifest {
+    pub created: String,
+    pub foundry_version: Option<String>,
+    /// `node --version` of the image, it is not reinstalled but compared
+    pub node_version: Option<String>,
+    /// Systems and modules keyed like `installed_packages`
+    pub packages: BTreeMap<String, PackageRecord>,
+    /// See `fingerprint::settings_sha256`, a different one means the configuration changed
+    pub config_sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageRecord {
+    pub version: String,
+    /// SHA-256 over the paths and contents of the package's files in the snapshot, `None` when
+    /// `SCAN_EXCLUDE` left all of them out
+    pub sha256: Option<String>,
+    /// `download` of the package's manifest, most hosts answer exactly this version there
+    pub download: Option<String>,
+}
+
+impl EnvironmentManifest {
+    /// Describe the running environment, hashing the packages among the files of a snapshot
+    pub fn capture(entries: &[ScanEntry]) -> Result<Self> {
+        let data = PathBuf::from(&*paths::DATA_DIR).join("Data");
+        let installed = installed_packages();
+        let mut packages = BTreeMap::new();
+        for (key, version) in &installed {
+            let Some(dir) = package_dir(&data, key) else {
+                continue;
+            };
+            let files: Vec<&Path> = entries
+                .iter()
+                .map(|entry| entry.path.as_path())
+                .filter(|path| path.starts_with(&dir))
+                .collect();
+            packages.insert(
+                key.clone(),
+                PackageRecord {
+                    version: version.clone(),
+                    sha256: match files.is_empty() {
+                        true => None,
+                        false => Some(tree_sha256(&dir, &files)?),
+                    },
+                    download: manifest_download(&dir, key),
+                },
+            );
+        }
+        Ok(Self {
+            created: Utc::now().to_rfc3339(),
+            foundry_version: installed.get("core").cloned(),
+            node_version: tool_version("node"),
+            packages,
+            config_sha256: fingerprint::settings_sha256(),
+        })
+    }
+
+    /// The manifest of a snapshot, `None` for snapshots taken before they had one
+    pub fn read(archive: &Path) -> Result<Option<Self>> {
+        let file =
+            File::open(archive).with_context(|| format!("Failed to open {}", archive.display()))?;
+        let mut archive = ZipArchive::new(file)?;
+        let mut entry = match archive.by_name(MANIFEST) {
+            Ok(entry) => entry,
+            Err(ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+        serde_json::from_slice(&contents)
+            .map(Some)
+            .with_context(|| format!("Invalid {}", MANIFEST))
+    }
+
+    /// Make the packages below the `Data` directory `data` those of the snapshot, reinstalling
+    /// missing or changed ones from their download. Answers what was done or differs.
+    pub async fn reproduce_packages(&self, data: &Path) -> Result<Vec<String>> {
+        let mut notes = Vec::new();
+        let mut failures = Vec::new();
+        for (key, record) in &self.packages {
+            let Some(dir) = package_dir(data, key) else {
+                continue;
+            };
+            let manifest = dir.join(format!(
+                "{}.json",
+                key.split(':').next().unwrap_or_default()
+            ));
+            let present = match &record.sha256 {
+                Some(sha256) => digest_of(&dir)?.as_ref() == Some(sha256),
+                None => {
+                    packages::read_manifest_version(&manifest).as_ref() == Some(&record.version)
+                }
+            };
+            if present {
+                continue;
+            }
+            let Some(download) = &record.download else {
+                failures.push(format!("{} {} has no download", key, record.version));
+                continue;
+            };
+            info!("📦 Reinstalling {} {} for the restore", key, record.version);
+            if let Err(e) = packages::install_version(key, &record.version, download, data).await {
+                failures.push(format!("{} {}: {:#}", key, record.version, e));
+                continue;
+            }
+            let version = packages::read_manifest_version(&manifest);
+            if version.as_deref() != Some(record.version.as_str()) {
+                failures.push(format!(
+                    "{} {}: the download installed {}",
+                    key,
+                    record.version,
+                    version.as_deref().unwrap_or("no version")
+                ));
+                continue;
+            }
+            // Files edited after the install, e.g. a patched module, cannot come back
+            match &record.sha256 {
+                Some(sha256) if digest_of(&dir)?.as_ref() != Some(sha256) => notes.push(format!(
+                    "Reinstalled {} {}, its files differ from those of the snapshot",
+                    key, record.version
+                )),
+                _ => notes.push(format!("Reinstalled {} {}", key, record.version)),
+            }
+        }
+        if !failures.is_empty() {
+            return Err(anyhow!(
+                "Failed to reproduce the packages of the snapshot: {}",
+                failures.join("; ")
+            ));
+        }
+        Ok(notes)
+    }
+
+    /// Differences the restore cannot undo, to mention next to its result
+    pub fn differences(&self) -> Vec<String> {
+        let mut differences = Vec::new();
+        let node = tool_version("node");
+        if let (Some(snapshot), Some(current)) = (&self.node_version, &node)
+            && snapshot != current
+        {
+            differences.push(format!(
+                "The snapshot was taken with Node {}, this image has {}",
+                snapshot, current
+            ));
+        }
+        if self.config_sha256 != fingerprint::settings_sha256() {
+            differences
+                .push("The settings differ from those the snapshot was taken with".to_string());
+        }
+        differences
+    }
+}
+
+/// `Data/systems/<id>` or `Data/modules/<id>` of a package key, `None` for the core
+fn package_dir(data: &Path, key: &str) -> Option<PathBuf> {
+    match key.split_once(':')? {
+        (kind @ ("system" | "module"), id) => Some(data.join(format!("{}s", kind)).join(id)),
+        _ => None,
+    }
+}
+
+fn manifest_download(dir: &Path, key: &str) -> Option<String> {
+    let kind = key.split(':').next()?;
+    let contents = fs::read_to_string(dir.join(format!("{}.json", kind))).ok()?;
+    let manifest: Value = serde_json::from_str(&contents).ok()?;
+    manifest["download"]
+        .as_str()
+        .filter(|url| !url.is_empty())
+        .map(str::to_string)
+}
+
+/// Digest of a package directory on disk, `None` when it is missing
+fn digest_of(dir: &Path) -> Result<Option<String>> {
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    let mut entries = ScanService::walk(dir, &[])?;
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    let files: Vec<&Path> = entries.iter().map(|entry| entry.path.as_path()).collect();
+    tree_sha256(dir, &files).map(Some)
+}
+
+/// Hash of the paths relative to `dir` and the contents of `files`, which are sorted
+fn tree_sha256(dir: &Path, files: &[&Path]) -> Result<String> {
+    let mut hasher = Sha256::new();
+    for path in files {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            // Removed since the scan, like the snapshot skips it
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        let relative = path.strip_prefix(dir).unwrap_or(path);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        io::copy(&mut file, &mut hasher)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        hasher.update([0]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Warn instead of failing the backup, a snapshot without a manifest still restores
+pub fn capture_or_warn(entries: &[ScanEntry]) -> Option<Vec<u8>> {
+    match EnvironmentManifest::capture(entries).and_then(|manifest| {
+        serde_json::to_vec_pretty(&manifest).context("Failed to serialize the manifest")
+    }) {
+        Ok(contents) => Some(contents),
+        Err(e) => {
+            warn!(
+                "⚠️ Failed to record the environment of the snapshot: {:#}",
+                e
+            );
+            None
+        }
+    }
+}
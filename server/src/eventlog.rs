@@ -0,0 +1,88 @@
+use chrono::Utc;
+use serde_json::{Value, json};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use tracing::debug;
+
+use crate::permissions::{self, FileKind};
+use crate::utils::paths;
+
+/// Raised only for changes that break readers, new events and fields keep it
+const SCHEMA: u32 = 1;
+/// The log is rotated once it grows beyond this
+const MAX_BYTES: u64 = 10 * 1024 * 1024;
+/// Rotated logs kept next to it, `events.ndjson.1` being the newest
+const KEEP: usize = 3;
+
+/// Only the wrapper appends, management commands in a process of their own do not
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Serializes appending and rotating
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Start appending events to `DATA_DIR/.wrapper/events.ndjson`, called once at startup
+pub fn init(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Append `event` with its `data` as one line of JSON.
+///
+/// Each line is `{"schema": 1, "time": <RFC 3339>, "event": <name>, "data": {...}}`, see the
+/// README for the events and their fields. A line that cannot be written is dropped, the
+/// wrapper never waits for the log.
+pub fn record(event: &str, data: Value) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut line = json!({
+        "schema": SCHEMA,
+        "time": Utc::now().to_rfc3339(),
+        "event": event,
+        "data": data,
+    })
+    .to_string();
+    line.push('\n');
+
+    let _guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Err(e) = append(&line) {
+        debug!("Failed to append the {} event: {}", event, e);
+    }
+}
+
+fn append(line: &str) -> io::Result<()> {
+    let path = path();
+    match fs::metadata(&path) {
+        Ok(metadata) if metadata.len() + line.len() as u64 > MAX_BYTES => rotate(&path)?,
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            permissions::create_dir_all(&paths::WRAPPER_STATE_DIR)?;
+        }
+        Err(e) => return Err(e),
+    }
+    if !path.exists() {
+        permissions::create(&path, FileKind::Regular)?;
+    }
+    OpenOptions::new()
+        .append(true)
+        .open(&path)?
+        .write_all(line.as_bytes())
+}
+
+/// Shift `events.ndjson.<n>` to `<n + 1>`, dropping the oldest. Shippers following the file
+/// by name pick up the new one, like after logrotate.
+fn rotate(path: &Path) -> io::Result<()> {
+    let rotated = |n: usize| PathBuf::from(format!("{}.{}", path.display(), n));
+    let _ = fs::remove_file(rotated(KEEP));
+    for n in (1..KEEP).rev() {
+        if rotated(n).exists() {
+            fs::rename(rotated(n), rotated(n + 1))?;
+        }
+    }
+    fs::rename(path, rotated(1))
+}
+
+fn path() -> PathBuf {
+    paths::WRAPPER_STATE_DIR.join("events.ndjson")
+}
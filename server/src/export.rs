@@ -0,0 +1,287 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use tokio::time::{Duration, sleep};
+use tracing::{debug, error, info, warn};
+
+use crate::db::Collection;
+use crate::jobs;
+use crate::permissions::FileKind;
+use crate::storage::storage;
+use crate::utils::paths;
+use crate::worlds::WorldService;
+
+/// Embedded collections of an actor that are part of its export
+const ACTOR_EMBEDDED: &[&str] = &["items", "effects"];
+
+/// Exports campaign content to files that can be read without Foundry
+pub struct ExportService;
+
+impl ExportService {
+    /// Export the actors and journals of a world to `BACKUP_DIR/exports/<world>/<timestamp>/`.
+    ///
+    /// Reads from a snapshot of the database, so this is safe while the world is running.
+    pub fn export_world(world_id: &str) -> Result<PathBuf> {
+        let target = PathBuf::from(&*paths::BACKUP_DIR)
+            .join("exports")
+            .join(world_id)
+            .join(chrono::Utc::now().format("%Y%m%d-%H%M%S").to_string());
+
+        let actors = export_actors(world_id, &target.join("actors"))?;
+        let journals = export_journals(world_id, &target.join("journals"))?;
+
+        info!(
+            "📦 Exported {} actors and {} journals of world {} to {}",
+            actors,
+            journals,
+            world_id,
+            target.display()
+        );
+        Ok(target)
+    }
+
+    /// Remove all but the newest `keep_last` exports of a world
+    pub fn prune(world_id: &str, keep_last: usize) -> Result<Vec<PathBuf>> {
+        let dir = PathBuf::from(&*paths::BACKUP_DIR)
+            .join("exports")
+            .join(world_id);
+        if !storage().exists(&dir) {
+            return Ok(Vec::new());
+        }
+        let mut exports: Vec<PathBuf> = storage()
+            .list(&dir)
+            .with_context(|| format!("Failed to list {}", dir.display()))?
+            .into_iter()
+            .filter(|entry| entry.is_dir)
+            .map(|entry| entry.path)
+            .collect();
+        // Named by their UTC time, newest first
+        exports.sort();
+        exports.reverse();
+        let mut removed = Vec::new();
+        for export in exports.into_iter().skip(keep_last) {
+            match storage().remove_dir_all(&export) {
+                Ok(()) => {
+                    debug!("Removed export {}", export.display());
+                    removed.push(export);
+                }
+                Err(e) => warn!("Failed to remove {}: {}", export.display(), e),
+            }
+        }
+        if !removed.is_empty() {
+            info!(
+                "Removed {} old exports of world {}",
+                removed.len(),
+                world_id
+            );
+        }
+        Ok(removed)
+    }
+
+    /// Export `worlds` (or every world for `all`) every `interval`, running until the process
+    /// ends. Only the newest `keep_last` exports of each world are kept, when set.
+    pub async fn run_schedule(worlds: Vec<String>, interval: Duration, keep_last: Option<usize>) {
+        info!(
+            "Scheduled content export of {} every {:?}",
+            worlds.join(", "),
+            interval
+        );
+        loop {
+            sleep(interval).await;
+
+            let world_ids = if worlds.iter().any(|w| w == "all") {
+                match WorldService::ids() {
+                    Ok(ids) => ids,
+                    Err(e) => {
+                        error!("❌ Failed to list worlds for export: {}", e);
+                        continue;
+                    }
+                }
+            } else {
+                worlds.clone()
+            };
+
+            for world_id in &world_ids {
+                // Blocking file IO, keep it off the runtime threads serving the launcher
+                let id = world_id.clone();
+                let result = jobs::run("export", move || {
+                    let target = Self::export_world(&id)?;
+                    if let Some(keep_last) = keep_last {
+                        Self::prune(&id, keep_last)?;
+                    }
+                    anyhow::Ok(target)
+                })
+                .await;
+                match result {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => error!("❌ Export of world {} failed: {}", world_id, e),
+                    Err(e) => error!("❌ Export of world {} failed: {}", world_id, e),
+                }
+            }
+        }
+    }
+}
+
+fn export_actors(world_id: &str, dir: &Path) -> Result<usize> {
+    // Worlds without any actors have no collection yet
+    if !Collection::exists(world_id, "actors") {
+        return Ok(0);
+    }
+    let mut actors = Collection::open_snapshot(world_id, "actors")?;
+    let documents = actors.documents()?;
+    create_dir(dir)?;
+
+    for mut actor in documents.clone() {
+        for field in ACTOR_EMBEDDED {
+            actor[*field] = Value::Array(actors.embedded(field, &actor)?);
+        }
+        let path = dir.join(format!("{}.json", file_stem(&actor)));
+        storage()
+            .write(
+                &path,
+                serde_json::to_string_pretty(&actor)?.as_bytes(),
+                FileKind::Regular,
+            )
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(documents.len())
+}
+
+fn export_journals(world_id: &str, dir: &Path) -> Result<usize> {
+    if !Collection::exists(world_id, "journal") {
+        return Ok(0);
+    }
+    let mut journals = Collection::open_snapshot(world_id, "journal")?;
+    let documents = journals.documents()?;
+    create_dir(dir)?;
+
+    for journal in &documents {
+        let mut pages = journals.embedded("pages", journal)?;
+        pages.sort_by_key(|page| page["sort"].as_i64().unwrap_or_default());
+
+        let mut markdown = format!("# {}\n", journal["name"].as_str().unwrap_or("Untitled"));
+        // v9 and older journals have no pages, their text lives on the entry itself
+        if pages.is_empty()
+            && let Some(content) = journal["content"].as_str()
+        {
+            markdown.push('\n');
+            markdown.push_str(&html_to_markdown(content));
+        }
+        for page in &pages {
+            markdown.push_str(&format!(
+                "\n## {}\n\n",
+                page["name"].as_str().unwrap_or("Untitled")
+            ));
+            match page["type"].as_str() {
+                Some("text") => markdown.push_str(&html_to_markdown(
+                    page["text"]["content"].as_str().unwrap_or_default(),
+                )),
+                Some("image") | Some("video") | Some("pdf") => markdown.push_str(&format!(
+                    "[{}]({})\n",
+                    page["name"].as_str().unwrap_or_default(),
+                    page["src"].as_str().unwrap_or_default()
+                )),
+                other => markdown.push_str(&format!(
+                    "_{} page, not exportable_\n",
+                    other.unwrap_or("unknown")
+                )),
+            }
+        }
+
+        let path = dir.join(format!("{}.md", file_stem(journal)));
+        storage()
+            .write(&path, markdown.as_bytes(), FileKind::Regular)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(documents.len())
+}
+
+fn create_dir(dir: &Path) -> Result<()> {
+    storage()
+        .create_dir_all(dir)
+        .with_context(|| format!("Failed to create {}", dir.display()))
+}
+
+/// `<slugified name>-<id>`, names alone are not unique
+fn file_stem(document: &Value) -> String {
+    let slug: String = document["name"]
+        .as_str()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let slug = slug
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    format!("{}-{}", slug, document["_id"].as_str().unwrap_or("unknown"))
+}
+
+/// Convert the HTML Foundry's editor produces into readable Markdown.
+///
+/// Only the common formatting tags are translated, any other tag is dropped with its text kept.
+fn html_to_markdown(html: &str) -> String {
+    let mut markdown = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        markdown.push_str(&decode_entities(&rest[..start]));
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let tag = rest[start + 1..start + end].trim().to_lowercase();
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default();
+
+        match (name, closing) {
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", false) => {
+                let level = name[1..].parse::<usize>().unwrap_or(1);
+                // Page titles are already level 2, nest page headings below them
+                markdown.push_str(&format!("\n{} ", "#".repeat((level + 2).min(6))));
+            }
+            ("p" | "div" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "ul" | "ol", true) => {
+                markdown.push_str("\n\n")
+            }
+            ("br", _) => markdown.push('\n'),
+            ("li", false) => markdown.push_str("- "),
+            ("li", true) => markdown.push('\n'),
+            ("strong" | "b", _) => markdown.push_str("**"),
+            ("em" | "i", _) => markdown.push('_'),
+            ("hr", _) => markdown.push_str("\n---\n"),
+            _ => {}
+        }
+        rest = &rest[start + end + 1..];
+    }
+    markdown.push_str(&decode_entities(rest));
+
+    // Collapse the blank lines left behind by nested block tags
+    let mut collapsed = String::new();
+    for line in markdown.lines().map(|l| l.trim_end()) {
+        if line.is_empty() && (collapsed.is_empty() || collapsed.ends_with("\n\n")) {
+            continue;
+        }
+        collapsed.push_str(line);
+        collapsed.push('\n');
+    }
+    collapsed
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
@@ -1,5 +1,9 @@
 use crate::events::ProgressEvent;
+use crate::hostfs;
+use std::collections::HashSet;
 use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 use std::sync::Arc;
 use tokio::fs;
@@ -7,6 +11,7 @@ use tokio::sync::broadcast;
 use tokio::task;
 use tracing::{debug, error, info, warn};
 use zip::read::ZipArchive;
+use zip::result::ZipError;
 
 pub struct ExtractorService;
 
@@ -63,7 +68,7 @@ impl ExtractorService {
             debug!("Extracting to canonical path: {}", target_dir_obj.display());
 
             // Open and extract the file
-            let file = match File::open(&archive_path) {
+            let mut file = match File::open(&archive_path) {
                 Ok(f) => f,
                 Err(e) => {
                     error!("Failed to open archive file: {}", e);
@@ -76,6 +81,12 @@ impl ExtractorService {
                 }
             };
 
+            if let Err(e) = refuse_tar(&mut file) {
+                error!("{}", e);
+                let _ = event_tx_for_task.send(ProgressEvent::new("error", &e.to_string(), None));
+                return Err(e);
+            }
+
             let mut archive = match ZipArchive::new(file) {
                 Ok(a) => {
                     let file_count = a.len();
@@ -101,8 +112,16 @@ impl ExtractorService {
                 }
             };
 
+            // Windows drives cannot hold every name a Linux archive can contain
+            let windows = hostfs::is_windows_mount(target_dir_obj);
+            if windows {
+                info!("Target directory is on a Windows bind mount, checking archive names");
+            }
+            let extracted =
+                extract_entries(&mut archive, target_dir_obj, windows, &event_tx_for_task);
+
             // Extract with detailed error information
-            match archive.extract(&target_directory) {
+            match extracted {
                 Ok(()) => {
                     debug!("ZIP extraction completed successfully");
                     let _ = event_tx_for_task.send(ProgressEvent::new(
@@ -119,9 +138,8 @@ impl ExtractorService {
                         &format!("Extraction failed: {}", e),
                         None,
                     ));
-                    // Convert ZipError to std::io::Error
                     Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
+                        e.kind(),
                         format!("Extraction failed: {}", e),
                     ))
                 }
@@ -135,10 +153,10 @@ impl ExtractorService {
                 &format!("Extraction thread panicked: {}", e),
                 None,
             ));
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Extraction thread panicked: {}", e),
-            ))
+            Err(std::io::Error::other(format!(
+                "Extraction thread panicked: {}",
+                e
+            )))
         })?;
 
         info!(
@@ -148,3 +166,178 @@ impl ExtractorService {
         Ok(())
     }
 }
+
+/// Extract entry by entry into `target`, reporting progress. An entry that would land outside
+/// `target` by its name, or any link, fails the whole archive before anything of it is written
+/// there: a link is only checked by its text, and a chain of links can still lead out of
+/// `target`. On Windows mounts, names NTFS cannot store are skipped instead of failing the
+/// install.
+fn extract_entries(
+    archive: &mut ZipArchive<File>,
+    target: &Path,
+    windows: bool,
+    event_tx: &broadcast::Sender<ProgressEvent>,
+) -> io::Result<()> {
+    let mut relatives = Vec::with_capacity(archive.len());
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index).map_err(zip_error)?;
+        let relative = entry
+            .enclosed_name()
+            .ok_or_else(|| outside_target(entry.name()))?;
+        if entry.is_symlink() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Archive entry {} is a link, links are not extracted",
+                    entry.name()
+                ),
+            ));
+        }
+        relatives.push(relative);
+    }
+
+    let case_insensitive = windows && hostfs::is_case_insensitive(target);
+    let mut seen = HashSet::new();
+    let total = relatives.len();
+    let mut reported = 0;
+    for (index, relative) in relatives.into_iter().enumerate() {
+        let done = (index + 1) * 10 / total.max(1);
+        if done > reported {
+            reported = done;
+            debug!("Extracting entry {} of {}", index + 1, total);
+            let _ = event_tx.send(ProgressEvent::new(
+                "extracting",
+                &format!("Extracting file {} of {}", index + 1, total),
+                Some(65.0 + done as f32 * 2.0),
+            ));
+        }
+
+        let mut entry = archive.by_index(index).map_err(zip_error)?;
+        if windows {
+            let problem = relative.components().find_map(|component| {
+                hostfs::windows_name_problem(&component.as_os_str().to_string_lossy())
+            });
+            if let Some(problem) = problem {
+                warn!("Skipping {} ({})", relative.display(), problem);
+                continue;
+            }
+            // Entries differing only in case would overwrite each other
+            let key = relative.to_string_lossy().to_lowercase();
+            if case_insensitive && !entry.is_dir() && !seen.insert(key) {
+                warn!(
+                    "Skipping {}, another entry only differs in case",
+                    relative.display()
+                );
+                continue;
+            }
+        }
+
+        let path = target.join(&relative);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut out = File::create(&path)?;
+        io::copy(&mut entry, &mut out)?;
+        if let Some(mode) = entry.unix_mode() {
+            // Windows mounts without metadata support reject modes, the file itself is fine
+            let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode & 0o7777));
+        }
+    }
+    Ok(())
+}
+
+/// Fail with a clear message for tar archives, plain or gzipped, which are not supported,
+/// instead of the zip parser's complaint about a missing central directory
+fn refuse_tar(file: &mut File) -> io::Result<()> {
+    let mut head = Vec::with_capacity(262);
+    file.by_ref().take(262).read_to_end(&mut head)?;
+    file.seek(SeekFrom::Start(0))?;
+    let gzip = head.starts_with(&[0x1f, 0x8b]);
+    let tar = head.get(257..262) == Some(b"ustar".as_slice());
+    if gzip || tar {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Tar archives are not supported, only zip archives can be extracted",
+        ));
+    }
+    Ok(())
+}
+
+fn outside_target(name: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Archive entry {} points outside the target directory", name),
+    )
+}
+
+fn zip_error(e: ZipError) -> io::Error {
+    match e {
+        ZipError::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+
+    fn scratch(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("extractor-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn chained_links_are_refused() {
+        let dir = scratch("links");
+        let archive_path = dir.join("slip.zip");
+        let mut zip = ZipWriter::new(File::create(&archive_path).unwrap());
+        let options = SimpleFileOptions::default();
+        zip.add_symlink("a", ".", options).unwrap();
+        zip.add_symlink("a/b", "../..", options).unwrap();
+        zip.start_file("a/b/escaped.txt", options).unwrap();
+        zip.write_all(b"outside").unwrap();
+        zip.finish().unwrap();
+
+        let target = dir.join("target");
+        std::fs::create_dir_all(&target).unwrap();
+        let mut archive = ZipArchive::new(File::open(&archive_path).unwrap()).unwrap();
+        let (event_tx, _) = broadcast::channel(16);
+        let extracted = extract_entries(&mut archive, &target, false, &event_tx);
+
+        assert_eq!(extracted.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(std::fs::read_dir(&target).unwrap().next().is_none());
+        assert!(!dir.join("escaped.txt").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tar_archives_are_refused() {
+        let dir = scratch("tar");
+        let mut header = vec![0u8; 512];
+        header[..9].copy_from_slice(b"world.txt");
+        header[257..262].copy_from_slice(b"ustar");
+        let tar = dir.join("world.tar");
+        std::fs::write(&tar, &header).unwrap();
+        let gzip = dir.join("world.tar.gz");
+        std::fs::write(&gzip, [0x1f, 0x8b, 0x08, 0x00]).unwrap();
+        let zip = dir.join("world.zip");
+        ZipWriter::new(File::create(&zip).unwrap())
+            .finish()
+            .unwrap();
+
+        assert!(refuse_tar(&mut File::open(&tar).unwrap()).is_err());
+        assert!(refuse_tar(&mut File::open(&gzip).unwrap()).is_err());
+        let mut file = File::open(&zip).unwrap();
+        assert!(refuse_tar(&mut file).is_ok());
+        assert!(ZipArchive::new(file).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+use tracing::debug;
+
+use crate::configfile;
+use crate::permissions::{self, FileKind};
+use crate::utils::{installed_foundry_version, paths};
+
+/// Environment variables that change between container runs without affecting the setup
+pub const VOLATILE_ENV_VARS: &[&str] = &["HOSTNAME", "PWD", "OLDPWD", "SHLVL", "_", "TERM"];
+
+/// Hash over every input that influences startup validation and rendering
+pub struct BootFingerprint {
+    digest: String,
+}
+
+impl BootFingerprint {
+    /// Fingerprint the current environment, Foundry config and installed version
+    pub fn compute() -> Self {
+        let mut hasher = Sha256::new();
+        hash_settings(&mut hasher);
+
+        let options_json = PathBuf::from(&*paths::DATA_DIR)
+            .join("Config")
+            .join("options.json");
+        if let Ok(contents) = fs::read(options_json) {
+            hasher.update(Sha256::digest(contents));
+        }
+
+        if let Some(version) = installed_foundry_version() {
+            hasher.update(version.as_bytes());
+        }
+
+        Self {
+            digest: format!("{:x}", hasher.finalize()),
+        }
+    }
+
+    /// Whether this fingerprint equals the one recorded by the last successful boot
+    pub fn matches_last_boot(&self) -> bool {
+        match fs::read_to_string(Self::record_path()) {
+            Ok(previous) => previous.trim() == self.digest,
+            Err(_) => false,
+        }
+    }
+
+    /// Persist this fingerprint so the next boot can take the warm-start path
+    pub fn record_successful_boot(&self) -> Result<()> {
+        let path = Self::record_path();
+        if let Some(parent) = path.parent() {
+            permissions::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        permissions::write(&path, &self.digest, FileKind::Regular)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        debug!("Recorded boot fingerprint {}", self.digest);
+        Ok(())
+    }
+
+    fn record_path() -> PathBuf {
+        paths::WRAPPER_STATE_DIR.join("boot-fingerprint")
+    }
+}
+
+/// SHA-256 over the settings of the environment and the config file, without volatile ones
+pub fn settings_sha256() -> String {
+    let mut hasher = Sha256::new();
+    hash_settings(&mut hasher);
+    format!("{:x}", hasher.finalize())
+}
+
+fn hash_settings(hasher: &mut Sha256) {
+    let mut vars: Vec<(String, String)> = configfile::vars()
+        .into_iter()
+        .filter(|(key, _)| !VOLATILE_ENV_VARS.contains(&key.as_str()))
+        .collect();
+    vars.sort();
+    for (key, value) in vars {
+        hasher.update(key.as_bytes());
+        hasher.update(b"=");
+        hasher.update(value.as_bytes());
+        hasher.update(b"\n");
+    }
+}
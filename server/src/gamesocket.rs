@@ -0,0 +1,400 @@
+use anyhow::{Context, Result, anyhow};
+use futures_util::{SinkExt, StreamExt};
+use reqwest::header::{COOKIE, SET_COOKIE};
+use reqwest::redirect::Policy;
+use serde_json::{Value, json};
+use std::sync::OnceLock;
+use tokio::net::TcpStream;
+use tokio::time::{Duration, sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
+use tracing::{debug, info, warn};
+
+use crate::config::AppConfig;
+use crate::utils::installed_foundry_version;
+
+/// How long Foundry has to answer a single packet
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Name chat messages of the wrapper are shown with
+const SPEAKER: &str = "Server";
+/// `CONST.USER_ROLES.ASSISTANT`, the least role Foundry counts as Gamemaster
+const ASSISTANT_ROLE: u64 = 3;
+
+static CLIENT: OnceLock<GameClient> = OnceLock::new();
+
+/// Talks to the active world over Foundry's own socket.io connection, like a browser does.
+/// Reading who is connected needs no account, chat messages and shutting the world down need
+/// `GAME_USER`, a Gamemaster of the world the wrapper joins as.
+#[derive(Clone)]
+pub struct GameClient {
+    pub foundry_port: u16,
+    /// `GAME_USER` and `GAME_USER_PASSWORD`
+    pub user: Option<(String, String)>,
+    /// Players are warned in the chat this long before the wrapper restarts Foundry
+    pub restart_warning: Duration,
+}
+
+/// Set up the client, called once at startup
+pub fn init(config: &AppConfig) {
+    let client = GameClient::from_config(config);
+    if let Some((name, _)) = &client.user {
+        info!("Joining worlds as {} for chat messages and shutdowns", name);
+    }
+    let _ = CLIENT.set(client);
+}
+
+/// The client set up by `init`
+pub fn client() -> Option<&'static GameClient> {
+    CLIENT.get()
+}
+
+impl GameClient {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            foundry_port: config.server_port,
+            user: config.game_user.clone(),
+            restart_warning: Duration::from_secs(config.restart_warning),
+        }
+    }
+
+    /// Names of the users connected to the active world
+    pub async fn connected_users(&self) -> Result<Vec<String>> {
+        let session = self.session().await?;
+        let mut socket = GameSocket::open(self.foundry_port, &session).await?;
+        let data = socket.join_data().await?;
+        socket.close().await;
+        let active: Vec<&str> = data["activeUsers"]
+            .as_array()
+            .map(|ids| ids.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        Ok(users(&data)
+            .filter(|user| active.contains(&user.0))
+            .map(|user| user.1.to_string())
+            .collect())
+    }
+
+    /// Post `message` to the chat of the active world
+    pub async fn announce(&self, message: &str) -> Result<()> {
+        let (session, user_id) = self.join().await?;
+        let mut socket = GameSocket::open(self.foundry_port, &session).await?;
+        let content = html_escape(message);
+        let major = installed_foundry_version()
+            .and_then(|version| version.split('.').next()?.parse::<u32>().ok())
+            .unwrap_or(12);
+        // v12 wraps the data in an operation and renamed the message's user to author
+        let request = if major >= 12 {
+            json!({
+                "type": "ChatMessage",
+                "action": "create",
+                "operation": {
+                    "action": "create",
+                    "data": [{
+                        "content": content,
+                        "author": user_id,
+                        "speaker": { "alias": SPEAKER },
+                    }],
+                    "modifiedTime": chrono::Utc::now().timestamp_millis(),
+                    "render": true,
+                    "renderSheet": false,
+                },
+            })
+        } else {
+            json!({
+                "type": "ChatMessage",
+                "action": "create",
+                "data": [{
+                    "content": content,
+                    "user": user_id,
+                    "speaker": { "alias": SPEAKER },
+                }],
+                "options": { "render": true, "renderSheet": false },
+                "pack": null,
+            })
+        };
+        let response = socket.call("modifyDocument", vec![request]).await;
+        socket.close().await;
+        let response = response?;
+        if let Some(error) = response.get("error") {
+            return Err(anyhow!(
+                "Foundry refused the chat message: {}",
+                error["message"].as_str().unwrap_or(&error.to_string())
+            ));
+        }
+        Ok(())
+    }
+
+    /// Pause or unpause the game of the active world for everyone, like the Gamemaster's
+    /// space bar does
+    pub async fn set_paused(&self, paused: bool) -> Result<()> {
+        let (session, _) = self.join().await?;
+        let mut socket = GameSocket::open(self.foundry_port, &session).await?;
+        // Foundry does not acknowledge the pause, it only passes it on to the players
+        let sent = socket.emit("pause", vec![json!(paused)]).await;
+        socket.close().await;
+        sent
+    }
+
+    /// Shut the active world down, Foundry returns to the setup screen without a restart
+    pub async fn shut_down(&self) -> Result<()> {
+        let (session, _) = self.join().await?;
+        let response = http_client()?
+            .post(format!("http://127.0.0.1:{}/setup", self.foundry_port))
+            .header(COOKIE, format!("session={}", session))
+            .json(&json!({ "shutdown": true }))
+            .send()
+            .await?;
+        // Foundry answers the shutdown with a redirect to the setup screen
+        if response.status().is_client_error() || response.status().is_server_error() {
+            return Err(anyhow!(
+                "Foundry refused the shutdown: {}",
+                response.status()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Warn connected players in the chat and give them `RESTART_WARNING` to finish their turn
+    pub async fn warn_restart(&self) {
+        if self.user.is_none() || self.restart_warning.is_zero() {
+            return;
+        }
+        match self.connected_users().await {
+            Ok(users) if users.is_empty() => return,
+            Ok(users) => debug!("Warning {} about the restart", users.join(", ")),
+            // No active world, or Foundry does not answer, nobody to warn
+            Err(e) => {
+                debug!("Not warning about the restart: {:#}", e);
+                return;
+            }
+        }
+        let message = format!(
+            "The server restarts in {} seconds, the world is back shortly after.",
+            self.restart_warning.as_secs()
+        );
+        match self.announce(&message).await {
+            Ok(()) => {
+                info!(
+                    "Warned the players, restarting in {} seconds",
+                    self.restart_warning.as_secs()
+                );
+                sleep(self.restart_warning).await;
+            }
+            Err(e) => warn!("⚠️ Failed to warn the players about the restart: {:#}", e),
+        }
+    }
+
+    /// Log in as `GAME_USER`, answering the session cookie and the id of the user
+    async fn join(&self) -> Result<(String, String)> {
+        let (name, password) = self
+            .user
+            .as_ref()
+            .ok_or_else(|| anyhow!("GAME_USER is not set"))?;
+        let session = self.session().await?;
+        let mut socket = GameSocket::open(self.foundry_port, &session).await?;
+        let data = socket.join_data().await?;
+        socket.close().await;
+        let (id, _, role) = users(&data)
+            .find(|user| user.1.eq_ignore_ascii_case(name))
+            .ok_or_else(|| anyhow!("The world has no user {}", name))?;
+        if role < ASSISTANT_ROLE {
+            warn!(
+                "⚠️ GAME_USER {} is no Gamemaster, Foundry may refuse its requests",
+                name
+            );
+        }
+
+        let response = http_client()?
+            .post(format!("http://127.0.0.1:{}/join", self.foundry_port))
+            .header(COOKIE, format!("session={}", session))
+            .json(&json!({ "action": "join", "userid": id, "password": password }))
+            .send()
+            .await?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or_default();
+        if !status.is_success() || body.get("error").is_some() {
+            return Err(anyhow!(
+                "Foundry refused to join as {}: {}",
+                name,
+                body["error"].as_str().unwrap_or(status.as_str())
+            ));
+        }
+        Ok((session, id.to_string()))
+    }
+
+    /// Fresh session cookie from the join screen, which redirects while no world is active
+    async fn session(&self) -> Result<String> {
+        let response = http_client()?
+            .get(format!("http://127.0.0.1:{}/join", self.foundry_port))
+            .send()
+            .await
+            .context("Foundry does not answer")?;
+        if response.status().is_redirection() {
+            return Err(anyhow!("No world is active"));
+        }
+        response
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .find_map(|cookie| cookie.strip_prefix("session="))
+            .map(|cookie| cookie.split(';').next().unwrap_or_default().to_string())
+            .ok_or_else(|| anyhow!("Foundry did not set a session cookie"))
+    }
+}
+
+/// Whether Foundry's socket answers the Engine.IO handshake, which takes a turn of its event
+/// loop. Needs no session, the handshake comes before socket.io asks for one.
+pub async fn ping(port: u16, timeout_after: Duration) -> Result<()> {
+    let url = format!(
+        "ws://127.0.0.1:{}/socket.io/?EIO=4&transport=websocket",
+        port
+    );
+    let (mut socket, _) = timeout(timeout_after, connect_async(url))
+        .await
+        .context("Foundry did not accept the socket in time")?
+        .context("Failed to connect to Foundry's socket")?;
+    let open = timeout(timeout_after, socket.next())
+        .await
+        .context("Foundry did not answer on the socket")?;
+    let _ = socket.close(None).await;
+    match open {
+        Some(Ok(Message::Text(text))) if text.starts_with('0') => Ok(()),
+        Some(Ok(message)) => Err(anyhow!("Unexpected Engine.IO handshake: {}", message)),
+        Some(Err(e)) => Err(e.into()),
+        None => Err(anyhow!("Foundry closed the socket")),
+    }
+}
+
+/// `(id, name, role)` of the users in the join data of a world
+fn users(data: &Value) -> impl Iterator<Item = (&str, &str, u64)> {
+    data["users"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|user| {
+            Some((
+                user["_id"].as_str()?,
+                user["name"].as_str()?,
+                user["role"].as_u64().unwrap_or_default(),
+            ))
+        })
+}
+
+fn http_client() -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(REPLY_TIMEOUT)
+        .redirect(Policy::none())
+        .build()
+        .context("Failed to build the HTTP client")
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// One socket.io connection to Foundry, Engine.IO v4 over a websocket
+struct GameSocket {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    next_ack: u64,
+}
+
+impl GameSocket {
+    async fn open(port: u16, session: &str) -> Result<Self> {
+        let url = format!(
+            "ws://127.0.0.1:{}/socket.io/?session={}&EIO=4&transport=websocket",
+            port, session
+        );
+        let (socket, _) = timeout(REPLY_TIMEOUT, connect_async(url))
+            .await
+            .context("Foundry did not accept the socket in time")?
+            .context("Failed to connect to Foundry's socket")?;
+        let mut socket = Self {
+            socket,
+            next_ack: 0,
+        };
+        let open = socket.next_packet().await?;
+        if !open.starts_with('0') {
+            return Err(anyhow!("Unexpected Engine.IO handshake: {}", open));
+        }
+        socket.socket.send(Message::text("40")).await?;
+        loop {
+            let packet = socket.next_packet().await?;
+            if packet.starts_with("40") {
+                return Ok(socket);
+            }
+            if let Some(error) = packet.strip_prefix("44") {
+                return Err(anyhow!("Foundry refused the socket: {}", error));
+            }
+        }
+    }
+
+    /// The users of the world and who of them is connected, as the join screen reads them
+    async fn join_data(&mut self) -> Result<Value> {
+        self.call("getJoinData", Vec::new()).await
+    }
+
+    /// Emit an event nobody answers
+    async fn emit(&mut self, event: &str, args: Vec<Value>) -> Result<()> {
+        let mut payload = vec![json!(event)];
+        payload.extend(args);
+        self.socket
+            .send(Message::text(format!("42{}", Value::Array(payload))))
+            .await?;
+        Ok(())
+    }
+
+    /// Emit an event and wait for its acknowledgement, events received meanwhile are dropped
+    async fn call(&mut self, event: &str, args: Vec<Value>) -> Result<Value> {
+        let id = self.next_ack;
+        self.next_ack += 1;
+        let mut payload = vec![json!(event)];
+        payload.extend(args);
+        self.socket
+            .send(Message::text(format!("42{}{}", id, Value::Array(payload))))
+            .await?;
+        let prefix = format!("43{}", id);
+        loop {
+            let packet = self.next_packet().await?;
+            if let Some(rest) = packet.strip_prefix(&prefix)
+                && rest.starts_with('[')
+            {
+                let mut values: Vec<Value> =
+                    serde_json::from_str(rest).context("Invalid acknowledgement")?;
+                return Ok(match values.is_empty() {
+                    true => Value::Null,
+                    false => values.swap_remove(0),
+                });
+            }
+        }
+    }
+
+    /// Next Engine.IO packet, answering pings on the way
+    async fn next_packet(&mut self) -> Result<String> {
+        loop {
+            let message = timeout(REPLY_TIMEOUT, self.socket.next())
+                .await
+                .context("Foundry did not answer on the socket")?;
+            match message {
+                Some(Ok(Message::Text(text))) if text.as_str() == "2" => {
+                    self.socket.send(Message::text("3")).await?;
+                }
+                Some(Ok(Message::Text(text))) => return Ok(text.to_string()),
+                Some(Ok(Message::Close(frame))) => {
+                    return Err(anyhow!("Foundry closed the socket: {:?}", frame));
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(e.into()),
+                None => return Err(anyhow!("Foundry closed the socket")),
+            }
+        }
+    }
+
+    /// Leave, so the user does not stay listed as connected
+    async fn close(mut self) {
+        let _ = self.socket.send(Message::text("41")).await;
+        let _ = self.socket.close(None).await;
+    }
+}
@@ -0,0 +1,170 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use sha2::{Digest, Sha256};
+
+use crate::headers;
+
+/// Realm shown in the browser's login prompt
+const REALM: &str = "Foundry VTT";
+
+/// Authentication the proxy asks for before anything reaches Foundry, the join page and game
+/// websockets included. A second factor in front of Foundry's own login for private tables.
+#[derive(Debug, Clone)]
+pub enum AuthGate {
+    /// HTTP basic auth against a fixed list of users
+    Basic { users: Vec<(String, String)> },
+    /// A header set by a forward-auth reverse proxy such as Authelia or Authentik after login.
+    /// The proxy port must only be reachable through it, clients could send it themselves.
+    ForwardAuth { header: String },
+}
+
+impl AuthGate {
+    /// Users from `user:password` entries separated by commas or line breaks
+    pub fn basic(entries: &str) -> Self {
+        let users = entries
+            .split([',', '\n'])
+            .filter_map(|entry| entry.trim().split_once(':'))
+            .map(|(user, password)| (user.to_string(), password.to_string()))
+            .collect();
+        Self::Basic { users }
+    }
+
+    /// The answer refusing the request with the head `request`, nothing when it may pass
+    pub fn check(&self, request: &[u8]) -> Option<Vec<u8>> {
+        let answer = match self {
+            Self::Basic { users } => {
+                let credentials = headers::header(request, "authorization")
+                    .and_then(|value| {
+                        let (scheme, encoded) = value.split_once(' ')?;
+                        scheme
+                            .eq_ignore_ascii_case("basic")
+                            .then(|| STANDARD.decode(encoded.trim()).ok())?
+                    })
+                    .and_then(|decoded| String::from_utf8(decoded).ok());
+                let authorized = credentials
+                    .as_deref()
+                    .and_then(|c| c.split_once(':'))
+                    .is_some_and(|(user, password)| {
+                        // Compare every entry, the time taken does not tell which user exists
+                        users
+                            .iter()
+                            .fold(false, |found, (known_user, known_password)| {
+                                found | (equal(user, known_user) & equal(password, known_password))
+                            })
+                    });
+                if authorized {
+                    return None;
+                }
+                format!(
+                    "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"{}\", charset=\"UTF-8\"\r\n\
+                     Connection: close\r\nContent-Length: 0\r\n\r\n",
+                    REALM
+                )
+            }
+            Self::ForwardAuth { header } => {
+                if headers::header(request, &header.to_ascii_lowercase())
+                    .is_some_and(|user| !user.is_empty())
+                {
+                    return None;
+                }
+                "HTTP/1.1 403 Forbidden\r\nConnection: close\r\nContent-Length: 0\r\n\r\n"
+                    .to_string()
+            }
+        };
+        Some(answer.into_bytes())
+    }
+}
+
+/// Compare in constant time, hashing first so the length does not leak either
+fn equal(a: &str, b: &str) -> bool {
+    let (a, b) = (Sha256::digest(a), Sha256::digest(b));
+    a.iter()
+        .zip(b.iter())
+        .fold(0, |diff, (x, y)| diff | (x ^ y))
+        == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(headers: &str) -> Vec<u8> {
+        format!("GET /join HTTP/1.1\r\nHost: localhost\r\n{}\r\n", headers).into_bytes()
+    }
+
+    fn basic(credentials: &str) -> Vec<u8> {
+        request(&format!(
+            "Authorization: Basic {}\r\n",
+            STANDARD.encode(credentials)
+        ))
+    }
+
+    fn status(answer: Option<Vec<u8>>) -> Option<String> {
+        answer.map(|answer| String::from_utf8(answer).unwrap()[9..12].to_string())
+    }
+
+    #[test]
+    fn basic_auth_lets_known_users_pass() {
+        let gate = AuthGate::basic("gm:secret, player:p:w:d\n");
+        assert_eq!(status(gate.check(&basic("gm:secret"))), None);
+        // Only the first colon separates the user, passwords may contain more
+        assert_eq!(status(gate.check(&basic("player:p:w:d"))), None);
+        let lowercase = request(&format!(
+            "authorization: basic {}\r\n",
+            STANDARD.encode("gm:secret")
+        ));
+        assert_eq!(status(gate.check(&lowercase)), None);
+    }
+
+    #[test]
+    fn basic_auth_refuses_everything_else() {
+        let gate = AuthGate::basic("gm:secret");
+        for refused in [
+            basic("gm:wrong"),
+            basic("nobody:secret"),
+            basic("gm"),
+            basic(""),
+            request(""),
+            request(&format!(
+                "Authorization: Bearer {}\r\n",
+                STANDARD.encode("gm:secret")
+            )),
+            request("Authorization: Basic not*base64\r\n"),
+            request("Authorization: Basic Z206c2VjcmV0=\r\n"),
+            request("Authorization: Basic\r\n"),
+        ] {
+            let answer = String::from_utf8(gate.check(&refused).unwrap()).unwrap();
+            assert!(answer.starts_with("HTTP/1.1 401 "), "{}", answer);
+            assert!(answer.contains("WWW-Authenticate: Basic realm=\"Foundry VTT\""));
+        }
+    }
+
+    #[test]
+    fn forward_auth_needs_the_header_with_a_user() {
+        let gate = AuthGate::ForwardAuth {
+            header: "Remote-User".to_string(),
+        };
+        assert_eq!(status(gate.check(&request("remote-user: alice\r\n"))), None);
+        assert_eq!(status(gate.check(&request(""))).as_deref(), Some("403"));
+        assert_eq!(
+            status(gate.check(&request("Remote-User:\r\n"))).as_deref(),
+            Some("403")
+        );
+        assert_eq!(
+            status(gate.check(&request("Remote-User: \r\n"))).as_deref(),
+            Some("403")
+        );
+        assert_eq!(
+            status(gate.check(&request("X-Remote-User: alice\r\n"))).as_deref(),
+            Some("403")
+        );
+    }
+
+    #[test]
+    fn equal_compares_whole_strings() {
+        assert!(equal("secret", "secret"));
+        assert!(!equal("secret", "secreT"));
+        assert!(!equal("secret", "secret2"));
+        assert!(!equal("", "secret"));
+    }
+}
@@ -1,15 +1,20 @@
+use crate::assets;
 use crate::downloader::DownloadService;
 use crate::events::ProgressEvent;
-use crate::extractor::ExtractorService;
+use crate::i18n;
+use crate::install::InstallService;
 use crate::server::AppState;
+use crate::tempfiles;
 use actix_multipart::Multipart;
-use actix_web::{HttpResponse, Responder, web};
+use actix_web::http::header;
+use actix_web::{Either, HttpRequest, HttpResponse, Responder, web};
 use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 use tokio::sync::broadcast;
+use tokio::time::{Duration, sleep};
 use tracing::{debug, error, info, warn};
 
 #[derive(Deserialize)]
@@ -34,6 +39,138 @@ pub async fn info() -> impl Responder {
     })
 }
 
+/// Seconds between reloads of the progress page while the installation is running
+const PROGRESS_REFRESH: &str = r#"<meta http-equiv="refresh" content="3" />"#;
+
+/// Time polling progress pages get to show the result before the installer stops
+const COMPLETION_NOTICE: Duration = Duration::from_secs(5);
+
+/// Render the setup page, replacing `{{ id }}` placeholders with messages from the catalog
+pub async fn index(app_state: web::Data<AppState>) -> impl Responder {
+    render_page(&app_state, "index.html", &[]).await
+}
+
+/// Server rendered installation progress for browsers without JavaScript, the page reloads
+/// itself until the installation finished or failed
+pub async fn progress(app_state: web::Data<AppState>) -> impl Responder {
+    let event = app_state.last_event.lock().unwrap().clone();
+    let percent = event
+        .as_ref()
+        .and_then(|event| event.progress)
+        .unwrap_or(0.0)
+        .clamp(0.0, 100.0)
+        .round()
+        .to_string();
+
+    let (heading, message, hint, refresh) = match &event {
+        Some(event) if event.event_type == "error" => (
+            i18n::tr("progress-error"),
+            escape_html(&event.message),
+            i18n::tr("progress-error-hint"),
+            "",
+        ),
+        Some(event) if event.event_type == "complete" || event.event_type == "transition" => (
+            i18n::tr("progress-complete"),
+            escape_html(&event.message),
+            i18n::tr("progress-complete-hint"),
+            // Foundry takes over the port once the installer stopped
+            r#"<meta http-equiv="refresh" content="30; url=/" />"#,
+        ),
+        Some(event) => (
+            i18n::tr("progress-running"),
+            escape_html(&event.message),
+            i18n::tr("progress-running-hint"),
+            PROGRESS_REFRESH,
+        ),
+        None => (
+            i18n::tr("progress-running"),
+            i18n::tr("progress-waiting"),
+            i18n::tr("progress-running-hint"),
+            PROGRESS_REFRESH,
+        ),
+    };
+
+    render_page(
+        &app_state,
+        "progress.html",
+        &[
+            ("heading", heading),
+            ("message", message),
+            ("hint", hint),
+            ("percent", percent),
+            ("refresh", refresh.to_string()),
+        ],
+    )
+    .await
+}
+
+async fn render_page(app_state: &AppState, name: &str, values: &[(&str, String)]) -> HttpResponse {
+    match assets::load(name, app_state.static_files_dir.as_deref()).await {
+        Ok(template) => HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .insert_header((header::CACHE_CONTROL, "no-store"))
+            .body(render_template(&template, values)),
+        Err(e) => {
+            error!("{}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Setup page not found".to_string(),
+            })
+        }
+    }
+}
+
+/// Catalog entries are trusted HTML shipped with the binary and inserted as they are, so are
+/// `values`, callers escape anything coming from outside
+fn render_template(template: &str, values: &[(&str, String)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        let key = rest[start + 2..start + end].trim();
+        match key {
+            "lang" => rendered.push_str(i18n::language()),
+            // Script messages are read by index.js, `</` must not end the script element
+            "messages" => rendered.push_str(
+                &serde_json::to_string(&i18n::messages("setup-js-"))
+                    .unwrap_or_default()
+                    .replace("</", "<\\/"),
+            ),
+            _ => match values.iter().find(|(name, _)| *name == key) {
+                Some((_, value)) => rendered.push_str(value),
+                None => rendered.push_str(&i18n::tr(key)),
+            },
+        }
+        rest = &rest[start + end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Browsers submitting the setup forms without JavaScript follow the progress page
+fn see_progress() -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, "/progress"))
+        .finish()
+}
+
+/// Whether the request is a plain form post from a browser rather than a script
+fn accepts_html(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"))
+}
+
 // Helper functions to reduce code duplication
 
 /// Ensures the target directory exists and returns its path
@@ -75,13 +212,9 @@ async fn extract_and_cleanup(
         Some(60.0),
     ));
 
-    // Extract the archive
-    if let Err(e) = ExtractorService::extract_zip(
-        archive_path.clone(),
-        target_directory.clone(),
-        event_tx.clone(),
-    )
-    .await
+    // Verify and install the archive
+    if let Err(e) =
+        InstallService::install_archive(&archive_path, &target_directory, event_tx.clone()).await
     {
         error!("Extraction error: {}", e);
         let _ = event_tx.send(ProgressEvent::new(
@@ -139,6 +272,7 @@ async fn complete_installation(
     }
 
     // Signal the server to shut down
+    sleep(COMPLETION_NOTICE).await;
     if let Some(tx) = app_state.shutdown_sender.lock().unwrap().take() {
         let _ = tx.send(());
         info!("Sent shutdown signal to Actix server");
@@ -157,10 +291,22 @@ async fn complete_installation(
 }
 
 pub async fn download_and_extract(
-    url_payload: web::Json<UrlPayload>,
+    payload: Either<web::Json<UrlPayload>, web::Form<UrlPayload>>,
     app_state: web::Data<AppState>,
 ) -> impl Responder {
-    let url = url_payload.url.clone();
+    match payload {
+        Either::Left(json) => install_from_url(json.into_inner().url, app_state).await,
+        // Plain form posts come from browsers without JavaScript, the download continues in the
+        // background while they follow the progress page
+        Either::Right(form) => {
+            *app_state.last_event.lock().unwrap() = None;
+            actix_web::rt::spawn(install_from_url(form.into_inner().url, app_state));
+            see_progress()
+        }
+    }
+}
+
+async fn install_from_url(url: String, app_state: web::Data<AppState>) -> HttpResponse {
     let event_tx = app_state.event_channel.clone();
 
     info!("Received request to download and extract from URL: {}", url);
@@ -260,13 +406,38 @@ pub async fn download_and_extract(
 }
 
 pub async fn upload_and_extract(
-    mut payload: Multipart,
+    req: HttpRequest,
+    payload: Multipart,
     app_state: web::Data<AppState>,
 ) -> impl Responder {
     let event_tx = app_state.event_channel.clone();
 
     info!("Received file upload request");
+    // The upload has to be read within the request, browsers without JavaScript only follow the
+    // progress page for the extraction
+    let from_form = accepts_html(&req);
+    if from_form {
+        *app_state.last_event.lock().unwrap() = None;
+    }
+
+    let (archive_path, target_directory) = match save_upload(payload, &event_tx).await {
+        Ok(saved) => saved,
+        Err(_) if from_form => return see_progress(),
+        Err(response) => return response,
+    };
+
+    if from_form {
+        actix_web::rt::spawn(install_upload(archive_path, target_directory, app_state));
+        return see_progress();
+    }
+    install_upload(archive_path, target_directory, app_state).await
+}
 
+/// Store the uploaded archive in the target directory, returning its path and the directory
+async fn save_upload(
+    mut payload: Multipart,
+    event_tx: &broadcast::Sender<ProgressEvent>,
+) -> Result<(String, String), HttpResponse> {
     // Send initial progress event
     let _ = event_tx.send(ProgressEvent::new(
         "start",
@@ -275,13 +446,11 @@ pub async fn upload_and_extract(
     ));
 
     // Ensure target directory exists
-    let target_directory = match ensure_target_directory(&event_tx).await {
-        Ok(dir) => dir,
-        Err(response) => return response,
-    };
+    let target_directory = ensure_target_directory(event_tx).await?;
 
     let archive_path = format!("{}/archive.zip", target_directory);
     debug!("Archive will be saved to: {}", archive_path);
+    tempfiles::track(Path::new(&archive_path), "upload");
 
     // Process the uploaded file
     let mut file = match fs::File::create(&archive_path).await {
@@ -293,9 +462,9 @@ pub async fn upload_and_extract(
                 &format!("Failed to create file: {}", e),
                 None,
             ));
-            return HttpResponse::InternalServerError().json(ErrorResponse {
+            return Err(HttpResponse::InternalServerError().json(ErrorResponse {
                 error: format!("Failed to create file: {}", e),
-            });
+            }));
         }
     };
 
@@ -313,9 +482,9 @@ pub async fn upload_and_extract(
                     &format!("Upload error: {}", e),
                     None,
                 ));
-                return HttpResponse::InternalServerError().json(ErrorResponse {
+                return Err(HttpResponse::InternalServerError().json(ErrorResponse {
                     error: format!("Upload error: {}", e),
-                });
+                }));
             }
         };
 
@@ -338,9 +507,9 @@ pub async fn upload_and_extract(
                         &format!("Upload error: {}", e),
                         None,
                     ));
-                    return HttpResponse::InternalServerError().json(ErrorResponse {
+                    return Err(HttpResponse::InternalServerError().json(ErrorResponse {
                         error: format!("Upload error: {}", e),
-                    });
+                    }));
                 }
             };
 
@@ -355,9 +524,9 @@ pub async fn upload_and_extract(
                     &format!("Error writing to file: {}", e),
                     None,
                 ));
-                return HttpResponse::InternalServerError().json(ErrorResponse {
+                return Err(HttpResponse::InternalServerError().json(ErrorResponse {
                     error: format!("Error writing to file: {}", e),
-                });
+                }));
             }
 
             // Update progress (scaling between 10-50%)
@@ -377,9 +546,9 @@ pub async fn upload_and_extract(
     if total_bytes == 0 {
         error!("Uploaded file is empty");
         let _ = event_tx.send(ProgressEvent::new("error", "Uploaded file is empty", None));
-        return HttpResponse::BadRequest().json(ErrorResponse {
+        return Err(HttpResponse::BadRequest().json(ErrorResponse {
             error: "Uploaded file is empty".to_string(),
-        });
+        }));
     }
 
     info!(
@@ -402,9 +571,9 @@ pub async fn upload_and_extract(
             if size_bytes == 0 {
                 error!("Uploaded file is empty (0 bytes)");
                 let _ = event_tx.send(ProgressEvent::new("error", "Uploaded file is empty", None));
-                return HttpResponse::InternalServerError().json(ErrorResponse {
+                return Err(HttpResponse::InternalServerError().json(ErrorResponse {
                     error: "Uploaded file is empty".to_string(),
-                });
+                }));
             }
             info!("Uploaded file size: {} bytes", size_bytes);
         }
@@ -415,12 +584,22 @@ pub async fn upload_and_extract(
                 &format!("Failed to verify uploaded file: {}", e),
                 None,
             ));
-            return HttpResponse::InternalServerError().json(ErrorResponse {
+            return Err(HttpResponse::InternalServerError().json(ErrorResponse {
                 error: format!("Failed to verify uploaded file: {}", e),
-            });
+            }));
         }
     }
 
+    Ok((archive_path, target_directory))
+}
+
+async fn install_upload(
+    archive_path: String,
+    target_directory: String,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let event_tx = app_state.event_channel.clone();
+
     // Extract and cleanup
     if let Err(response) =
         extract_and_cleanup(archive_path, target_directory, event_tx.clone()).await
@@ -0,0 +1,157 @@
+/// Security and CORS headers the proxy adds to Foundry's responses.
+///
+/// `None` leaves a header as Foundry sends it. Game websockets are not touched.
+#[derive(Debug, Clone, Default)]
+pub struct SecurityHeaders {
+    /// `Strict-Transport-Security`, browsers refuse plain HTTP to the host for a year after it
+    pub hsts: Option<String>,
+    /// `Content-Security-Policy`
+    pub csp: Option<String>,
+    /// `X-Frame-Options`
+    pub frame_options: Option<String>,
+    /// `X-Content-Type-Options`
+    pub content_type_options: Option<String>,
+    /// `Referrer-Policy`
+    pub referrer_policy: Option<String>,
+    /// Origins allowed to read responses cross-origin, `*` allows every origin
+    pub cors_origins: Vec<String>,
+}
+
+/// Methods allowed in answers to CORS preflight requests
+const CORS_METHODS: &str = "GET, HEAD, POST, PUT, PATCH, DELETE, OPTIONS";
+
+impl SecurityHeaders {
+    /// Defaults of a deployment mode: `public` for players connecting over the internet with
+    /// HTTPS, `lan` for plain HTTP at home or at the table and `off` to add nothing.
+    ///
+    /// The policy only forbids framing by other sites, a stricter one breaks modules loading
+    /// scripts and fonts from CDNs. Set `PROXY_CSP` for that.
+    pub fn for_mode(mode: &str) -> Option<Self> {
+        let hsts = match mode {
+            "public" => Some("max-age=31536000".to_string()),
+            "lan" => None,
+            "off" => return Some(Self::default()),
+            _ => return None,
+        };
+        Some(Self {
+            hsts,
+            csp: Some("frame-ancestors 'self'".to_string()),
+            frame_options: Some("SAMEORIGIN".to_string()),
+            content_type_options: Some("nosniff".to_string()),
+            referrer_policy: Some("same-origin".to_string()),
+            cors_origins: Vec::new(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers().next().is_none() && self.cors_origins.is_empty()
+    }
+
+    fn headers(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        [
+            ("Strict-Transport-Security", &self.hsts),
+            ("Content-Security-Policy", &self.csp),
+            ("X-Frame-Options", &self.frame_options),
+            ("X-Content-Type-Options", &self.content_type_options),
+            ("Referrer-Policy", &self.referrer_policy),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value.as_deref()?)))
+    }
+
+    /// The value of `Access-Control-Allow-Origin` for a request from `origin`
+    fn allowed_origin(&self, origin: Option<&str>) -> Option<String> {
+        let origin = origin?;
+        if self.cors_origins.iter().any(|o| o == "*") {
+            Some("*".to_string())
+        } else {
+            self.cors_origins
+                .iter()
+                .find(|o| o.eq_ignore_ascii_case(origin))
+                .map(|_| origin.to_string())
+        }
+    }
+
+    /// `response_head` with the configured headers replacing Foundry's, for a request with
+    /// the head `request_head`
+    pub fn apply(&self, request_head: &[u8], response_head: &[u8]) -> Vec<u8> {
+        let Some(end) = response_head.windows(4).position(|w| w == b"\r\n\r\n") else {
+            return response_head.to_vec();
+        };
+        let head = String::from_utf8_lossy(&response_head[..end]);
+        let mut lines = head.split("\r\n");
+        let status_line = lines.next().unwrap_or_default();
+
+        let mut added: Vec<(&str, String)> = self
+            .headers()
+            .map(|(name, value)| (name, value.to_string()))
+            .collect();
+        let cors_origin = self.allowed_origin(header(request_head, "origin").as_deref());
+        if let Some(origin) = &cors_origin {
+            added.push(("Access-Control-Allow-Origin", origin.clone()));
+        }
+
+        let mut out = format!("{}\r\n", status_line);
+        for line in lines {
+            let name = line.split(':').next().unwrap_or_default().trim();
+            if !added
+                .iter()
+                .any(|(added, _)| added.eq_ignore_ascii_case(name))
+            {
+                out.push_str(line);
+                out.push_str("\r\n");
+            }
+        }
+        for (name, value) in &added {
+            out.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        // Next to Foundry's own `Vary`, caches keep the answers per origin
+        if cors_origin.is_some() {
+            out.push_str("Vary: Origin\r\n");
+        }
+        out.push_str("\r\n");
+
+        let mut bytes = out.into_bytes();
+        bytes.extend_from_slice(&response_head[end + 4..]);
+        bytes
+    }
+
+    /// The answer to a CORS preflight request from an allowed origin, which Foundry itself
+    /// does not know how to answer
+    pub fn preflight(&self, request_head: &[u8]) -> Option<Vec<u8>> {
+        let request = String::from_utf8_lossy(request_head);
+        if !request.starts_with("OPTIONS ") {
+            return None;
+        }
+        header(request_head, "access-control-request-method")?;
+        let origin = self.allowed_origin(header(request_head, "origin").as_deref())?;
+
+        let mut answer = format!(
+            "HTTP/1.1 204 No Content\r\nAccess-Control-Allow-Origin: {}\r\nVary: Origin\r\n\
+             Access-Control-Allow-Methods: {}\r\nAccess-Control-Max-Age: 600\r\n",
+            origin, CORS_METHODS
+        );
+        if let Some(requested) = header(request_head, "access-control-request-headers") {
+            answer.push_str(&format!("Access-Control-Allow-Headers: {}\r\n", requested));
+        }
+        for (name, value) in self.headers() {
+            answer.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        answer.push_str("Content-Length: 0\r\n\r\n");
+        Some(answer.into_bytes())
+    }
+}
+
+/// Value of the header `name` (lowercase) in an HTTP head
+pub fn header(head: &[u8], name: &str) -> Option<String> {
+    let end = head
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .unwrap_or(head.len());
+    String::from_utf8_lossy(&head[..end])
+        .split("\r\n")
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim().to_string())
+}
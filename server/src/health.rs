@@ -0,0 +1,182 @@
+use actix_web::{App, HttpResponse, HttpServer, Responder, web};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::capabilities::{self, Capability};
+use crate::config::AppConfig;
+use crate::idle;
+use crate::status;
+use crate::tasks;
+use crate::uptime::{self, UptimeReport};
+
+pub struct HealthConfig {
+    pub foundry_port: u16,
+}
+
+/// Serve the probes alone on `HEALTHCHECK_PORT`. Unlike the admin API it exposes nothing but
+/// health, so it can be published to load balancers and probed without credentials.
+pub async fn start_health_server(config: &AppConfig, port: u16) -> std::io::Result<()> {
+    let health_config = web::Data::new(HealthConfig {
+        foundry_port: config.server_port,
+    });
+    // Probes come every few seconds, they are not logged like admin requests
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(health_config.clone())
+            .route("/livez", web::get().to(livez))
+            .route("/healthz", web::get().to(healthz))
+            .route("/readyz", web::get().to(readyz))
+    })
+    .workers(1)
+    .disable_signals()
+    .bind((config.server_host.clone(), port))?
+    .run();
+
+    info!("Health checks listening on {}:{}", config.server_host, port);
+    tokio::spawn(server);
+    Ok(())
+}
+
+/// Liveness of the wrapper itself
+#[derive(Serialize, ToSchema)]
+pub struct Alive {
+    pub alive: bool,
+}
+
+/// The Foundry process, the wrapper's subsystems and the configured integrations
+#[derive(Serialize, ToSchema)]
+pub struct Health {
+    pub installing: bool,
+    pub running: bool,
+    pub pid: Option<u32>,
+    pub restarts: u32,
+    /// Foundry is stopped while idle, the next connection starts it
+    pub sleeping: bool,
+    pub subsystems: BTreeMap<String, Subsystem>,
+    pub capabilities: BTreeMap<String, Capability>,
+    pub uptime: UptimeReport,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Subsystem {
+    pub healthy: bool,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Readiness {
+    pub ready: bool,
+    /// Active world, when ready
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub world: Option<String>,
+    /// Connected players, when ready
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub players: Option<u32>,
+    /// Why players cannot join, when not ready
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Readiness {
+    fn not_ready(reason: impl Into<String>) -> Self {
+        Self {
+            ready: false,
+            world: None,
+            players: None,
+            reason: Some(reason.into()),
+        }
+    }
+}
+
+/// Liveness of the wrapper itself, whatever Foundry is doing
+#[utoipa::path(
+    get,
+    path = "/livez",
+    operation_id = "livez",
+    responses((status = 200, description = "The wrapper answers", body = Alive))
+)]
+pub async fn livez() -> impl Responder {
+    HttpResponse::Ok().json(Alive { alive: true })
+}
+
+/// Liveness of the Foundry process or the installer waiting for a release
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    operation_id = "healthz",
+    responses(
+        (status = 200, description = "Foundry is running, stopped while idle, or the installer waits for a release", body = Health),
+        (status = 503, description = "Foundry is not running", body = Health),
+    )
+)]
+pub async fn healthz() -> impl Responder {
+    let status = status::current();
+    // Crashed integrations are reported, they never make Foundry itself unhealthy
+    let subsystems = tasks::subsystems()
+        .into_iter()
+        .map(|(name, state)| {
+            let subsystem = Subsystem {
+                healthy: state.healthy,
+                restarts: state.restarts,
+                last_error: state.last_error,
+            };
+            (name.to_string(), subsystem)
+        })
+        .collect();
+    let body = Health {
+        installing: status.installing,
+        running: status.running,
+        pid: status.pid,
+        restarts: status.restarts,
+        sleeping: idle::sleeping(),
+        subsystems,
+        capabilities: capabilities::all(),
+        uptime: uptime::report(),
+    };
+    // Stopped while idle, a connection starts Foundry again
+    if body.installing || body.running || body.sleeping {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+/// Readiness, Foundry is past the setup and license screens with a world active
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    operation_id = "readyz",
+    responses(
+        (status = 200, description = "A world is active", body = Readiness),
+        (status = 503, description = "Players cannot join yet", body = Readiness),
+    )
+)]
+pub async fn readyz(config: web::Data<HealthConfig>) -> impl Responder {
+    if idle::sleeping() {
+        return HttpResponse::ServiceUnavailable().json(Readiness::not_ready(
+            "Foundry is stopped while idle, the next connection starts it",
+        ));
+    }
+    if !status::current().running {
+        return HttpResponse::ServiceUnavailable()
+            .json(Readiness::not_ready("Foundry is not running"));
+    }
+    match status::fetch_server_status(config.foundry_port).await {
+        Ok(server) if server.active => HttpResponse::Ok().json(Readiness {
+            ready: true,
+            world: server.world,
+            players: Some(server.users),
+            reason: None,
+        }),
+        Ok(_) => HttpResponse::ServiceUnavailable().json(Readiness::not_ready(
+            "No world is active, Foundry is on the setup screen",
+        )),
+        Err(e) => HttpResponse::ServiceUnavailable().json(Readiness::not_ready(format!(
+            "Foundry does not answer: {}",
+            e
+        ))),
+    }
+}
@@ -0,0 +1,69 @@
+use tokio::time::{Duration, interval};
+use tracing::{debug, info, warn};
+
+use crate::capabilities;
+use crate::resilience::{self, Service};
+use crate::status;
+
+/// Pushes heartbeats to an external uptime monitor such as Uptime Kuma or healthchecks.io
+#[derive(Clone)]
+pub struct Heartbeat {
+    /// Pinged while Foundry is running and answering
+    pub success_url: String,
+    /// Pinged instead while Foundry is down, silence is reported when unset
+    pub failure_url: Option<String>,
+    pub interval: Duration,
+    pub foundry_port: u16,
+}
+
+impl Heartbeat {
+    pub async fn run(self) {
+        info!(
+            "Pushing heartbeats every {:?} to the configured monitor",
+            self.interval
+        );
+        let client = reqwest::Client::new();
+        let mut ticker = interval(self.interval);
+        let mut seen_healthy = false;
+
+        loop {
+            ticker.tick().await;
+
+            // A running process alone is not enough, Foundry has to answer requests as well
+            let healthy = status::current().running
+                && status::fetch_server_status(self.foundry_port).await.is_ok();
+            seen_healthy |= healthy;
+            let url = match (healthy, &self.failure_url) {
+                (true, _) => &self.success_url,
+                // Still booting, a monitor never hearing from us alerts on its own
+                (false, Some(failure_url)) if seen_healthy => failure_url,
+                (false, _) => {
+                    debug!("Foundry is not healthy, skipping heartbeat");
+                    continue;
+                }
+            };
+
+            let sent = resilience::call(Service::Heartbeat, || async {
+                Ok::<_, anyhow::Error>(client.get(url).send().await?)
+            })
+            .await;
+            match sent {
+                Ok(resp) if resp.status().is_success() => {
+                    debug!("Heartbeat sent (healthy: {})", healthy);
+                    capabilities::active("heartbeat");
+                }
+                Ok(resp) => {
+                    warn!("Heartbeat monitor answered {}", resp.status());
+                    capabilities::failed(
+                        "heartbeat",
+                        format!("The monitor answered {}", resp.status()),
+                    );
+                }
+                Err(e) => {
+                    warn!("Failed to send heartbeat: {}", e);
+                    capabilities::failed("heartbeat", &e);
+                }
+            }
+        }
+    }
+}
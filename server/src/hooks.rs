@@ -0,0 +1,143 @@
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use tokio::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::command::{CommandError, CommandResult, CommandRunner};
+use crate::eventlog;
+use crate::utils::{installed_foundry_version, paths};
+
+/// A hook still running after this is killed
+const HOOK_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Moments of the lifecycle scripts in `DATA_DIR/hooks/<event>.d` run at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    /// Before Foundry is first launched, a failing hook keeps it from starting
+    PreStart,
+    /// After Foundry was first launched
+    PostStart,
+    /// Before a snapshot is written
+    PreBackup,
+    /// After another Foundry release was switched to
+    PostUpdate,
+}
+
+impl HookEvent {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PreStart => "pre-start",
+            Self::PostStart => "post-start",
+            Self::PreBackup => "pre-backup",
+            Self::PostUpdate => "post-update",
+        }
+    }
+
+    fn dir(self) -> PathBuf {
+        paths::HOOKS_DIR.join(format!("{}.d", self.name()))
+    }
+}
+
+/// Run the executable files in the directory of `event` in the order of their names, like
+/// `run-parts`. Each gets the event and the installed version in its environment and `payload`
+/// as JSON, in `FOUNDRY_HOOK_PAYLOAD` and on stdin. Their output goes to the log. The first
+/// failing hook stops the others and is the error.
+pub async fn run(event: HookEvent, payload: Value) -> Result<()> {
+    eventlog::record(event.name(), payload.clone());
+    for hook in hooks(event) {
+        let name = hook_name(&hook);
+        let ran = command(event, &hook, &payload).run().await;
+        finish(event, &name, ran)?;
+    }
+    Ok(())
+}
+
+/// [`run`] for the synchronous backup
+pub fn run_blocking(event: HookEvent, payload: Value) -> Result<()> {
+    eventlog::record(event.name(), payload.clone());
+    for hook in hooks(event) {
+        let name = hook_name(&hook);
+        let ran = command(event, &hook, &payload).run_blocking();
+        finish(event, &name, ran)?;
+    }
+    Ok(())
+}
+
+/// Executable files of the event, sorted. Hidden files and editor backups are skipped.
+fn hooks(event: HookEvent) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(event.dir()) else {
+        return Vec::new();
+    };
+    let mut hooks: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            !name.starts_with('.') && !name.ends_with('~')
+        })
+        .filter(|entry| {
+            let executable = entry.metadata().is_ok_and(|metadata| {
+                metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+            });
+            if !executable {
+                debug!(
+                    "Skipping {}, it is not an executable file",
+                    entry.path().display()
+                );
+            }
+            executable
+        })
+        .map(|entry| entry.path())
+        .collect();
+    hooks.sort();
+    hooks
+}
+
+fn command(event: HookEvent, hook: &Path, payload: &Value) -> CommandRunner {
+    info!("🪝 Running {} hook {}", event.name(), hook_name(hook));
+    let payload = payload.to_string();
+    CommandRunner::new(hook)
+        .env("FOUNDRY_HOOK_EVENT", event.name())
+        .env(
+            "FOUNDRY_VERSION",
+            installed_foundry_version().unwrap_or_default(),
+        )
+        .env("DATA_DIR", &*paths::DATA_DIR)
+        .env("APPLICATION_DIR", &*paths::APPLICATION_DIR)
+        .env("FOUNDRY_HOOK_PAYLOAD", &payload)
+        .current_dir(&*paths::DATA_DIR)
+        .stdin(payload)
+        .timeout(HOOK_TIMEOUT)
+}
+
+fn finish(event: HookEvent, name: &str, ran: Result<CommandResult, CommandError>) -> Result<()> {
+    match ran {
+        Ok(result) => {
+            log_output(event, name, &result);
+            Ok(())
+        }
+        Err(e) => {
+            if let CommandError::Failed { result, .. } = &e {
+                log_output(event, name, result);
+            }
+            Err(anyhow!("The {} hook {} failed: {}", event.name(), name, e))
+        }
+    }
+}
+
+fn log_output(event: HookEvent, name: &str, result: &CommandResult) {
+    for line in result.stdout.lines().filter(|l| !l.trim().is_empty()) {
+        info!(hook = name, event = event.name(), "{}", line);
+    }
+    for line in result.stderr.lines().filter(|l| !l.trim().is_empty()) {
+        warn!(hook = name, event = event.name(), "{}", line);
+    }
+}
+
+fn hook_name(hook: &Path) -> String {
+    hook.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
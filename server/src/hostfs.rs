@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::Path;
+
+/// File systems Docker Desktop and WSL2 use to share Windows drives with containers
+const WINDOWS_FS_TYPES: &[&str] = &["drvfs", "fakeowner", "grpcfuse", "fuse.grpcfuse"];
+
+/// Names Windows refuses for files and directories, with or without an extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Whether `path` lives on a bind mount of a Windows drive, read from `/proc/self/mountinfo`
+pub fn is_windows_mount(path: &Path) -> bool {
+    let Ok(mountinfo) = fs::read_to_string("/proc/self/mountinfo") else {
+        return false;
+    };
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    // The mount with the longest mount point containing `path` is the one it lives on
+    mountinfo
+        .lines()
+        .filter_map(|line| {
+            let (mount, filesystem) = line.split_once(" - ")?;
+            let mount_point = mount.split_whitespace().nth(4)?;
+            let mut fields = filesystem.split_whitespace();
+            let fs_type = fields.next()?;
+            let options = fields.nth(1).unwrap_or_default();
+            Some((mount_point.to_string(), fs_type, options))
+        })
+        .filter(|(mount_point, _, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _, _)| mount_point.len())
+        .is_some_and(|(_, fs_type, options)| {
+            WINDOWS_FS_TYPES.contains(&fs_type)
+                || (fs_type == "9p" && options.contains("aname=drvfs"))
+        })
+}
+
+/// Whether names in `dir` are case-insensitive, as on NTFS
+pub fn is_case_insensitive(dir: &Path) -> bool {
+    let probe = dir.join(format!(".CaseProbe-{}", std::process::id()));
+    if fs::write(&probe, b"").is_err() {
+        return false;
+    }
+    let insensitive = dir
+        .join(format!(".caseprobe-{}", std::process::id()))
+        .exists();
+    let _ = fs::remove_file(&probe);
+    insensitive
+}
+
+/// Why Windows cannot store a file called `name`, `None` when it can
+pub fn windows_name_problem(name: &str) -> Option<&'static str> {
+    let stem = name.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        return Some("reserved device name");
+    }
+    if name.ends_with('.') || name.ends_with(' ') {
+        return Some("trailing dot or space");
+    }
+    if name
+        .chars()
+        .any(|c| "<>:\"\\|?*".contains(c) || c.is_control())
+    {
+        return Some("character not allowed on Windows");
+    }
+    None
+}
+
+/// Drop a UTF-8 byte order mark and CRLF line endings that Windows editors add
+pub fn normalize_text(contents: &str) -> String {
+    contents
+        .strip_prefix('\u{feff}')
+        .unwrap_or(contents)
+        .replace("\r\n", "\n")
+}
@@ -0,0 +1,393 @@
+#[cfg(feature = "full")]
+use actix_web::http::StatusCode;
+#[cfg(feature = "full")]
+use actix_web::{HttpRequest, Responder, web};
+use anyhow::{Context, Result, anyhow};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use tokio::sync::broadcast;
+use tokio::time::Duration;
+use tracing::info;
+use utoipa::ToSchema;
+use zip::CompressionMethod;
+use zip::write::{SimpleFileOptions, ZipWriter};
+
+#[cfg(feature = "full")]
+use crate::api::{self, ApiConfig, Message};
+use crate::backup::{self, INCLUDED};
+use crate::config::AppConfig;
+use crate::configfile;
+use crate::extractor::ExtractorService;
+use crate::fingerprint::VOLATILE_ENV_VARS;
+use crate::objectstore;
+use crate::packages::installed_packages;
+use crate::permissions::{self, FileKind};
+#[cfg(feature = "full")]
+use crate::replicate::{self, Replication};
+use crate::smoke::SmokeTest;
+use crate::state::WrapperState;
+use crate::tempfiles;
+use crate::utils::{compare_versions, hostname, installed_foundry_version, paths};
+use crate::worlds::WorldService;
+
+/// Name of the manifest at the root of a migration archive, and below `.wrapper` once it
+/// arrived on the new host
+const MANIFEST: &str = "migration.json";
+/// Directory below `BACKUP_DIR` the archives of `migrate-host pack` are written to
+const ARCHIVE_DIR: &str = "migrations";
+/// Directory below `DATA_DIR` an archive is extracted to before it replaces the data
+pub const STAGING_DIR: &str = ".migrate-staging";
+/// Set on every host without saying anything about the setup
+const HOST_ENV_VARS: &[&str] = &["HOME", "PATH", "USER", "LANG", "LC_ALL"];
+
+/// What the old host looked like, to compare the new one with
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HostManifest {
+    #[schema(format = DateTime)]
+    pub created: String,
+    /// Hostname of the old container
+    pub source: String,
+    pub foundry_version: Option<String>,
+    /// Core and package versions keyed like `installed_packages`
+    pub packages: BTreeMap<String, String>,
+    pub worlds: Vec<String>,
+    /// SHA-256 of every environment variable, differences show up without revealing secrets
+    pub environment: BTreeMap<String, String>,
+    /// Wrapper state without the snapshot catalog, which belongs to the old `BACKUP_DIR`
+    #[schema(value_type = Object)]
+    pub state: WrapperState,
+}
+
+impl HostManifest {
+    pub fn capture() -> Result<Self> {
+        let mut state = WrapperState::load()?;
+        state.backups.clear();
+        Ok(Self {
+            created: Utc::now().to_rfc3339(),
+            source: hostname(),
+            foundry_version: installed_foundry_version(),
+            packages: installed_packages(),
+            worlds: WorldService::ids().unwrap_or_default(),
+            environment: environment(),
+            state,
+        })
+    }
+
+    /// Keep the manifest for `verify` and take over the wrapper state, keeping the snapshot
+    /// catalog of this host
+    fn adopt(&self) -> Result<()> {
+        let mut adopted = self.state.clone();
+        adopted.backups = WrapperState::load()?.backups;
+        adopted.save()?;
+
+        permissions::create_dir_all(&paths::WRAPPER_STATE_DIR)?;
+        let path = paths::WRAPPER_STATE_DIR.join(MANIFEST);
+        permissions::write(&path, serde_json::to_vec_pretty(self)?, FileKind::Regular)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        info!(
+            "Adopted the wrapper state of {} from {}",
+            self.source, self.created
+        );
+        Ok(())
+    }
+
+    fn load() -> Result<Self> {
+        let path = paths::WRAPPER_STATE_DIR.join(MANIFEST);
+        let contents = fs::read(&path).with_context(|| {
+            format!(
+                "{} does not exist, unpack an archive or push from the old host first",
+                path.display()
+            )
+        })?;
+        serde_json::from_slice(&contents).with_context(|| format!("Invalid {}", path.display()))
+    }
+}
+
+fn environment() -> BTreeMap<String, String> {
+    configfile::vars()
+        .into_iter()
+        .filter(|(name, _)| {
+            !VOLATILE_ENV_VARS.contains(&name.as_str()) && !HOST_ENV_VARS.contains(&name.as_str())
+        })
+        .map(|(name, value)| (name, objectstore::hex(&Sha256::digest(value))))
+        .collect()
+}
+
+/// `migrate-host pack`: write `Config`, `Data` and the manifest to one archive. Blocks.
+pub fn pack(out: Option<PathBuf>, excludes: &[String]) -> Result<(PathBuf, usize, u64)> {
+    let manifest = HostManifest::capture()?;
+    let path = out.unwrap_or_else(|| {
+        PathBuf::from(&*paths::BACKUP_DIR)
+            .join(ARCHIVE_DIR)
+            .join(format!(
+                "foundry-host-{}.zip",
+                Utc::now().format("%Y%m%d-%H%M%S")
+            ))
+    });
+    if let Some(parent) = path.parent() {
+        permissions::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let partial = path.with_extension("partial");
+    // The license and the account of options.json are inside
+    let file = permissions::create(&partial, FileKind::Secret)
+        .with_context(|| format!("Failed to create {}", partial.display()))?;
+    let mut zip = ZipWriter::new(io::BufWriter::new(file));
+    let (files, bytes) = backup::add_files(&mut zip, &backup::snapshot_entries(excludes)?)?;
+    zip.start_file(
+        MANIFEST,
+        SimpleFileOptions::default().compression_method(CompressionMethod::Deflated),
+    )?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    zip.finish()?;
+    fs::rename(&partial, &path)
+        .with_context(|| format!("Failed to move {} into place", path.display()))?;
+    Ok((path, files, bytes))
+}
+
+/// `migrate-host unpack`: replace `Config` and `Data` with those of an archive of `pack`
+pub async fn unpack(archive: &Path, force: bool) -> Result<HostManifest> {
+    let existing = WorldService::ids().unwrap_or_default();
+    if !existing.is_empty() && !force {
+        return Err(anyhow!(
+            "DATA_DIR holds {} worlds already, pass --force to replace them",
+            existing.len()
+        ));
+    }
+    let data_dir = PathBuf::from(&*paths::DATA_DIR);
+    let staging = data_dir.join(STAGING_DIR);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    tempfiles::track(&staging, "migration staging");
+    // Nobody follows the progress on the command line
+    let (event_tx, _) = broadcast::channel(16);
+    let unpacked = async {
+        ExtractorService::extract_zip(
+            archive.to_string_lossy().to_string(),
+            staging.to_string_lossy().to_string(),
+            event_tx,
+        )
+        .await
+        .context("The archive does not extract")?;
+        let manifest: HostManifest = serde_json::from_slice(
+            &fs::read(staging.join(MANIFEST))
+                .context("The archive has no migration.json, was it written by `pack`?")?,
+        )?;
+        for dir in INCLUDED {
+            let (current, unpacked) = (data_dir.join(dir), staging.join(dir));
+            if !unpacked.exists() {
+                continue;
+            }
+            if current.exists() {
+                fs::remove_dir_all(&current)
+                    .with_context(|| format!("Failed to remove {}", current.display()))?;
+            }
+            fs::rename(&unpacked, &current)
+                .with_context(|| format!("Failed to move {} into place", current.display()))?;
+        }
+        manifest.adopt()?;
+        Ok(manifest)
+    }
+    .await;
+    let _ = fs::remove_dir_all(&staging);
+    unpacked
+}
+
+/// `migrate-host push`: replicate the data directory to the new instance, then hand it the
+/// manifest
+#[cfg(feature = "full")]
+pub struct HostPush {
+    pub replication: Replication,
+}
+
+#[cfg(feature = "full")]
+impl HostPush {
+    pub async fn run(&self) -> Result<HostManifest> {
+        let summary = self.replication.run().await?;
+        info!(
+            "Sent {} files ({} MB), {} were there already",
+            summary.uploaded,
+            summary.bytes / (1024 * 1024),
+            summary.unchanged
+        );
+        let manifest = HostManifest::capture()?;
+        let url = format!(
+            "{}/api/migrate/manifest",
+            self.replication.target.trim_end_matches('/')
+        );
+        let resp = reqwest::Client::builder()
+            .timeout(Duration::from_secs(60))
+            .build()?
+            .post(url)
+            .bearer_auth(&self.replication.token)
+            .json(&manifest)
+            .send()
+            .await?;
+        replicate::check(resp)
+            .await
+            .context("The new instance did not take the manifest")?;
+        Ok(manifest)
+    }
+}
+
+/// Adopt the wrapper state and the description of the old host, the last step of
+/// `migrate-host push`
+#[cfg(feature = "full")]
+#[utoipa::path(
+    post,
+    path = "/api/migrate/manifest",
+    operation_id = "migrateManifest",
+    security(("token" = [])),
+    request_body = HostManifest,
+    responses(
+        (status = 200, description = "Adopted", body = Message),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 409, description = "A world is active", body = Message),
+        (status = 500, description = "The manifest could not be adopted", body = Message),
+    )
+)]
+pub async fn receive_manifest(
+    req: HttpRequest,
+    body: web::Json<HostManifest>,
+    config: web::Data<ApiConfig>,
+) -> impl Responder {
+    if let Some(rejected) = api::reject(&req, &config) {
+        return rejected;
+    }
+    if replicate::world_active(&config).await {
+        return replicate::message(
+            StatusCode::CONFLICT,
+            "A world is active, return to the setup screen first",
+        );
+    }
+    let manifest = body.into_inner();
+    match web::block(move || manifest.adopt()).await {
+        Ok(Ok(())) => replicate::message(StatusCode::OK, "Adopted"),
+        Ok(Err(e)) => replicate::message(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)),
+        Err(e) => replicate::message(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// What keeps the new host from taking over
+    pub problems: Vec<String>,
+    /// Differences worth a look that do not break the worlds
+    pub notes: Vec<String>,
+    pub worlds: usize,
+}
+
+/// `migrate-host verify`: compare the new host with the manifest of the old one and boot each
+/// world headlessly
+pub struct HostVerify {
+    pub foundry_port: u16,
+    pub port: u16,
+    pub timeout: Duration,
+    pub script_path: String,
+}
+
+impl HostVerify {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            foundry_port: config.server_port,
+            port: config.restore_test_port,
+            timeout: Duration::from_secs(config.smoke_test_timeout),
+            script_path: paths::resolve_foundry_script_path()
+                .to_string_lossy()
+                .to_string(),
+        }
+    }
+
+    pub async fn run(&self) -> Result<VerifyReport> {
+        let manifest = HostManifest::load()?;
+        // A second Foundry cannot open the data directory next to a running one
+        if TcpStream::connect(("127.0.0.1", self.foundry_port)).is_ok() {
+            return Err(anyhow!(
+                "Foundry is running on port {}, stop it and verify from a one-off container",
+                self.foundry_port
+            ));
+        }
+        let mut report = VerifyReport::default();
+
+        let installed = installed_foundry_version();
+        match (&manifest.foundry_version, &installed) {
+            (Some(source), Some(installed)) => match compare_versions(installed, source) {
+                Ordering::Less => report.problems.push(format!(
+                    "Foundry {} is installed, the worlds come from Foundry {}",
+                    installed, source
+                )),
+                Ordering::Greater => report.notes.push(format!(
+                    "Foundry {} is installed, newer than {} of the old host, the worlds are migrated when they are launched",
+                    installed, source
+                )),
+                Ordering::Equal => {}
+            },
+            (Some(source), None) => report
+                .problems
+                .push(format!("Foundry is not installed, the old host ran {}", source)),
+            _ => {}
+        }
+
+        let packages = installed_packages();
+        for (id, version) in manifest
+            .packages
+            .iter()
+            .filter(|(id, _)| id.as_str() != "core")
+        {
+            match packages.get(id) {
+                None => report
+                    .problems
+                    .push(format!("{} {} is missing", id, version)),
+                Some(here) if here != version => report
+                    .notes
+                    .push(format!("{} is {} here and was {}", id, here, version)),
+                _ => {}
+            }
+        }
+
+        let environment = environment();
+        for (name, digest) in &manifest.environment {
+            match environment.get(name) {
+                None => report
+                    .notes
+                    .push(format!("{} is not set on this host", name)),
+                Some(here) if here != digest => report
+                    .notes
+                    .push(format!("{} has another value here", name)),
+                _ => {}
+            }
+        }
+
+        for world in &manifest.worlds {
+            if !paths::WORLDS_DIR.join(world).join("world.json").is_file() {
+                report.problems.push(format!("World {} is missing", world));
+                continue;
+            }
+            let outcome = SmokeTest {
+                world: world.clone(),
+                data_dir: paths::DATA_DIR.clone(),
+                port: self.port,
+                timeout: self.timeout,
+                background: false,
+            }
+            .run(&self.script_path)
+            .await?;
+            report.worlds += 1;
+            if !outcome.passed {
+                report
+                    .problems
+                    .push(format!("World {} does not load: {}", world, outcome.reason));
+            }
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,128 @@
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use tracing::{info, warn};
+use unic_langid::LanguageIdentifier;
+
+use crate::configfile;
+
+/// Message catalogs compiled into the binary, the first one is the fallback
+const CATALOGS: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en/wrapper.ftl")),
+    ("de", include_str!("../locales/de/wrapper.ftl")),
+];
+
+struct Catalog {
+    language: &'static str,
+    bundle: FluentBundle<FluentResource>,
+    fallback: FluentBundle<FluentResource>,
+}
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// Select the language of wrapper messages from `WRAPPER_LANGUAGE`, e.g. `de` or `de_DE.UTF-8`
+pub fn init() {
+    let requested = configfile::var("WRAPPER_LANGUAGE").unwrap_or_default();
+    let code = requested
+        .split(['_', '-', '.'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let language = match CATALOGS.iter().find(|(lang, _)| *lang == code) {
+        Some((lang, _)) => *lang,
+        None => {
+            if !requested.is_empty() {
+                warn!(
+                    "No messages for WRAPPER_LANGUAGE={}, using English",
+                    requested
+                );
+            }
+            CATALOGS[0].0
+        }
+    };
+    if CATALOG.set(Catalog::load(language)).is_ok() && language != CATALOGS[0].0 {
+        info!("Wrapper messages in language {}", language);
+    }
+}
+
+/// Language of the wrapper messages, for `lang` attributes
+pub fn language() -> &'static str {
+    catalog().language
+}
+
+/// The message `id` in the selected language
+pub fn tr(id: &str) -> String {
+    tr_args(id, &[])
+}
+
+/// The message `id` with its `{ $name }` variables filled in from `args`
+pub fn tr_args(id: &str, args: &[(&str, &str)]) -> String {
+    let catalog = catalog();
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, *value);
+    }
+    format(&catalog.bundle, id, &fluent_args)
+        .or_else(|| format(&catalog.fallback, id, &fluent_args))
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// All messages whose id starts with `prefix`, variables are left as `{$name}` placeholders
+pub fn messages(prefix: &str) -> BTreeMap<String, String> {
+    // Every message starts a line with `id =`, continuation lines are indented
+    CATALOGS[0]
+        .1
+        .lines()
+        .filter_map(|line| line.split_once(" = ").map(|(id, _)| id))
+        .filter(|id| id.starts_with(prefix) && !id.starts_with(' '))
+        .map(|id| (id.to_string(), tr(id)))
+        .collect()
+}
+
+fn catalog() -> &'static Catalog {
+    // Commands that never called `init` get English
+    CATALOG.get_or_init(|| Catalog::load(CATALOGS[0].0))
+}
+
+/// Format a message, unset variables render as `{$name}`
+fn format(bundle: &FluentBundle<FluentResource>, id: &str, args: &FluentArgs) -> Option<String> {
+    let pattern = bundle.get_message(id)?.value()?;
+    let mut errors = Vec::new();
+    Some(
+        bundle
+            .format_pattern(pattern, Some(args), &mut errors)
+            .to_string(),
+    )
+}
+
+impl Catalog {
+    fn load(language: &'static str) -> Self {
+        let source = CATALOGS
+            .iter()
+            .find(|(lang, _)| *lang == language)
+            .map(|(_, source)| *source)
+            .unwrap_or(CATALOGS[0].1);
+        Self {
+            language,
+            bundle: bundle(language, source),
+            fallback: bundle(CATALOGS[0].0, CATALOGS[0].1),
+        }
+    }
+}
+
+fn bundle(language: &str, source: &str) -> FluentBundle<FluentResource> {
+    let langid: LanguageIdentifier = language.parse().expect("Invalid catalog language");
+    let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+    // Unicode isolation marks end up as garbage in terminals and logs
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(parse(source))
+        .expect("Duplicate message in catalog");
+    bundle
+}
+
+fn parse(source: &str) -> FluentResource {
+    FluentResource::try_new(source.to_string()).expect("Invalid message catalog")
+}
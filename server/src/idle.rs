@@ -0,0 +1,161 @@
+use nix::sys::signal::{Signal, kill};
+use nix::unistd::Pid;
+use std::collections::HashSet;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{Duration, Instant, sleep, timeout};
+use tracing::{debug, info, warn};
+
+use crate::launch;
+use crate::notify::{self, Notification, Severity};
+use crate::status;
+
+/// Set while Foundry is stopped until the next connection
+static SLEEPING: AtomicBool = AtomicBool::new(false);
+
+/// Whether Foundry was stopped for being idle and waits for a connection
+pub fn sleeping() -> bool {
+    SLEEPING.load(Ordering::Relaxed)
+}
+
+/// Answer of the wake listener, the browser reloads until Foundry serves the page
+const WAKE_PAGE: &str = "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 10\r\nContent-Type: text/html; charset=utf-8\r\nConnection: close\r\n\r\n<!DOCTYPE html><html><head><meta http-equiv=\"refresh\" content=\"10\"><title>Starting</title></head><body><p>The server is starting, this page reloads in a moment.</p></body></html>";
+
+/// Stops Foundry once nothing was connected to its port for `idle`, for hosting billed by the
+/// hour. With `exit` the container stops, otherwise the wrapper listens on the port itself and
+/// starts Foundry again for the next connection.
+#[derive(Clone)]
+pub struct IdleShutdown {
+    pub idle: Duration,
+    pub exit: bool,
+    pub foundry_port: u16,
+    pub interval: Duration,
+}
+
+impl IdleShutdown {
+    pub async fn run(self) {
+        let mut empty_since: Option<Instant> = None;
+        let mut previous = HashSet::new();
+        loop {
+            sleep(self.interval).await;
+            if !status::current().running {
+                empty_since = None;
+                continue;
+            }
+            // Only connections open for a whole interval count, the wrapper's own status
+            // requests are over within a moment
+            let open = established_connections(self.foundry_port);
+            let lasting = open.intersection(&previous).count();
+            previous = open;
+            if lasting > 0 {
+                debug!("{} connections to Foundry are open", lasting);
+                empty_since = None;
+                continue;
+            }
+            let empty_for = empty_since.get_or_insert_with(Instant::now).elapsed();
+            if empty_for < self.idle {
+                continue;
+            }
+            empty_since = None;
+
+            let minutes = self.idle.as_secs() / 60;
+            if self.exit {
+                info!(
+                    "💤 Nothing was connected to Foundry for {} minutes, stopping the container",
+                    minutes
+                );
+                notify::send(Notification {
+                    severity: Severity::Info,
+                    title: "Container stopped while idle".to_string(),
+                    message: format!("Nobody was connected for {} minutes.", minutes),
+                });
+                // Stops like `docker stop`, flushing the data and recording a clean shutdown
+                if let Err(e) = kill(Pid::this(), Signal::SIGTERM) {
+                    warn!("⚠️ Failed to stop the container: {}", e);
+                }
+                return;
+            }
+            info!(
+                "💤 Nothing was connected to Foundry for {} minutes, stopping it until the next connection",
+                minutes
+            );
+            self.sleep_until_wake().await;
+        }
+    }
+
+    /// Stop Foundry and hold it until a connection reaches its port
+    async fn sleep_until_wake(&self) {
+        let hold = launch::pause().await;
+        let listener = match TcpListener::bind(("0.0.0.0", self.foundry_port)).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                warn!(
+                    "⚠️ Failed to listen on port {}, starting Foundry again: {}",
+                    self.foundry_port, e
+                );
+                return;
+            }
+        };
+        SLEEPING.store(true, Ordering::Relaxed);
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("Wake listener failed to accept a connection: {}", e);
+                    continue;
+                }
+            };
+            if answer(stream).await {
+                info!("⏰ {} connected, starting Foundry", peer.ip());
+                break;
+            }
+        }
+        // Foundry needs the port
+        drop(listener);
+        SLEEPING.store(false, Ordering::Relaxed);
+        drop(hold);
+    }
+}
+
+/// Answer a connection to the sleeping Foundry, answering whether it wakes Foundry. Status
+/// requests of the wrapper's own monitoring do not.
+async fn answer(mut stream: TcpStream) -> bool {
+    let mut head = [0u8; 1024];
+    let read = timeout(Duration::from_secs(2), stream.read(&mut head))
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .unwrap_or(0);
+    let request = String::from_utf8_lossy(&head[..read]);
+    let wakes = !request.starts_with("GET /api/status");
+    let _ = stream.write_all(WAKE_PAGE.as_bytes()).await;
+    let _ = stream.shutdown().await;
+    wakes
+}
+
+/// Remote addresses of the established TCP connections to `port` from /proc/net/tcp and
+/// tcp6, players keep one open for the game socket
+fn established_connections(port: u16) -> HashSet<String> {
+    ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .flat_map(|table| {
+            table
+                .lines()
+                .skip(1)
+                .filter_map(|line| {
+                    let fields: Vec<&str> = line.split_whitespace().collect();
+                    // State 01 is ESTABLISHED, the local address ends in the hex port
+                    if fields.get(3) != Some(&"01") {
+                        return None;
+                    }
+                    let local = fields.get(1)?.rsplit(':').next()?;
+                    (u16::from_str_radix(local, 16).ok()? == port)
+                        .then(|| fields.get(2).map(|remote| remote.to_string()))?
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
@@ -0,0 +1,96 @@
+use anyhow::{Result, anyhow};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tokio::time::timeout;
+use tracing::{info, warn};
+
+/// How often the wrapper reports what it still waits for
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Critical sections running, by id, with what they do and since when
+static ACTIVE: Mutex<BTreeMap<u64, (String, Instant)>> = Mutex::new(BTreeMap::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static RELEASED: Notify = Notify::const_new();
+/// Set once the container stops, no critical section starts afterwards
+static STOPPING: AtomicBool = AtomicBool::new(false);
+
+/// Delays the stop of the container while it is held, see [`wait`]
+pub struct Inhibitor {
+    id: u64,
+}
+
+impl Drop for Inhibitor {
+    fn drop(&mut self) {
+        ACTIVE
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.id);
+        RELEASED.notify_waiters();
+    }
+}
+
+/// Hold off the stop of the container until the guard is dropped, `what` names the operation
+/// in the log. Fails once the container stops, an operation refused then never started.
+pub fn acquire(what: impl Into<String>) -> Result<Inhibitor> {
+    let what = what.into();
+    let mut active = ACTIVE.lock().unwrap_or_else(PoisonError::into_inner);
+    // Checked under the lock, `wait` sees every section that got past it
+    if STOPPING.load(Ordering::SeqCst) {
+        return Err(anyhow!(
+            "The container is stopping, not starting the {}",
+            what
+        ));
+    }
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    active.insert(id, (what, Instant::now()));
+    Ok(Inhibitor { id })
+}
+
+/// Wait up to `bound` for the running critical sections when the container stops.
+///
+/// Whatever still runs after `bound` is abandoned. Partial snapshots and downloads are
+/// removed on the next start, see the README.
+pub async fn wait(bound: Duration) {
+    STOPPING.store(true, Ordering::SeqCst);
+    let deadline = Instant::now() + bound;
+    let mut reported = false;
+    loop {
+        // Registered before the check, a release right after it still wakes the wait
+        let released = RELEASED.notified();
+        let running = running();
+        if running.is_empty() {
+            if reported {
+                info!("Nothing critical runs anymore, stopping");
+            }
+            return;
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            warn!(
+                "⚠️ Stopping although {} still runs after SHUTDOWN_INHIBIT_TIMEOUT, it is left incomplete",
+                running.join(", ")
+            );
+            return;
+        }
+        info!(
+            "⏳ Waiting up to {}s for {} to finish before stopping",
+            left.as_secs(),
+            running.join(", ")
+        );
+        reported = true;
+        let _ = timeout(left.min(REPORT_INTERVAL), released).await;
+    }
+}
+
+/// What the running sections do and for how long, oldest first
+fn running() -> Vec<String> {
+    ACTIVE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .values()
+        .map(|(what, since)| format!("the {} (running for {}s)", what, since.elapsed().as_secs()))
+        .collect()
+}
@@ -1,39 +1,73 @@
 use anyhow::{Context, Result, anyhow};
-use std::env;
+use nix::sys::statvfs::statvfs;
+use nix::unistd::{geteuid, getuid};
 use std::fs;
-use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
-use std::process::Command;
+use std::time::Duration;
 use tracing::{debug, error, info, warn};
 
+use crate::assetgc::{AssetCollector, GcMode};
+use crate::command::CommandRunner;
 use crate::config::AppConfig;
-use crate::utils::{paths, run_command};
+use crate::configfile;
+use crate::doctor;
+use crate::fingerprint::BootFingerprint;
+use crate::integrity::IntegrityService;
+use crate::join;
+use crate::runtime::RuntimeInfo;
+use crate::scan::ScanService;
+use crate::shutdown;
+use crate::utils::{find_executable, paths};
 
 pub fn initialize(app_config: &AppConfig) -> Result<()> {
     print_banner()?;
+    join::print_join_url(&app_config.join_url, app_config.join_qr);
+    verify_previous_shutdown(app_config);
+    // Before the fingerprint, a changed variable makes it a cold start
+    app_config.foundry_options.render(app_config.config_apply)?;
+
+    let fingerprint = BootFingerprint::compute();
+    if app_config.warm_start && fingerprint.matches_last_boot() {
+        info!("⚡ Nothing changed since the last successful boot, skipping validation");
+        prepare_directories()?;
+        info!("──────────────────────────────────────────────────────────");
+        return Ok(());
+    }
+
     print_system_info()?;
+    check_runtime(app_config);
     check_required_env()?;
     validate_env()?;
+    for lint in doctor::foundry_config_lints() {
+        warn!("⚠️ {}", lint.message);
+    }
+    prepare_directories()?;
     ensure_directories()?;
+    if app_config.report_data_usage {
+        report_data_usage(app_config);
+    }
+    if let Some(mode) = app_config.asset_gc {
+        collect_unused_assets(app_config, mode);
+    }
 
     info!("Configuration Summary:");
     info!("  - Application directory: {}", app_config.target_dir);
     info!("  - Data directory: {}", *paths::DATA_DIR);
     info!(
         "  - Host: {}",
-        env::var("APPLICATION_HOST").unwrap_or_else(|_| "foundry.vtt".to_string())
+        configfile::var("APPLICATION_HOST").unwrap_or_else(|_| "foundry.vtt".to_string())
     );
     info!(
         "  - SSL Proxy: {}",
-        env::var("SSL_PROXY").unwrap_or_else(|_| "false".to_string())
+        configfile::var("SSL_PROXY").unwrap_or_else(|_| "false".to_string())
     );
     info!(
         "  - Port: {}",
-        env::var("APPLICATION_PORT").unwrap_or_else(|_| "4444".to_string())
+        configfile::var("APPLICATION_PORT").unwrap_or_else(|_| "4444".to_string())
     );
     info!(
         "  - Empty App Dir On Start: {}",
-        env::var("EMPTY_APP_DIR_ON_START").unwrap_or_else(|_| "false".to_string())
+        configfile::var("EMPTY_APP_DIR_ON_START").unwrap_or_else(|_| "false".to_string())
     );
 
     info!("──────────────────────────────────────────────────────────");
@@ -50,36 +84,68 @@ fn print_banner() -> Result<()> {
     Ok(())
 }
 
+/// Check the world databases when the previous run did not record a clean shutdown
+fn verify_previous_shutdown(app_config: &AppConfig) {
+    if shutdown::consume_clean_marker() {
+        debug!("Previous run shut down cleanly");
+        return;
+    }
+
+    warn!("No clean shutdown recorded for the previous run, checking world databases");
+    match IntegrityService::check_worlds(&app_config.scan_excludes) {
+        Ok(report) if !report.problems.is_empty() => warn!(
+            "⚠️ Found {} integrity problems, Foundry may fail to load affected worlds",
+            report.problems.len()
+        ),
+        Ok(_) => {}
+        Err(e) => warn!("Integrity check failed: {}", e),
+    }
+}
+
 fn print_system_info() -> Result<()> {
-    // Collect system information in a more compact format
-    let hostname = run_command("hostname", &[])?.trim().to_string();
-    let kernel = run_command("uname", &["-r"])?.trim().to_string();
-    let os = run_command(
-        "sh",
-        &[
-            "-c",
-            "grep PRETTY_NAME /etc/os-release | cut -d= -f2 | tr -d '\"'",
-        ],
-    )?
-    .trim()
-    .to_string();
-    let cpu = run_command(
-        "sh",
-        &[
-            "-c",
-            "lscpu | grep 'Model name' | cut -d: -f2 | sed 's/^ *//'",
-        ],
-    )?
-    .trim()
-    .to_string();
-    let memory = run_command("sh", &["-c", "free -h | awk '/^Mem:/ {print $2}'"])?
-        .trim()
-        .to_string();
-    let disk = run_command("sh", &["-c", "df -h / | awk 'NR==2 {print $4}'"])?
-        .trim()
-        .to_string();
-    let node_version = run_command("node", &["--version"])?.trim().to_string();
-    let npm_version = run_command("npm", &["--version"])?.trim().to_string();
+    // Read from /proc and /etc so this works in images without a shell or coreutils
+    let hostname = read_trimmed("/proc/sys/kernel/hostname")
+        .or_else(|| configfile::var("HOSTNAME").ok())
+        .unwrap_or_else(|| "unknown".to_string());
+    let kernel = read_trimmed("/proc/sys/kernel/osrelease").unwrap_or_default();
+    let os = fs::read_to_string("/etc/os-release")
+        .ok()
+        .and_then(|release| {
+            release
+                .lines()
+                .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+                .map(|name| name.trim_matches('"').to_string())
+        })
+        .unwrap_or_else(|| "Unknown".to_string());
+    let cpu = fs::read_to_string("/proc/cpuinfo")
+        .ok()
+        .and_then(|cpuinfo| {
+            cpuinfo
+                .lines()
+                .find(|line| line.starts_with("model name"))
+                .and_then(|line| line.split_once(':'))
+                .map(|(_, name)| name.trim().to_string())
+        })
+        .unwrap_or_default();
+    let memory = fs::read_to_string("/proc/meminfo")
+        .ok()
+        .and_then(|meminfo| {
+            meminfo
+                .lines()
+                .find_map(|line| line.strip_prefix("MemTotal:"))
+                .and_then(|kb| kb.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+        })
+        .map(|kb| human_size(kb * 1024))
+        .unwrap_or_else(|| "Unknown".to_string());
+    let disk = statvfs("/")
+        .map(|stat| human_size(stat.blocks_available() * stat.fragment_size()))
+        .unwrap_or_else(|_| "Unknown".to_string());
+    // Foundry cannot start without node, npm is optional
+    let node_version = tool_version("node").unwrap_or_else(|| {
+        error!("❌ node was not found in PATH, Foundry cannot be started in this image");
+        "missing".to_string()
+    });
+    let npm_version = tool_version("npm").unwrap_or_else(|| "not installed".to_string());
 
     info!("System Information:");
     info!("  - Hostname: {}", hostname);
@@ -90,7 +156,7 @@ fn print_system_info() -> Result<()> {
         memory,
         disk
     );
-    info!("  - Node: v{}, NPM: v{}", node_version, npm_version);
+    info!("  - Node: {}, NPM: {}", node_version, npm_version);
 
     debug!("Detailed System Information:");
     debug!("  - Hostname: {}", hostname);
@@ -105,13 +171,68 @@ fn print_system_info() -> Result<()> {
     Ok(())
 }
 
+fn read_trimmed(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+/// `--version` of `tool`, prefixed with `v` like node prints it
+pub fn tool_version(tool: &str) -> Option<String> {
+    let path = find_executable(tool)?;
+    let result = CommandRunner::new(path)
+        .arg("--version")
+        .timeout(Duration::from_secs(10))
+        .run_blocking()
+        .ok()?;
+    let version = result.stdout.trim().to_string();
+    Some(format!("v{}", version.trim_start_matches('v')))
+}
+
+fn human_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    for unit in ["B", "K", "M", "G", "T"] {
+        if size < 1024.0 {
+            return format!("{:.1}{}", size, unit);
+        }
+        size /= 1024.0;
+    }
+    format!("{:.1}P", size)
+}
+
+/// Warn about setups that behave differently under rootless runtimes like rootless Podman
+fn check_runtime(app_config: &AppConfig) {
+    let runtime = RuntimeInfo::detect();
+    info!("  - Container runtime: {}", runtime.describe());
+    if !runtime.rootless() {
+        return;
+    }
+
+    let uid = geteuid().as_raw();
+    if let Some(host_uid) = runtime.host_uid(uid) {
+        info!(
+            "Running as uid {} in a user namespace, files on volumes belong to host uid {}",
+            uid, host_uid
+        );
+    }
+    if !runtime.can_chown {
+        info!(
+            "Ownership of volume files cannot be changed here, fix it on the host with `podman unshare chown`"
+        );
+    }
+    if app_config.server_port < 1024 {
+        warn!(
+            "⚠️ Port {} is below 1024, rootless runtimes can only publish it with net.ipv4.ip_unprivileged_port_start lowered on the host",
+            app_config.server_port
+        );
+    }
+}
+
 fn check_required_env() -> Result<()> {
-    let required_vars = ["APPLICATION_DIR", "DATA_DIR", "APPLICATION_HOST"];
+    let required_vars = ["APPLICATION_DIR", "DATA_DIR"];
     let mut missing = false;
 
     info!("Checking environment variables");
     for var in required_vars {
-        match env::var(var) {
+        match configfile::var(var) {
             Ok(value) => debug!("{} = {}", var, value),
             Err(_) => {
                 error!("{} is required but not set!", var);
@@ -138,7 +259,7 @@ fn validate_env() -> Result<()> {
         return Err(anyhow!("APPLICATION_DIR and DATA_DIR cannot be the same"));
     }
 
-    let app_port = env::var("APPLICATION_PORT").unwrap_or_else(|_| "4444".to_string());
+    let app_port = configfile::var("APPLICATION_PORT").unwrap_or_else(|_| "4444".to_string());
     if app_port.parse::<u32>().is_err() {
         error!("APPLICATION_PORT must be a number: {}", app_port);
         return Err(anyhow!("Invalid APPLICATION_PORT"));
@@ -147,14 +268,13 @@ fn validate_env() -> Result<()> {
     Ok(())
 }
 
-fn ensure_directories() -> Result<()> {
-    info!("Validating directories");
-
+/// Empty and create the application and data directories, required on every boot
+fn prepare_directories() -> Result<()> {
     let app_dir = &*paths::APPLICATION_DIR;
     let data_dir = &*paths::DATA_DIR;
 
     // Check if we should empty the application directory
-    let empty_app_dir = env::var("EMPTY_APP_DIR_ON_START")
+    let empty_app_dir = configfile::var("EMPTY_APP_DIR_ON_START")
         .unwrap_or_else(|_| "false".to_string())
         .to_lowercase()
         == "true";
@@ -186,15 +306,34 @@ fn ensure_directories() -> Result<()> {
             info!("Creating directory: {} (missing)", dir);
             fs::create_dir_all(path).with_context(|| format!("Failed to create {}", dir))?;
         }
+    }
 
-        // Check if directory is writable
-        let metadata = fs::metadata(path)?;
-        let permissions = metadata.permissions();
-        let is_writable = permissions.mode() & 0o200 != 0;
+    Ok(())
+}
 
-        if !is_writable {
-            warn!("Directory not writable: {}. This might cause issues.", dir);
-        }
+fn ensure_directories() -> Result<()> {
+    info!("Validating directories");
+
+    let app_dir = &*paths::APPLICATION_DIR;
+    let data_dir = &*paths::DATA_DIR;
+
+    for dir in &[app_dir, data_dir] {
+        let path = Path::new(dir);
+
+        // Check if directory is writable, the mode bits say nothing about SELinux or AppArmor
+        let is_writable = match doctor::probe_write(path) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!(
+                    "Directory not writable: {} ({}). This might cause issues.",
+                    dir, e
+                );
+                for hint in doctor::explain_write_failure(path, &e) {
+                    warn!("  → {}", hint);
+                }
+                false
+            }
+        };
 
         // Print detailed directory info only at debug level
         debug!("Directory details for {}:", dir);
@@ -224,25 +363,86 @@ fn ensure_directories() -> Result<()> {
         }
     }
 
-    // Network configuration - simplified for INFO level
-    let user_info = run_command("id", &["-u"])?.trim().to_string();
-    info!("Running as UID: {}", user_info);
+    info!("Running as UID: {}", getuid());
 
-    // Network configuration at debug level
+    // Network configuration at debug level, from /sys and /proc instead of ip or netstat
     debug!("Network configuration:");
-    if Command::new("ip").arg("addr").arg("show").status().is_ok() {
-        let network_info = run_command("ip", &["addr", "show"])?;
-        // Just log that we have this info, full details in debug
-        debug!("{}", network_info);
+    if let Ok(interfaces) = fs::read_dir("/sys/class/net") {
+        let mut names: Vec<String> = interfaces
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        debug!("  - Interfaces: {}", names.join(", "));
     }
+    debug!("  - Listening TCP ports: {:?}", listening_ports());
 
-    if Command::new("netstat").arg("-tulpn").status().is_ok() {
-        let ports_info = run_command("netstat", &["-tulpn"])?;
-        debug!("{}", ports_info);
-    } else if Command::new("ss").arg("-tulpn").status().is_ok() {
-        let ports_info = run_command("ss", &["-tulpn"])?;
-        debug!("{}", ports_info);
+    Ok(())
+}
+
+/// Local ports in LISTEN state from /proc/net/tcp and tcp6
+fn listening_ports() -> Vec<u16> {
+    let mut ports: Vec<u16> = ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .flat_map(|table| {
+            table
+                .lines()
+                .skip(1)
+                .filter_map(|line| {
+                    let fields: Vec<&str> = line.split_whitespace().collect();
+                    // State 0A is LISTEN, the local address ends in the hex port
+                    if fields.get(3) != Some(&"0A") {
+                        return None;
+                    }
+                    let port = fields.get(1)?.rsplit(':').next()?;
+                    u16::from_str_radix(port, 16).ok()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    ports.sort();
+    ports.dedup();
+    ports
+}
+
+fn report_data_usage(app_config: &AppConfig) {
+    let data_dir = Path::new(&*paths::DATA_DIR);
+    let started = std::time::Instant::now();
+
+    match ScanService::disk_usage(data_dir, &app_config.scan_excludes) {
+        Ok(usage) => {
+            info!(
+                "Data directory usage: {:.1} MB in {} files (scanned in {:.2?})",
+                usage.total_bytes as f64 / 1_048_576.0,
+                usage.file_count,
+                started.elapsed()
+            );
+            for (entry, bytes) in &usage.by_top_level {
+                debug!("  - {}: {:.1} MB", entry, *bytes as f64 / 1_048_576.0);
+            }
+        }
+        Err(e) => warn!("Could not determine data directory usage: {}", e),
     }
+}
 
-    Ok(())
+fn collect_unused_assets(app_config: &AppConfig, mode: GcMode) {
+    // Files changed within the last day may belong to a document Foundry has not saved yet
+    let collector = AssetCollector::from_config(app_config, std::time::Duration::from_secs(86_400));
+    match collector.run(mode == GcMode::Collect) {
+        Ok(report) if report.unreferenced.is_empty() => {
+            info!("No unreferenced assets in Data");
+        }
+        Ok(report) => {
+            info!(
+                "{} files in Data ({:.1} MB) are not used by any world",
+                report.unreferenced.len(),
+                report.bytes as f64 / 1_048_576.0
+            );
+            for path in &report.unreferenced {
+                debug!("  - {}", path);
+            }
+        }
+        Err(e) => warn!("Could not look for unreferenced assets: {}", e),
+    }
 }
@@ -0,0 +1,159 @@
+use anyhow::{Context, Result, anyhow};
+use futures_util::{SinkExt, StreamExt};
+use nix::sys::signal::{Signal, kill};
+use nix::unistd::Pid;
+use serde_json::{Value, json};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use tokio::net::TcpStream;
+use tokio::time::{Duration, sleep};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
+use tracing::info;
+
+use crate::permissions::{self, FileKind};
+use crate::utils::paths;
+
+/// Default port node opens its inspector on after SIGUSR1
+const INSPECTOR_PORT: u16 = 9229;
+
+/// Chrome DevTools protocol session with the running Foundry node process.
+///
+/// The inspector is enabled on demand with SIGUSR1 and stays reachable on localhost until
+/// Foundry restarts.
+pub struct Inspector {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    next_id: u64,
+}
+
+impl Inspector {
+    /// Enable the inspector of the Foundry process and attach to it
+    pub async fn attach() -> Result<Self> {
+        let pid = find_foundry_pid()?;
+        kill(Pid::from_raw(pid), Signal::SIGUSR1)
+            .with_context(|| format!("Failed to signal Foundry process {}", pid))?;
+
+        let list_url = format!("http://127.0.0.1:{}/json/list", INSPECTOR_PORT);
+        let mut targets = None;
+        for _ in 0..20 {
+            sleep(Duration::from_millis(500)).await;
+            if let Ok(resp) = reqwest::get(&list_url).await {
+                targets = resp.json::<Value>().await.ok();
+                break;
+            }
+        }
+        let targets =
+            targets.ok_or_else(|| anyhow!("Inspector of process {} did not open", pid))?;
+        let url = targets[0]["webSocketDebuggerUrl"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Inspector did not list a debugger URL"))?;
+
+        let (socket, _) = connect_async(url)
+            .await
+            .context("Failed to connect to the inspector")?;
+        info!("Attached to the inspector of Foundry process {}", pid);
+        Ok(Self { socket, next_id: 1 })
+    }
+
+    /// Call a protocol method, handing every event received meanwhile to `on_event`
+    async fn call(
+        &mut self,
+        method: &str,
+        params: Value,
+        mut on_event: impl FnMut(&Value) -> Result<()>,
+    ) -> Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = json!({ "id": id, "method": method, "params": params });
+        self.socket.send(Message::text(request.to_string())).await?;
+
+        while let Some(message) = self.socket.next().await {
+            let Message::Text(text) = message? else {
+                continue;
+            };
+            let payload: Value = serde_json::from_str(&text)?;
+            if payload["id"] == id {
+                if let Some(error) = payload.get("error") {
+                    return Err(anyhow!("{} failed: {}", method, error));
+                }
+                return Ok(payload["result"].clone());
+            }
+            on_event(&payload)?;
+        }
+        Err(anyhow!("Inspector closed the connection during {}", method))
+    }
+
+    /// Write a heap snapshot to `DATA_DIR/diagnostics/`
+    pub async fn heap_snapshot(&mut self) -> Result<PathBuf> {
+        let path = diagnostics_file("heapsnapshot")?;
+        // Heap snapshots contain everything Foundry holds in memory, including credentials
+        let mut file = permissions::create(&path, FileKind::Secret)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+
+        info!("Taking heap snapshot, Foundry pauses until it is written");
+        self.call("HeapProfiler.takeHeapSnapshot", json!({}), |event| {
+            if event["method"] == "HeapProfiler.addHeapSnapshotChunk" {
+                file.write_all(
+                    event["params"]["chunk"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .as_bytes(),
+                )?;
+            }
+            Ok(())
+        })
+        .await?;
+        file.flush()?;
+
+        info!("📸 Heap snapshot saved to {}", path.display());
+        Ok(path)
+    }
+
+    /// Record a CPU profile for `duration` and write it to `DATA_DIR/diagnostics/`
+    pub async fn cpu_profile(&mut self, duration: Duration) -> Result<PathBuf> {
+        let ignore = |_: &Value| Ok(());
+        self.call("Profiler.enable", json!({}), ignore).await?;
+        self.call("Profiler.start", json!({}), ignore).await?;
+        info!("Recording CPU profile for {:?}", duration);
+        sleep(duration).await;
+        let result = self.call("Profiler.stop", json!({}), ignore).await?;
+
+        let path = diagnostics_file("cpuprofile")?;
+        permissions::write(&path, result["profile"].to_string(), FileKind::Regular)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        info!("⏱️ CPU profile saved to {}", path.display());
+        Ok(path)
+    }
+}
+
+fn diagnostics_file(extension: &str) -> Result<PathBuf> {
+    permissions::create_dir_all(&paths::DIAGNOSTICS_DIR)
+        .with_context(|| format!("Failed to create {}", paths::DIAGNOSTICS_DIR.display()))?;
+    let name = format!(
+        "foundry-{}.{}",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S"),
+        extension
+    );
+    Ok(paths::DIAGNOSTICS_DIR.join(name))
+}
+
+/// Find the `node main.js` process, skipping the npx process that started it
+fn find_foundry_pid() -> Result<i32> {
+    for entry in fs::read_dir("/proc")?.flatten() {
+        let Ok(pid) = entry.file_name().to_string_lossy().parse::<i32>() else {
+            continue;
+        };
+        let Ok(cmdline) = fs::read(entry.path().join("cmdline")) else {
+            continue;
+        };
+        let args: Vec<String> = cmdline
+            .split(|b| *b == 0)
+            .map(|arg| String::from_utf8_lossy(arg).to_string())
+            .collect();
+        if args.len() > 1 && args[0].ends_with("node") && args[1].ends_with("main.js") {
+            return Ok(pid);
+        }
+    }
+    Err(anyhow!("No running Foundry process found"))
+}
@@ -0,0 +1,129 @@
+use anyhow::{Context, Result, anyhow};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+use zip::read::ZipArchive;
+
+use crate::events::ProgressEvent;
+use crate::extractor::ExtractorService;
+use crate::reconcile;
+use crate::storage::{self, storage};
+use crate::tempfiles;
+use crate::utils::installed_foundry_version;
+
+/// Entry every usable Linux/Node.js release contains
+const MAIN_SCRIPT: &str = "resources/app/main.js";
+const PACKAGE_JSON: &str = "resources/app/package.json";
+
+/// Installs release archives, shared by downloads, uploads and `FOUNDRY_RELEASE_PATH`
+pub struct InstallService;
+
+impl InstallService {
+    /// Verify `archive_path` and install it into `target_directory`.
+    ///
+    /// The archive is extracted next to the installed release first and only moved into place
+    /// once it extracted completely and contains Foundry's `main.js`, so a broken archive never
+    /// replaces a working installation. The archive itself is left alone.
+    pub async fn install_archive(
+        archive_path: &str,
+        target_directory: &str,
+        event_tx: broadcast::Sender<ProgressEvent>,
+    ) -> Result<()> {
+        let size = storage()
+            .metadata(Path::new(archive_path))
+            .with_context(|| format!("Failed to read {}", archive_path))?
+            .size;
+        if size == 0 {
+            return Err(anyhow!("{} is empty", archive_path));
+        }
+
+        let staging = Path::new(target_directory).join(".install-staging");
+        tempfiles::track(&staging, "install staging");
+        let stale = staging.clone();
+        storage::blocking(move |storage| storage.remove(&stale)).await?;
+        let staging_dir = staging.to_string_lossy().to_string();
+
+        let extracted = async {
+            ExtractorService::extract_zip(archive_path.to_string(), staging_dir, event_tx).await?;
+            if !storage().exists(&staging.join(MAIN_SCRIPT)) {
+                return Err(anyhow!(
+                    "The archive contains no {}, download the Linux/NodeJS release",
+                    MAIN_SCRIPT
+                ));
+            }
+            Self::move_into_place(&staging, Path::new(target_directory)).await
+        }
+        .await;
+
+        let leftover = staging.clone();
+        if let Err(e) = storage::blocking(move |storage| storage.remove(&leftover)).await {
+            warn!("Failed to remove {}: {}", staging.display(), e);
+        }
+        // Also for releases staged by the updater, the live installation is recorded then
+        if extracted.is_ok() {
+            reconcile::record();
+        }
+        extracted
+    }
+
+    /// Install the release archive at `path` unless the same version is installed already
+    pub async fn install_release(path: &str, target_directory: &str) -> Result<()> {
+        let archive_version = release_version(Path::new(path))
+            .with_context(|| format!("{} is not a Foundry release archive", path))?;
+        let installed = installed_foundry_version();
+        if installed.as_deref() == Some(archive_version.as_str()) {
+            info!(
+                "Foundry {} from {} is installed already",
+                archive_version, path
+            );
+            return Ok(());
+        }
+
+        info!(
+            "📦 Installing Foundry {} from {} (installed: {})",
+            archive_version,
+            path,
+            installed.as_deref().unwrap_or("none")
+        );
+        storage().create_dir_all(Path::new(target_directory))?;
+        // Nobody follows the progress of an unattended install
+        let (event_tx, _) = broadcast::channel(16);
+        Self::install_archive(path, target_directory, event_tx).await?;
+        info!("✅ Installed Foundry {}", archive_version);
+        Ok(())
+    }
+
+    /// Replace the top level entries of `target` with those of `staging`, one rename each
+    async fn move_into_place(staging: &Path, target: &Path) -> Result<()> {
+        let (staging, target) = (staging.to_path_buf(), target.to_path_buf());
+        let moved = storage::blocking(move |storage| {
+            for entry in storage.list(&staging)? {
+                let Some(name) = entry.path.file_name() else {
+                    continue;
+                };
+                let destination: PathBuf = target.join(name);
+                storage.remove(&destination)?;
+                storage.rename(&entry.path, &destination)?;
+            }
+            Ok(())
+        });
+        moved.await.context("Failed to move the release into place")
+    }
+}
+
+/// Version of the release in a Foundry archive, read without extracting it
+pub fn release_version(path: &Path) -> Result<String> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut contents = String::new();
+    archive
+        .by_name(PACKAGE_JSON)
+        .with_context(|| format!("No {} in the archive", PACKAGE_JSON))?
+        .read_to_string(&mut contents)?;
+    let manifest: serde_json::Value = serde_json::from_str(&contents)?;
+    manifest["version"]
+        .as_str()
+        .map(|v| v.to_string())
+        .ok_or_else(|| anyhow!("{} has no version", PACKAGE_JSON))
+}
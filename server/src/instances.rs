@@ -0,0 +1,380 @@
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Mutex, PoisonError};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Child;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio::time::{Duration, sleep};
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+
+use crate::api::{self, ApiConfig, Message};
+use crate::config::AppConfig;
+use crate::launch::{foundry_command, terminate_group};
+use crate::notify::{self, Notification, Severity};
+use crate::permissions;
+use crate::status;
+use crate::supervisor::{RestartPolicy, RestartSupervisor};
+use crate::utils::paths;
+use crate::watchdog::diagnostic_node_options;
+
+/// Name the Foundry of `DATA_DIR` and `SERVER_PORT` has in the routing map
+pub const MAIN: &str = "main";
+
+/// A further Foundry started next to the main one, from the same installation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instance {
+    pub name: String,
+    pub port: u16,
+}
+
+impl Instance {
+    /// `DATA_DIR/instances/<name>`, with its own `Config`, `Data` and license
+    pub fn data_dir(&self) -> PathBuf {
+        paths::INSTANCES_DIR.join(&self.name)
+    }
+}
+
+/// Parse `INSTANCES`, comma separated `name:port` pairs. Invalid and clashing entries are
+/// skipped with a warning, `taken` are the ports the container serves already.
+pub fn parse(value: &str, taken: &[u16]) -> Vec<Instance> {
+    let mut instances: Vec<Instance> = Vec::new();
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let parsed = entry
+            .split_once(':')
+            .and_then(|(name, port)| Some((name.trim(), port.trim().parse::<u16>().ok()?)));
+        let Some((name, port)) = parsed.filter(|(name, _)| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        }) else {
+            warn!(
+                "⚠️ Ignoring INSTANCES entry {}, expected name:port with a lower case name",
+                entry
+            );
+            continue;
+        };
+        if name == MAIN || instances.iter().any(|i| i.name == name) {
+            warn!("⚠️ Ignoring INSTANCES entry {}, the name is taken", entry);
+            continue;
+        }
+        if taken.contains(&port) || instances.iter().any(|i| i.port == port) {
+            warn!(
+                "⚠️ Ignoring INSTANCES entry {}, port {} is taken",
+                entry, port
+            );
+            continue;
+        }
+        instances.push(Instance {
+            name: name.to_string(),
+            port,
+        });
+    }
+    instances
+}
+
+/// State of an instance for the routing map
+#[derive(Debug, Clone, Default)]
+struct InstanceState {
+    running: bool,
+    pid: Option<u32>,
+    restarts: u32,
+}
+
+static STATES: Mutex<BTreeMap<String, InstanceState>> = Mutex::new(BTreeMap::new());
+
+fn set_state(name: &str, update: impl FnOnce(&mut InstanceState)) {
+    let mut states = STATES.lock().unwrap_or_else(PoisonError::into_inner);
+    update(states.entry(name.to_string()).or_default());
+}
+
+/// Runs the `INSTANCES` until the main Foundry stops, restarting them like the main one
+pub struct InstanceSupervisor {
+    pub instances: Vec<Instance>,
+    pub script_path: String,
+    /// Arguments of the main Foundry, `--dataPath` and `--port` are replaced per instance
+    pub args: Vec<String>,
+    pub restart_policy: RestartPolicy,
+    pub max_restarts: Option<u32>,
+    pub stop_grace: Duration,
+}
+
+/// Running instances, stopped together with the main Foundry
+pub struct RunningInstances {
+    stop: watch::Sender<bool>,
+    tasks: JoinSet<()>,
+}
+
+impl InstanceSupervisor {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            instances: config.instances.clone(),
+            script_path: config.foundry_script.clone(),
+            args: config.foundry_args.clone(),
+            restart_policy: config.restart_policy,
+            max_restarts: config.max_restarts,
+            stop_grace: Duration::from_secs(config.shutdown_timeout),
+        }
+    }
+
+    pub fn start(self) -> RunningInstances {
+        let (stop, _) = watch::channel(false);
+        let mut tasks = JoinSet::new();
+        for instance in self.instances.clone() {
+            let args: Vec<String> = self
+                .args
+                .iter()
+                .filter(|arg| !arg.starts_with("--dataPath=") && !arg.starts_with("--port="))
+                .cloned()
+                .chain([
+                    format!("--dataPath={}", instance.data_dir().display()),
+                    format!("--port={}", instance.port),
+                ])
+                .collect();
+            let runner = InstanceRunner {
+                instance,
+                script_path: self.script_path.clone(),
+                args,
+                supervisor: RestartSupervisor::new(self.restart_policy, self.max_restarts),
+                stop_grace: self.stop_grace,
+            };
+            tasks.spawn(runner.run(stop.subscribe()));
+        }
+        RunningInstances { stop, tasks }
+    }
+}
+
+impl RunningInstances {
+    /// Stop every instance, each gets `SHUTDOWN_TIMEOUT` to close its world
+    pub async fn stop(mut self) {
+        let _ = self.stop.send(true);
+        while self.tasks.join_next().await.is_some() {}
+    }
+}
+
+struct InstanceRunner {
+    instance: Instance,
+    script_path: String,
+    args: Vec<String>,
+    supervisor: RestartSupervisor,
+    stop_grace: Duration,
+}
+
+impl InstanceRunner {
+    async fn run(mut self, mut stop: watch::Receiver<bool>) {
+        let name = self.instance.name.clone();
+        let data_dir = self.instance.data_dir();
+        if let Err(e) = permissions::create_dir_all(&data_dir) {
+            error!("❌ Failed to create {}: {}", data_dir.display(), e);
+            return;
+        }
+        let args: Vec<&str> = self.args.iter().map(String::as_str).collect();
+        loop {
+            // Like the main Foundry, waits for the installer to place a release
+            if !Path::new(&self.script_path).exists() {
+                tokio::select! {
+                    _ = sleep(Duration::from_secs(10)) => continue,
+                    _ = stopped(&mut stop) => return,
+                }
+            }
+            info!(
+                "🚀 Launching instance {} on port {}",
+                name, self.instance.port
+            );
+            let mut command = foundry_command(&self.script_path, &args);
+            command
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .env("NODE_OPTIONS", diagnostic_node_options())
+                .process_group(0);
+            self.supervisor.started();
+            let failed = match command.spawn() {
+                Ok(mut child) => {
+                    set_state(&name, |state| {
+                        state.running = true;
+                        state.pid = child.id();
+                    });
+                    forward_output(&name, &mut child);
+                    let failed = tokio::select! {
+                        exit = child.wait() => match exit {
+                            Ok(exit) => {
+                                warn!("⚠️ Instance {} exited with: {}", name, exit);
+                                !exit.success()
+                            }
+                            Err(e) => {
+                                error!("❌ Failed to wait for instance {}: {}", name, e);
+                                true
+                            }
+                        },
+                        _ = stopped(&mut stop) => {
+                            info!("Stopping instance {}", name);
+                            terminate_group(&mut child, self.stop_grace).await;
+                            set_state(&name, |state| {
+                                state.running = false;
+                                state.pid = None;
+                            });
+                            return;
+                        }
+                    };
+                    set_state(&name, |state| {
+                        state.running = false;
+                        state.pid = None;
+                    });
+                    failed
+                }
+                Err(e) => {
+                    error!("❌ Failed to spawn instance {}: {}", name, e);
+                    true
+                }
+            };
+
+            // The main Foundry keeps running, only this instance is given up
+            let Some(delay) = self.supervisor.next_restart(failed) else {
+                if failed {
+                    notify::send(Notification {
+                        severity: Severity::Critical,
+                        title: "Foundry instance stopped".to_string(),
+                        message: format!(
+                            "Instance {} failed after {} restarts and is not started again.",
+                            name,
+                            self.supervisor.restarts()
+                        ),
+                    });
+                }
+                return;
+            };
+            set_state(&name, |state| state.restarts = self.supervisor.restarts());
+            if failed {
+                notify::send(Notification {
+                    severity: Severity::Warning,
+                    title: "Foundry instance crashed".to_string(),
+                    message: format!(
+                        "Instance {} exited unexpectedly and is started again in {} seconds.",
+                        name,
+                        delay.as_secs()
+                    ),
+                });
+            }
+            tokio::select! {
+                _ = sleep(delay) => {}
+                _ = stopped(&mut stop) => return,
+            }
+        }
+    }
+}
+
+/// Resolves once the instances are stopped, the guard of `wait_for` must not be held
+async fn stopped(stop: &mut watch::Receiver<bool>) {
+    let _ = stop.wait_for(|stop| *stop).await;
+}
+
+/// Log the output of an instance prefixed with its name, it would be indistinguishable from
+/// the main Foundry's otherwise
+fn forward_output(name: &str, child: &mut Child) {
+    if let Some(stdout) = child.stdout.take() {
+        tokio::spawn(forward_lines(name.to_string(), stdout));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        tokio::spawn(forward_lines(name.to_string(), stderr));
+    }
+}
+
+async fn forward_lines<R: AsyncRead + Unpin>(name: String, output: R) {
+    let mut lines = BufReader::new(output).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        info!("[{}] {}", name, line);
+    }
+}
+
+/// Entry of the routing map
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = InstanceRoute)]
+pub struct Route {
+    /// `main` for the Foundry of `DATA_DIR` and `SERVER_PORT`
+    pub name: String,
+    pub port: u16,
+    pub data_dir: String,
+    pub running: bool,
+    pub pid: Option<u32>,
+    pub restarts: u32,
+    /// Active world, `None` on the setup screen or while the instance does not answer
+    pub world: Option<String>,
+    pub users: u32,
+}
+
+/// What the routing map is built from
+#[derive(Clone)]
+pub struct RoutingConfig {
+    pub foundry_port: u16,
+    pub instances: Vec<Instance>,
+}
+
+/// Every Foundry of the container, the main one first, e.g. for a reverse proxy to route by
+pub async fn routes(config: &RoutingConfig) -> Vec<Route> {
+    let main = status::current();
+    let mut routes = vec![Route {
+        name: MAIN.to_string(),
+        port: config.foundry_port,
+        data_dir: paths::DATA_DIR.clone(),
+        running: main.running,
+        pid: main.pid,
+        restarts: main.restarts,
+        world: None,
+        users: 0,
+    }];
+    let states = STATES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    for instance in &config.instances {
+        let state = states.get(&instance.name).cloned().unwrap_or_default();
+        routes.push(Route {
+            name: instance.name.clone(),
+            port: instance.port,
+            data_dir: instance.data_dir().display().to_string(),
+            running: state.running,
+            pid: state.pid,
+            restarts: state.restarts,
+            world: None,
+            users: 0,
+        });
+    }
+    for route in routes.iter_mut().filter(|route| route.running) {
+        if let Ok(server) = status::fetch_server_status(route.port).await
+            && server.active
+        {
+            route.world = server.world;
+            route.users = server.users;
+        }
+    }
+    routes
+}
+
+/// The main Foundry and the `INSTANCES` with their ports and active worlds, e.g. for a
+/// reverse proxy to route by
+#[utoipa::path(
+    get,
+    path = "/api/instances",
+    operation_id = "instances",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "Routing map, the main instance first", body = Vec<Route>),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+    )
+)]
+pub async fn show(
+    req: HttpRequest,
+    config: web::Data<ApiConfig>,
+    routing: web::Data<RoutingConfig>,
+) -> impl Responder {
+    if let Some(rejected) = api::reject(&req, &config) {
+        return rejected;
+    }
+    HttpResponse::Ok().json(routes(&routing).await)
+}
@@ -0,0 +1,127 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+use crate::scan::{ScanEntry, ScanService};
+use crate::utils::paths;
+
+/// Outcome of an integrity check over all worlds
+#[derive(Debug, Default)]
+pub struct IntegrityReport {
+    pub databases_checked: usize,
+    pub problems: Vec<String>,
+}
+
+pub struct IntegrityService;
+
+impl IntegrityService {
+    /// Check every world database below `DATA_DIR/Data/worlds` for signs of corruption.
+    ///
+    /// LevelDB stores (v11+) must have a `CURRENT` file pointing at an existing manifest and no
+    /// empty table files; NeDB files (v10 and older) must contain one JSON document per line.
+    pub fn check_worlds(excludes: &[String]) -> Result<IntegrityReport> {
+        let mut report = IntegrityReport::default();
+        let worlds_dir = &*paths::WORLDS_DIR;
+        if !worlds_dir.exists() {
+            debug!(
+                "No worlds directory at {}, nothing to check",
+                worlds_dir.display()
+            );
+            return Ok(report);
+        }
+
+        // Group files by their directory so each LevelDB store is checked as a unit
+        let mut directories: BTreeMap<PathBuf, Vec<ScanEntry>> = BTreeMap::new();
+        for entry in ScanService::walk(worlds_dir, excludes)? {
+            if let Some(parent) = entry.path.parent() {
+                directories
+                    .entry(parent.to_path_buf())
+                    .or_default()
+                    .push(entry);
+            }
+        }
+
+        for (dir, files) in &directories {
+            if files.iter().any(|f| file_name(&f.path) == "CURRENT") {
+                report.databases_checked += 1;
+                check_leveldb(dir, files, &mut report.problems);
+            }
+            for file in files.iter().filter(|f| has_extension(&f.path, "db")) {
+                report.databases_checked += 1;
+                check_nedb(&file.path, &mut report.problems);
+            }
+        }
+
+        if report.problems.is_empty() {
+            info!(
+                "Integrity check passed for {} world databases",
+                report.databases_checked
+            );
+        } else {
+            for problem in &report.problems {
+                warn!("Integrity problem: {}", problem);
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+fn check_leveldb(dir: &Path, files: &[ScanEntry], problems: &mut Vec<String>) {
+    match fs::read_to_string(dir.join("CURRENT")) {
+        Ok(current) => {
+            let manifest = current.trim();
+            if manifest.is_empty() || !dir.join(manifest).exists() {
+                problems.push(format!(
+                    "{}: CURRENT references missing manifest '{}'",
+                    dir.display(),
+                    manifest
+                ));
+            }
+        }
+        Err(e) => problems.push(format!("{}: unreadable CURRENT file: {}", dir.display(), e)),
+    }
+
+    for file in files {
+        if has_extension(&file.path, "ldb") && file.size == 0 {
+            problems.push(format!("{}: empty table file", file.path.display()));
+        }
+    }
+}
+
+fn check_nedb(path: &Path, problems: &mut Vec<String>) {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            problems.push(format!("{}: unreadable database: {}", path.display(), e));
+            return;
+        }
+    };
+
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let valid = line
+            .map(|l| l.trim().is_empty() || serde_json::from_str::<serde_json::Value>(&l).is_ok())
+            .unwrap_or(false);
+        if !valid {
+            problems.push(format!(
+                "{}: malformed record on line {}",
+                path.display(),
+                index + 1
+            ));
+            return;
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().is_some_and(|e| e == extension)
+}
@@ -0,0 +1,178 @@
+use anyhow::{Result, anyhow};
+use std::sync::OnceLock;
+use tokio::sync::{Semaphore, SemaphorePermit, oneshot};
+use tokio::time::{Duration, sleep};
+use tracing::{debug, info, warn};
+
+use crate::config::AppConfig;
+use crate::status;
+
+/// Linux I/O scheduling classes of `ioprio_set`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoClass {
+    /// Keep the I/O priority of the launcher
+    Unchanged,
+    /// Lowest priority of the best-effort class, still served under load
+    BestEffort,
+    /// Only served when no one else uses the disk
+    Idle,
+}
+
+impl IoClass {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "none" => Some(Self::Unchanged),
+            "best-effort" => Some(Self::BestEffort),
+            "idle" => Some(Self::Idle),
+            _ => None,
+        }
+    }
+
+    /// Value for `ioprio_set`, the class in the upper bits and the level in the lower ones
+    fn ioprio(self) -> Option<i32> {
+        match self {
+            Self::Unchanged => None,
+            Self::BestEffort => Some((2 << 13) | 7),
+            Self::Idle => Some(3 << 13),
+        }
+    }
+}
+
+/// How scheduled work like backups, test restores, exports and state syncs shares the machine
+/// with the game, configured with `BACKGROUND_*`
+#[derive(Debug, Clone)]
+pub struct JobLimits {
+    /// Added to the niceness of job threads, 0 to 19
+    pub nice: i32,
+    pub io_class: IoClass,
+    /// Jobs running at the same time, further ones wait for a slot
+    pub concurrency: usize,
+    /// Hold jobs back while players are connected
+    pub pause_while_playing: bool,
+    pub foundry_port: u16,
+}
+
+struct Jobs {
+    limits: JobLimits,
+    slots: Semaphore,
+}
+
+static JOBS: OnceLock<Jobs> = OnceLock::new();
+
+/// Set up the limits, called once at startup
+pub fn init(config: &AppConfig) {
+    let limits = config.background_jobs.clone();
+    debug!("Background job limits: {:?}", limits);
+    let _ = JOBS.set(Jobs {
+        slots: Semaphore::new(limits.concurrency),
+        limits,
+    });
+}
+
+/// Wait until a background job may start: no player is connected when
+/// `BACKGROUND_WHEN_IDLE` is set, and one of the `BACKGROUND_CONCURRENCY` slots is free.
+/// The job holds its slot until the permit is dropped.
+pub async fn slot(name: &str) -> Option<SemaphorePermit<'static>> {
+    let jobs = JOBS.get()?;
+    if jobs.limits.pause_while_playing {
+        let mut announced = false;
+        while let Some(players) = connected_players(jobs.limits.foundry_port).await {
+            if !announced {
+                info!(
+                    "⏸️ Holding back the {} until no player is connected ({} online)",
+                    name, players
+                );
+                announced = true;
+            }
+            sleep(Duration::from_secs(60)).await;
+        }
+    }
+    if jobs.slots.available_permits() == 0 {
+        debug!("The {} waits for another background job to finish", name);
+    }
+    jobs.slots.acquire().await.ok()
+}
+
+/// Number of connected players, `None` when nobody plays or Foundry does not answer
+async fn connected_players(foundry_port: u16) -> Option<u32> {
+    if !status::current().running {
+        return None;
+    }
+    match status::fetch_server_status(foundry_port).await {
+        Ok(server) if server.users > 0 => Some(server.users),
+        _ => None,
+    }
+}
+
+/// Run blocking `job` once it gets a slot, on a thread of its own at background priority.
+///
+/// The thread is not taken from tokio's blocking pool: an unprivileged process cannot raise
+/// the priority of a thread again, and the pool would hand the lowered thread to other work.
+pub async fn run<T, F>(name: &'static str, job: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let _slot = slot(name).await;
+    let (done_tx, done_rx) = oneshot::channel();
+    // Jobs that talk to buckets block on the runtime like threads of the blocking pool do
+    let runtime = tokio::runtime::Handle::current();
+    std::thread::Builder::new()
+        .name(format!("job-{}", name.replace(' ', "-")))
+        .spawn(move || {
+            let _runtime = runtime.enter();
+            lower_priority();
+            let _ = done_tx.send(job());
+        })?;
+    done_rx.await.map_err(|_| anyhow!("The {} panicked", name))
+}
+
+/// Lower the CPU and I/O priority of the calling thread to the configured limits. Children
+/// spawned by the thread afterwards inherit them.
+pub fn lower_priority() {
+    let Some(jobs) = JOBS.get() else {
+        return;
+    };
+    if let Err(e) = apply(&jobs.limits) {
+        warn!("Failed to lower the priority of a background job: {}", e);
+    }
+}
+
+/// Start `command` at background priority, e.g. the Foundry instance of a test restore
+pub fn lower_command_priority(command: &mut tokio::process::Command) {
+    let Some(jobs) = JOBS.get() else {
+        return;
+    };
+    let limits = jobs.limits.clone();
+    // SAFETY: `apply` only issues syscalls, nothing that allocates or locks after the fork.
+    // A failure is ignored, the job runs at normal priority then.
+    unsafe {
+        command.pre_exec(move || {
+            let _ = apply(&limits);
+            Ok(())
+        });
+    }
+}
+
+/// Set the niceness and I/O class of the calling thread, which on Linux are per thread
+fn apply(limits: &JobLimits) -> std::io::Result<()> {
+    if limits.nice > 0 {
+        // SAFETY: plain syscall on the calling thread, 0 selects it
+        let result = unsafe {
+            let current = nix::libc::getpriority(nix::libc::PRIO_PROCESS, 0);
+            nix::libc::setpriority(nix::libc::PRIO_PROCESS, 0, (current + limits.nice).min(19))
+        };
+        if result != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    if let Some(ioprio) = limits.io_class.ioprio() {
+        // IOPRIO_WHO_PROCESS with 0 selects the calling thread
+        // SAFETY: plain syscall without pointers
+        let result = unsafe { nix::libc::syscall(nix::libc::SYS_ioprio_set, 1, 0, ioprio) };
+        if result != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
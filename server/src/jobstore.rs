@@ -0,0 +1,127 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+use tracing::warn;
+
+use crate::permissions::{self, FileKind};
+use crate::utils::paths;
+
+/// Serializes updates of the file, downloads and uploads may run at the same time
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Long-running transfers in progress, kept in `DATA_DIR/.wrapper/jobs.json` so a restarted
+/// container continues them instead of starting over, and the temporary files that are removed
+/// once a run left them behind
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JobStore {
+    /// Downloads by the path they are written to
+    pub downloads: BTreeMap<String, RangeDownload>,
+    /// Multipart uploads by the key of the object
+    pub uploads: BTreeMap<String, MultipartUpload>,
+    /// Temporary files and directories by their path
+    pub temporary: BTreeMap<String, TemporaryFile>,
+}
+
+/// A download continued with a `Range` request, the length of the file is its progress
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RangeDownload {
+    pub url: String,
+    /// Length of the complete file
+    pub total: u64,
+    /// Validators of the response, a resumed download must get the same file
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub started: String,
+}
+
+/// A multipart upload to an S3 compatible bucket and the parts already accepted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultipartUpload {
+    pub bucket: String,
+    pub upload_id: String,
+    pub source: PathBuf,
+    /// Size and modification time of the source, a changed file is uploaded from scratch
+    pub size: u64,
+    pub modified: i64,
+    pub part_size: u64,
+    pub parts: Vec<UploadedPart>,
+    pub started: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadedPart {
+    pub number: u32,
+    pub etag: String,
+}
+
+/// A download, staging directory or partial archive the wrapper removes when it is done
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemporaryFile {
+    /// What wrote it, e.g. `download` or `package staging`
+    pub purpose: String,
+    pub created: String,
+}
+
+impl JobStore {
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid job store {}", path.display()))
+    }
+
+    /// Change the store under the lock and write it atomically
+    pub fn update(change: impl FnOnce(&mut Self)) -> Result<()> {
+        let _guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut store = Self::load()?;
+        change(&mut store);
+        permissions::create_dir_all(&paths::WRAPPER_STATE_DIR)
+            .with_context(|| format!("Failed to create {}", paths::WRAPPER_STATE_DIR.display()))?;
+        let path = Self::path();
+        let tmp_path = path.with_extension("json.tmp");
+        permissions::write(
+            &tmp_path,
+            serde_json::to_string_pretty(&store)?,
+            FileKind::Regular,
+        )
+        .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, &path)
+            .with_context(|| format!("Failed to replace {}", path.display()))
+    }
+
+    fn path() -> PathBuf {
+        paths::WRAPPER_STATE_DIR.join("jobs.json")
+    }
+}
+
+/// The stored download of `path`, if one was interrupted
+pub fn download(path: &str) -> Option<RangeDownload> {
+    JobStore::load().ok()?.downloads.remove(path)
+}
+
+/// The stored upload of `key`, if one was interrupted
+pub fn upload(key: &str) -> Option<MultipartUpload> {
+    JobStore::load().ok()?.uploads.remove(key)
+}
+
+/// Uploads interrupted by a restart, by key
+pub fn pending_uploads() -> BTreeMap<String, MultipartUpload> {
+    JobStore::load()
+        .map(|store| store.uploads)
+        .unwrap_or_default()
+}
+
+/// Update the store, a transfer whose progress cannot be recorded still goes on
+pub fn record(change: impl FnOnce(&mut JobStore)) {
+    if let Err(e) = JobStore::update(change) {
+        warn!("Failed to record the progress of a transfer: {:#}", e);
+    }
+}
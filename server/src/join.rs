@@ -0,0 +1,49 @@
+use actix_web::http::header;
+use actix_web::{HttpResponse, Responder, web};
+use tracing::{info, warn};
+
+use crate::qr::QrCode;
+
+/// Pixels per module of the PNG, large enough to scan from across a table
+const PNG_SCALE: usize = 8;
+
+/// Where players join the game, shown in the startup banner and on the admin API
+pub struct JoinConfig {
+    pub url: String,
+}
+
+/// Log the join URL, with `qr` as a QR code players at the table can scan with a tablet
+pub fn print_join_url(url: &str, qr: bool) {
+    info!("🔗 Players join at {}", url);
+    if !qr {
+        return;
+    }
+    match QrCode::encode(url) {
+        Ok(code) => {
+            for line in code.to_text() {
+                info!("{}", line);
+            }
+        }
+        Err(e) => warn!("Could not show the join URL as QR code: {}", e),
+    }
+}
+
+/// The join URL as QR code, e.g. for a tablet at the table or a second screen
+#[utoipa::path(
+    get,
+    path = "/join.png",
+    operation_id = "joinQrCode",
+    responses(
+        (status = 200, description = "PNG image", body = Vec<u8>, content_type = "image/png"),
+        (status = 500, description = "The URL does not fit into a QR code", body = String, content_type = "text/plain"),
+    )
+)]
+pub async fn qr_png(config: web::Data<JoinConfig>) -> impl Responder {
+    match QrCode::encode(&config.url) {
+        Ok(code) => HttpResponse::Ok()
+            .content_type("image/png")
+            .insert_header((header::CACHE_CONTROL, "no-cache"))
+            .body(code.to_png(PNG_SCALE)),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::permissions::{self, FileKind};
+use crate::utils::paths;
+
+/// A change of an operation, written to the journal before it is made
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum Step {
+    /// `from` is moved to `to`, undone by moving it back
+    Rename { from: PathBuf, to: PathBuf },
+    /// `link` is pointed elsewhere, undone by pointing it at `previous` again or removing it
+    Relink {
+        link: PathBuf,
+        previous: Option<PathBuf>,
+    },
+}
+
+/// Write-ahead journal of an operation moving files of the installation or the data
+/// directory around, `DATA_DIR/.wrapper/journal.json`.
+///
+/// Each step is recorded before it is made. When the wrapper is killed halfway, the next start
+/// undoes the recorded steps, or finishes the operation once it was committed, instead of
+/// starting against half of two installations.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Journal {
+    /// What the operation does, for the log
+    pub operation: String,
+    pub started: String,
+    pub steps: Vec<Step>,
+    /// Set once every step is made, recovery then only removes `cleanup`
+    #[serde(default)]
+    pub committed: bool,
+    /// Left over once the operation is done or undone, e.g. staging directories
+    #[serde(default)]
+    pub cleanup: Vec<PathBuf>,
+    /// Logged when the operation is interrupted, for steps the journal cannot undo
+    #[serde(default)]
+    pub hint: Option<String>,
+}
+
+impl Journal {
+    /// Start journaling `operation`, failing while an interrupted one was not recovered
+    pub fn begin(operation: impl Into<String>) -> Result<Self> {
+        let path = path();
+        if path.exists() {
+            return Err(anyhow!(
+                "{} records another operation, wait for it or restart the container to recover it",
+                path.display()
+            ));
+        }
+        let journal = Self {
+            operation: operation.into(),
+            started: Utc::now().to_rfc3339(),
+            steps: Vec::new(),
+            committed: false,
+            cleanup: Vec::new(),
+            hint: None,
+        };
+        journal.save()?;
+        Ok(journal)
+    }
+
+    /// Remove `path` when the operation is done or undone
+    pub fn clean_up(&mut self, path: impl Into<PathBuf>) -> Result<()> {
+        self.cleanup.push(path.into());
+        self.save()
+    }
+
+    /// Record `step` before the caller makes it
+    pub fn record(&mut self, step: Step) -> Result<()> {
+        self.steps.push(step);
+        self.save()
+    }
+
+    /// Move `from` to `to` as a recorded step
+    pub fn rename(&mut self, from: &Path, to: &Path) -> Result<()> {
+        self.record(Step::Rename {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        })?;
+        fs::rename(from, to)
+            .with_context(|| format!("Failed to move {} to {}", from.display(), to.display()))
+    }
+
+    /// Log `hint` when the operation is interrupted
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Result<Self> {
+        self.hint = Some(hint.into());
+        self.save()?;
+        Ok(self)
+    }
+
+    /// Every step is made, an interruption from now on finishes the operation
+    pub fn commit(&mut self) -> Result<()> {
+        self.committed = true;
+        self.save()
+    }
+
+    /// Commit and finish when the steps made up to `result` succeeded, roll them back otherwise
+    pub fn complete<T>(mut self, result: Result<T>) -> Result<T> {
+        match result {
+            Ok(value) => {
+                // Not committed, the next start would undo a finished operation
+                if let Err(e) = self.commit() {
+                    warn!("Failed to commit the {}: {:#}", self.operation, e);
+                }
+                self.finish();
+                Ok(value)
+            }
+            Err(e) => {
+                let operation = self.operation.clone();
+                if let Err(undo) = self.roll_back() {
+                    warn!(
+                        "⚠️ {:#}, the next start tries again to roll back the {}",
+                        undo, operation
+                    );
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Remove what is left over and the journal, after `commit`
+    pub fn finish(self) {
+        self.remove_leftovers();
+        if let Err(e) = fs::remove_file(path()) {
+            warn!(
+                "Failed to remove the journal of the {}: {}",
+                self.operation, e
+            );
+        }
+    }
+
+    /// Undo the steps made so far, newest first, after a step failed
+    pub fn roll_back(self) -> Result<()> {
+        for step in self.steps.iter().rev() {
+            undo(step).with_context(|| format!("Failed to roll back the {}", self.operation))?;
+        }
+        self.remove_leftovers();
+        fs::remove_file(path()).context("Failed to remove the journal")
+    }
+
+    fn remove_leftovers(&self) {
+        for path in &self.cleanup {
+            let removed = match fs::symlink_metadata(path) {
+                Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+                Ok(_) => fs::remove_file(path),
+                Err(_) => continue,
+            };
+            if let Err(e) = removed {
+                warn!("Failed to remove {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// Written and synced before the step it records is made
+    fn save(&self) -> Result<()> {
+        let path = path();
+        permissions::create_dir_all(&paths::WRAPPER_STATE_DIR)
+            .with_context(|| format!("Failed to create {}", paths::WRAPPER_STATE_DIR.display()))?;
+        let tmp_path = path.with_extension("json.tmp");
+        let mut file = permissions::create(&tmp_path, FileKind::Regular)
+            .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &path)
+            .with_context(|| format!("Failed to replace {}", path.display()))?;
+        fs::File::open(&*paths::WRAPPER_STATE_DIR)
+            .and_then(|dir| dir.sync_all())
+            .with_context(|| format!("Failed to sync {}", paths::WRAPPER_STATE_DIR.display()))
+    }
+}
+
+/// Operation the journal records, one is running or the last run was killed in it
+pub fn pending() -> Option<String> {
+    let contents = fs::read_to_string(path()).ok()?;
+    serde_json::from_str::<Journal>(&contents)
+        .ok()
+        .map(|journal| journal.operation)
+}
+
+/// Finish or undo an operation the last run was killed in, called at startup before anything
+/// reads the installation or the data directory
+pub fn recover() -> Result<()> {
+    let path = path();
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    let journal: Journal = serde_json::from_str(&contents)
+        .with_context(|| format!("Invalid journal {}, move it away to start", path.display()))?;
+
+    if journal.committed {
+        info!(
+            "The last run stopped before cleaning up after the {}, finishing it",
+            journal.operation
+        );
+        journal.finish();
+        return Ok(());
+    }
+    warn!(
+        "⚠️ The last run was killed during the {} started {}",
+        journal.operation, journal.started
+    );
+    if let Some(hint) = &journal.hint {
+        warn!("⚠️ {}", hint);
+    }
+    let (operation, steps) = (journal.operation.clone(), journal.steps.len());
+    journal.roll_back()?;
+    if steps > 0 {
+        info!("Rolled back the {} steps of the {}", steps, operation);
+    }
+    Ok(())
+}
+
+/// Undo `step` as far as it was made, a step recorded but never made is skipped
+fn undo(step: &Step) -> Result<()> {
+    match step {
+        Step::Rename { from, to } => {
+            if fs::symlink_metadata(to).is_err() || fs::symlink_metadata(from).is_ok() {
+                return Ok(());
+            }
+            if let Some(parent) = from.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(to, from)
+                .with_context(|| format!("Failed to move {} back", to.display()))?;
+        }
+        Step::Relink { link, previous } => {
+            let Some(previous) = previous else {
+                return match fs::remove_file(link) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+                    _ => Ok(()),
+                };
+            };
+            if fs::read_link(link).ok().as_ref() == Some(previous) {
+                return Ok(());
+            }
+            let staged = link.with_extension("rollback");
+            let _ = fs::remove_file(&staged);
+            symlink(previous, &staged)?;
+            fs::rename(&staged, link)
+                .with_context(|| format!("Failed to point {} back", link.display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn path() -> PathBuf {
+    paths::WRAPPER_STATE_DIR.join("journal.json")
+}
@@ -1,110 +1,541 @@
+use crate::branding::Branding;
+use crate::changelog::Changelog;
 use crate::config::AppConfig;
-use std::path::Path;
+use crate::fingerprint::BootFingerprint;
+use crate::gamesocket;
+use crate::hooks::{self, HookEvent};
+use crate::instances::InstanceSupervisor;
+use crate::license::LicenseCheck;
+use crate::logging;
+use crate::migration;
+use crate::notify::{self, Notification, Severity};
+use crate::packages::PackageInstaller;
+use crate::permissions;
+use crate::preflight::{Failure, Preflight};
+use crate::presets::SettingsPresets;
+use crate::reaper;
+use crate::reconcile;
+use crate::resilience::{self, Phase};
+use crate::status;
+use crate::supervisor::RestartSupervisor;
+use crate::upgrade::UpgradeVerifier;
+use crate::utils::{find_executable, installed_foundry_version};
+use crate::watchdog::{
+    ResponsivenessWatchdog, StartupWatchdog, capture_diagnostic_report, diagnostic_node_options,
+};
+use nix::sys::signal::{Signal, killpg};
+use nix::unistd::Pid;
+use serde_json::json;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use tokio::process::Command;
-use tokio::sync::oneshot;
-use tokio::time::{Duration, sleep};
+use tokio::process::{Child, Command};
+use tokio::signal::unix::{Signal as UnixSignal, SignalKind, signal};
+use tokio::sync::{Notify, RwLock, RwLockWriteGuard, oneshot};
+use tokio::time::{Duration, Instant, sleep, timeout};
 use tracing::{debug, error, info, warn};
 
+/// Wakes the launcher loop when Foundry should be restarted
+static RESTART_REQUESTED: Notify = Notify::const_new();
+
+/// Restart the running Foundry process, does nothing while Foundry is not running
+pub fn request_restart() {
+    RESTART_REQUESTED.notify_waiters();
+}
+
+/// Held while Foundry must stay stopped, e.g. during a restore through the admin API
+static HOLD: RwLock<()> = RwLock::const_new(());
+
+/// Stop Foundry and keep it stopped until the guard is dropped, it is started again then
+pub async fn pause() -> RwLockWriteGuard<'static, ()> {
+    let guard = HOLD.write().await;
+    // The launcher marks Foundry stopped once its process group is gone. Requests only reach
+    // a launcher waiting on the process, so they are repeated until one does.
+    while status::current().running {
+        request_restart();
+        sleep(Duration::from_millis(200)).await;
+    }
+    guard
+}
+
+/// Restart like `request_restart`, after warning connected players in the chat for
+/// `RESTART_WARNING` seconds when `GAME_USER` is set
+pub async fn restart_after_warning() {
+    if let Some(client) = gamesocket::client() {
+        client.warn_restart().await;
+    }
+    request_restart();
+}
+
+/// Run Foundry until the container stops, failing when the restart policy gave up on it.
+/// Answers whether Foundry had to be killed at the end, its world may not be saved then.
 pub async fn launch_foundry_process(
     shutdown_rx: Option<oneshot::Receiver<()>>,
     config: &AppConfig,
-) {
+) -> io::Result<bool> {
     // Convert string args to &str for the launch_foundry function
     let args: Vec<&str> = config.foundry_args.iter().map(|s| s.as_str()).collect();
+    // After the setup UI the budget starts again for the steps below
+    resilience::begin(Phase::Startup);
+
+    // Verify upgraded packages before players can join, a broken combination is reported here
+    if Path::new(&config.foundry_script).exists() {
+        if let Some(url) = &config.release_notes_url {
+            Changelog::refresh(url).await;
+        }
+        LicenseCheck::run(config.foundry_license_key.as_deref());
+        let branding = Branding {
+            source_dir: config.branding_dir.as_ref().map(PathBuf::from),
+            fonts: config.custom_fonts.clone(),
+        };
+        if let Err(e) = branding.apply() {
+            warn!("⚠️ Failed to provision the branding: {:#}", e);
+        }
+        if let Some(dir) = &config.settings_presets_dir {
+            let presets = SettingsPresets {
+                dir: PathBuf::from(dir),
+            };
+            if let Err(e) = presets.apply() {
+                warn!("⚠️ Failed to seed the settings presets: {:#}", e);
+            }
+        }
+        // Before the smoke test, which then covers the packages installed here
+        PackageInstaller::from_config(config).install_all().await;
+        match UpgradeVerifier::verify_installed_packages(config).await {
+            Ok(true) => {}
+            Ok(false) => warn!(
+                "⚠️ Starting Foundry although the failed upgrade could not be rolled back completely"
+            ),
+            Err(e) => warn!("⚠️ Could not verify installed packages: {}", e),
+        }
+    }
+
+    // Exits with a code per problem, a restart loop would only hide it
+    Preflight::enforce(config).await;
+    // Allowed by the preflight checks, the worlds are migrated once they are launched
+    if let Some(migration) = migration::detect()
+        && let Err(e) = migration::back_up_before(config, &migration).await
+    {
+        error!(
+            "❌ Not migrating the worlds, the snapshot before the migration failed: {:#}",
+            e
+        );
+        std::process::exit(Failure::MajorMigration.exit_code());
+    }
+    // The last word before Foundry starts, e.g. to mount a share or render a config
+    if let Err(e) = hooks::run(
+        HookEvent::PreStart,
+        json!({ "world": config.foundry_options.world }),
+    )
+    .await
+    {
+        error!("❌ Not starting Foundry: {:#}", e);
+        std::process::exit(Failure::PreStartHook.exit_code());
+    }
+    resilience::end(Phase::Startup);
 
     // Launch Foundry in the same task, passing the shutdown channel
-    launch_foundry(&args, &config.foundry_script, shutdown_rx).await;
+    let watchdog = StartupWatchdog {
+        port: config.server_port,
+        timeout: config.startup_timeout.map(Duration::from_secs),
+    };
+
+    let responsiveness = config
+        .watchdog_interval
+        .map(|interval| ResponsivenessWatchdog {
+            port: config.server_port,
+            interval: Duration::from_secs(interval),
+            failures: config.watchdog_failures,
+        });
+
+    // The further instances share the installation, they run as long as the main one
+    let instances = InstanceSupervisor::from_config(config).start();
+    let supervisor = RestartSupervisor::new(config.restart_policy, config.max_restarts);
+    let result = launch_foundry(
+        &args,
+        &config.foundry_script,
+        shutdown_rx,
+        watchdog,
+        responsiveness,
+        supervisor,
+        Duration::from_secs(config.shutdown_timeout),
+    )
+    .await;
+    instances.stop().await;
+    result
 }
 
 pub async fn launch_foundry(
     args: &[&str],
     script_path: &str,
     shutdown_rx: Option<oneshot::Receiver<()>>,
-) {
+    watchdog: StartupWatchdog,
+    responsiveness: Option<ResponsivenessWatchdog>,
+    mut supervisor: RestartSupervisor,
+    stop_grace: Duration,
+) -> io::Result<bool> {
     let script_path_owned = script_path.to_string();
 
     // Take ownership of the shutdown_rx outside the loop
     let mut shutdown_rx_option = shutdown_rx;
+    let mut boot_recorded = false;
+    let fingerprint_recorded = Cell::new(false);
+    let mut crash_loop = CrashLoopDetector::default();
+
+    let mut sigterm = signal(SignalKind::terminate()).expect("Failed to register SIGTERM handler");
+    let mut sigint = signal(SignalKind::interrupt()).expect("Failed to register SIGINT handler");
 
     loop {
         // Wait until the script file is present
         if !Path::new(&script_path_owned).exists() {
             warn!("⚠️ Script not found at {}, waiting...", script_path_owned);
-            sleep(Duration::from_secs(10)).await;
+            if stop_signalled(Duration::from_secs(10), &mut sigterm, &mut sigint).await {
+                return Ok(false);
+            }
             continue;
         }
 
+        let held = HOLD.try_read().is_err();
+        // A stop while a restore holds Foundry is left to wait for the restore, not for a start
+        tokio::select! {
+            guard = HOLD.read() => drop(guard),
+            _ = sigterm.recv() => {
+                info!("Received SIGTERM while FoundryVTT is held");
+                return Ok(false);
+            }
+            _ = sigint.recv() => {
+                info!("Received SIGINT while FoundryVTT is held");
+                return Ok(false);
+            }
+        }
+        if held {
+            info!("Foundry was held, starting it again");
+        }
+        // Also on restarts, a core update from the setup screen restarts Foundry
+        reconcile::adopt();
         info!("🚀 Launching FoundryVTT with script: {}", script_path_owned);
-        debug!(
-            "Launch command: npx --yes node {} with args: {:?}",
-            script_path_owned, args
-        );
+        debug!("Launch script: {} with args: {:?}", script_path_owned, args);
 
-        let mut cmd = Command::new("npx");
-        cmd.arg("--yes")
-            .arg("node")
-            .arg(&script_path_owned)
-            .args(args)
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit());
+        let mut cmd = foundry_command(&script_path_owned, args);
+        // Own process group, so diagnostics reach node and not only npx
+        cmd.stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .env("NODE_OPTIONS", diagnostic_node_options())
+            .process_group(0);
 
         debug!("Full command: {:?}", cmd);
 
+        supervisor.started();
         let mut child = match cmd.spawn() {
             Ok(child) => child,
             Err(e) => {
                 error!("❌ Failed to spawn FoundryVTT: {}", e);
-                sleep(Duration::from_secs(5)).await;
-                continue;
+                match restart_delay(&mut supervisor, true)? {
+                    Some(delay) if !stop_signalled(delay, &mut sigterm, &mut sigint).await => {
+                        continue;
+                    }
+                    _ => return Ok(false),
+                }
             }
         };
 
         info!("FoundryVTT process started");
+        logging::forward_foundry(&mut child);
+        status::mark_started(child.id());
+        let pgid = child.id();
 
-        // Handle shutdown signal if provided
-        if let Some(shutdown_rx) = shutdown_rx_option.take() {
-            let child_id = child.id();
-            tokio::select! {
-                exit_status = child.wait() => {
-                    match exit_status {
-                        Ok(exit) => {
-                            warn!("⚠️ FoundryVTT exited with: {}", exit);
-                        }
-                        Err(e) => {
-                            error!("❌ Failed to wait for FoundryVTT: {}", e);
-                        }
-                    }
-                },
-                _ = shutdown_rx => {
-                    info!("Received shutdown signal, terminating FoundryVTT process");
-                    if let Some(pid) = child_id {
-                        info!("Sending SIGTERM to FoundryVTT process (PID: {})", pid);
-                        if let Err(e) = child.kill().await {
-                            error!("Failed to kill FoundryVTT process: {}", e);
-                        }
+        if !boot_recorded {
+            boot_recorded = true;
+            let pid = child.id();
+            tokio::spawn(async move {
+                if let Err(e) = hooks::run(HookEvent::PostStart, json!({ "pid": pid })).await {
+                    warn!("⚠️ {:#}", e);
+                }
+            });
+            notify::send(Notification {
+                severity: Severity::Info,
+                title: "Foundry started".to_string(),
+                message: format!(
+                    "Foundry {} was started.",
+                    installed_foundry_version()
+                        .as_deref()
+                        .unwrap_or("(unknown version)")
+                ),
+            });
+        }
+
+        // Stop on an explicit shutdown request or when the container is being stopped
+        let shutdown_requested = async {
+            match shutdown_rx_option.as_mut() {
+                Some(rx) => {
+                    let _ = rx.await;
+                }
+                None => std::future::pending::<()>().await,
+            }
+        };
+
+        let startup_hang = watchdog.wait_for_hang();
+
+        let stall = async {
+            match &responsiveness {
+                Some(watchdog) => watchdog.wait_for_stall().await,
+                None => std::future::pending::<()>().await,
+            }
+        };
+
+        // Only a boot Foundry finished lets the next one skip the validation, fingerprinted
+        // after the spawn so a freshly installed release is part of the record
+        let fingerprint = (!fingerprint_recorded.get()).then(BootFingerprint::compute);
+        let boot_completed = async {
+            if let Some(fingerprint) = &fingerprint {
+                watchdog.wait_for_startup().await;
+                match fingerprint.record_successful_boot() {
+                    Ok(()) => fingerprint_recorded.set(true),
+                    Err(e) => warn!("Failed to record boot fingerprint: {}", e),
+                }
+            }
+            std::future::pending::<()>().await
+        };
+
+        let failed = tokio::select! {
+            exit_status = child.wait() => {
+                status::mark_stopped();
+                let failed = match exit_status {
+                    Ok(exit) => {
+                        warn!("⚠️ FoundryVTT exited with: {}", exit);
+                        !exit.success()
                     }
-                    // Wait for child process to exit after kill signal
-                    if let Err(e) = child.wait().await {
-                        error!("Error waiting for FoundryVTT to exit: {}", e);
+                    Err(e) => {
+                        error!("❌ Failed to wait for FoundryVTT: {}", e);
+                        true
                     }
-                    info!("FoundryVTT process terminated");
-                    return; // Exit the function, don't restart
+                };
+                // Workers started by modules may outlive node and keep the port bound
+                if let Some(pgid) = pgid {
+                    reaper::terminate_stray(&reaper::descendants(pgid), stop_grace).await;
+                }
+                if failed {
+                    crash_loop.record_exit();
                 }
+                failed
+            },
+            _ = boot_completed => unreachable!("recording the boot never resolves"),
+            _ = shutdown_requested => {
+                info!("Received shutdown signal, terminating FoundryVTT process");
+                let killed = terminate_child(&mut child, stop_grace).await;
+                return Ok(killed); // Exit the function, don't restart
             }
-        } else {
-            // Without shutdown channel, just wait for the process
-            match child.wait().await {
-                Ok(exit) => {
-                    warn!("⚠️ FoundryVTT exited with: {}", exit);
+            _ = startup_hang => {
+                error!("❌ FoundryVTT did not finish starting within the startup timeout");
+                if let Some(pid) = child.id() {
+                    capture_diagnostic_report(pid).await;
                 }
-                Err(e) => {
-                    error!("❌ Failed to wait for FoundryVTT: {}", e);
+                notify::send(Notification {
+                    severity: Severity::Critical,
+                    title: "Foundry startup hung".to_string(),
+                    message: "Foundry did not answer requests within STARTUP_TIMEOUT_SECONDS and is being restarted.".to_string(),
+                });
+                terminate_child(&mut child, stop_grace).await;
+                crash_loop.record_exit();
+                true
+            }
+            _ = stall => {
+                error!("❌ FoundryVTT stopped answering requests although it is running");
+                if let Some(pid) = child.id() {
+                    capture_diagnostic_report(pid).await;
                 }
+                notify::send(Notification {
+                    severity: Severity::Critical,
+                    title: "Foundry stopped responding".to_string(),
+                    message: "Foundry failed WATCHDOG_FAILURES responsiveness checks in a row and is being restarted.".to_string(),
+                });
+                terminate_child(&mut child, stop_grace).await;
+                crash_loop.record_exit();
+                true
+            }
+            _ = RESTART_REQUESTED.notified() => {
+                info!("Restart requested, terminating FoundryVTT process");
+                terminate_child(&mut child, stop_grace).await;
+                continue; // Start again right away
+            }
+            _ = sigterm.recv() => {
+                info!("Received SIGTERM, terminating FoundryVTT process");
+                return Ok(terminate_child(&mut child, stop_grace).await);
             }
+            _ = sigint.recv() => {
+                info!("Received SIGINT, terminating FoundryVTT process");
+                return Ok(terminate_child(&mut child, stop_grace).await);
+            }
+        };
+
+        match restart_delay(&mut supervisor, failed)? {
+            Some(delay) if !stop_signalled(delay, &mut sigterm, &mut sigint).await => {}
+            _ => return Ok(false),
         }
+    }
+}
+
+/// Backoff before Foundry is started again, `None` when the policy stops here
+fn restart_delay(supervisor: &mut RestartSupervisor, failed: bool) -> io::Result<Option<Duration>> {
+    let Some(delay) = supervisor.next_restart(failed) else {
+        if !failed {
+            info!("FoundryVTT exited cleanly, not restarting it");
+            return Ok(None);
+        }
+        error!(
+            "❌ FoundryVTT failed after {} restarts, giving up",
+            supervisor.restarts()
+        );
+        notify::send(Notification {
+            severity: Severity::Critical,
+            title: "Foundry stopped".to_string(),
+            message: format!(
+                "Foundry failed after {} restarts and is not started again, the container exits.",
+                supervisor.restarts()
+            ),
+        });
+        return Err(io::Error::other("FoundryVTT is not restarted anymore"));
+    };
+    info!(
+        "Restarting FoundryVTT in {:?} (restart {})",
+        delay,
+        supervisor.restarts()
+    );
+    if failed {
+        notify::send(Notification {
+            severity: Severity::Warning,
+            title: "Foundry crashed".to_string(),
+            message: format!(
+                "Foundry exited unexpectedly and is started again in {} seconds (restart {}).",
+                delay.as_secs(),
+                supervisor.restarts()
+            ),
+        });
+    }
+    Ok(Some(delay))
+}
+
+/// Wait for `delay` while Foundry is not running, true when the container is stopped meanwhile.
+///
+/// The handlers registered by the launcher replace the default action of the signals, without
+/// this `docker stop` would wait for the kill while a restart is pending.
+async fn stop_signalled(
+    delay: Duration,
+    sigterm: &mut UnixSignal,
+    sigint: &mut UnixSignal,
+) -> bool {
+    tokio::select! {
+        _ = sleep(delay) => false,
+        _ = sigterm.recv() => {
+            info!("Received SIGTERM while FoundryVTT is not running");
+            true
+        }
+        _ = sigint.recv() => {
+            info!("Received SIGINT while FoundryVTT is not running");
+            true
+        }
+    }
+}
+
+/// Number of unexpected exits within `CRASH_LOOP_WINDOW` that count as a crash loop
+const CRASH_LOOP_EXITS: usize = 3;
+const CRASH_LOOP_WINDOW: Duration = Duration::from_secs(600);
+
+/// Notifies operators when Foundry keeps exiting shortly after being started
+#[derive(Default)]
+struct CrashLoopDetector {
+    exits: VecDeque<Instant>,
+}
+
+impl CrashLoopDetector {
+    fn record_exit(&mut self) {
+        let now = Instant::now();
+        self.exits.push_back(now);
+        while self
+            .exits
+            .front()
+            .is_some_and(|exit| now.duration_since(*exit) > CRASH_LOOP_WINDOW)
+        {
+            self.exits.pop_front();
+        }
+
+        if self.exits.len() >= CRASH_LOOP_EXITS {
+            notify::send(Notification {
+                severity: Severity::Critical,
+                title: "Foundry is crash looping".to_string(),
+                message: format!(
+                    "Foundry exited {} times within {} minutes and keeps being restarted, check the container logs.",
+                    self.exits.len(),
+                    CRASH_LOOP_WINDOW.as_secs() / 60
+                ),
+            });
+            // Notify again only after another full set of crashes
+            self.exits.clear();
+        }
+    }
+}
+
+/// Build the command running Foundry's `main.js` with `args`
+///
+/// Distroless Node.js images ship node without npm, node is started directly there.
+pub fn foundry_command(script_path: &str, args: &[&str]) -> Command {
+    let mut cmd = if find_executable("npx").is_some() {
+        let mut cmd = Command::new("npx");
+        cmd.arg("--yes").arg("node");
+        cmd
+    } else {
+        Command::new("node")
+    };
+    cmd.arg(script_path).args(args);
+    // npx keeps its cache in the home directory
+    if let Some(home) = permissions::switched_home() {
+        cmd.env("HOME", home);
+    }
+    cmd
+}
+
+/// Stop the main Foundry process, see `terminate_group`
+async fn terminate_child(child: &mut Child, grace: Duration) -> bool {
+    let killed = terminate_group(child, grace).await;
+    status::mark_stopped();
+    info!("FoundryVTT process terminated");
+    killed
+}
+
+/// Ask the child and everything it started to exit with SIGTERM and wait until all of them are gone.
+///
+/// Foundry closes the world databases on SIGTERM, `grace` is the time it gets for that before
+/// the remaining processes are killed. Answers whether Foundry had to be killed.
+pub async fn terminate_group(child: &mut Child, grace: Duration) -> bool {
+    let mut killed = false;
+    if let Some(pid) = child.id() {
+        // Remember the tree now, descendants lose their parent link once npx exits
+        let mut stray = reaper::descendants(pid);
+
+        info!("Sending SIGTERM to FoundryVTT process group (PID: {})", pid);
+        // The child leads its own process group, so node started by npx receives the signal too
+        if let Err(e) = killpg(Pid::from_raw(pid as i32), Signal::SIGTERM) {
+            error!("Failed to signal FoundryVTT process: {}", e);
+            let _ = child.start_kill();
+        }
+
+        if timeout(grace, child.wait()).await.is_err() {
+            warn!(
+                "FoundryVTT did not exit within {:?}, sending SIGKILL, the world may not be saved",
+                grace
+            );
+            let _ = killpg(Pid::from_raw(pid as i32), Signal::SIGKILL);
+            killed = true;
+        }
+        stray.extend(reaper::descendants(pid));
+        reaper::terminate_stray(&stray, grace).await;
+    }
 
-        // Retry after 5 seconds if the script or process exits (only if we didn't get a shutdown signal)
-        sleep(Duration::from_secs(5)).await;
+    // Wait for child process to exit after the signal
+    if let Err(e) = child.wait().await {
+        error!("Error waiting for FoundryVTT to exit: {}", e);
     }
+    killed
 }
@@ -0,0 +1,155 @@
+use serde::Deserialize;
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+use tracing::{debug, info, warn};
+
+use crate::notify::{self, Notification, Severity};
+use crate::permissions::{self, FileKind};
+use crate::utils::{installed_foundry_version, paths};
+
+/// License Foundry stores in `DATA_DIR/Config/license.json` once a key was entered
+#[derive(Debug, Deserialize)]
+pub struct StoredLicense {
+    #[serde(default)]
+    pub license: String,
+    /// Foundry version that signed the license, a new generation signs it again
+    pub version: Option<String>,
+    pub signature: Option<String>,
+}
+
+/// Checks the stored license before Foundry starts, so problems are reported before players
+/// end up at the license screen
+pub struct LicenseCheck;
+
+impl LicenseCheck {
+    /// Store `key` unless it is stored already, then log and notify about license problems.
+    /// A missing license only means a fresh install.
+    pub fn run(key: Option<&str>) {
+        let path = Self::path();
+        // A typo must not replace a license Foundry already signed
+        let key = key.filter(|key| {
+            let valid = well_formed(key);
+            if !valid {
+                warn!("🔑 FOUNDRY_LICENSE_KEY is malformed, it is not stored");
+            }
+            valid
+        });
+        if let Some(key) = key {
+            match Self::apply(key) {
+                Ok(true) => {
+                    info!("🔑 Stored FOUNDRY_LICENSE_KEY, Foundry signs it when it starts");
+                    return;
+                }
+                Ok(false) => {}
+                Err(e) => warn!(
+                    "Failed to store FOUNDRY_LICENSE_KEY in {}: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(_) => {
+                info!("No Foundry license stored yet, it is entered on the license screen");
+                return;
+            }
+        };
+
+        let problems = match serde_json::from_str::<StoredLicense>(&contents) {
+            Ok(license) => license.problems(installed_foundry_version().as_deref()),
+            Err(e) => vec![format!("{} is not valid JSON: {}", path.display(), e)],
+        };
+        if problems.is_empty() {
+            debug!("Stored Foundry license looks valid");
+            return;
+        }
+
+        for problem in &problems {
+            warn!("🔑 {}", problem);
+        }
+        notify::send(Notification {
+            severity: Severity::Warning,
+            title: "Foundry license needs attention".to_string(),
+            message: format!(
+                "{} Fix it before the next session, otherwise Foundry stops at its license screen.",
+                problems.join(" ")
+            ),
+        });
+    }
+
+    /// Write a license with `key` when the stored one has another key, answering whether it did.
+    /// The signature of the old key does not cover the new one, only the key is written.
+    fn apply(key: &str) -> std::io::Result<bool> {
+        let normalized = |key: &str| -> String {
+            key.chars()
+                .filter(|c| *c != '-' && !c.is_whitespace())
+                .collect::<String>()
+                .to_uppercase()
+        };
+        let path = Self::path();
+        let stored = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<StoredLicense>(&contents).ok());
+        if stored.is_some_and(|stored| normalized(&stored.license) == normalized(key)) {
+            return Ok(false);
+        }
+        if let Some(dir) = path.parent() {
+            permissions::create_dir_all(dir)?;
+        }
+        let license = serde_json::to_string_pretty(&json!({ "license": key.trim() }))?;
+        permissions::write(&path, license, FileKind::Secret)?;
+        Ok(true)
+    }
+
+    pub fn path() -> PathBuf {
+        PathBuf::from(&*paths::DATA_DIR)
+            .join("Config")
+            .join("license.json")
+    }
+}
+
+impl StoredLicense {
+    /// Problems that would send Foundry to the license screen when running `installed`
+    pub fn problems(&self, installed: Option<&str>) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if !well_formed(&self.license) {
+            problems.push("The stored license key is malformed.".to_string());
+        }
+        if self.signature.as_deref().is_none_or(str::is_empty) {
+            problems.push(
+                "The stored license was never signed, Foundry asks for the key again.".to_string(),
+            );
+        }
+
+        // Licenses are signed per generation, a newer one has to sign it again online and
+        // older keys may not cover it
+        if let (Some(signed), Some(installed)) = (self.version.as_deref(), installed)
+            && let (Some(signed_generation), Some(installed_generation)) =
+                (generation(signed), generation(installed))
+            && installed_generation != signed_generation
+        {
+            problems.push(format!(
+                "The license was signed by Foundry {} but {} is installed, the license has to be signed again and may not cover version {}.",
+                signed, installed, installed_generation
+            ));
+        }
+        problems
+    }
+}
+
+/// Keys are shown as six dash separated groups of four letters and digits
+fn well_formed(key: &str) -> bool {
+    let key: String = key
+        .chars()
+        .filter(|c| *c != '-' && !c.is_whitespace())
+        .collect();
+    key.len() == 24 && key.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Major version of a Foundry release, e.g. 12 for `12.331`
+pub fn generation(version: &str) -> Option<u32> {
+    version.split('.').next()?.parse().ok()
+}
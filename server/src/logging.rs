@@ -0,0 +1,288 @@
+use chrono::{SecondsFormat, Utc};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Child;
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::{Event, Subscriber, debug, error, info, warn};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::configfile;
+use crate::logs;
+use crate::permissions::{self, FileKind};
+use crate::utils::paths;
+
+/// Target of the lines Foundry prints, they are in its own log files already
+pub const FOUNDRY_TARGET: &str = "foundry";
+/// Rotated files kept next to the log, `wrapper.1.log` being the newest
+const KEEP: usize = 3;
+
+/// The log file of the wrapper, `None` until [`enable_file`]
+static FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+
+/// Set up logging to stdout, or to stderr for management commands so what they print can be
+/// piped. Called once the settings file is read.
+///
+/// `LOG_FORMAT=json` prints one JSON object per line for log drivers that parse them, the
+/// default `pretty` the human readable lines. `LOG_LEVEL` is the level logged when `RUST_LOG`
+/// does not say otherwise.
+pub fn init(stderr: bool) {
+    let format = configfile::var("LOG_FORMAT").map(|v| v.to_lowercase());
+    let json = format.as_deref() == Ok("json");
+    let level = configfile::var("LOG_LEVEL").ok();
+    let default_level = level
+        .as_deref()
+        .and_then(|level| LevelFilter::from_str(level).ok())
+        .unwrap_or(LevelFilter::INFO);
+    let filter = EnvFilter::builder()
+        .with_default_directive(default_level.into())
+        .from_env_lossy();
+
+    // Foundry's own lines are in its log files, the file only gets what the wrapper adds
+    let file = JsonLines { sink: Sink::File }
+        .with_filter(filter_fn(|metadata| metadata.target() != FOUNDRY_TARGET));
+    let registry = tracing_subscriber::registry().with(filter).with(file);
+    if json {
+        registry
+            .with(JsonLines {
+                sink: Sink::Console { stderr },
+            })
+            .init();
+    } else {
+        let writer = match stderr {
+            true => BoxMakeWriter::new(io::stderr),
+            false => BoxMakeWriter::new(io::stdout),
+        };
+        registry
+            .with(tracing_subscriber::fmt::layer().with_writer(writer))
+            .init();
+    }
+
+    if let Ok(format) = format.as_deref()
+        && !matches!(format, "json" | "pretty")
+    {
+        warn!("Ignoring LOG_FORMAT={}, expected json or pretty", format);
+    }
+    if let Some(level) = level.filter(|level| LevelFilter::from_str(level).is_err()) {
+        warn!(
+            "Ignoring LOG_LEVEL={}, expected error, warn, info, debug or trace",
+            level
+        );
+    }
+}
+
+/// Also append the wrapper's log to `DATA_DIR/Logs/wrapper.log` as JSON lines, rotated once
+/// it grows beyond `max_mb`. Only the wrapper writes it, management commands do not.
+pub fn enable_file(max_mb: u64) {
+    let path = PathBuf::from(&*paths::DATA_DIR)
+        .join("Logs")
+        .join("wrapper.log");
+    *FILE.lock().unwrap_or_else(PoisonError::into_inner) = Some(LogFile {
+        path,
+        max_bytes: max_mb.max(1) * 1024 * 1024,
+        file: None,
+        written: 0,
+    });
+}
+
+/// Log what Foundry prints through the wrapper's log, at the level of each line
+pub fn forward_foundry(child: &mut Child) {
+    if let Some(stdout) = child.stdout.take() {
+        tokio::spawn(forward_lines("stdout", stdout));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        tokio::spawn(forward_lines("stderr", stderr));
+    }
+}
+
+/// Foundry prints `FoundryVTT | <time> | [<level>] <message>`. Lines without a level, like
+/// stack traces, keep the level of the line before them.
+async fn forward_lines<R: AsyncRead + Unpin>(stream: &'static str, output: R) {
+    let mut lines = BufReader::new(output).lines();
+    let mut level = match stream {
+        "stderr" => "error".to_string(),
+        _ => "info".to_string(),
+    };
+    while let Ok(Some(line)) = lines.next_line().await {
+        let line = strip_ansi(&line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let message = match logs::parse_line(&line) {
+            Some((_, parsed, message)) => {
+                level = parsed;
+                message
+            }
+            None => line,
+        };
+        match level.as_str() {
+            "error" => error!(target: FOUNDRY_TARGET, stream, "{}", message),
+            "warn" | "warning" => warn!(target: FOUNDRY_TARGET, stream, "{}", message),
+            "debug" | "verbose" | "silly" => debug!(target: FOUNDRY_TARGET, stream, "{}", message),
+            _ => info!(target: FOUNDRY_TARGET, stream, "{}", message),
+        }
+    }
+}
+
+/// Remove the color codes Foundry's logger adds
+fn strip_ansi(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // `ESC [ ... <letter>`
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+        stripped.push(c);
+    }
+    stripped
+}
+
+enum Sink {
+    Console { stderr: bool },
+    File,
+}
+
+/// Formats events as `{"timestamp", "level", "target", "message", ...fields}` lines
+struct JsonLines {
+    sink: Sink,
+}
+
+impl<S: Subscriber> Layer<S> for JsonLines {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut file = match self.sink {
+            Sink::File => {
+                let file = FILE.lock().unwrap_or_else(PoisonError::into_inner);
+                if file.is_none() {
+                    return;
+                }
+                Some(file)
+            }
+            Sink::Console { .. } => None,
+        };
+        let metadata = event.metadata();
+        let mut line = Map::new();
+        line.insert(
+            "timestamp".to_string(),
+            Utc::now()
+                .to_rfc3339_opts(SecondsFormat::Millis, true)
+                .into(),
+        );
+        line.insert(
+            "level".to_string(),
+            metadata.level().as_str().to_lowercase().into(),
+        );
+        line.insert("target".to_string(), metadata.target().into());
+        event.record(&mut JsonFields(&mut line));
+        let mut line = Value::Object(line).to_string();
+        line.push('\n');
+
+        match file.as_mut().and_then(|file| file.as_mut()) {
+            // Logging about a failure to log would only fail again
+            Some(file) => {
+                let _ = file.append(&line);
+            }
+            None if matches!(self.sink, Sink::Console { stderr: true }) => {
+                let _ = io::stderr().lock().write_all(line.as_bytes());
+            }
+            None => {
+                let _ = io::stdout().lock().write_all(line.as_bytes());
+            }
+        }
+    }
+}
+
+struct JsonFields<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonFields<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value).into());
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    max_bytes: u64,
+    /// Opened with the first line, after the wrapper switched to `PUID`
+    file: Option<File>,
+    written: u64,
+}
+
+impl LogFile {
+    fn append(&mut self, line: &str) -> io::Result<()> {
+        if self.file.is_some() && self.written + line.len() as u64 > self.max_bytes {
+            self.file = None;
+            rotate(&self.path)?;
+        }
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let file = open(&self.path)?;
+                self.written = file.metadata()?.len();
+                self.file.insert(file)
+            }
+        };
+        file.write_all(line.as_bytes())?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    if let Some(dir) = path.parent() {
+        permissions::create_dir_all(dir)?;
+    }
+    if !path.exists() {
+        permissions::create(path, FileKind::Regular)?;
+    }
+    OpenOptions::new().append(true).open(path)
+}
+
+/// Shift `wrapper.<n>.log` to `<n + 1>`, dropping the oldest. The rotated files keep the
+/// `.log` extension, so log queries include them like Foundry's own rotated logs.
+fn rotate(path: &Path) -> io::Result<()> {
+    let rotated = |n: usize| path.with_extension(format!("{}.log", n));
+    let _ = fs::remove_file(rotated(KEEP));
+    for n in (1..KEEP).rev() {
+        if rotated(n).exists() {
+            fs::rename(rotated(n), rotated(n + 1))?;
+        }
+    }
+    fs::rename(path, rotated(1))
+}
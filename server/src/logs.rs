@@ -0,0 +1,281 @@
+use actix_web::{HttpRequest, HttpResponse, Responder, http::header, web};
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::api::{self, ApiConfig, Message};
+use crate::utils::paths;
+
+/// Queries answered per minute, a dashboard polling every few seconds must not keep the disk busy
+const QUERIES_PER_MINUTE: usize = 30;
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+static RECENT_QUERIES: Mutex<VecDeque<Instant>> = Mutex::new(VecDeque::new());
+
+/// Winston levels Foundry logs with, most severe first
+const LEVELS: [&str; 7] = ["error", "warn", "info", "http", "verbose", "debug", "silly"];
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LogQuery {
+    /// RFC 3339 time or a duration back from now like `30m`, `24h` or `7d`
+    since: Option<String>,
+    /// Entries of this level and more severe ones, `error`, `warn`, `info`, `http`,
+    /// `verbose`, `debug` or `silly`
+    level: Option<String>,
+    #[param(minimum = 1, maximum = 1000, default = 100)]
+    limit: Option<usize>,
+    #[param(default = 0)]
+    offset: Option<usize>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LogEntry {
+    #[schema(format = DateTime)]
+    time: Option<String>,
+    level: String,
+    /// Log file below `DATA_DIR/Logs` the entry was read from
+    file: String,
+    message: String,
+    #[serde(skip)]
+    at: Option<DateTime<Utc>>,
+}
+
+/// One page of log entries
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LogPage {
+    entries: Vec<LogEntry>,
+    /// Entries matching the query on all pages
+    total: usize,
+    /// Offset of the next page, missing on the last one
+    next_offset: Option<usize>,
+}
+
+/// Entries of Foundry's log files in `DATA_DIR/Logs`, rotated ones included, oldest first
+///
+/// At most 30 queries per minute are answered.
+#[utoipa::path(
+    get,
+    path = "/api/logs",
+    operation_id = "logs",
+    security(("token" = [])),
+    params(LogQuery),
+    responses(
+        (status = 200, description = "One page of entries", body = LogPage),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 400, description = "Invalid since or level", body = String, content_type = "text/plain"),
+        (status = 429, description = "Too many queries, retry after a minute", body = String, content_type = "text/plain"),
+    )
+)]
+pub async fn query(
+    req: HttpRequest,
+    config: web::Data<ApiConfig>,
+    query: web::Query<LogQuery>,
+) -> impl Responder {
+    if let Some(rejected) = api::reject(&req, &config) {
+        return rejected;
+    }
+    if !allow_query() {
+        return HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, "60"))
+            .body(format!(
+                "At most {} log queries per minute",
+                QUERIES_PER_MINUTE
+            ));
+    }
+    let query = query.into_inner();
+    let since = match query.since.as_deref().map(parse_since) {
+        Some(None) => {
+            return HttpResponse::BadRequest()
+                .body("since must be an RFC 3339 time or a duration like 24h");
+        }
+        Some(since) => since,
+        None => None,
+    };
+    let max_level = match query.level.as_deref().map(level_rank) {
+        Some(None) => {
+            return HttpResponse::BadRequest()
+                .body(format!("level must be one of {}", LEVELS.join(", ")));
+        }
+        Some(rank) => rank,
+        None => None,
+    };
+
+    let entries = match web::block(move || read_entries(&logs_dir())).await {
+        Ok(Ok(entries)) => entries,
+        Ok(Err(e)) => return HttpResponse::InternalServerError().body(format!("{:#}", e)),
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let matching: Vec<LogEntry> = entries
+        .into_iter()
+        .filter(|entry| since.is_none_or(|since| entry.at.is_some_and(|at| at >= since)))
+        .filter(|entry| {
+            max_level.is_none_or(|max| level_rank(&entry.level).is_some_and(|rank| rank <= max))
+        })
+        .collect();
+
+    let total = matching.len();
+    let offset = query.offset.unwrap_or(0);
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let entries: Vec<LogEntry> = matching.into_iter().skip(offset).take(limit).collect();
+    let next_offset = (offset + entries.len() < total).then_some(offset + entries.len());
+    HttpResponse::Ok().json(LogPage {
+        entries,
+        total,
+        next_offset,
+    })
+}
+
+fn allow_query() -> bool {
+    let mut recent = RECENT_QUERIES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let now = Instant::now();
+    while recent
+        .front()
+        .is_some_and(|at| now.duration_since(*at) > Duration::from_secs(60))
+    {
+        recent.pop_front();
+    }
+    if recent.len() >= QUERIES_PER_MINUTE {
+        return false;
+    }
+    recent.push_back(now);
+    true
+}
+
+/// An RFC 3339 time, or a duration back from now like `30m`, `24h` or `7d`
+pub fn parse_since(since: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(since) {
+        return Some(time.with_timezone(&Utc));
+    }
+    let unit = since.chars().last()?;
+    let amount: i64 = since[..since.len() - unit.len_utf8()].parse().ok()?;
+    let seconds = match unit {
+        'm' => 60,
+        'h' => 3600,
+        'd' => 24 * 3600,
+        _ => return None,
+    };
+    Some(Utc::now() - chrono::Duration::seconds(amount.checked_mul(seconds)?))
+}
+
+fn level_rank(level: &str) -> Option<usize> {
+    LEVELS
+        .iter()
+        .position(|known| known.eq_ignore_ascii_case(level.trim()))
+}
+
+/// The newest `lines` entries of Foundry's log files, oldest first
+pub fn tail(lines: usize) -> Result<Vec<LogEntry>> {
+    let mut entries = read_entries(&logs_dir())?;
+    let newest = entries.len().saturating_sub(lines);
+    Ok(entries.split_off(newest))
+}
+
+/// Messages of the error entries logged since `since`, with their stack traces
+pub fn errors_since(since: DateTime<Utc>) -> Result<Vec<String>> {
+    Ok(read_entries(&logs_dir())?
+        .into_iter()
+        .filter(|entry| entry.level == "error" && entry.at.is_some_and(|at| at >= since))
+        .map(|entry| entry.message)
+        .collect())
+}
+
+fn logs_dir() -> PathBuf {
+    PathBuf::from(&*paths::DATA_DIR).join("Logs")
+}
+
+/// Entries of every log file, rotated ones included, sorted by time
+fn read_entries(logs_dir: &Path) -> Result<Vec<LogEntry>> {
+    let mut entries = Vec::new();
+    if !logs_dir.is_dir() {
+        return Ok(entries);
+    }
+    let mut files: Vec<PathBuf> = fs::read_dir(logs_dir)
+        .with_context(|| format!("Failed to list {}", logs_dir.display()))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "log"))
+        .collect();
+    files.sort();
+    for path in files {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let contents = fs::read(&path).with_context(|| format!("Failed to read {}", name))?;
+        parse_log(&String::from_utf8_lossy(&contents), &name, &mut entries);
+    }
+    // Stable, entries of the same second keep the order they were written in
+    entries.sort_by_key(|entry| entry.at);
+    Ok(entries)
+}
+
+/// Parse JSON lines as Foundry writes them, and plain `time | [level] message` lines. Lines
+/// without a level, like stack traces, belong to the entry before them.
+fn parse_log(contents: &str, file: &str, entries: &mut Vec<LogEntry>) {
+    let first = entries.len();
+    for line in contents.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_line(line) {
+            Some((at, level, message)) => entries.push(LogEntry {
+                time: at.map(|at| at.to_rfc3339()),
+                level,
+                file: file.to_string(),
+                message,
+                at,
+            }),
+            None if entries.len() > first => {
+                if let Some(last) = entries.last_mut() {
+                    last.message.push('\n');
+                    last.message.push_str(line);
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+/// Time, level and message of a line of Foundry's logs, `None` for lines without a level
+pub fn parse_line(line: &str) -> Option<(Option<DateTime<Utc>>, String, String)> {
+    if let Ok(serde_json::Value::Object(object)) = serde_json::from_str(line) {
+        let level = object.get("level")?.as_str()?.to_ascii_lowercase();
+        let message = match object.get("message") {
+            Some(serde_json::Value::String(message)) => message.clone(),
+            Some(other) => other.to_string(),
+            None => String::new(),
+        };
+        let at = object
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .and_then(parse_time);
+        return Some((at, level, message));
+    }
+
+    let start = line.find('[')?;
+    let end = start + line[start..].find(']')?;
+    let level = line[start + 1..end].trim().to_ascii_lowercase();
+    level_rank(&level)?;
+    let at = parse_time(line[..start].trim().trim_end_matches('|').trim());
+    Some((at, level, line[end + 1..].trim().to_string()))
+}
+
+fn parse_time(time: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(time) {
+        return Some(time.with_timezone(&Utc));
+    }
+    NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(|time| time.and_utc())
+}
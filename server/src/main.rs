@@ -1,55 +1,362 @@
+mod acme;
+mod admin;
+#[cfg(feature = "full")]
+mod alerts;
+mod api;
+mod archive;
+mod assetgc;
+mod assets;
+mod autoreturn;
+mod backup;
+mod bisect;
+mod branding;
+mod cache;
+mod capabilities;
+mod changelog;
+mod cli;
+mod clock;
+mod command;
+mod compat;
+mod completions;
 mod config;
+mod configfile;
+mod db;
+#[cfg(feature = "full")]
+mod discord;
+mod diskspace;
+mod doctor;
 mod downloader;
+mod dump;
+#[cfg(feature = "full")]
+mod email;
+mod environment;
+mod eventlog;
 mod events;
+mod export;
 mod extractor;
+mod fingerprint;
+mod gamesocket;
+mod gate;
 mod handlers;
+mod headers;
+mod health;
+#[cfg(feature = "full")]
+mod heartbeat;
+mod hooks;
+mod hostfs;
+mod hostmigrate;
+mod i18n;
+mod idle;
+mod inhibit;
 mod initialization;
+mod inspector;
+mod install;
+mod instances;
+mod integrity;
+mod jobs;
+mod jobstore;
+mod join;
+mod journal;
 mod launch;
+mod license;
+mod logging;
+mod logs;
+mod memory;
+#[cfg(feature = "full")]
+mod metrics;
+mod migrate;
+mod migration;
+mod moduleguard;
+#[cfg(feature = "full")]
+mod mqtt;
+mod notify;
+mod objectstore;
+mod options;
+mod packages;
+mod permissions;
+mod preflight;
+#[cfg(feature = "full")]
+mod preload;
+mod presets;
+mod proxy;
+mod qr;
+mod reaper;
+mod reconcile;
+mod recording;
+mod release;
+#[cfg(feature = "full")]
+mod replicate;
+mod report;
+mod resilience;
+mod restore;
+mod restoretest;
+mod rewrite;
+mod runtime;
+mod scan;
+mod schedule;
+mod secrets;
+mod selftest;
 mod server;
+mod settings;
+mod shutdown;
+mod smoke;
+mod socketactivation;
+mod standby;
+mod state;
+mod statesync;
+mod stateversion;
+mod status;
+mod storage;
+mod supervisor;
+mod tasks;
+mod tempfiles;
+mod tls;
+mod transfer;
+mod troubleshoot;
+mod updater;
+mod upgrade;
+mod uptime;
 mod utils;
+mod watchdog;
+#[cfg(feature = "full")]
+mod webhooknotify;
+#[cfg(feature = "full")]
+mod webhooks;
+mod worlds;
 
+use crate::resilience::Phase;
 use crate::utils::paths;
+use clap::Parser;
+use serde_json::json;
+use std::time::Duration;
 use tokio::sync::oneshot;
-use tracing::{Level, error, info};
+use tracing::{debug, error, info, warn};
+
+/// Build profile of the binary, `minimal` leaves the integrations with other services out
+const PROFILE: &str = if cfg!(feature = "full") {
+    "full"
+} else {
+    "minimal"
+};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    // Initialize tracing with a more verbose default level
-    tracing_subscriber::fmt()
-        .with_max_level(Level::DEBUG)
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .with_writer(std::io::stdout)
-        .init();
+    let cli = cli::Cli::parse();
+
+    // Everything below reads its configuration through the settings, the log format too
+    let settings = configfile::init();
+    logging::init(cli.command.is_some());
+    info!(
+        "foundry-watcher {} ({} profile)",
+        env!("CARGO_PKG_VERSION"),
+        PROFILE
+    );
 
-    info!("Logging initialized at DEBUG level");
+    match settings {
+        Ok(settings) => {
+            if let Some(file) = &settings.file {
+                info!("⚙️ Reading settings from {}", file.display());
+                for name in settings.overridden() {
+                    debug!(
+                        "{} of {} is overridden by the environment",
+                        name,
+                        file.display()
+                    );
+                }
+            }
+            compat::warn_deprecated(settings);
+        }
+        Err(e) => {
+            error!("❌ {:#}", e);
+            return Err(std::io::Error::other(format!("{:#}", e)));
+        }
+    }
+
+    // Before any command runs, every file created afterwards follows the policy
+    permissions::init();
+    // Started as root with PUID, the volumes are handed over before anything writes to them
+    if let Err(e) = permissions::switch_user(cli.command.is_none()) {
+        error!("❌ Failed to switch to PUID and PGID: {}", e);
+        return Err(e);
+    }
+    i18n::init();
+
+    // Panics in background tasks are recovered from, make sure they still show up in the logs
+    std::panic::set_hook(Box::new(|panic| error!("💥 {}", panic)));
+
+    // Management subcommands run to completion without starting anything
+    if let Some(command) = cli.command {
+        return cli::run(command, cli.output).await.map_err(|e| {
+            error!("Command failed: {:#}", e);
+            std::io::Error::other(format!("{:#}", e))
+        });
+    }
 
     // Load application configuration
-    let app_config = config::AppConfig::from_env();
+    let mut app_config = config::AppConfig::from_env();
+    if app_config.log_file {
+        logging::enable_file(app_config.log_file_max_mb);
+    }
+    // Sockets bound by systemd or podman enable the listeners they are meant for
+    socketactivation::init(&mut app_config);
+
+    // Stateless mode restores the data directory before anything reads it
+    let state_sync = statesync::StateSync::from_config(&app_config);
+    if let Some(sync) = &state_sync {
+        warn!("🧪 Stateless mode is experimental, keep separate backups of your worlds");
+        let hydrating = sync.clone();
+        let hydrated = tokio::task::spawn_blocking(move || hydrating.hydrate())
+            .await
+            .map_err(|e| anyhow::anyhow!("{}", e))
+            .and_then(|result| result);
+        // Starting empty would delete everything in the bucket with the next sync
+        if let Err(e) = hydrated {
+            error!(
+                "❌ Failed to restore the data directory from the bucket: {:#}",
+                e
+            );
+            return Err(std::io::Error::other(e.to_string()));
+        }
+        match &app_config.standby {
+            // Everything else starts once this instance took over
+            Some(config) => {
+                let standby = standby::Standby {
+                    config: config.clone(),
+                    sync: sync.clone(),
+                };
+                standby.wait_for_promotion().await;
+            }
+            None => {
+                let remote = sync.remote.clone();
+                match tokio::task::spawn_blocking(move || standby::claim_on_start(&remote)).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => warn!("⚠️ Failed to check the fence of the bucket: {:#}", e),
+                    Err(e) => warn!("⚠️ Checking the fence of the bucket panicked: {}", e),
+                }
+            }
+        }
+    }
+
+    // Before anything touches the wrapper's files, a newer wrapper may have changed them
+    if let Err(e) = stateversion::check(app_config.allow_state_downgrade) {
+        error!("❌ {:#}", e);
+        return Err(std::io::Error::other(e.to_string()));
+    }
+
+    // An update or restore the last run was killed in leaves halves of two installations
+    if let Err(e) = journal::recover() {
+        error!("❌ {:#}", e);
+        return Err(std::io::Error::other(e.to_string()));
+    }
+
+    // Restoring the data directory above may take as long as it needs, what follows not
+    resilience::init(&app_config);
+    resilience::begin(Phase::Startup);
 
     // Run initialization checks and setup from the old run.sh
     if let Err(e) = initialization::initialize(&app_config) {
         error!("Initialization failed: {}", e);
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            e.to_string(),
-        ));
+        return Err(std::io::Error::other(e.to_string()));
+    }
+
+    // Self-contained builds must not depend on anything but the binary
+    if let Err(e) = assets::verify()
+        && cfg!(feature = "self-contained")
+    {
+        return Err(std::io::Error::other(e.to_string()));
+    }
+
+    // Before the background jobs are set up and Foundry gets its node options
+    memory::check(&mut app_config);
+
+    // Before anything writes temporary files again
+    tempfiles::sweep_at_startup(Duration::from_secs(app_config.temp_file_max_age * 3600));
+
+    capabilities::init();
+    #[cfg(feature = "full")]
+    metrics::init();
+    eventlog::init(app_config.event_log);
+    eventlog::record(
+        "wrapper-started",
+        json!({ "version": env!("CARGO_PKG_VERSION"), "profile": PROFILE }),
+    );
+    notify::init(&app_config);
+    gamesocket::init(&app_config);
+    jobs::init(&app_config);
+    reaper::init();
+
+    if let Some(port) = app_config.admin_api_port {
+        admin::start_admin_server(&app_config, port).await?;
+    }
+    if let Some(port) = app_config.healthcheck_port {
+        health::start_health_server(&app_config, port).await?;
+    }
+
+    tasks::start_subsystems(&app_config);
+
+    if cache::offline() {
+        info!("📴 OFFLINE is set, releases and packages are only installed from CACHE_DIR");
+    }
+
+    // A local release archive replaces the web installer, and upgrades when it changes
+    if let Some(path) = &app_config.foundry_release_path
+        && let Err(e) = install::InstallService::install_release(path, &app_config.target_dir).await
+    {
+        error!("❌ Failed to install {}: {:#}", path, e);
+        return Err(std::io::Error::other(e.to_string()));
+    }
+
+    // Unattended first boot, the setup UI stays the fallback when the link expired
+    if let Some(download) = &app_config.foundry_download
+        && !paths::resolve_foundry_script_path().exists()
+        && let Err(e) = download.install(&app_config.target_dir).await
+    {
+        error!("❌ Failed to download Foundry: {:#}", e);
+    }
+
+    // Before Foundry starts, an update never interrupts a session
+    if app_config.auto_update && !cache::offline() && paths::resolve_foundry_script_path().exists()
+    {
+        match updater::Updater::from_config(&app_config).update().await {
+            // The release is now reached through the `current` link
+            Ok(Some(_)) => {
+                app_config.foundry_script = paths::resolve_foundry_script_path()
+                    .to_string_lossy()
+                    .to_string();
+            }
+            Ok(None) => {}
+            Err(e) => warn!("⚠️ Update failed, starting the installed release: {:#}", e),
+        }
     }
 
     // Check if we should directly launch Foundry
-    if paths::FOUNDRY_SCRIPT_PATH.exists() {
+    if paths::resolve_foundry_script_path().exists() {
         info!("Foundry main.js detected, skipping Actix server and launching Foundry directly");
-        launch::launch_foundry_process(None, &app_config).await;
-        return Ok(());
+        let result = launch::launch_foundry_process(None, &app_config).await;
+        inhibit::wait(Duration::from_secs(app_config.shutdown_inhibit_timeout)).await;
+        resilience::begin(Phase::Shutdown);
+        eventlog::record("wrapper-stopping", json!({}));
+        shutdown::flush_and_mark_clean(&app_config.scan_excludes, matches!(result, Ok(false)));
+        if let Some(sync) = &state_sync {
+            sync.push_in_background().await;
+        }
+        return result.map(|_| ());
     }
 
     // Log configuration settings
-    info!("Serving static files from: {}", app_config.static_files_dir);
+    match &app_config.static_files_dir {
+        Some(dir) => info!("Serving static files from: {}", dir),
+        None => info!("Serving the embedded setup UI"),
+    }
     info!("Downloading files to: {}", app_config.target_dir);
 
     // Create a channel for shutting down Foundry when needed
     let (_foundry_tx, foundry_rx) = oneshot::channel::<()>();
 
     // Start the HTTP server
+    status::mark_installing();
+    // Waiting for the user in the setup UI is not part of the startup
+    resilience::end(Phase::Startup);
     let server_handle = server::start_server(&app_config).await?;
 
     // Wait for the server to complete (after receiving shutdown signal)
@@ -58,7 +365,14 @@ async fn main() -> std::io::Result<()> {
     info!("Actix server has terminated, launching Foundry VTT");
 
     // After server stops, launch Foundry directly with the shutdown channel
-    launch::launch_foundry_process(Some(foundry_rx), &app_config).await;
+    let result = launch::launch_foundry_process(Some(foundry_rx), &app_config).await;
+    inhibit::wait(Duration::from_secs(app_config.shutdown_inhibit_timeout)).await;
+    resilience::begin(Phase::Shutdown);
+    eventlog::record("wrapper-stopping", json!({}));
+    shutdown::flush_and_mark_clean(&app_config.scan_excludes, matches!(result, Ok(false)));
+    if let Some(sync) = &state_sync {
+        sync.push_in_background().await;
+    }
 
-    Ok(())
+    result.map(|_| ())
 }
@@ -0,0 +1,156 @@
+use std::fs;
+use std::sync::OnceLock;
+use tracing::{debug, info, warn};
+
+use crate::config::AppConfig;
+use crate::configfile;
+
+/// Memory of the wrapper, the proxy and the node runtime itself before Foundry loads a world
+const CONTAINER_OVERHEAD_MB: u64 = 150;
+/// Below this Foundry gets killed loading a large scene, the well-known Raspberry Pi failure
+const PRACTICAL_MINIMUM_MB: u64 = 1024;
+/// Share of the memory left for Foundry that its JavaScript heap may take, the rest are
+/// buffers, textures being processed and native allocations of node
+const HEAP_SHARE_PERCENT: u64 = 75;
+const MIN_HEAP_MB: u64 = 256;
+
+/// Heap limit for Foundry's node process chosen by the guardrails
+static HEAP_LIMIT_MB: OnceLock<u64> = OnceLock::new();
+
+/// Memory available to the container
+#[derive(Debug, Clone)]
+pub struct MemoryInfo {
+    /// The smaller one of the host memory and the cgroup limit
+    pub total_mb: u64,
+    /// Whether `total_mb` is the limit of the container rather than the host memory
+    pub limited: bool,
+    /// Swap on disk, without zram
+    pub swap_mb: u64,
+    /// Swap on compressed RAM
+    pub zram_mb: u64,
+}
+
+impl MemoryInfo {
+    pub fn detect() -> Option<Self> {
+        let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+        let field = |name: &str| {
+            meminfo
+                .lines()
+                .find_map(|line| line.strip_prefix(name))
+                .and_then(|kb| kb.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+                .map(|kb| kb / 1024)
+        };
+        let host_mb = field("MemTotal:")?;
+        let limit_mb = cgroup_limit_mb();
+        let zram_mb = zram_swap_mb();
+        Some(Self {
+            total_mb: limit_mb.map_or(host_mb, |limit| limit.min(host_mb)),
+            limited: limit_mb.is_some_and(|limit| limit < host_mb),
+            swap_mb: field("SwapTotal:").unwrap_or(0).saturating_sub(zram_mb),
+            zram_mb,
+        })
+    }
+
+    /// What is left for Foundry once the container runs
+    pub fn available_mb(&self) -> u64 {
+        self.total_mb.saturating_sub(CONTAINER_OVERHEAD_MB)
+    }
+
+    pub fn low(&self) -> bool {
+        self.available_mb() < PRACTICAL_MINIMUM_MB
+    }
+
+    fn heap_mb(&self) -> u64 {
+        (self.available_mb() * HEAP_SHARE_PERCENT / 100).max(MIN_HEAP_MB)
+    }
+}
+
+/// Warn on hosts with too little memory for Foundry, and with `MEMORY_GUARDRAILS` cap the
+/// heap of node and keep background jobs from running next to a session.
+///
+/// Node sizes its heap after the host memory, not after the limit of the container, and
+/// backups and exports compete with Foundry loading a map. Either gets Foundry killed by the
+/// OOM killer where a smaller heap only makes the garbage collector run more often.
+pub fn check(config: &mut AppConfig) {
+    let Some(memory) = MemoryInfo::detect() else {
+        debug!("Could not read the available memory");
+        return;
+    };
+    debug!("Memory: {:?}", memory);
+    if !memory.low() {
+        return;
+    }
+
+    let source = match memory.limited {
+        true => "container limit",
+        false => "host memory",
+    };
+    warn!(
+        "⚠️ Only {} MB of {} MB ({}) are left for Foundry, which needs about {} MB to load large scenes",
+        memory.available_mb(),
+        memory.total_mb,
+        source,
+        PRACTICAL_MINIMUM_MB
+    );
+    match (memory.swap_mb, memory.zram_mb) {
+        (0, 0) => warn!(
+            "⚠️ The host has no swap, add a swap file or enable zram, e.g. with zram-tools on Raspberry Pi OS"
+        ),
+        (swap, zram) => info!("The host has {} MB of swap and {} MB of zram", swap, zram),
+    }
+    if !config.memory_guardrails {
+        info!("Set MEMORY_GUARDRAILS=true to limit Foundry's heap and background jobs");
+        return;
+    }
+
+    if node_options_set_heap() {
+        info!("NODE_OPTIONS sets --max-old-space-size already, keeping it");
+    } else {
+        let heap_mb = memory.heap_mb();
+        let _ = HEAP_LIMIT_MB.set(heap_mb);
+        info!("🛡️ Limiting the heap of Foundry to {} MB", heap_mb);
+    }
+    let jobs = &mut config.background_jobs;
+    if jobs.concurrency > 1 || !jobs.pause_while_playing {
+        jobs.concurrency = 1;
+        jobs.pause_while_playing = true;
+        info!("🛡️ Background jobs run one at a time and only while nobody plays");
+    }
+}
+
+/// The `--max-old-space-size` the guardrails chose for Foundry
+pub fn heap_option() -> Option<String> {
+    HEAP_LIMIT_MB
+        .get()
+        .map(|mb| format!("--max-old-space-size={}", mb))
+}
+
+fn node_options_set_heap() -> bool {
+    configfile::var("NODE_OPTIONS").is_ok_and(|options| options.contains("--max-old-space-size"))
+}
+
+/// Limit of the cgroup v2 or v1 the container runs in, `None` when it has none
+fn cgroup_limit_mb() -> Option<u64> {
+    [
+        "/sys/fs/cgroup/memory.max",
+        "/sys/fs/cgroup/memory/memory.limit_in_bytes",
+    ]
+    .iter()
+    .find_map(|path| fs::read_to_string(path).ok())
+    .and_then(|limit| limit.trim().parse::<u64>().ok())
+    .map(|bytes| bytes / (1024 * 1024))
+}
+
+/// Size of the swap devices on zram
+fn zram_swap_mb() -> u64 {
+    let Ok(swaps) = fs::read_to_string("/proc/swaps") else {
+        return 0;
+    };
+    swaps
+        .lines()
+        .skip(1)
+        .filter(|line| line.starts_with("/dev/zram"))
+        .filter_map(|line| line.split_whitespace().nth(2)?.parse::<u64>().ok())
+        .sum::<u64>()
+        / 1024
+}
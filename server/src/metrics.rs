@@ -0,0 +1,234 @@
+use actix_web::{HttpResponse, Responder, web};
+use chrono::DateTime;
+use std::fmt::{Display, Write};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, UNIX_EPOCH};
+
+use crate::backup;
+use crate::diskspace;
+use crate::status;
+use crate::updater;
+use crate::utils::paths;
+
+static STARTED: OnceLock<Instant> = OnceLock::new();
+static BACKUPS_SUCCEEDED: AtomicU64 = AtomicU64::new(0);
+static BACKUPS_FAILED: AtomicU64 = AtomicU64::new(0);
+
+/// Start counting the uptime of the wrapper, called once at startup
+pub fn init() {
+    STARTED.get_or_init(Instant::now);
+}
+
+/// Count a finished snapshot, by the wrapper or through it
+pub fn backup_finished(succeeded: bool) {
+    let counter = match succeeded {
+        true => &BACKUPS_SUCCEEDED,
+        false => &BACKUPS_FAILED,
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The wrapper and the Foundry process in the Prometheus text format, for scraping
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    operation_id = "metrics",
+    responses(
+        (status = 200, description = "Prometheus exposition format 0.0.4", body = String, content_type = "text/plain"),
+        (status = 500, description = "The metrics could not be collected", body = String, content_type = "text/plain"),
+    )
+)]
+pub async fn show() -> impl Responder {
+    // The catalog and the proc files are read from disk
+    match web::block(render).await {
+        Ok(metrics) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4; charset=utf-8")
+            .body(metrics),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+fn render() -> String {
+    let mut out = String::new();
+    let status = status::current();
+
+    if let Some(started) = STARTED.get() {
+        gauge(
+            &mut out,
+            "foundry_watcher_uptime_seconds",
+            "Seconds since the wrapper started",
+            started.elapsed().as_secs(),
+        );
+    }
+    if let Some(report) = diskspace::last() {
+        if let Some(free_mb) = report.free_mb {
+            gauge(
+                &mut out,
+                "foundry_watcher_disk_free_bytes",
+                "Space available on the volume of DATA_DIR",
+                free_mb * 1024 * 1024,
+            );
+        }
+        if let Some(used_mb) = report.data_dir_mb {
+            gauge(
+                &mut out,
+                "foundry_watcher_data_dir_bytes",
+                "Size of the files in DATA_DIR, measured with DATA_DIR_QUOTA_MB",
+                used_mb * 1024 * 1024,
+            );
+        }
+    }
+    gauge(
+        &mut out,
+        "foundry_up",
+        "Whether the Foundry process is running",
+        u8::from(status.running),
+    );
+    if let Some(version) = &status.version {
+        header(
+            &mut out,
+            "foundry_info",
+            "Installed Foundry release",
+            "gauge",
+        );
+        let _ = writeln!(out, "foundry_info{{version=\"{}\"}} 1", escape(version));
+    }
+    if let (true, Some(started_at)) = (status.running, status.started_at) {
+        gauge(
+            &mut out,
+            "foundry_uptime_seconds",
+            "Seconds since Foundry was last started",
+            started_at.elapsed().as_secs(),
+        );
+    }
+    counter(
+        &mut out,
+        "foundry_restarts_total",
+        "Times Foundry was started again since the wrapper started",
+        status.restarts,
+    );
+    if let Some(usage) = status.pid.and_then(ProcessUsage::read) {
+        counter(
+            &mut out,
+            "foundry_cpu_seconds_total",
+            "CPU time the Foundry process spent in user and system mode",
+            format!("{:.2}", usage.cpu_seconds),
+        );
+        gauge(
+            &mut out,
+            "foundry_resident_memory_bytes",
+            "Resident memory of the Foundry process",
+            usage.rss_bytes,
+        );
+    }
+    if let Some(updated) = last_update() {
+        gauge(
+            &mut out,
+            "foundry_last_update_timestamp_seconds",
+            "When the running Foundry release was installed or switched to",
+            updated,
+        );
+    }
+
+    header(
+        &mut out,
+        "foundry_watcher_backups_total",
+        "Snapshots taken since the wrapper started, by result",
+        "counter",
+    );
+    for (result, count) in [
+        ("success", &BACKUPS_SUCCEEDED),
+        ("failure", &BACKUPS_FAILED),
+    ] {
+        let _ = writeln!(
+            out,
+            "foundry_watcher_backups_total{{result=\"{}\"}} {}",
+            result,
+            count.load(Ordering::Relaxed)
+        );
+    }
+    let newest_backup = backup::catalog(None).ok().and_then(|catalog| {
+        let newest = catalog.last()?;
+        DateTime::parse_from_rfc3339(&newest.created).ok()
+    });
+    if let Some(created) = newest_backup {
+        gauge(
+            &mut out,
+            "foundry_watcher_last_backup_timestamp_seconds",
+            "When the newest snapshot in BACKUP_DIR was taken",
+            created.timestamp(),
+        );
+    }
+    out
+}
+
+/// CPU time and memory of a process, from `/proc`
+struct ProcessUsage {
+    cpu_seconds: f64,
+    rss_bytes: u64,
+}
+
+impl ProcessUsage {
+    fn read(pid: u32) -> Option<Self> {
+        // `pid (comm) state ppid ...`, comm may contain spaces, utime and stime are the 14th
+        // and 15th field
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+        let ticks: u64 =
+            fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?;
+        // SAFETY: sysconf only reads a constant of the system
+        let per_second = unsafe { nix::libc::sysconf(nix::libc::_SC_CLK_TCK) }.max(1) as f64;
+
+        let status = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+        let rss_kb = status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmRSS:"))
+            .and_then(|kb| kb.trim().trim_end_matches("kB").trim().parse::<u64>().ok())?;
+        Some(Self {
+            cpu_seconds: ticks as f64 / per_second,
+            rss_bytes: rss_kb * 1024,
+        })
+    }
+}
+
+/// Modification of the `current` link of side by side releases, or of the manifest of a
+/// release installed in place
+fn last_update() -> Option<u64> {
+    let app_dir = PathBuf::from(&*paths::APPLICATION_DIR);
+    let modified = fs::symlink_metadata(app_dir.join(updater::CURRENT_LINK))
+        .or_else(|_| {
+            fs::metadata(paths::resolve_foundry_script_path().with_file_name("package.json"))
+        })
+        .and_then(|metadata| metadata.modified())
+        .ok()?;
+    modified
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|since| since.as_secs())
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: impl Display) {
+    header(out, name, help, "gauge");
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: impl Display) {
+    header(out, name, help, "counter");
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// Label values are quoted, backslashes, quotes and newlines escaped
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
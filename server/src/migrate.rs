@@ -0,0 +1,376 @@
+use anyhow::{Context, Result, anyhow};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+use crate::journal::Journal;
+use crate::objectstore::{ObjectStoreConfig, S3Storage, uri_encode};
+use crate::permissions::{self, FileKind};
+use crate::rewrite::{self, percent_decode, rewrite_world};
+use crate::scan::ScanService;
+use crate::storage::Storage;
+use crate::utils::paths;
+use crate::worlds;
+
+/// Directories of `Data` that stay local: packages reference their files by relative path and
+/// worlds hold the databases
+const KEPT_LOCAL: &[&str] = &["/worlds/", "/systems/", "/modules/"];
+
+/// The bucket assets are moved to or from, read from Foundry's own S3 configuration (`awsConfig` in
+/// `options.json`) so the file picker shows the same bucket afterwards
+pub struct S3Target {
+    pub store: ObjectStoreConfig,
+    /// URL the bucket's objects are publicly read from, documents point below it
+    pub base_url: String,
+}
+
+impl S3Target {
+    /// The bucket `bucket` of Foundry's S3 configuration, its first bucket when none is given
+    pub fn from_foundry_config(bucket: Option<String>, base_url: Option<String>) -> Result<Self> {
+        let config_dir = PathBuf::from(&*paths::DATA_DIR).join("Config");
+        let options: Value = serde_json::from_str(
+            &fs::read_to_string(config_dir.join("options.json"))
+                .context("Failed to read Config/options.json")?,
+        )?;
+        let aws_config = options["awsConfig"]
+            .as_str()
+            .filter(|path| !path.is_empty())
+            .ok_or_else(|| anyhow!("options.json has no awsConfig, set up S3 in Foundry first"))?;
+        // Relative paths are relative to the Config directory
+        let aws_path = config_dir.join(aws_config);
+        let aws: Value = serde_json::from_str(
+            &fs::read_to_string(&aws_path)
+                .with_context(|| format!("Failed to read {}", aws_path.display()))?,
+        )
+        .with_context(|| format!("Invalid {}", aws_path.display()))?;
+
+        let bucket = bucket
+            .or_else(|| aws["buckets"][0].as_str().map(str::to_string))
+            .ok_or_else(|| anyhow!("{} lists no buckets, pass --bucket", aws_path.display()))?;
+        let region = aws["region"].as_str().unwrap_or("us-east-1").to_string();
+        // v11+ nests the keys under `credentials`, older versions keep them at the top
+        let credentials = if aws["credentials"].is_object() {
+            &aws["credentials"]
+        } else {
+            &aws
+        };
+        let (Some(access_key), Some(secret_key)) = (
+            credentials["accessKeyId"].as_str(),
+            credentials["secretAccessKey"].as_str(),
+        ) else {
+            return Err(anyhow!("{} has no access keys", aws_path.display()));
+        };
+
+        // `endpoint` is a URL, a bare host or `{protocol, hostname, port}` for other providers
+        let endpoint = match &aws["endpoint"] {
+            Value::String(url) if url.contains("://") => {
+                Some(url.trim_end_matches('/').to_string())
+            }
+            Value::String(host) => Some(format!("https://{}", host.trim_end_matches('/'))),
+            Value::Object(endpoint) => {
+                endpoint
+                    .get("hostname")
+                    .and_then(Value::as_str)
+                    .map(|host| {
+                        let protocol = endpoint
+                            .get("protocol")
+                            .and_then(Value::as_str)
+                            .unwrap_or("https")
+                            .trim_end_matches(':');
+                        match endpoint.get("port").and_then(Value::as_u64) {
+                            Some(port) => format!("{}://{}:{}", protocol, host, port),
+                            None => format!("{}://{}", protocol, host),
+                        }
+                    })
+            }
+            _ => None,
+        };
+        let base_url = base_url
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| match &endpoint {
+                Some(endpoint) => format!("{}/{}", endpoint, bucket),
+                None => format!("https://{}.s3.{}.amazonaws.com", bucket, region),
+            });
+
+        Ok(Self {
+            store: ObjectStoreConfig {
+                endpoint: endpoint
+                    .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", region)),
+                bucket,
+                region,
+                prefix: String::new(),
+                access_key: access_key.to_string(),
+                secret_key: secret_key.to_string(),
+            },
+            base_url,
+        })
+    }
+}
+
+/// Outcome of a migration
+pub struct MigrationSummary {
+    /// Files uploaded or downloaded
+    pub files: usize,
+    pub bytes: u64,
+    pub documents: usize,
+    pub verified: usize,
+    /// Sampled links that did not load, or bucket objects that could not be downloaded
+    pub broken: Vec<String>,
+}
+
+/// Moves the user's assets between `Data` and an S3 bucket and points the world documents at
+/// their new place.
+///
+/// Files are copied, the originals stay in place until the links are known to work. The
+/// worlds must not be running, their databases are written in place.
+pub struct AssetMigration {
+    pub target: S3Target,
+    /// Worlds whose documents are rewritten, all of them when empty
+    pub worlds: Vec<String>,
+    /// Rewritten links requested again from the bucket afterwards
+    pub sample: usize,
+    /// Port of the running Foundry, its active world is not written
+    pub foundry_port: u16,
+}
+
+impl AssetMigration {
+    /// Upload the files of `Data` and link the documents to the bucket
+    pub async fn upload_to_s3(self) -> Result<MigrationSummary> {
+        let worlds = rewrite::world_ids(&self.worlds)?;
+        for world in &worlds {
+            worlds::refuse_active(self.foundry_port, world, "migrating its assets").await?;
+        }
+        // Uploads and rewritten links are kept, running the migration again finishes it
+        let journal = Journal::begin("asset migration to S3")?.with_hint(
+            "The originals stay in Data, some documents may link to the bucket already. Run \
+             `assets migrate-to-s3` again to finish the migration.",
+        )?;
+        let data_dir = PathBuf::from(&*paths::DATA_DIR).join("Data");
+        let storage = S3Storage::new(self.target.store.clone(), data_dir.clone())?;
+        let base_url = self.target.base_url.clone();
+        let migrated = tokio::task::spawn_blocking(move || {
+            let (urls, bytes) = upload(&storage, &data_dir, &base_url)?;
+            let mut links = Vec::new();
+            let mut documents = 0;
+            for world in &worlds {
+                documents += rewrite_world(world, false, &mut |path| {
+                    let url = lookup(path, &urls)?.clone();
+                    links.push(url.clone());
+                    Some(url)
+                })?;
+            }
+            anyhow::Ok((urls.len(), bytes, documents, links))
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|migrated| migrated);
+        let (files, bytes, documents, links) = journal.complete(migrated)?;
+
+        let (verified, broken) = verify(&links, self.sample).await;
+        Ok(MigrationSummary {
+            files,
+            bytes,
+            documents,
+            verified,
+            broken,
+        })
+    }
+
+    /// Download the bucket's objects the documents link to into `Data/assets` and link the
+    /// documents to the local copies, for leaving cloud storage
+    pub async fn download_to_local(self) -> Result<MigrationSummary> {
+        let worlds = rewrite::world_ids(&self.worlds)?;
+        for world in &worlds {
+            worlds::refuse_active(self.foundry_port, world, "migrating its assets").await?;
+        }
+        let journal = Journal::begin("asset migration to local files")?.with_hint(
+            "Downloaded files stay in Data/assets, some documents may link to them already. Run \
+             `assets migrate-to-local` again to finish the migration.",
+        )?;
+        let assets_dir = PathBuf::from(&*paths::DATA_DIR).join("Data").join("assets");
+        let storage = S3Storage::new(self.target.store.clone(), assets_dir.clone())?;
+        let base_url = self.target.base_url.clone();
+        let migrated = tokio::task::spawn_blocking(move || {
+            // Nothing is rewritten in the first pass, it only collects the linked objects
+            let mut keys = BTreeSet::new();
+            for world in &worlds {
+                rewrite_world(world, true, &mut |text| {
+                    keys.extend(remote_key(text, &base_url));
+                    None
+                })?;
+            }
+            info!(
+                "⬇️ Downloading {} objects from bucket {}",
+                keys.len(),
+                storage.bucket()
+            );
+
+            let (mut bytes, mut broken) = (0, Vec::new());
+            let mut local = HashMap::new();
+            for key in &keys {
+                let path = assets_dir.join(key);
+                let contents = match storage.read(&path) {
+                    Ok(contents) => contents,
+                    Err(e) => {
+                        warn!("❌ Failed to download {}: {}", key, e);
+                        broken.push(key.clone());
+                        continue;
+                    }
+                };
+                if let Some(parent) = path.parent() {
+                    permissions::create_dir_all(parent)
+                        .with_context(|| format!("Failed to create {}", parent.display()))?;
+                }
+                bytes += contents.len() as u64;
+                permissions::write(&path, contents, FileKind::Regular)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                local.insert(key.clone(), format!("assets/{}", uri_encode(key, true)));
+            }
+
+            let mut documents = 0;
+            for world in &worlds {
+                documents += rewrite_world(world, false, &mut |text| {
+                    local.get(&remote_key(text, &base_url)?).cloned()
+                })?;
+            }
+            Ok(MigrationSummary {
+                files: local.len(),
+                bytes,
+                documents,
+                verified: 0,
+                broken,
+            })
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|migrated| migrated);
+        journal.complete(migrated)
+    }
+}
+
+/// Upload every file of `Data` outside worlds and packages, returning the bucket URL of each
+/// path as documents reference it and the bytes uploaded
+fn upload(
+    storage: &S3Storage,
+    data_dir: &Path,
+    base_url: &str,
+) -> Result<(HashMap<String, String>, u64)> {
+    let excludes: Vec<String> = KEPT_LOCAL.iter().map(|dir| dir.to_string()).collect();
+    let files = ScanService::walk(data_dir, &excludes)?;
+    info!(
+        "☁️ Uploading {} files to bucket {}",
+        files.len(),
+        storage.bucket()
+    );
+
+    let mut urls = HashMap::new();
+    let mut bytes = 0;
+    for file in files {
+        let Ok(relative) = file.path.strip_prefix(data_dir) else {
+            continue;
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        let contents =
+            fs::read(&file.path).with_context(|| format!("Failed to read {}", relative))?;
+        storage
+            .upload(&file.path, contents, content_type(&relative))
+            .with_context(|| format!("Failed to upload {}", relative))?;
+        bytes += file.size;
+        urls.insert(
+            relative.clone(),
+            format!("{}/{}", base_url, uri_encode(&relative, true)),
+        );
+    }
+    Ok((urls, bytes))
+}
+
+/// Key of an object linked as `url` below the bucket's `base_url`, as a relative path that
+/// cannot leave the directory it is downloaded to
+fn remote_key(url: &str, base_url: &str) -> Option<String> {
+    let encoded = url.strip_prefix(base_url)?.strip_prefix('/')?;
+    let encoded = encoded.split(['?', '#']).next().unwrap_or(encoded);
+    let key = percent_decode(encoded)?;
+    key.split('/')
+        .all(|part| !part.is_empty() && part != "." && part != "..")
+        .then_some(key)
+}
+
+/// The bucket URL of a path as Foundry stores it, with or without a leading slash and
+/// percent encoded or not
+fn lookup<'a>(path: &str, urls: &'a HashMap<String, String>) -> Option<&'a String> {
+    if path.is_empty() || path.contains("://") {
+        return None;
+    }
+    let path = path.trim_start_matches('/');
+    urls.get(path).or_else(|| urls.get(&percent_decode(path)?))
+}
+
+/// Request an evenly spread sample of `links`, returning how many were checked and the
+/// ones that failed
+async fn verify(links: &[String], sample: usize) -> (usize, Vec<String>) {
+    let mut unique: Vec<&String> = links.iter().collect();
+    unique.sort();
+    unique.dedup();
+    if unique.is_empty() || sample == 0 {
+        return (0, Vec::new());
+    }
+    let step = unique.len().div_ceil(sample);
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .expect("Failed to build verification HTTP client");
+
+    let (mut verified, mut broken) = (0, Vec::new());
+    for url in unique.into_iter().step_by(step) {
+        verified += 1;
+        match client.head(url.as_str()).send().await {
+            Ok(response) if response.status().is_success() => {}
+            Ok(response) => {
+                warn!("❌ {} answered {}", url, response.status());
+                broken.push(url.clone());
+            }
+            Err(e) => {
+                warn!("❌ {} failed: {}", url, e);
+                broken.push(url.clone());
+            }
+        }
+    }
+    (verified, broken)
+}
+
+/// Content type of the asset formats Foundry supports, by extension
+fn content_type(path: &str) -> &'static str {
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "svg" => "image/svg+xml",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "webm" => "video/webm",
+        "mp4" | "m4v" => "video/mp4",
+        "ogv" => "video/ogg",
+        "mp3" => "audio/mpeg",
+        "ogg" | "oga" => "audio/ogg",
+        "opus" => "audio/opus",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "m4a" => "audio/mp4",
+        "pdf" => "application/pdf",
+        "json" => "application/json",
+        "txt" | "md" => "text/plain; charset=utf-8",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+use crate::backup::{BackupService, BackupTrigger};
+use crate::config::AppConfig;
+use crate::license::generation;
+use crate::permissions::{self, FileKind};
+use crate::utils::{compare_versions, installed_foundry_version, paths};
+
+/// The installed release is of a newer generation than the worlds were last opened with.
+/// Foundry migrates a world when it is launched, and the migrated world no longer opens in
+/// the older generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MajorMigration {
+    /// Newest `coreVersion` of the worlds
+    pub from: String,
+    /// The installed release
+    pub to: String,
+}
+
+/// The migration a snapshot was taken for, so restarts do not take one each
+#[derive(Debug, Serialize, Deserialize)]
+struct MigrationRecord {
+    #[serde(flatten)]
+    migration: MajorMigration,
+    backup: String,
+}
+
+/// The generation jump starting the installed release would migrate the worlds across
+pub fn detect() -> Option<MajorMigration> {
+    let to = installed_foundry_version()?;
+    let from = newest_world_version()?;
+    (generation(&to)? > generation(&from)?).then_some(MajorMigration { from, to })
+}
+
+/// Newest `coreVersion` in the `world.json` of the worlds in `DATA_DIR`
+fn newest_world_version() -> Option<String> {
+    fs::read_dir(&*paths::WORLDS_DIR)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| fs::read_to_string(entry.path().join("world.json")).ok())
+        .filter_map(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok())
+        .filter_map(|manifest| manifest["coreVersion"].as_str().map(str::to_string))
+        .max_by(|a, b| compare_versions(a, b))
+}
+
+/// Take a snapshot before the worlds are migrated, once per migration. Fails when it could
+/// not be written, the worlds must not be migrated without one.
+pub async fn back_up_before(config: &AppConfig, migration: &MajorMigration) -> Result<()> {
+    if let Some(record) = recorded().filter(|record| record.migration == *migration) {
+        info!(
+            "Snapshot {} was taken before migrating from Foundry {} to {}",
+            record.backup, migration.from, migration.to
+        );
+        return Ok(());
+    }
+    warn!(
+        "⚠️ Migrating the worlds from Foundry {} to {}, taking a snapshot first",
+        migration.from, migration.to
+    );
+    let service = BackupService::from_config(config);
+    let summary = tokio::task::spawn_blocking(move || service.create(BackupTrigger::Migration))
+        .await
+        .context("The snapshot task failed")??;
+    info!(
+        "📦 Took snapshot {}, restore it with `backup restore` and an image of Foundry {} to undo the migration",
+        summary.record.file, migration.from
+    );
+    record(MigrationRecord {
+        migration: migration.clone(),
+        backup: summary.record.file,
+    })
+}
+
+fn recorded() -> Option<MigrationRecord> {
+    let contents = fs::read_to_string(path()).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn record(record: MigrationRecord) -> Result<()> {
+    permissions::create_dir_all(&paths::WRAPPER_STATE_DIR)
+        .with_context(|| format!("Failed to create {}", paths::WRAPPER_STATE_DIR.display()))?;
+    let path = path();
+    permissions::write(
+        &path,
+        serde_json::to_string_pretty(&record)?,
+        FileKind::Regular,
+    )
+    .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn path() -> PathBuf {
+    paths::WRAPPER_STATE_DIR.join("migration.json")
+}
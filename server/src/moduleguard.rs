@@ -0,0 +1,231 @@
+use actix_web::{HttpResponse, Responder, web};
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+use tokio::time::{Duration, sleep};
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+use crate::bisect::{read_module_configuration, write_module_configuration};
+use crate::launch;
+use crate::logs;
+use crate::notify::{self, Notification, Severity};
+use crate::permissions::{self, FileKind};
+use crate::status;
+use crate::utils::paths;
+
+/// How long a world gets to load after Foundry started
+const LOAD_TIMEOUT: Duration = Duration::from_secs(180);
+/// Modules disabled one after another without the world loading in between, a world still
+/// failing after that is broken by something else
+const MAX_DISABLED_IN_A_ROW: u32 = 3;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// A module disabled because the world failed to load with it
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DisabledModule {
+    pub world: String,
+    pub module: String,
+    #[schema(format = DateTime)]
+    pub disabled_at: String,
+    /// First error of the startup that named the module
+    pub error: String,
+}
+
+/// Disables the module that breaks loading `FOUNDRY_WORLD`, so the table can still play.
+///
+/// Module code runs in the browsers, the server only sees a module fail while activating the
+/// world, e.g. with a broken manifest or pack. When the world is not active within
+/// `LOAD_TIMEOUT` of a start, the error entries Foundry logged since then are searched for an
+/// enabled module of the world.
+#[derive(Clone)]
+pub struct ModuleGuard {
+    pub world: String,
+    pub foundry_port: u16,
+    pub interval: Duration,
+}
+
+impl ModuleGuard {
+    pub async fn run(self) {
+        let mut checked: Option<Instant> = None;
+        let mut disabled_in_a_row = 0;
+        loop {
+            sleep(self.interval).await;
+            let current = status::current();
+            let Some(started) = current.started_at.filter(|_| current.running) else {
+                continue;
+            };
+            if checked == Some(started) {
+                continue;
+            }
+            checked = Some(started);
+            let started_at = Utc::now()
+                - chrono::Duration::from_std(started.elapsed()).unwrap_or_default()
+                - chrono::Duration::seconds(5);
+            if self.loads(started).await {
+                debug!("World {} loaded", self.world);
+                disabled_in_a_row = 0;
+                continue;
+            }
+
+            let errors = match logs::errors_since(started_at) {
+                Ok(errors) => errors,
+                Err(e) => {
+                    warn!("⚠️ Failed to read the logs of the failed start: {:#}", e);
+                    continue;
+                }
+            };
+            let Some((module, error)) = self.culprit(&errors) else {
+                warn!(
+                    "⚠️ World {} did not load and the logs name none of its modules",
+                    self.world
+                );
+                continue;
+            };
+            if disabled_in_a_row >= MAX_DISABLED_IN_A_ROW {
+                warn!(
+                    "⚠️ World {} still fails to load after disabling {} modules, not disabling {}",
+                    self.world, disabled_in_a_row, module
+                );
+                continue;
+            }
+            match self.disable(&module, &error).await {
+                Ok(()) => disabled_in_a_row += 1,
+                Err(e) => warn!("⚠️ Failed to disable module {}: {:#}", module, e),
+            }
+        }
+    }
+
+    /// Whether a world is active before `LOAD_TIMEOUT` ends, false when Foundry stops first
+    async fn loads(&self, started: Instant) -> bool {
+        while started.elapsed() < LOAD_TIMEOUT {
+            let current = status::current();
+            if !current.running || current.started_at != Some(started) {
+                return false;
+            }
+            if let Ok(server) = status::fetch_server_status(self.foundry_port).await
+                && server.active
+            {
+                return true;
+            }
+            sleep(Duration::from_secs(2)).await;
+        }
+        false
+    }
+
+    /// The enabled module named first in `errors`, with the error naming it
+    fn culprit(&self, errors: &[String]) -> Option<(String, String)> {
+        let configuration = read_module_configuration(&self.world)
+            .inspect_err(|e| warn!("⚠️ Failed to read the modules of {}: {:#}", self.world, e))
+            .ok()?;
+        let enabled: Vec<&String> = configuration
+            .iter()
+            .filter(|(_, active)| active.as_bool().unwrap_or(false))
+            .map(|(id, _)| id)
+            .collect();
+        errors.iter().find_map(|error| {
+            enabled.iter().find(|id| names(error, id)).map(|id| {
+                (
+                    id.to_string(),
+                    error.lines().next().unwrap_or_default().to_string(),
+                )
+            })
+        })
+    }
+
+    /// Stop Foundry, disable `module` in the world and start Foundry again
+    async fn disable(&self, module: &str, error: &str) -> Result<()> {
+        warn!(
+            "⚠️ World {} failed to load because of module {}, disabling it: {}",
+            self.world, module, error
+        );
+        // The world database is locked while Foundry runs
+        let hold = launch::pause().await;
+        let mut configuration = read_module_configuration(&self.world)?;
+        configuration.insert(module.to_string(), Value::Bool(false));
+        let written = write_module_configuration(&self.world, &configuration);
+        drop(hold);
+        written?;
+
+        record(DisabledModule {
+            world: self.world.clone(),
+            module: module.to_string(),
+            disabled_at: Utc::now().to_rfc3339(),
+            error: error.to_string(),
+        })?;
+        info!("Disabled module {} of world {}", module, self.world);
+        notify::send(Notification {
+            severity: Severity::Warning,
+            title: "Module disabled".to_string(),
+            message: format!(
+                "World {} failed to load because of module {}, which was disabled: {}. Enable it again under Manage Modules once it is fixed.",
+                self.world, module, error
+            ),
+        });
+        Ok(())
+    }
+}
+
+/// Whether `error` names the module `id`, by its path or quoted like Foundry's messages do
+fn names(error: &str, id: &str) -> bool {
+    error.contains(&format!("modules/{}/", id))
+        || error.contains(&format!("\"{}\"", id))
+        || error.contains(&format!("'{}'", id))
+}
+
+/// The modules disabled so far, oldest first
+pub fn disabled() -> Result<Vec<DisabledModule>> {
+    let path = path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&contents).with_context(|| format!("Invalid {}", path.display()))
+}
+
+fn record(module: DisabledModule) -> Result<()> {
+    let _guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut modules = disabled()?;
+    modules.push(module);
+    permissions::create_dir_all(&paths::WRAPPER_STATE_DIR)
+        .with_context(|| format!("Failed to create {}", paths::WRAPPER_STATE_DIR.display()))?;
+    let path = path();
+    let tmp_path = path.with_extension("json.tmp");
+    permissions::write(
+        &tmp_path,
+        serde_json::to_string_pretty(&modules)?,
+        FileKind::Regular,
+    )
+    .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, &path).with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+fn path() -> PathBuf {
+    paths::WRAPPER_STATE_DIR.join("disabled-modules.json")
+}
+
+/// Modules `AUTO_DISABLE_MODULES` disabled because the world failed to load with them,
+/// oldest first
+#[utoipa::path(
+    get,
+    path = "/disabled-modules",
+    operation_id = "disabledModules",
+    responses(
+        (status = 200, description = "Disabled modules", body = Vec<DisabledModule>),
+        (status = 500, description = "The record could not be read", body = String, content_type = "text/plain"),
+    )
+)]
+pub async fn show() -> impl Responder {
+    match web::block(disabled).await {
+        Ok(Ok(modules)) => HttpResponse::Ok().json(modules),
+        Ok(Err(e)) => HttpResponse::InternalServerError().body(format!("{:#}", e)),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
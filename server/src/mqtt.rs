@@ -0,0 +1,208 @@
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Packet, QoS};
+use serde_json::json;
+use tokio::time::{Duration, interval};
+use tracing::{info, warn};
+
+use crate::backup;
+use crate::capabilities;
+use crate::launch;
+use crate::status;
+use crate::worlds::WorldService;
+
+/// Publishes server state to MQTT and accepts commands, with Home Assistant discovery
+#[derive(Clone)]
+pub struct MqttBridge {
+    pub host: String,
+    pub port: u16,
+    pub credentials: Option<(String, String)>,
+    /// Prefix of the state, availability and command topics
+    pub topic_prefix: String,
+    pub discovery_prefix: String,
+    /// Port of the Foundry server to read the players online from
+    pub foundry_port: u16,
+}
+
+impl MqttBridge {
+    pub async fn run(self) {
+        let availability_topic = format!("{}/availability", self.topic_prefix);
+        let state_topic = format!("{}/state", self.topic_prefix);
+        let command_topic = format!("{}/command", self.topic_prefix);
+
+        let mut options = MqttOptions::new("foundry-watcher", &self.host, self.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        // The broker marks the server offline when the wrapper disappears
+        options.set_last_will(LastWill::new(
+            &availability_topic,
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some((username, password)) = &self.credentials {
+            options.set_credentials(username, password);
+        }
+
+        let (client, mut eventloop) = AsyncClient::new(options, 32);
+        let mut publish_interval = interval(Duration::from_secs(30));
+
+        loop {
+            tokio::select! {
+                _ = publish_interval.tick() => {
+                    let status = status::current();
+                    let server = status::fetch_server_status(self.foundry_port).await.ok();
+                    // The catalog is read from disk
+                    let last_backup = tokio::task::spawn_blocking(backup::last_result)
+                        .await
+                        .ok()
+                        .and_then(Result::ok)
+                        .flatten();
+                    let state = json!({
+                        "running": status.running,
+                        "version": status.version,
+                        "restarts": status.restarts,
+                        "world_active": server.as_ref().is_some_and(|s| s.active),
+                        "world": server.as_ref().and_then(|s| s.world.clone()),
+                        "players": server.as_ref().map(|s| s.users).unwrap_or_default(),
+                        "last_backup": last_backup.as_ref().map(|b| b.at.clone()),
+                        "last_backup_result": last_backup.as_ref().map(|b| match b.succeeded {
+                            true => "success",
+                            false => "failure",
+                        }),
+                    });
+                    // Never wait on the request queue here, only the poll below drains it
+                    if let Err(e) =
+                        client.try_publish(&state_topic, QoS::AtLeastOnce, true, state.to_string())
+                    {
+                        warn!("Failed to queue MQTT state: {}", e);
+                    }
+                }
+                event = eventloop.poll() => match event {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!("📡 Connected to MQTT broker {}:{}", self.host, self.port);
+                        capabilities::active("mqtt");
+                        self.announce(&client, &availability_topic, &state_topic, &command_topic);
+                        publish_interval.reset_immediately();
+                    }
+                    Ok(Event::Incoming(Packet::Publish(publish))) if publish.topic == command_topic => {
+                        handle_command(&String::from_utf8_lossy(&publish.payload));
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        // The event loop reconnects on the next poll
+                        warn!("⚠️ MQTT connection error: {}", e);
+                        capabilities::failed("mqtt", &e);
+                        tokio::time::sleep(Duration::from_secs(10)).await;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Subscribe to commands and publish availability and Home Assistant discovery configs
+    fn announce(
+        &self,
+        client: &AsyncClient,
+        availability_topic: &str,
+        state_topic: &str,
+        command_topic: &str,
+    ) {
+        let device = json!({
+            "identifiers": [self.topic_prefix],
+            "name": "Foundry VTT",
+            "manufacturer": "foundryvtt-docker",
+        });
+        let entities = [
+            (
+                "binary_sensor",
+                "running",
+                json!({
+                    "name": "Running",
+                    "device_class": "running",
+                    "value_template": "{{ 'ON' if value_json.running else 'OFF' }}",
+                }),
+            ),
+            (
+                "sensor",
+                "players",
+                json!({
+                    "name": "Players online",
+                    "value_template": "{{ value_json.players }}",
+                }),
+            ),
+            (
+                "sensor",
+                "version",
+                json!({
+                    "name": "Version",
+                    "value_template": "{{ value_json.version }}",
+                }),
+            ),
+            (
+                "sensor",
+                "world",
+                json!({
+                    "name": "Active world",
+                    "value_template": "{{ value_json.world }}",
+                }),
+            ),
+            (
+                "sensor",
+                "last_backup",
+                json!({
+                    "name": "Last backup",
+                    "device_class": "timestamp",
+                    "value_template": "{{ value_json.last_backup }}",
+                }),
+            ),
+            (
+                "binary_sensor",
+                "backup_failed",
+                json!({
+                    "name": "Backup failed",
+                    "device_class": "problem",
+                    "value_template": "{{ 'ON' if value_json.last_backup_result == 'failure' else 'OFF' }}",
+                }),
+            ),
+            (
+                "button",
+                "restart",
+                json!({
+                    "name": "Restart",
+                    "command_topic": command_topic,
+                    "payload_press": "restart",
+                }),
+            ),
+        ];
+
+        for (component, object_id, mut config) in entities {
+            config["unique_id"] = json!(format!("{}_{}", self.topic_prefix, object_id));
+            config["state_topic"] = json!(state_topic);
+            config["availability_topic"] = json!(availability_topic);
+            config["device"] = device.clone();
+            let topic = format!(
+                "{}/{}/{}/{}/config",
+                self.discovery_prefix, component, self.topic_prefix, object_id
+            );
+            let _ = client.try_publish(topic, QoS::AtLeastOnce, true, config.to_string());
+        }
+
+        let _ = client.try_subscribe(command_topic, QoS::AtLeastOnce);
+        let _ = client.try_publish(availability_topic, QoS::AtLeastOnce, true, "online");
+    }
+}
+
+/// `restart`, `lock:<world>` or `unlock:<world>`
+fn handle_command(command: &str) {
+    info!("MQTT command '{}'", command);
+    let result = match command.trim().split_once(':') {
+        None if command.trim() == "restart" => {
+            tokio::spawn(launch::restart_after_warning());
+            Ok(())
+        }
+        Some(("lock", world)) => WorldService::lock(world, &crate::worlds::default_lock_message()),
+        Some(("unlock", world)) => WorldService::unlock(world),
+        _ => Err(anyhow::anyhow!("Unknown command")),
+    };
+    if let Err(e) = result {
+        warn!("❌ MQTT command '{}' failed: {}", command, e);
+    }
+}
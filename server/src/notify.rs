@@ -0,0 +1,144 @@
+use anyhow::Result;
+#[cfg(feature = "full")]
+use anyhow::anyhow;
+use futures_util::future::BoxFuture;
+use serde_json::json;
+use std::sync::{Arc, OnceLock};
+use tracing::{info, warn};
+
+use crate::capabilities;
+use crate::config::AppConfig;
+#[cfg(feature = "full")]
+use crate::email::EmailNotifier;
+use crate::eventlog;
+#[cfg(feature = "full")]
+use crate::webhooknotify::WebhookNotifier;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Lifecycle events worth a line in a chat channel, e.g. a completed backup
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    #[cfg(feature = "full")]
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "info" => Ok(Self::Info),
+            "warning" => Ok(Self::Warning),
+            "critical" => Ok(Self::Critical),
+            other => Err(anyhow!("Unknown severity '{}'", other)),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+/// Something operators should hear about, e.g. a crash loop or a failed backup
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub severity: Severity,
+    pub title: String,
+    pub message: String,
+}
+
+/// A target notifications are delivered to. A new target implements this and is set up in
+/// `init` next to the others.
+pub trait Notifier: Send + Sync {
+    /// Shown in the logs when a delivery fails
+    fn name(&self) -> &'static str;
+
+    /// Least severity the target wants to hear about
+    fn min_severity(&self) -> Severity;
+
+    fn deliver<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<()>>;
+}
+
+static NOTIFIERS: OnceLock<Vec<Arc<dyn Notifier>>> = OnceLock::new();
+
+/// Set up the configured notifiers, called once at startup
+#[cfg(feature = "full")]
+pub fn init(config: &AppConfig) {
+    let mut notifiers: Vec<Arc<dyn Notifier>> = Vec::new();
+    match EmailNotifier::from_config(config) {
+        Ok(Some(email)) => {
+            info!("Email notifications enabled for critical events");
+            capabilities::configured("email");
+            notifiers.push(Arc::new(email));
+        }
+        Ok(None) => {}
+        Err(e) => {
+            warn!("⚠️ Email notifications disabled: {}", e);
+            capabilities::failed("email", e);
+        }
+    }
+    match WebhookNotifier::from_config(config) {
+        Ok(Some(webhook)) => {
+            info!(
+                "Webhook notifications enabled from severity {}",
+                webhook.min_severity().as_str()
+            );
+            capabilities::configured("webhook");
+            notifiers.push(Arc::new(webhook));
+        }
+        Ok(None) => {}
+        Err(e) => {
+            warn!("⚠️ Webhook notifications disabled: {}", e);
+            capabilities::failed("webhook", e);
+        }
+    }
+    let _ = NOTIFIERS.set(notifiers);
+}
+
+/// The `minimal` profile has no notifiers, notifications are only logged
+#[cfg(not(feature = "full"))]
+pub fn init(_: &AppConfig) {}
+
+/// Deliver a notification in the background, failures are only logged
+pub fn send(notification: Notification) {
+    if notification.severity == Severity::Info {
+        info!("🔔 {}: {}", notification.title, notification.message);
+    } else {
+        warn!("🔔 {}: {}", notification.title, notification.message);
+    }
+    eventlog::record(
+        "notification",
+        json!({
+            "severity": notification.severity.as_str(),
+            "title": notification.title,
+            "message": notification.message,
+        }),
+    );
+    let Some(notifiers) = NOTIFIERS.get() else {
+        return;
+    };
+
+    for notifier in notifiers {
+        if notification.severity < notifier.min_severity() {
+            continue;
+        }
+        let notifier = notifier.clone();
+        let notification = notification.clone();
+        tokio::spawn(async move {
+            match notifier.deliver(&notification).await {
+                Ok(()) => capabilities::active(notifier.name()),
+                Err(e) => {
+                    warn!(
+                        "Failed to send the {} notification: {:#}",
+                        notifier.name(),
+                        e
+                    );
+                    capabilities::failed(notifier.name(), e);
+                }
+            }
+        });
+    }
+}
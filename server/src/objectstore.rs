@@ -0,0 +1,621 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode, Url};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+use tokio::runtime::Handle;
+use tracing::{info, warn};
+
+use crate::jobstore::{self, MultipartUpload, UploadedPart};
+use crate::permissions::FileKind;
+use crate::resilience::{self, Service};
+use crate::storage::{Storage, StoredEntry};
+
+/// Size of the parts of multipart uploads, S3 takes at most 10000 parts of at least 5 MiB
+const PART_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Where an S3 compatible bucket is and how to sign requests for it
+#[derive(Debug, Clone)]
+pub struct ObjectStoreConfig {
+    /// e.g. `https://s3.eu-central-1.amazonaws.com` or the URL of a MinIO server
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    /// Keys are stored below this prefix, so one bucket can hold several instances
+    pub prefix: String,
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+/// A bucket of an S3 compatible object storage as storage backend.
+///
+/// Paths below `root` map to keys below the prefix, directories only exist as key prefixes.
+/// Requests are signed with AWS signature version 4 and use path style URLs, which AWS,
+/// MinIO, Cloudflare R2 and Backblaze B2 all accept. Operations block, run them on a
+/// blocking thread of the runtime, e.g. with `storage::blocking`.
+#[derive(Clone)]
+pub struct S3Storage {
+    config: ObjectStoreConfig,
+    root: PathBuf,
+    client: reqwest::Client,
+}
+
+/// An object or common prefix of a bucket listing
+struct Listed {
+    key: String,
+    size: u64,
+    modified: Option<SystemTime>,
+    is_prefix: bool,
+}
+
+impl S3Storage {
+    pub fn new(config: ObjectStoreConfig, root: PathBuf) -> io::Result<Self> {
+        Ok(Self {
+            config,
+            root,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    pub fn bucket(&self) -> &str {
+        &self.config.bucket
+    }
+
+    /// Upload a file with the `Content-Type` browsers need to show it when loaded from the
+    /// bucket directly, `write` leaves it to the object storage
+    pub fn upload(&self, path: &Path, contents: Vec<u8>, content_type: &str) -> io::Result<()> {
+        self.request(
+            Method::PUT,
+            &self.key(path)?,
+            &[],
+            &[("content-type", content_type.to_string())],
+            contents,
+        )
+        .map(|_| ())
+    }
+
+    /// Upload a local file to the object of `path`, in parts when it is larger than one part
+    /// so archives of any size need neither a single huge request nor the memory for it
+    pub fn upload_file(&self, path: &Path, source: &Path) -> io::Result<()> {
+        self.upload_file_as(&self.key(path)?, source)
+    }
+
+    /// Upload a local file to `key`. A multipart upload interrupted by a restart is
+    /// continued with the parts the bucket accepted before, see [`jobstore`].
+    pub fn upload_file_as(&self, key: &str, source: &Path) -> io::Result<()> {
+        let metadata = source.metadata()?;
+        let size = metadata.len();
+        let mut file = File::open(source)?;
+        if size <= PART_SIZE {
+            let mut contents = Vec::with_capacity(size as usize);
+            file.read_to_end(&mut contents)?;
+            return self
+                .request(Method::PUT, key, &[], &[], contents)
+                .map(|_| ());
+        }
+
+        let modified = metadata
+            .modified()
+            .map(|time| DateTime::<Utc>::from(time).timestamp())
+            .unwrap_or_default();
+        let (resumed, stale): (Vec<_>, Vec<_>) =
+            jobstore::upload(key).into_iter().partition(|job| {
+                job.bucket == self.config.bucket
+                    && job.source == source
+                    && job.size == size
+                    && job.modified == modified
+                    && job.part_size == PART_SIZE
+            });
+        for job in stale.iter().filter(|job| job.bucket == self.config.bucket) {
+            self.abort(key, &job.upload_id);
+        }
+        let job = match resumed.into_iter().next() {
+            Some(job) => {
+                info!(
+                    "Resuming the upload of {} after {} of {} parts",
+                    key,
+                    job.parts.len(),
+                    size.div_ceil(PART_SIZE)
+                );
+                job
+            }
+            None => {
+                let body = self.request(Method::POST, key, &[("uploads", "")], &[], Vec::new())?;
+                let upload_id =
+                    xml_value(&String::from_utf8_lossy(&body), "UploadId").ok_or_else(|| {
+                        io::Error::other(format!("No upload id in the answer to uploading {}", key))
+                    })?;
+                let job = MultipartUpload {
+                    bucket: self.config.bucket.clone(),
+                    upload_id,
+                    source: source.to_path_buf(),
+                    size,
+                    modified,
+                    part_size: PART_SIZE,
+                    parts: Vec::new(),
+                    started: Utc::now().to_rfc3339(),
+                };
+                let stored = job.clone();
+                jobstore::record(|store| {
+                    store.uploads.insert(key.to_string(), stored);
+                });
+                job
+            }
+        };
+        let resuming = !job.parts.is_empty();
+        let upload_id = job.upload_id.clone();
+        let result = self.upload_parts(key, job, &mut file);
+        jobstore::record(|store| {
+            store.uploads.remove(key);
+        });
+        match result {
+            // The bucket expired or aborted the upload meanwhile, e.g. by a lifecycle rule
+            Err(e) if resuming && e.kind() == io::ErrorKind::NotFound => {
+                warn!("The interrupted upload of {} is gone, starting over", key);
+                self.upload_file_as(key, source)
+            }
+            Err(e) => {
+                self.abort(key, &upload_id);
+                Err(e)
+            }
+            Ok(()) => Ok(()),
+        }
+    }
+
+    /// Parts of an unfinished upload are billed until it is aborted
+    pub fn abort(&self, key: &str, upload_id: &str) {
+        let _ = self.request(
+            Method::DELETE,
+            key,
+            &[("uploadId", upload_id)],
+            &[],
+            Vec::new(),
+        );
+    }
+
+    fn upload_parts(&self, key: &str, mut job: MultipartUpload, file: &mut File) -> io::Result<()> {
+        let first = job.parts.len() as u32 + 1;
+        file.seek(SeekFrom::Start(job.parts.len() as u64 * PART_SIZE))?;
+        for number in first.. {
+            let mut part = Vec::with_capacity(PART_SIZE as usize);
+            file.by_ref().take(PART_SIZE).read_to_end(&mut part)?;
+            if part.is_empty() {
+                break;
+            }
+            let part_number = number.to_string();
+            let (headers, _) = self.send(
+                Method::PUT,
+                key,
+                &[("partNumber", &part_number), ("uploadId", &job.upload_id)],
+                &[],
+                part,
+            )?;
+            let etag = headers
+                .get("etag")
+                .and_then(|etag| etag.to_str().ok())
+                .ok_or_else(|| {
+                    io::Error::other(format!("No ETag for part {} of {}", number, key))
+                })?;
+            let part = UploadedPart {
+                number,
+                etag: etag.to_string(),
+            };
+            job.parts.push(part.clone());
+            jobstore::record(|store| {
+                if let Some(stored) = store.uploads.get_mut(key) {
+                    stored.parts.push(part);
+                }
+            });
+        }
+        let mut completed = String::from("<CompleteMultipartUpload>");
+        for part in &job.parts {
+            completed.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                part.number, part.etag
+            ));
+        }
+        completed.push_str("</CompleteMultipartUpload>");
+        let body = self.request(
+            Method::POST,
+            key,
+            &[("uploadId", &job.upload_id)],
+            &[("content-type", "application/xml".to_string())],
+            completed.into_bytes(),
+        )?;
+        // Completing can fail after the status line was sent, the error is in the body then
+        let body = String::from_utf8_lossy(&body);
+        if body.contains("<Error>") {
+            return Err(io::Error::other(format!(
+                "Completing the upload of {} failed: {}",
+                key,
+                xml_value(&body, "Message").unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Key of a local path, paths outside `root` have none
+    pub fn key(&self, path: &Path) -> io::Result<String> {
+        let relative = path.strip_prefix(&self.root).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is outside {}", path.display(), self.root.display()),
+            )
+        })?;
+        let mut parts: Vec<String> = self
+            .config
+            .prefix
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .map(str::to_string)
+            .collect();
+        for component in relative.components() {
+            if let Component::Normal(part) = component {
+                parts.push(part.to_string_lossy().to_string());
+            }
+        }
+        Ok(parts.join("/"))
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let prefix = self.config.prefix.trim_matches('/');
+        let relative = key
+            .strip_prefix(prefix)
+            .unwrap_or(key)
+            .trim_start_matches('/');
+        self.root.join(relative)
+    }
+
+    fn request(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, String)],
+        body: Vec<u8>,
+    ) -> io::Result<Vec<u8>> {
+        self.send(method, key, query, headers, body)
+            .map(|(_, body)| body)
+    }
+
+    /// Signed request, answering the response headers and body of successful ones
+    fn send(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, String)],
+        body: Vec<u8>,
+    ) -> io::Result<(HeaderMap, Vec<u8>)> {
+        let canonical_uri = format!(
+            "/{}/{}",
+            uri_encode(&self.config.bucket, false),
+            uri_encode(key, true)
+        );
+        let mut query: Vec<(String, String)> = query
+            .iter()
+            .map(|(k, v)| (uri_encode(k, false), uri_encode(v, false)))
+            .collect();
+        query.sort();
+        let canonical_query = query
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let mut url = Url::parse(&format!(
+            "{}{}",
+            self.config.endpoint.trim_end_matches('/'),
+            canonical_uri
+        ))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        if !canonical_query.is_empty() {
+            url.set_query(Some(&canonical_query));
+        }
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "The object storage endpoint has no host",
+                ));
+            }
+        };
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex(&Sha256::digest(&body));
+
+        let mut signed: Vec<(String, String)> = vec![
+            ("host".to_string(), host),
+            ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+            ("x-amz-date".to_string(), amz_date.clone()),
+        ];
+        for (name, value) in headers {
+            signed.push((name.to_ascii_lowercase(), value.trim().to_string()));
+        }
+        signed.sort();
+        let canonical_headers: String = signed
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value))
+            .collect();
+        let signed_headers = signed
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method, canonical_uri, canonical_query, canonical_headers, signed_headers, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let mut signing_key = hmac(format!("AWS4{}", self.config.secret_key).as_bytes(), &date);
+        for part in [self.config.region.as_str(), "s3", "aws4_request"] {
+            signing_key = hmac(&signing_key, part);
+        }
+        let signature = hex(&hmac(&signing_key, &string_to_sign));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.config.access_key, scope, signed_headers, signature
+        );
+
+        let mut request = self
+            .client
+            .request(method.clone(), url)
+            .header("authorization", authorization);
+        for (name, value) in signed.iter().filter(|(name, _)| name != "host") {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        let request = request.body(body);
+
+        Handle::current().block_on(resilience::call(Service::S3, || async {
+            let request = request
+                .try_clone()
+                .ok_or_else(|| io::Error::other("The request cannot be sent again"))?;
+            let response = request.send().await.map_err(io::Error::other)?;
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response.bytes().await.map_err(io::Error::other)?;
+            let kind = match status {
+                status if status.is_success() => return Ok((headers, body.to_vec())),
+                StatusCode::NOT_FOUND => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("{} does not exist", key_or_bucket(key, &self.config.bucket)),
+                    ));
+                }
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => io::ErrorKind::PermissionDenied,
+                // Sending a rejected request again gets the same answer
+                StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS => io::ErrorKind::Other,
+                status if status.is_client_error() => io::ErrorKind::InvalidInput,
+                _ => io::ErrorKind::Other,
+            };
+            Err(io::Error::new(
+                kind,
+                format!(
+                    "{} {} failed with {}: {}",
+                    method,
+                    key_or_bucket(key, &self.config.bucket),
+                    status,
+                    xml_value(&String::from_utf8_lossy(&body), "Message").unwrap_or_default()
+                ),
+            ))
+        }))
+    }
+
+    /// Objects below `prefix`, with `delimiter` only the direct children and their prefixes
+    fn list_prefix(&self, prefix: &str, delimiter: bool) -> io::Result<Vec<Listed>> {
+        let mut listed = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+            if delimiter {
+                query.push(("delimiter", "/"));
+            }
+            if let Some(token) = &token {
+                query.push(("continuation-token", token));
+            }
+            let body = self.request(Method::GET, "", &query, &[], Vec::new())?;
+            let xml = String::from_utf8_lossy(&body);
+
+            for contents in xml_blocks(&xml, "Contents") {
+                let Some(key) = xml_value(contents, "Key") else {
+                    continue;
+                };
+                listed.push(Listed {
+                    key,
+                    size: xml_value(contents, "Size")
+                        .and_then(|s| s.parse().ok())
+                        .unwrap_or(0),
+                    modified: xml_value(contents, "LastModified")
+                        .and_then(|m| DateTime::parse_from_rfc3339(&m).ok())
+                        .map(SystemTime::from),
+                    is_prefix: false,
+                });
+            }
+            for common in xml_blocks(&xml, "CommonPrefixes") {
+                if let Some(key) = xml_value(common, "Prefix") {
+                    listed.push(Listed {
+                        key: key.trim_end_matches('/').to_string(),
+                        size: 0,
+                        modified: None,
+                        is_prefix: true,
+                    });
+                }
+            }
+
+            token = xml_value(&xml, "NextContinuationToken");
+            if xml_value(&xml, "IsTruncated").as_deref() != Some("true") || token.is_none() {
+                return Ok(listed);
+            }
+        }
+    }
+
+    fn dir_prefix(&self, path: &Path) -> io::Result<String> {
+        let key = self.key(path)?;
+        Ok(if key.is_empty() {
+            key
+        } else {
+            format!("{}/", key)
+        })
+    }
+}
+
+impl Storage for S3Storage {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.request(Method::GET, &self.key(path)?, &[], &[], Vec::new())
+    }
+
+    /// Objects have no modes, `kind` only matters once they are stored locally again
+    fn write(&self, path: &Path, contents: &[u8], _kind: FileKind) -> io::Result<()> {
+        self.request(Method::PUT, &self.key(path)?, &[], &[], contents.to_vec())
+            .map(|_| ())
+    }
+
+    fn create_dir_all(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<StoredEntry>> {
+        Ok(self
+            .list_prefix(&self.dir_prefix(dir)?, true)?
+            .into_iter()
+            .map(|listed| StoredEntry {
+                path: self.path(&listed.key),
+                size: listed.size,
+                is_dir: listed.is_prefix,
+                modified: listed.modified,
+            })
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<StoredEntry> {
+        let key = self.key(path)?;
+        let files = self.list_prefix(&key, true)?;
+        if let Some(file) = files.iter().find(|listed| listed.key == key) {
+            return Ok(StoredEntry {
+                path: path.to_path_buf(),
+                size: file.size,
+                is_dir: file.is_prefix,
+                modified: file.modified,
+            });
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} does not exist", key),
+        ))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let source = self.metadata(from)?;
+        if !source.is_dir {
+            self.copy(from, to)?;
+            return self.remove_file(from);
+        }
+        for listed in self.list_prefix(&self.dir_prefix(from)?, false)? {
+            let path = self.path(&listed.key);
+            let relative = path.strip_prefix(from).unwrap_or(&path);
+            self.copy(&path, &to.join(relative))?;
+            self.remove_file(&path)?;
+        }
+        Ok(())
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        let source = format!(
+            "/{}/{}",
+            uri_encode(&self.config.bucket, false),
+            uri_encode(&self.key(from)?, true)
+        );
+        self.request(
+            Method::PUT,
+            &self.key(to)?,
+            &[],
+            &[("x-amz-copy-source", source)],
+            Vec::new(),
+        )?;
+        Ok(self.metadata(to)?.size)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.request(Method::DELETE, &self.key(path)?, &[], &[], Vec::new())
+            .map(|_| ())
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        for listed in self.list_prefix(&self.dir_prefix(path)?, false)? {
+            self.request(Method::DELETE, &listed.key, &[], &[], Vec::new())?;
+        }
+        Ok(())
+    }
+}
+
+fn key_or_bucket<'a>(key: &'a str, bucket: &'a str) -> &'a str {
+    if key.is_empty() { bucket } else { key }
+}
+
+/// Percent encoding of everything but unreserved characters, as signature version 4 requires
+pub fn uri_encode(value: &str, keep_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if keep_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Contents of every `<tag>` element, enough for the flat responses of S3
+fn xml_blocks<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+    let mut blocks = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        let Some(end) = rest.find(&close) else {
+            break;
+        };
+        blocks.push(&rest[..end]);
+        rest = &rest[end + close.len()..];
+    }
+    blocks
+}
+
+fn xml_value(xml: &str, tag: &str) -> Option<String> {
+    let value = xml_blocks(xml, tag).into_iter().next()?;
+    Some(
+        value
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    )
+}
@@ -0,0 +1,351 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::permissions::{self, FileKind};
+use crate::utils::paths;
+
+/// Lines of context around each change in the diff
+const DIFF_CONTEXT: usize = 3;
+
+/// Changes of the last render, or of the proposal applied since
+static LAST_DIFF: Mutex<Option<OptionsDiff>> = Mutex::new(None);
+
+/// How changed options reach `Config/options.json`, set with `CONFIG_APPLY`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ApplyMode {
+    /// Write the file on every start
+    Auto,
+    /// Write `options.json.proposed` next to it and keep the file until the change is confirmed
+    Manual,
+}
+
+impl ApplyMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "auto" => Some(Self::Auto),
+            "manual" => Some(Self::Manual),
+            _ => None,
+        }
+    }
+}
+
+/// A key of `options.json` that differs, with the variable that set it
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct OptionChange {
+    pub key: String,
+    /// `None` for keys no `FOUNDRY_*` variable sets, e.g. in a hand-edited proposal
+    #[schema(value_type = Option<String>)]
+    pub variable: Option<&'static str>,
+    pub before: Option<Value>,
+    pub after: Value,
+}
+
+/// What a render changes in `options.json`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct OptionsDiff {
+    #[schema(format = DateTime)]
+    pub rendered_at: String,
+    /// `false` while the change waits in `options.json.proposed`
+    pub applied: bool,
+    pub changes: Vec<OptionChange>,
+    /// Unified diff of the file
+    pub diff: String,
+}
+
+/// Settings of Foundry's `Config/options.json` set from `FOUNDRY_*` environment variables.
+///
+/// Only the keys set here are written, everything else in the file stays as Foundry or the
+/// user left it. The names are those Foundry uses in the file.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FoundryOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_hostname: Option<String>,
+    /// Path the game is served below, without slashes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route_prefix: Option<String>,
+    #[serde(rename = "proxySSL", skip_serializing_if = "Option::is_none")]
+    pub proxy_ssl: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upnp: Option<bool>,
+    /// e.g. `en.core` or `de.lang-de`, the language `de` of the package `lang-de`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// World launched on start instead of the setup screen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub world: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update_channel: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telemetry: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compress_static: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compress_socket: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssl_cert: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssl_key: Option<String>,
+}
+
+impl FoundryOptions {
+    /// Merge the set options into `Config/options.json` in `DATA_DIR`, creating it when Foundry
+    /// never started yet. The file is only written when a value changed, and with
+    /// [`ApplyMode::Manual`] only proposed next to it.
+    pub fn render(&self, mode: ApplyMode) -> Result<()> {
+        let Value::Object(set) = serde_json::to_value(self)? else {
+            return Err(anyhow!("Foundry options are not an object"));
+        };
+        if set.is_empty() {
+            return Ok(());
+        }
+
+        let path = options_path();
+        let options = read_options(&path)?;
+
+        let mut rendered = options.clone();
+        for (key, value) in set {
+            rendered.insert(key, value);
+        }
+        let proposed_path = proposed_path();
+        let Some(diff) = OptionsDiff::between(&options, &rendered, mode == ApplyMode::Auto) else {
+            // A proposal of an earlier start is outdated once the file matches the variables
+            if proposed_path.exists() {
+                fs::remove_file(&proposed_path)
+                    .with_context(|| format!("Failed to remove {}", proposed_path.display()))?;
+            }
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            permissions::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let contents = serde_json::to_string_pretty(&Value::Object(rendered))?;
+        let target = match mode {
+            ApplyMode::Auto => &path,
+            ApplyMode::Manual => &proposed_path,
+        };
+        permissions::write(target, contents, FileKind::Regular)
+            .with_context(|| format!("Failed to write {}", target.display()))?;
+        let reasons: Vec<String> = diff
+            .changes
+            .iter()
+            .map(|change| match change.variable {
+                Some(variable) => format!("{} ({})", change.key, variable),
+                None => change.key.clone(),
+            })
+            .collect();
+        match mode {
+            ApplyMode::Auto => info!("⚙️ Set {} in options.json", reasons.join(", ")),
+            ApplyMode::Manual => info!(
+                "⏸️ Proposed {} in {}, confirm with `foundry-watcher options apply` or POST /api/options/apply",
+                reasons.join(", "),
+                proposed_path.display()
+            ),
+        }
+        for line in diff.diff.lines() {
+            info!("  {}", line);
+        }
+        *LAST_DIFF.lock().unwrap() = Some(diff);
+        Ok(())
+    }
+}
+
+impl OptionsDiff {
+    /// Changes from `before` to `after`, `None` when they are equal
+    fn between(
+        before: &Map<String, Value>,
+        after: &Map<String, Value>,
+        applied: bool,
+    ) -> Option<Self> {
+        let changes: Vec<OptionChange> = after
+            .iter()
+            .filter(|(key, value)| before.get(*key) != Some(*value))
+            .map(|(key, value)| OptionChange {
+                key: key.clone(),
+                variable: variable_of(key),
+                before: before.get(key).cloned(),
+                after: value.clone(),
+            })
+            .collect();
+        if changes.is_empty() {
+            return None;
+        }
+        // Both sides pretty printed alike, so the diff only shows values that changed
+        let print = |options: &Map<String, Value>| {
+            serde_json::to_string_pretty(&Value::Object(options.clone())).unwrap_or_default()
+        };
+        Some(Self {
+            rendered_at: Utc::now().to_rfc3339(),
+            applied,
+            changes,
+            diff: unified_diff("Config/options.json", &print(before), &print(after)),
+        })
+    }
+}
+
+/// Changes of the last render since the wrapper started
+pub fn last_diff() -> Option<OptionsDiff> {
+    LAST_DIFF.lock().unwrap().clone()
+}
+
+/// Changes `options.json.proposed` would make, `None` without a proposal
+pub fn proposal() -> Result<Option<OptionsDiff>> {
+    let proposed_path = proposed_path();
+    if !proposed_path.exists() {
+        return Ok(None);
+    }
+    Ok(OptionsDiff::between(
+        &read_options(&options_path())?,
+        &read_options(&proposed_path)?,
+        false,
+    ))
+}
+
+/// Move `options.json.proposed` into place, answering what it changed. Foundry reads the file
+/// when it starts.
+pub fn apply_proposal() -> Result<Option<OptionsDiff>> {
+    let proposed_path = proposed_path();
+    let Some(mut diff) = proposal()? else {
+        if proposed_path.exists() {
+            fs::remove_file(&proposed_path)
+                .with_context(|| format!("Failed to remove {}", proposed_path.display()))?;
+        }
+        return Ok(None);
+    };
+    let path = options_path();
+    fs::rename(&proposed_path, &path).with_context(|| {
+        format!(
+            "Failed to move {} to {}",
+            proposed_path.display(),
+            path.display()
+        )
+    })?;
+    info!("⚙️ Applied the proposed options.json");
+    diff.applied = true;
+    *LAST_DIFF.lock().unwrap() = Some(diff.clone());
+    Ok(Some(diff))
+}
+
+/// `options.json` at `path`, an empty object when it does not exist yet
+fn read_options(path: &Path) -> Result<Map<String, Value>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => match serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid {}, fix or remove it", path.display()))?
+        {
+            Value::Object(options) => Ok(options),
+            _ => Err(anyhow!("{} is not a JSON object", path.display())),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Map::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Variable setting `key` of `options.json`
+fn variable_of(key: &str) -> Option<&'static str> {
+    Some(match key {
+        "port" => "FOUNDRY_PORT",
+        "hostname" => "FOUNDRY_HOSTNAME",
+        "localHostname" => "FOUNDRY_LOCAL_HOSTNAME",
+        "routePrefix" => "FOUNDRY_ROUTE_PREFIX",
+        "proxySSL" => "FOUNDRY_PROXY_SSL",
+        "proxyPort" => "FOUNDRY_PROXY_PORT",
+        "upnp" => "FOUNDRY_UPNP",
+        "language" => "FOUNDRY_LANGUAGE",
+        "world" => "FOUNDRY_WORLD",
+        "updateChannel" => "FOUNDRY_UPDATE_CHANNEL",
+        "telemetry" => "FOUNDRY_TELEMETRY",
+        "compressStatic" => "FOUNDRY_COMPRESS_STATIC",
+        "compressSocket" => "FOUNDRY_COMPRESS_WEBSOCKET",
+        "sslCert" => "FOUNDRY_SSL_CERT",
+        "sslKey" => "FOUNDRY_SSL_KEY",
+        _ => return None,
+    })
+}
+
+/// Line diff of `before` and `after` in the format of `diff -u`
+fn unified_diff(name: &str, before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+    // Longest common subsequence of the lines, the files have a few dozen
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = match old[i] == new[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+    // Each line with its marker and the lines of either side before it
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i], i, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(('-', old[i], i, j));
+            i += 1;
+        } else {
+            lines.push(('+', new[j], i, j));
+            j += 1;
+        }
+    }
+
+    let mut diff = format!("--- a/{}\n+++ b/{}\n", name, name);
+    let changed: Vec<usize> = (0..lines.len()).filter(|&k| lines[k].0 != ' ').collect();
+    let mut k = 0;
+    while k < changed.len() {
+        let start = changed[k].saturating_sub(DIFF_CONTEXT);
+        let mut end = changed[k] + 1;
+        // Changes closer than twice the context share a hunk
+        while k < changed.len() && changed[k] <= end + 2 * DIFF_CONTEXT {
+            end = changed[k] + 1;
+            k += 1;
+        }
+        let end = (end + DIFF_CONTEXT).min(lines.len());
+        let hunk = &lines[start..end];
+        let old_len = hunk.iter().filter(|line| line.0 != '+').count();
+        let new_len = hunk.iter().filter(|line| line.0 != '-').count();
+        // An empty side starts at the line before, as in `diff -u`
+        let (_, _, old_start, new_start) = hunk[0];
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start + usize::from(old_len > 0),
+            old_len,
+            new_start + usize::from(new_len > 0),
+            new_len
+        ));
+        for (marker, line, _, _) in hunk {
+            diff.push_str(&format!("{}{}\n", marker, line));
+        }
+    }
+    diff
+}
+
+fn options_path() -> PathBuf {
+    PathBuf::from(&*paths::DATA_DIR)
+        .join("Config")
+        .join("options.json")
+}
+
+fn proposed_path() -> PathBuf {
+    options_path().with_extension("json.proposed")
+}
@@ -0,0 +1,389 @@
+use anyhow::{Context, Result, anyhow};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+use crate::cache;
+use crate::config::AppConfig;
+use crate::diskspace;
+use crate::downloader::DownloadService;
+use crate::extractor::ExtractorService;
+use crate::notify::{self, Notification, Severity};
+use crate::objectstore;
+use crate::reconcile;
+use crate::resilience::{self, Service};
+use crate::state::WrapperState;
+use crate::tempfiles;
+use crate::upgrade;
+use crate::utils::{compare_versions, installed_foundry_version, paths};
+
+/// Versions of the installed core, systems and modules keyed by `core`, `system:<id>`, `module:<id>`
+pub fn installed_packages() -> BTreeMap<String, String> {
+    let mut packages = BTreeMap::new();
+    if let Some(version) = installed_foundry_version() {
+        packages.insert("core".to_string(), version);
+    }
+
+    let data_dir = PathBuf::from(&*paths::DATA_DIR).join("Data");
+    for (kind, manifest_name) in [("system", "system.json"), ("module", "module.json")] {
+        let Ok(entries) = fs::read_dir(data_dir.join(format!("{}s", kind))) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let manifest = entry.path().join(manifest_name);
+            let Some(version) = read_manifest_version(&manifest) else {
+                continue;
+            };
+            let id = entry.file_name().to_string_lossy().to_string();
+            packages.insert(format!("{}:{}", kind, id), version);
+        }
+    }
+    packages
+}
+
+/// Human readable list of packages that were added, removed or changed version
+pub fn describe_changes(
+    before: &BTreeMap<String, String>,
+    after: &BTreeMap<String, String>,
+) -> Vec<String> {
+    let mut changes = Vec::new();
+    for (id, version) in after {
+        match before.get(id) {
+            Some(old) if old != version => changes.push(format!("{} {} → {}", id, old, version)),
+            None => changes.push(format!("{} {} (new)", id, version)),
+            _ => {}
+        }
+    }
+    for (id, version) in before {
+        if !after.contains_key(id) {
+            changes.push(format!("{} {} (removed)", id, version));
+        }
+    }
+    changes
+}
+
+pub fn read_manifest_version(path: &Path) -> Option<String> {
+    let manifest: Value = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    manifest["version"].as_str().map(|v| v.to_string())
+}
+
+/// Kinds of packages installed from manifest URLs
+#[derive(Debug, Clone, Copy)]
+enum PackageKind {
+    Module,
+    System,
+}
+
+impl PackageKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Module => "module",
+            Self::System => "system",
+        }
+    }
+
+    /// `Data/modules` or `Data/systems`
+    fn dir(self) -> PathBuf {
+        PathBuf::from(&*paths::DATA_DIR)
+            .join("Data")
+            .join(format!("{}s", self.name()))
+    }
+
+    fn manifest_name(self) -> String {
+        format!("{}.json", self.name())
+    }
+}
+
+/// Installs the modules and systems of `INSTALL_MODULES` and `INSTALL_SYSTEMS` before Foundry
+/// starts, from the manifest URLs package authors publish. A package already installed in the
+/// version of its manifest is left alone, so a boot without updates downloads nothing.
+pub struct PackageInstaller {
+    pub modules: Vec<String>,
+    pub systems: Vec<String>,
+}
+
+impl PackageInstaller {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            modules: config.install_modules.clone(),
+            systems: config.install_systems.clone(),
+        }
+    }
+
+    /// Install every listed package, a failed one is reported and does not stop the others
+    pub async fn install_all(&self) {
+        if self.modules.is_empty() && self.systems.is_empty() {
+            return;
+        }
+        let core = installed_foundry_version();
+        let mut installed = false;
+        let mut failures = Vec::new();
+        let listed = (self.systems.iter().map(|url| (PackageKind::System, url)))
+            .chain(self.modules.iter().map(|url| (PackageKind::Module, url)));
+        for (kind, url) in listed {
+            match install(kind, url, core.as_deref()).await {
+                Ok(changed) => installed |= changed,
+                Err(e) => {
+                    warn!(
+                        "⚠️ Failed to install the {} of {}: {:#}",
+                        kind.name(),
+                        url,
+                        e
+                    );
+                    failures.push(format!("{}: {:#}", url, e));
+                }
+            }
+        }
+        // Installed by the wrapper, not from Foundry's setup screen
+        if installed {
+            reconcile::record();
+        }
+        if !failures.is_empty() {
+            notify::send(Notification {
+                severity: Severity::Warning,
+                title: "Package installation failed".to_string(),
+                message: failures.join("; "),
+            });
+        }
+    }
+}
+
+/// Install the package of the manifest at `url` unless its version is installed already,
+/// answering whether anything changed
+async fn install(kind: PackageKind, url: &str, core: Option<&str>) -> Result<bool> {
+    let manifest: Value =
+        serde_json::from_slice(&fetch_manifest(url).await?).context("The manifest is not JSON")?;
+    // `name` before Foundry 10
+    let id = manifest["id"]
+        .as_str()
+        .or_else(|| manifest["name"].as_str())
+        .filter(|id| {
+            !id.is_empty()
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+        .ok_or_else(|| anyhow!("The manifest has no valid id"))?;
+    let version = manifest["version"]
+        .as_str()
+        .ok_or_else(|| anyhow!("The manifest of {} has no version", id))?;
+    let target = kind.dir().join(id);
+    if read_manifest_version(&target.join(kind.manifest_name())).as_deref() == Some(version) {
+        debug!("{} {} {} is installed already", kind.name(), id, version);
+        return Ok(false);
+    }
+    let key = format!("{}:{}", kind.name(), id);
+    if WrapperState::load()?
+        .rejected_packages
+        .get(&key)
+        .map(String::as_str)
+        == Some(version)
+    {
+        warn!(
+            "⚠️ Not installing {} {} {} again, the upgrade smoke test failed with it",
+            kind.name(),
+            id,
+            version
+        );
+        return Ok(false);
+    }
+    if let Some(core) = core {
+        check_compatibility(&manifest, core)
+            .with_context(|| format!("{} {} does not fit Foundry {}", id, version, core))?;
+    }
+    let download = manifest["download"]
+        .as_str()
+        .ok_or_else(|| anyhow!("The manifest of {} has no download", id))?;
+
+    info!("📦 Installing {} {} {}", kind.name(), id, version);
+    install_into(kind, id, version, download, &kind.dir()).await?;
+    info!("✅ Installed {} {} {}", kind.name(), id, version);
+    Ok(true)
+}
+
+/// Install `version` of the package keyed like `installed_packages` from the archive at
+/// `download` below the `Data` directory `data`, e.g. the staging copy of a restore
+pub async fn install_version(key: &str, version: &str, download: &str, data: &Path) -> Result<()> {
+    let (kind, id) = match key.split_once(':') {
+        Some(("system", id)) => (PackageKind::System, id),
+        Some(("module", id)) => (PackageKind::Module, id),
+        _ => return Err(anyhow!("{} is not a system or module", key)),
+    };
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(anyhow!("{} has no valid id", key));
+    }
+    install_into(
+        kind,
+        id,
+        version,
+        download,
+        &data.join(format!("{}s", kind.name())),
+    )
+    .await
+}
+
+/// Replace `dir/<id>` with the package archive, cleaning up whatever the attempt left behind
+async fn install_into(
+    kind: PackageKind,
+    id: &str,
+    version: &str,
+    download: &str,
+    dir: &Path,
+) -> Result<()> {
+    if diskspace::critical() {
+        return Err(anyhow!(
+            "Disk space is critically low, not installing {} {}",
+            id,
+            version
+        ));
+    }
+    fs::create_dir_all(dir)?;
+    let target = dir.join(id);
+    let archive = dir.join(format!(".{}.zip", id));
+    let staging = dir.join(format!(".{}.staging", id));
+    tempfiles::track(&archive, "package archive");
+    tempfiles::track(&staging, "package staging");
+    let cache_key = cache::key(&["packages", kind.name(), id, version], "zip");
+    let unpacked = unpack(kind, download, &cache_key, &archive, &staging, &target).await;
+    for leftover in [&archive, &staging] {
+        let removed = match leftover.is_dir() {
+            true => fs::remove_dir_all(leftover),
+            false => fs::remove_file(leftover),
+        };
+        if let Err(e) = removed
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            warn!("Failed to remove {}: {}", leftover.display(), e);
+        }
+    }
+    unpacked
+}
+
+/// The manifest at `url`, kept in the download cache for offline installs
+async fn fetch_manifest(url: &str) -> Result<Vec<u8>> {
+    let cache_key = cache::key(
+        &[
+            "packages",
+            "manifests",
+            &objectstore::hex(&Sha256::digest(url)),
+        ],
+        "json",
+    );
+    // Online the manifest is always fetched, it may announce a new version
+    if let Some(manifest) = cache::read(&cache_key).filter(|_| cache::offline()) {
+        return Ok(manifest);
+    }
+    cache::ensure_online("the manifest")?;
+    let client = reqwest::Client::new();
+    let manifest = resilience::call(Service::Packages, || async {
+        Ok::<_, anyhow::Error>(
+            client
+                .get(url)
+                .send()
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await?,
+        )
+    })
+    .await?;
+    cache::write(&cache_key, &manifest);
+    Ok(manifest.to_vec())
+}
+
+/// Download the package archive, or take it from the download cache, and replace `target`
+/// with it once it extracted completely
+async fn unpack(
+    kind: PackageKind,
+    download: &str,
+    cache_key: &str,
+    archive: &Path,
+    staging: &Path,
+    target: &Path,
+) -> Result<()> {
+    // Nobody follows the progress of an unattended install
+    let (event_tx, _) = broadcast::channel(16);
+    let archive_path = archive.to_string_lossy().to_string();
+    let cached = cache::restore(cache_key, archive).await?;
+    if !cached {
+        cache::ensure_online(download)?;
+        DownloadService::download_file_from_url(download, &archive_path, event_tx.clone())
+            .await
+            .map_err(|e| anyhow!("Failed to download {}: {}", download, e))?;
+    }
+    if staging.exists() {
+        fs::remove_dir_all(staging)?;
+    }
+    let extracted = ExtractorService::extract_zip(
+        archive_path,
+        staging.to_string_lossy().to_string(),
+        event_tx,
+    )
+    .await;
+    match (&extracted, cached) {
+        (Ok(_), false) => cache::store(cache_key, archive).await,
+        (Err(_), true) => cache::evict(cache_key).await,
+        _ => {}
+    }
+    extracted.context("The package archive does not extract")?;
+
+    // Archives contain the package files or a single directory with them
+    let root = match staging.join(kind.manifest_name()).is_file() {
+        true => staging.to_path_buf(),
+        false => fs::read_dir(staging)?
+            .flatten()
+            .map(|entry| entry.path())
+            .find(|path| path.join(kind.manifest_name()).is_file())
+            .ok_or_else(|| anyhow!("The archive contains no {}", kind.manifest_name()))?,
+    };
+    // The verified version of an installed package is kept to roll a failed upgrade back to
+    let kept = target.exists()
+        && target.parent() == Some(kind.dir().as_path())
+        && upgrade::keep_previous(kind.name(), target).unwrap_or_else(|e| {
+            warn!(
+                "Failed to keep {} for a rollback: {:#}",
+                target.display(),
+                e
+            );
+            false
+        });
+    if target.exists() && !kept {
+        fs::remove_dir_all(target)
+            .with_context(|| format!("Failed to remove {}", target.display()))?;
+    }
+    fs::rename(&root, target).with_context(|| format!("Failed to move {}", target.display()))
+}
+
+/// Whether the package supports the `core` version, with the fields of Foundry 10 and later
+/// or the `minimumCoreVersion` of older manifests
+fn check_compatibility(manifest: &Value, core: &str) -> Result<()> {
+    let minimum = manifest["compatibility"]["minimum"]
+        .as_str()
+        .or_else(|| manifest["minimumCoreVersion"].as_str());
+    if let Some(minimum) = minimum
+        && compare_versions(core, minimum) == Ordering::Less
+    {
+        return Err(anyhow!("it needs at least Foundry {}", minimum));
+    }
+    // A maximum of `12` covers every 12.x release
+    if let Some(maximum) = manifest["compatibility"]["maximum"]
+        .as_str()
+        .filter(|m| !m.is_empty())
+    {
+        let depth = maximum.split('.').count();
+        let core_prefix = core.split('.').take(depth).collect::<Vec<_>>().join(".");
+        if compare_versions(&core_prefix, maximum) == Ordering::Greater {
+            return Err(anyhow!("it supports Foundry up to {}", maximum));
+        }
+    }
+    Ok(())
+}
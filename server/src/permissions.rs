@@ -0,0 +1,247 @@
+use nix::sys::stat::{Mode, umask};
+use nix::unistd::{Gid, Uid, User, geteuid, setgid, setgroups, setuid};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt, lchown};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::configfile;
+use crate::utils::paths;
+
+/// What a file written by the wrapper contains, secrets get the tightest mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Regular,
+    /// Settings exports, heap snapshots and anything else that may contain credentials
+    Secret,
+}
+
+/// Explicit modes for created files, `None` leaves the mode to the umask
+#[derive(Debug, Clone, Copy, Default)]
+struct PermissionPolicy {
+    file_mode: Option<u32>,
+    secret_mode: Option<u32>,
+    dir_mode: Option<u32>,
+}
+
+static POLICY: OnceLock<PermissionPolicy> = OnceLock::new();
+/// Home of the user the wrapper switched to with `PUID`, for the processes it starts
+static SWITCHED_HOME: OnceLock<PathBuf> = OnceLock::new();
+
+/// Apply `UMASK` and read the permission policy, called before anything is written.
+///
+/// `FILE_PERMISSIONS=strict` defaults to umask 027 with 0640 files, 0600 secrets and 0750
+/// directories; `FILE_MODE`, `SECRET_FILE_MODE` and `DIR_MODE` override single modes.
+pub fn init() {
+    let strict = configfile::var("FILE_PERMISSIONS")
+        .map(|v| v.to_lowercase() == "strict")
+        .unwrap_or(false);
+
+    let mask = octal_from_env("UMASK").or(strict.then_some(0o027));
+    if let Some(mask) = mask {
+        // The umask is inherited by Foundry, so files it creates follow the policy as well
+        umask(Mode::from_bits_truncate(mask));
+        info!("Using umask {:03o}", mask);
+    }
+
+    let policy = PermissionPolicy {
+        file_mode: octal_from_env("FILE_MODE").or(strict.then_some(0o640)),
+        secret_mode: octal_from_env("SECRET_FILE_MODE").or(strict.then_some(0o600)),
+        dir_mode: octal_from_env("DIR_MODE").or(strict.then_some(0o750)),
+    };
+    if strict {
+        info!("Strict file permissions enabled");
+    }
+    let _ = POLICY.set(policy);
+}
+
+/// When started as root with `PUID` and `PGID`, hand the volumes to that user and continue as
+/// it, called before anything writes to them.
+///
+/// `DATA_DIR`, `BACKUP_DIR` and, unless `CHOWN_APPLICATION_DIR=false`, `APPLICATION_DIR` get
+/// the owner first when `chown` is set. Files owned already are only looked at, so later
+/// starts are quick. Management commands run with `docker exec` switch without changing
+/// owners, files they write belong to the same user as those of the wrapper.
+pub fn switch_user(chown: bool) -> io::Result<()> {
+    let Some((uid, gid)) = remapped_owner() else {
+        return Ok(());
+    };
+    let current = geteuid();
+    if !current.is_root() {
+        if current != uid && chown {
+            warn!(
+                "⚠️ PUID={} is ignored, the wrapper runs as uid {} instead of root. Start the container as root, e.g. with `user: root`, to switch to it",
+                uid, current
+            );
+        }
+        return Ok(());
+    }
+    if uid.is_root() {
+        return Ok(());
+    }
+
+    if chown {
+        let chown_app_dir = configfile::var("CHOWN_APPLICATION_DIR")
+            .map(|v| v.to_lowercase() != "false")
+            .unwrap_or(true);
+        let mut dirs = vec![
+            PathBuf::from(&*paths::DATA_DIR),
+            PathBuf::from(&*paths::BACKUP_DIR),
+        ];
+        if chown_app_dir {
+            dirs.push(PathBuf::from(&*paths::APPLICATION_DIR));
+        }
+        for dir in dirs.iter().filter(|dir| dir.exists()) {
+            let started = Instant::now();
+            let (changed, failed) = chown_tree(dir, uid, gid);
+            if changed > 0 {
+                info!(
+                    "Changed the owner of {} files in {} to {}:{} in {:.1?}",
+                    changed,
+                    dir.display(),
+                    uid,
+                    gid,
+                    started.elapsed()
+                );
+            }
+            if failed > 0 {
+                warn!(
+                    "⚠️ Failed to change the owner of {} files in {}, rootless runtimes cannot change owners, fix them on the host",
+                    failed,
+                    dir.display()
+                );
+            }
+        }
+    }
+
+    let home = User::from_uid(uid)
+        .ok()
+        .flatten()
+        .map(|user| user.dir)
+        .unwrap_or_else(|| PathBuf::from("/tmp"));
+    // Supplementary groups of root go first, they would keep its access
+    setgroups(&[gid]).map_err(io::Error::from)?;
+    setgid(gid).map_err(io::Error::from)?;
+    setuid(uid).map_err(io::Error::from)?;
+    let _ = SWITCHED_HOME.set(home);
+    info!("Running as uid {} and gid {} from PUID and PGID", uid, gid);
+    Ok(())
+}
+
+/// `HOME` for processes the wrapper starts after switching to `PUID`, root's is not writable
+pub fn switched_home() -> Option<&'static Path> {
+    SWITCHED_HOME.get().map(PathBuf::as_path)
+}
+
+/// `PUID` and `PGID`, the group defaults to the user id
+fn remapped_owner() -> Option<(Uid, Gid)> {
+    let id = |name: &str| {
+        let value = configfile::var(name).ok().filter(|v| !v.is_empty())?;
+        match value.trim().parse::<u32>() {
+            Ok(id) => Some(id),
+            Err(_) => {
+                warn!("Ignoring {}={}, expected a numeric id", name, value);
+                None
+            }
+        }
+    };
+    let uid = id("PUID")?;
+    let gid = id("PGID").unwrap_or(uid);
+    Some((Uid::from_raw(uid), Gid::from_raw(gid)))
+}
+
+/// Change the owner of `dir` and everything below it without following links, answering the
+/// number of changed and failed entries
+fn chown_tree(dir: &Path, uid: Uid, gid: Gid) -> (usize, usize) {
+    let (mut changed, mut failed) = (0, 0);
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            failed += 1;
+            continue;
+        };
+        if metadata.uid() != uid.as_raw() || metadata.gid() != gid.as_raw() {
+            match lchown(&path, Some(uid.as_raw()), Some(gid.as_raw())) {
+                Ok(()) => changed += 1,
+                Err(_) => failed += 1,
+            }
+        }
+        if metadata.is_dir()
+            && let Ok(entries) = fs::read_dir(&path)
+        {
+            pending.extend(entries.flatten().map(|entry| entry.path()));
+        }
+    }
+    (changed, failed)
+}
+
+/// Create or truncate `path` with the mode configured for `kind`
+pub fn create(path: &Path, kind: FileKind) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    if let Some(mode) = mode_for(kind) {
+        // Set on creation so secrets are never readable by others, not even briefly
+        options.mode(mode);
+    }
+    let file = options.open(path)?;
+    restrict(path, kind)?;
+    Ok(file)
+}
+
+/// Write `contents` to `path` with the mode configured for `kind`
+pub fn write(path: &Path, contents: impl AsRef<[u8]>, kind: FileKind) -> io::Result<()> {
+    create(path, kind)?.write_all(contents.as_ref())
+}
+
+/// Create `path` with its parents, the configured directory mode is applied to `path` itself.
+///
+/// Existing directories are left alone, volume mount points may belong to a user the wrapper
+/// cannot change, e.g. an unmapped host user under rootless Podman.
+pub fn create_dir_all(path: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        return Ok(());
+    }
+    fs::create_dir_all(path)?;
+    match policy().dir_mode {
+        Some(mode) => fs::set_permissions(path, fs::Permissions::from_mode(mode)),
+        None => Ok(()),
+    }
+}
+
+/// Apply the mode configured for `kind` to an existing file
+pub fn restrict(path: &Path, kind: FileKind) -> io::Result<()> {
+    match mode_for(kind) {
+        Some(mode) => fs::set_permissions(path, fs::Permissions::from_mode(mode)),
+        None => Ok(()),
+    }
+}
+
+fn mode_for(kind: FileKind) -> Option<u32> {
+    let policy = policy();
+    match kind {
+        FileKind::Regular => policy.file_mode,
+        // Without an explicit secret mode secrets are at least as strict as regular files
+        FileKind::Secret => policy.secret_mode.or(policy.file_mode),
+    }
+}
+
+fn policy() -> PermissionPolicy {
+    POLICY.get().copied().unwrap_or_default()
+}
+
+fn octal_from_env(name: &str) -> Option<u32> {
+    let value = configfile::var(name).ok().filter(|v| !v.is_empty())?;
+    match u32::from_str_radix(value.trim_start_matches("0o"), 8) {
+        Ok(mode) if mode <= 0o7777 => Some(mode),
+        _ => {
+            warn!(
+                "Ignoring {}={}, expected an octal mode like 0640",
+                name, value
+            );
+            None
+        }
+    }
+}
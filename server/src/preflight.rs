@@ -0,0 +1,321 @@
+use nix::sys::statvfs::statvfs;
+use std::fs;
+use std::net::TcpListener;
+use std::path::Path;
+use tokio::time::Duration;
+use tracing::{debug, error};
+
+use crate::command::CommandRunner;
+use crate::config::AppConfig;
+use crate::doctor;
+use crate::license::LicenseCheck;
+use crate::migration;
+use crate::utils::{find_executable, installed_foundry_version, paths};
+
+/// A problem that keeps Foundry from starting, each with an exit code of its own so
+/// orchestration tooling can tell them apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    MainScript,
+    NodeMissing,
+    NodeVersion,
+    DataDir,
+    DiskSpace,
+    PortInUse,
+    License,
+    MajorMigration,
+    /// A `pre-start` hook failed
+    PreStartHook,
+}
+
+impl Failure {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::MainScript => 10,
+            Self::NodeMissing => 11,
+            Self::NodeVersion => 12,
+            Self::DataDir => 13,
+            Self::DiskSpace => 14,
+            Self::PortInUse => 15,
+            Self::License => 16,
+            Self::MajorMigration => 17,
+            Self::PreStartHook => 18,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub failure: Failure,
+    pub message: String,
+    /// What to change, shown below the message
+    pub hints: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct PreflightReport {
+    pub findings: Vec<Finding>,
+    pub passed: Vec<String>,
+}
+
+impl PreflightReport {
+    /// Exit code of the first failed check, in the order the checks ran
+    pub fn exit_code(&self) -> Option<i32> {
+        self.findings
+            .first()
+            .map(|finding| finding.failure.exit_code())
+    }
+
+    /// Log every failed check in one block, so one look at the logs shows all that is wrong
+    pub fn log(&self) {
+        for check in &self.passed {
+            debug!("Preflight: {}", check);
+        }
+        if self.findings.is_empty() {
+            return;
+        }
+        error!("❌ Foundry cannot be started, the preflight checks failed:");
+        for finding in &self.findings {
+            error!(
+                "  - [exit {}] {}",
+                finding.failure.exit_code(),
+                finding.message
+            );
+            for hint in &finding.hints {
+                error!("      {}", hint);
+            }
+        }
+    }
+
+    fn fail(&mut self, failure: Failure, message: String, hints: Vec<String>) {
+        self.findings.push(Finding {
+            failure,
+            message,
+            hints,
+        });
+    }
+}
+
+/// Validates the environment right before Foundry is launched, after the launcher installed
+/// and configured everything it was asked to
+pub struct Preflight;
+
+impl Preflight {
+    pub async fn run(config: &AppConfig) -> PreflightReport {
+        let mut report = PreflightReport::default();
+        Self::check_main_script(config, &mut report);
+        Self::check_node(&mut report).await;
+        Self::check_data_dir(config, &mut report);
+        Self::check_port(config, &mut report);
+        Self::check_license(config, &mut report);
+        Self::check_migration(config, &mut report);
+        report
+    }
+
+    /// Run the checks and exit with the code of the first failure
+    pub async fn enforce(config: &AppConfig) {
+        let report = Self::run(config).await;
+        report.log();
+        if let Some(code) = report.exit_code() {
+            std::process::exit(code);
+        }
+    }
+
+    fn check_main_script(config: &AppConfig, report: &mut PreflightReport) {
+        // Follows the `current` link of side by side releases, a dangling one fails here
+        match fs::metadata(&config.foundry_script) {
+            Ok(meta) if meta.is_file() => {
+                report
+                    .passed
+                    .push(format!("{} is present", config.foundry_script));
+            }
+            _ => report.fail(
+                Failure::MainScript,
+                format!("{} does not exist", config.foundry_script),
+                vec![format!(
+                    "Check that APPLICATION_DIR {} contains an extracted Foundry release",
+                    *paths::APPLICATION_DIR
+                )],
+            ),
+        }
+    }
+
+    async fn check_node(report: &mut PreflightReport) {
+        let Some(node) = find_executable("node") else {
+            report.fail(
+                Failure::NodeMissing,
+                "node was not found in PATH".to_string(),
+                vec!["Use an image with Node.js, or add its directory to PATH".to_string()],
+            );
+            return;
+        };
+        let version = CommandRunner::new(&node)
+            .arg("--version")
+            .timeout(Duration::from_secs(10))
+            .run()
+            .await;
+        let version = match version {
+            Ok(result) => result.stdout.trim().to_string(),
+            Err(e) => {
+                report.fail(Failure::NodeMissing, e.to_string(), Vec::new());
+                return;
+            }
+        };
+        let major = version
+            .trim_start_matches('v')
+            .split('.')
+            .next()
+            .and_then(|major| major.parse::<u32>().ok());
+        match (major, required_node_major()) {
+            (Some(major), Some(required)) if major < required => report.fail(
+                Failure::NodeVersion,
+                format!(
+                    "Node {} is installed, the Foundry release needs Node {} or newer",
+                    version, required
+                ),
+                vec!["Pull a newer image or install a matching Node.js".to_string()],
+            ),
+            _ => report
+                .passed
+                .push(format!("Node {} at {}", version, node.display())),
+        }
+    }
+
+    fn check_data_dir(config: &AppConfig, report: &mut PreflightReport) {
+        let data_dir = Path::new(&*paths::DATA_DIR);
+        if let Err(e) = doctor::probe_write(data_dir) {
+            report.fail(
+                Failure::DataDir,
+                format!("DATA_DIR {} is not writable: {}", data_dir.display(), e),
+                doctor::explain_write_failure(data_dir, &e),
+            );
+            return;
+        }
+        report
+            .passed
+            .push(format!("DATA_DIR {} is writable", data_dir.display()));
+
+        if config.preflight_min_free_mb == 0 {
+            return;
+        }
+        let Ok(stat) = statvfs(data_dir) else {
+            return;
+        };
+        let free_mb = stat.blocks_available() * stat.fragment_size() / (1024 * 1024);
+        if free_mb < config.preflight_min_free_mb {
+            report.fail(
+                Failure::DiskSpace,
+                format!(
+                    "Only {} MiB are free on the volume of DATA_DIR, {} MiB are required",
+                    free_mb, config.preflight_min_free_mb
+                ),
+                vec!["Free space on the volume, or lower PREFLIGHT_MIN_FREE_MB".to_string()],
+            );
+        } else {
+            report
+                .passed
+                .push(format!("{} MiB free on the volume of DATA_DIR", free_mb));
+        }
+    }
+
+    fn check_port(config: &AppConfig, report: &mut PreflightReport) {
+        // Released right away, Foundry binds the port moments later
+        match TcpListener::bind(("0.0.0.0", config.server_port)) {
+            Ok(_) => report
+                .passed
+                .push(format!("Port {} is free", config.server_port)),
+            Err(e) => report.fail(
+                Failure::PortInUse,
+                format!("Port {} cannot be bound: {}", config.server_port, e),
+                vec![
+                    "Another process listens on it, or a second container shares the network namespace"
+                        .to_string(),
+                ],
+            ),
+        }
+    }
+
+    fn check_license(config: &AppConfig, report: &mut PreflightReport) {
+        let path = LicenseCheck::path();
+        // Without a key, a missing license only means the license screen is shown
+        if path.exists() {
+            report.passed.push(format!("{} is present", path.display()));
+        } else if config.foundry_license_key.is_some() {
+            report.fail(
+                Failure::License,
+                format!(
+                    "FOUNDRY_LICENSE_KEY is set but {} was not written",
+                    path.display()
+                ),
+                vec!["Check the warnings above about storing the license".to_string()],
+            );
+        }
+    }
+
+    fn check_migration(config: &AppConfig, report: &mut PreflightReport) {
+        let Some(migration) = migration::detect() else {
+            report
+                .passed
+                .push("The worlds match the generation of the installed release".to_string());
+            return;
+        };
+        if config.allow_major_migration {
+            report.passed.push(format!(
+                "Migrating the worlds from Foundry {} to {} is allowed",
+                migration.from, migration.to
+            ));
+            return;
+        }
+        report.fail(
+            Failure::MajorMigration,
+            format!(
+                "The worlds were last opened with Foundry {}, Foundry {} would migrate them and they no longer open in the older version",
+                migration.from, migration.to
+            ),
+            vec![
+                "Set ALLOW_MAJOR_MIGRATION=true to migrate, a snapshot is taken first".to_string(),
+                format!(
+                    "Or go back to an image of Foundry {}, e.g. pin the image tag",
+                    migration.from
+                ),
+            ],
+        );
+    }
+}
+
+/// Oldest Node major the installed release supports, from `engines` of its package.json or
+/// the requirements of its generation
+fn required_node_major() -> Option<u32> {
+    let package_json = paths::foundry_app_dir()
+        .join("resources")
+        .join("app")
+        .join("package.json");
+    let manifest = fs::read_to_string(package_json)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok());
+    // e.g. `>=20.0.0`, the first number is the minimum
+    if let Some(engines) = manifest
+        .as_ref()
+        .and_then(|m| m["engines"]["node"].as_str())
+    {
+        let digits: String = engines
+            .chars()
+            .skip_while(|c| !c.is_ascii_digit())
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        if let Ok(major) = digits.parse() {
+            return Some(major);
+        }
+    }
+    let generation: u32 = installed_foundry_version()?
+        .split('.')
+        .next()?
+        .parse()
+        .ok()?;
+    Some(match generation {
+        ..=10 => 14,
+        11 => 16,
+        12 => 18,
+        _ => 20,
+    })
+}
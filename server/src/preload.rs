@@ -0,0 +1,148 @@
+use anyhow::Result;
+use chrono::{Local, NaiveTime};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use tokio::time::{Duration, interval};
+use tracing::{debug, info, warn};
+
+use crate::db::Collection;
+use crate::status;
+
+/// Embedded documents of a scene whose images are loaded when players join
+const SCENE_EMBEDDED: &[&str] = &["tiles", "tokens", "notes"];
+
+/// Loads the images of the active scene once, so the first players joining do not wait for
+/// the disk. Requests go through the proxy when it runs, warming its path as well.
+#[derive(Clone)]
+pub struct ScenePreloader {
+    /// Port the assets are requested from, the proxy's if it runs
+    pub port: u16,
+    pub foundry_port: u16,
+    /// Local times to warm up again, e.g. right before the weekly session
+    pub at: Vec<NaiveTime>,
+}
+
+impl ScenePreloader {
+    /// Warm up whenever a world is launched and at the configured times, until the process ends
+    pub async fn run(self) {
+        info!("Preloading the active scene when a world is launched");
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .expect("Failed to build preload HTTP client");
+        let mut ticker = interval(Duration::from_secs(30));
+        let mut warmed_world: Option<String> = None;
+        let mut last_check = Local::now().time();
+
+        loop {
+            ticker.tick().await;
+            let now = Local::now().time();
+            let scheduled = self.at.iter().any(|at| passed(last_check, now, *at));
+            last_check = now;
+
+            let world = match status::fetch_server_status(self.foundry_port).await {
+                Ok(server) if server.active => server.world,
+                _ => None,
+            };
+            let Some(world) = world else {
+                warmed_world = None;
+                continue;
+            };
+            if !scheduled && warmed_world.as_ref() == Some(&world) {
+                continue;
+            }
+            warmed_world = Some(world.clone());
+
+            if let Err(e) = self.warm(&client, &world).await {
+                warn!("Failed to preload the active scene of {}: {:#}", world, e);
+            }
+        }
+    }
+
+    async fn warm(&self, client: &reqwest::Client, world_id: &str) -> Result<()> {
+        let id = world_id.to_string();
+        // Foundry holds the database, read from a snapshot off the runtime threads
+        let scene = tokio::task::spawn_blocking(move || active_scene(&id)).await??;
+        let Some((name, assets)) = scene else {
+            debug!("World {} has no active scene to preload", world_id);
+            return Ok(());
+        };
+
+        let base = reqwest::Url::parse(&format!("http://127.0.0.1:{}/", self.port))?;
+        let (mut loaded, mut bytes) = (0, 0);
+        for asset in &assets {
+            let url = base.join(asset)?;
+            let result = async {
+                let response = client.get(url).send().await?.error_for_status()?;
+                anyhow::Ok(response.bytes().await?.len())
+            }
+            .await;
+            match result {
+                Ok(size) => {
+                    loaded += 1;
+                    bytes += size;
+                }
+                Err(e) => debug!("Failed to preload {}: {}", asset, e),
+            }
+        }
+        info!(
+            "🔥 Preloaded {} of {} images ({:.1} MB) of scene {}",
+            loaded,
+            assets.len(),
+            bytes as f64 / 1_048_576.0,
+            name
+        );
+        Ok(())
+    }
+}
+
+/// Name and local image paths of the world's active scene
+fn active_scene(world_id: &str) -> Result<Option<(String, BTreeSet<String>)>> {
+    if !Collection::exists(world_id, "scenes") {
+        return Ok(None);
+    }
+    let mut scenes = Collection::open_snapshot(world_id, "scenes")?;
+    let Some(scene) = scenes
+        .documents()?
+        .into_iter()
+        .find(|scene| scene["active"].as_bool().unwrap_or(false))
+    else {
+        return Ok(None);
+    };
+
+    let mut assets = BTreeSet::new();
+    collect_images(&scene, &mut assets);
+    for field in SCENE_EMBEDDED {
+        for document in scenes.embedded(field, &scene)? {
+            collect_images(&document, &mut assets);
+        }
+    }
+    let name = scene["name"].as_str().unwrap_or("Untitled").to_string();
+    Ok(Some((name, assets)))
+}
+
+/// Image paths of a scene or placeable in the layouts of v9 (`img`, `icon`) and v10+
+/// (`background.src`, `texture.src`), external URLs are left to the players' browsers
+fn collect_images(document: &Value, assets: &mut BTreeSet<String>) {
+    let candidates = [
+        &document["img"],
+        &document["icon"],
+        &document["foreground"],
+        &document["background"]["src"],
+        &document["texture"]["src"],
+    ];
+    for path in candidates.into_iter().filter_map(Value::as_str) {
+        if !path.is_empty() && !path.contains("://") && !path.starts_with("data:") {
+            assets.insert(path.trim_start_matches('/').to_string());
+        }
+    }
+}
+
+/// Whether `at` lies in `(from, to]`, including the step over midnight
+fn passed(from: NaiveTime, to: NaiveTime, at: NaiveTime) -> bool {
+    if from <= to {
+        from < at && at <= to
+    } else {
+        at > from || at <= to
+    }
+}
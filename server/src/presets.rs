@@ -0,0 +1,86 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::PathBuf;
+use tracing::{debug, info, warn};
+
+use crate::db::Collection;
+use crate::settings::{SettingEntry, SettingsService};
+use crate::state::WrapperState;
+use crate::worlds::WorldService;
+
+/// Named bundles of module settings seeded into every world once, e.g. a tuned Dice So Nice
+/// and combat tracker setup a hosting provider offers as a "pre-configured table".
+///
+/// Each `<name>.json` in the directory is a file written by `settings export`. A preset only
+/// fills in settings the world has no value for, so changes made at the table survive.
+pub struct SettingsPresets {
+    pub dir: PathBuf,
+}
+
+impl SettingsPresets {
+    /// Seed the presets each world has not received yet. Worlds created later are seeded on
+    /// the next start, after Foundry launched them once and created their settings.
+    pub fn apply(&self) -> Result<()> {
+        let presets = self.load()?;
+        if presets.is_empty() {
+            debug!("No settings presets in {}", self.dir.display());
+            return Ok(());
+        }
+
+        let mut state = WrapperState::load()?;
+        let mut changed = false;
+        for world in WorldService::ids()? {
+            if !Collection::exists(&world, "settings") {
+                continue;
+            }
+            let seeded = state.seeded_presets.entry(world.clone()).or_default();
+            for (name, entries) in &presets {
+                if seeded.contains(name) {
+                    continue;
+                }
+                match SettingsService::seed(&world, entries) {
+                    Ok(count) => {
+                        info!(
+                            "🧩 Seeded {} settings of preset {} into world {}",
+                            count, name, world
+                        );
+                        seeded.push(name.clone());
+                        changed = true;
+                    }
+                    Err(e) => warn!(
+                        "⚠️ Failed to seed preset {} into world {}: {:#}",
+                        name, world, e
+                    ),
+                }
+            }
+        }
+        if changed {
+            state.save()?;
+        }
+        Ok(())
+    }
+
+    /// Presets by name, sorted so they are seeded in a stable order
+    fn load(&self) -> Result<Vec<(String, Vec<SettingEntry>)>> {
+        let mut presets = Vec::new();
+        let entries = fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to list {}", self.dir.display()))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let Some(name) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+                continue;
+            };
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            match serde_json::from_str(&contents) {
+                Ok(settings) => presets.push((name, settings)),
+                Err(e) => warn!("⚠️ Skipping invalid preset {}: {}", path.display(), e),
+            }
+        }
+        presets.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(presets)
+    }
+}
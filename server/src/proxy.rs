@@ -0,0 +1,1025 @@
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, VecDeque};
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant, timeout};
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+use crate::api::{self, ApiConfig, Message};
+use crate::gate::AuthGate;
+use crate::headers::{self, SecurityHeaders};
+use crate::recording::Recorder;
+use crate::socketactivation::{self, Listener};
+use crate::{status, worlds};
+
+/// Largest request head accepted, the same limit Node applies
+const MAX_HEAD: usize = 16 * 1024;
+/// Longest request line accepted, longer URLs are answered with 414
+const MAX_REQUEST_LINE: usize = 8 * 1024;
+/// Window reconnects are counted in, a player reconnecting often within it has a bad line
+const RECONNECT_WINDOW: Duration = Duration::from_secs(600);
+
+/// Forwards players to Foundry and records how each client's game connection behaves
+#[derive(Clone)]
+pub struct Proxy {
+    pub listen_host: String,
+    pub listen_port: u16,
+    pub upstream_port: u16,
+    pub chaos: Chaos,
+    /// Game websockets are recorded to this directory for `foundry-watcher replay`
+    pub record_dir: Option<String>,
+    /// Added to every response except on game websockets
+    pub headers: SecurityHeaders,
+    pub limits: Limits,
+    /// Login asked for before any request reaches Foundry
+    pub auth: Option<AuthGate>,
+}
+
+/// Protection of the Node server behind the proxy, which has hardly any of its own
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// Open connections per client address, 0 for no limit
+    pub connections_per_ip: u32,
+    /// Time a client gets to send a complete request head, slow clients holding connections
+    /// open with a trickle of bytes are cut off after it
+    pub header_timeout: Duration,
+}
+
+/// Bad connections simulated on game websockets, for module developers testing how their
+/// code behaves for players with lag. Never enable it for real games.
+#[derive(Debug, Clone, Default)]
+pub struct Chaos {
+    /// Delay added to everything sent in either direction
+    pub latency: Duration,
+    /// The delay varies by up to this much either way, order is kept
+    pub jitter: Duration,
+    /// Share of websocket messages dropped, between 0 and 1
+    pub drop_rate: f64,
+}
+
+/// Game connection statistics of one client address
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ClientStats {
+    pub client: String,
+    /// Id of the user the address last joined as, joins are seen when they pass the proxy
+    pub user_id: Option<String>,
+    /// Name of that user in the active world
+    pub player: Option<String>,
+    /// Open websocket connections
+    pub active: u32,
+    /// Websocket connections since the wrapper started
+    pub connections: u64,
+    /// Connections opened within the last ten minutes after the first one
+    pub recent_reconnects: usize,
+    pub bytes_to_client: u64,
+    pub bytes_from_client: u64,
+    pub last_connected: String,
+    #[serde(skip)]
+    connected_at: VecDeque<Instant>,
+}
+
+static STATS: Mutex<BTreeMap<String, ClientStats>> = Mutex::new(BTreeMap::new());
+/// User id each client address last joined as
+static PLAYERS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+fn lock_stats() -> MutexGuard<'static, BTreeMap<String, ClientStats>> {
+    STATS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn lock_players() -> MutexGuard<'static, BTreeMap<String, String>> {
+    PLAYERS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Statistics of every client seen so far, most reconnects first
+pub fn client_stats() -> Vec<ClientStats> {
+    let now = Instant::now();
+    let players = lock_players().clone();
+    let mut clients: Vec<ClientStats> = lock_stats()
+        .values()
+        .map(|stats| {
+            let mut stats = stats.clone();
+            stats.user_id = players.get(&stats.client).cloned();
+            stats.recent_reconnects = stats
+                .connected_at
+                .iter()
+                .filter(|at| now.duration_since(**at) < RECONNECT_WINDOW)
+                .count()
+                .saturating_sub(1);
+            stats
+        })
+        .collect();
+    clients.sort_by(|a, b| {
+        b.recent_reconnects
+            .cmp(&a.recent_reconnects)
+            .then_with(|| a.client.cmp(&b.client))
+    });
+    clients
+}
+
+/// Game connection statistics per client address, collected by the proxy, with the player
+/// each address joined as
+#[utoipa::path(
+    get,
+    path = "/api/connections",
+    operation_id = "connections",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "One entry per client address", body = Vec<ClientStats>),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+    )
+)]
+pub async fn connections(req: HttpRequest, config: web::Data<ApiConfig>) -> impl Responder {
+    if let Some(rejected) = api::reject(&req, &config) {
+        return rejected;
+    }
+    let mut clients = client_stats();
+    if clients.iter().any(|stats| stats.user_id.is_some()) {
+        let names = match status::fetch_server_status(config.foundry_port)
+            .await
+            .ok()
+            .and_then(|server| server.world)
+        {
+            Some(world) => tokio::task::spawn_blocking(move || worlds::user_names(&world))
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|names| names)
+                .inspect_err(|e| debug!("Players of the connections are unknown: {:#}", e))
+                .unwrap_or_default(),
+            None => Default::default(),
+        };
+        for stats in &mut clients {
+            stats.player = stats.user_id.as_ref().and_then(|id| names.get(id)).cloned();
+        }
+    }
+    HttpResponse::Ok().json(clients)
+}
+
+/// Page showing the connection statistics per player, it asks for the `ADMIN_API_TOKEN` and
+/// reads `/api/connections` with it
+pub async fn dashboard() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(include_str!("../static/connections.html"))
+}
+
+impl Proxy {
+    pub async fn run(self) {
+        let listener = match socketactivation::listener(Listener::Proxy) {
+            Some(activated) => activated.and_then(TcpListener::from_std),
+            None => TcpListener::bind((self.listen_host.as_str(), self.listen_port)).await,
+        };
+        let listener = match listener {
+            Ok(listener) => listener,
+            Err(e) => {
+                warn!(
+                    "⚠️ Proxy could not listen on {}:{}: {}",
+                    self.listen_host, self.listen_port, e
+                );
+                return;
+            }
+        };
+        info!(
+            "Proxy listening on {}, forwarding to Foundry on port {}",
+            address(&listener, &self.listen_host, self.listen_port),
+            self.upstream_port
+        );
+        if self.chaos.is_active() {
+            warn!(
+                "🐢 Chaos mode: game connections get {}ms ±{}ms latency and {:.1}% of their messages dropped",
+                self.chaos.latency.as_millis(),
+                self.chaos.jitter.as_millis(),
+                self.chaos.drop_rate * 100.0
+            );
+        }
+        if let Some(dir) = &self.record_dir {
+            warn!(
+                "⏺️ Recording game connections to {}, the recordings contain everything players send",
+                dir
+            );
+        }
+
+        match &self.auth {
+            Some(AuthGate::Basic { users }) => {
+                info!("🔒 Proxy asks for one of {} basic auth logins", users.len())
+            }
+            Some(AuthGate::ForwardAuth { header }) => {
+                info!(
+                    "🔒 Proxy only lets requests with a {} header through",
+                    header
+                )
+            }
+            None => {}
+        }
+
+        loop {
+            let (client, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("Proxy failed to accept a connection: {}", e);
+                    continue;
+                }
+            };
+            let proxy = self.clone();
+            tokio::spawn(async move { proxy.serve(client, peer).await });
+        }
+    }
+
+    /// Forward one client connection, also those the TLS listener decrypted
+    pub async fn serve<S: ClientStream>(&self, client: S, peer: SocketAddr) {
+        let Some(slot) = ConnectionSlot::acquire(peer.ip(), self.limits.connections_per_ip) else {
+            debug!("Refused a connection from {}, too many are open", peer.ip());
+            let _ = reject(client, "429 Too Many Requests").await;
+            return;
+        };
+        if let Err(e) = forward(client, peer, self).await {
+            debug!("Proxied connection from {} ended: {}", peer, e);
+        }
+        drop(slot);
+    }
+}
+
+/// Connection of a player, plain TCP or TLS
+pub trait ClientStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> ClientStream for S {}
+
+async fn forward<S: ClientStream>(
+    mut client: S,
+    peer: SocketAddr,
+    proxy: &Proxy,
+) -> std::io::Result<()> {
+    let Ok(head) = timeout(proxy.limits.header_timeout, read_head(&mut client)).await else {
+        debug!("{} sent no complete request in time", peer.ip());
+        return reject(client, "408 Request Timeout").await;
+    };
+    let mut head = head?;
+    if head.is_empty() {
+        return Ok(());
+    }
+    let request_line = head.iter().position(|b| *b == b'\n').unwrap_or(head.len());
+    if request_line > MAX_REQUEST_LINE {
+        return reject(client, "414 URI Too Long").await;
+    }
+    if !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        return reject(client, "431 Request Header Fields Too Large").await;
+    }
+    if let Some(answer) = refusal(proxy, &head) {
+        client.write_all(&answer).await?;
+        return client.shutdown().await;
+    }
+    let chaos = &proxy.chaos;
+    let mut upstream = TcpStream::connect(("127.0.0.1", proxy.upstream_port)).await?;
+
+    // Only Foundry's game socket is tracked, page loads and assets pass through
+    let Some(client_id) = game_socket_client(&head, peer) else {
+        // Keep-alive connections carry further requests, each one has to pass the login, joins
+        // of a locked world are checked and joins tell which player an address belongs to
+        if !proxy.headers.is_empty()
+            || proxy.auth.is_some()
+            || worlds::any_locked()
+            || is_join(&head)
+        {
+            return exchange(client, upstream, head, peer, proxy).await;
+        }
+        upstream.write_all(&head).await?;
+        tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+        return Ok(());
+    };
+
+    // Dropping compressed messages would corrupt the compression context of the ones after
+    if chaos.drop_rate > 0.0 {
+        head = without_header(&head, "sec-websocket-extensions");
+    }
+    upstream.write_all(&head).await?;
+
+    let recorder = proxy.record_dir.as_ref().and_then(|dir| {
+        Recorder::create(dir, &client_id, &request_path(&head))
+            .inspect(|recorder| debug!("Recording to {}", recorder.path.display()))
+            .inspect_err(|e| warn!("Failed to start recording {}: {}", client_id, e))
+            .ok()
+    });
+
+    connection_opened(&client_id);
+    record_bytes(&client_id, |stats| {
+        stats.bytes_from_client += head.len() as u64
+    });
+
+    let (mut client_read, mut client_write) = tokio::io::split(client);
+    let (mut upstream_read, mut upstream_write) = upstream.into_split();
+    let from_client = |n| record_bytes(&client_id, |stats| stats.bytes_from_client += n);
+    let to_client = |n| record_bytes(&client_id, |stats| stats.bytes_to_client += n);
+    let result = if chaos.is_active() || recorder.is_some() {
+        let recorder = recorder.as_ref();
+        tokio::select! {
+            result = frame_pump(&mut client_read, &mut upstream_write, chaos, recorder, true, from_client) => result,
+            result = frame_pump(&mut upstream_read, &mut client_write, chaos, recorder, false, to_client) => result,
+        }
+    } else {
+        tokio::select! {
+            result = pump(&mut client_read, &mut upstream_write, from_client) => result,
+            result = pump(&mut upstream_read, &mut client_write, to_client) => result,
+        }
+    };
+
+    connection_closed(&client_id);
+    result
+}
+
+/// How the body following a request or response head ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Body {
+    None,
+    Length(u64),
+    Chunked,
+    /// HTTP/1.0 style, the body ends with the connection
+    UntilClose,
+}
+
+/// Forward requests one by one, adding the proxy's headers to every response.
+///
+/// Keep-alive connections carry several requests, so bodies are read by their length to find
+/// where the next head starts. Upgrades other than the game socket are passed through.
+async fn exchange<S: ClientStream>(
+    client: S,
+    upstream: TcpStream,
+    first: Vec<u8>,
+    peer: SocketAddr,
+    proxy: &Proxy,
+) -> std::io::Result<()> {
+    let headers = &proxy.headers;
+    let (client_read, mut client_write) = tokio::io::split(client);
+    let (upstream_read, mut upstream_write) = upstream.into_split();
+
+    // `read_head` may have read past the head into the body
+    let split = first
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map_or(first.len(), |end| end + 4);
+    let body_start = std::io::Cursor::new(first[split..].to_vec());
+    let mut client_read = BufReader::new(body_start.chain(client_read));
+    let mut upstream_read = BufReader::new(upstream_read);
+    let mut request = first[..split].to_vec();
+
+    while !request.is_empty() {
+        if let Some(answer) = headers.preflight(&request) {
+            copy_body(
+                &mut client_read,
+                &mut tokio::io::sink(),
+                request_body(&request),
+            )
+            .await?;
+            client_write.write_all(&answer).await?;
+        } else if let Some(answer) = refusal(proxy, &request) {
+            client_write.write_all(&answer).await?;
+            break;
+        } else {
+            if headers::header(&request, "upgrade").is_some() {
+                upstream_write.write_all(&request).await?;
+                let to_upstream = async {
+                    tokio::io::copy(&mut client_read, &mut upstream_write).await?;
+                    upstream_write.shutdown().await
+                };
+                let to_client = async {
+                    tokio::io::copy(&mut upstream_read, &mut client_write).await?;
+                    client_write.shutdown().await
+                };
+                return tokio::try_join!(to_upstream, to_client).map(|_| ());
+            }
+
+            // The join form names the user, players are kept out of a locked world there and
+            // their connections are attributed to them
+            let mut join = None;
+            if is_join(&request) {
+                let locked = worlds::any_locked();
+                match join_form_length(request_body(&request)) {
+                    Some(length) => {
+                        let mut body = vec![0; length as usize];
+                        client_read.read_exact(&mut body).await?;
+                        let user = join_user(&body).unwrap_or_default();
+                        if locked
+                            && let Some(message) =
+                                worlds::join_refusal(proxy.upstream_port, &user).await
+                        {
+                            client_write.write_all(&join_refused(&message)).await?;
+                            break;
+                        }
+                        if !user.is_empty() {
+                            lock_players().insert(client_address(&request, peer), user);
+                        }
+                        join = Some(body);
+                    }
+                    // A form that cannot be read at once could name anyone, the lock has to hold
+                    None if locked => {
+                        client_write.write_all(JOIN_TOO_LARGE).await?;
+                        break;
+                    }
+                    None => {}
+                }
+            }
+
+            // The proxy does not answer `100 Continue`, clients send the body after a moment
+            upstream_write
+                .write_all(&without_header(&request, "expect"))
+                .await?;
+            match &join {
+                Some(body) => upstream_write.write_all(body).await?,
+                None => {
+                    copy_body(
+                        &mut client_read,
+                        &mut upstream_write,
+                        request_body(&request),
+                    )
+                    .await?
+                }
+            }
+
+            let response = read_head_buffered(&mut upstream_read).await?;
+            if response.is_empty() {
+                break;
+            }
+            client_write
+                .write_all(&headers.apply(&request, &response))
+                .await?;
+            let body = response_body(&request, &response);
+            copy_body(&mut upstream_read, &mut client_write, body).await?;
+            if body == Body::UntilClose || closes(&request) || closes(&response) {
+                break;
+            }
+        }
+        // Idle keep-alive connections get the same time as slow ones
+        match timeout(
+            proxy.limits.header_timeout,
+            read_head_buffered(&mut client_read),
+        )
+        .await
+        {
+            Ok(next) => request = next?,
+            Err(_) => break,
+        }
+    }
+    client_write.shutdown().await
+}
+
+/// Read a head up to and including the empty line, nothing when the connection closed
+async fn read_head_buffered<R>(reader: &mut R) -> std::io::Result<Vec<u8>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut head = Vec::new();
+    loop {
+        let read = reader.read_until(b'\n', &mut head).await?;
+        if read == 0 || head.ends_with(b"\r\n\r\n") {
+            return Ok(head);
+        }
+        // Empty lines between keep-alive requests are allowed
+        if head == b"\r\n" {
+            head.clear();
+        }
+        if head.len() > MAX_HEAD {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "HTTP head too large",
+            ));
+        }
+    }
+}
+
+fn request_body(request: &[u8]) -> Body {
+    if headers::header(request, "transfer-encoding").is_some_and(|te| te.contains("chunked")) {
+        Body::Chunked
+    } else {
+        content_length(request).map_or(Body::None, Body::Length)
+    }
+}
+
+fn response_body(request: &[u8], response: &[u8]) -> Body {
+    let status = String::from_utf8_lossy(response)
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .unwrap_or_default();
+    if request.starts_with(b"HEAD ") || status < 200 || status == 204 || status == 304 {
+        Body::None
+    } else if headers::header(response, "transfer-encoding")
+        .is_some_and(|te| te.contains("chunked"))
+    {
+        Body::Chunked
+    } else {
+        content_length(response).map_or(Body::UntilClose, Body::Length)
+    }
+}
+
+fn content_length(head: &[u8]) -> Option<u64> {
+    headers::header(head, "content-length")?.parse().ok()
+}
+
+/// Whether the connection ends after this request or response
+fn closes(head: &[u8]) -> bool {
+    let connection = headers::header(head, "connection")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let first_line = head.split(|b| *b == b'\n').next().unwrap_or_default();
+    let http_10 = first_line.windows(8).any(|w| w == b"HTTP/1.0");
+    connection.contains("close") || (http_10 && !connection.contains("keep-alive"))
+}
+
+async fn copy_body<R, W>(reader: &mut R, writer: &mut W, body: Body) -> std::io::Result<()>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    match body {
+        Body::None => Ok(()),
+        Body::Length(length) => copy_exactly(reader, writer, length).await,
+        Body::UntilClose => tokio::io::copy(reader, writer).await.map(|_| ()),
+        Body::Chunked => loop {
+            let mut line = Vec::new();
+            reader.read_until(b'\n', &mut line).await?;
+            writer.write_all(&line).await?;
+            let size = String::from_utf8_lossy(&line);
+            let size = size.split(';').next().unwrap_or_default().trim();
+            let size = u64::from_str_radix(size, 16).map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, "Malformed chunk size")
+            })?;
+            if size > 0 {
+                // The chunk and the line break after it
+                copy_exactly(reader, writer, size + 2).await?;
+                continue;
+            }
+            // Trailers up to the empty line
+            loop {
+                line.clear();
+                let read = reader.read_until(b'\n', &mut line).await?;
+                writer.write_all(&line).await?;
+                if read == 0 || line == b"\r\n" {
+                    return Ok(());
+                }
+            }
+        },
+    }
+}
+
+async fn copy_exactly<R, W>(reader: &mut R, writer: &mut W, length: u64) -> std::io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let copied = tokio::io::copy(&mut reader.take(length), writer).await?;
+    if copied < length {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// The answer refusing a request that did not pass the login, CORS preflights carry no
+/// credentials and are answered anyway
+fn refusal(proxy: &Proxy, request: &[u8]) -> Option<Vec<u8>> {
+    if proxy.headers.preflight(request).is_some() {
+        return None;
+    }
+    proxy.auth.as_ref()?.check(request)
+}
+
+/// Whether the request submits Foundry's join form, below a route prefix too
+fn is_join(request: &[u8]) -> bool {
+    let path = request_path(request);
+    let path = path.split('?').next().unwrap_or_default();
+    request.starts_with(b"POST ") && (path == "/join" || path.ends_with("/join"))
+}
+
+/// Length of a join form the proxy can read in one piece, `None` for chunked or oversized ones
+fn join_form_length(body: Body) -> Option<u64> {
+    match body {
+        Body::None => Some(0),
+        Body::Length(length) if length <= MAX_HEAD as u64 => Some(length),
+        _ => None,
+    }
+}
+
+/// Answer to join forms of a locked world that are sent chunked or too large to check
+const JOIN_TOO_LARGE: &[u8] =
+    b"HTTP/1.1 413 Payload Too Large\r\nConnection: close\r\nContent-Length: 0\r\n\r\n";
+
+/// Id of the user a join form names, sent as JSON, URL encoded or multipart form
+fn join_user(body: &[u8]) -> Option<String> {
+    if let Ok(form) = serde_json::from_slice::<serde_json::Value>(body) {
+        return form["userid"].as_str().map(str::to_string);
+    }
+    let body = String::from_utf8_lossy(body);
+    if let Some((_, rest)) = body.split_once("name=\"userid\"") {
+        let value = rest.split_once("\r\n\r\n")?.1;
+        return value.split("\r\n").next().map(str::to_string);
+    }
+    body.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == "userid").then(|| value.to_string())
+    })
+}
+
+/// Foundry's own answer to a failed join, its join screen shows the message
+fn join_refused(message: &str) -> Vec<u8> {
+    let body = serde_json::json!({
+        "request": "join",
+        "status": "failed",
+        "error": message,
+        "message": message,
+    })
+    .to_string();
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+    .into_bytes()
+}
+
+/// Answer with an error status and close the connection
+async fn reject<S: ClientStream>(mut client: S, status: &str) -> std::io::Result<()> {
+    let answer = format!(
+        "HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+        status
+    );
+    client.write_all(answer.as_bytes()).await?;
+    client.shutdown().await
+}
+
+static OPEN_CONNECTIONS: Mutex<BTreeMap<IpAddr, u32>> = Mutex::new(BTreeMap::new());
+
+fn lock_open_connections() -> MutexGuard<'static, BTreeMap<IpAddr, u32>> {
+    OPEN_CONNECTIONS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// One of the connections a client address may have open, given back when dropped
+struct ConnectionSlot(IpAddr);
+
+impl ConnectionSlot {
+    fn acquire(ip: IpAddr, limit: u32) -> Option<Self> {
+        let mut open = lock_open_connections();
+        let count = open.entry(ip).or_default();
+        if limit > 0 && *count >= limit {
+            return None;
+        }
+        *count += 1;
+        Some(Self(ip))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        let mut open = lock_open_connections();
+        if let Some(count) = open.get_mut(&self.0) {
+            *count -= 1;
+            if *count == 0 {
+                open.remove(&self.0);
+            }
+        }
+    }
+}
+
+/// Read until the end of the request head, the bytes read are forwarded unchanged
+async fn read_head<S: ClientStream>(client: &mut S) -> std::io::Result<Vec<u8>> {
+    let mut head = Vec::new();
+    let mut buffer = [0u8; 4096];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_HEAD {
+        let read = client.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buffer[..read]);
+    }
+    Ok(head)
+}
+
+/// Client address of a websocket upgrade to Foundry's socket.io endpoint
+fn game_socket_client(head: &[u8], peer: SocketAddr) -> Option<String> {
+    let path = request_path(head);
+    let upgrade = headers::header(head, "upgrade")
+        .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+    (path.starts_with("/socket.io/") && upgrade).then(|| client_address(head, peer))
+}
+
+/// Address a request comes from.
+///
+/// Behind a reverse proxy every connection comes from the same address, the address it
+/// forwarded for is used then.
+fn client_address(head: &[u8], peer: SocketAddr) -> String {
+    headers::header(head, "x-forwarded-for")
+        .and_then(|addresses| addresses.split(',').next().map(|ip| ip.trim().to_string()))
+        .or_else(|| headers::header(head, "x-real-ip"))
+        .unwrap_or_else(|| peer.ip().to_string())
+}
+
+/// Copy from `reader` to `writer` until either side closes, reporting every chunk copied
+async fn pump<R, W>(reader: &mut R, writer: &mut W, copied: impl Fn(u64)) -> std::io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buffer = vec![0u8; 16 * 1024];
+    loop {
+        let read = reader.read(&mut buffer).await?;
+        if read == 0 {
+            return writer.shutdown().await;
+        }
+        writer.write_all(&buffer[..read]).await?;
+        copied(read as u64);
+    }
+}
+
+/// Like `pump`, but splits the stream into websocket frames to delay and drop them as `chaos`
+/// says and hand the forwarded ones to `recorder`.
+///
+/// Reading goes on while delayed chunks wait, so latency does not limit the bandwidth.
+async fn frame_pump<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    chaos: &Chaos,
+    recorder: Option<&Recorder>,
+    from_client: bool,
+    copied: impl Fn(u64),
+) -> std::io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let (tx, mut rx) = mpsc::unbounded_channel::<(Instant, Vec<u8>)>();
+    // Foundry answers with the response head of the upgrade before the first frame
+    let mut response_head = !from_client;
+
+    let read = async move {
+        let mut rng = Rng::new();
+        let mut buffer = vec![0u8; 16 * 1024];
+        let mut pending = Vec::new();
+        let mut due = Instant::now();
+        loop {
+            let read = reader.read(&mut buffer).await?;
+            if read == 0 {
+                // Dropping the sender lets the writer finish the delayed chunks
+                return Ok::<_, std::io::Error>(());
+            }
+            pending.extend_from_slice(&buffer[..read]);
+
+            let mut chunk = Vec::new();
+            if response_head {
+                let Some(end) = pending.windows(4).position(|w| w == b"\r\n\r\n") else {
+                    continue;
+                };
+                chunk.extend(pending.drain(..end + 4));
+                response_head = false;
+            }
+            while let Some(len) = frame_len(&pending) {
+                let frame: Vec<u8> = pending.drain(..len).collect();
+                if is_message(&frame) && rng.next_f64() < chaos.drop_rate {
+                    debug!("Chaos mode dropped a {} byte game message", len);
+                    continue;
+                }
+                if let Some(recorder) = recorder {
+                    recorder.record(&frame, from_client);
+                }
+                chunk.extend(frame);
+            }
+            if chunk.is_empty() {
+                continue;
+            }
+
+            due = due.max(Instant::now() + chaos.delay(&mut rng));
+            if tx.send((due, chunk)).is_err() {
+                return Ok(());
+            }
+        }
+    };
+
+    let write = async {
+        while let Some((due, chunk)) = rx.recv().await {
+            tokio::time::sleep_until(due).await;
+            writer.write_all(&chunk).await?;
+            copied(chunk.len() as u64);
+        }
+        writer.shutdown().await
+    };
+
+    tokio::try_join!(read, write).map(|_| ())
+}
+
+impl Chaos {
+    pub fn is_active(&self) -> bool {
+        !self.latency.is_zero() || !self.jitter.is_zero() || self.drop_rate > 0.0
+    }
+
+    /// Latency of the next chunk, varied by the jitter
+    fn delay(&self, rng: &mut Rng) -> Duration {
+        let variation = self.jitter.as_secs_f64() * (rng.next_f64() * 2.0 - 1.0);
+        Duration::from_secs_f64((self.latency.as_secs_f64() + variation).max(0.0))
+    }
+}
+
+/// Length of the websocket frame at the start of `buffer` once it is complete
+fn frame_len(buffer: &[u8]) -> Option<usize> {
+    let [_, second, ..] = buffer else {
+        return None;
+    };
+    let (header, payload) = match second & 0x7f {
+        126 => (
+            4,
+            u16::from_be_bytes(buffer.get(2..4)?.try_into().ok()?) as usize,
+        ),
+        127 => (
+            10,
+            usize::try_from(u64::from_be_bytes(buffer.get(2..10)?.try_into().ok()?)).ok()?,
+        ),
+        len => (2, len as usize),
+    };
+    // Frames sent by clients are masked with a four byte key
+    let mask = if second & 0x80 != 0 { 4 } else { 0 };
+    let len = header + mask + payload;
+    (buffer.len() >= len).then_some(len)
+}
+
+/// Whether a frame is a complete text or binary message. Control frames and fragments are
+/// never dropped, losing them breaks the websocket instead of losing a message.
+fn is_message(frame: &[u8]) -> bool {
+    let fin = frame[0] & 0x80 != 0;
+    fin && matches!(frame[0] & 0x0f, 0x1 | 0x2)
+}
+
+/// Path of the request line, e.g. `/socket.io/?session=…&EIO=4&transport=websocket`
+fn request_path(head: &[u8]) -> String {
+    String::from_utf8_lossy(head)
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .unwrap_or("/")
+        .to_string()
+}
+
+/// The request head without the header `name` (lowercase), e.g. `Sec-WebSocket-Extensions` so
+/// Foundry does not compress frames
+fn without_header(head: &[u8], name: &str) -> Vec<u8> {
+    let Some(end) = head.windows(4).position(|w| w == b"\r\n\r\n") else {
+        return head.to_vec();
+    };
+    let mut stripped = Vec::with_capacity(head.len());
+    for line in head[..end].split(|b| *b == b'\n') {
+        let matches = line
+            .split(|b| *b == b':')
+            .next()
+            .is_some_and(|key| key.trim_ascii().eq_ignore_ascii_case(name.as_bytes()));
+        if !matches {
+            stripped.extend_from_slice(line);
+            stripped.push(b'\n');
+        }
+    }
+    stripped.pop();
+    stripped.extend_from_slice(&head[end..]);
+    stripped
+}
+
+/// Xorshift generator, chaos mode needs no cryptographic randomness
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        Self(RandomState::new().build_hasher().finish() | 1)
+    }
+
+    /// Uniform in `0..1`
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn connection_opened(client: &str) {
+    let now = Instant::now();
+    let mut stats = lock_stats();
+    let entry = stats
+        .entry(client.to_string())
+        .or_insert_with(|| ClientStats {
+            client: client.to_string(),
+            user_id: None,
+            player: None,
+            active: 0,
+            connections: 0,
+            recent_reconnects: 0,
+            bytes_to_client: 0,
+            bytes_from_client: 0,
+            last_connected: Utc::now().to_rfc3339(),
+            connected_at: VecDeque::new(),
+        });
+    entry.active += 1;
+    entry.connections += 1;
+    entry.last_connected = Utc::now().to_rfc3339();
+    entry.connected_at.push_back(now);
+    while entry
+        .connected_at
+        .front()
+        .is_some_and(|at| now.duration_since(*at) > RECONNECT_WINDOW)
+    {
+        entry.connected_at.pop_front();
+    }
+    debug!(
+        "Game connection opened by {} ({} open)",
+        client, entry.active
+    );
+}
+
+fn connection_closed(client: &str) {
+    if let Some(entry) = lock_stats().get_mut(client) {
+        entry.active = entry.active.saturating_sub(1);
+    }
+}
+
+fn record_bytes(client: &str, update: impl FnOnce(&mut ClientStats)) {
+    if let Some(entry) = lock_stats().get_mut(client) {
+        update(entry);
+    }
+}
+
+/// Where `listener` accepts, an activated socket may be bound elsewhere than configured
+pub fn address(listener: &TcpListener, host: &str, port: u16) -> String {
+    listener.local_addr().map_or_else(
+        |_| format!("{}:{}", host, port),
+        |address| address.to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_posts_are_recognized() {
+        assert!(is_join(b"POST /join HTTP/1.1\r\nHost: vtt\r\n\r\n"));
+        assert!(is_join(b"POST /vtt/join?x=1 HTTP/1.1\r\n\r\n"));
+        assert!(!is_join(b"GET /join HTTP/1.1\r\n\r\n"));
+        assert!(!is_join(b"POST /joined HTTP/1.1\r\n\r\n"));
+    }
+
+    #[test]
+    fn only_join_forms_read_at_once_are_let_through() {
+        assert_eq!(join_form_length(Body::Length(120)), Some(120));
+        assert_eq!(join_form_length(Body::None), Some(0));
+        assert_eq!(join_form_length(Body::Length(MAX_HEAD as u64 + 1)), None);
+        assert_eq!(join_form_length(Body::Chunked), None);
+    }
+
+    #[test]
+    fn join_user_is_read_from_every_form_encoding() {
+        let json = br#"{"userid":"aB3dE5","password":"","action":"join"}"#;
+        assert_eq!(join_user(json).as_deref(), Some("aB3dE5"));
+        assert_eq!(
+            join_user(b"action=join&userid=aB3dE5&password=").as_deref(),
+            Some("aB3dE5")
+        );
+        let multipart = b"------x\r\nContent-Disposition: form-data; name=\"userid\"\r\n\r\naB3dE5\r\n------x--\r\n";
+        assert_eq!(join_user(multipart).as_deref(), Some("aB3dE5"));
+        assert_eq!(join_user(b"password=secret"), None);
+    }
+
+    #[test]
+    fn connections_are_attributed_to_the_forwarded_address() {
+        let peer: SocketAddr = "10.0.0.2:5123".parse().unwrap();
+        let upgrade =
+            b"GET /socket.io/?EIO=4&transport=websocket HTTP/1.1\r\nUpgrade: websocket\r\n\r\n";
+        assert_eq!(
+            game_socket_client(upgrade, peer).as_deref(),
+            Some("10.0.0.2")
+        );
+        let forwarded = b"GET /socket.io/?EIO=4 HTTP/1.1\r\nUpgrade: WebSocket\r\nX-Forwarded-For: 203.0.113.7, 10.0.0.1\r\n\r\n";
+        assert_eq!(
+            game_socket_client(forwarded, peer).as_deref(),
+            Some("203.0.113.7")
+        );
+        assert_eq!(
+            game_socket_client(b"GET /socket.io/?EIO=4 HTTP/1.1\r\n\r\n", peer),
+            None
+        );
+        let join = b"POST /join HTTP/1.1\r\nX-Real-IP: 198.51.100.4\r\n\r\n";
+        assert_eq!(client_address(join, peer), "198.51.100.4");
+    }
+
+    #[test]
+    fn refused_joins_carry_the_notice() {
+        let answer = String::from_utf8(join_refused("Back at 8pm")).unwrap();
+        let (head, body) = answer.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains(&format!("Content-Length: {}", body.len())));
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["status"], "failed");
+        assert_eq!(body["error"], "Back at 8pm");
+    }
+}
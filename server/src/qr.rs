@@ -0,0 +1,662 @@
+use anyhow::{Result, anyhow};
+
+/// Error correction codewords per block and number of blocks at level M, by version.
+/// Versions up to 10 hold 213 bytes, plenty for a join URL.
+const ECC_PER_BLOCK: [usize; 11] = [0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26];
+const BLOCKS: [usize; 11] = [0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5];
+const MAX_VERSION: usize = 10;
+/// Light modules around the code that scanners need to find it
+const QUIET_ZONE: usize = 4;
+
+/// QR code of a byte string at error correction level M
+pub struct QrCode {
+    size: usize,
+    modules: Vec<bool>,
+    function: Vec<bool>,
+}
+
+impl QrCode {
+    pub fn encode(text: &str) -> Result<Self> {
+        let data = text.as_bytes();
+        let version = (1..=MAX_VERSION)
+            .find(|v| 4 + count_bits(*v) + data.len() * 8 <= data_codewords(*v) * 8)
+            .ok_or_else(|| anyhow!("{} bytes do not fit into a QR code", data.len()))?;
+
+        let mut bits = Vec::new();
+        push_bits(&mut bits, 0b0100, 4);
+        push_bits(&mut bits, data.len() as u32, count_bits(version));
+        for byte in data {
+            push_bits(&mut bits, *byte as u32, 8);
+        }
+        let capacity = data_codewords(version) * 8;
+        let terminator = (capacity - bits.len()).min(4);
+        push_bits(&mut bits, 0, terminator);
+        let padding = (8 - bits.len() % 8) % 8;
+        push_bits(&mut bits, 0, padding);
+        for pad in [0xEC, 0x11].into_iter().cycle() {
+            if bits.len() >= capacity {
+                break;
+            }
+            push_bits(&mut bits, pad, 8);
+        }
+        let codewords: Vec<u8> = bits
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0u8, |acc, bit| acc << 1 | *bit as u8))
+            .collect();
+
+        let size = version * 4 + 17;
+        let mut code = Self {
+            size,
+            modules: vec![false; size * size],
+            function: vec![false; size * size],
+        };
+        code.draw_function_patterns(version);
+        code.draw_codewords(&add_ecc_and_interleave(&codewords, version));
+
+        // Keep the mask that is easiest to scan
+        let mut best = (0, usize::MAX);
+        for mask in 0..8 {
+            code.apply_mask(mask);
+            code.draw_format_bits(mask);
+            let penalty = code.penalty();
+            if penalty < best.1 {
+                best = (mask, penalty);
+            }
+            code.apply_mask(mask);
+        }
+        code.apply_mask(best.0);
+        code.draw_format_bits(best.0);
+        Ok(code)
+    }
+
+    /// Two module rows per line with half blocks, light modules are drawn so the code reads
+    /// right on the usual dark terminal background
+    pub fn to_text(&self) -> Vec<String> {
+        let light = |x: isize, y: isize| !self.dark(x, y);
+        let start = -(QUIET_ZONE as isize) / 2;
+        let end = (self.size + QUIET_ZONE / 2) as isize;
+        (start..end)
+            .step_by(2)
+            .map(|y| {
+                (start..end)
+                    .map(|x| match (light(x, y), light(x, y + 1)) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Black and white PNG with `scale` pixels per module
+    pub fn to_png(&self, scale: usize) -> Vec<u8> {
+        let modules = self.size + QUIET_ZONE * 2;
+        let width = modules * scale;
+        let mut raw = Vec::with_capacity((width.div_ceil(8) + 1) * width);
+        for py in 0..width {
+            // Filter type none
+            raw.push(0);
+            let y = (py / scale) as isize - QUIET_ZONE as isize;
+            let row: Vec<bool> = (0..width)
+                .map(|px| !self.dark((px / scale) as isize - QUIET_ZONE as isize, y))
+                .collect();
+            raw.extend(row.chunks(8).map(|byte| {
+                (0..8).fold(0u8, |acc, i| acc << 1 | *byte.get(i).unwrap_or(&true) as u8)
+            }));
+        }
+
+        let mut header = Vec::new();
+        header.extend((width as u32).to_be_bytes());
+        header.extend((width as u32).to_be_bytes());
+        // One bit greyscale, default compression, filter and no interlacing
+        header.extend([1, 0, 0, 0, 0]);
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png_chunk(&mut png, b"IHDR", &header);
+        png_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+        png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    /// Modules outside the code belong to the quiet zone
+    fn dark(&self, x: isize, y: isize) -> bool {
+        let size = self.size as isize;
+        (0..size).contains(&x) && (0..size).contains(&y) && self.modules[(y * size + x) as usize]
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    fn draw_function_patterns(&mut self, version: usize) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+        for (x, y) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            self.draw_finder(x, y);
+        }
+
+        let positions = alignment_positions(version);
+        let last = positions.len().saturating_sub(1);
+        for (i, x) in positions.iter().enumerate() {
+            for (j, y) in positions.iter().enumerate() {
+                // The corners with finder patterns have no alignment pattern
+                if (i, j) != (0, 0) && (i, j) != (0, last) && (i, j) != (last, 0) {
+                    self.draw_alignment(*x, *y);
+                }
+            }
+        }
+
+        // Reserve the format areas, the real bits are drawn once the mask is chosen
+        self.draw_format_bits(0);
+        if version >= 7 {
+            let mut remainder = version as u32;
+            for _ in 0..12 {
+                remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
+            }
+            let bits = (version as u32) << 12 | remainder;
+            for i in 0..18 {
+                let bit = bits >> i & 1 != 0;
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                self.set_function(a, b, bit);
+                self.set_function(b, a, bit);
+            }
+        }
+    }
+
+    fn draw_finder(&mut self, x: usize, y: usize) {
+        for dy in -4isize..=4 {
+            for dx in -4isize..=4 {
+                let (xx, yy) = (x as isize + dx, y as isize + dy);
+                let distance = dx.abs().max(dy.abs());
+                if (0..self.size as isize).contains(&xx) && (0..self.size as isize).contains(&yy) {
+                    self.set_function(xx as usize, yy as usize, distance != 2 && distance != 4);
+                }
+            }
+        }
+    }
+
+    fn draw_alignment(&mut self, x: usize, y: usize) {
+        for dy in -2isize..=2 {
+            for dx in -2isize..=2 {
+                let dark = dx.abs().max(dy.abs()) != 1;
+                self.set_function((x as isize + dx) as usize, (y as isize + dy) as usize, dark);
+            }
+        }
+    }
+
+    fn draw_format_bits(&mut self, mask: u32) {
+        // Level M is encoded as 0
+        let data = mask;
+        let mut remainder = data;
+        for _ in 0..10 {
+            remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+        }
+        let bits = (data << 10 | remainder) ^ 0x5412;
+        let bit = |i: usize| bits >> i & 1 != 0;
+
+        let size = self.size;
+        for i in 0..=5 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        self.set_function(8, size - 8, true);
+    }
+
+    /// Place the codewords in the zigzag of two module wide columns from the bottom right
+    fn draw_codewords(&mut self, data: &[u8]) {
+        let size = self.size;
+        let mut i = 0;
+        let mut right = size - 1;
+        while right >= 1 {
+            if right == 6 {
+                right = 5;
+            }
+            for vertical in 0..size {
+                for j in 0..2 {
+                    let x = right - j;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward {
+                        size - 1 - vertical
+                    } else {
+                        vertical
+                    };
+                    if !self.function[y * size + x] && i < data.len() * 8 {
+                        self.modules[y * size + x] = data[i >> 3] >> (7 - (i & 7)) & 1 != 0;
+                        i += 1;
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    /// XOR the data modules with mask pattern `mask`, applying it twice removes it again
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                let index = y * self.size + x;
+                self.modules[index] ^= invert && !self.function[index];
+            }
+        }
+    }
+
+    /// Penalty for long runs, blocks of one colour and an unbalanced ratio. The finder like
+    /// pattern rule is left out, it rarely changes which mask wins.
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let at = |x: usize, y: usize| self.modules[y * size + x];
+        let mut penalty = 0;
+
+        for horizontal in [true, false] {
+            for a in 0..size {
+                let mut run = 1;
+                for b in 1..size {
+                    let (current, previous) = if horizontal {
+                        (at(b, a), at(b - 1, a))
+                    } else {
+                        (at(a, b), at(a, b - 1))
+                    };
+                    if current == previous {
+                        run += 1;
+                        if run == 5 {
+                            penalty += 3;
+                        } else if run > 5 {
+                            penalty += 1;
+                        }
+                    } else {
+                        run = 1;
+                    }
+                }
+            }
+        }
+
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let colour = at(x, y);
+                if colour == at(x + 1, y) && colour == at(x, y + 1) && colour == at(x + 1, y + 1) {
+                    penalty += 3;
+                }
+            }
+        }
+
+        let dark = self.modules.iter().filter(|m| **m).count();
+        let total = size * size;
+        let deviation = (dark * 20).abs_diff(total * 10);
+        penalty + deviation.div_ceil(total).saturating_sub(1) * 10
+    }
+}
+
+fn push_bits(bits: &mut Vec<bool>, value: u32, count: usize) {
+    for i in (0..count).rev() {
+        bits.push(value >> i & 1 != 0);
+    }
+}
+
+/// Width of the byte count
+fn count_bits(version: usize) -> usize {
+    if version <= 9 { 8 } else { 16 }
+}
+
+/// Modules left for data and error correction after the function patterns
+fn raw_data_modules(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let alignments = version / 7 + 2;
+        modules -= (25 * alignments - 10) * alignments - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules
+}
+
+fn data_codewords(version: usize) -> usize {
+    raw_data_modules(version) / 8 - ECC_PER_BLOCK[version] * BLOCKS[version]
+}
+
+fn alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let count = version / 7 + 2;
+    let step = (version * 4 + count * 2 + 1) / (count * 2 - 2) * 2;
+    let mut positions = vec![6];
+    let mut position = version * 4 + 17 - 7;
+    for _ in 0..count - 1 {
+        positions.insert(1, position);
+        position -= step;
+    }
+    positions
+}
+
+/// Split the data into blocks, append their Reed-Solomon codewords and interleave them
+fn add_ecc_and_interleave(data: &[u8], version: usize) -> Vec<u8> {
+    let blocks = BLOCKS[version];
+    let ecc_len = ECC_PER_BLOCK[version];
+    let raw_codewords = raw_data_modules(version) / 8;
+    let short_blocks = blocks - raw_codewords % blocks;
+    let short_len = raw_codewords / blocks;
+    let divisor = reed_solomon_divisor(ecc_len);
+
+    let mut split = Vec::with_capacity(blocks);
+    let mut offset = 0;
+    for i in 0..blocks {
+        let len = short_len - ecc_len + usize::from(i >= short_blocks);
+        let mut block = data[offset..offset + len].to_vec();
+        offset += len;
+        let ecc = reed_solomon_remainder(&block, &divisor);
+        if i < short_blocks {
+            // Placeholder so all blocks line up, skipped when interleaving
+            block.push(0);
+        }
+        block.extend(ecc);
+        split.push(block);
+    }
+
+    let mut result = Vec::with_capacity(raw_codewords);
+    for i in 0..split[0].len() {
+        for (j, block) in split.iter().enumerate() {
+            if i != short_len - ecc_len || j >= short_blocks {
+                result.push(block[i]);
+            }
+        }
+    }
+    result
+}
+
+fn reed_solomon_divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0u8; degree];
+    result[degree - 1] = 1;
+    let mut root = 1u8;
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = gf_multiply(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+        root = gf_multiply(root, 0x02);
+    }
+    result
+}
+
+fn reed_solomon_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0u8; divisor.len()];
+    for byte in data {
+        let factor = byte ^ result.remove(0);
+        result.push(0);
+        for (x, y) in result.iter_mut().zip(divisor) {
+            *x ^= gf_multiply(*y, factor);
+        }
+    }
+    result
+}
+
+/// Multiplication in GF(2^8) modulo x^8 + x^4 + x^3 + x^2 + 1
+fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut z = 0u8;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x1D);
+        z ^= ((y >> i) & 1) * x;
+    }
+    z
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+/// Zlib stream of uncompressed deflate blocks, a QR code image is small enough as it is
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        out.extend([1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(u8::from(blocks.peek().is_none()));
+        let len = block.len() as u16;
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend(block);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    out.extend((b << 16 | a).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    /// Format information of level M for masks 0 to 7, as tabulated in ISO/IEC 18004
+    const FORMAT_M: [u32; 8] = [
+        0b101010000010010,
+        0b101000100100101,
+        0b101111001111100,
+        0b101101101001011,
+        0b100010111111001,
+        0b100000011001110,
+        0b100111110010111,
+        0b100101010100000,
+    ];
+
+    fn module(code: &QrCode, x: usize, y: usize) -> bool {
+        code.modules[y * code.size + x]
+    }
+
+    /// Both copies of the format information, bit 14 first
+    fn format_bits(code: &QrCode) -> (u32, u32) {
+        let size = code.size;
+        let mut first = Vec::new();
+        first.extend((0..=5).map(|y| (8, y)));
+        first.extend([(8, 7), (8, 8), (7, 8)]);
+        first.extend((9..15).map(|i| (14 - i, 8)));
+        let mut second: Vec<(usize, usize)> = (0..8).map(|i| (size - 1 - i, 8)).collect();
+        second.extend((8..15).map(|i| (8, size - 15 + i)));
+        let read = |positions: &[(usize, usize)]| {
+            positions.iter().enumerate().fold(0, |bits, (i, (x, y))| {
+                bits | (module(code, *x, *y) as u32) << i
+            })
+        };
+        (read(&first), read(&second))
+    }
+
+    /// Data modules in placement order: two module wide columns from the right, alternately
+    /// upwards and downwards, skipping the vertical timing pattern
+    fn data_positions(code: &QrCode) -> Vec<(usize, usize)> {
+        let size = code.size;
+        let columns = (0..size).rev().filter(|x| *x != 6).collect::<Vec<_>>();
+        let mut positions = Vec::new();
+        for (pair, columns) in columns.chunks(2).enumerate() {
+            let rows: Vec<usize> = match pair % 2 {
+                0 => (0..size).rev().collect(),
+                _ => (0..size).collect(),
+            };
+            for y in rows {
+                for x in columns {
+                    if !code.function[y * size + x] {
+                        positions.push((*x, y));
+                    }
+                }
+            }
+        }
+        positions
+    }
+
+    #[test]
+    fn reed_solomon_matches_the_published_example() {
+        // HELLO WORLD at 1-M from the thonky.com QR code tutorial
+        let data = [
+            32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236, 17, 236, 17,
+        ];
+        let ecc = reed_solomon_remainder(&data, &reed_solomon_divisor(10));
+        assert_eq!(ecc, [196, 35, 39, 119, 235, 215, 231, 226, 93, 23]);
+    }
+
+    #[test]
+    fn hello_is_drawn_as_a_version_1_code_that_reads_back() {
+        let code = QrCode::encode("HELLO").unwrap();
+        assert_eq!(code.size, 21);
+
+        // Finder patterns in three corners, timing patterns between them and the dark module
+        for (cx, cy) in [(3, 3), (17, 3), (3, 17)] {
+            for dy in -3isize..=3 {
+                for dx in -3isize..=3 {
+                    let dark = dx.abs().max(dy.abs()) != 2;
+                    let (x, y) = ((cx + dx) as usize, (cy + dy) as usize);
+                    assert_eq!(module(&code, x, y), dark, "finder at {},{}", x, y);
+                }
+            }
+        }
+        for i in 8..13 {
+            assert_eq!(module(&code, i, 6), i % 2 == 0);
+            assert_eq!(module(&code, 6, i), i % 2 == 0);
+        }
+        assert!(module(&code, 8, 13));
+
+        let (first, second) = format_bits(&code);
+        assert_eq!(first, second);
+        let mask = FORMAT_M
+            .iter()
+            .position(|bits| *bits == first)
+            .expect("format information of level M") as u32;
+
+        // "HELLO" in byte mode: mode 0100, length 5, the bytes, terminator, then padding
+        let mut expected = vec![0x40, 0x54, 0x84, 0x54, 0xC4, 0xC4, 0xF0];
+        expected.extend([0xEC, 0x11].iter().cycle().take(9));
+        let ecc = reed_solomon_remainder(&expected, &reed_solomon_divisor(10));
+        expected.extend(ecc);
+
+        let mut unmasked = QrCode {
+            size: code.size,
+            modules: code.modules.clone(),
+            function: code.function.clone(),
+        };
+        unmasked.apply_mask(mask);
+        let bits: Vec<bool> = data_positions(&unmasked)
+            .into_iter()
+            .map(|(x, y)| module(&unmasked, x, y))
+            .collect();
+        let codewords: Vec<u8> = bits
+            .chunks(8)
+            .take(expected.len())
+            .map(|byte| byte.iter().fold(0u8, |acc, bit| acc << 1 | *bit as u8))
+            .collect();
+        assert_eq!(codewords, expected);
+    }
+
+    #[test]
+    fn version_7_carries_its_version_information() {
+        let code = QrCode::encode(&"x".repeat(120)).unwrap();
+        assert_eq!(code.size, 7 * 4 + 17);
+        let bits = (0..18).fold(0u32, |bits, i| {
+            let (a, b) = (code.size - 11 + i % 3, i / 3);
+            assert_eq!(module(&code, a, b), module(&code, b, a));
+            bits | (module(&code, a, b) as u32) << i
+        });
+        // Version 7 from the version information table of ISO/IEC 18004
+        assert_eq!(bits, 0b000111110010010100);
+    }
+
+    #[test]
+    fn capacity_ends_with_version_10() {
+        assert_eq!(QrCode::encode(&"x".repeat(213)).unwrap().size, 57);
+        assert!(QrCode::encode(&"x".repeat(214)).is_err());
+    }
+
+    #[test]
+    fn crc32_matches_the_reference() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i * 31 % 251) as u8).collect();
+        assert_eq!(crc32(&data), crc32fast::hash(&data));
+    }
+
+    #[test]
+    fn zlib_streams_inflate_to_the_input() {
+        assert_eq!(
+            zlib_stored(b"Wikipedia"),
+            b"\x78\x01\x01\x09\x00\xf6\xffWikipedia\x11\xe6\x03\x98"
+        );
+        // Several blocks, the last one marked final
+        let data: Vec<u8> = (0..150_000u32).map(|i| (i % 253) as u8).collect();
+        for input in [&data[..], &[]] {
+            let mut inflated = Vec::new();
+            flate2::read::ZlibDecoder::new(&zlib_stored(input)[..])
+                .read_to_end(&mut inflated)
+                .unwrap();
+            assert_eq!(inflated, input);
+        }
+    }
+
+    #[test]
+    fn png_chunks_carry_their_checksums() {
+        let png = QrCode::encode("HELLO").unwrap().to_png(2);
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        let mut rest = &png[8..];
+        let mut kinds = Vec::new();
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let chunk = &rest[4..8 + length];
+            let crc = u32::from_be_bytes(rest[8 + length..12 + length].try_into().unwrap());
+            assert_eq!(crc, crc32fast::hash(chunk));
+            kinds.push(String::from_utf8_lossy(&chunk[..4]).to_string());
+            rest = &rest[12 + length..];
+        }
+        assert_eq!(kinds, ["IHDR", "IDAT", "IEND"]);
+        // 21 modules and the quiet zone of 4 on each side, 2 pixels each
+        assert_eq!(&png[16..24], [0, 0, 0, 58, 0, 0, 0, 58]);
+    }
+}
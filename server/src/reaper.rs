@@ -0,0 +1,173 @@
+use nix::sys::prctl;
+use nix::sys::signal::{Signal, kill};
+use nix::sys::wait::{WaitPidFlag, waitpid};
+use nix::unistd::{Pid, getpid};
+use std::collections::HashSet;
+use std::fs;
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::time::{Duration, Instant, interval, sleep};
+use tracing::{debug, info, warn};
+
+/// How long processes may take to disappear after SIGKILL, e.g. stuck in uninterruptible I/O
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Make orphaned descendants (hook scripts, node workers) get reparented to the wrapper
+pub fn init() {
+    if let Err(e) = prctl::set_child_subreaper(true) {
+        warn!("Failed to become a child subreaper: {}", e);
+    }
+    if getpid().as_raw() == 1 {
+        info!("Running as PID 1, orphaned processes are reaped by the wrapper");
+    }
+}
+
+/// Reap zombies nobody waits for.
+///
+/// Children spawned through tokio are reaped by tokio itself, so only zombies that are still
+/// around on the next scan are collected here.
+pub async fn run() {
+    let mut sigchld = signal(SignalKind::child()).expect("Failed to register SIGCHLD handler");
+    let mut ticker = interval(Duration::from_secs(30));
+    let mut previous_scan = HashSet::new();
+
+    loop {
+        tokio::select! {
+            _ = sigchld.recv() => {
+                // Give tokio the chance to collect its own children first
+                sleep(Duration::from_secs(1)).await;
+            }
+            _ = ticker.tick() => {}
+        }
+
+        let own_pid = getpid().as_raw();
+        let zombies: HashSet<i32> = processes()
+            .into_iter()
+            .filter(|process| process.zombie && process.ppid == own_pid)
+            .map(|process| process.pid)
+            .collect();
+        for pid in zombies.intersection(&previous_scan) {
+            match waitpid(Pid::from_raw(*pid), Some(WaitPidFlag::WNOHANG)) {
+                Ok(status) => debug!("Reaped orphaned process {}: {:?}", pid, status),
+                Err(e) => debug!("Process {} was already reaped: {}", pid, e),
+            }
+        }
+        previous_scan = zombies;
+    }
+}
+
+/// Living processes in the process group `pgid` or below `pgid` in the process tree.
+///
+/// Descendants that left the group with `setsid` are still found as long as their parent is alive.
+pub fn descendants(pgid: u32) -> HashSet<i32> {
+    let pgid = pgid as i32;
+    let processes = processes();
+    let mut found: HashSet<i32> = processes
+        .iter()
+        .filter(|process| process.pgid == pgid || process.pid == pgid)
+        .map(|process| process.pid)
+        .collect();
+
+    // Follow the parent links until no new children turn up
+    loop {
+        let children: Vec<i32> = processes
+            .iter()
+            .filter(|process| found.contains(&process.ppid) && !found.contains(&process.pid))
+            .map(|process| process.pid)
+            .collect();
+        if children.is_empty() {
+            break;
+        }
+        found.extend(children);
+    }
+
+    let zombies: HashSet<i32> = processes
+        .iter()
+        .filter(|process| process.zombie)
+        .map(|process| process.pid)
+        .collect();
+    &found - &zombies
+}
+
+/// Stop processes left behind by Foundry, escalating to SIGKILL after `grace`
+pub async fn terminate_stray(pids: &HashSet<i32>, grace: Duration) {
+    let mut remaining = alive(pids);
+    if remaining.is_empty() {
+        return;
+    }
+    warn!(
+        "Stopping {} stray processes left behind by FoundryVTT: {:?}",
+        remaining.len(),
+        remaining
+    );
+    for pid in &remaining {
+        let _ = kill(Pid::from_raw(*pid), Signal::SIGTERM);
+    }
+
+    let deadline = Instant::now() + grace;
+    while !remaining.is_empty() && Instant::now() < deadline {
+        sleep(Duration::from_millis(200)).await;
+        remaining = alive(&remaining);
+    }
+
+    if !remaining.is_empty() {
+        warn!("Killing stray processes ignoring SIGTERM: {:?}", remaining);
+        for pid in &remaining {
+            let _ = kill(Pid::from_raw(*pid), Signal::SIGKILL);
+        }
+        // Ports are only released once the processes are really gone
+        let deadline = Instant::now() + KILL_TIMEOUT;
+        while !remaining.is_empty() && Instant::now() < deadline {
+            sleep(Duration::from_millis(100)).await;
+            remaining = alive(&remaining);
+        }
+        if !remaining.is_empty() {
+            warn!(
+                "Stray processes still running {}s after SIGKILL, continuing without them: {:?}",
+                KILL_TIMEOUT.as_secs(),
+                remaining
+            );
+        }
+    }
+}
+
+/// The subset of `pids` that is still running
+fn alive(pids: &HashSet<i32>) -> HashSet<i32> {
+    pids.iter()
+        .copied()
+        .filter(|pid| read_process(*pid).is_some_and(|process| !process.zombie))
+        .collect()
+}
+
+struct ProcessInfo {
+    pid: i32,
+    ppid: i32,
+    pgid: i32,
+    zombie: bool,
+}
+
+fn processes() -> Vec<ProcessInfo> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_string_lossy().parse::<i32>().ok())
+        .filter_map(read_process)
+        .collect()
+}
+
+fn read_process(pid: i32) -> Option<ProcessInfo> {
+    // `/proc/<pid>/stat` is `pid (comm) state ppid pgrp ...`, comm may contain spaces
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let (_, rest) = stat.rsplit_once(')')?;
+    let mut fields = rest.split_whitespace();
+    let zombie = fields.next()? == "Z";
+    let ppid = fields.next()?.parse().ok()?;
+    let pgid = fields.next()?.parse().ok()?;
+    Some(ProcessInfo {
+        pid,
+        ppid,
+        pgid,
+        zombie,
+    })
+}
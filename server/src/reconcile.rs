@@ -0,0 +1,76 @@
+use anyhow::Result;
+use tracing::{info, warn};
+
+use crate::notify::{self, Notification, Severity};
+use crate::packages::{describe_changes, installed_packages};
+use crate::state::WrapperState;
+use crate::updater;
+
+/// Record the installed core and packages as known to the wrapper, called after the wrapper
+/// changed them itself so the change is not mistaken for one made in Foundry
+pub fn record() {
+    let recorded = WrapperState::load().and_then(|mut state| {
+        state.known_packages = installed_packages();
+        state.save()
+    });
+    if let Err(e) = recorded {
+        warn!("Failed to record the installed packages: {:#}", e);
+    }
+}
+
+/// Adopt core updates and package changes made in Foundry's setup screen since the last launch.
+///
+/// Without `FOUNDRY_NO_UPDATE` players with the admin password can update the core and install
+/// packages from the UI. The wrapper keeps what they installed: a core updated in place of a
+/// side by side release is moved to the directory of its version, a release rejected by
+/// `AUTO_UPDATE` is tried again once someone installed it by hand, and the changes are logged.
+/// A core installed that way is also reported as a warning.
+pub fn adopt() {
+    if let Err(e) = adopt_changes() {
+        warn!("⚠️ Failed to reconcile changes made in Foundry: {:#}", e);
+    }
+}
+
+fn adopt_changes() -> Result<()> {
+    let mut state = WrapperState::load()?;
+    let installed = installed_packages();
+    if state.known_packages.is_empty() {
+        state.known_packages = installed;
+        return state.save();
+    }
+    let changes = describe_changes(&state.known_packages, &installed);
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    info!("Packages changed in Foundry since the last launch:");
+    for change in &changes {
+        info!("  - {}", change);
+    }
+    let core = installed
+        .get("core")
+        .filter(|core| state.known_packages.get("core") != Some(*core))
+        .cloned();
+    if let Some(core) = &core {
+        if updater::side_by_side() {
+            updater::rename_current(core)?;
+        }
+        state.rejected_releases.retain(|rejected| rejected != core);
+    }
+    state.known_packages = installed;
+    state.save()?;
+
+    // Package updates from the setup screen are routine, the core is what the wrapper installs
+    if let Some(core) = core {
+        notify::send(Notification {
+            severity: Severity::Warning,
+            title: "Foundry updated from its setup screen".to_string(),
+            message: format!(
+                "Foundry {} was installed outside the wrapper and adopted: {}",
+                core,
+                changes.join(", ")
+            ),
+        });
+    }
+    Ok(())
+}
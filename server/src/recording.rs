@@ -0,0 +1,269 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::Utc;
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use tokio::time::{Duration, Instant, sleep, sleep_until};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::permissions::{self, FileKind};
+
+/// How long Foundry has to stay quiet after the last recorded message before replay ends
+const REPLAY_LINGER: Duration = Duration::from_secs(3);
+
+/// First line of a recording, describing the recorded connection
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub client: String,
+    /// Request path of the websocket, including Foundry's query string
+    pub path: String,
+    pub started: String,
+}
+
+/// One websocket message of a recording, one JSON line each after the header
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// Milliseconds since the connection opened
+    pub at_ms: u64,
+    pub from_client: bool,
+    /// Websocket opcode, 1 for text, 2 for binary and 0 for continuations
+    pub opcode: u8,
+    /// Text payload, or the binary payload as hex
+    pub payload: String,
+}
+
+/// Writes the game websocket of one connection to `PROXY_RECORD_DIR` while the proxy
+/// forwards it
+pub struct Recorder {
+    pub path: PathBuf,
+    started: Instant,
+    file: Mutex<BufWriter<File>>,
+}
+
+impl Recorder {
+    pub fn create(dir: &str, client: &str, request_path: &str) -> Result<Self> {
+        permissions::create_dir_all(Path::new(dir))?;
+        let started = Utc::now();
+        let name = format!(
+            "{}-{}.jsonl",
+            started.format("%Y%m%d-%H%M%S%.3f"),
+            client.replace([':', '.', '/'], "_")
+        );
+        let path = Path::new(dir).join(name);
+        // Everything players type ends up in the recording, chat and passwords included
+        let mut file = BufWriter::new(permissions::create(&path, FileKind::Secret)?);
+        let header = RecordingHeader {
+            client: client.to_string(),
+            path: request_path.to_string(),
+            started: started.to_rfc3339(),
+        };
+        writeln!(file, "{}", serde_json::to_string(&header)?)?;
+        Ok(Self {
+            path,
+            started: Instant::now(),
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Record a complete websocket frame, control frames are left out
+    pub fn record(&self, frame: &[u8], from_client: bool) {
+        let opcode = frame[0] & 0x0f;
+        if opcode > 0x2 {
+            return;
+        }
+        let Some(payload) = frame_payload(frame) else {
+            return;
+        };
+        let payload = match opcode {
+            0x1 => String::from_utf8_lossy(&payload).to_string(),
+            _ => payload.iter().map(|b| format!("{:02x}", b)).collect(),
+        };
+        let line = RecordedFrame {
+            at_ms: self.started.elapsed().as_millis() as u64,
+            from_client,
+            opcode,
+            payload,
+        };
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Ok(line) = serde_json::to_string(&line) {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self
+            .file
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .flush();
+    }
+}
+
+/// Unmasked payload of a complete websocket frame
+fn frame_payload(frame: &[u8]) -> Option<Vec<u8>> {
+    let mut offset = match frame.get(1)? & 0x7f {
+        126 => 4,
+        127 => 10,
+        _ => 2,
+    };
+    let mask = if frame[1] & 0x80 != 0 {
+        let key: [u8; 4] = frame.get(offset..offset + 4)?.try_into().ok()?;
+        offset += 4;
+        Some(key)
+    } else {
+        None
+    };
+    let mut payload = frame.get(offset..)?.to_vec();
+    if let Some(key) = mask {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= key[i % 4];
+        }
+    }
+    Some(payload)
+}
+
+/// Sends the client side of a recording to a Foundry instance with the recorded timing.
+///
+/// Engine.io pings of the instance are answered live, the recorded answers to the pings of
+/// the original session are skipped.
+pub struct Replay {
+    pub recording: PathBuf,
+    /// Base URL of the instance, e.g. `ws://localhost:30000`
+    pub url: String,
+    /// Session cookie on the instance, replaces the recorded one
+    pub session: Option<String>,
+}
+
+impl Replay {
+    pub async fn run(&self) -> Result<()> {
+        let (header, frames) = read_recording(&self.recording)?;
+        let path = match &self.session {
+            Some(session) => replace_session(&header.path, session),
+            None => header.path.clone(),
+        };
+        let url = format!("{}{}", self.url.trim_end_matches('/'), path);
+        println!(
+            "Replaying {} messages of {} recorded {} against {}",
+            frames.iter().filter(|f| f.from_client).count(),
+            header.client,
+            header.started,
+            url
+        );
+
+        let (socket, _) = connect_async(&url)
+            .await
+            .with_context(|| format!("Failed to connect to {}", url))?;
+        let (mut sink, mut stream) = socket.split();
+        let started = Instant::now();
+
+        let mut pending = frames.into_iter().filter(|f| f.from_client).peekable();
+        loop {
+            let next = pending
+                .peek()
+                .map(|f| started + Duration::from_millis(f.at_ms))
+                .unwrap_or_else(|| Instant::now() + REPLAY_LINGER);
+            tokio::select! {
+                _ = sleep_until(next) => {
+                    let Some(frame) = pending.next() else {
+                        break;
+                    };
+                    if frame.opcode == 0x1 && frame.payload == "3" {
+                        continue;
+                    }
+                    println!("{:>8}ms → {}", frame.at_ms, truncate(&frame.payload));
+                    sink.send(to_message(&frame)?).await?;
+                }
+                message = stream.next() => {
+                    let message = match message {
+                        Some(message) => message?,
+                        None => {
+                            println!("Foundry closed the connection");
+                            return Ok(());
+                        }
+                    };
+                    let elapsed = started.elapsed().as_millis();
+                    match message {
+                        Message::Text(text) if text.as_str() == "2" => {
+                            sink.send(Message::text("3")).await?;
+                        }
+                        Message::Text(text) => println!("{:>8}ms ← {}", elapsed, truncate(&text)),
+                        Message::Binary(data) => {
+                            println!("{:>8}ms ← {} binary bytes", elapsed, data.len())
+                        }
+                        Message::Close(_) => {
+                            println!("Foundry closed the connection");
+                            return Ok(());
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let _ = sink.send(Message::Close(None)).await;
+        // Give the close frame a moment to reach Foundry
+        sleep(Duration::from_millis(100)).await;
+        println!("Replay finished");
+        Ok(())
+    }
+}
+
+fn read_recording(path: &Path) -> Result<(RecordingHeader, Vec<RecordedFrame>)> {
+    let file = File::open(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut lines = BufReader::new(file).lines();
+    let header: RecordingHeader = serde_json::from_str(
+        &lines
+            .next()
+            .ok_or_else(|| anyhow!("{} is empty", path.display()))??,
+    )
+    .with_context(|| format!("{} is not a recording", path.display()))?;
+    let mut frames = Vec::new();
+    for (number, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        frames.push(
+            serde_json::from_str(&line)
+                .with_context(|| format!("Invalid message on line {}", number + 2))?,
+        );
+    }
+    Ok((header, frames))
+}
+
+fn to_message(frame: &RecordedFrame) -> Result<Message> {
+    if frame.opcode == 0x1 {
+        return Ok(Message::text(frame.payload.clone()));
+    }
+    let bytes = (0..frame.payload.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(frame.payload.get(i..i + 2).unwrap_or("zz"), 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| anyhow!("Invalid binary payload at {}ms", frame.at_ms))?;
+    Ok(Message::binary(bytes))
+}
+
+/// `path` with its `session` query parameter set to `session`
+fn replace_session(path: &str, session: &str) -> String {
+    let (base, query) = path.split_once('?').unwrap_or((path, ""));
+    let mut params: Vec<String> = query
+        .split('&')
+        .filter(|param| !param.is_empty() && !param.starts_with("session="))
+        .map(|param| param.to_string())
+        .collect();
+    params.insert(0, format!("session={}", session));
+    format!("{}?{}", base, params.join("&"))
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(200) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
@@ -0,0 +1,452 @@
+use anyhow::{Context, Result, anyhow};
+use reqwest::Client;
+use reqwest::header::{CONTENT_TYPE, COOKIE, HeaderMap, LOCATION, REFERER, SET_COOKIE};
+use reqwest::redirect::Policy;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::cache;
+use crate::downloader::DownloadService;
+use crate::install::InstallService;
+use crate::objectstore::{hex, uri_encode};
+use crate::permissions;
+use crate::resilience::{self, Service};
+use crate::tempfiles;
+
+const FOUNDRY_SITE: &str = "https://foundryvtt.com";
+/// Name of the archive while it is downloaded, next to the installation
+const ARCHIVE_NAME: &str = "foundryvtt-download.zip";
+
+/// Where the first boot fetches Foundry from
+#[derive(Clone)]
+pub enum ReleaseSource {
+    /// Timed download link copied from the purchased licenses page of foundryvtt.com
+    Url(String),
+    /// foundryvtt.com account, a fresh link to `version` is requested on every download
+    Account {
+        username: String,
+        password: String,
+        version: String,
+    },
+}
+
+/// Downloads and installs Foundry without the setup UI, for deployments nobody opens the
+/// browser for before the first start
+#[derive(Clone)]
+pub struct ReleaseDownload {
+    pub source: ReleaseSource,
+    /// Checked in addition to the length, when the release is pinned
+    pub sha256: Option<String>,
+    /// Where the size and checksum of the release are published, checked on every install
+    pub metadata: Option<PublisherMetadata>,
+}
+
+/// Endpoint answering the size and SHA-256 of a release, `RELEASE_METADATA_URL`
+#[derive(Clone)]
+pub struct PublisherMetadata {
+    /// `{version}` and `{build}` are replaced
+    pub url: String,
+    /// Refuse releases the endpoint has no answer for instead of installing them unchecked
+    pub required: bool,
+}
+
+/// What the publisher states about a release archive
+#[derive(Debug, Default)]
+struct PublishedRelease {
+    size: Option<u64>,
+    sha256: Option<String>,
+}
+
+impl ReleaseDownload {
+    pub async fn install(&self, target_dir: &str) -> Result<()> {
+        permissions::create_dir_all(Path::new(target_dir))
+            .with_context(|| format!("Failed to create {}", target_dir))?;
+        let archive = Path::new(target_dir).join(ARCHIVE_NAME);
+        tempfiles::track(&archive, "release archive");
+        let cache_key = self.cache_key();
+        let cached = cache::restore(&cache_key, &archive).await?;
+        let archive = archive.to_string_lossy().to_string();
+        if !cached {
+            self.download(&archive).await?;
+        }
+
+        let installed = async {
+            self.verify(&archive).await?;
+            InstallService::install_release(&archive, target_dir).await
+        }
+        .await;
+        match (&installed, cached) {
+            (Ok(()), false) => cache::store(&cache_key, Path::new(&archive)).await,
+            // Downloaded again the next time
+            (Err(_), true) => cache::evict(&cache_key).await,
+            _ => {}
+        }
+        if let Err(e) = tokio::fs::remove_file(&archive).await {
+            warn!("Failed to remove {}: {}", archive, e);
+        }
+        installed
+    }
+
+    async fn download(&self, archive: &str) -> Result<()> {
+        cache::ensure_online("the Foundry release")?;
+        let url = match &self.source {
+            ReleaseSource::Url(url) => url.clone(),
+            ReleaseSource::Account {
+                username,
+                password,
+                version,
+            } => {
+                info!(
+                    "🔑 Requesting a download link for Foundry {} as {}",
+                    version, username
+                );
+                account_download_url(username, password, version).await?
+            }
+        };
+        // Nobody follows the progress, the setup UI is not running
+        let (event_tx, _) = broadcast::channel(16);
+        DownloadService::download_file_from_url(&url, archive, event_tx)
+            .await
+            .map_err(|e| anyhow!("Failed to download Foundry: {}", e))
+    }
+
+    /// Check the archive against the pinned checksum and what the publisher states about the
+    /// release. Any difference is fatal, the archive is not what was asked for.
+    async fn verify(&self, archive: &str) -> Result<()> {
+        let published = self.published().await?;
+        let size = tokio::fs::metadata(archive)
+            .await
+            .with_context(|| format!("Failed to read {}", archive))?
+            .len();
+        if let Some(expected) = published.size
+            && expected != size
+        {
+            return Err(anyhow!(
+                "The download has {} bytes, the publisher states {} for this release",
+                size,
+                expected
+            ));
+        }
+        if self.sha256.is_none() && published.sha256.is_none() {
+            return Ok(());
+        }
+
+        let actual = sha256_of(archive).await?;
+        if let Some(expected) = &self.sha256
+            && !actual.eq_ignore_ascii_case(expected.trim())
+        {
+            return Err(anyhow!(
+                "The download has the SHA-256 {}, expected {}",
+                actual,
+                expected
+            ));
+        }
+        if let Some(expected) = &published.sha256
+            && !actual.eq_ignore_ascii_case(expected)
+        {
+            return Err(anyhow!(
+                "The download has the SHA-256 {}, the publisher states {} for this release",
+                actual,
+                expected
+            ));
+        }
+        info!("✅ SHA-256 of the download matches");
+        Ok(())
+    }
+
+    /// The size and checksum the publisher states for the release, nothing when they are not
+    /// published for it and `RELEASE_METADATA_REQUIRED` is not set
+    async fn published(&self) -> Result<PublishedRelease> {
+        let Some(metadata) = &self.metadata else {
+            return Ok(PublishedRelease::default());
+        };
+        let unchecked = |reason: String| {
+            if metadata.required {
+                return Err(anyhow!(
+                    "{}, RELEASE_METADATA_REQUIRED refuses to install the release",
+                    reason
+                ));
+            }
+            warn!("⚠️ {}, the download is not cross-checked", reason);
+            Ok(PublishedRelease::default())
+        };
+        let Some(version) = self.version() else {
+            return unchecked("The version of the release link is unknown".to_string());
+        };
+        if cache::offline() {
+            return unchecked(format!(
+                "OFFLINE is set, the metadata of Foundry {} is not fetched",
+                version
+            ));
+        }
+
+        let build = version
+            .split_once('.')
+            .map_or(version.as_str(), |(_, build)| build);
+        let url = metadata
+            .url
+            .replace("{version}", &version)
+            .replace("{build}", build);
+        let client = Client::new();
+        let body = resilience::call(Service::Updates, || async {
+            let response = client
+                .get(&url)
+                .send()
+                .await
+                .with_context(|| format!("Failed to fetch {}", url))?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Ok::<_, anyhow::Error>(None);
+            }
+            let body = response
+                .error_for_status()
+                .with_context(|| format!("Failed to fetch {}", url))?
+                .text()
+                .await?;
+            Ok(Some(body))
+        })
+        .await;
+        let body = match body {
+            Ok(Some(body)) => body,
+            Ok(None) => return unchecked(format!("{} publishes no metadata", url)),
+            Err(e) => return unchecked(format!("{:#}", e)),
+        };
+        let published = parse_published(&body)
+            .with_context(|| format!("{} answered no release metadata", url))?;
+        if published.size.is_none() && published.sha256.is_none() {
+            return unchecked(format!("{} states no size or SHA-256", url));
+        }
+        info!(
+            "🔏 Checking the download against the metadata of Foundry {} from {}",
+            version, url
+        );
+        Ok(published)
+    }
+
+    /// Version of the release, taken from the file name of a link like `FoundryVTT-12.331.zip`
+    fn version(&self) -> Option<String> {
+        match &self.source {
+            ReleaseSource::Account { version, .. } => Some(version.trim().to_string()),
+            ReleaseSource::Url(url) => {
+                let name = link_file_name(url)?;
+                let stem = name.strip_suffix(".zip").unwrap_or(&name);
+                stem.split(['-', '_'])
+                    .find(|part| {
+                        part.split_once('.').is_some_and(|(major, build)| {
+                            !major.is_empty()
+                                && !build.is_empty()
+                                && major.chars().all(|c| c.is_ascii_digit())
+                                && build.chars().all(|c| c.is_ascii_digit())
+                        })
+                    })
+                    .map(str::to_string)
+            }
+        }
+    }
+
+    /// The checksum when the release is pinned, its version or else the file name of the
+    /// link, which stays the same while the timed query changes
+    fn cache_key(&self) -> String {
+        if let Some(sha256) = &self.sha256 {
+            return cache::key(
+                &[
+                    "releases",
+                    &format!("sha256-{}", sha256.trim().to_lowercase()),
+                ],
+                "zip",
+            );
+        }
+        match &self.source {
+            ReleaseSource::Account { version, .. } => cache::key(&["releases", version], "zip"),
+            ReleaseSource::Url(url) => {
+                let name = link_file_name(url).unwrap_or_else(|| "release".to_string());
+                let name = name.strip_suffix(".zip").unwrap_or(&name);
+                cache::key(&["releases", "url", name], "zip")
+            }
+        }
+    }
+}
+
+/// File name of a download link, without the query of timed links
+fn link_file_name(url: &str) -> Option<String> {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.path_segments()?.next_back().map(str::to_string))
+        .filter(|name| !name.is_empty())
+}
+
+/// `{"size": <bytes>, "sha256": "<hex>"}`, or the same below `linux` when the endpoint
+/// describes the archives of every platform
+fn parse_published(body: &str) -> Result<PublishedRelease> {
+    let answer: serde_json::Value = serde_json::from_str(body)?;
+    let release = match answer.get("linux") {
+        Some(linux) if linux.is_object() => linux,
+        _ => &answer,
+    };
+    if !release.is_object() {
+        return Err(anyhow!("The answer is not a JSON object"));
+    }
+    let sha256 = release["sha256"]
+        .as_str()
+        .map(|sha256| sha256.trim().trim_start_matches("sha256:").to_lowercase());
+    if let Some(sha256) = &sha256
+        && (sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return Err(anyhow!("{} is no SHA-256", sha256));
+    }
+    Ok(PublishedRelease {
+        size: release["size"].as_u64(),
+        sha256,
+    })
+}
+
+async fn sha256_of(path: &str) -> Result<String> {
+    let file = path.to_string();
+    tokio::task::spawn_blocking(move || -> Result<String> {
+        let mut hasher = Sha256::new();
+        let mut reader =
+            std::fs::File::open(&file).with_context(|| format!("Failed to open {}", file))?;
+        std::io::copy(&mut reader, &mut hasher)
+            .with_context(|| format!("Failed to read {}", file))?;
+        Ok(hex(&hasher.finalize()))
+    })
+    .await?
+}
+
+/// Log in to foundryvtt.com like the website does and request the timed link of `version`.
+///
+/// The site is a Django application: the login form needs the CSRF token of the start page
+/// and answers with a session cookie, which the release download then redirects with.
+async fn account_download_url(username: &str, password: &str, version: &str) -> Result<String> {
+    // `12.331` is build 331, the download is requested by build
+    let build = version
+        .split_once('.')
+        .map_or(version, |(_, build)| build)
+        .trim();
+    if build.is_empty() || !build.chars().all(|c| c.is_ascii_digit()) {
+        return Err(anyhow!(
+            "FOUNDRY_VERSION {} is no release like 12.331",
+            version
+        ));
+    }
+
+    // Redirects carry the cookies and the download link, they are followed by hand
+    let client = Client::builder().redirect(Policy::none()).build()?;
+    let mut cookies = Cookies::default();
+
+    let home = client
+        .get(format!("{}/", FOUNDRY_SITE))
+        .send()
+        .await
+        .context("Failed to reach foundryvtt.com")?;
+    cookies.store(home.headers());
+    let page = home.text().await?;
+    let token = hidden_input(&page, "csrfmiddlewaretoken")
+        .or_else(|| cookies.get("csrftoken"))
+        .ok_or_else(|| anyhow!("foundryvtt.com sent no CSRF token"))?;
+
+    let form = [
+        ("csrfmiddlewaretoken", token.as_str()),
+        ("login_password", password),
+        ("login_redirect", "/"),
+        ("login_username", username),
+        ("login", ""),
+    ]
+    .iter()
+    .map(|(key, value)| format!("{}={}", key, uri_encode(value, false)))
+    .collect::<Vec<_>>()
+    .join("&");
+    let login = client
+        .post(format!("{}/auth/login/", FOUNDRY_SITE))
+        .header(REFERER, format!("{}/", FOUNDRY_SITE))
+        .header(COOKIE, cookies.header())
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(form)
+        .send()
+        .await
+        .context("Failed to log in to foundryvtt.com")?;
+    cookies.store(login.headers());
+    if cookies.get("sessionid").is_none() {
+        return Err(anyhow!(
+            "foundryvtt.com rejected the login of {}, check FOUNDRY_USERNAME and FOUNDRY_PASSWORD",
+            username
+        ));
+    }
+
+    let release = client
+        .get(format!(
+            "{}/releases/download?build={}&platform=linux",
+            FOUNDRY_SITE, build
+        ))
+        .header(COOKIE, cookies.header())
+        .send()
+        .await
+        .context("Failed to request the download link")?;
+    if let Some(location) = release
+        .headers()
+        .get(LOCATION)
+        .and_then(|l| l.to_str().ok())
+    {
+        return Ok(location.to_string());
+    }
+    let status = release.status();
+    if !status.is_success() {
+        return Err(anyhow!(
+            "foundryvtt.com answered {} for build {}, does the account own a license?",
+            status,
+            build
+        ));
+    }
+    let body: serde_json::Value = release
+        .json()
+        .await
+        .context("Unexpected answer to the download request")?;
+    body.get("url")
+        .and_then(|url| url.as_str())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("foundryvtt.com sent no download link for build {}", build))
+}
+
+/// Cookies of foundryvtt.com, the only site this client talks to
+#[derive(Default)]
+struct Cookies(BTreeMap<String, String>);
+
+impl Cookies {
+    fn store(&mut self, headers: &HeaderMap) {
+        for cookie in headers.get_all(SET_COOKIE) {
+            let Ok(cookie) = cookie.to_str() else {
+                continue;
+            };
+            let pair = cookie.split(';').next().unwrap_or_default();
+            if let Some((name, value)) = pair.split_once('=') {
+                self.0
+                    .insert(name.trim().to_string(), value.trim().to_string());
+            }
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<String> {
+        self.0.get(name).filter(|v| !v.is_empty()).cloned()
+    }
+
+    fn header(&self) -> String {
+        self.0
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// Value of the hidden form field `name` in `html`
+fn hidden_input(html: &str, name: &str) -> Option<String> {
+    let start = html.find(&format!("name=\"{}\"", name))?;
+    let tag_end = html[start..].find('>')? + start;
+    let tag_start = html[..start].rfind('<')?;
+    let tag = &html[tag_start..tag_end];
+    let value = tag.split_once("value=\"")?.1;
+    let value = &value[..value.find('"')?];
+    Some(value.to_string()).filter(|v| !v.is_empty())
+}
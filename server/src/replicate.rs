@@ -0,0 +1,570 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use anyhow::{Context, Result, anyhow};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::time::Duration;
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::api::{self, ApiConfig, Message};
+use crate::backup;
+use crate::objectstore;
+use crate::permissions::{self, FileKind};
+use crate::scan::ScanService;
+use crate::status;
+use crate::utils::paths;
+
+/// Bytes per request, an interrupted transfer repeats at most one chunk
+const CHUNK_SIZE: usize = 8 * 1024 * 1024;
+/// Hashes of the files in `DATA_DIR`, a file is hashed again once its size or modification
+/// time changed
+const INDEX: &str = "sync-index.json";
+/// Transfers in progress on the receiving side, named by the SHA-256 of the complete file
+const INCOMING: &str = "sync-incoming";
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct IndexedFile {
+    pub size: u64,
+    pub sha256: String,
+    /// Local modification time in milliseconds
+    #[serde(default)]
+    pub modified: Option<u64>,
+}
+
+/// Files of `DATA_DIR` by their path relative to it. Only `Config` and `Data` are replicated,
+/// like a snapshot; the wrapper's state and scratch directories belong to each instance.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct FileIndex {
+    pub files: BTreeMap<String, IndexedFile>,
+}
+
+impl FileIndex {
+    /// Index `DATA_DIR`, hashing only files that changed since the last index. Blocks.
+    pub fn build(excludes: &[String]) -> Result<Self> {
+        let data_dir = PathBuf::from(&*paths::DATA_DIR);
+        let index_path = paths::WRAPPER_STATE_DIR.join(INDEX);
+        let previous: FileIndex = fs::read(&index_path)
+            .ok()
+            .and_then(|contents| serde_json::from_slice(&contents).ok())
+            .unwrap_or_default();
+
+        let mut index = FileIndex::default();
+        for entry in ScanService::walk(&data_dir, excludes)? {
+            let Some(relative) = entry
+                .path
+                .strip_prefix(&data_dir)
+                .ok()
+                .map(|path| path.to_string_lossy().to_string())
+                .filter(|relative| replicated(relative))
+            else {
+                continue;
+            };
+            let Ok(metadata) = fs::metadata(&entry.path) else {
+                // Removed while walking
+                continue;
+            };
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64);
+            let known = previous.files.get(&relative).filter(|file| {
+                file.size == metadata.len() && modified.is_some() && file.modified == modified
+            });
+            let file = match known {
+                Some(file) => file.clone(),
+                None => IndexedFile {
+                    size: metadata.len(),
+                    sha256: hash_file(&entry.path)
+                        .with_context(|| format!("Failed to read {}", entry.path.display()))?,
+                    modified,
+                },
+            };
+            index.files.insert(relative, file);
+        }
+
+        permissions::create_dir_all(&paths::WRAPPER_STATE_DIR)?;
+        permissions::write(&index_path, serde_json::to_vec(&index)?, FileKind::Regular)
+            .with_context(|| format!("Failed to save {}", index_path.display()))?;
+        Ok(index)
+    }
+}
+
+fn replicated(relative: &str) -> bool {
+    Path::new(relative)
+        .components()
+        .next()
+        .is_some_and(|first| backup::INCLUDED.iter().any(|dir| first.as_os_str() == *dir))
+}
+
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(objectstore::hex(&hasher.finalize()))
+}
+
+/// Where a received file goes, `None` for paths leaving `DATA_DIR` or outside `Config` and `Data`
+fn target_path(relative: &str) -> Option<PathBuf> {
+    let path = Path::new(relative);
+    let normal = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    (normal && !relative.is_empty() && replicated(relative))
+        .then(|| PathBuf::from(&*paths::DATA_DIR).join(path))
+}
+
+fn incoming_path(sha256: &str) -> Option<PathBuf> {
+    (sha256.len() == 64 && sha256.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| paths::WRAPPER_STATE_DIR.join(INCOMING).join(sha256))
+}
+
+fn received(path: &Path) -> u64 {
+    fs::metadata(path).map(|meta| meta.len()).unwrap_or(0)
+}
+
+pub fn message(status: StatusCode, message: impl Into<String>) -> HttpResponse {
+    HttpResponse::build(status).json(Message::new(message))
+}
+
+/// Bytes of a file received so far
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Received {
+    pub received: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl Received {
+    fn new(received: u64) -> Self {
+        Self {
+            received,
+            message: None,
+        }
+    }
+}
+
+/// Foundry keeps the databases of the active world open, replacing them underneath breaks it
+pub async fn world_active(config: &ApiConfig) -> bool {
+    status::current().running
+        && status::fetch_server_status(config.foundry_port)
+            .await
+            .is_ok_and(|server| server.active)
+}
+
+/// Files of the data directory with their SHA-256, for `foundry-watcher sync` to compare with
+#[utoipa::path(
+    get,
+    path = "/api/sync/manifest",
+    operation_id = "syncManifest",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "Files by their path relative to DATA_DIR", body = FileIndex),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 500, description = "The data directory could not be indexed", body = Message),
+    )
+)]
+pub async fn manifest(req: HttpRequest, config: web::Data<ApiConfig>) -> impl Responder {
+    if let Some(rejected) = api::reject(&req, &config) {
+        return rejected;
+    }
+    let excludes = config.scan_excludes.clone();
+    match web::block(move || FileIndex::build(&excludes)).await {
+        Ok(Ok(index)) => HttpResponse::Ok().json(index),
+        Ok(Err(e)) => message(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)),
+        Err(e) => message(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Bytes received of a file, where an interrupted transfer continues
+#[utoipa::path(
+    get,
+    path = "/api/sync/uploads/{sha256}",
+    operation_id = "syncUploadStatus",
+    security(("token" = [])),
+    params(("sha256" = String, Path, description = "SHA-256 of the complete file")),
+    responses(
+        (status = 200, description = "Bytes received so far", body = Received),
+        (status = 400, description = "Not a SHA-256 digest", body = Message),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+    )
+)]
+pub async fn upload_status(
+    req: HttpRequest,
+    path: web::Path<String>,
+    config: web::Data<ApiConfig>,
+) -> impl Responder {
+    if let Some(rejected) = api::reject(&req, &config) {
+        return rejected;
+    }
+    match incoming_path(&path) {
+        Some(incoming) => HttpResponse::Ok().json(Received::new(received(&incoming))),
+        None => message(StatusCode::BAD_REQUEST, "Not a SHA-256 digest"),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ChunkQuery {
+    /// Bytes received so far
+    offset: u64,
+}
+
+/// Append a chunk of the file, `offset` has to be the number of bytes received so far
+#[utoipa::path(
+    put,
+    path = "/api/sync/uploads/{sha256}",
+    operation_id = "syncUploadChunk",
+    security(("token" = [])),
+    params(
+        ("sha256" = String, Path, description = "SHA-256 of the complete file"),
+        ChunkQuery,
+    ),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses(
+        (status = 200, description = "Bytes received so far", body = Received),
+        (status = 400, description = "Not a SHA-256 digest", body = Message),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 409, description = "The offset is not the number of bytes received", body = Received),
+        (status = 500, description = "The chunk could not be stored", body = Message),
+    )
+)]
+pub async fn upload_chunk(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<ChunkQuery>,
+    mut payload: web::Payload,
+    config: web::Data<ApiConfig>,
+) -> impl Responder {
+    if let Some(rejected) = api::reject(&req, &config) {
+        return rejected;
+    }
+    let Some(incoming) = incoming_path(&path) else {
+        return message(StatusCode::BAD_REQUEST, "Not a SHA-256 digest");
+    };
+    let current = received(&incoming);
+    if query.offset != current {
+        return HttpResponse::Conflict().json(Received {
+            received: current,
+            message: Some(format!("Expected offset {}", current)),
+        });
+    }
+
+    let written = async {
+        if let Some(parent) = incoming.parent() {
+            permissions::create_dir_all(parent)?;
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&incoming)
+            .await?;
+        while let Some(chunk) = payload.next().await {
+            file.write_all(&chunk.map_err(io::Error::other)?).await?;
+        }
+        file.flush().await?;
+        permissions::restrict(&incoming, FileKind::Regular)
+    }
+    .await;
+    match written {
+        Ok(()) => HttpResponse::Ok().json(Received::new(received(&incoming))),
+        Err(e) => message(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to store the chunk: {}", e),
+        ),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CommitRequest {
+    /// Relative to `DATA_DIR`, e.g. `Data/worlds/my-world/world.json`
+    path: String,
+    sha256: String,
+    size: u64,
+}
+
+/// Check a completely received file and move it into place, refused while a world is active
+#[utoipa::path(
+    post,
+    path = "/api/sync/files",
+    operation_id = "syncCommit",
+    security(("token" = [])),
+    request_body = CommitRequest,
+    responses(
+        (status = 200, description = "Stored", body = Message),
+        (status = 400, description = "Invalid path or digest", body = Message),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 409, description = "A world is active or the received data does not match", body = Message),
+    )
+)]
+pub async fn commit(
+    req: HttpRequest,
+    body: web::Json<CommitRequest>,
+    config: web::Data<ApiConfig>,
+) -> impl Responder {
+    if let Some(rejected) = api::reject(&req, &config) {
+        return rejected;
+    }
+    let (Some(target), Some(incoming)) = (target_path(&body.path), incoming_path(&body.sha256))
+    else {
+        return message(StatusCode::BAD_REQUEST, "Invalid path or digest");
+    };
+    if world_active(&config).await {
+        return message(
+            StatusCode::CONFLICT,
+            "A world is active, return to the setup screen first",
+        );
+    }
+
+    let CommitRequest { path, sha256, size } = body.into_inner();
+    let moved = web::block(move || -> Result<()> {
+        // Empty files need no transfer
+        if size == 0 && !incoming.exists() {
+            permissions::create_dir_all(incoming.parent().unwrap_or(Path::new("/")))?;
+            permissions::write(&incoming, [], FileKind::Regular)?;
+        }
+        if received(&incoming) != size || hash_file(&incoming)? != sha256 {
+            let _ = fs::remove_file(&incoming);
+            return Err(anyhow!(
+                "The received data does not match {}, send it again",
+                path
+            ));
+        }
+        if let Some(parent) = target.parent() {
+            permissions::create_dir_all(parent)?;
+        }
+        fs::rename(&incoming, &target)
+            .with_context(|| format!("Failed to replace {}", target.display()))?;
+        info!("Received {} ({} bytes)", path, size);
+        Ok(())
+    })
+    .await;
+    match moved {
+        Ok(Ok(())) => message(StatusCode::OK, "Stored"),
+        Ok(Err(e)) => message(StatusCode::CONFLICT, format!("{:#}", e)),
+        Err(e) => message(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RemoveQuery {
+    /// Relative to `DATA_DIR`
+    path: String,
+}
+
+/// Delete a file the sender no longer has, refused while a world is active
+#[utoipa::path(
+    delete,
+    path = "/api/sync/files",
+    operation_id = "syncRemove",
+    security(("token" = [])),
+    params(RemoveQuery),
+    responses(
+        (status = 204, description = "Deleted, or did not exist"),
+        (status = 400, description = "Invalid path", body = Message),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+        (status = 409, description = "A world is active", body = Message),
+        (status = 500, description = "The file could not be deleted", body = Message),
+    )
+)]
+pub async fn remove(
+    req: HttpRequest,
+    query: web::Query<RemoveQuery>,
+    config: web::Data<ApiConfig>,
+) -> impl Responder {
+    if let Some(rejected) = api::reject(&req, &config) {
+        return rejected;
+    }
+    let Some(target) = target_path(&query.path) else {
+        return message(StatusCode::BAD_REQUEST, "Invalid path");
+    };
+    if world_active(&config).await {
+        return message(
+            StatusCode::CONFLICT,
+            "A world is active, return to the setup screen first",
+        );
+    }
+    match fs::remove_file(&target) {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => HttpResponse::NoContent().finish(),
+        Err(e) => message(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to delete {}: {}", query.path, e),
+        ),
+    }
+}
+
+/// `foundry-watcher sync --to`: push the changes of `DATA_DIR` to another instance's admin API
+pub struct Replication {
+    /// Admin API of the receiving instance, e.g. `https://home.example.com:8081`
+    pub target: String,
+    pub token: String,
+    pub excludes: Vec<String>,
+    /// Also delete files the receiver has and this instance does not
+    pub delete: bool,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct ReplicationSummary {
+    pub uploaded: usize,
+    pub bytes: u64,
+    pub deleted: usize,
+    pub unchanged: usize,
+}
+
+impl Replication {
+    pub async fn run(&self) -> Result<ReplicationSummary> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(300))
+            .build()?;
+        let remote: FileIndex = check(
+            client
+                .get(self.url("sync/manifest"))
+                .bearer_auth(&self.token)
+                .send()
+                .await?,
+        )
+        .await?
+        .json()
+        .await
+        .context("The other instance answered an invalid manifest")?;
+        let excludes = self.excludes.clone();
+        let local = tokio::task::spawn_blocking(move || FileIndex::build(&excludes)).await??;
+
+        let mut summary = ReplicationSummary::default();
+        for (relative, file) in &local.files {
+            if remote
+                .files
+                .get(relative)
+                .is_some_and(|known| known.sha256 == file.sha256)
+            {
+                summary.unchanged += 1;
+                continue;
+            }
+            if self.dry_run {
+                println!("Would send {} ({} bytes)", relative, file.size);
+            } else {
+                self.send(&client, relative, file)
+                    .await
+                    .with_context(|| format!("Failed to send {}", relative))?;
+            }
+            summary.uploaded += 1;
+            summary.bytes += file.size;
+        }
+
+        if self.delete {
+            for relative in remote.files.keys() {
+                if local.files.contains_key(relative) {
+                    continue;
+                }
+                if self.dry_run {
+                    println!("Would delete {}", relative);
+                } else {
+                    let resp = client
+                        .delete(self.url("sync/files"))
+                        .bearer_auth(&self.token)
+                        .query(&[("path", relative)])
+                        .send()
+                        .await?;
+                    check(resp)
+                        .await
+                        .with_context(|| format!("Failed to delete {}", relative))?;
+                }
+                summary.deleted += 1;
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Continue the transfer of `file` where the receiver stopped, then commit it
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        relative: &str,
+        file: &IndexedFile,
+    ) -> Result<()> {
+        let upload = self.url(&format!("sync/uploads/{}", file.sha256));
+        let status: serde_json::Value =
+            check(client.get(&upload).bearer_auth(&self.token).send().await?)
+                .await?
+                .json()
+                .await?;
+        let mut offset = status["received"].as_u64().unwrap_or(0);
+        if offset > 0 && offset < file.size {
+            info!("Resuming {} at {} of {} bytes", relative, offset, file.size);
+        }
+
+        let path = PathBuf::from(&*paths::DATA_DIR).join(relative);
+        let mut source = tokio::fs::File::open(&path).await?;
+        source.seek(io::SeekFrom::Start(offset)).await?;
+        let mut buffer = vec![0; CHUNK_SIZE];
+        while offset < file.size {
+            let mut filled = 0;
+            while filled < buffer.len() {
+                match source.read(&mut buffer[filled..]).await? {
+                    0 => break,
+                    n => filled += n,
+                }
+            }
+            if filled == 0 {
+                return Err(anyhow!("{} shrank while it was sent", relative));
+            }
+            let resp = client
+                .put(&upload)
+                .bearer_auth(&self.token)
+                .query(&[("offset", offset)])
+                .body(buffer[..filled].to_vec())
+                .send()
+                .await?;
+            check(resp).await?;
+            offset += filled as u64;
+        }
+
+        let resp = client
+            .post(self.url("sync/files"))
+            .bearer_auth(&self.token)
+            .json(&json!({
+                "path": relative,
+                "sha256": file.sha256,
+                "size": file.size,
+            }))
+            .send()
+            .await?;
+        check(resp).await?;
+        info!("Sent {} ({} bytes)", relative, file.size);
+        Ok(())
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/api/{}", self.target.trim_end_matches('/'), path)
+    }
+}
+
+/// The response, or its message as the error
+pub async fn check(resp: reqwest::Response) -> Result<reqwest::Response> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp);
+    }
+    let body: serde_json::Value = resp.json().await.unwrap_or_default();
+    let detail = body["message"].as_str().unwrap_or("no details").to_string();
+    if status == reqwest::StatusCode::UNAUTHORIZED {
+        warn!("The other instance rejected the token");
+    }
+    Err(anyhow!("{}: {}", status, detail))
+}
@@ -0,0 +1,311 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use crate::dump::DumpService;
+use crate::storage::storage;
+use crate::utils::paths;
+
+/// Chat messages further apart than this belong to different sessions
+const SESSION_GAP_MS: i64 = 4 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+/// Statistics of a campaign, gathered from the chat log of its world.
+///
+/// Sessions are inferred from gaps in the chat timestamps, which works as long as the table
+/// talks or rolls at least once every few hours.
+pub struct CampaignReport {
+    pub title: String,
+    pub messages: usize,
+    pub sessions: Vec<Session>,
+    /// Results rolled per die size, e.g. how often each face of a d20 came up
+    pub dice: BTreeMap<u64, BTreeMap<u64, u64>>,
+    /// Ordered by messages sent, the most active player first
+    pub players: Vec<PlayerActivity>,
+}
+
+pub struct Session {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub messages: usize,
+}
+
+pub struct PlayerActivity {
+    pub name: String,
+    pub messages: usize,
+    pub rolls: usize,
+}
+
+impl CampaignReport {
+    /// Read the chat log and users of a world. Stop the world first, like for `db dump`.
+    pub fn build(world_id: &str) -> Result<Self> {
+        let manifest = paths::WORLDS_DIR.join(world_id).join("world.json");
+        let info: Value = serde_json::from_str(&storage().read_to_string(&manifest)?)?;
+        let title = info["title"].as_str().unwrap_or(world_id).to_string();
+
+        let names: HashMap<String, String> = DumpService::documents(world_id, "users")?
+            .into_iter()
+            .filter_map(|user| {
+                Some((
+                    user["_id"].as_str()?.to_string(),
+                    user["name"].as_str()?.to_string(),
+                ))
+            })
+            .collect();
+
+        let mut messages = DumpService::documents(world_id, "messages")?;
+        messages.sort_by_key(|message| message["timestamp"].as_i64().unwrap_or_default());
+
+        let mut sessions: Vec<Session> = Vec::new();
+        let mut dice = BTreeMap::new();
+        let mut activity: HashMap<String, PlayerActivity> = HashMap::new();
+        let mut last = None;
+        for message in &messages {
+            if let Some(at) = message["timestamp"]
+                .as_i64()
+                .and_then(DateTime::from_timestamp_millis)
+            {
+                match sessions.last_mut() {
+                    Some(session)
+                        if last.is_some_and(|last: i64| {
+                            at.timestamp_millis() - last <= SESSION_GAP_MS
+                        }) =>
+                    {
+                        session.end = at;
+                        session.messages += 1;
+                    }
+                    _ => sessions.push(Session {
+                        start: at,
+                        end: at,
+                        messages: 1,
+                    }),
+                }
+                last = Some(at.timestamp_millis());
+            }
+
+            let rolls = rolls_of(message);
+            for roll in &rolls {
+                count_dice(roll, &mut dice);
+            }
+
+            // v12 renamed `user` to `author`
+            let Some(user) = message["author"].as_str().or(message["user"].as_str()) else {
+                continue;
+            };
+            let player = activity
+                .entry(user.to_string())
+                .or_insert_with(|| PlayerActivity {
+                    name: names.get(user).cloned().unwrap_or_else(|| user.to_string()),
+                    messages: 0,
+                    rolls: 0,
+                });
+            player.messages += 1;
+            player.rolls += rolls.len();
+        }
+
+        let mut players: Vec<PlayerActivity> = activity.into_values().collect();
+        players.sort_by(|a, b| b.messages.cmp(&a.messages).then(a.name.cmp(&b.name)));
+
+        Ok(Self {
+            title,
+            messages: messages.len(),
+            sessions,
+            dice,
+            players,
+        })
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Markdown => self.to_markdown(),
+            ReportFormat::Html => self.to_html(),
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n\n", self.title);
+        let _ = writeln!(
+            out,
+            "{} chat messages in {} sessions.\n",
+            self.messages,
+            self.sessions.len()
+        );
+
+        out.push_str("## Sessions\n\n| # | Date (UTC) | Duration | Messages |\n|---|------------|----------|----------|\n");
+        for (number, session) in self.sessions.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} |",
+                number + 1,
+                session.start.format("%Y-%m-%d %H:%M"),
+                duration(session),
+                session.messages
+            );
+        }
+
+        out.push_str("\n## Most Active Players\n\n| Player | Messages | Rolls |\n|--------|----------|-------|\n");
+        for player in &self.players {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} |",
+                player.name.replace('|', "\\|"),
+                player.messages,
+                player.rolls
+            );
+        }
+
+        out.push_str("\n## Dice Rolls\n");
+        for (faces, results) in &self.dice {
+            let total: u64 = results.values().sum();
+            let _ = writeln!(
+                out,
+                "\n### d{} ({} rolled, average {:.2})\n\n| Result | Count | Share |\n|--------|-------|-------|",
+                faces,
+                total,
+                average(results)
+            );
+            for (result, count) in results {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {:.1}% |",
+                    result,
+                    count,
+                    *count as f64 * 100.0 / total as f64
+                );
+            }
+        }
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+             <style>body{{font-family:sans-serif;max-width:50em;margin:2em auto}}\
+             table{{border-collapse:collapse;margin-bottom:1em}}td,th{{padding:.2em .8em;text-align:left}}\
+             .bar{{background:#c33;height:1em}}</style>\n</head>\n<body>\n<h1>{title}</h1>\n\
+             <p>{} chat messages in {} sessions.</p>\n",
+            self.messages,
+            self.sessions.len(),
+            title = escape_html(&self.title)
+        );
+
+        out.push_str("<h2>Sessions</h2>\n<table>\n<tr><th>#</th><th>Date (UTC)</th><th>Duration</th><th>Messages</th></tr>\n");
+        for (number, session) in self.sessions.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                number + 1,
+                session.start.format("%Y-%m-%d %H:%M"),
+                duration(session),
+                session.messages
+            );
+        }
+        out.push_str("</table>\n");
+
+        out.push_str("<h2>Most Active Players</h2>\n<table>\n<tr><th>Player</th><th>Messages</th><th>Rolls</th></tr>\n");
+        for player in &self.players {
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape_html(&player.name),
+                player.messages,
+                player.rolls
+            );
+        }
+        out.push_str("</table>\n<h2>Dice Rolls</h2>\n");
+
+        for (faces, results) in &self.dice {
+            let total: u64 = results.values().sum();
+            let most = results.values().max().copied().unwrap_or(1);
+            let _ = writeln!(
+                out,
+                "<h3>d{} ({} rolled, average {:.2})</h3>\n<table>",
+                faces,
+                total,
+                average(results)
+            );
+            for (result, count) in results {
+                let _ = writeln!(
+                    out,
+                    "<tr><td>{}</td><td>{}</td><td style=\"width:20em\"><div class=\"bar\" style=\"width:{:.0}%\"></div></td></tr>",
+                    result,
+                    count,
+                    *count as f64 * 100.0 / most as f64
+                );
+            }
+            out.push_str("</table>\n");
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+/// Rolls of a chat message, v10+ keeps a list of them, older versions a single one
+fn rolls_of(message: &Value) -> Vec<Value> {
+    let serialized: Vec<&Value> = match message["rolls"].as_array() {
+        Some(rolls) => rolls.iter().collect(),
+        None if !message["roll"].is_null() => vec![&message["roll"]],
+        None => Vec::new(),
+    };
+    serialized
+        .into_iter()
+        .filter_map(|roll| match roll {
+            // Stored as JSON strings in the database
+            Value::String(json) => serde_json::from_str(json).ok(),
+            Value::Object(_) => Some(roll.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Count the results of every die in a roll, including dice nested in pools and parentheses
+fn count_dice(value: &Value, dice: &mut BTreeMap<u64, BTreeMap<u64, u64>>) {
+    match value {
+        Value::Object(object) => {
+            if let (Some(faces), Some(results)) = (
+                object.get("faces").and_then(Value::as_u64),
+                object.get("results").and_then(Value::as_array),
+            ) {
+                let counts = dice.entry(faces).or_default();
+                for result in results.iter().filter_map(|r| r["result"].as_u64()) {
+                    *counts.entry(result).or_default() += 1;
+                }
+            }
+            for nested in object.values() {
+                count_dice(nested, dice);
+            }
+        }
+        Value::Array(values) => {
+            for nested in values {
+                count_dice(nested, dice);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn average(results: &BTreeMap<u64, u64>) -> f64 {
+    let total: u64 = results.values().sum();
+    let sum: u64 = results.iter().map(|(result, count)| result * count).sum();
+    sum as f64 / total.max(1) as f64
+}
+
+fn duration(session: &Session) -> String {
+    let minutes = (session.end - session.start).num_minutes();
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,324 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::io;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use tokio::time::{Duration, Instant, sleep, timeout};
+use tracing::{debug, info, warn};
+
+use crate::config::AppConfig;
+
+/// The first retry waits this long, every further one twice as long as the one before
+const BACKOFF: Duration = Duration::from_secs(1);
+/// Calls in a row failing before a service is skipped for its cooldown
+const FAILURE_THRESHOLD: u32 = 3;
+const COOLDOWN: Duration = Duration::from_secs(120);
+
+/// Third-party services the wrapper talks to, each with a breaker of its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Service {
+    /// Package manifests of `INSTALL_MODULES` and `INSTALL_SYSTEMS`
+    Packages,
+    /// The bucket of S3 backups and stateless mode
+    S3,
+    Webhooks,
+    Heartbeat,
+    /// The check for a newer Foundry release and its published metadata, not its download
+    Updates,
+    ReleaseNotes,
+    Clock,
+}
+
+impl Service {
+    const ALL: [Self; 7] = [
+        Self::Packages,
+        Self::S3,
+        Self::Webhooks,
+        Self::Heartbeat,
+        Self::Updates,
+        Self::ReleaseNotes,
+        Self::Clock,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Packages => "packages",
+            Self::S3 => "s3",
+            Self::Webhooks => "webhooks",
+            Self::Heartbeat => "heartbeat",
+            Self::Updates => "updates",
+            Self::ReleaseNotes => "release-notes",
+            Self::Clock => "clock",
+        }
+    }
+
+    /// Timeout of one attempt unless `EXTERNAL_TIMEOUTS` sets another
+    fn default_timeout(self) -> Duration {
+        Duration::from_secs(match self {
+            Self::Packages | Self::Updates => 30,
+            Self::S3 => 300,
+            Self::ReleaseNotes => 15,
+            Self::Webhooks | Self::Heartbeat | Self::Clock => 10,
+        })
+    }
+
+    /// Heartbeats and the clock check run on a schedule, their next run is the retry
+    fn retries(self) -> u32 {
+        match self {
+            Self::Packages | Self::S3 | Self::Webhooks => 2,
+            Self::Updates | Self::ReleaseNotes => 1,
+            Self::Heartbeat | Self::Clock => 0,
+        }
+    }
+}
+
+/// Parts of the lifecycle external calls may only take a bounded time of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// From the configuration being read until Foundry is spawned or the setup UI is shown
+    Startup,
+    /// From Foundry having stopped until the wrapper exits
+    Shutdown,
+}
+
+impl Phase {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Startup => "startup",
+            Self::Shutdown => "shutdown",
+        }
+    }
+}
+
+struct Settings {
+    timeouts: BTreeMap<Service, Duration>,
+    startup_budget: Duration,
+    shutdown_budget: Duration,
+}
+
+#[derive(Default)]
+struct Breaker {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+static BREAKERS: Mutex<BTreeMap<Service, Breaker>> = Mutex::new(BTreeMap::new());
+/// The running phase and when its budget is spent
+static PHASE: Mutex<Option<(Phase, Instant)>> = Mutex::new(None);
+
+/// Take the timeouts and budgets from the configuration, management commands keep the defaults
+pub fn init(config: &AppConfig) {
+    let mut timeouts = BTreeMap::new();
+    for (name, seconds) in &config.external_timeouts {
+        match Service::ALL.iter().find(|service| service.name() == name) {
+            Some(service) => {
+                timeouts.insert(*service, Duration::from_secs(*seconds));
+            }
+            None => warn!(
+                "⚠️ EXTERNAL_TIMEOUTS names unknown service {}, known are {}",
+                name,
+                Service::ALL.map(Service::name).join(", ")
+            ),
+        }
+    }
+    let _ = SETTINGS.set(Settings {
+        timeouts,
+        startup_budget: Duration::from_secs(config.external_startup_budget),
+        shutdown_budget: Duration::from_secs(config.external_shutdown_budget),
+    });
+}
+
+/// Bound the external calls from now on to the budget of `phase`, a phase already running
+/// keeps its budget
+pub fn begin(phase: Phase) {
+    let Some(settings) = SETTINGS.get() else {
+        return;
+    };
+    let mut running = PHASE.lock().unwrap_or_else(PoisonError::into_inner);
+    if running.is_some_and(|(running, _)| running == phase) {
+        return;
+    }
+    let budget = match phase {
+        Phase::Startup => settings.startup_budget,
+        Phase::Shutdown => settings.shutdown_budget,
+    };
+    debug!("External calls get {:?} of the {}", budget, phase.name());
+    *running = Some((phase, Instant::now() + budget));
+}
+
+/// Calls are no longer bounded by the budget of `phase`
+pub fn end(phase: Phase) {
+    let mut running = PHASE.lock().unwrap_or_else(PoisonError::into_inner);
+    if running.is_some_and(|(running, _)| running == phase) {
+        *running = None;
+    }
+}
+
+/// Errors of an external call, telling apart the ones worth another attempt
+pub trait CallError: Display {
+    /// A timeout, a refused connection or an overloaded service. A rejected request answered
+    /// by a working service is not retried and does not count as a failure of it.
+    fn transient(&self) -> bool;
+    /// The call was not attempted or did not answer in time
+    fn unavailable(message: String) -> Self;
+}
+
+impl CallError for anyhow::Error {
+    fn transient(&self) -> bool {
+        self.chain()
+            .any(|cause| match cause.downcast_ref::<reqwest::Error>() {
+                Some(e) => e.status().is_none_or(|status| {
+                    status.is_server_error() || matches!(status.as_u16(), 408 | 429)
+                }),
+                None => cause
+                    .downcast_ref::<io::Error>()
+                    .is_some_and(|e| e.transient()),
+            })
+    }
+
+    fn unavailable(message: String) -> Self {
+        <io::Error as CallError>::unavailable(message).into()
+    }
+}
+
+impl CallError for io::Error {
+    fn transient(&self) -> bool {
+        !matches!(
+            self.kind(),
+            io::ErrorKind::NotFound
+                | io::ErrorKind::PermissionDenied
+                | io::ErrorKind::InvalidInput
+                | io::ErrorKind::InvalidData
+                | io::ErrorKind::Unsupported
+        )
+    }
+
+    fn unavailable(message: String) -> Self {
+        io::Error::new(io::ErrorKind::TimedOut, message)
+    }
+}
+
+/// Run `attempt` against `service` with its timeout, retrying transient failures with a
+/// backoff. Each attempt gets at most what is left of the budget of the running phase.
+///
+/// A service failing several calls in a row is skipped for a cooldown, calls fail right
+/// away then instead of waiting for the timeout each. The first call after the cooldown
+/// decides whether it is skipped again.
+pub async fn call<T, E, F, Fut>(service: Service, mut attempt: F) -> Result<T, E>
+where
+    E: CallError,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut retry = 0;
+    loop {
+        let limit = allowance(service).map_err(E::unavailable)?;
+        let result = match timeout(limit, attempt()).await {
+            Ok(result) => result,
+            Err(_) => Err(E::unavailable(format!(
+                "{} did not answer within {:?}",
+                service.name(),
+                limit
+            ))),
+        };
+        let e = match result {
+            Ok(value) => {
+                answered(service);
+                return Ok(value);
+            }
+            Err(e) if !e.transient() => {
+                answered(service);
+                return Err(e);
+            }
+            Err(e) => e,
+        };
+        let wait = BACKOFF * 2u32.pow(retry);
+        if retry >= service.retries() || !budget_allows(wait) {
+            failed(service, &e);
+            return Err(e);
+        }
+        retry += 1;
+        debug!(
+            "Calling {} failed, attempt {} in {:?}: {}",
+            service.name(),
+            retry + 1,
+            wait,
+            e
+        );
+        sleep(wait).await;
+    }
+}
+
+/// The timeout of the next attempt, or why there is none
+fn allowance(service: Service) -> Result<Duration, String> {
+    let now = Instant::now();
+    if let Some(open_until) = breaker(service, |breaker| breaker.open_until)
+        && open_until > now
+    {
+        return Err(format!(
+            "{} is skipped for another {}s after failing {} times in a row",
+            service.name(),
+            (open_until - now).as_secs() + 1,
+            FAILURE_THRESHOLD
+        ));
+    }
+    let limit = SETTINGS
+        .get()
+        .and_then(|settings| settings.timeouts.get(&service).copied())
+        .unwrap_or_else(|| service.default_timeout());
+    match *PHASE.lock().unwrap_or_else(PoisonError::into_inner) {
+        Some((phase, deadline)) if deadline <= now => Err(format!(
+            "the {} budget for external services is spent, {} is not called",
+            phase.name(),
+            service.name()
+        )),
+        Some((_, deadline)) => Ok(limit.min(deadline - now)),
+        None => Ok(limit),
+    }
+}
+
+fn budget_allows(wait: Duration) -> bool {
+    PHASE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_none_or(|(_, deadline)| Instant::now() + wait < deadline)
+}
+
+fn breaker<T>(service: Service, f: impl FnOnce(&mut Breaker) -> T) -> T {
+    let mut breakers = BREAKERS.lock().unwrap_or_else(PoisonError::into_inner);
+    f(breakers.entry(service).or_default())
+}
+
+fn answered(service: Service) {
+    let recovered = breaker(service, |breaker| {
+        let recovered = breaker.failures >= FAILURE_THRESHOLD;
+        *breaker = Breaker::default();
+        recovered
+    });
+    if recovered {
+        info!("🔌 {} answers again", service.name());
+    }
+}
+
+fn failed(service: Service, e: &impl Display) {
+    let failures = breaker(service, |breaker| {
+        breaker.failures += 1;
+        if breaker.failures >= FAILURE_THRESHOLD {
+            breaker.open_until = Some(Instant::now() + COOLDOWN);
+        }
+        breaker.failures
+    });
+    // A failed trial after the cooldown skips it again without another warning
+    if failures > FAILURE_THRESHOLD {
+        debug!("{} still fails, skipping it again: {}", service.name(), e);
+    } else if failures == FAILURE_THRESHOLD {
+        warn!(
+            "⚠️ {} failed {} calls in a row, skipping it for {:?}: {}",
+            service.name(),
+            FAILURE_THRESHOLD,
+            COOLDOWN,
+            e
+        );
+    }
+}
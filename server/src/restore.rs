@@ -0,0 +1,377 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use std::cmp::Ordering;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+use zip::read::ZipArchive;
+
+use crate::backup::{self, BackupRecord, BackupService, BackupTrigger};
+use crate::config::AppConfig;
+use crate::environment::EnvironmentManifest;
+use crate::extractor::ExtractorService;
+use crate::inhibit;
+use crate::journal::Journal;
+use crate::reconcile;
+use crate::release::{ReleaseDownload, ReleaseSource};
+use crate::status;
+use crate::tempfiles;
+use crate::utils::{compare_versions, installed_foundry_version, paths};
+
+pub const STAGING_DIR: &str = ".restore-staging";
+pub const PREVIOUS_DIR: &str = ".restore-previous";
+
+/// Restores snapshots of `BACKUP_DIR/snapshots` into `DATA_DIR`.
+///
+/// A snapshot taken under a newer Foundry than the installed one is refused: Foundry migrates
+/// worlds forward only, an older core opening them corrupts the databases silently. Systems
+/// and modules are part of the snapshot and come back at the versions the worlds were saved
+/// with, only the core is checked.
+pub struct RestoreService {
+    /// Current data is snapshotted first, so a restore can be undone
+    pub backup: BackupService,
+    /// Credentials of `FOUNDRY_USERNAME`, used to install the core version of a snapshot
+    pub download: Option<ReleaseDownload>,
+    pub target_dir: String,
+    pub foundry_port: u16,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RestoreOptions {
+    /// Only restore this world's directory, leaving `Config` and the other worlds alone
+    pub world: Option<String>,
+    /// Restore even when the snapshot was taken under a newer Foundry
+    pub force: bool,
+    /// Install the Foundry version of the snapshot first when the installed one is older
+    pub install_core: bool,
+    /// Skip the snapshot of the current data
+    pub no_backup: bool,
+    /// Install the exact Foundry version of the snapshot's environment manifest, and reinstall
+    /// its systems and modules that are missing or differ
+    pub reproduce: bool,
+}
+
+#[derive(Debug)]
+pub struct RestoreSummary {
+    pub record: BackupRecord,
+    /// The single world restored, `None` for the whole data directory
+    pub world: Option<String>,
+    /// Snapshot of the data before the restore
+    pub previous: Option<PathBuf>,
+    /// Foundry version installed for the snapshot
+    pub installed_core: Option<String>,
+    /// Packages reinstalled and differences to the environment of the snapshot
+    pub environment: Vec<String>,
+}
+
+impl RestoreService {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            backup: BackupService::from_config(config),
+            download: config.foundry_download.clone(),
+            target_dir: config.target_dir.clone(),
+            foundry_port: config.server_port,
+        }
+    }
+
+    pub async fn restore(&self, file: &str, options: RestoreOptions) -> Result<RestoreSummary> {
+        let _inhibitor = inhibit::acquire(format!("restore of {}", file))?;
+        let record = backup::catalog(None)?
+            .into_iter()
+            .find(|record| record.file == file)
+            .ok_or_else(|| anyhow!("No snapshot {}, see `backup list`", file))?;
+        let path = backup::snapshot_dir().join(&record.file);
+        if let Some(world) = &options.world {
+            if options.reproduce {
+                return Err(anyhow!(
+                    "--reproduce sets up the packages of the whole data directory, restore {} \
+                     without it",
+                    world
+                ));
+            }
+            if !record.worlds.is_empty() && !record.worlds.contains(world) {
+                return Err(anyhow!(
+                    "{} does not contain the world {}, it has {}",
+                    record.file,
+                    world,
+                    record.worlds.join(", ")
+                ));
+            }
+        }
+
+        // The databases of other worlds are closed, only the active one is in the way
+        if let Ok(server) = status::fetch_server_status(self.foundry_port).await
+            && server.active
+            && (options.world.is_none() || server.world == options.world)
+        {
+            return Err(anyhow!(
+                "World {} is active, return to the setup screen before restoring",
+                server.world.unwrap_or_default()
+            ));
+        }
+
+        let checked = path.clone();
+        let entries = tokio::task::spawn_blocking(move || verify_archive(&checked))
+            .await?
+            .with_context(|| format!("{} is damaged, nothing was restored", record.file))?;
+        info!("Verified the {} entries of {}", entries, record.file);
+
+        let manifest = EnvironmentManifest::read(&path)?;
+        if options.reproduce && manifest.is_none() {
+            return Err(anyhow!(
+                "{} was taken before snapshots recorded their environment, restore it without \
+                 --reproduce",
+                record.file
+            ));
+        }
+        let snapshot_version = match manifest
+            .as_ref()
+            .and_then(|manifest| manifest.foundry_version.clone())
+            .or_else(|| record.foundry_version.clone())
+        {
+            Some(version) => Some(version),
+            None => archive_core_version(&path)?,
+        };
+        let installed_core = self.check_core(snapshot_version, &options).await?;
+
+        let data_dir = PathBuf::from(&*paths::DATA_DIR);
+        let staging = data_dir.join(STAGING_DIR);
+        remove_if_exists(&staging)?;
+        tempfiles::track(&staging, "restore staging");
+        // Nobody follows the progress of a restore
+        let (event_tx, _) = broadcast::channel(16);
+        let extracted = ExtractorService::extract_zip(
+            path.to_string_lossy().to_string(),
+            staging.to_string_lossy().to_string(),
+            event_tx,
+        )
+        .await
+        .with_context(|| format!("Failed to extract {}", record.file));
+        if let Err(e) = extracted {
+            let _ = remove_if_exists(&staging);
+            return Err(e);
+        }
+
+        let dirs: Vec<PathBuf> = match &options.world {
+            Some(world) => {
+                let dir = Path::new("Data").join("worlds").join(world);
+                if !staging.join(&dir).join("world.json").is_file() {
+                    let _ = remove_if_exists(&staging);
+                    return Err(anyhow!("{} has no world {}", record.file, world));
+                }
+                vec![dir]
+            }
+            None => backup::INCLUDED.iter().map(PathBuf::from).collect(),
+        };
+
+        // Packages are set up in the staging copy, a failure leaves the current data alone
+        let mut environment = Vec::new();
+        if let Some(manifest) = &manifest {
+            if options.reproduce {
+                match manifest.reproduce_packages(&staging.join("Data")).await {
+                    Ok(notes) => environment.extend(notes),
+                    Err(e) => {
+                        let _ = remove_if_exists(&staging);
+                        return Err(e);
+                    }
+                }
+            }
+            environment.extend(manifest.differences());
+        }
+
+        // Taken after the extraction, pruning may remove the snapshot being restored
+        let previous = if options.no_backup {
+            None
+        } else {
+            let service = self.backup.clone();
+            let summary =
+                tokio::task::spawn_blocking(move || service.create(BackupTrigger::Manual))
+                    .await?
+                    .context("Failed to snapshot the current data, nothing was restored")?;
+            Some(summary.path)
+        };
+
+        tokio::task::spawn_blocking(move || swap_in(&data_dir, &staging, &dirs)).await??;
+        match &options.world {
+            Some(world) => info!("♻️ Restored the world {} from {}", world, record.file),
+            None => {
+                // The packages of the snapshot are the wrapper's doing, not changes made in Foundry
+                reconcile::record();
+                info!("♻️ Restored {}", record.file);
+            }
+        }
+        Ok(RestoreSummary {
+            record,
+            world: options.world,
+            previous,
+            installed_core,
+            environment,
+        })
+    }
+
+    /// Refuse snapshots of a newer core, or install it first. Answers the installed version.
+    async fn check_core(
+        &self,
+        snapshot: Option<String>,
+        options: &RestoreOptions,
+    ) -> Result<Option<String>> {
+        let installed = installed_foundry_version();
+        let (Some(snapshot), Some(current)) = (snapshot, installed) else {
+            warn!("⚠️ The Foundry version of the snapshot or the installation is unknown");
+            return Ok(None);
+        };
+        // Reproducing the environment also installs an older core
+        match compare_versions(&snapshot, &current) {
+            Ordering::Equal => return Ok(None),
+            Ordering::Less if !options.reproduce => return Ok(None),
+            _ => {}
+        }
+
+        if options.install_core || options.reproduce {
+            let Some(ReleaseDownload {
+                source:
+                    ReleaseSource::Account {
+                        username, password, ..
+                    },
+                metadata,
+                ..
+            }) = &self.download
+            else {
+                return Err(anyhow!(
+                    "Installing Foundry {} needs FOUNDRY_USERNAME and FOUNDRY_PASSWORD",
+                    snapshot
+                ));
+            };
+            let download = ReleaseDownload {
+                source: ReleaseSource::Account {
+                    username: username.clone(),
+                    password: password.clone(),
+                    version: snapshot.clone(),
+                },
+                // A pinned checksum belongs to FOUNDRY_VERSION, not to this release
+                sha256: None,
+                metadata: metadata.clone(),
+            };
+            download
+                .install(&self.target_dir)
+                .await
+                .with_context(|| format!("Failed to install Foundry {}", snapshot))?;
+            return Ok(Some(snapshot));
+        }
+        if options.force {
+            warn!(
+                "⚠️ Restoring a snapshot of Foundry {} into Foundry {}, the worlds may not load",
+                snapshot, current
+            );
+            return Ok(None);
+        }
+        Err(anyhow!(
+            "The snapshot was taken with Foundry {} but {} is installed, opening its worlds with \
+             the older version may corrupt them. Install Foundry {} first with --install-core, \
+             or restore anyway with --force",
+            snapshot,
+            current,
+            snapshot
+        ))
+    }
+}
+
+/// Replace `dirs` of `DATA_DIR` with those extracted to `staging`, putting the current ones
+/// back when a rename fails or the wrapper is killed meanwhile
+fn swap_in(data_dir: &Path, staging: &Path, dirs: &[PathBuf]) -> Result<()> {
+    let previous = data_dir.join(PREVIOUS_DIR);
+    remove_if_exists(&previous)?;
+    fs::create_dir_all(&previous)?;
+
+    let mut journal = Journal::begin("restore")?;
+    journal.clean_up(staging)?;
+    journal.clean_up(&previous)?;
+    let swapped = (|| -> Result<()> {
+        for dir in dirs {
+            let current = data_dir.join(dir);
+            if current.exists() {
+                let aside = previous.join(dir);
+                if let Some(parent) = aside.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                journal
+                    .rename(&current, &aside)
+                    .with_context(|| format!("Failed to move {} aside", current.display()))?;
+            }
+            let restored = staging.join(dir);
+            if restored.exists() {
+                if let Some(parent) = current.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                journal
+                    .rename(&restored, &current)
+                    .with_context(|| format!("Failed to restore {}", current.display()))?;
+            }
+        }
+        Ok(())
+    })();
+    journal.complete(swapped)
+}
+
+/// Read every entry of a snapshot, the zip reader fails on a CRC that does not match.
+/// Answers the number of entries.
+pub fn verify_archive(path: &Path) -> Result<usize> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        io::copy(&mut entry, &mut io::sink())
+            .with_context(|| format!("Failed to read {}", entry.name()))?;
+    }
+    Ok(archive.len())
+}
+
+/// Newest snapshot taken at or before `time`, to go back to the state of that moment
+pub fn snapshot_at(time: DateTime<Utc>) -> Result<BackupRecord> {
+    backup::catalog(None)?
+        .into_iter()
+        .filter_map(|record| {
+            let created = DateTime::parse_from_rfc3339(&record.created).ok()?;
+            (created <= time).then_some((created, record))
+        })
+        .max_by_key(|(created, _)| *created)
+        .map(|(_, record)| record)
+        .ok_or_else(|| anyhow!("No snapshot was taken before {}", time.to_rfc3339()))
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_dir_all(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Newest `coreVersion` of the worlds in a snapshot, for snapshots taken before the catalog
+fn archive_core_version(path: &Path) -> Result<Option<String>> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut newest: Option<String> = None;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().to_string();
+        let parts: Vec<&str> = name.split('/').collect();
+        if !matches!(parts[..], ["Data", "worlds", _, "world.json"]) {
+            continue;
+        }
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents)?;
+        let Ok(manifest) = serde_json::from_str::<serde_json::Value>(&contents) else {
+            continue;
+        };
+        if let Some(version) = manifest["coreVersion"].as_str()
+            && newest
+                .as_deref()
+                .is_none_or(|newest| compare_versions(version, newest) == Ordering::Greater)
+        {
+            newest = Some(version.to_string());
+        }
+    }
+    Ok(newest)
+}
@@ -0,0 +1,189 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::sync::broadcast;
+use tokio::time::{Duration, sleep};
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+use crate::backup;
+use crate::config::AppConfig;
+use crate::extractor::ExtractorService;
+use crate::jobs;
+use crate::notify::{self, Notification, Severity};
+use crate::schedule::CronSchedule;
+use crate::smoke::SmokeTest;
+use crate::state::WrapperState;
+use crate::utils::paths;
+
+/// Directory below `DATA_DIR` snapshots are restored to for testing
+pub const TEST_DIR: &str = ".restore-test";
+
+/// Outcome of the last test restore of a snapshot, kept in its catalog entry
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RestoreTestResult {
+    #[schema(format = DateTime)]
+    pub tested_at: String,
+    pub passed: bool,
+    pub reason: String,
+}
+
+/// Restores a snapshot into a throwaway data directory and boots each of its worlds there on
+/// a spare port, proving the snapshot can actually be restored. The live data is not touched.
+#[derive(Debug, Clone)]
+pub struct RestoreTest {
+    pub port: u16,
+    pub timeout: Duration,
+    pub script_path: String,
+}
+
+impl RestoreTest {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            port: config.restore_test_port,
+            timeout: Duration::from_secs(config.smoke_test_timeout),
+            script_path: config.foundry_script.clone(),
+        }
+    }
+
+    /// Test `file`, or the newest snapshot, and record the result in the catalog
+    pub async fn run(&self, file: Option<&str>) -> Result<(String, RestoreTestResult)> {
+        let catalog = backup::catalog(None)?;
+        let record = match file {
+            Some(file) => catalog.into_iter().find(|record| record.file == file),
+            None => catalog.into_iter().last(),
+        }
+        .ok_or_else(|| match file {
+            Some(file) => anyhow!("No snapshot {}, see `backup list`", file),
+            None => anyhow!("There is no snapshot to test yet"),
+        })?;
+
+        let dir = PathBuf::from(&*paths::DATA_DIR).join(TEST_DIR);
+        let tested = self
+            .test(&backup::snapshot_dir().join(&record.file), &dir)
+            .await;
+        if let Err(e) = fs::remove_dir_all(&dir)
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            warn!("Failed to remove {}: {}", dir.display(), e);
+        }
+        let result = RestoreTestResult {
+            tested_at: Utc::now().to_rfc3339(),
+            passed: tested
+                .as_ref()
+                .is_ok_and(|(_, failures)| failures.is_empty()),
+            reason: match tested {
+                Ok((0, _)) => "The snapshot extracted, it contains no worlds".to_string(),
+                Ok((worlds, failures)) if failures.is_empty() => {
+                    format!("All {} worlds loaded", worlds)
+                }
+                Ok((_, failures)) => failures.join("; "),
+                Err(e) => format!("{:#}", e),
+            },
+        };
+
+        let mut state = WrapperState::load()?;
+        if let Some(known) = state
+            .backups
+            .iter_mut()
+            .find(|known| known.file == record.file)
+        {
+            known.restore_test = Some(result.clone());
+            state.save()?;
+        }
+        if result.passed {
+            info!("✅ Test restore of {} passed", record.file);
+        } else {
+            warn!(
+                "❌ Test restore of {} failed: {}",
+                record.file, result.reason
+            );
+        }
+        Ok((record.file, result))
+    }
+
+    /// Extract the snapshot to `dir` and boot its worlds one after the other, answering the
+    /// number of worlds and those that did not load
+    async fn test(&self, snapshot: &Path, dir: &Path) -> Result<(usize, Vec<String>)> {
+        if dir.exists() {
+            fs::remove_dir_all(dir)
+                .with_context(|| format!("Failed to remove {}", dir.display()))?;
+        }
+        // Nobody follows the progress of a test restore
+        let (event_tx, _) = broadcast::channel(16);
+        ExtractorService::extract_zip(
+            snapshot.to_string_lossy().to_string(),
+            dir.to_string_lossy().to_string(),
+            event_tx,
+        )
+        .await
+        .context("The snapshot does not extract")?;
+
+        let worlds = restored_worlds(dir);
+        let mut failures = Vec::new();
+        for world in &worlds {
+            let outcome = SmokeTest {
+                world: world.clone(),
+                data_dir: dir.to_string_lossy().to_string(),
+                port: self.port,
+                timeout: self.timeout,
+                background: true,
+            }
+            .run(&self.script_path)
+            .await?;
+            if !outcome.passed {
+                failures.push(format!("{}: {}", world, outcome.reason));
+            }
+        }
+        Ok((worlds.len(), failures))
+    }
+}
+
+/// Worlds in the restored data directory, whatever the catalog says about the snapshot
+fn restored_worlds(dir: &Path) -> Vec<String> {
+    let mut worlds: Vec<String> = fs::read_dir(dir.join("Data").join("worlds"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().join("world.json").is_file())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    worlds.sort();
+    worlds
+}
+
+/// Test restores the newest snapshot at the times of `RESTORE_TEST_SCHEDULE`
+#[derive(Clone)]
+pub struct RestoreTestScheduler {
+    pub test: RestoreTest,
+    pub schedule: CronSchedule,
+}
+
+impl RestoreTestScheduler {
+    pub async fn run(self) {
+        loop {
+            let Some(next) = self.schedule.next_after(Local::now()) else {
+                warn!("⚠️ RESTORE_TEST_SCHEDULE never matches, no test restores run");
+                return;
+            };
+            debug!("Next test restore at {}", next);
+            sleep((next - Local::now()).to_std().unwrap_or_default()).await;
+
+            let slot = jobs::slot("test restore").await;
+            let tested = self.test.run(None).await;
+            drop(slot);
+            let message = match tested {
+                Ok((_, result)) if result.passed => continue,
+                Ok((file, result)) => format!("{} does not restore: {}", file, result.reason),
+                Err(e) => format!("{:#}", e),
+            };
+            notify::send(Notification {
+                severity: Severity::Critical,
+                title: "Test restore failed".to_string(),
+                message,
+            });
+        }
+    }
+}
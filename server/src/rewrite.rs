@@ -0,0 +1,272 @@
+use anyhow::{Context, Result, anyhow};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::db::Collection;
+use crate::permissions;
+use crate::scan::ScanService;
+use crate::utils::paths;
+use crate::worlds::{self, WorldService};
+
+/// Replaces the start of asset paths in world documents, e.g. after reorganizing the asset
+/// folders every token and tile points to.
+///
+/// The worlds must not be running, their databases are written in place.
+pub struct PathRewrite {
+    /// Prefix of the paths to replace, e.g. `assets/old/`
+    pub from: String,
+    pub to: String,
+    /// Worlds to rewrite, all of them when empty
+    pub worlds: Vec<String>,
+    /// Only report what would change
+    pub dry_run: bool,
+    /// Copy the databases to `BACKUP_DIR/path-rewrites` before writing them
+    pub backup: bool,
+    /// Port of the running Foundry, its active world is not written
+    pub foundry_port: u16,
+}
+
+/// Outcome of a rewrite
+#[derive(Default)]
+pub struct RewriteSummary {
+    pub documents: usize,
+    /// Every distinct path replaced and how often
+    pub paths: BTreeMap<(String, String), usize>,
+    /// New local paths without a file behind them
+    pub missing: Vec<String>,
+    pub backups: Vec<PathBuf>,
+}
+
+impl PathRewrite {
+    pub async fn run(&self) -> Result<RewriteSummary> {
+        if self.from.is_empty() {
+            return Err(anyhow!("The prefix to replace must not be empty"));
+        }
+        let worlds = world_ids(&self.worlds)?;
+        for world in &worlds {
+            worlds::refuse_active(self.foundry_port, world, "rewriting its paths").await?;
+        }
+        let mut summary = RewriteSummary::default();
+        let mut map = |path: &str| {
+            // Foundry stores most paths relative to `Data`, some with a leading slash
+            let (slash, relative) = match path.strip_prefix('/') {
+                Some(relative) if !self.from.starts_with('/') => ("/", relative),
+                _ => ("", path),
+            };
+            let rest = relative.strip_prefix(self.from.as_str())?;
+            let replaced = format!("{}{}{}", slash, self.to, rest);
+            *summary
+                .paths
+                .entry((path.to_string(), replaced.clone()))
+                .or_default() += 1;
+            Some(replaced)
+        };
+
+        let mut documents = 0;
+        let mut backups = Vec::new();
+        for world in &worlds {
+            if self.backup && !self.dry_run {
+                backups.push(backup(world)?);
+            }
+            documents += rewrite_world(world, self.dry_run, &mut map)?;
+        }
+        summary.documents = documents;
+        summary.backups = backups;
+
+        let data_dir = PathBuf::from(&*paths::DATA_DIR).join("Data");
+        let missing: BTreeSet<&String> = summary
+            .paths
+            .keys()
+            .map(|(_, replaced)| replaced)
+            .filter(|replaced| !replaced.contains("://"))
+            .filter(|replaced| {
+                let relative = replaced.trim_start_matches('/');
+                let decoded = percent_decode(relative).unwrap_or_else(|| relative.to_string());
+                !data_dir.join(decoded).exists()
+            })
+            .collect();
+        summary.missing = missing.into_iter().cloned().collect();
+        Ok(summary)
+    }
+}
+
+/// The worlds `worlds` names, every world when empty, checked before anything is written
+pub fn world_ids(worlds: &[String]) -> Result<Vec<String>> {
+    if worlds.is_empty() {
+        return WorldService::ids();
+    }
+    for world in worlds {
+        if !paths::WORLDS_DIR.join(world).join("world.json").is_file() {
+            return Err(anyhow!("World {} does not exist", world));
+        }
+    }
+    Ok(worlds.to_vec())
+}
+
+/// Copy the databases of a world to `BACKUP_DIR/path-rewrites/<world>/<timestamp>/`
+fn backup(world_id: &str) -> Result<PathBuf> {
+    let source = paths::WORLDS_DIR.join(world_id).join("data");
+    let target = PathBuf::from(&*paths::BACKUP_DIR)
+        .join("path-rewrites")
+        .join(world_id)
+        .join(chrono::Utc::now().format("%Y%m%d-%H%M%S").to_string());
+    for entry in ScanService::walk(&source, &[])? {
+        let destination = target.join(entry.path.strip_prefix(&source)?);
+        if let Some(parent) = destination.parent() {
+            permissions::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        fs::copy(&entry.path, &destination)
+            .with_context(|| format!("Failed to copy {}", entry.path.display()))?;
+    }
+    info!(
+        "Backed up the databases of {} to {}",
+        world_id,
+        target.display()
+    );
+    Ok(target)
+}
+
+/// Replace the asset paths `map` knows a new place for in every document of a world,
+/// returning the number of documents changed. With `dry_run` they are only counted.
+pub fn rewrite_world(
+    world_id: &str,
+    dry_run: bool,
+    map: &mut dyn FnMut(&str) -> Option<String>,
+) -> Result<usize> {
+    let data_dir = paths::WORLDS_DIR.join(world_id).join("data");
+    let mut total = 0;
+    for name in collection_names(&data_dir)?
+        .iter()
+        .filter(|name| Collection::exists(world_id, name))
+    {
+        let mut collection = Collection::open(world_id, name)?;
+        let changed = collection.rewrite(dry_run, |document| rewrite_value(document, map))?;
+        if changed > 0 {
+            info!("  - {}/{}: {} documents", world_id, name, changed);
+        }
+        total += changed;
+    }
+    Ok(total)
+}
+
+/// Pass every string of every document of a world to `visit`, its compendium packs included.
+///
+/// The databases are read from point in time copies, so this also works while the world runs.
+pub fn visit_world(world_id: &str, visit: &mut dyn FnMut(&str)) -> Result<()> {
+    let world_dir = paths::WORLDS_DIR.join(world_id);
+    for (dir, open) in [
+        (
+            "data",
+            Collection::open_snapshot as fn(&str, &str) -> Result<Collection>,
+        ),
+        ("packs", Collection::open_pack),
+    ] {
+        let dir = world_dir.join(dir);
+        if !dir.is_dir() {
+            continue;
+        }
+        for name in collection_names(&dir)? {
+            let mut collection = open(world_id, &name)?;
+            collection.rewrite(true, |document| {
+                visit_strings(document, visit);
+                false
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Collections stored below `dir`, LevelDB directories and NeDB files alike
+fn collection_names(dir: &Path) -> Result<Vec<String>> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to list {}", dir.display()))?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.path().is_dir() {
+                Some(name)
+            } else {
+                name.strip_suffix(".db").map(str::to_string)
+            }
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+/// Pass every string of `value`, nested ones included, to `visit`
+pub fn visit_strings(value: &Value, visit: &mut dyn FnMut(&str)) {
+    match value {
+        Value::String(text) => visit(text),
+        Value::Array(values) => values.iter().for_each(|v| visit_strings(v, visit)),
+        Value::Object(object) => object.values().for_each(|v| visit_strings(v, visit)),
+        _ => {}
+    }
+}
+
+/// Replace asset paths in `value`, both whole strings like `img` and `src` attributes in the
+/// HTML of journals and biographies
+fn rewrite_value(value: &mut Value, map: &mut dyn FnMut(&str) -> Option<String>) -> bool {
+    match value {
+        Value::String(text) => {
+            if let Some(replacement) = map(text) {
+                *text = replacement;
+                return true;
+            }
+            let mut changed = false;
+            for quote in ['"', '\''] {
+                let attribute = format!("src={}", quote);
+                if !text.contains(&attribute) {
+                    continue;
+                }
+                let mut out = String::with_capacity(text.len());
+                let mut rest = text.as_str();
+                while let Some(start) = rest.find(&attribute) {
+                    let (before, after) = rest.split_at(start + attribute.len());
+                    out.push_str(before);
+                    let end = after.find(quote).unwrap_or(after.len());
+                    match map(&after[..end]) {
+                        Some(replacement) => {
+                            out.push_str(&replacement);
+                            changed = true;
+                        }
+                        None => out.push_str(&after[..end]),
+                    }
+                    rest = &after[end..];
+                }
+                out.push_str(rest);
+                *text = out;
+            }
+            changed
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .fold(false, |changed, v| rewrite_value(v, map) | changed),
+        Value::Object(object) => object
+            .values_mut()
+            .fold(false, |changed, v| rewrite_value(v, map) | changed),
+        _ => false,
+    }
+}
+
+pub fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
@@ -0,0 +1,167 @@
+use std::fs;
+use std::path::Path;
+
+/// Overflow id the kernel shows for owners that are not mapped into the user namespace
+pub const OVERFLOW_UID: u32 = 65534;
+
+/// One line of `/proc/self/uid_map`: `count` ids starting at `inside` map to `outside` on the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UidMapping {
+    pub inside: u32,
+    pub outside: u32,
+    pub count: u32,
+}
+
+/// The container engine and user namespace the wrapper runs in
+#[derive(Debug, Clone)]
+pub struct RuntimeInfo {
+    pub engine: &'static str,
+    pub uid_map: Vec<UidMapping>,
+    /// Whether the process holds CAP_CHOWN, rootless runtimes usually drop it
+    pub can_chown: bool,
+}
+
+impl RuntimeInfo {
+    pub fn detect() -> Self {
+        // Podman writes `/run/.containerenv`, Docker `/.dockerenv`
+        let engine = if Path::new("/run/.containerenv").exists() {
+            "podman"
+        } else if Path::new("/.dockerenv").exists() {
+            "docker"
+        } else {
+            "unknown"
+        };
+        let uid_map = fs::read_to_string("/proc/self/uid_map").unwrap_or_default();
+        let status = fs::read_to_string("/proc/self/status").unwrap_or_default();
+        Self::from_parts(engine, &uid_map, &status)
+    }
+
+    /// Build from the contents of `/proc/self/uid_map` and `/proc/self/status`
+    pub fn from_parts(engine: &'static str, uid_map: &str, status: &str) -> Self {
+        let uid_map = uid_map
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace().map(|f| f.parse::<u32>().ok());
+                Some(UidMapping {
+                    inside: fields.next()??,
+                    outside: fields.next()??,
+                    count: fields.next()??,
+                })
+            })
+            .collect();
+
+        // CAP_CHOWN is bit 0 of the effective capability set
+        let can_chown = status
+            .lines()
+            .find_map(|line| line.strip_prefix("CapEff:"))
+            .and_then(|caps| u64::from_str_radix(caps.trim(), 16).ok())
+            .is_some_and(|caps| caps & 1 != 0);
+
+        Self {
+            engine,
+            uid_map,
+            can_chown,
+        }
+    }
+
+    /// Ids are remapped, e.g. rootless Podman where container root is the invoking host user
+    pub fn rootless(&self) -> bool {
+        match self.uid_map.as_slice() {
+            // No user namespace maps the complete id range onto itself
+            [] => false,
+            [mapping] => {
+                !(mapping.inside == 0 && mapping.outside == 0 && mapping.count == u32::MAX)
+            }
+            _ => true,
+        }
+    }
+
+    /// Host uid of the container `uid`, `None` when it is not mapped
+    pub fn host_uid(&self, uid: u32) -> Option<u32> {
+        self.uid_map
+            .iter()
+            .find(|m| uid >= m.inside && uid - m.inside < m.count)
+            .map(|m| m.outside + (uid - m.inside))
+    }
+
+    pub fn describe(&self) -> String {
+        if self.rootless() {
+            format!("{} (rootless, user namespace)", self.engine)
+        } else {
+            self.engine.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `/proc/self/status` of root with every capability
+    const ROOT_STATUS: &str = "Name:\tfoundry-watcher\nUid:\t0\t0\t0\t0\nCapInh:\t0000000000000000\nCapPrm:\t000001ffffffffff\nCapEff:\t000001ffffffffff\n";
+    /// The default Docker set without CAP_CHOWN, e.g. `--cap-drop CHOWN`
+    const NO_CHOWN_STATUS: &str = "Uid:\t0\t0\t0\t0\nCapEff:\t00000000a80425fa\n";
+
+    #[test]
+    fn identity_map_is_not_rootless() {
+        let info =
+            RuntimeInfo::from_parts("docker", "         0          0 4294967295\n", ROOT_STATUS);
+        assert_eq!(
+            info.uid_map,
+            vec![UidMapping {
+                inside: 0,
+                outside: 0,
+                count: u32::MAX
+            }]
+        );
+        assert!(!info.rootless());
+        assert!(info.can_chown);
+        assert_eq!(info.host_uid(1000), Some(1000));
+        assert_eq!(info.describe(), "docker");
+    }
+
+    #[test]
+    fn rootless_podman_maps_root_to_the_invoking_user() {
+        let info = RuntimeInfo::from_parts(
+            "podman",
+            "         0       1000          1\n         1     100000      65536\n",
+            ROOT_STATUS,
+        );
+        assert!(info.rootless());
+        assert_eq!(info.host_uid(0), Some(1000));
+        assert_eq!(info.host_uid(1000), Some(100999));
+        assert_eq!(info.host_uid(65536), Some(165535));
+        assert_eq!(info.host_uid(65537), None);
+        assert_eq!(info.describe(), "podman (rootless, user namespace)");
+    }
+
+    #[test]
+    fn remapped_root_is_rootless() {
+        // Docker with `userns-remap`, container root is an unprivileged host id
+        let info = RuntimeInfo::from_parts("docker", "0 231072 65536\n", NO_CHOWN_STATUS);
+        assert!(info.rootless());
+        assert!(!info.can_chown);
+        assert_eq!(info.host_uid(0), Some(231072));
+        assert_eq!(info.host_uid(OVERFLOW_UID), Some(231072 + OVERFLOW_UID));
+    }
+
+    #[test]
+    fn unreadable_proc_files_mean_no_namespace_and_no_capabilities() {
+        let info = RuntimeInfo::from_parts("unknown", "", "");
+        assert!(info.uid_map.is_empty());
+        assert!(!info.rootless());
+        assert!(!info.can_chown);
+        assert_eq!(info.host_uid(0), None);
+        // Malformed lines are skipped
+        let info = RuntimeInfo::from_parts("unknown", "0 0\nx 1 2\n5 6 7\n", "CapEff:\tzz\n");
+        assert_eq!(
+            info.uid_map,
+            vec![UidMapping {
+                inside: 5,
+                outside: 6,
+                count: 7
+            }]
+        );
+        assert!(!info.can_chown);
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::{Context, Result};
+use ignore::overrides::OverrideBuilder;
+use ignore::{WalkBuilder, WalkState};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use tracing::{debug, warn};
+
+/// A regular file found while walking a directory tree
+#[derive(Debug, Clone)]
+pub struct ScanEntry {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// Aggregated size information for a directory tree
+#[derive(Debug, Clone, Default)]
+pub struct DiskUsage {
+    pub total_bytes: u64,
+    pub file_count: u64,
+    /// Bytes per first-level entry below the scanned root
+    pub by_top_level: BTreeMap<String, u64>,
+}
+
+pub struct ScanService;
+
+impl ScanService {
+    /// Walk `root` on all available cores, skipping paths matching any of the `excludes` globs.
+    ///
+    /// Globs use gitignore syntax and are matched relative to `root`; a trailing `/` only
+    /// matches directories, which prunes the whole subtree from the walk.
+    pub fn walk(root: &Path, excludes: &[String]) -> Result<Vec<ScanEntry>> {
+        let mut overrides = OverrideBuilder::new(root);
+        for pattern in excludes {
+            // Overrides are whitelists by default, a leading `!` turns them into ignore rules
+            overrides
+                .add(&format!("!{}", pattern))
+                .with_context(|| format!("Invalid scan exclusion glob: {}", pattern))?;
+        }
+        let overrides = overrides
+            .build()
+            .context("Failed to build scan exclusions")?;
+
+        let walker = WalkBuilder::new(root)
+            .standard_filters(false)
+            .follow_links(false)
+            .overrides(overrides)
+            .build_parallel();
+
+        let (tx, rx) = mpsc::channel();
+        walker.run(|| {
+            let tx = tx.clone();
+            Box::new(move |result| {
+                match result {
+                    Ok(entry) => {
+                        if entry.file_type().is_some_and(|t| t.is_file()) {
+                            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                            let _ = tx.send(ScanEntry {
+                                path: entry.into_path(),
+                                size,
+                            });
+                        }
+                    }
+                    Err(e) => warn!("Skipping unreadable path during scan: {}", e),
+                }
+                WalkState::Continue
+            })
+        });
+        drop(tx);
+
+        let entries: Vec<ScanEntry> = rx.into_iter().collect();
+        debug!("Scanned {} files below {}", entries.len(), root.display());
+        Ok(entries)
+    }
+
+    /// Sum the size of every file below `root`
+    pub fn disk_usage(root: &Path, excludes: &[String]) -> Result<DiskUsage> {
+        let usage =
+            Self::walk(root, excludes)?
+                .iter()
+                .fold(DiskUsage::default(), |mut usage, entry| {
+                    usage.total_bytes += entry.size;
+                    usage.file_count += 1;
+                    if let Some(top) = entry
+                        .path
+                        .strip_prefix(root)
+                        .ok()
+                        .and_then(|p| p.components().next())
+                    {
+                        let key = top.as_os_str().to_string_lossy().to_string();
+                        *usage.by_top_level.entry(key).or_default() += entry.size;
+                    }
+                    usage
+                });
+        Ok(usage)
+    }
+}
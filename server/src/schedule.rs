@@ -0,0 +1,123 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Datelike, Duration, Local, TimeZone, Timelike};
+use std::collections::BTreeSet;
+
+/// Cron expression in local time: minute, hour, day of month, month and day of week, e.g.
+/// `30 3 * * *` for every night at 03:30. Fields take `*`, lists, ranges and `/` steps.
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    minutes: BTreeSet<u32>,
+    hours: BTreeSet<u32>,
+    days: BTreeSet<u32>,
+    months: BTreeSet<u32>,
+    /// 0 is Sunday, 7 is accepted for it as well
+    weekdays: BTreeSet<u32>,
+    /// Cron matches either day field when both are restricted
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let expression = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(anyhow!(
+                "expected five fields (minute hour day month weekday), got {}",
+                expression
+            ));
+        };
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        if weekdays.remove(&7) {
+            weekdays.insert(0);
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            days_restricted: day != "*",
+            weekdays_restricted: weekday != "*",
+        })
+    }
+
+    /// First matching minute after `after`, `None` for expressions that never match
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        // Every expression that can match does so within four years, February 29th included
+        let mut time = start.naive_local();
+        let end = time + Duration::days(4 * 366);
+        while time < end {
+            if !self.months.contains(&time.month()) || !self.day_matches(&time) {
+                time = (time.date() + Duration::days(1)).and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !self.hours.contains(&time.hour()) {
+                time = time.with_minute(0)? + Duration::hours(1);
+                continue;
+            }
+            if self.minutes.contains(&time.minute()) {
+                // Times skipped by a daylight saving change do not exist, the next one is used
+                if let Some(local) = Local.from_local_datetime(&time).earliest() {
+                    return Some(local);
+                }
+            }
+            time += Duration::minutes(1);
+        }
+        None
+    }
+
+    fn day_matches(&self, time: &chrono::NaiveDateTime) -> bool {
+        let day = self.days.contains(&time.day());
+        let weekday = self
+            .weekdays
+            .contains(&time.weekday().num_days_from_sunday());
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            (true, false) => day,
+            (false, true) => weekday,
+            (false, false) => true,
+        }
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<BTreeSet<u32>> {
+    let mut values = BTreeSet::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)),
+            None => (part, Some(1)),
+        };
+        let step = step.ok_or_else(|| anyhow!("invalid step in {}", part))?;
+        let (from, to) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((from, to)) => (parse_value(from)?, parse_value(to)?),
+                // `5/10` starts at 5 and runs to the end of the range
+                None if part.contains('/') => (parse_value(range)?, max),
+                None => {
+                    let value = parse_value(range)?;
+                    (value, value)
+                }
+            },
+        };
+        if from < min || to > max || from > to {
+            return Err(anyhow!("{} is outside {}-{}", part, min, max));
+        }
+        values.extend((from..=to).step_by(step as usize));
+    }
+    Ok(values)
+}
+
+fn parse_value(value: &str) -> Result<u32> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("{} is not a number", value))
+}
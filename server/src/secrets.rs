@@ -0,0 +1,28 @@
+use std::fs;
+use tracing::warn;
+
+use crate::configfile;
+use crate::hostfs;
+
+/// Read a secret from `NAME` or from the file named by `NAME_FILE`, e.g. a Docker secret in
+/// `/run/secrets` or a mounted Kubernetes secret. Empty values count as unset.
+pub fn from_env(name: &str) -> Option<String> {
+    let file_var = format!("{}_FILE", name);
+    if let Ok(value) = configfile::var(name) {
+        if configfile::var(&file_var).is_ok() {
+            warn!("⚠️ {} and {} are both set, using {}", name, file_var, name);
+        }
+        return Some(value).filter(|v| !v.is_empty());
+    }
+    let path = configfile::var(&file_var).ok()?;
+    match fs::read_to_string(&path) {
+        // Secrets written on Windows hosts may carry a BOM and CRLF
+        Ok(contents) => {
+            Some(hostfs::normalize_text(&contents).trim().to_string()).filter(|v| !v.is_empty())
+        }
+        Err(e) => {
+            warn!("Failed to read {} {}: {}", file_var, path, e);
+            None
+        }
+    }
+}
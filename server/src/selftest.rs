@@ -0,0 +1,291 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::env;
+use std::fs::{self, File};
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+use tokio::sync::broadcast;
+use tokio::time::{Duration, timeout};
+use tracing::debug;
+use zip::ZipArchive;
+use zip::write::{SimpleFileOptions, ZipWriter};
+
+use crate::backup::{BackupService, BackupTrigger, Retention};
+use crate::install::InstallService;
+use crate::launch::terminate_group;
+use crate::options::{ApplyMode, FoundryOptions};
+use crate::supervisor::{RestartPolicy, RestartSupervisor};
+use crate::utils::{installed_foundry_version, paths};
+
+/// Set to the fixture directory in the process running the checks
+const FIXTURE_VAR: &str = "SELF_TEST_FIXTURE";
+/// Passed on to the fixture process, everything else of the container's configuration is not
+const PASSED_VARS: &[&str] = &["PATH", "HOME", "TMPDIR", "RUST_LOG", "WRAPPER_LANGUAGE"];
+/// The fixture process is killed after this
+const SELF_TEST_TIMEOUT: Duration = Duration::from_secs(300);
+/// Version of the bundled dummy release
+const DUMMY_VERSION: &str = "0.0.0-self-test";
+const FIXTURE_WORLD: &str = "self-test";
+const REPORT: &str = "report.json";
+
+/// Outcome of one part of the wrapper exercised by `self-test`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SelfTestCheck {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// The fixture directory when this process was started by [`run`] to run the checks
+pub fn fixture() -> Option<PathBuf> {
+    env::var(FIXTURE_VAR).ok().map(PathBuf::from)
+}
+
+/// Exercise the installer, supervisor, config renderer and backup pipeline against fixtures,
+/// e.g. after building a custom image.
+///
+/// The paths are read once per process, so the checks run in a copy of this binary with
+/// `DATA_DIR`, `APPLICATION_DIR` and `BACKUP_DIR` in a temporary directory and none of the
+/// container's other settings. The real volumes are not touched.
+pub async fn run() -> Result<Vec<SelfTestCheck>> {
+    let root = env::temp_dir().join(format!("foundry-self-test-{}", std::process::id()));
+    let ran = run_fixture_process(&root).await;
+    if let Err(e) = fs::remove_dir_all(&root) {
+        debug!("Failed to remove {}: {}", root.display(), e);
+    }
+    ran
+}
+
+async fn run_fixture_process(root: &Path) -> Result<Vec<SelfTestCheck>> {
+    for dir in ["app", "data", "backups"] {
+        fs::create_dir_all(root.join(dir))
+            .with_context(|| format!("Failed to create {}", root.join(dir).display()))?;
+    }
+    let mut command = Command::new(env::current_exe().context("Failed to find this binary")?);
+    command
+        .arg("self-test")
+        .env_clear()
+        .envs(
+            PASSED_VARS
+                .iter()
+                .filter_map(|name| Some((name, env::var(name).ok()?))),
+        )
+        .env(FIXTURE_VAR, root)
+        .env("APPLICATION_DIR", root.join("app"))
+        .env("DATA_DIR", root.join("data"))
+        .env("BACKUP_DIR", root.join("backups"))
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+    let status = timeout(SELF_TEST_TIMEOUT, command.status())
+        .await
+        .map_err(|_| {
+            anyhow!(
+                "The self-test did not finish within {:?}",
+                SELF_TEST_TIMEOUT
+            )
+        })?
+        .context("Failed to start the self-test")?;
+
+    let report = fs::read_to_string(root.join(REPORT))
+        .map_err(|_| anyhow!("The self-test exited with {} before reporting", status))?;
+    Ok(serde_json::from_str(&report)?)
+}
+
+/// Run the checks in the fixture process and report them to the parent
+pub async fn run_in_fixture(root: &Path) -> Result<()> {
+    let checks = vec![
+        check("installer", check_installer(root)).await,
+        check("supervisor", check_supervisor()).await,
+        check("config renderer", check_config_renderer()).await,
+        check("backup", check_backup()).await,
+    ];
+    fs::write(root.join(REPORT), serde_json::to_string(&checks)?)
+        .context("Failed to write the self-test report")
+}
+
+async fn check(name: &str, check: impl Future<Output = Result<String>>) -> SelfTestCheck {
+    let (passed, detail) = match check.await {
+        Ok(detail) => (true, detail),
+        Err(e) => (false, format!("{:#}", e)),
+    };
+    SelfTestCheck {
+        name: name.to_string(),
+        passed,
+        detail,
+    }
+}
+
+/// Install the dummy release, then make sure an archive without `main.js` does not replace it
+async fn check_installer(root: &Path) -> Result<String> {
+    let release = root.join("foundryvtt-dummy.zip");
+    write_archive(
+        &release,
+        &[
+            (
+                "resources/app/package.json",
+                json!({ "name": "foundryvtt", "version": DUMMY_VERSION }).to_string(),
+            ),
+            ("resources/app/main.js", "process.exit(0);\n".to_string()),
+        ],
+    )?;
+    InstallService::install_release(&release.to_string_lossy(), &paths::APPLICATION_DIR).await?;
+    if installed_foundry_version().as_deref() != Some(DUMMY_VERSION) {
+        return Err(anyhow!("The installed version is not {}", DUMMY_VERSION));
+    }
+    if !paths::resolve_foundry_script_path().exists() {
+        return Err(anyhow!("main.js was not installed"));
+    }
+
+    let broken = root.join("foundryvtt-broken.zip");
+    write_archive(&broken, &[("resources/app/package.json", "{}".to_string())])?;
+    let (event_tx, _) = broadcast::channel(16);
+    let installed = InstallService::install_archive(
+        &broken.to_string_lossy(),
+        &paths::APPLICATION_DIR,
+        event_tx,
+    )
+    .await;
+    if installed.is_ok() {
+        return Err(anyhow!("An archive without main.js was installed"));
+    }
+    if installed_foundry_version().as_deref() != Some(DUMMY_VERSION) {
+        return Err(anyhow!("A broken archive replaced the installation"));
+    }
+    Ok(format!(
+        "installed Foundry {} from a dummy archive and kept it when a broken one was rejected",
+        DUMMY_VERSION
+    ))
+}
+
+/// Restart decisions for a crashing and a cleanly exiting stub, and stopping a stub that runs
+async fn check_supervisor() -> Result<String> {
+    let mut supervisor = RestartSupervisor::new(RestartPolicy::OnFailure, Some(1));
+    supervisor.started();
+    let crashed = stub("exit 3")
+        .status()
+        .await
+        .context("Failed to start a stub child")?;
+    if crashed.success() || supervisor.next_restart(true).is_none() {
+        return Err(anyhow!("A crashed child was not restarted"));
+    }
+    if supervisor.next_restart(true).is_some() {
+        return Err(anyhow!("The child was restarted beyond MAX_RESTARTS"));
+    }
+
+    let mut supervisor = RestartSupervisor::new(RestartPolicy::OnFailure, None);
+    supervisor.started();
+    let exited = stub("exit 0")
+        .status()
+        .await
+        .context("Failed to start a stub child")?;
+    if !exited.success() || supervisor.next_restart(false).is_some() {
+        return Err(anyhow!("A cleanly exited child was restarted on-failure"));
+    }
+
+    let mut running = stub("sleep 60 & wait")
+        .process_group(0)
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to start a stub child")?;
+    terminate_group(&mut running, Duration::from_secs(5)).await;
+    if running.try_wait()?.is_none() {
+        return Err(anyhow!("The stub child was not stopped"));
+    }
+    Ok(
+        "restarted a crashed stub child once, not a clean exit, and stopped a running one"
+            .to_string(),
+    )
+}
+
+fn stub(script: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(script).stdin(Stdio::null());
+    command
+}
+
+/// Render options over an existing `options.json`, keeping the keys it does not set
+async fn check_config_renderer() -> Result<String> {
+    let path = Path::new(&*paths::DATA_DIR)
+        .join("Config")
+        .join("options.json");
+    fs::create_dir_all(path.parent().unwrap_or(Path::new("/")))?;
+    fs::write(&path, json!({ "port": 1, "selfTest": "kept" }).to_string())?;
+
+    let options = FoundryOptions {
+        port: Some(30000),
+        hostname: Some("self-test.example.com".to_string()),
+        proxy_ssl: Some(true),
+        ..Default::default()
+    };
+    options.render(ApplyMode::Auto)?;
+
+    let rendered: Value = serde_json::from_str(&fs::read_to_string(&path)?)
+        .context("Rendered options.json is not JSON")?;
+    let expected = [
+        ("port", json!(30000)),
+        ("hostname", json!("self-test.example.com")),
+        ("proxySSL", json!(true)),
+        ("selfTest", json!("kept")),
+    ];
+    for (key, value) in expected {
+        if rendered[key] != value {
+            return Err(anyhow!("{} is {} instead of {}", key, rendered[key], value));
+        }
+    }
+    Ok("merged port, hostname and proxySSL into options.json, other keys kept".to_string())
+}
+
+/// Snapshot a fixture world and look for it in the archive
+async fn check_backup() -> Result<String> {
+    let world = paths::WORLDS_DIR.join(FIXTURE_WORLD);
+    fs::create_dir_all(world.join("data"))?;
+    fs::write(
+        world.join("world.json"),
+        json!({ "id": FIXTURE_WORLD, "title": "Self-test", "coreVersion": DUMMY_VERSION })
+            .to_string(),
+    )?;
+    fs::write(world.join("data").join("notes.txt"), "fixture\n")?;
+
+    let service = BackupService {
+        excludes: Vec::new(),
+        retention: Retention {
+            keep_last: None,
+            keep_days: None,
+        },
+        s3: None,
+    };
+    let summary = tokio::task::spawn_blocking(move || service.create(BackupTrigger::Manual))
+        .await
+        .context("The backup task failed")??;
+
+    let mut archive = ZipArchive::new(File::open(&summary.path)?)?;
+    for entry in [
+        format!("Data/worlds/{}/world.json", FIXTURE_WORLD),
+        format!("Data/worlds/{}/data/notes.txt", FIXTURE_WORLD),
+    ] {
+        archive
+            .by_name(&entry)
+            .with_context(|| format!("{} is missing from the snapshot", entry))?;
+    }
+    if !summary.record.worlds.iter().any(|id| id == FIXTURE_WORLD) {
+        return Err(anyhow!("The snapshot is not cataloged with its world"));
+    }
+    Ok(format!(
+        "snapshot {} of a fixture world has {} files",
+        summary.record.file, summary.record.files
+    ))
+}
+
+fn write_archive(path: &Path, files: &[(&str, String)]) -> Result<()> {
+    let mut zip = ZipWriter::new(File::create(path)?);
+    for (name, contents) in files {
+        zip.start_file(*name, SimpleFileOptions::default())?;
+        zip.write_all(contents.as_bytes())?;
+    }
+    zip.finish()?;
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use crate::assets;
 use crate::config::AppConfig;
 use crate::events::{self, ProgressEvent};
 use crate::handlers;
@@ -15,6 +16,9 @@ use tracing_actix_web::TracingLogger;
 pub struct AppState {
     pub shutdown_sender: Arc<Mutex<Option<oneshot::Sender<()>>>>,
     pub event_channel: broadcast::Sender<ProgressEvent>,
+    pub static_files_dir: Option<String>,
+    /// Latest progress event, rendered by the progress page for browsers without JavaScript
+    pub last_event: Arc<Mutex<Option<ProgressEvent>>>,
 }
 
 pub async fn start_server(config: &AppConfig) -> std::io::Result<JoinHandle<std::io::Result<()>>> {
@@ -30,7 +34,10 @@ pub async fn start_server(config: &AppConfig) -> std::io::Result<JoinHandle<std:
     let app_state = web::Data::new(AppState {
         shutdown_sender: Arc::clone(&shared_tx),
         event_channel: event_tx,
+        static_files_dir: config.static_files_dir.clone(),
+        last_event: Arc::new(Mutex::new(None)),
     });
+    record_last_event(&app_state);
 
     info!(
         "Server is running on {}:{}",
@@ -56,30 +63,60 @@ pub async fn start_server(config: &AppConfig) -> std::io::Result<JoinHandle<std:
             .route("/download", web::post().to(handlers::download_and_extract))
             .route("/upload", web::post().to(handlers::upload_and_extract))
             .route("/events", web::get().to(events::sse_events))
+            .route("/progress", web::get().to(handlers::progress))
             .route("/dev-info", web::get().to(handlers::info))
-            .service(Files::new("/", &static_files_dir).index_file("index.html"))
+            // The setup page is rendered in the configured language
+            .route("/", web::get().to(handlers::index))
+            .route("/index.html", web::get().to(handlers::index))
+            .configure(|cfg| match &static_files_dir {
+                Some(dir) => {
+                    cfg.service(Files::new("/", dir).index_file("index.html"));
+                }
+                None => {
+                    cfg.route("/{name}", web::get().to(assets::serve));
+                }
+            })
     })
     .bind((server_host, server_port))?
     .run();
 
     let server_handle = server.handle();
 
+    // Setup signal handlers for SIGTERM and SIGINT while the installer is running
+    let signal_tasks = setup_signal_handlers();
+
     // Spawn a task to wait for the shutdown signal
     tokio::spawn(async move {
         // If we receive the shutdown signal, stop the server gracefully
         if rx.await.is_ok() {
             info!("Received shutdown signal, stopping Actix server");
             server_handle.stop(true).await;
+            // The launcher handles signals itself from here on
+            for task in signal_tasks {
+                task.abort();
+            }
             info!("Actix server stopped, transitioning to process management mode");
         }
     });
 
-    // Setup signal handlers for SIGTERM and SIGINT
-    setup_signal_handlers();
-
     Ok(tokio::spawn(server))
 }
 
+/// Keep the latest progress event around for clients polling the progress page
+fn record_last_event(app_state: &web::Data<AppState>) {
+    let mut rx = app_state.event_channel.subscribe();
+    let last_event = Arc::clone(&app_state.last_event);
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => *last_event.lock().unwrap() = Some(event),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
 // Function to redirect 404 responses to the root path
 fn redirect_to_root<B>(res: ServiceResponse<B>) -> Result<ErrorHandlerResponse<B>> {
     let response = HttpResponse::Found()
@@ -90,26 +127,23 @@ fn redirect_to_root<B>(res: ServiceResponse<B>) -> Result<ErrorHandlerResponse<B
     Ok(ErrorHandlerResponse::Response(res.into_response(response)))
 }
 
-fn setup_signal_handlers() {
-    #[cfg(unix)]
-    {
-        use tokio::signal::unix::{SignalKind, signal};
-        use tracing::info;
+fn setup_signal_handlers() -> Vec<JoinHandle<()>> {
+    use tokio::signal::unix::{SignalKind, signal};
 
+    vec![
         // Handle SIGTERM
         tokio::spawn(async move {
             let mut sigterm = signal(SignalKind::terminate()).unwrap();
             sigterm.recv().await;
             info!("Received SIGTERM, initiating shutdown");
             std::process::exit(0);
-        });
-
+        }),
         // Handle SIGINT
         tokio::spawn(async move {
             let mut sigint = signal(SignalKind::interrupt()).unwrap();
             sigint.recv().await;
             info!("Received SIGINT, initiating shutdown");
             std::process::exit(0);
-        });
-    }
+        }),
+    ]
 }
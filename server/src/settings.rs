@@ -0,0 +1,199 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+use crate::db::{Collection, new_document_id};
+use crate::worlds;
+
+/// Setting namespaces owned by Foundry itself rather than a module
+const CORE_NAMESPACES: &[&str] = &["core"];
+
+/// A single world or user scoped setting, as exported to JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingEntry {
+    /// Full setting key, `<module id>.<setting name>`
+    pub key: String,
+    /// The JSON encoded value exactly as Foundry stores it
+    pub value: Value,
+    /// Owning user for user scoped settings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Name of the owning user. User ids differ between worlds, a user scoped setting is
+    /// imported for the user of the same name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_name: Option<String>,
+}
+
+pub struct SettingsService;
+
+impl SettingsService {
+    /// Module settings of a world, optionally limited to the modules in `modules`
+    pub fn export(world_id: &str, modules: &[String]) -> Result<Vec<SettingEntry>> {
+        let mut settings = Collection::open(world_id, "settings")?;
+        let mut entries: Vec<SettingEntry> = settings
+            .documents()?
+            .into_iter()
+            .filter_map(|doc| {
+                let key = doc["key"].as_str()?.to_string();
+                let namespace = key.split('.').next()?;
+                if CORE_NAMESPACES.contains(&namespace)
+                    || (!modules.is_empty() && !modules.iter().any(|m| m == namespace))
+                {
+                    return None;
+                }
+                Some(SettingEntry {
+                    key,
+                    value: doc["value"].clone(),
+                    user: doc["user"].as_str().map(|u| u.to_string()),
+                    user_name: None,
+                })
+            })
+            .collect();
+        if entries.iter().any(|entry| entry.user.is_some()) {
+            let names = worlds::user_names(world_id)?;
+            for entry in &mut entries {
+                entry.user_name = entry.user.as_ref().and_then(|id| names.get(id)).cloned();
+            }
+        }
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(a.user.cmp(&b.user)));
+
+        info!(
+            "Exported {} module settings from world {}",
+            entries.len(),
+            world_id
+        );
+        Ok(entries)
+    }
+
+    /// Write settings into a world, replacing existing values of the same key and user. User
+    /// scoped settings go to the user of the same name, those of users the world does not
+    /// have are skipped.
+    pub fn import(world_id: &str, entries: &[SettingEntry]) -> Result<usize> {
+        let entries = Self::for_users_of(world_id, entries)?;
+        Self::write(world_id, &entries)
+    }
+
+    fn write(world_id: &str, entries: &[SettingEntry]) -> Result<usize> {
+        let mut settings = Collection::open(world_id, "settings")?;
+        let existing = settings.documents()?;
+
+        for entry in entries {
+            let namespace = entry.key.split('.').next().unwrap_or_default();
+            if CORE_NAMESPACES.contains(&namespace) {
+                return Err(anyhow!(
+                    "Refusing to import core setting {}, only module settings are supported",
+                    entry.key
+                ));
+            }
+
+            let current = existing.iter().find(|doc| {
+                doc["key"] == entry.key.as_str() && doc["user"].as_str() == entry.user.as_deref()
+            });
+            let document = match current {
+                Some(doc) => {
+                    let mut doc = doc.clone();
+                    doc["value"] = entry.value.clone();
+                    doc
+                }
+                None => serde_json::json!({
+                    "_id": new_document_id(&entry.key),
+                    "key": entry.key,
+                    "value": entry.value,
+                    "user": entry.user,
+                }),
+            };
+            settings.put(&document)?;
+        }
+
+        info!(
+            "Imported {} module settings into world {}",
+            entries.len(),
+            world_id
+        );
+        Ok(entries.len())
+    }
+
+    /// Write the settings of `entries` the world does not have a value for yet, returning
+    /// how many. Values changed in the world are never overwritten.
+    pub fn seed(world_id: &str, entries: &[SettingEntry]) -> Result<usize> {
+        let existing = Collection::open(world_id, "settings")?.documents()?;
+        let missing: Vec<SettingEntry> = Self::for_users_of(world_id, entries)?
+            .into_iter()
+            .filter(|entry| {
+                !existing.iter().any(|doc| {
+                    doc["key"] == entry.key.as_str()
+                        && doc["user"].as_str() == entry.user.as_deref()
+                })
+            })
+            .collect();
+        if missing.is_empty() {
+            return Ok(0);
+        }
+        Self::write(world_id, &missing)
+    }
+
+    /// `entries` with the user ids of `world_id`, looked up by user name
+    fn for_users_of(world_id: &str, entries: &[SettingEntry]) -> Result<Vec<SettingEntry>> {
+        if entries.iter().all(|entry| entry.user.is_none()) {
+            return Ok(entries.to_vec());
+        }
+        let ids: HashMap<String, String> = worlds::user_names(world_id)?
+            .into_iter()
+            .map(|(id, name)| (name, id))
+            .collect();
+        let mut mapped = Vec::with_capacity(entries.len());
+        for entry in entries {
+            if entry.user.is_none() {
+                mapped.push(entry.clone());
+                continue;
+            }
+            match entry.user_name.as_ref().and_then(|name| ids.get(name)) {
+                Some(id) => mapped.push(SettingEntry {
+                    user: Some(id.clone()),
+                    ..entry.clone()
+                }),
+                None => warn!(
+                    "Skipping setting {} of user {}, world {} has no such user",
+                    entry.key,
+                    entry.user_name.as_deref().unwrap_or("without a name"),
+                    world_id
+                ),
+            }
+        }
+        Ok(mapped)
+    }
+
+    /// The stored value of the world scoped setting `key`, JSON encoded like Foundry keeps it
+    pub fn get(world_id: &str, key: &str) -> Result<Option<Value>> {
+        let mut settings = Collection::open(world_id, "settings")?;
+        Ok(settings
+            .documents()?
+            .into_iter()
+            .find(|doc| doc["key"] == key && doc["user"].is_null())
+            .map(|doc| doc["value"].clone()))
+    }
+
+    /// Write the world scoped setting `key`, core settings included
+    pub fn set(world_id: &str, key: &str, value: Value) -> Result<()> {
+        let mut settings = Collection::open(world_id, "settings")?;
+        let current = settings
+            .documents()?
+            .into_iter()
+            .find(|doc| doc["key"] == key && doc["user"].is_null());
+        let document = match current {
+            Some(mut doc) => {
+                doc["value"] = value;
+                doc
+            }
+            None => serde_json::json!({
+                "_id": new_document_id(key),
+                "key": key,
+                "value": value,
+                "user": null,
+            }),
+        };
+        settings.put(&document)
+    }
+}
@@ -0,0 +1,88 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+use crate::permissions::{self, FileKind};
+use crate::scan::ScanService;
+use crate::utils::paths;
+
+/// Flush world data to disk and leave a marker telling the next boot the stop was clean.
+/// Without `stopped_cleanly`, e.g. when Foundry was killed or gave up restarting, there is no
+/// telling whether it saved the worlds.
+pub fn flush_and_mark_clean(excludes: &[String], stopped_cleanly: bool) {
+    let started = std::time::Instant::now();
+
+    match flush_critical_data(excludes) {
+        Ok(count) => {
+            debug!("Synced {} files in {:.2?}", count, started.elapsed());
+            if !stopped_cleanly {
+                warn!("Foundry did not stop cleanly, the next boot verifies the world databases");
+            } else if let Err(e) = write_clean_marker() {
+                warn!("Failed to write clean shutdown marker: {}", e);
+            } else {
+                info!("Data flushed, clean shutdown recorded");
+            }
+        }
+        // Without a successful flush the next boot should verify the databases
+        Err(e) => warn!("Failed to flush data directory on shutdown: {}", e),
+    }
+}
+
+/// Remove the clean shutdown marker, returning whether the previous run stopped cleanly
+pub fn consume_clean_marker() -> bool {
+    let marker = marker_path();
+    if !marker.exists() {
+        return false;
+    }
+    if let Err(e) = fs::remove_file(&marker) {
+        warn!("Failed to remove clean shutdown marker: {}", e);
+    }
+    true
+}
+
+fn flush_critical_data(excludes: &[String]) -> Result<usize> {
+    let data_dir = PathBuf::from(&*paths::DATA_DIR);
+    let critical_dirs = [data_dir.join("Config"), paths::WORLDS_DIR.clone()];
+
+    let mut synced = 0;
+    let mut directories = BTreeSet::from([data_dir.clone()]);
+    for dir in critical_dirs.iter().filter(|d| d.exists()) {
+        directories.insert(dir.clone());
+        for entry in ScanService::walk(dir, excludes)? {
+            sync_path(&entry.path)?;
+            if let Some(parent) = entry.path.parent() {
+                directories.insert(parent.to_path_buf());
+            }
+            synced += 1;
+        }
+    }
+
+    // Directory entries must hit the disk too, or freshly renamed files may vanish
+    for dir in &directories {
+        sync_path(dir)?;
+    }
+
+    Ok(synced)
+}
+
+fn sync_path(path: &Path) -> Result<()> {
+    File::open(path)
+        .and_then(|f| f.sync_all())
+        .with_context(|| format!("Failed to sync {}", path.display()))
+}
+
+fn write_clean_marker() -> Result<()> {
+    let marker = marker_path();
+    if let Some(parent) = marker.parent() {
+        permissions::create_dir_all(parent)?;
+    }
+    permissions::write(&marker, chrono::Utc::now().to_rfc3339(), FileKind::Regular)?;
+    sync_path(&marker)?;
+    Ok(())
+}
+
+fn marker_path() -> PathBuf {
+    paths::WRAPPER_STATE_DIR.join("clean-shutdown")
+}
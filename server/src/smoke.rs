@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use nix::sys::signal::{Signal, killpg};
+use nix::unistd::Pid;
+use reqwest::redirect::Policy;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::time::{Duration, Instant, sleep, timeout};
+use tracing::{debug, info, warn};
+
+use crate::jobs;
+use crate::launch::foundry_command;
+
+/// Result of booting a world headlessly
+#[derive(Debug)]
+pub struct SmokeTestOutcome {
+    pub passed: bool,
+    pub reason: String,
+    /// Error lines Foundry printed while loading the world
+    pub errors: Vec<String>,
+}
+
+/// Boots a single world on a spare port without any browser attached
+pub struct SmokeTest {
+    pub world: String,
+    pub data_dir: String,
+    pub port: u16,
+    pub timeout: Duration,
+    /// Boot at the priority of background jobs, for tests running next to a live game
+    pub background: bool,
+}
+
+impl SmokeTest {
+    /// Launch Foundry with `--world`, wait until the join page is served and stop it again.
+    ///
+    /// The world counts as loaded once `/join` answers with 200; Foundry redirects to the setup
+    /// screen instead when the world could not be activated.
+    pub async fn run(&self, script_path: &str) -> Result<SmokeTestOutcome> {
+        let args = [
+            format!("--dataPath={}", self.data_dir),
+            format!("--port={}", self.port),
+            format!("--world={}", self.world),
+            "--noupnp".to_string(),
+        ];
+        let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+        info!(
+            "🧪 Smoke testing world {} on port {} (timeout {:?})",
+            self.world, self.port, self.timeout
+        );
+        // Own process group, so node started by npx is stopped together with it
+        let mut command = foundry_command(script_path, &args);
+        if self.background {
+            jobs::lower_command_priority(&mut command);
+        }
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to spawn Foundry for the smoke test")?;
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let mut readers = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(tokio::spawn(collect_errors(stdout, Arc::clone(&errors))));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(tokio::spawn(collect_errors(stderr, Arc::clone(&errors))));
+        }
+
+        let client = reqwest::Client::builder()
+            .redirect(Policy::none())
+            .timeout(Duration::from_secs(5))
+            .build()?;
+        let join_url = format!("http://127.0.0.1:{}/join", self.port);
+        let deadline = Instant::now() + self.timeout;
+
+        let loaded: Result<(), String> = loop {
+            if let Some(status) = child.try_wait()? {
+                break Err(format!("Foundry exited during startup with {}", status));
+            }
+            if Instant::now() >= deadline {
+                break Err(format!("World not loaded within {:?}", self.timeout));
+            }
+            match client.get(&join_url).send().await {
+                Ok(resp) if resp.status().is_success() => break Ok(()),
+                Ok(resp) if resp.status().is_redirection() => {
+                    let location = resp
+                        .headers()
+                        .get(reqwest::header::LOCATION)
+                        .and_then(|l| l.to_str().ok())
+                        .unwrap_or_default()
+                        .to_string();
+                    // Foundry falls back to these screens when the world could not be launched
+                    if location.contains("setup") || location.contains("license") {
+                        break Err(format!("Foundry redirected to {}", location));
+                    }
+                }
+                Ok(resp) => debug!("Join page answered {} while booting", resp.status()),
+                Err(_) => debug!("Foundry not accepting connections yet"),
+            }
+            sleep(Duration::from_secs(1)).await;
+        };
+
+        if let Some(pid) = child.id() {
+            let _ = killpg(Pid::from_raw(pid as i32), Signal::SIGTERM);
+        }
+        if let Err(e) = child.wait().await {
+            warn!("Failed to wait for the smoke test instance: {}", e);
+        }
+        // Output ends once every process of the group is gone, collect the last lines
+        for reader in readers {
+            let _ = timeout(Duration::from_secs(10), reader).await;
+        }
+
+        let errors = errors.lock().unwrap().clone();
+        let (passed, reason) = match loaded {
+            Ok(()) if errors.is_empty() => (true, "World loaded".to_string()),
+            Ok(()) => (false, format!("World loaded with {} errors", errors.len())),
+            Err(reason) => (false, reason),
+        };
+
+        if passed {
+            info!("✅ Smoke test of world {} passed", self.world);
+        } else {
+            warn!("❌ Smoke test of world {} failed: {}", self.world, reason);
+        }
+        Ok(SmokeTestOutcome {
+            passed,
+            reason,
+            errors,
+        })
+    }
+}
+
+/// Forward Foundry output to the debug log and remember every error line
+async fn collect_errors<R: AsyncRead + Unpin>(output: R, errors: Arc<Mutex<Vec<String>>>) {
+    let mut lines = BufReader::new(output).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        debug!("[smoke] {}", line);
+        if line.contains("[error]") || line.contains("Error:") {
+            errors.lock().unwrap().push(line);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use nix::fcntl::{FcntlArg, FdFlag, fcntl};
+use std::collections::BTreeMap;
+use std::env;
+use std::io;
+use std::net::TcpListener;
+use std::os::fd::{FromRawFd, IntoRawFd, RawFd};
+use std::sync::OnceLock;
+use tracing::{debug, info, warn};
+
+use crate::config::AppConfig;
+
+/// The first socket passed by the service manager, after stdin, stdout and stderr
+const FIRST_FD: RawFd = 3;
+
+/// Listeners of the wrapper that take a socket from the service manager instead of binding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Listener {
+    /// The proxy in front of Foundry, `PROXY_PORT`
+    Proxy,
+    /// TLS termination, `TLS_PORT`
+    Tls,
+    /// ACME challenges and the redirect to HTTPS, `TLS_HTTP_PORT`
+    Http,
+}
+
+impl Listener {
+    /// The `FileDescriptorName=` of the socket in the socket unit
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "proxy" => Some(Self::Proxy),
+            "tls" | "https" => Some(Self::Tls),
+            "http" => Some(Self::Http),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Proxy => "proxy",
+            Self::Tls => "tls",
+            Self::Http => "http",
+        }
+    }
+}
+
+static SOCKETS: OnceLock<BTreeMap<Listener, TcpListener>> = OnceLock::new();
+
+/// Take the sockets systemd or podman bound for the wrapper (`LISTEN_FDS`), and enable the
+/// listeners they are meant for.
+///
+/// The sockets stay open for as long as the socket unit is active, so players connecting
+/// while the container restarts or the wrapper is upgraded wait in the backlog instead of
+/// being refused. Foundry cannot take a socket itself, it gets the connections through the
+/// proxy, which is enabled for an activated socket even without `PROXY_PORT`.
+pub fn init(config: &mut AppConfig) {
+    let Some(count) = passed_fds() else {
+        return;
+    };
+    let names: Vec<String> = env::var("LISTEN_FDNAMES")
+        .unwrap_or_default()
+        .split(':')
+        .map(str::to_string)
+        .collect();
+
+    let mut sockets = BTreeMap::new();
+    let mut unnamed = Vec::new();
+    for index in 0..count {
+        let fd = FIRST_FD + index;
+        let Some(socket) = take(fd) else {
+            continue;
+        };
+        let name = names.get(index as usize).map(String::as_str).unwrap_or("");
+        match Listener::from_name(name) {
+            Some(listener) => {
+                sockets.insert(listener, socket);
+            }
+            None => unnamed.push((name.to_string(), socket)),
+        }
+    }
+
+    // A single socket without a name is the one players connect to
+    let front = match config.tls {
+        Some(_) => Listener::Tls,
+        None => Listener::Proxy,
+    };
+    for (name, socket) in unnamed {
+        if sockets.contains_key(&front) {
+            warn!(
+                "⚠️ The activated socket {} has no known name, set FileDescriptorName= to proxy, tls or http",
+                if name.is_empty() {
+                    "without a name"
+                } else {
+                    &name
+                }
+            );
+            continue;
+        }
+        sockets.insert(front, socket);
+    }
+
+    for (listener, socket) in &sockets {
+        let port = match socket.local_addr() {
+            Ok(address) => {
+                info!(
+                    "🔌 The {} listens on {} passed by the service manager",
+                    listener.name(),
+                    address
+                );
+                address.port()
+            }
+            Err(_) => continue,
+        };
+        match listener {
+            Listener::Proxy if config.proxy_port.is_none() => {
+                info!("Forwarding the activated socket to Foundry through the proxy");
+                config.proxy_port = Some(port);
+            }
+            Listener::Tls | Listener::Http if config.tls.is_none() => warn!(
+                "⚠️ The activated {} socket stays unused without TLS_CERT and TLS_KEY or TLS_DOMAIN",
+                listener.name()
+            ),
+            Listener::Http if config.tls_http_port.is_none() => config.tls_http_port = Some(port),
+            _ => {}
+        }
+    }
+    let _ = SOCKETS.set(sockets);
+}
+
+/// The socket `listener` was passed, `None` when it binds its port itself. Each call gets
+/// a handle of its own, so a listener restarted by its supervisor accepts on the same socket.
+pub fn listener(listener: Listener) -> Option<io::Result<TcpListener>> {
+    SOCKETS
+        .get()
+        .and_then(|sockets| sockets.get(&listener))
+        .map(TcpListener::try_clone)
+}
+
+/// Number of sockets passed to this process. The variables come from the service manager,
+/// never from the settings file. `LISTEN_PID` tells children that the sockets were not meant
+/// for them, so the variables are left for Foundry and hooks to ignore.
+fn passed_fds() -> Option<RawFd> {
+    let pid = env::var("LISTEN_PID").ok()?;
+    if pid.trim().parse::<u32>().ok() != Some(std::process::id()) {
+        debug!(
+            "LISTEN_FDS was set for process {}, not for the wrapper",
+            pid
+        );
+        return None;
+    }
+    env::var("LISTEN_FDS")
+        .ok()?
+        .trim()
+        .parse::<RawFd>()
+        .ok()
+        .filter(|count| *count > 0)
+}
+
+fn take(fd: RawFd) -> Option<TcpListener> {
+    // SAFETY: the service manager passed the descriptor to this process, nothing else owns it
+    let socket = unsafe { TcpListener::from_raw_fd(fd) };
+    let checked = socket
+        .local_addr()
+        .and_then(|_| socket.set_nonblocking(true))
+        .and_then(|_| {
+            // Foundry, hooks and jobs get the connections through the wrapper, not the socket
+            fcntl(&socket, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))
+                .map(drop)
+                .map_err(io::Error::from)
+        });
+    match checked {
+        Ok(()) => Some(socket),
+        Err(e) => {
+            warn!(
+                "⚠️ Descriptor {} is not a TCP socket, ignoring it: {}",
+                fd, e
+            );
+            // Left open, whatever it is was not handed over for the wrapper to close
+            let _ = socket.into_raw_fd();
+            None
+        }
+    }
+}
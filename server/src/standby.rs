@@ -0,0 +1,232 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use tokio::time::{Duration, Instant, sleep};
+use tracing::{debug, error, info, warn};
+
+use crate::notify::{self, Notification, Severity};
+use crate::objectstore::S3Storage;
+use crate::permissions::FileKind;
+use crate::statesync::StateSync;
+use crate::storage::{Storage, storage};
+use crate::utils::{hostname, paths};
+
+/// Fencing token in the bucket, relative to `DATA_DIR` like the synced files
+const FENCE: &str = ".wrapper/fence.json";
+/// Epoch this instance holds, never synced so every disk keeps its own
+const HELD: &str = ".wrapper/fence-held.json";
+/// Created by `foundry-watcher promote` for the waiting standby
+const PROMOTE_TRIGGER: &str = "promote";
+
+/// Experimental: wait as a warm standby of another instance sharing the bucket of stateless
+/// mode, until it is promoted
+#[derive(Debug, Clone)]
+pub struct StandbyConfig {
+    /// Health endpoint of the primary, e.g. `http://primary:8081/healthz`
+    pub primary_url: String,
+    /// Promote without an operator once the primary missed `failures` checks in a row
+    pub auto_promote: bool,
+    pub failures: u32,
+    /// Seconds between health checks
+    pub poll_interval: u64,
+}
+
+/// The epoch grows with every promotion. Only the instance holding the newest one uploads,
+/// so a primary that comes back after a standby took over cannot overwrite its data.
+#[derive(Debug, Serialize, Deserialize)]
+struct Fence {
+    epoch: u64,
+    holder: String,
+    since: String,
+}
+
+/// Files of the fencing protocol, the state sync skips them
+pub fn is_fence_file(relative: &str) -> bool {
+    relative == FENCE || relative == HELD
+}
+
+fn fence_path() -> PathBuf {
+    PathBuf::from(&*paths::DATA_DIR).join(FENCE)
+}
+
+fn held_path() -> PathBuf {
+    PathBuf::from(&*paths::DATA_DIR).join(HELD)
+}
+
+fn read_fence(remote: &S3Storage) -> Result<Option<Fence>> {
+    match remote.read(&fence_path()) {
+        Ok(contents) => Ok(Some(
+            serde_json::from_slice(&contents).context("The fence in the bucket is invalid")?,
+        )),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).context("Failed to read the fence from the bucket"),
+    }
+}
+
+fn held_epoch() -> Option<u64> {
+    let contents = fs::read(held_path()).ok()?;
+    serde_json::from_slice::<Fence>(&contents)
+        .ok()
+        .map(|fence| fence.epoch)
+}
+
+/// Take the next epoch, fencing off whoever held the previous one. Blocks.
+pub fn claim(remote: &S3Storage) -> Result<u64> {
+    let epoch = read_fence(remote)?.map_or(1, |fence| fence.epoch + 1);
+    let fence = Fence {
+        epoch,
+        holder: hostname(),
+        since: Utc::now().to_rfc3339(),
+    };
+    let json = serde_json::to_vec_pretty(&fence)?;
+    remote
+        .write(&fence_path(), &json, FileKind::Regular)
+        .context("Failed to write the fence to the bucket")?;
+    let local = storage();
+    local.create_dir_all(&paths::WRAPPER_STATE_DIR)?;
+    local
+        .write(&held_path(), &json, FileKind::Regular)
+        .with_context(|| format!("Failed to write {}", held_path().display()))?;
+    info!("🛡️ Holding epoch {} of bucket {}", epoch, remote.bucket());
+    Ok(epoch)
+}
+
+/// On the start of an instance that is no standby. A fresh disk was started on purpose and
+/// takes over, a disk that held an older epoch was replaced by a promoted standby. Blocks.
+pub fn claim_on_start(remote: &S3Storage) -> Result<()> {
+    match (held_epoch(), read_fence(remote)?) {
+        (None, _) => claim(remote).map(|_| ()),
+        (Some(held), Some(fence)) if fence.epoch > held => {
+            error!(
+                "❌ {} took over with epoch {} since {}, this instance no longer syncs. Run `foundry-watcher promote` to take over again.",
+                fence.holder, fence.epoch, fence.since
+            );
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Fail unless this instance holds the newest epoch. Buckets used before fencing existed have
+/// no fence and anybody may write. Blocks.
+pub fn check(remote: &S3Storage) -> Result<()> {
+    let Some(fence) = read_fence(remote)? else {
+        return Ok(());
+    };
+    if held_epoch() == Some(fence.epoch) {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "Fenced off, {} holds epoch {} of the bucket since {}",
+        fence.holder,
+        fence.epoch,
+        fence.since
+    ))
+}
+
+/// Promote from another process: take over the fence and wake a waiting standby. Blocks.
+pub fn promote(remote: &S3Storage) -> Result<u64> {
+    let epoch = claim(remote)?;
+    let local = storage();
+    local.create_dir_all(&paths::WRAPPER_STATE_DIR)?;
+    local.write(
+        &paths::WRAPPER_STATE_DIR.join(PROMOTE_TRIGGER),
+        Utc::now().to_rfc3339().as_bytes(),
+        FileKind::Regular,
+    )?;
+    Ok(epoch)
+}
+
+pub struct Standby {
+    pub config: StandbyConfig,
+    pub sync: StateSync,
+}
+
+impl Standby {
+    /// Poll the primary and pull its uploads every `STATE_SYNC_INTERVAL`, returning once this
+    /// instance was promoted and holds the data the primary uploaded last
+    pub async fn wait_for_promotion(&self) {
+        info!(
+            "🛡️ Standing by for {}, promotion is {}",
+            self.config.primary_url,
+            if self.config.auto_promote {
+                "automatic"
+            } else {
+                "manual"
+            }
+        );
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap_or_default();
+        let trigger = paths::WRAPPER_STATE_DIR.join(PROMOTE_TRIGGER);
+        let _ = fs::remove_file(&trigger);
+        let mut failures = 0;
+        let mut last_pull = Instant::now();
+
+        let reason = loop {
+            if trigger.exists() {
+                let _ = fs::remove_file(&trigger);
+                break "it was promoted by hand".to_string();
+            }
+            match client.get(&self.config.primary_url).send().await {
+                Ok(resp) if resp.status().is_success() => {
+                    if failures > 0 {
+                        info!("🛡️ The primary answers again");
+                    }
+                    failures = 0;
+                }
+                answer => {
+                    failures += 1;
+                    let detail = match answer {
+                        Ok(resp) => resp.status().to_string(),
+                        Err(e) => e.to_string(),
+                    };
+                    warn!(
+                        "⚠️ The primary failed its health check ({} in a row): {}",
+                        failures, detail
+                    );
+                }
+            }
+            if self.config.auto_promote && failures >= self.config.failures {
+                break format!("the primary failed {} health checks in a row", failures);
+            }
+            if last_pull.elapsed() >= self.sync.interval {
+                self.pull().await;
+                last_pull = Instant::now();
+            }
+            sleep(Duration::from_secs(self.config.poll_interval)).await;
+        };
+
+        // `promote` took the fence already, the automatic promotion takes it here
+        let remote = self.sync.remote.clone();
+        let claimed = tokio::task::spawn_blocking(move || match check(&remote) {
+            Ok(()) => Ok(()),
+            Err(_) => claim(&remote).map(|_| ()),
+        })
+        .await;
+        match claimed {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("❌ Failed to take over the fence: {:#}", e),
+            Err(e) => error!("❌ Taking over the fence panicked: {}", e),
+        }
+        // The last upload of the primary, which is the clean one when it stopped in time
+        self.pull().await;
+        notify::send(Notification {
+            severity: Severity::Critical,
+            title: "Standby promoted".to_string(),
+            message: format!("This instance starts Foundry because {}.", reason),
+        });
+    }
+
+    async fn pull(&self) {
+        let sync = self.sync.clone();
+        match tokio::task::spawn_blocking(move || sync.hydrate()).await {
+            Ok(Ok(())) => debug!("Pulled the uploads of the primary"),
+            Ok(Err(e)) => warn!("⚠️ Failed to pull the uploads of the primary: {:#}", e),
+            Err(e) => warn!("⚠️ Pulling the uploads of the primary panicked: {}", e),
+        }
+    }
+}
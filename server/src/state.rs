@@ -0,0 +1,84 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use utoipa::ToSchema;
+
+use crate::archive::ArchivedWorld;
+use crate::backup::{BackupRecord, FailedBackup};
+use crate::permissions::{self, FileKind};
+use crate::utils::paths;
+
+/// Persistent wrapper state stored in `DATA_DIR/.wrapper/state.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WrapperState {
+    /// Worlds currently locked for maintenance, keyed by world id
+    pub world_locks: BTreeMap<String, WorldLock>,
+    /// Core, system and module versions the smoke test last passed with
+    pub verified_packages: BTreeMap<String, String>,
+    /// Settings presets already seeded into each world, keyed by world id
+    pub seeded_presets: BTreeMap<String, Vec<String>>,
+    /// Catalog of the snapshots in `BACKUP_DIR/snapshots`, oldest first
+    pub backups: Vec<BackupRecord>,
+    /// Core and package versions of the last launch or of the wrapper's last install, changes
+    /// found besides them were made in Foundry
+    pub known_packages: BTreeMap<String, String>,
+    /// Foundry releases that did not boot when updating to them, they are not tried again
+    pub rejected_releases: Vec<String>,
+    /// System and module versions a failed upgrade smoke test was rolled back from, keyed like
+    /// `verified_packages`, the installer does not install them again
+    pub rejected_packages: BTreeMap<String, String>,
+    /// Worlds moved out of the data directory with `world archive`, keyed by world id
+    pub archived_worlds: BTreeMap<String, ArchivedWorld>,
+    /// The last snapshot that could not be written, cleared by the next one that is
+    pub failed_backup: Option<FailedBackup>,
+    /// Copy of a world the upgrade smoke test runs against, the only world the wrapper removes
+    /// on its own. Left here when the copy could not be removed after the test.
+    pub canary_world: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WorldLock {
+    pub message: String,
+    /// RFC 3339
+    pub locked_at: String,
+    /// The world description before the lock notice replaced it
+    pub original_description: Option<String>,
+}
+
+impl WrapperState {
+    /// Load the state file, starting from an empty state when none exists yet
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid state file {}", path.display()))
+    }
+
+    /// Write the state atomically so a crash never leaves a truncated file behind
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        permissions::create_dir_all(&paths::WRAPPER_STATE_DIR)
+            .with_context(|| format!("Failed to create {}", paths::WRAPPER_STATE_DIR.display()))?;
+
+        let tmp_path = path.with_extension("json.tmp");
+        permissions::write(
+            &tmp_path,
+            serde_json::to_string_pretty(self)?,
+            FileKind::Regular,
+        )
+        .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, &path)
+            .with_context(|| format!("Failed to replace {}", path.display()))
+    }
+
+    pub fn path() -> PathBuf {
+        paths::WRAPPER_STATE_DIR.join("state.json")
+    }
+}
@@ -0,0 +1,296 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tokio::time::{Duration, sleep};
+use tracing::{debug, error, info, warn};
+
+use crate::archive;
+use crate::capabilities;
+use crate::config::AppConfig;
+use crate::hostmigrate;
+use crate::jobs;
+use crate::objectstore::{self, S3Storage};
+use crate::permissions::FileKind;
+use crate::restore;
+use crate::restoretest;
+use crate::scan::ScanService;
+use crate::standby;
+use crate::storage::{Storage, StoredEntry, storage};
+use crate::transfer;
+use crate::updater;
+use crate::utils::paths;
+
+/// Manifest of the synced files, relative to `DATA_DIR`. The copy in the bucket says what a
+/// new instance has to download, the local copy what changed since the last upload.
+const MANIFEST: &str = ".wrapper/sync-manifest.json";
+/// Directories below `DATA_DIR` a restore, import, migration or update fills and removes again,
+/// they would be uploaded halfway and deleted from the bucket on the next run
+const SCRATCH_DIRS: [&str; 7] = [
+    restore::STAGING_DIR,
+    restore::PREVIOUS_DIR,
+    restoretest::TEST_DIR,
+    transfer::STAGING_DIR,
+    archive::STAGING_DIR,
+    hostmigrate::STAGING_DIR,
+    updater::PROBE_DIR,
+];
+
+/// Experimental: `DATA_DIR` on an ephemeral disk, kept in object storage.
+///
+/// The data directory is downloaded before anything reads it, uploaded on a schedule while
+/// Foundry runs and once more when it stopped. Only changed files are uploaded, they are
+/// found by size and modification time and compared by SHA-256.
+#[derive(Clone)]
+pub struct StateSync {
+    pub remote: S3Storage,
+    pub interval: Duration,
+    pub excludes: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SyncManifest {
+    files: BTreeMap<String, SyncedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SyncedFile {
+    size: u64,
+    sha256: String,
+    /// Local modification time in milliseconds, unchanged files are not hashed again
+    #[serde(default)]
+    modified: Option<u64>,
+}
+
+impl StateSync {
+    pub fn from_config(config: &AppConfig) -> Option<Self> {
+        let store = config.state_sync.clone()?;
+        match S3Storage::new(store, PathBuf::from(&*paths::DATA_DIR)) {
+            Ok(remote) => Some(Self {
+                remote,
+                interval: Duration::from_secs(config.state_sync_interval * 60),
+                excludes: config.scan_excludes.clone(),
+            }),
+            Err(e) => {
+                error!("❌ Failed to set up the object storage client: {}", e);
+                capabilities::failed("state-sync", &e);
+                None
+            }
+        }
+    }
+
+    /// Download everything the bucket holds that is missing or different locally.
+    ///
+    /// Local files the bucket does not know are kept. Blocks, run it on a blocking thread.
+    pub fn hydrate(&self) -> Result<()> {
+        let data_dir = PathBuf::from(&*paths::DATA_DIR);
+        let manifest_path = data_dir.join(MANIFEST);
+        let remote_manifest = match self.remote.read(&manifest_path) {
+            Ok(contents) => serde_json::from_slice::<SyncManifest>(&contents)
+                .context("The manifest in the bucket is invalid")?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!(
+                    "☁️ Nothing stored in the bucket yet, starting with the local data directory"
+                );
+                return Ok(());
+            }
+            Err(e) => return Err(e).context("Failed to read the manifest from the bucket"),
+        };
+
+        let local = storage();
+        let mut manifest = SyncManifest::default();
+        let mut downloaded = 0;
+        for (relative, file) in remote_manifest.files {
+            let path = data_dir.join(&relative);
+            let current = local
+                .metadata(&path)
+                .ok()
+                .filter(|entry| entry.size == file.size);
+            let unchanged = current.is_some()
+                && local
+                    .read(&path)
+                    .is_ok_and(|contents| sha256(&contents) == file.sha256);
+            if !unchanged {
+                let contents = self
+                    .remote
+                    .read(&path)
+                    .with_context(|| format!("Failed to download {}", relative))?;
+                if let Some(parent) = path.parent() {
+                    local.create_dir_all(parent)?;
+                }
+                local
+                    .write(&path, &contents, FileKind::Regular)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                downloaded += 1;
+            }
+            let modified = local
+                .metadata(&path)
+                .ok()
+                .and_then(|entry| modified_ms(&entry));
+            manifest
+                .files
+                .insert(relative, SyncedFile { modified, ..file });
+        }
+
+        save_manifest(local, &manifest_path, &manifest)?;
+        info!(
+            "☁️ Restored the data directory from the bucket, {} of {} files downloaded",
+            downloaded,
+            manifest.files.len()
+        );
+        Ok(())
+    }
+
+    /// Upload files changed since the last upload and delete the ones removed locally, unless
+    /// a promoted standby fenced this instance off. Blocks, run it on a blocking thread.
+    pub fn push(&self) -> Result<()> {
+        standby::check(&self.remote)?;
+        let data_dir = PathBuf::from(&*paths::DATA_DIR);
+        let manifest_path = data_dir.join(MANIFEST);
+        let local = storage();
+        let previous: SyncManifest = local
+            .read(&manifest_path)
+            .ok()
+            .and_then(|contents| serde_json::from_slice(&contents).ok())
+            .unwrap_or_default();
+
+        let mut manifest = SyncManifest::default();
+        let (mut uploaded, mut removed) = (0, 0);
+        for entry in ScanService::walk(&data_dir, &self.excludes)? {
+            let Ok(relative) = entry.path.strip_prefix(&data_dir) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().to_string();
+            if relative == MANIFEST || standby::is_fence_file(&relative) || scratch(&relative) {
+                continue;
+            }
+            let Ok(metadata) = local.metadata(&entry.path) else {
+                // Removed while walking
+                continue;
+            };
+            let modified = modified_ms(&metadata);
+            let known = previous.files.get(&relative).filter(|file| {
+                file.size == metadata.size && modified.is_some() && file.modified == modified
+            });
+
+            let file = match known {
+                Some(file) => file.clone(),
+                None => {
+                    let contents = local
+                        .read(&entry.path)
+                        .with_context(|| format!("Failed to read {}", entry.path.display()))?;
+                    let file = SyncedFile {
+                        size: contents.len() as u64,
+                        sha256: sha256(&contents),
+                        modified,
+                    };
+                    let changed = previous
+                        .files
+                        .get(&relative)
+                        .is_none_or(|old| old.sha256 != file.sha256);
+                    if changed {
+                        self.remote
+                            .write(&entry.path, &contents, FileKind::Regular)
+                            .with_context(|| format!("Failed to upload {}", relative))?;
+                        uploaded += 1;
+                    }
+                    file
+                }
+            };
+            manifest.files.insert(relative, file);
+        }
+
+        for relative in previous.files.keys() {
+            if !manifest.files.contains_key(relative) {
+                match self.remote.remove_file(&data_dir.join(relative)) {
+                    Ok(()) => removed += 1,
+                    Err(e) => warn!("Failed to delete {} from the bucket: {}", relative, e),
+                }
+            }
+        }
+
+        // The bucket's manifest goes last, a new instance never expects files not uploaded yet
+        save_manifest(&self.remote, &manifest_path, &manifest)?;
+        save_manifest(local, &manifest_path, &manifest)?;
+        if uploaded > 0 || removed > 0 {
+            info!(
+                "☁️ Synced the data directory to the bucket, {} files uploaded and {} deleted",
+                uploaded, removed
+            );
+        } else {
+            debug!("Data directory unchanged since the last sync");
+        }
+        Ok(())
+    }
+
+    /// `push` on a blocking thread, logging failures
+    pub async fn push_in_background(&self) {
+        let sync = self.clone();
+        match tokio::task::spawn_blocking(move || sync.push()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!(
+                "❌ Syncing the data directory to the bucket failed: {:#}",
+                e
+            ),
+            Err(e) => error!(
+                "❌ Syncing the data directory to the bucket panicked: {}",
+                e
+            ),
+        }
+    }
+
+    /// Upload changes every `interval`, running until the process ends
+    pub async fn run_schedule(self) {
+        info!(
+            "Syncing the data directory to bucket {} every {:?}",
+            self.remote.bucket(),
+            self.interval
+        );
+        loop {
+            sleep(self.interval).await;
+            let sync = self.clone();
+            match jobs::run("state sync", move || sync.push()).await {
+                Ok(Ok(())) => capabilities::active("state-sync"),
+                Ok(Err(e)) => {
+                    error!(
+                        "❌ Syncing the data directory to the bucket failed: {:#}",
+                        e
+                    );
+                    capabilities::failed("state-sync", e);
+                }
+                Err(e) => error!("❌ {}", e),
+            }
+        }
+    }
+}
+
+fn scratch(relative: &str) -> bool {
+    Path::new(relative)
+        .components()
+        .next()
+        .is_some_and(|first| SCRATCH_DIRS.iter().any(|dir| first.as_os_str() == *dir))
+}
+
+fn save_manifest(target: &dyn Storage, path: &Path, manifest: &SyncManifest) -> Result<()> {
+    let json = serde_json::to_vec_pretty(manifest)?;
+    if let Some(parent) = path.parent() {
+        target.create_dir_all(parent)?;
+    }
+    target
+        .write(path, &json, FileKind::Regular)
+        .with_context(|| format!("Failed to save {}", path.display()))
+}
+
+fn modified_ms(entry: &StoredEntry) -> Option<u64> {
+    entry
+        .modified?
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_millis() as u64)
+}
+
+fn sha256(contents: &[u8]) -> String {
+    objectstore::hex(&Sha256::digest(contents))
+}
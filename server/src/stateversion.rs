@@ -0,0 +1,126 @@
+use anyhow::{Context, Result, bail};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+use crate::permissions::{self, FileKind};
+use crate::utils::paths;
+
+/// Layout of the files the wrapper manages in the data directory, raised whenever a release
+/// changes one of them
+const SCHEMA: u32 = 1;
+/// Oldest schema a release has to know to use files written by this one. It stays while
+/// changes only add fields older releases skip, and is raised with `SCHEMA` when they would
+/// misread or overwrite what they do not know.
+const READABLE_FROM: u32 = 1;
+
+/// The newest wrapper that managed the data directory, `DATA_DIR/.wrapper/version.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateVersion {
+    pub schema: u32,
+    pub readable_from: u32,
+    /// Release of the wrapper that wrote the schema
+    pub wrapper: String,
+    pub written_at: String,
+}
+
+/// Refuse to start against a data directory managed by a newer wrapper that changed its files
+/// in ways this one would corrupt, and record this wrapper's schema otherwise.
+///
+/// A data directory from a newer but compatible release keeps its record, so starting an
+/// older image once does not hide what the newer one needs. `allow_downgrade` starts anyway.
+pub fn check(allow_downgrade: bool) -> Result<()> {
+    // A damaged record says nothing about the files, it is replaced like a missing one
+    let recorded = load().unwrap_or_else(|e| {
+        warn!("⚠️ {:#}, recording the state schema of this release", e);
+        None
+    });
+    match &recorded {
+        None => {}
+        Some(recorded) if recorded.schema <= SCHEMA => {}
+        Some(recorded) if recorded.readable_from <= SCHEMA => {
+            info!(
+                "The data directory was last managed by wrapper {}, a newer release whose files this one still reads",
+                recorded.wrapper
+            );
+            return Ok(());
+        }
+        Some(recorded) if allow_downgrade => {
+            warn!(
+                "⚠️ Starting against a data directory of wrapper {} though it needs state schema {} and this release has {}, ALLOW_STATE_DOWNGRADE is set",
+                recorded.wrapper, recorded.readable_from, SCHEMA
+            );
+            return Ok(());
+        }
+        Some(recorded) => bail!(
+            "The data directory is managed by wrapper {} (state schema {}), which changed files \
+             in DATA_DIR/.wrapper that wrapper {} (state schema {}) would misread or overwrite. \
+             Start the image of wrapper {} or newer again, or restore a backup taken before \
+             upgrading to it. Set ALLOW_STATE_DOWNGRADE=true to start anyway at the risk of \
+             losing backups, locks and other wrapper state.",
+            recorded.wrapper,
+            recorded.schema,
+            env!("CARGO_PKG_VERSION"),
+            SCHEMA,
+            recorded.wrapper
+        ),
+    }
+
+    let current = StateVersion {
+        schema: SCHEMA,
+        readable_from: READABLE_FROM,
+        wrapper: env!("CARGO_PKG_VERSION").to_string(),
+        written_at: Utc::now().to_rfc3339(),
+    };
+    let unchanged = recorded.as_ref().is_some_and(|recorded| {
+        recorded.schema == current.schema
+            && recorded.readable_from == current.readable_from
+            && recorded.wrapper == current.wrapper
+    });
+    if unchanged {
+        return Ok(());
+    }
+    if let Some(recorded) = recorded
+        .as_ref()
+        .filter(|recorded| recorded.schema < SCHEMA)
+    {
+        info!(
+            "Data directory upgraded from wrapper {} (state schema {}) to {} (state schema {})",
+            recorded.wrapper, recorded.schema, current.wrapper, SCHEMA
+        );
+    }
+    save(&current)
+}
+
+fn load() -> Result<Option<StateVersion>> {
+    let path = path();
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&contents)
+        .map(Some)
+        .with_context(|| format!("Invalid state version {}", path.display()))
+}
+
+/// Written atomically like the state file, a truncated record would stop every later start
+fn save(version: &StateVersion) -> Result<()> {
+    let path = path();
+    permissions::create_dir_all(&paths::WRAPPER_STATE_DIR)
+        .with_context(|| format!("Failed to create {}", paths::WRAPPER_STATE_DIR.display()))?;
+    let tmp_path = path.with_extension("json.tmp");
+    permissions::write(
+        &tmp_path,
+        serde_json::to_string_pretty(version)?,
+        FileKind::Regular,
+    )
+    .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, &path).with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+fn path() -> PathBuf {
+    paths::WRAPPER_STATE_DIR.join("version.json")
+}
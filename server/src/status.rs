@@ -0,0 +1,123 @@
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+
+use crate::eventlog;
+use crate::utils::installed_foundry_version;
+
+/// Snapshot of the launcher and the Foundry process it supervises
+#[derive(Debug, Clone, Default)]
+pub struct FoundryStatus {
+    /// The installer is waiting for a Foundry release, nothing to supervise yet
+    pub installing: bool,
+    pub running: bool,
+    pub pid: Option<u32>,
+    /// Monotonic, so uptimes survive the wall clock being corrected
+    pub started_at: Option<Instant>,
+    /// How often Foundry was started again after the first launch
+    pub restarts: u32,
+    pub version: Option<String>,
+}
+
+/// Answer of Foundry's own `/api/status` endpoint
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerStatus {
+    /// Whether a world is active, false while on the setup screen
+    #[serde(default)]
+    pub active: bool,
+    pub world: Option<String>,
+    /// Number of connected players
+    #[serde(default)]
+    pub users: u32,
+}
+
+static STATUS: Mutex<Option<FoundryStatus>> = Mutex::new(None);
+
+/// Lock the status, a panic elsewhere while holding it must not break every later reader
+fn lock_status() -> MutexGuard<'static, Option<FoundryStatus>> {
+    STATUS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Current status, shared by the admin API and the chat and monitoring integrations
+pub fn current() -> FoundryStatus {
+    lock_status().clone().unwrap_or_default()
+}
+
+/// Record that the installer is serving until a release is installed
+pub fn mark_installing() {
+    *lock_status() = Some(FoundryStatus {
+        installing: true,
+        ..FoundryStatus::default()
+    });
+}
+
+/// Record a freshly spawned Foundry process
+pub fn mark_started(pid: Option<u32>) {
+    let started = {
+        let mut status = lock_status();
+        let previous = status.take();
+        status
+            .insert(FoundryStatus {
+                installing: false,
+                running: true,
+                pid,
+                started_at: Some(Instant::now()),
+                restarts: previous
+                    .filter(|s| s.started_at.is_some())
+                    .map(|s| s.restarts + 1)
+                    .unwrap_or_default(),
+                version: installed_foundry_version(),
+            })
+            .clone()
+    };
+    eventlog::record(
+        "foundry-started",
+        json!({ "pid": pid, "version": started.version, "restarts": started.restarts }),
+    );
+}
+
+/// Record that the Foundry process exited
+pub fn mark_stopped() {
+    let pid = lock_status().as_mut().and_then(|status| {
+        status.running = false;
+        status.pid.take()
+    });
+    eventlog::record("foundry-stopped", json!({ "pid": pid }));
+}
+
+/// Ask the running Foundry server about the active world and connected players
+pub async fn fetch_server_status(port: u16) -> Result<ServerStatus> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(5))
+        .build()?;
+    let status = client
+        .get(format!("http://127.0.0.1:{}/api/status", port))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(status)
+}
+
+impl FoundryStatus {
+    /// One line summary for chat replies and logs
+    #[cfg(feature = "full")]
+    pub fn summary(&self) -> String {
+        match (self.running, self.started_at) {
+            (true, Some(started_at)) => {
+                let minutes = started_at.elapsed().as_secs() / 60;
+                format!(
+                    "🟢 Foundry {} running for {}h {}m ({} restarts)",
+                    self.version.as_deref().unwrap_or("(unknown version)"),
+                    minutes / 60,
+                    minutes % 60,
+                    self.restarts
+                )
+            }
+            _ => "🔴 Foundry is not running".to_string(),
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+use crate::permissions::{self, FileKind};
+
+/// A file or directory as a storage backend reports it
+#[derive(Debug, Clone)]
+pub struct StoredEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub is_dir: bool,
+    pub modified: Option<SystemTime>,
+}
+
+/// File operations of the subsystems that install releases and back up or restore worlds.
+///
+/// They go through this instead of `std::fs`, so a backend other than the local disk, e.g. an
+/// object storage layout for stateless deployments, only has to implement this trait. Paths
+/// are the local paths under `DATA_DIR`, `BACKUP_DIR` or the application directory, a backend
+/// maps them to its own layout.
+pub trait Storage: Send + Sync {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Create or replace `path`, with the mode the permission policy sets for `kind`
+    fn write(&self, path: &Path, contents: &[u8], kind: FileKind) -> io::Result<()>;
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    /// Direct children of `dir`
+    fn list(&self, dir: &Path) -> io::Result<Vec<StoredEntry>>;
+
+    /// Metadata of `path` itself, symlinks are not followed
+    fn metadata(&self, path: &Path) -> io::Result<StoredEntry>;
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64>;
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Remove whatever is at `path`, nothing being there is fine
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        match self.metadata(path) {
+            Ok(entry) if entry.is_dir => self.remove_dir_all(path),
+            Ok(_) => self.remove_file(path),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The local disk, where Foundry itself keeps everything
+pub struct LocalStorage;
+
+impl Storage for LocalStorage {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8], kind: FileKind) -> io::Result<()> {
+        permissions::write(path, contents, kind)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        permissions::create_dir_all(path)
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<StoredEntry>> {
+        fs::read_dir(dir)?
+            .map(|entry| self.metadata(&entry?.path()))
+            .collect()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<StoredEntry> {
+        let meta = fs::symlink_metadata(path)?;
+        Ok(StoredEntry {
+            path: path.to_path_buf(),
+            size: meta.len(),
+            is_dir: meta.is_dir(),
+            modified: meta.modified().ok(),
+        })
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        fs::copy(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir_all(path)
+    }
+}
+
+static STORAGE: OnceLock<Box<dyn Storage>> = OnceLock::new();
+
+/// The storage backend of this process
+pub fn storage() -> &'static dyn Storage {
+    STORAGE.get_or_init(|| Box::new(LocalStorage)).as_ref()
+}
+
+/// Run `operation` on a blocking thread, removing or moving a whole release takes a while
+pub async fn blocking<T, F>(operation: F) -> io::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&'static dyn Storage) -> io::Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || operation(storage()))
+        .await
+        .map_err(io::Error::other)?
+}
@@ -0,0 +1,82 @@
+use tokio::time::{Duration, Instant};
+
+/// Delay before the first restart, doubled after every further crash
+const INITIAL_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+/// Foundry running this long counts as recovered, backoff and restart count start over
+const STABLE_RUN: Duration = Duration::from_secs(600);
+
+/// When Foundry is started again after its process exited, set with `RESTART_POLICY`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicy {
+    Always,
+    /// Only after a crash, a clean exit stops the container
+    OnFailure,
+    /// The container stops with Foundry, e.g. to leave restarts to the orchestrator
+    Never,
+}
+
+impl RestartPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "always" => Some(Self::Always),
+            "on-failure" => Some(Self::OnFailure),
+            "never" | "no" => Some(Self::Never),
+            _ => None,
+        }
+    }
+}
+
+/// Decides whether and when the launcher restarts Foundry after its process exited
+pub struct RestartSupervisor {
+    policy: RestartPolicy,
+    /// Restarts in a row before giving up, unlimited when `None`
+    max_restarts: Option<u32>,
+    restarts: u32,
+    backoff: Duration,
+    started: Instant,
+}
+
+impl RestartSupervisor {
+    pub fn new(policy: RestartPolicy, max_restarts: Option<u32>) -> Self {
+        Self {
+            policy,
+            max_restarts,
+            restarts: 0,
+            backoff: INITIAL_BACKOFF,
+            started: Instant::now(),
+        }
+    }
+
+    /// Record that Foundry was just started
+    pub fn started(&mut self) {
+        self.started = Instant::now();
+    }
+
+    /// Delay before the next start after Foundry exited, `None` to stop supervising.
+    ///
+    /// `failed` is false only for an exit with status 0, a hung startup is a failure too.
+    pub fn next_restart(&mut self, failed: bool) -> Option<Duration> {
+        match self.policy {
+            RestartPolicy::Never => return None,
+            RestartPolicy::OnFailure if !failed => return None,
+            _ => {}
+        }
+        if self.started.elapsed() >= STABLE_RUN {
+            self.restarts = 0;
+            self.backoff = INITIAL_BACKOFF;
+        }
+        if self.max_restarts.is_some_and(|max| self.restarts >= max) {
+            return None;
+        }
+        self.restarts += 1;
+        let delay = self.backoff;
+        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+        Some(delay)
+    }
+
+    /// Restarts in a row since Foundry last ran stably
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+}
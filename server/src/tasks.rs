@@ -0,0 +1,388 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Mutex, PoisonError};
+use tokio::time::{Duration, Instant, sleep};
+use tracing::{error, info, warn};
+
+#[cfg(feature = "full")]
+use crate::alerts::{self, AlertEngine};
+use crate::autoreturn::AutoReturn;
+use crate::backup::{self, BackupScheduler, BackupService};
+use crate::capabilities;
+use crate::clock::ClockMonitor;
+use crate::config::AppConfig;
+#[cfg(feature = "full")]
+use crate::discord::DiscordBot;
+use crate::diskspace::DiskSpaceMonitor;
+use crate::export::ExportService;
+#[cfg(feature = "full")]
+use crate::heartbeat::Heartbeat;
+use crate::idle::IdleShutdown;
+use crate::moduleguard::ModuleGuard;
+#[cfg(feature = "full")]
+use crate::mqtt::MqttBridge;
+#[cfg(feature = "full")]
+use crate::preload::ScenePreloader;
+use crate::proxy::{Chaos, Limits, Proxy};
+use crate::reaper;
+use crate::restoretest::{RestoreTest, RestoreTestScheduler};
+use crate::statesync::StateSync;
+use crate::tempfiles::TempFileSweeper;
+use crate::tls::{Certificates, HttpRedirect, TlsTerminator};
+use crate::uptime::UptimeMonitor;
+
+/// State of a supervised background subsystem
+#[derive(Debug, Clone)]
+pub struct SubsystemState {
+    pub healthy: bool,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+}
+
+static SUBSYSTEMS: Mutex<BTreeMap<&'static str, SubsystemState>> = Mutex::new(BTreeMap::new());
+
+/// All supervised subsystems by name, for the status endpoints
+pub fn subsystems() -> BTreeMap<&'static str, SubsystemState> {
+    SUBSYSTEMS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Run `task` in its own tokio task and start it again with backoff whenever it panics.
+///
+/// A panicking integration must never take the launcher and with it Foundry down.
+pub fn supervise<F, Fut>(name: &'static str, task: F)
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut backoff = Duration::from_secs(5);
+        loop {
+            set_state(name, |state| state.healthy = true);
+            let started = Instant::now();
+
+            let error = match tokio::spawn(task()).await {
+                Ok(()) => {
+                    info!("Subsystem {} finished", name);
+                    return;
+                }
+                Err(e) if e.is_panic() => panic_message(e.into_panic()),
+                Err(e) => e.to_string(),
+            };
+
+            // A subsystem that ran for a while before failing gets a fresh backoff
+            if started.elapsed() > Duration::from_secs(600) {
+                backoff = Duration::from_secs(5);
+            }
+            error!(
+                "❌ Subsystem {} crashed: {}, restarting in {:?}",
+                name, error, backoff
+            );
+            set_state(name, |state| {
+                state.healthy = false;
+                state.restarts += 1;
+                state.last_error = Some(error);
+            });
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(Duration::from_secs(300));
+        }
+    });
+}
+
+/// Start every configured integration under supervision
+pub fn start_subsystems(config: &AppConfig) {
+    supervise("reaper", reaper::run);
+
+    let uptime = UptimeMonitor {
+        foundry_port: config.server_port,
+        interval: Duration::from_secs(30),
+    };
+    supervise("uptime", move || uptime.clone().run());
+
+    if config.notify_disk_free_mb > 0
+        || config.disk_critical_free_mb > 0
+        || config.data_dir_quota_mb.is_some()
+    {
+        let monitor = DiskSpaceMonitor {
+            min_free_mb: config.notify_disk_free_mb,
+            critical_free_mb: config.disk_critical_free_mb,
+            quota_mb: config.data_dir_quota_mb,
+            excludes: config.scan_excludes.clone(),
+            interval: Duration::from_secs(300),
+        };
+        supervise("disk-space", move || monitor.clone().run());
+    }
+
+    if let Some(schedule) = &config.backup_schedule {
+        let scheduler = BackupScheduler {
+            service: BackupService::from_config(config),
+            schedule: schedule.clone(),
+            when_idle: config.backup_when_idle,
+            foundry_port: config.server_port,
+        };
+        supervise("backup", move || scheduler.clone().run());
+    }
+
+    if let Some(s3) = &config.backup_s3 {
+        capabilities::configured("s3-backup");
+        let s3 = s3.clone();
+        supervise("backup-resume", move || backup::resume_uploads(s3.clone()));
+    }
+
+    if let Some(schedule) = &config.restore_test_schedule {
+        let scheduler = RestoreTestScheduler {
+            test: RestoreTest::from_config(config),
+            schedule: schedule.clone(),
+        };
+        supervise("restore-test", move || scheduler.clone().run());
+    }
+
+    if config.return_to_setup_after.is_some() || config.return_to_setup_schedule.is_some() {
+        // A restart would launch the same world again, shutting it down as GAME_USER does not
+        if let Some(world) = &config.foundry_options.world
+            && config.game_user.is_none()
+        {
+            warn!(
+                "⚠️ Not returning to the setup screen, FOUNDRY_WORLD launches {} on start",
+                world
+            );
+        } else {
+            let monitor = AutoReturn {
+                idle: config
+                    .return_to_setup_after
+                    .map(|minutes| Duration::from_secs(minutes * 60)),
+                schedule: config.return_to_setup_schedule.clone(),
+                foundry_port: config.server_port,
+                interval: Duration::from_secs(60),
+            };
+            supervise("return-to-setup", move || monitor.clone().run());
+        }
+    }
+
+    if let Some(minutes) = config.idle_shutdown_minutes {
+        let monitor = IdleShutdown {
+            idle: Duration::from_secs(minutes * 60),
+            exit: config.idle_shutdown_exit,
+            foundry_port: config.server_port,
+            interval: Duration::from_secs(30),
+        };
+        supervise("idle-shutdown", move || monitor.clone().run());
+    }
+
+    if config.auto_disable_modules {
+        match &config.foundry_options.world {
+            Some(world) => {
+                let guard = ModuleGuard {
+                    world: world.clone(),
+                    foundry_port: config.server_port,
+                    interval: Duration::from_secs(5),
+                };
+                supervise("module-guard", move || guard.clone().run());
+            }
+            None => {
+                warn!("⚠️ AUTO_DISABLE_MODULES needs FOUNDRY_WORLD, the world launched on start")
+            }
+        }
+    }
+
+    let sweeper = TempFileSweeper {
+        max_age: Duration::from_secs(config.temp_file_max_age * 3600),
+    };
+    supervise("temp-files", move || sweeper.clone().run());
+
+    if let Some(url) = &config.clock_check_url {
+        let monitor = ClockMonitor {
+            check_url: url.clone(),
+        };
+        supervise("clock", move || monitor.clone().run());
+    }
+
+    let proxy = config.proxy_port.map(|port| Proxy {
+        listen_host: config.server_host.clone(),
+        listen_port: port,
+        upstream_port: config.server_port,
+        chaos: Chaos {
+            latency: Duration::from_millis(config.proxy_latency_ms),
+            jitter: Duration::from_millis(config.proxy_jitter_ms),
+            drop_rate: config.proxy_drop_percent / 100.0,
+        },
+        record_dir: config.proxy_record_dir.clone(),
+        headers: config.proxy_headers.clone(),
+        limits: Limits {
+            connections_per_ip: config.proxy_max_connections_per_ip,
+            header_timeout: Duration::from_secs(config.proxy_header_timeout),
+        },
+        auth: config.proxy_auth.clone(),
+    });
+    if let Some(proxy) = &proxy {
+        let proxy = proxy.clone();
+        supervise("proxy", move || proxy.clone().run());
+    }
+
+    if let Some(source) = &config.tls {
+        capabilities::configured("tls");
+        let certificates = Certificates::new(source.clone());
+        let terminator = TlsTerminator {
+            listen_host: config.server_host.clone(),
+            listen_port: config.tls_port,
+            upstream_port: config.proxy_port.unwrap_or(config.server_port),
+            certificates: certificates.clone(),
+            proxy,
+        };
+        supervise("tls", move || terminator.clone().run());
+        supervise("tls-certificates", move || certificates.clone().refresh());
+        if let Some(port) = config.tls_http_port {
+            let redirect = HttpRedirect {
+                listen_host: config.server_host.clone(),
+                listen_port: port,
+                https_port: config.tls_port,
+            };
+            supervise("tls-http", move || redirect.clone().run());
+        }
+    }
+
+    if config.proxy_auth.is_some() && config.proxy_port.is_none() {
+        warn!(
+            "⚠️ PROXY_AUTH_USERS and PROXY_AUTH_HEADER need PROXY_PORT, nothing asks for a login"
+        );
+    }
+
+    if let Some(sync) = StateSync::from_config(config) {
+        capabilities::configured("state-sync");
+        supervise("state-sync", move || sync.clone().run_schedule());
+    }
+
+    start_integrations(config);
+
+    // Opt-in content export, runs next to the installer and Foundry
+    if !config.export_worlds.is_empty() {
+        let worlds = config.export_worlds.clone();
+        let interval = Duration::from_secs(config.export_interval_hours * 3600);
+        let keep_last = config.export_keep_last;
+        supervise("export", move || {
+            ExportService::run_schedule(worlds.clone(), interval, keep_last)
+        });
+    }
+}
+
+/// Integrations with chat, smart home and monitoring services, left out of the `minimal` profile
+#[cfg(feature = "full")]
+fn start_integrations(config: &AppConfig) {
+    let rules = alerts::parse_rules(&config.alert_rules);
+    if !rules.is_empty() {
+        let engine = AlertEngine {
+            rules,
+            cooldown: Duration::from_secs(config.alert_cooldown_minutes * 60),
+            interval: Duration::from_secs(30),
+        };
+        supervise("alerts", move || engine.clone().run());
+    }
+
+    if config.preload_scenes {
+        let preloader = ScenePreloader {
+            port: config.proxy_port.unwrap_or(config.server_port),
+            foundry_port: config.server_port,
+            at: config.preload_at.clone(),
+        };
+        supervise("preload", move || preloader.clone().run());
+    }
+
+    match (&config.discord_bot_token, &config.discord_allowed_role) {
+        (Some(token), Some(role)) => {
+            capabilities::configured("discord");
+            let bot = DiscordBot {
+                token: token.clone(),
+                allowed_role: role.clone(),
+                prefix: config.discord_prefix.clone(),
+                backup: BackupService::from_config(config),
+            };
+            supervise("discord", move || bot.clone().run());
+        }
+        (Some(_), None) => {
+            warn!("⚠️ DISCORD_ALLOWED_ROLE is not set, the Discord bot stays off");
+            capabilities::failed("discord", "DISCORD_ALLOWED_ROLE is not set");
+        }
+        _ => {}
+    }
+
+    if let Some(host) = &config.mqtt_host {
+        capabilities::configured("mqtt");
+        let bridge = MqttBridge {
+            host: host.clone(),
+            port: config.mqtt_port,
+            credentials: config
+                .mqtt_username
+                .clone()
+                .map(|user| (user, config.mqtt_password.clone().unwrap_or_default())),
+            topic_prefix: config.mqtt_topic_prefix.clone(),
+            discovery_prefix: config.mqtt_discovery_prefix.clone(),
+            foundry_port: config.server_port,
+        };
+        supervise("mqtt", move || bridge.clone().run());
+    }
+
+    if let Some(url) = &config.heartbeat_url {
+        capabilities::configured("heartbeat");
+        let heartbeat = Heartbeat {
+            success_url: url.clone(),
+            failure_url: config.heartbeat_failure_url.clone(),
+            interval: Duration::from_secs(config.heartbeat_interval),
+            foundry_port: config.server_port,
+        };
+        supervise("heartbeat", move || heartbeat.clone().run());
+    }
+}
+
+/// The `minimal` profile leaves the integrations out, their settings are reported instead of
+/// silently ignored
+#[cfg(not(feature = "full"))]
+fn start_integrations(config: &AppConfig) {
+    let configured = [
+        ("alerts", "ALERT_RULES", !config.alert_rules.is_empty()),
+        ("preload", "PRELOAD_SCENES", config.preload_scenes),
+        (
+            "discord",
+            "DISCORD_BOT_TOKEN",
+            config.discord_bot_token.is_some(),
+        ),
+        ("mqtt", "MQTT_HOST", config.mqtt_host.is_some()),
+        ("heartbeat", "HEARTBEAT_URL", config.heartbeat_url.is_some()),
+        ("email", "SMTP_HOST", config.smtp_host.is_some()),
+        (
+            "webhook",
+            "NOTIFY_WEBHOOK_URL",
+            config.notify_webhook_url.is_some(),
+        ),
+    ];
+    for (capability, name, _) in configured.iter().filter(|(_, _, set)| *set) {
+        let reason = format!("{} is set, but this is the minimal build without it", name);
+        warn!("⚠️ {}", reason);
+        capabilities::failed(capability, reason);
+    }
+    // Incoming hooks are no capability, the admin API just does not serve them
+    if !config.webhooks.is_empty() {
+        warn!("⚠️ WEBHOOKS is set, but this is the minimal build without it");
+    }
+}
+
+fn set_state(name: &'static str, update: impl FnOnce(&mut SubsystemState)) {
+    let mut subsystems = SUBSYSTEMS.lock().unwrap_or_else(PoisonError::into_inner);
+    let state = subsystems.entry(name).or_insert(SubsystemState {
+        healthy: true,
+        restarts: 0,
+        last_error: None,
+    });
+    update(state);
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
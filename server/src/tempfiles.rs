@@ -0,0 +1,272 @@
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{PoisonError, RwLock};
+use std::time::SystemTime;
+use tokio::time::{Duration, sleep};
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+use crate::api::{self, ApiConfig, Message};
+use crate::jobstore::{self, JobStore, TemporaryFile};
+use crate::utils::paths;
+
+/// How often stale temporary files are looked for while the wrapper runs
+const SWEEP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// A tracked temporary file as served on `GET /api/tempfiles`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TrackedFile {
+    pub path: String,
+    /// What wrote it, e.g. download or package staging
+    pub purpose: String,
+    /// Size of the file, or of everything below the directory
+    pub bytes: u64,
+    /// Newest modification of the file or anything in the directory
+    #[schema(format = DateTime)]
+    pub modified: String,
+    /// A partial download a restarted container continues
+    pub resumable: bool,
+}
+
+/// Outcome of the last sweep
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SweepReport {
+    pub removed: usize,
+    pub freed_bytes: u64,
+    pub kept: usize,
+    pub kept_bytes: u64,
+    #[schema(format = DateTime)]
+    pub swept_at: String,
+}
+
+/// The tracked temporary files and what the last sweep removed
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TempFiles {
+    pub files: Vec<TrackedFile>,
+    pub last_sweep: Option<SweepReport>,
+}
+
+static LAST: RwLock<Option<SweepReport>> = RwLock::new(None);
+
+/// Remember `path` until it is gone, so an interrupted run does not leave it in the volume
+/// for good
+pub fn track(path: &Path, purpose: &str) {
+    let key = path.to_string_lossy().to_string();
+    let purpose = purpose.to_string();
+    jobstore::record(|store| {
+        store.temporary.entry(key).or_insert(TemporaryFile {
+            purpose,
+            created: Utc::now().to_rfc3339(),
+        });
+    });
+}
+
+/// Remove what earlier runs left behind, before anything writes temporary files again.
+///
+/// Nothing is in progress at this point, so every tracked file goes except partial downloads
+/// that are continued and younger than `max_age`. Staging directories and partial archives
+/// of releases before they were tracked are found by their names.
+pub fn sweep_at_startup(max_age: Duration) {
+    let mut candidates = tracked();
+    for leftover in leftovers() {
+        candidates
+            .entry(leftover.to_string_lossy().to_string())
+            .or_insert_with(|| "leftover".to_string());
+    }
+    sweep(candidates, max_age, true);
+}
+
+/// Removes tracked files not modified for `max_age`, e.g. the archive of an upload abandoned
+/// in the setup UI
+#[derive(Clone)]
+pub struct TempFileSweeper {
+    pub max_age: Duration,
+}
+
+impl TempFileSweeper {
+    pub async fn run(self) {
+        loop {
+            sleep(SWEEP_INTERVAL).await;
+            let max_age = self.max_age;
+            if let Err(e) =
+                tokio::task::spawn_blocking(move || sweep(tracked(), max_age, false)).await
+            {
+                warn!("Sweeping the temporary files failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Tracked paths with their purpose, partial downloads included
+fn tracked() -> BTreeMap<String, String> {
+    let store = JobStore::load().unwrap_or_default();
+    let mut tracked: BTreeMap<String, String> = store
+        .temporary
+        .into_iter()
+        .map(|(path, file)| (path, file.purpose))
+        .collect();
+    for path in store.downloads.into_keys() {
+        tracked
+            .entry(path)
+            .or_insert_with(|| "download".to_string());
+    }
+    tracked
+}
+
+fn sweep(candidates: BTreeMap<String, String>, max_age: Duration, startup: bool) {
+    let resumable = JobStore::load().unwrap_or_default().downloads;
+    let now = SystemTime::now();
+    let mut report = SweepReport {
+        removed: 0,
+        freed_bytes: 0,
+        kept: 0,
+        kept_bytes: 0,
+        swept_at: Utc::now().to_rfc3339(),
+    };
+    let mut gone = Vec::new();
+    for (path, purpose) in candidates {
+        let Some((bytes, modified)) = measure(Path::new(&path)) else {
+            gone.push(path);
+            continue;
+        };
+        let age = now.duration_since(modified).unwrap_or_default();
+        let stale = age > max_age || (startup && !resumable.contains_key(&path));
+        if !stale {
+            report.kept += 1;
+            report.kept_bytes += bytes;
+            continue;
+        }
+        match remove(Path::new(&path)) {
+            Ok(()) => {
+                info!(
+                    "🧹 Removed the stale {} {} ({} MB)",
+                    purpose,
+                    path,
+                    bytes / (1024 * 1024)
+                );
+                report.removed += 1;
+                report.freed_bytes += bytes;
+                gone.push(path);
+            }
+            Err(e) => warn!("⚠️ Failed to remove the stale {} {}: {}", purpose, path, e),
+        }
+    }
+    if !gone.is_empty() {
+        jobstore::record(|store| {
+            for path in &gone {
+                store.temporary.remove(path);
+                store.downloads.remove(path);
+            }
+        });
+    }
+    if report.removed > 0 {
+        info!(
+            "🧹 Freed {} MB of temporary files, {} MB are still in use",
+            report.freed_bytes / (1024 * 1024),
+            report.kept_bytes / (1024 * 1024)
+        );
+    } else {
+        debug!("No stale temporary files, {} are in use", report.kept);
+    }
+    *LAST.write().unwrap_or_else(PoisonError::into_inner) = Some(report);
+}
+
+/// Names that interrupted installs, restores and backups of earlier releases left behind
+fn leftovers() -> Vec<PathBuf> {
+    let application_dir = Path::new(&*paths::APPLICATION_DIR);
+    let data_dir = Path::new(&*paths::DATA_DIR);
+    let mut leftovers = vec![
+        application_dir.join(".install-staging"),
+        application_dir.join("archive.zip"),
+        application_dir.join("foundryvtt-download.zip"),
+        data_dir.join(".restore-staging"),
+        data_dir.join(".migrate-staging"),
+        data_dir.join(".unarchive-staging"),
+    ];
+    let hidden = |dir: &Path, suffixes: &[&str]| -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy())
+                    .is_some_and(|name| {
+                        name.starts_with('.') && suffixes.iter().any(|s| name.ends_with(s))
+                    })
+            })
+            .collect()
+    };
+    for kind in ["modules", "systems"] {
+        leftovers.extend(hidden(
+            &data_dir.join("Data").join(kind),
+            &[".zip", ".staging"],
+        ));
+    }
+    leftovers.extend(hidden(Path::new(&*paths::BACKUP_DIR), &[".partial"]));
+    leftovers
+}
+
+/// Size and newest modification of the file, or of everything below the directory
+fn measure(path: &Path) -> Option<(u64, SystemTime)> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    let mut bytes = metadata.len();
+    let mut modified = metadata.modified().ok()?;
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path).ok()?.flatten() {
+            if let Some((entry_bytes, entry_modified)) = measure(&entry.path()) {
+                bytes += entry_bytes;
+                modified = modified.max(entry_modified);
+            }
+        }
+    }
+    Some((bytes, modified))
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path)?.is_dir() {
+        true => fs::remove_dir_all(path),
+        false => fs::remove_file(path),
+    }
+}
+
+/// Temporary files and partial downloads the wrapper tracks, and what the last sweep removed
+#[utoipa::path(
+    get,
+    path = "/api/tempfiles",
+    operation_id = "tempfiles",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "Tracked temporary files", body = TempFiles),
+        (status = 401, description = "Invalid token", body = Message),
+        (status = 403, description = "ADMIN_API_TOKEN is not set", body = Message),
+    )
+)]
+pub async fn show(req: HttpRequest, config: web::Data<ApiConfig>) -> impl Responder {
+    if let Some(rejected) = api::reject(&req, &config) {
+        return rejected;
+    }
+    let resumable = JobStore::load().unwrap_or_default().downloads;
+    let files: Vec<TrackedFile> = tracked()
+        .into_iter()
+        .filter_map(|(path, purpose)| {
+            let (bytes, modified) = measure(Path::new(&path))?;
+            Some(TrackedFile {
+                resumable: resumable.contains_key(&path),
+                path,
+                purpose,
+                bytes,
+                modified: DateTime::<Utc>::from(modified).to_rfc3339(),
+            })
+        })
+        .collect();
+    let last_sweep = LAST.read().unwrap_or_else(PoisonError::into_inner).clone();
+    HttpResponse::Ok().json(TempFiles { files, last_sweep })
+}
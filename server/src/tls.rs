@@ -0,0 +1,483 @@
+use actix_web::{App, HttpRequest, HttpResponse, HttpServer, Responder, http::header, web};
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::SystemTime;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{Duration, sleep, timeout};
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::rustls::crypto::ring::{default_provider, sign};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tracing::{debug, info, warn};
+
+use crate::acme::{self, AcmeClient};
+use crate::capabilities;
+use crate::notify::{self, Notification, Severity};
+use crate::permissions::{self, FileKind};
+use crate::proxy::{self, Proxy};
+use crate::socketactivation::{self, Listener};
+use crate::utils::paths;
+
+/// Time a client gets to complete the TLS handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often certificate files are checked for changes and ACME certificates for renewal
+const REFRESH_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Where the certificate players get comes from, configured with `TLS_*`
+#[derive(Debug, Clone)]
+pub enum CertificateSource {
+    /// PEM files maintained outside the wrapper, e.g. by certbot, reloaded when they change
+    Files { cert: PathBuf, key: PathBuf },
+    /// Ordered from an ACME CA and renewed once a third of its lifetime is left
+    Acme {
+        domain: String,
+        email: String,
+        directory: String,
+    },
+}
+
+/// The certificate currently served, swapped without dropping open connections
+#[derive(Debug, Clone)]
+pub struct Certificates {
+    pub source: CertificateSource,
+    current: Arc<RwLock<Option<Arc<CertifiedKey>>>>,
+}
+
+impl ResolvesServerCert for Certificates {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl Certificates {
+    pub fn new(source: CertificateSource) -> Self {
+        Self {
+            source,
+            current: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some()
+    }
+
+    /// PEM files of the certificate chain and its key
+    fn paths(&self) -> (PathBuf, PathBuf) {
+        match &self.source {
+            CertificateSource::Files { cert, key } => (cert.clone(), key.clone()),
+            CertificateSource::Acme { domain, .. } => (
+                storage_dir().join(format!("{}.crt", domain)),
+                storage_dir().join(format!("{}.key", domain)),
+            ),
+        }
+    }
+
+    /// Load the certificate from its files, answering when it expires
+    fn load(&self) -> Result<DateTime<Utc>> {
+        let (cert_path, key_path) = self.paths();
+        let chain = CertificateDer::pem_file_iter(&cert_path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .with_context(|| {
+                format!("Failed to read the certificates in {}", cert_path.display())
+            })?;
+        let leaf = chain
+            .first()
+            .ok_or_else(|| anyhow!("{} holds no certificate", cert_path.display()))?;
+        let (_, not_after) = validity(leaf)?;
+        let key = PrivateKeyDer::from_pem_file(&key_path)
+            .with_context(|| format!("Failed to read the key in {}", key_path.display()))?;
+        let key = sign::any_supported_type(&key)
+            .map_err(|e| anyhow!("{} is no usable key: {}", key_path.display(), e))?;
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) =
+            Some(Arc::new(CertifiedKey::new(chain, key)));
+        info!(
+            "🔐 Serving the certificate from {}, valid until {}",
+            cert_path.display(),
+            not_after.format("%Y-%m-%d")
+        );
+        Ok(not_after)
+    }
+
+    /// Keep the certificate current: reload changed files, or order a new certificate from the
+    /// CA when there is none yet or it is due for renewal
+    pub async fn refresh(self) {
+        let mut modified = None;
+        loop {
+            let retry = match &self.source {
+                CertificateSource::Files { cert, key } => {
+                    let changed = modified_at(cert).max(modified_at(key));
+                    if changed != modified {
+                        modified = changed;
+                        match self.load() {
+                            Ok(_) => capabilities::active("tls"),
+                            Err(e) => {
+                                warn!("⚠️ Keeping the previous certificate: {:#}", e);
+                                capabilities::failed("tls", &e);
+                            }
+                        }
+                    }
+                    REFRESH_INTERVAL
+                }
+                CertificateSource::Acme { .. } => match self.renew_if_due().await {
+                    Ok(()) => {
+                        capabilities::active("tls");
+                        REFRESH_INTERVAL
+                    }
+                    Err(e) => {
+                        warn!("⚠️ Failed to obtain a certificate: {:#}", e);
+                        capabilities::failed("tls", &e);
+                        notify::send(Notification {
+                            severity: if self.is_loaded() {
+                                Severity::Warning
+                            } else {
+                                Severity::Critical
+                            },
+                            title: "Certificate not renewed".to_string(),
+                            message: format!("{:#}", e),
+                        });
+                        // Let's Encrypt allows only a few failed validations per hour
+                        REFRESH_INTERVAL
+                    }
+                },
+            };
+            sleep(retry).await;
+        }
+    }
+
+    async fn renew_if_due(&self) -> Result<()> {
+        let CertificateSource::Acme {
+            domain,
+            email,
+            directory,
+        } = &self.source
+        else {
+            return Ok(());
+        };
+        let (cert_path, key_path) = self.paths();
+        if cert_path.exists() {
+            let leaf = CertificateDer::pem_file_iter(&cert_path)
+                .ok()
+                .and_then(|mut certs| certs.next())
+                .and_then(|cert| cert.ok());
+            match leaf.as_ref().map(validity) {
+                Some(Ok((not_before, not_after))) if !renewal_due(not_before, not_after) => {
+                    if !self.is_loaded() {
+                        self.load()?;
+                    }
+                    return Ok(());
+                }
+                Some(Ok(_)) => info!("🔐 The certificate for {} is due for renewal", domain),
+                _ => warn!(
+                    "⚠️ {} is unreadable, ordering a new one",
+                    cert_path.display()
+                ),
+            }
+        }
+
+        permissions::create_dir_all(&storage_dir())
+            .with_context(|| format!("Failed to create {}", storage_dir().display()))?;
+        let account_path = storage_dir().join("account.key");
+        let account_key = match fs::read(&account_path) {
+            Ok(key) => key,
+            Err(_) => {
+                let key = acme::generate_key()?;
+                permissions::write(&account_path, &key, FileKind::Secret)
+                    .with_context(|| format!("Failed to write {}", account_path.display()))?;
+                key
+            }
+        };
+        info!(
+            "🔐 Ordering a certificate for {} from {}",
+            domain, directory
+        );
+        let client = AcmeClient {
+            directory: directory.clone(),
+            email: email.clone(),
+            account_key,
+        };
+        let (chain, key) = client.order(domain).await?;
+        permissions::write(&key_path, pem("PRIVATE KEY", &key), FileKind::Secret)
+            .with_context(|| format!("Failed to write {}", key_path.display()))?;
+        permissions::write(&cert_path, chain, FileKind::Regular)
+            .with_context(|| format!("Failed to write {}", cert_path.display()))?;
+        self.load()?;
+        Ok(())
+    }
+}
+
+/// Terminates TLS for players and hands the decrypted connections to the proxy, or straight
+/// to Foundry without one
+#[derive(Clone)]
+pub struct TlsTerminator {
+    pub listen_host: String,
+    pub listen_port: u16,
+    pub upstream_port: u16,
+    pub certificates: Certificates,
+    pub proxy: Option<Proxy>,
+}
+
+impl TlsTerminator {
+    pub async fn run(self) {
+        let listener = match socketactivation::listener(Listener::Tls) {
+            Some(activated) => activated.and_then(TcpListener::from_std),
+            None => TcpListener::bind((self.listen_host.as_str(), self.listen_port)).await,
+        };
+        let listener = match listener {
+            Ok(listener) => listener,
+            Err(e) => {
+                warn!(
+                    "⚠️ TLS could not listen on {}:{}: {}",
+                    self.listen_host, self.listen_port, e
+                );
+                capabilities::failed("tls", format!("Port {} is taken: {}", self.listen_port, e));
+                return;
+            }
+        };
+        let config = match ServerConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+        {
+            Ok(builder) => builder,
+            Err(e) => {
+                warn!("⚠️ TLS could not be set up: {}", e);
+                capabilities::failed("tls", &e);
+                return;
+            }
+        };
+        let mut config = config
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(self.certificates.clone()));
+        // Foundry's websocket upgrade needs HTTP/1.1
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        info!(
+            "TLS listening on {}, forwarding to {} on port {}",
+            proxy::address(&listener, &self.listen_host, self.listen_port),
+            if self.proxy.is_some() {
+                "the proxy"
+            } else {
+                "Foundry"
+            },
+            self.upstream_port
+        );
+
+        loop {
+            let (client, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    debug!("Failed to accept a TLS connection: {}", e);
+                    continue;
+                }
+            };
+            let terminator = self.clone();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move { terminator.serve(&acceptor, client, peer).await });
+        }
+    }
+
+    async fn serve(&self, acceptor: &TlsAcceptor, client: TcpStream, peer: SocketAddr) {
+        let stream = match timeout(HANDSHAKE_TIMEOUT, acceptor.accept(client)).await {
+            Ok(Ok(stream)) => stream,
+            Ok(Err(e)) => {
+                debug!("TLS handshake with {} failed: {}", peer, e);
+                return;
+            }
+            Err(_) => {
+                debug!("{} did not complete the TLS handshake in time", peer);
+                return;
+            }
+        };
+        if let Some(proxy) = &self.proxy {
+            return proxy.serve(stream, peer).await;
+        }
+        let mut stream = stream;
+        let piped = async {
+            let mut upstream = TcpStream::connect(("127.0.0.1", self.upstream_port)).await?;
+            tokio::io::copy_bidirectional(&mut stream, &mut upstream).await
+        };
+        if let Err(e) = piped.await {
+            debug!("TLS connection from {} ended: {}", peer, e);
+        }
+    }
+}
+
+/// Plain HTTP on `TLS_HTTP_PORT`: answers ACME challenges and sends everyone else to HTTPS
+#[derive(Clone)]
+pub struct HttpRedirect {
+    pub listen_host: String,
+    pub listen_port: u16,
+    pub https_port: u16,
+}
+
+impl HttpRedirect {
+    pub async fn run(self) {
+        // Only the running server is Send, the builder is dropped before awaiting it
+        let server = match self.bind() {
+            Ok(server) => server,
+            Err(e) => {
+                warn!(
+                    "⚠️ HTTP redirect could not listen on {}:{}: {}",
+                    self.listen_host, self.listen_port, e
+                );
+                return;
+            }
+        };
+        info!(
+            "Redirecting HTTP on {}:{} to HTTPS",
+            self.listen_host, self.listen_port
+        );
+        if let Err(e) = server.await {
+            warn!("⚠️ The HTTP redirect stopped: {}", e);
+        }
+    }
+
+    fn bind(&self) -> std::io::Result<actix_web::dev::Server> {
+        let https_port = web::Data::new(self.https_port);
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(https_port.clone())
+                .route(
+                    "/.well-known/acme-challenge/{token}",
+                    web::get().to(challenge),
+                )
+                .default_service(web::to(redirect))
+        })
+        .workers(1)
+        .disable_signals();
+        let server = match socketactivation::listener(Listener::Http) {
+            Some(activated) => server.listen(activated?)?,
+            None => server.bind((self.listen_host.clone(), self.listen_port))?,
+        };
+        Ok(server.run())
+    }
+}
+
+/// `GET /.well-known/acme-challenge/{token}`: the key authorization of a pending challenge
+async fn challenge(token: web::Path<String>) -> impl Responder {
+    match acme::challenge_response(&token) {
+        Some(response) => HttpResponse::Ok().content_type("text/plain").body(response),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+/// Anything else: the same URL on HTTPS
+async fn redirect(req: HttpRequest, https_port: web::Data<u16>) -> impl Responder {
+    let info = req.connection_info();
+    let host = info.host();
+    // Strip the port of the host, keeping IPv6 addresses in brackets intact
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if !name.ends_with(':') && port.chars().all(|c| c.is_ascii_digit()) => {
+            name
+        }
+        _ => host,
+    };
+    let authority = match **https_port {
+        443 => host.to_string(),
+        port => format!("{}:{}", host, port),
+    };
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+    HttpResponse::PermanentRedirect()
+        .insert_header((header::LOCATION, format!("https://{}{}", authority, path)))
+        .finish()
+}
+
+/// Certificates and the account key ordered with ACME
+fn storage_dir() -> PathBuf {
+    paths::WRAPPER_STATE_DIR.join("tls")
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Renew once less than a third of the lifetime is left, 30 days for Let's Encrypt
+fn renewal_due(not_before: DateTime<Utc>, not_after: DateTime<Utc>) -> bool {
+    let lifetime = not_after - not_before;
+    Utc::now() > not_after - lifetime / 3
+}
+
+fn pem(label: &str, der: &[u8]) -> String {
+    let encoded = STANDARD.encode(der);
+    let lines: Vec<&str> = encoded
+        .as_bytes()
+        .chunks(64)
+        .map(|line| std::str::from_utf8(line).unwrap_or_default())
+        .collect();
+    format!(
+        "-----BEGIN {label}-----\n{}\n-----END {label}-----\n",
+        lines.join("\n")
+    )
+}
+
+/// `notBefore` and `notAfter` of a certificate
+fn validity(cert: &CertificateDer<'_>) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let invalid = || anyhow!("The certificate is malformed");
+    let (_, certificate, _) = der_element(cert.as_ref()).ok_or_else(invalid)?;
+    let (_, mut tbs, _) = der_element(certificate).ok_or_else(invalid)?;
+    // The version is optional and tagged [0], then come serial, signature and issuer
+    let mut skipped = 0;
+    while skipped < 3 {
+        let (tag, _, rest) = der_element(tbs).ok_or_else(invalid)?;
+        if tag != 0xa0 {
+            skipped += 1;
+        }
+        tbs = rest;
+    }
+    let (_, validity, _) = der_element(tbs).ok_or_else(invalid)?;
+    let (not_before_tag, not_before, rest) = der_element(validity).ok_or_else(invalid)?;
+    let (not_after_tag, not_after, _) = der_element(rest).ok_or_else(invalid)?;
+    Ok((
+        der_time(not_before_tag, not_before).ok_or_else(invalid)?,
+        der_time(not_after_tag, not_after).ok_or_else(invalid)?,
+    ))
+}
+
+/// Tag, content and remainder of the DER element `bytes` starts with
+fn der_element(bytes: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = bytes.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (length, rest) = match first {
+        0..0x80 => (first as usize, rest),
+        _ => {
+            let count = (first & 0x7f) as usize;
+            if count > 4 || rest.len() < count {
+                return None;
+            }
+            let (length, rest) = rest.split_at(count);
+            let length = length.iter().fold(0, |acc, b| (acc << 8) | *b as usize);
+            (length, rest)
+        }
+    };
+    (rest.len() >= length).then(|| (tag, &rest[..length], &rest[length..]))
+}
+
+/// UTCTime (0x17) or GeneralizedTime (0x18)
+fn der_time(tag: u8, content: &[u8]) -> Option<DateTime<Utc>> {
+    let text = std::str::from_utf8(content).ok()?;
+    let format = match tag {
+        0x17 => "%y%m%d%H%M%SZ",
+        0x18 => "%Y%m%d%H%M%SZ",
+        _ => return None,
+    };
+    NaiveDateTime::parse_from_str(text, format)
+        .ok()
+        .map(|time| time.and_utc())
+}
@@ -0,0 +1,313 @@
+use anyhow::{Context, Result, anyhow};
+use serde::Serialize;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+use zip::CompressionMethod;
+use zip::write::{SimpleFileOptions, ZipWriter};
+
+use crate::config::AppConfig;
+use crate::extractor::ExtractorService;
+use crate::journal::Journal;
+use crate::permissions::{self, FileKind};
+use crate::restore;
+use crate::scan::ScanService;
+use crate::tempfiles;
+use crate::utils::{compare_versions, installed_foundry_version, paths};
+use crate::worlds;
+
+pub const STAGING_DIR: &str = ".import-staging";
+
+/// A world written to a portable zip
+#[derive(Debug, Serialize)]
+pub struct ExportedWorld {
+    pub path: PathBuf,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// A world put into `Data/worlds` from a zip
+#[derive(Debug, Serialize)]
+pub struct ImportedWorld {
+    pub id: String,
+    pub title: String,
+    /// A world with the same id was replaced with `--force`
+    pub replaced: bool,
+}
+
+/// Moves worlds between containers as zips with `world.json` at the top, the layout of the
+/// world packages Foundry installs itself, so the zips also work outside this image.
+pub struct WorldTransfer {
+    pub foundry_port: u16,
+}
+
+impl WorldTransfer {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            foundry_port: config.server_port,
+        }
+    }
+
+    /// Zip `world` to `output`, `BACKUP_DIR/exports/<id>-<UTC time>.zip` by default
+    pub async fn export(&self, world: &str, output: Option<PathBuf>) -> Result<ExportedWorld> {
+        let dir = paths::WORLDS_DIR.join(world);
+        let manifest = read_manifest(&dir).with_context(|| format!("World {} not found", world))?;
+        let id = worlds::validate_manifest(&manifest)?;
+        if id != world {
+            return Err(anyhow!(
+                "world.json of {} names the world {}, Foundry would not load it",
+                world,
+                id
+            ));
+        }
+        worlds::refuse_active(self.foundry_port, world, "exporting it").await?;
+
+        let output = output.unwrap_or_else(|| {
+            PathBuf::from(&*paths::BACKUP_DIR)
+                .join("exports")
+                .join(format!(
+                    "{}-{}.zip",
+                    world,
+                    chrono::Utc::now().format("%Y%m%d-%H%M%S")
+                ))
+        });
+        if output.exists() {
+            return Err(anyhow!("{} exists already", output.display()));
+        }
+        let exported = tokio::task::spawn_blocking(move || write_zip(&dir, &output)).await??;
+        info!(
+            "📦 Exported world {} ({} files) to {}",
+            world,
+            exported.files,
+            exported.path.display()
+        );
+        Ok(exported)
+    }
+
+    /// Put the world of `archive` into `Data/worlds`, as `id` when given. An existing world
+    /// with that id is only replaced with `force`, and stays in place when the import fails.
+    pub async fn import(
+        &self,
+        archive: &Path,
+        id: Option<&str>,
+        force: bool,
+    ) -> Result<ImportedWorld> {
+        if let Some(id) = id {
+            worlds::validate_id(id)?;
+        }
+        let checked = archive.to_path_buf();
+        tokio::task::spawn_blocking(move || restore::verify_archive(&checked))
+            .await?
+            .with_context(|| format!("{} is not a readable zip", archive.display()))?;
+
+        let staging = PathBuf::from(&*paths::DATA_DIR).join(STAGING_DIR);
+        remove_if_exists(&staging)?;
+        tempfiles::track(&staging, "world import staging");
+        let imported = self.import_staged(archive, &staging, id, force).await;
+        let _ = remove_if_exists(&staging);
+        let imported = imported?;
+
+        info!(
+            "📦 Imported world {} ({}) from {}",
+            imported.id,
+            imported.title,
+            archive.display()
+        );
+        Ok(imported)
+    }
+
+    async fn import_staged(
+        &self,
+        archive: &Path,
+        staging: &Path,
+        id: Option<&str>,
+        force: bool,
+    ) -> Result<ImportedWorld> {
+        let unpacked = staging.join("archive");
+        // Nobody follows the progress of an import
+        let (event_tx, _) = broadcast::channel(16);
+        ExtractorService::extract_zip(
+            archive.to_string_lossy().to_string(),
+            unpacked.to_string_lossy().to_string(),
+            event_tx,
+        )
+        .await
+        .with_context(|| format!("Failed to extract {}", archive.display()))?;
+
+        let root = world_root(&unpacked)?
+            .ok_or_else(|| anyhow!("{} contains no world.json", archive.display()))?;
+        let mut manifest = read_manifest(&root)?;
+        let manifest_id = worlds::validate_manifest(&manifest)?;
+        let id = id.unwrap_or(&manifest_id).to_string();
+        if id != manifest_id {
+            for key in ["id", "name"] {
+                if manifest.get(key).is_some() {
+                    manifest[key] = Value::String(id.clone());
+                }
+            }
+            let path = root.join("world.json");
+            permissions::write(
+                &path,
+                serde_json::to_string_pretty(&manifest)?,
+                FileKind::Regular,
+            )
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        warn_incompatible(&id, &manifest);
+
+        let target = paths::WORLDS_DIR.join(&id);
+        let replaced = target.exists();
+        if replaced {
+            if !force {
+                return Err(anyhow!(
+                    "World {} exists already, pass --force to replace it or --id to import it next to it",
+                    id
+                ));
+            }
+            worlds::refuse_active(self.foundry_port, &id, "replacing it").await?;
+        }
+
+        permissions::create_dir_all(&paths::WORLDS_DIR)
+            .with_context(|| format!("Failed to create {}", paths::WORLDS_DIR.display()))?;
+        let mut journal = Journal::begin(format!("import of world {}", id))?;
+        let moved = (|| {
+            // Removed with the staging directory once the import is done
+            journal.clean_up(staging)?;
+            if replaced {
+                journal.rename(&target, &staging.join("replaced"))?;
+            }
+            journal.rename(&root, &target)
+        })();
+        journal.complete(moved)?;
+
+        Ok(ImportedWorld {
+            title: manifest["title"].as_str().unwrap_or(&id).to_string(),
+            id,
+            replaced,
+        })
+    }
+}
+
+fn write_zip(dir: &Path, output: &Path) -> Result<ExportedWorld> {
+    let parent = output
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    permissions::create_dir_all(parent)
+        .with_context(|| format!("Failed to create {}", parent.display()))?;
+    let name = output
+        .file_name()
+        .ok_or_else(|| anyhow!("{} is not a file name", output.display()))?;
+    let partial = parent.join(format!(".{}.partial", name.to_string_lossy()));
+    tempfiles::track(&partial, "partial world export");
+
+    let entries = ScanService::walk(dir, &[])?;
+    let written = (|| -> Result<(usize, u64)> {
+        let file = permissions::create(&partial, FileKind::Regular)
+            .with_context(|| format!("Failed to create {}", partial.display()))?;
+        let mut zip = ZipWriter::new(io::BufWriter::new(file));
+        let mut written = (0, 0);
+        for entry in &entries {
+            let relative = entry.path.strip_prefix(dir)?;
+            let mut source = match fs::File::open(&entry.path) {
+                Ok(source) => source,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("Failed to read {}", entry.path.display()));
+                }
+            };
+            let options = SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Deflated)
+                .large_file(entry.size >= u32::MAX as u64);
+            zip.start_file(relative.to_string_lossy(), options)?;
+            written.1 += io::copy(&mut source, &mut zip)
+                .with_context(|| format!("Failed to export {}", relative.display()))?;
+            written.0 += 1;
+        }
+        zip.finish()?;
+        restore::verify_archive(&partial)?;
+        Ok(written)
+    })()
+    .inspect_err(|_| {
+        let _ = fs::remove_file(&partial);
+    })?;
+    fs::rename(&partial, output)
+        .with_context(|| format!("Failed to move {} into place", output.display()))?;
+
+    Ok(ExportedWorld {
+        path: output.to_path_buf(),
+        files: written.0,
+        bytes: written.1,
+    })
+}
+
+/// Directory of the shallowest `world.json`, zips made by zipping the world's folder have it
+/// one level down
+fn world_root(dir: &Path) -> Result<Option<PathBuf>> {
+    let mut roots: Vec<PathBuf> = ScanService::walk(dir, &[])?
+        .into_iter()
+        .filter(|entry| {
+            entry
+                .path
+                .file_name()
+                .is_some_and(|name| name == "world.json")
+        })
+        .filter_map(|entry| entry.path.parent().map(Path::to_path_buf))
+        .collect();
+    roots.sort_by_key(|root| root.components().count());
+    match roots.as_slice() {
+        [] => Ok(None),
+        [first, second, ..] if first.components().count() == second.components().count() => Err(
+            anyhow!("The archive contains several worlds, import them one at a time"),
+        ),
+        [first, ..] => Ok(Some(first.clone())),
+    }
+}
+
+/// Foundry refuses worlds for a newer release and shows those of a missing system as broken,
+/// neither stops the import
+fn warn_incompatible(id: &str, manifest: &Value) {
+    if let (Some(minimum), Some(installed)) = (
+        manifest["compatibility"]["minimum"].as_str(),
+        installed_foundry_version(),
+    ) && compare_versions(minimum, &installed) == Ordering::Greater
+    {
+        warn!(
+            "⚠️ World {} needs Foundry {} or newer, {} is installed",
+            id, minimum, installed
+        );
+    }
+    if let Some(system) = manifest["system"].as_str()
+        && !PathBuf::from(&*paths::DATA_DIR)
+            .join("Data")
+            .join("systems")
+            .join(system)
+            .exists()
+    {
+        warn!(
+            "⚠️ World {} uses the system {}, which is not installed",
+            id, system
+        );
+    }
+}
+
+fn read_manifest(dir: &Path) -> Result<Value> {
+    let path = dir.join("world.json");
+    let contents =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&contents).with_context(|| format!("Invalid {}", path.display()))
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_dir_all(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
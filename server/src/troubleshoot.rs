@@ -0,0 +1,445 @@
+use anyhow::Result;
+use chrono::Utc;
+use clap::ValueEnum;
+use reqwest::redirect::Policy;
+use serde_json::Value;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use tokio::net::TcpStream;
+use tokio::time::{Duration, timeout};
+
+use crate::config::AppConfig;
+use crate::doctor::{Check, Doctor};
+use crate::i18n::{tr, tr_args};
+use crate::integrity::IntegrityService;
+use crate::license::generation;
+use crate::logs;
+use crate::runtime::RuntimeInfo;
+use crate::status;
+use crate::utils::{installed_foundry_version, paths};
+use crate::worlds::WorldService;
+
+/// Foundry log errors of this many hours are shown for a world that does not load
+const RECENT_ERRORS_HOURS: i64 = 24;
+const MAX_ERRORS: usize = 5;
+
+/// The failures `troubleshoot` walks through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Problem {
+    /// The browser cannot reach the server
+    Unreachable,
+    /// A world does not load
+    World,
+    /// Permission denied errors in the log
+    Permissions,
+    /// Everything above
+    Other,
+}
+
+impl Problem {
+    const ALL: [Problem; 4] = [
+        Problem::Unreachable,
+        Problem::World,
+        Problem::Permissions,
+        Problem::Other,
+    ];
+
+    fn label(self) -> String {
+        tr(match self {
+            Self::Unreachable => "troubleshoot-problem-unreachable",
+            Self::World => "troubleshoot-problem-world",
+            Self::Permissions => "troubleshoot-problem-permissions",
+            Self::Other => "troubleshoot-problem-other",
+        })
+    }
+}
+
+/// Checks run for one problem, printed and summarized for an issue
+pub struct Troubleshooting {
+    pub problem: Problem,
+    pub checks: Vec<Check>,
+}
+
+impl Troubleshooting {
+    /// Ask what is wrong unless `problem` says it, then run the diagnostics of `doctor` and the
+    /// connectivity checks that belong to it. Without a terminal nothing is asked and every
+    /// check runs.
+    pub async fn run(problem: Option<Problem>, world: Option<String>) -> Result<Self> {
+        let interactive = io::stdin().is_terminal();
+        let problem = match problem {
+            Some(problem) => problem,
+            None if interactive => {
+                let labels: Vec<String> = Problem::ALL.iter().map(|p| p.label()).collect();
+                ask(&tr("troubleshoot-ask-problem"), &labels)
+                    .map_or(Problem::Other, |choice| Problem::ALL[choice])
+            }
+            None => Problem::Other,
+        };
+
+        let config = AppConfig::from_env();
+        let mut checks = Vec::new();
+        if matches!(problem, Problem::Unreachable | Problem::Other) {
+            checks.push(check_port(config.server_port).await);
+            if !config.join_url.is_empty() {
+                checks.push(check_join_url(&config.join_url, config.server_port).await);
+            }
+            checks.push(Doctor::check_foundry_config());
+        }
+        if matches!(problem, Problem::World | Problem::Other) {
+            let world = world.or_else(|| choose_world(interactive && problem == Problem::World));
+            match world {
+                Some(world) => checks.extend(check_world(&world, &config)),
+                None => checks.push(Check {
+                    name: tr("troubleshoot-worlds"),
+                    ok: problem != Problem::World,
+                    detail: tr("troubleshoot-no-world"),
+                    hints: Vec::new(),
+                }),
+            }
+        }
+        if matches!(problem, Problem::Permissions | Problem::Other) {
+            checks.extend(Doctor::volumes());
+            // Files copied in as root are a common cause, below a writable volume
+            let data = PathBuf::from(&*paths::DATA_DIR);
+            for dir in [
+                data.join("Config"),
+                data.join("Data"),
+                paths::WORLDS_DIR.clone(),
+                data.join("Data").join("modules"),
+                data.join("Data").join("systems"),
+            ] {
+                if dir.is_dir() {
+                    checks.push(Doctor::check_volume(&dir));
+                }
+            }
+        }
+        Ok(Self { problem, checks })
+    }
+
+    /// Everything the maintainers ask for first, in a code block to paste into the issue
+    pub fn summary(&self) -> String {
+        let mut lines = vec![
+            "```text".to_string(),
+            tr_args(
+                "troubleshoot-summary-problem",
+                &[("problem", &self.problem.label())],
+            ),
+            format!(
+                "foundry-watcher {} ({} profile), Foundry {}, {}",
+                env!("CARGO_PKG_VERSION"),
+                crate::PROFILE,
+                installed_foundry_version().unwrap_or_else(|| "-".to_string()),
+                RuntimeInfo::detect().describe()
+            ),
+            String::new(),
+        ];
+        for check in &self.checks {
+            lines.push(format!("{} {}: {}", check.icon(), check.name, check.detail));
+            for hint in &check.hints {
+                lines.push(format!("   → {}", hint));
+            }
+        }
+        lines.push("```".to_string());
+        lines.join("\n")
+    }
+}
+
+/// Index of the choice the user typed, `None` once stdin is closed
+fn ask(question: &str, choices: &[String]) -> Option<usize> {
+    println!("{}", question);
+    for (i, choice) in choices.iter().enumerate() {
+        println!("  {}) {}", i + 1, choice);
+    }
+    let prompt = tr_args(
+        "troubleshoot-ask-choice",
+        &[("count", &choices.len().to_string())],
+    );
+    let mut stdin = io::stdin().lock();
+    loop {
+        print!("{} ", prompt);
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        if stdin.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let answer = line.trim();
+        if let Some(index) = answer
+            .parse::<usize>()
+            .ok()
+            .filter(|n| (1..=choices.len()).contains(n))
+        {
+            return Some(index - 1);
+        }
+        // The name of a choice works too, e.g. the id of a world
+        if let Some(index) = choices.iter().position(|choice| choice == answer) {
+            return Some(index);
+        }
+    }
+}
+
+/// The world to look at: the only one, or the one the user picks
+fn choose_world(interactive: bool) -> Option<String> {
+    let mut worlds = WorldService::ids().unwrap_or_default();
+    if worlds.len() <= 1 || !interactive {
+        return (worlds.len() == 1).then(|| worlds.remove(0));
+    }
+    let choice = ask(&tr("troubleshoot-ask-world"), &worlds)?;
+    Some(worlds.remove(choice))
+}
+
+/// Whether Foundry answers on its port inside the container, ruling out the port mapping
+async fn check_port(port: u16) -> Check {
+    let name = tr_args("troubleshoot-port", &[("port", &port.to_string())]);
+    match status::fetch_server_status(port).await {
+        Ok(server) => {
+            let detail = match server.world.filter(|_| server.active) {
+                Some(world) => tr_args("troubleshoot-port-world", &[("world", &world)]),
+                None => tr("troubleshoot-port-setup"),
+            };
+            Check {
+                name,
+                ok: true,
+                detail,
+                hints: vec![tr_args(
+                    "troubleshoot-port-published",
+                    &[("port", &port.to_string())],
+                )],
+            }
+        }
+        Err(e) => {
+            let connected = timeout(
+                Duration::from_secs(5),
+                TcpStream::connect(("127.0.0.1", port)),
+            )
+            .await
+            .is_ok_and(|connected| connected.is_ok());
+            if !connected {
+                return Check {
+                    name,
+                    ok: false,
+                    detail: tr("troubleshoot-port-closed"),
+                    hints: vec![tr("troubleshoot-port-not-running")],
+                };
+            }
+            // Before a release is installed the installer serves the port
+            if installed_foundry_version().is_none() {
+                return Check {
+                    name,
+                    ok: true,
+                    detail: tr("troubleshoot-port-installer"),
+                    hints: Vec::new(),
+                };
+            }
+            Check {
+                name,
+                ok: false,
+                detail: tr_args(
+                    "troubleshoot-port-not-foundry",
+                    &[("error", &format!("{:#}", e))],
+                ),
+                hints: vec![tr("troubleshoot-port-starting")],
+            }
+        }
+    }
+}
+
+/// Whether the address players are given answers from inside the container
+async fn check_join_url(url: &str, port: u16) -> Check {
+    let name = tr_args("troubleshoot-join-url", &[("url", url)]);
+    let client = match reqwest::Client::builder()
+        .redirect(Policy::none())
+        .timeout(Duration::from_secs(10))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            return Check {
+                name,
+                ok: false,
+                detail: e.to_string(),
+                hints: Vec::new(),
+            };
+        }
+    };
+    match client.get(url).send().await {
+        Ok(response) if response.status().is_server_error() => Check {
+            name,
+            ok: false,
+            detail: tr_args(
+                "troubleshoot-join-status",
+                &[("status", response.status().as_str())],
+            ),
+            hints: vec![tr_args(
+                "troubleshoot-join-proxy",
+                &[("port", &port.to_string())],
+            )],
+        },
+        Ok(response) => Check {
+            name,
+            ok: true,
+            detail: tr_args(
+                "troubleshoot-join-status",
+                &[("status", response.status().as_str())],
+            ),
+            hints: Vec::new(),
+        },
+        Err(e) => Check {
+            name,
+            ok: false,
+            detail: format!("{:#}", anyhow::Error::from(e)),
+            hints: vec![tr("troubleshoot-join-unreachable")],
+        },
+    }
+}
+
+/// Manifest, system, version, lock and databases of `world`, and Foundry's recent errors
+fn check_world(world: &str, config: &AppConfig) -> Vec<Check> {
+    let dir = paths::WORLDS_DIR.join(world);
+    let manifest = match fs::read_to_string(dir.join("world.json"))
+        .map_err(anyhow::Error::from)
+        .and_then(|contents| Ok(serde_json::from_str::<Value>(&contents)?))
+    {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            return vec![Check {
+                name: tr_args("troubleshoot-world-manifest", &[("world", world)]),
+                ok: false,
+                detail: format!("{:#}", e),
+                hints: vec![tr_args("troubleshoot-world-restore", &[("world", world)])],
+            }];
+        }
+    };
+    let system = manifest["system"].as_str().unwrap_or_default();
+    let core = manifest["coreVersion"].as_str().unwrap_or_default();
+    let mut checks = vec![Check {
+        name: tr_args("troubleshoot-world-manifest", &[("world", world)]),
+        ok: true,
+        detail: tr_args(
+            "troubleshoot-world-manifest-ok",
+            &[
+                ("title", manifest["title"].as_str().unwrap_or(world)),
+                ("system", system),
+                ("core", core),
+            ],
+        ),
+        hints: Vec::new(),
+    }];
+
+    let installed = PathBuf::from(&*paths::DATA_DIR)
+        .join("Data")
+        .join("systems")
+        .join(system)
+        .join("system.json")
+        .exists();
+    checks.push(Check {
+        name: tr_args("troubleshoot-world-system", &[("system", system)]),
+        ok: installed,
+        detail: tr(if installed {
+            "troubleshoot-world-system-installed"
+        } else {
+            "troubleshoot-world-system-missing"
+        }),
+        hints: if installed {
+            Vec::new()
+        } else {
+            vec![tr_args(
+                "troubleshoot-world-install-system",
+                &[("system", system)],
+            )]
+        },
+    });
+
+    if let Some(installed) = installed_foundry_version() {
+        let (world_generation, installed_generation) = (generation(core), generation(&installed));
+        let args = [("core", core), ("installed", installed.as_str())];
+        checks.push(match world_generation.zip(installed_generation) {
+            Some((world_generation, installed_generation))
+                if world_generation > installed_generation =>
+            {
+                Check {
+                    name: tr("troubleshoot-world-version"),
+                    ok: false,
+                    detail: tr_args("troubleshoot-world-version-newer", &args),
+                    hints: vec![tr_args("troubleshoot-world-version-install", &args)],
+                }
+            }
+            Some((world_generation, installed_generation))
+                if world_generation < installed_generation =>
+            {
+                Check {
+                    name: tr("troubleshoot-world-version"),
+                    ok: true,
+                    detail: tr_args("troubleshoot-world-version-older", &args),
+                    hints: vec![tr("troubleshoot-world-version-migrate")],
+                }
+            }
+            _ => Check {
+                name: tr("troubleshoot-world-version"),
+                ok: true,
+                detail: tr_args("troubleshoot-world-version-ok", &args),
+                hints: Vec::new(),
+            },
+        });
+    }
+
+    if let Some((_, lock)) = WorldService::locks()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|(id, _)| id == world)
+    {
+        checks.push(Check {
+            name: tr("troubleshoot-world-lock"),
+            ok: true,
+            detail: tr_args("troubleshoot-world-locked", &[("since", &lock.locked_at)]),
+            hints: vec![tr_args("troubleshoot-world-unlock", &[("world", world)])],
+        });
+    }
+
+    let problems: Vec<String> = match IntegrityService::check_worlds(&config.scan_excludes) {
+        Ok(report) => report
+            .problems
+            .into_iter()
+            .filter(|problem| problem.starts_with(&*dir.to_string_lossy()))
+            .collect(),
+        Err(e) => vec![format!("{:#}", e)],
+    };
+    let mut hints = problems.clone();
+    if !problems.is_empty() {
+        hints.push(tr_args("troubleshoot-world-restore", &[("world", world)]));
+    }
+    checks.push(Check {
+        name: tr("troubleshoot-world-databases"),
+        ok: problems.is_empty(),
+        detail: tr_args(
+            "troubleshoot-world-problems",
+            &[("count", &problems.len().to_string())],
+        ),
+        hints,
+    });
+
+    let since = Utc::now() - chrono::Duration::hours(RECENT_ERRORS_HOURS);
+    let errors = logs::errors_since(since).unwrap_or_default();
+    let mut hints: Vec<String> = errors
+        .iter()
+        .rev()
+        .take(MAX_ERRORS)
+        .map(|error| error.lines().next().unwrap_or_default().to_string())
+        .collect();
+    if !errors.is_empty() {
+        hints.push(tr_args("troubleshoot-world-bisect", &[("world", world)]));
+    }
+    checks.push(Check {
+        name: tr("troubleshoot-world-log"),
+        ok: true,
+        detail: tr_args(
+            "troubleshoot-world-errors",
+            &[
+                ("count", &errors.len().to_string()),
+                ("hours", &RECENT_ERRORS_HOURS.to_string()),
+            ],
+        ),
+        hints,
+    });
+    checks
+}
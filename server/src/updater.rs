@@ -0,0 +1,443 @@
+use anyhow::{Context, Result, anyhow};
+use nix::sys::signal::{Signal, killpg};
+use nix::unistd::Pid;
+use reqwest::redirect::Policy;
+use std::cmp::Ordering;
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::time::{Duration, Instant, sleep, timeout};
+use tracing::{debug, info, warn};
+
+use crate::cache;
+use crate::config::AppConfig;
+use crate::hooks::{self, HookEvent};
+use crate::inhibit;
+use crate::journal::{Journal, Step};
+use crate::launch::foundry_command;
+use crate::notify::{self, Notification, Severity};
+use crate::reconcile;
+use crate::release::{PublisherMetadata, ReleaseDownload, ReleaseSource};
+use crate::resilience::{self, Service};
+use crate::state::WrapperState;
+use crate::utils::{compare_versions, installed_foundry_version, paths};
+
+const RELEASES_DIR: &str = "releases";
+pub const CURRENT_LINK: &str = "current";
+/// Where a release is downloaded to before its version is known
+const INCOMING_DIR: &str = ".incoming";
+/// Empty data directory the new release is booted with before it is switched to
+pub const PROBE_DIR: &str = ".update-probe";
+
+/// Keeps Foundry up to date with a release channel.
+///
+/// Releases are installed side by side in `APPLICATION_DIR/releases/<version>` and the
+/// `current` link points at the one in use. A new release is booted once on a spare port
+/// before the link is switched to it, a release that does not boot is never switched to and
+/// not tried again. The previous release is kept, so going back is a matter of the link.
+#[derive(Clone)]
+pub struct Updater {
+    /// Release channel, e.g. `stable` or `testing`
+    pub channel: String,
+    /// Answers the newest version of the channel, `{channel}` is replaced
+    pub check_url: String,
+    /// Username and password of the account releases are downloaded with
+    pub account: Option<(String, String)>,
+    /// Port the new release is booted on before it is switched to
+    pub probe_port: u16,
+    pub probe_timeout: Duration,
+    /// Size and checksum of the releases it downloads, `RELEASE_METADATA_URL`
+    pub metadata: Option<PublisherMetadata>,
+}
+
+impl Updater {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            channel: config.update_channel.clone(),
+            check_url: config.update_check_url.clone(),
+            account: config.foundry_account.clone(),
+            probe_port: config.smoke_test_port,
+            probe_timeout: Duration::from_secs(config.smoke_test_timeout),
+            metadata: config.release_metadata.clone(),
+        }
+    }
+
+    /// Account source of `version`
+    fn account_release(&self, version: &str) -> Result<ReleaseSource> {
+        let (username, password) = self.account.clone().ok_or_else(|| {
+            anyhow!(
+                "Downloading Foundry {} needs FOUNDRY_USERNAME and FOUNDRY_PASSWORD",
+                version
+            )
+        })?;
+        Ok(ReleaseSource::Account {
+            username,
+            password,
+            version: version.to_string(),
+        })
+    }
+
+    /// Install the newest release of the channel when it is newer than the installed one,
+    /// answering the version switched to
+    pub async fn update(&self) -> Result<Option<String>> {
+        if cache::offline() {
+            return Err(anyhow!("OFFLINE is set, no updates are looked for"));
+        }
+        let latest = self.latest_version().await?;
+        let installed = installed_foundry_version();
+        if installed
+            .as_deref()
+            .is_some_and(|installed| compare_versions(&latest, installed) != Ordering::Greater)
+        {
+            debug!("Foundry {} is the newest {} release", latest, self.channel);
+            return Ok(None);
+        }
+        if WrapperState::load()?.rejected_releases.contains(&latest) {
+            debug!(
+                "Foundry {} failed to boot before, not trying it again",
+                latest
+            );
+            return Ok(None);
+        }
+        info!(
+            "⬆️ Foundry {} is available on the {} channel (installed: {})",
+            latest,
+            self.channel,
+            installed.as_deref().unwrap_or("none")
+        );
+        self.install(self.account_release(&latest)?).await
+    }
+
+    /// Switch to `version`, downloading it unless it is kept below `releases` already, e.g. to
+    /// go back to the previous release
+    pub async fn install_version(&self, version: &str) -> Result<Option<String>> {
+        if installed_foundry_version().as_deref() == Some(version) {
+            return Ok(None);
+        }
+        if releases_dir()
+            .join(version)
+            .join("resources/app/main.js")
+            .exists()
+        {
+            let _inhibitor = inhibit::acquire(format!("switch to Foundry {}", version))?;
+            let previous = installed_foundry_version();
+            let mut journal = Journal::begin(format!("switch to Foundry {}", version))?;
+            let switched =
+                adopt_installation(&mut journal).and_then(|()| switch_to(version, &mut journal));
+            journal.complete(switched)?;
+            info!("✅ Switched to Foundry {}", version);
+            updated(version, previous).await;
+            return Ok(Some(version.to_string()));
+        }
+        self.install(self.account_release(version)?).await
+    }
+
+    /// Download the release of `source` next to the installed ones, boot it once and switch
+    /// to it. Answers `None` when it is installed already.
+    pub async fn install(&self, source: ReleaseSource) -> Result<Option<String>> {
+        let _inhibitor = inhibit::acquire("Foundry update")?;
+        let releases = releases_dir();
+        let incoming = releases.join(INCOMING_DIR);
+        remove_if_exists(&incoming)?;
+        fs::create_dir_all(&incoming)
+            .with_context(|| format!("Failed to create {}", incoming.display()))?;
+
+        let download = ReleaseDownload {
+            source,
+            // A pinned checksum belongs to FOUNDRY_VERSION, not to this release
+            sha256: None,
+            metadata: self.metadata.clone(),
+        };
+        let installed = download.install(&incoming.to_string_lossy()).await;
+        // Nothing is extracted for the version in use already
+        let version = match installed.and_then(|()| release_version(&incoming)) {
+            Ok(Some(version)) => version,
+            Ok(None) => {
+                remove_if_exists(&incoming)?;
+                return Ok(None);
+            }
+            Err(e) => {
+                let _ = remove_if_exists(&incoming);
+                return Err(e);
+            }
+        };
+        let release = releases.join(&version);
+        remove_if_exists(&release)?;
+        // Killed before the switch, the next start drops the release and keeps the installed one
+        let mut journal = Journal::begin(format!("update to Foundry {}", version))?;
+        journal.clean_up(&incoming)?;
+        if let Err(e) = journal.rename(&incoming, &release) {
+            return journal.complete(Err(e));
+        }
+
+        if let Err(e) = self.probe(&release).await {
+            let mut state = WrapperState::load()?;
+            state.rejected_releases.push(version.clone());
+            state.save()?;
+            journal.roll_back()?;
+            notify::send(Notification {
+                severity: Severity::Warning,
+                title: "Foundry update rolled back".to_string(),
+                message: format!(
+                    "Foundry {} does not boot, staying on {}: {:#}",
+                    version,
+                    installed_foundry_version()
+                        .as_deref()
+                        .unwrap_or("the installed release"),
+                    e
+                ),
+            });
+            return Err(e.context(format!("Foundry {} does not boot", version)));
+        }
+
+        let previous = installed_foundry_version();
+        let switched =
+            adopt_installation(&mut journal).and_then(|()| switch_to(&version, &mut journal));
+        journal.complete(switched)?;
+        prune(&version);
+        info!("✅ Switched to Foundry {}", version);
+        notify::send(Notification {
+            severity: Severity::Info,
+            title: "Foundry update installed".to_string(),
+            message: format!("Switched to Foundry {}.", version),
+        });
+        updated(&version, previous).await;
+        Ok(Some(version))
+    }
+
+    async fn latest_version(&self) -> Result<String> {
+        let url = self.check_url.replace("{channel}", &self.channel);
+        let client = reqwest::Client::new();
+        let body = resilience::call(Service::Updates, || async {
+            Ok::<_, anyhow::Error>(
+                client
+                    .get(&url)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .with_context(|| format!("Failed to check {} for updates", url))?
+                    .text()
+                    .await?,
+            )
+        })
+        .await?;
+        // Either `{"version": "13.345"}` or the bare version
+        let version = match serde_json::from_str::<serde_json::Value>(&body) {
+            Ok(serde_json::Value::Object(answer)) => answer
+                .get("version")
+                .and_then(|version| version.as_str())
+                .map(str::to_string),
+            _ => Some(body.trim().to_string()),
+        };
+        version
+            .filter(|version| {
+                version.contains('.') && version.chars().all(|c| c.is_ascii_digit() || c == '.')
+            })
+            .ok_or_else(|| anyhow!("{} answered no version like 13.345", url))
+    }
+
+    /// Boot `release` with an empty data directory and wait until it serves its first page
+    async fn probe(&self, release: &Path) -> Result<()> {
+        let data_dir = PathBuf::from(&*paths::DATA_DIR).join(PROBE_DIR);
+        remove_if_exists(&data_dir)?;
+        fs::create_dir_all(&data_dir)?;
+        let script = release.join("resources").join("app").join("main.js");
+        let args = [
+            format!("--dataPath={}", data_dir.display()),
+            format!("--port={}", self.probe_port),
+            "--noupnp".to_string(),
+        ];
+        let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+        info!(
+            "🧪 Booting the new release on port {} (timeout {:?})",
+            self.probe_port, self.probe_timeout
+        );
+        // Own process group, so node started by npx is stopped together with it
+        let mut child = foundry_command(&script.to_string_lossy(), &args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to spawn the new release")?;
+
+        let client = reqwest::Client::builder()
+            .redirect(Policy::none())
+            .timeout(Duration::from_secs(5))
+            .build()?;
+        let url = format!("http://127.0.0.1:{}/", self.probe_port);
+        let deadline = Instant::now() + self.probe_timeout;
+        let booted = loop {
+            if let Some(status) = child.try_wait()? {
+                break Err(anyhow!("It exited during startup with {}", status));
+            }
+            if Instant::now() >= deadline {
+                break Err(anyhow!("It did not answer within {:?}", self.probe_timeout));
+            }
+            // Any answer means the server is up, without a license it shows the license page
+            if client.get(&url).send().await.is_ok() {
+                break Ok(());
+            }
+            sleep(Duration::from_secs(1)).await;
+        };
+
+        if let Some(pid) = child.id() {
+            let _ = killpg(Pid::from_raw(pid as i32), Signal::SIGTERM);
+        }
+        if timeout(Duration::from_secs(10), child.wait())
+            .await
+            .is_err()
+        {
+            warn!("The new release did not stop, killing it");
+            let _ = child.kill().await;
+        }
+        if let Err(e) = remove_if_exists(&data_dir) {
+            warn!("{:#}", e);
+        }
+        booted
+    }
+}
+
+/// Whether releases are installed side by side, installs must then go through the updater
+pub fn side_by_side() -> bool {
+    PathBuf::from(&*paths::APPLICATION_DIR)
+        .join(CURRENT_LINK)
+        .exists()
+}
+
+fn releases_dir() -> PathBuf {
+    PathBuf::from(&*paths::APPLICATION_DIR).join(RELEASES_DIR)
+}
+
+/// Version of the release extracted to `dir`, `None` when nothing was extracted
+fn release_version(dir: &Path) -> Result<Option<String>> {
+    let manifest = dir.join("resources").join("app").join("package.json");
+    let contents = match fs::read_to_string(&manifest) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", manifest.display())),
+    };
+    let manifest: serde_json::Value = serde_json::from_str(&contents)?;
+    manifest["version"]
+        .as_str()
+        .map(|version| Some(version.to_string()))
+        .ok_or_else(|| anyhow!("The release has no version"))
+}
+
+/// Move a release installed directly into `APPLICATION_DIR` below `releases`, so it stays
+/// available to go back to
+fn adopt_installation(journal: &mut Journal) -> Result<()> {
+    let app_dir = PathBuf::from(&*paths::APPLICATION_DIR);
+    if app_dir.join(CURRENT_LINK).exists() || !app_dir.join("resources").exists() {
+        return Ok(());
+    }
+    let version = installed_foundry_version().unwrap_or_else(|| "previous".to_string());
+    let target = releases_dir().join(&version);
+    info!(
+        "Moving the installed Foundry {} to {}",
+        version,
+        target.display()
+    );
+    fs::create_dir_all(&target)?;
+    for entry in fs::read_dir(&app_dir)?.flatten() {
+        let name = entry.file_name();
+        if name == RELEASES_DIR || name == CURRENT_LINK {
+            continue;
+        }
+        journal
+            .rename(&entry.path(), &target.join(&name))
+            .with_context(|| format!("Failed to move {}", entry.path().display()))?;
+    }
+    switch_to(&version, journal)
+}
+
+/// Move the release `current` points at to the directory of `version`, after Foundry updated
+/// it in place
+pub fn rename_current(version: &str) -> Result<()> {
+    let app_dir = PathBuf::from(&*paths::APPLICATION_DIR);
+    let linked = fs::read_link(app_dir.join(CURRENT_LINK))
+        .with_context(|| format!("Failed to read the {} link", CURRENT_LINK))?;
+    let Some(name) = linked.file_name() else {
+        return Ok(());
+    };
+    if name == version {
+        return Ok(());
+    }
+    let target = releases_dir().join(version);
+    if target.exists() {
+        warn!(
+            "⚠️ Foundry updated {} in place to {}, which is installed already, keeping both",
+            name.to_string_lossy(),
+            version
+        );
+        return Ok(());
+    }
+    info!(
+        "Foundry updated release {} in place, moving it to {}",
+        name.to_string_lossy(),
+        target.display()
+    );
+    let mut journal = Journal::begin(format!("move of release {}", name.to_string_lossy()))?;
+    let moved = journal
+        .rename(&releases_dir().join(name), &target)
+        .with_context(|| format!("Failed to move the release to {}", target.display()))
+        .and_then(|()| switch_to(version, &mut journal));
+    journal.complete(moved)
+}
+
+/// Point the `current` link at `version`, replacing the old link in one rename
+fn switch_to(version: &str, journal: &mut Journal) -> Result<()> {
+    let app_dir = PathBuf::from(&*paths::APPLICATION_DIR);
+    let staged = app_dir.join(format!(".{}-next", CURRENT_LINK));
+    let _ = fs::remove_file(&staged);
+    // Relative, the link keeps working wherever the volume is mounted
+    symlink(Path::new(RELEASES_DIR).join(version), &staged)
+        .with_context(|| format!("Failed to link {}", staged.display()))?;
+    let link = app_dir.join(CURRENT_LINK);
+    journal.record(Step::Relink {
+        link: link.clone(),
+        previous: fs::read_link(&link).ok(),
+    })?;
+    fs::rename(&staged, &link)
+        .with_context(|| format!("Failed to switch to Foundry {}", version))?;
+    reconcile::record();
+    Ok(())
+}
+
+/// Remove every release but `current` and the one before it
+fn prune(current: &str) {
+    let Ok(entries) = fs::read_dir(releases_dir()) else {
+        return;
+    };
+    let mut versions: Vec<String> = entries
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| !name.starts_with('.') && name != current)
+        .collect();
+    versions.sort_by(|a, b| compare_versions(b, a));
+    for old in versions.iter().skip(1) {
+        let dir = releases_dir().join(old);
+        match fs::remove_dir_all(&dir) {
+            Ok(()) => info!("🗑️ Removed Foundry {}", old),
+            Err(e) => warn!("Failed to remove {}: {}", dir.display(), e),
+        }
+    }
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_dir_all(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Run the `post-update` hooks, the switch is done even when one fails
+async fn updated(version: &str, previous: Option<String>) {
+    let payload = serde_json::json!({ "version": version, "previous": previous });
+    if let Err(e) = hooks::run(HookEvent::PostUpdate, payload).await {
+        warn!("⚠️ {:#}", e);
+    }
+}
@@ -0,0 +1,281 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::Utc;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+use crate::config::AppConfig;
+use crate::journal::Journal;
+use crate::notify::{self, Notification, Severity};
+use crate::packages::{describe_changes, installed_packages, read_manifest_version};
+use crate::permissions;
+use crate::smoke::{SmokeTest, SmokeTestOutcome};
+use crate::state::WrapperState;
+use crate::updater::Updater;
+use crate::utils::paths;
+use crate::worlds::WorldService;
+
+pub struct UpgradeVerifier;
+
+impl UpgradeVerifier {
+    /// Smoke test the configured world when core, system or module versions changed since the
+    /// last verified boot, returning whether the installed packages are considered good.
+    ///
+    /// The new versions are only recorded as verified when the smoke test passes. A failed
+    /// upgrade is rolled back to the release and packages of the last verified boot, answering
+    /// whether that worked completely.
+    pub async fn verify_installed_packages(config: &AppConfig) -> Result<bool> {
+        let mut state = WrapperState::load()?;
+        let current = installed_packages();
+
+        if state.verified_packages.is_empty() {
+            info!("Recording installed package versions as the verified baseline");
+            state.verified_packages = current;
+            state.save()?;
+            return Ok(true);
+        }
+
+        let changes = describe_changes(&state.verified_packages, &current);
+        if changes.is_empty() {
+            return Ok(true);
+        }
+        info!("Installed packages changed since the last verified boot:");
+        for change in &changes {
+            info!("  - {}", change);
+        }
+
+        let Some(world) = &config.smoke_test_world else {
+            info!("SMOKE_TEST_WORLD not set, accepting the changes without a smoke test");
+            state.verified_packages = current;
+            state.save()?;
+            return Ok(true);
+        };
+
+        let outcome = if config.smoke_test_canary {
+            Self::smoke_test_canary(config, world).await?
+        } else {
+            Self::smoke_test(config, world)
+                .run(&config.foundry_script)
+                .await?
+        };
+
+        if outcome.passed {
+            info!(
+                "✅ Upgrade verified, world {} loads with the new versions",
+                world
+            );
+            state.verified_packages = current;
+            state.save()?;
+            // The kept versions are no longer the ones to go back to
+            remove_if_exists(&rollback_dir())?;
+            return Ok(true);
+        }
+
+        warn!(
+            "⚠️ World {} no longer loads after the upgrade: {}",
+            world, outcome.reason
+        );
+        for line in &outcome.errors {
+            warn!("  {}", line);
+        }
+        let missing = Self::roll_back(config, &current).await?;
+        let message = if missing.is_empty() {
+            format!(
+                "World {} no longer loads after the upgrade ({}), rolled back to the release and packages of the last verified boot: {}",
+                world,
+                outcome.reason,
+                changes.join(", ")
+            )
+        } else {
+            format!(
+                "World {} no longer loads after the upgrade ({}), these could not be put back: {}",
+                world,
+                outcome.reason,
+                missing.join(", ")
+            )
+        };
+        notify::send(Notification {
+            severity: Severity::Warning,
+            title: "Upgrade rolled back".to_string(),
+            message,
+        });
+        Ok(missing.is_empty())
+    }
+
+    /// Put back the release and the packages of the last verified boot, answering those that
+    /// could not be. The rejected versions are moved to `package-rollback/rejected` and not
+    /// installed again.
+    async fn roll_back(
+        config: &AppConfig,
+        current: &BTreeMap<String, String>,
+    ) -> Result<Vec<String>> {
+        let verified = WrapperState::load()?.verified_packages;
+        let mut missing = Vec::new();
+
+        if let Some(core) = verified.get("core")
+            && current.get("core") != Some(core)
+        {
+            match Updater::from_config(config).install_version(core).await {
+                Ok(_) => info!("↩️ Switched back to Foundry {}", core),
+                Err(e) => {
+                    warn!("⚠️ Could not switch back to Foundry {}: {:#}", core, e);
+                    missing.push(format!("core {}", core));
+                }
+            }
+        }
+
+        // Loaded after the switch, which records the release it switched to
+        let mut state = WrapperState::load()?;
+        if let Some(rejected) = current.get("core")
+            && verified.get("core") != Some(rejected)
+            && !state.rejected_releases.contains(rejected)
+        {
+            // The updater does not install it again
+            state.rejected_releases.push(rejected.clone());
+        }
+
+        let mut journal = Journal::begin("rollback of a failed upgrade")?;
+        let moved = (|| {
+            let keys: BTreeSet<&String> = verified.keys().chain(current.keys()).collect();
+            for key in keys.into_iter().filter(|key| *key != "core") {
+                let (before, after) = (verified.get(key), current.get(key));
+                let Some((kind, id)) = key.split_once(':').filter(|_| before != after) else {
+                    continue;
+                };
+                let installed = PathBuf::from(&*paths::DATA_DIR)
+                    .join("Data")
+                    .join(format!("{}s", kind))
+                    .join(id);
+                let previous = package_dir("previous", kind, id);
+                let restorable = before.is_some_and(|before| {
+                    read_manifest_version(&previous.join(format!("{}.json", kind))).as_ref()
+                        == Some(before)
+                });
+                if let Some(before) = before
+                    && !restorable
+                {
+                    // Better the new version than none at all
+                    missing.push(format!("{} {}", key, before));
+                    continue;
+                }
+                if let Some(after) = after {
+                    let rejected = package_dir("rejected", kind, id);
+                    remove_if_exists(&rejected)?;
+                    if let Some(parent) = rejected.parent() {
+                        permissions::create_dir_all(parent)?;
+                    }
+                    journal.rename(&installed, &rejected)?;
+                    state.rejected_packages.insert(key.clone(), after.clone());
+                }
+                if restorable {
+                    journal.rename(&previous, &installed)?;
+                }
+                info!(
+                    "↩️ Rolled {} back to {}",
+                    key,
+                    before.map(String::as_str).unwrap_or("not installed")
+                );
+            }
+            anyhow::Ok(())
+        })();
+        journal.complete(moved)?;
+        state.save()?;
+        Ok(missing)
+    }
+
+    /// Smoke test a fresh clone of `world`, the clone is removed again afterwards. Its id is
+    /// recorded first, so only a clone the wrapper made is ever removed.
+    async fn smoke_test_canary(config: &AppConfig, world: &str) -> Result<SmokeTestOutcome> {
+        let mut state = WrapperState::load()?;
+        if let Some(leftover) = state.canary_world.take() {
+            remove_canary(&leftover)?;
+            state.save()?;
+        }
+
+        let canary = format!("{}-canary-{}", world, Utc::now().timestamp());
+        if paths::WORLDS_DIR.join(&canary).exists() {
+            return Err(anyhow!(
+                "World {} already exists, it is not used as the canary",
+                canary
+            ));
+        }
+        state.canary_world = Some(canary.clone());
+        state.save()?;
+        WorldService::duplicate(world, &canary, &format!("{} (canary)", world))?;
+
+        let outcome = Self::smoke_test(config, &canary)
+            .run(&config.foundry_script)
+            .await;
+        match remove_canary(&canary) {
+            Ok(()) => {
+                let mut state = WrapperState::load()?;
+                state.canary_world = None;
+                state.save()?;
+            }
+            Err(e) => warn!("Failed to remove canary world {}: {:#}", canary, e),
+        }
+        outcome
+    }
+
+    fn smoke_test(config: &AppConfig, world: &str) -> SmokeTest {
+        SmokeTest {
+            world: world.to_string(),
+            data_dir: paths::DATA_DIR.clone(),
+            port: config.smoke_test_port,
+            timeout: Duration::from_secs(config.smoke_test_timeout),
+            background: false,
+        }
+    }
+}
+
+/// Remove the canary world `id` the wrapper recorded, unless it is already gone
+fn remove_canary(id: &str) -> Result<()> {
+    if !paths::WORLDS_DIR.join(id).exists() {
+        return Ok(());
+    }
+    WorldService::remove(id)
+}
+
+/// Keep the installed package `dir` of `kind` for a rollback instead of removing it, when it
+/// is the version of the last verified boot. Answers whether it was moved away.
+pub fn keep_previous(kind: &str, dir: &Path) -> Result<bool> {
+    let Some(id) = dir.file_name().map(|id| id.to_string_lossy().to_string()) else {
+        return Ok(false);
+    };
+    let verified = WrapperState::load()?
+        .verified_packages
+        .remove(&format!("{}:{}", kind, id));
+    let installed = read_manifest_version(&dir.join(format!("{}.json", kind)));
+    if verified.is_none() || verified != installed {
+        return Ok(false);
+    }
+    let previous = package_dir("previous", kind, id.as_str());
+    remove_if_exists(&previous)?;
+    if let Some(parent) = previous.parent() {
+        permissions::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::rename(dir, &previous)
+        .with_context(|| format!("Failed to move {} aside", dir.display()))?;
+    Ok(true)
+}
+
+/// Packages replaced since the last verified boot and those a rollback set aside
+fn rollback_dir() -> PathBuf {
+    paths::WRAPPER_STATE_DIR.join("package-rollback")
+}
+
+fn package_dir(set: &str, kind: &str, id: &str) -> PathBuf {
+    rollback_dir().join(set).join(format!("{}s", kind)).join(id)
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_dir_all(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
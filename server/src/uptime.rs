@@ -0,0 +1,248 @@
+use actix_web::{HttpResponse, Responder};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use tokio::time::{Duration, interval};
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+
+use crate::permissions::{self, FileKind};
+use crate::status;
+use crate::utils::paths;
+
+/// Transitions older than this are dropped, the longest window reported
+const RETENTION_SECS: i64 = 30 * 24 * 3600;
+/// How often `last_seen` is written while nothing changes
+const SAVE_INTERVAL_SECS: i64 = 300;
+/// Outages listed in the report, newest first
+const OUTAGES_SHOWN: usize = 10;
+
+/// Up and down transitions of Foundry, kept in `DATA_DIR/.wrapper/uptime.json`.
+///
+/// Uses the wall clock, the history has to survive restarts of the container.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct UptimeHistory {
+    transitions: Vec<Transition>,
+    /// Last time the wrapper saw Foundry, the container being down counts as downtime
+    last_seen: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Transition {
+    /// Unix seconds
+    at: i64,
+    up: bool,
+}
+
+/// Uptime over the last 24 hours, 7 and 30 days and the latest outages
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct UptimeReport {
+    pub up: Option<bool>,
+    #[schema(format = DateTime)]
+    pub since: Option<String>,
+    /// Percentages of the time the wrapper has any record of, `None` without one
+    pub uptime_24h: Option<f64>,
+    pub uptime_7d: Option<f64>,
+    pub uptime_30d: Option<f64>,
+    pub outages: Vec<Outage>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Outage {
+    #[schema(format = DateTime)]
+    pub start: String,
+    /// Missing while Foundry is still down
+    #[schema(format = DateTime)]
+    pub end: Option<String>,
+    pub minutes: i64,
+}
+
+static HISTORY: Mutex<Option<UptimeHistory>> = Mutex::new(None);
+
+/// Lock the history, loading it from disk on first use
+fn lock_history() -> MutexGuard<'static, Option<UptimeHistory>> {
+    let mut history = HISTORY.lock().unwrap_or_else(PoisonError::into_inner);
+    if history.is_none() {
+        *history = Some(load().unwrap_or_else(|e| {
+            warn!("Starting a new uptime history: {}", e);
+            UptimeHistory::default()
+        }));
+    }
+    history
+}
+
+/// Watches whether Foundry answers and records every change for the uptime report
+#[derive(Clone)]
+pub struct UptimeMonitor {
+    pub foundry_port: u16,
+    pub interval: Duration,
+}
+
+impl UptimeMonitor {
+    pub async fn run(self) {
+        let mut ticker = interval(self.interval);
+        let mut last_saved = 0;
+        let mut first = true;
+        loop {
+            ticker.tick().await;
+            // The installer waiting for a release is no downtime
+            let current = status::current();
+            if current.installing {
+                continue;
+            }
+            let up =
+                current.running && status::fetch_server_status(self.foundry_port).await.is_ok();
+            let now = Utc::now().timestamp();
+
+            let mut guard = lock_history();
+            let Some(history) = guard.as_mut() else {
+                continue;
+            };
+            let mut changed = false;
+            if first {
+                // Foundry was up when the container stopped, it was down since
+                if let (Some(last), Some(seen)) = (history.transitions.last(), history.last_seen)
+                    && last.up
+                    && seen < now
+                {
+                    history.transitions.push(Transition {
+                        at: seen,
+                        up: false,
+                    });
+                    changed = true;
+                }
+                first = false;
+            }
+            if history.transitions.last().is_none_or(|last| last.up != up) {
+                debug!("Foundry is {} now", if up { "up" } else { "down" });
+                history.transitions.push(Transition { at: now, up });
+                changed = true;
+            }
+            history.last_seen = Some(now);
+            prune(history, now);
+
+            if changed || now - last_saved >= SAVE_INTERVAL_SECS {
+                match save(history) {
+                    Ok(()) => last_saved = now,
+                    Err(e) => warn!("Failed to save the uptime history: {}", e),
+                }
+            }
+        }
+    }
+}
+
+/// The uptime report as of now
+pub fn report() -> UptimeReport {
+    let guard = lock_history();
+    let Some(history) = guard.as_ref() else {
+        return UptimeReport::default();
+    };
+    let now = Utc::now().timestamp();
+    let last = history.transitions.last();
+
+    let mut outages: Vec<Outage> = history
+        .transitions
+        .iter()
+        .enumerate()
+        .filter(|(_, transition)| !transition.up)
+        .map(|(i, down)| {
+            let end = history.transitions.get(i + 1).map(|next| next.at);
+            Outage {
+                start: format_time(down.at),
+                end: end.map(format_time),
+                minutes: (end.unwrap_or(now) - down.at) / 60,
+            }
+        })
+        .collect();
+    outages.reverse();
+    outages.truncate(OUTAGES_SHOWN);
+
+    UptimeReport {
+        up: last.map(|t| t.up),
+        since: last.map(|t| format_time(t.at)),
+        uptime_24h: uptime(&history.transitions, now - 24 * 3600, now),
+        uptime_7d: uptime(&history.transitions, now - 7 * 24 * 3600, now),
+        uptime_30d: uptime(&history.transitions, now - RETENTION_SECS, now),
+        outages,
+    }
+}
+
+/// Uptime of the last 24 hours, 7 and 30 days and the latest outages
+#[utoipa::path(
+    get,
+    path = "/uptime",
+    operation_id = "uptime",
+    responses((status = 200, description = "Uptime report", body = UptimeReport))
+)]
+pub async fn show() -> impl Responder {
+    HttpResponse::Ok().json(report())
+}
+
+/// Share of `[from, to)` Foundry was up, counting only time covered by the history
+fn uptime(transitions: &[Transition], from: i64, to: i64) -> Option<f64> {
+    let (mut known, mut up) = (0, 0);
+    for (i, transition) in transitions.iter().enumerate() {
+        let end = transitions.get(i + 1).map_or(to, |next| next.at).min(to);
+        let start = transition.at.max(from);
+        if end > start {
+            known += end - start;
+            if transition.up {
+                up += end - start;
+            }
+        }
+    }
+    // Three decimals tell 99.999% from 99.99%, the rest is noise
+    (known > 0).then(|| (up as f64 * 100_000.0 / known as f64).round() / 1000.0)
+}
+
+/// Drop transitions before the retention window, keeping the one telling the state at its start
+fn prune(history: &mut UptimeHistory, now: i64) {
+    let cutoff = now - RETENTION_SECS;
+    let outdated = history
+        .transitions
+        .iter()
+        .take_while(|transition| transition.at < cutoff)
+        .count();
+    if outdated > 1 {
+        history.transitions.drain(..outdated - 1);
+    }
+}
+
+fn format_time(at: i64) -> String {
+    DateTime::<Utc>::from_timestamp(at, 0)
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_default()
+}
+
+fn path() -> PathBuf {
+    paths::WRAPPER_STATE_DIR.join("uptime.json")
+}
+
+fn load() -> Result<UptimeHistory> {
+    let path = path();
+    if !path.exists() {
+        return Ok(UptimeHistory::default());
+    }
+    let contents =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&contents).with_context(|| format!("Invalid {}", path.display()))
+}
+
+/// Write atomically like the wrapper state, a crash must not lose the history
+fn save(history: &UptimeHistory) -> Result<()> {
+    let path = path();
+    permissions::create_dir_all(&paths::WRAPPER_STATE_DIR)
+        .with_context(|| format!("Failed to create {}", paths::WRAPPER_STATE_DIR.display()))?;
+    let tmp_path = path.with_extension("json.tmp");
+    permissions::write(
+        &tmp_path,
+        serde_json::to_string(history)?,
+        FileKind::Regular,
+    )
+    .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, &path).with_context(|| format!("Failed to replace {}", path.display()))
+}
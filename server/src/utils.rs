@@ -1,52 +1,119 @@
-use anyhow::{Context, Result};
-use lazy_static::lazy_static;
+use std::cmp::Ordering;
 use std::env;
 use std::path::PathBuf;
-use std::process::{Command, Output};
-use tracing::debug;
 
 pub mod paths {
     use super::*;
+    use crate::configfile::{self, Settings};
+    use std::ops::Deref;
 
-    lazy_static! {
-        /// Base application directory where the Foundry VTT application is installed
-        pub static ref APPLICATION_DIR: String = env::var("APPLICATION_DIR")
-            .unwrap_or_else(|_| "/foundryvtt".to_string());
-
-        /// Data directory for user data
-        pub static ref DATA_DIR: String = env::var("DATA_DIR")
-            .unwrap_or_else(|_| "/foundrydata".to_string());
-
-        /// Path to the main Foundry script
-        pub static ref FOUNDRY_SCRIPT_PATH: PathBuf = {
-            let mut path = PathBuf::from(&*APPLICATION_DIR);
-            path.push("resources");
-            path.push("app");
-            path.push("main.js");
-            path
-        };
+    /// A directory of the typed `Settings`, read where it is used like the value itself
+    pub struct SettingsPath<T: 'static>(fn(&'static Settings) -> &'static T);
+
+    impl<T> Deref for SettingsPath<T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            (self.0)(configfile::settings())
+        }
     }
-}
 
-/// Run a system command and return its output
-pub fn run_command(command: &str, args: &[&str]) -> Result<String> {
-    debug!("Running command: {} {:?}", command, args);
-
-    let output: Output = Command::new(command)
-        .args(args)
-        .output()
-        .with_context(|| format!("Failed to execute command: {} {:?}", command, args))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        debug!(
-            "Command failed with status code {:?}: {}",
-            output.status.code(),
-            stderr.trim()
-        );
+    /// Base application directory where the Foundry VTT application is installed
+    pub static APPLICATION_DIR: SettingsPath<String> = SettingsPath(|s| &s.application_dir);
+
+    /// Data directory for user data
+    pub static DATA_DIR: SettingsPath<String> = SettingsPath(|s| &s.data_dir);
+
+    /// Directory for backups and exports, kept outside the data volume by default
+    pub static BACKUP_DIR: SettingsPath<String> = SettingsPath(|s| &s.backup_dir);
+
+    /// Directory containing all worlds of the data volume
+    pub static WORLDS_DIR: SettingsPath<PathBuf> = SettingsPath(|s| &s.worlds_dir);
+
+    /// Diagnostic reports, heap snapshots and profiles of the Foundry process
+    pub static DIAGNOSTICS_DIR: SettingsPath<PathBuf> = SettingsPath(|s| &s.diagnostics_dir);
+
+    /// Downloaded release and package archives, kept for the next install when set
+    pub static CACHE_DIR: SettingsPath<Option<PathBuf>> = SettingsPath(|s| &s.cache_dir);
+
+    /// Data directories of the `INSTANCES`, one per name
+    pub static INSTANCES_DIR: SettingsPath<PathBuf> = SettingsPath(|s| &s.instances_dir);
+
+    /// Lifecycle hook scripts, one directory per event like `pre-start.d`
+    pub static HOOKS_DIR: SettingsPath<PathBuf> = SettingsPath(|s| &s.hooks_dir);
+
+    /// Directory inside the data volume where the wrapper keeps its own state
+    pub static WRAPPER_STATE_DIR: SettingsPath<PathBuf> = SettingsPath(|s| &s.wrapper_state_dir);
+
+    /// Directory of the Foundry release in use: the `current` link once releases are installed
+    /// side by side below `APPLICATION_DIR/releases`, `APPLICATION_DIR` itself before that
+    pub fn foundry_app_dir() -> PathBuf {
+        let current = PathBuf::from(&*APPLICATION_DIR).join("current");
+        if current.exists() {
+            current
+        } else {
+            PathBuf::from(&*APPLICATION_DIR)
+        }
+    }
+
+    /// Path to the main Foundry script of the release in use
+    pub fn resolve_foundry_script_path() -> PathBuf {
+        foundry_app_dir()
+            .join("resources")
+            .join("app")
+            .join("main.js")
     }
+}
+
+/// Read the version of the installed Foundry release from its package.json
+pub fn installed_foundry_version() -> Option<String> {
+    let package_json = paths::foundry_app_dir()
+        .join("resources")
+        .join("app")
+        .join("package.json");
+    let contents = std::fs::read_to_string(package_json).ok()?;
+    let manifest: serde_json::Value = serde_json::from_str(&contents).ok()?;
+    manifest["version"].as_str().map(|v| v.to_string())
+}
+
+/// Name of the container, `unknown` where the kernel does not tell
+pub fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|name| name.trim().to_string())
+        .ok()
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Compare dotted versions like `12.331` by their numeric parts
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |version: &str| -> Vec<u64> {
+        version
+            .split('.')
+            .map(|part| {
+                part.chars()
+                    .take_while(char::is_ascii_digit)
+                    .collect::<String>()
+                    .parse()
+                    .unwrap_or(0)
+            })
+            .collect()
+    };
+    parts(a).cmp(&parts(b))
+}
+
+/// Path of `name` in `PATH`, images without a shell or coreutils often lack the usual tools
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
 
-    // Return stdout as string
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-    Ok(stdout)
+    env::var_os("PATH")
+        .map(|path| env::split_paths(&path).collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|dir| dir.join(name))
+        .find(|candidate| {
+            candidate
+                .metadata()
+                .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        })
 }
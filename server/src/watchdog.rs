@@ -0,0 +1,197 @@
+use anyhow::{Context, Result};
+use nix::sys::signal::{Signal, killpg};
+use nix::unistd::Pid;
+use reqwest::redirect::Policy;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use tokio::time::{Duration, sleep, timeout};
+use tracing::{debug, error, info, warn};
+
+use crate::configfile;
+use crate::gamesocket;
+use crate::memory;
+use crate::permissions;
+use crate::status;
+use crate::utils::paths;
+
+/// Node options making every node process of the group write a diagnostic report on SIGUSR2
+pub fn diagnostic_node_options() -> String {
+    let mut options = configfile::var("NODE_OPTIONS").unwrap_or_default();
+    if let Some(heap) = memory::heap_option() {
+        options.push(' ');
+        options.push_str(&heap);
+    }
+    options.push_str(&format!(
+        " --report-on-signal --report-directory={}",
+        paths::DIAGNOSTICS_DIR.display()
+    ));
+    options.trim().to_string()
+}
+
+/// Detects a Foundry process that never gets to answering HTTP requests, and tells when its
+/// startup completed
+pub struct StartupWatchdog {
+    pub port: u16,
+    /// No startup is treated as hung without one
+    pub timeout: Option<Duration>,
+}
+
+impl StartupWatchdog {
+    /// Resolve once the startup timeout passed without Foundry answering, never if it does
+    pub async fn wait_for_hang(&self) {
+        if let Some(limit) = self.timeout
+            && timeout(limit, self.wait_for_startup()).await.is_err()
+        {
+            return;
+        }
+        std::future::pending::<()>().await
+    }
+
+    /// Resolve once Foundry answers status requests, the point its startup counts as complete
+    pub async fn wait_for_startup(&self) {
+        while status::fetch_server_status(self.port).await.is_err() {
+            sleep(Duration::from_secs(2)).await;
+        }
+        debug!("Foundry answered, startup completed");
+    }
+}
+
+/// Detects a Foundry process that is alive but stopped answering, e.g. with a stalled event
+/// loop. Every `interval` the join page is requested and the socket is pinged, both must
+/// answer within `CHECK_TIMEOUT`.
+pub struct ResponsivenessWatchdog {
+    pub port: u16,
+    pub interval: Duration,
+    /// Failed checks in a row that count as unresponsive
+    pub failures: u32,
+}
+
+/// How long each request of a responsiveness check may take
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl ResponsivenessWatchdog {
+    /// Resolve once `failures` checks in a row failed, never while Foundry answers. Checks
+    /// start once Foundry answered once, the startup is `StartupWatchdog`'s.
+    pub async fn wait_for_stall(&self) {
+        while self.check().await.is_err() {
+            sleep(Duration::from_secs(2)).await;
+        }
+        let mut failed = 0;
+        loop {
+            sleep(self.interval).await;
+            match self.check().await {
+                Ok(()) => failed = 0,
+                Err(e) => {
+                    failed += 1;
+                    warn!(
+                        "⚠️ Foundry did not answer the responsiveness check ({} of {}): {:#}",
+                        failed, self.failures, e
+                    );
+                    if failed >= self.failures {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn check(&self) -> Result<()> {
+        let client = reqwest::Client::builder()
+            .timeout(CHECK_TIMEOUT)
+            .redirect(Policy::none())
+            .build()?;
+        // Answered by the world, or redirected to the setup screen without one
+        client
+            .get(format!("http://127.0.0.1:{}/join", self.port))
+            .send()
+            .await
+            .context("The join page did not answer")?;
+        gamesocket::ping(self.port, CHECK_TIMEOUT).await
+    }
+}
+
+/// Ask all node processes of the group for a diagnostic report and log the Foundry stack
+pub async fn capture_diagnostic_report(process_group: u32) {
+    match request_report(process_group).await {
+        Ok(Some(path)) => log_report(&path),
+        Ok(None) => warn!("Foundry did not write a diagnostic report"),
+        Err(e) => warn!("Failed to capture a diagnostic report: {}", e),
+    }
+}
+
+async fn request_report(process_group: u32) -> Result<Option<PathBuf>> {
+    permissions::create_dir_all(&paths::DIAGNOSTICS_DIR)
+        .with_context(|| format!("Failed to create {}", paths::DIAGNOSTICS_DIR.display()))?;
+    // Compare against the reports already there, mtimes are useless when the clock jumps
+    let existing = report_files()?;
+    killpg(Pid::from_raw(process_group as i32), Signal::SIGUSR2)
+        .context("Failed to signal the Foundry process group")?;
+
+    // Reports are written from the event loop, a hung loop may take a moment
+    for _ in 0..10 {
+        sleep(Duration::from_secs(1)).await;
+        if let Some(path) = new_foundry_report(&existing)? {
+            return Ok(Some(path));
+        }
+    }
+    Ok(None)
+}
+
+/// JSON reports currently in the diagnostics directory
+fn report_files() -> Result<HashSet<PathBuf>> {
+    Ok(fs::read_dir(&*paths::DIAGNOSTICS_DIR)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect())
+}
+
+/// A report not in `existing` written by the process running Foundry's `main.js`
+fn new_foundry_report(existing: &HashSet<PathBuf>) -> Result<Option<PathBuf>> {
+    let mut reports: Vec<PathBuf> = report_files()?.difference(existing).cloned().collect();
+    // Report names start with date and time, the latest sorts last
+    reports.sort();
+    let report = reports.into_iter().rev().find(|path| {
+        // npx receives the signal as well, only keep reports of `node main.js`
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Value>(&contents).ok())
+            .and_then(|report| {
+                report["header"]["commandLine"][1]
+                    .as_str()
+                    .map(String::from)
+            })
+            .is_some_and(|script| script.ends_with("main.js"))
+    });
+    Ok(report)
+}
+
+fn log_report(path: &PathBuf) {
+    let report: Value = match fs::read_to_string(path).map(|c| serde_json::from_str(&c)) {
+        Ok(Ok(report)) => report,
+        _ => {
+            warn!("Unreadable diagnostic report {}", path.display());
+            return;
+        }
+    };
+    error!(
+        "JavaScript stack of the Foundry process ({}):",
+        path.display()
+    );
+    if let Some(message) = report["javascriptStack"]["message"]
+        .as_str()
+        .filter(|m| !m.is_empty())
+    {
+        error!("  {}", message);
+    }
+    for frame in report["javascriptStack"]["stack"]
+        .as_array()
+        .into_iter()
+        .flatten()
+    {
+        error!("  {}", frame.as_str().unwrap_or_default());
+    }
+    info!("Full diagnostic report saved to {}", path.display());
+}
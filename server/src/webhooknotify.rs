@@ -0,0 +1,114 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use serde_json::{Value, json};
+
+use crate::config::AppConfig;
+use crate::notify::{Notification, Notifier, Severity};
+use crate::resilience::{self, Service};
+
+/// Body of the posted notifications
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookFormat {
+    /// `{"severity", "title", "message", "time"}` for chat bridges and automation tools
+    Json,
+    /// An embed coloured by severity, for a Discord channel webhook
+    Discord,
+}
+
+/// Posts notifications to `NOTIFY_WEBHOOK_URL`
+#[derive(Clone)]
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+    format: WebhookFormat,
+    min_severity: Severity,
+}
+
+impl WebhookNotifier {
+    /// Build the notifier from the `NOTIFY_WEBHOOK_*` settings, `None` without a URL
+    pub fn from_config(config: &AppConfig) -> Result<Option<Self>> {
+        let Some(url) = &config.notify_webhook_url else {
+            return Ok(None);
+        };
+        let format = match config.notify_webhook_format.as_str() {
+            "auto" if is_discord(url) => WebhookFormat::Discord,
+            "auto" | "json" => WebhookFormat::Json,
+            "discord" => WebhookFormat::Discord,
+            other => return Err(anyhow!("Unknown NOTIFY_WEBHOOK_FORMAT '{}'", other)),
+        };
+        let min_severity = Severity::parse(&config.notify_webhook_min_severity)
+            .context("Invalid NOTIFY_WEBHOOK_MIN_SEVERITY")?;
+        Ok(Some(Self {
+            client: reqwest::Client::new(),
+            url: url.clone(),
+            format,
+            min_severity,
+        }))
+    }
+
+    fn body(&self, notification: &Notification) -> Value {
+        let time = Utc::now().to_rfc3339();
+        match self.format {
+            WebhookFormat::Json => json!({
+                "severity": notification.severity.as_str(),
+                "title": notification.title,
+                "message": notification.message,
+                "time": time,
+            }),
+            WebhookFormat::Discord => json!({
+                "username": "Foundry VTT",
+                "embeds": [{
+                    "title": notification.title,
+                    // Longer descriptions are rejected
+                    "description": notification.message.chars().take(4096).collect::<String>(),
+                    "color": match notification.severity {
+                        Severity::Info => 0x2ecc71,
+                        Severity::Warning => 0xf1c40f,
+                        Severity::Critical => 0xe74c3c,
+                    },
+                    "timestamp": time,
+                }],
+            }),
+        }
+    }
+
+    async fn post(&self, notification: &Notification) -> Result<()> {
+        let body = self.body(notification);
+        resilience::call(Service::Webhooks, || async {
+            self.client
+                .post(&self.url)
+                .json(&body)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .context("Webhook delivery failed")?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn min_severity(&self) -> Severity {
+        self.min_severity
+    }
+
+    fn deliver<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.post(notification))
+    }
+}
+
+fn is_discord(url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    matches!(
+        url.host_str(),
+        Some("discord.com" | "discordapp.com" | "canary.discord.com" | "ptb.discord.com")
+    ) && url.path().starts_with("/api/webhooks/")
+}
@@ -0,0 +1,429 @@
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use anyhow::{Result, anyhow};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, PoisonError};
+use tokio::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::api::Message;
+use crate::backup::{BackupService, BackupTrigger};
+use crate::command::{CommandError, CommandRunner};
+use crate::config::AppConfig;
+use crate::export::ExportService;
+use crate::inspector::Inspector;
+use crate::launch;
+use crate::status;
+use crate::updater::Updater;
+use crate::utils::paths;
+
+/// Header carrying `sha256=<hex HMAC of the hook name, the timestamp and the body, joined by dots>`
+const SIGNATURE_HEADER: &str = "X-Hub-Signature-256";
+/// Header carrying the Unix time the request was signed at
+const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+/// Requests signed longer ago or ahead are refused, and signatures are remembered this long
+const MAX_AGE: Duration = Duration::from_secs(300);
+/// Scripts still running after this are killed, e.g. when they wait on a hung network share
+const SCRIPT_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// What an inbound webhook triggers
+#[derive(Debug, Clone)]
+pub enum WebhookAction {
+    /// Restart the Foundry process
+    Restart,
+    /// Take a snapshot like `backup create`
+    Backup,
+    /// Install the newest release of the update channel, or the version given, and restart
+    /// into it
+    Update(Option<String>),
+    /// Export the actors and journals of a world
+    Export(String),
+    /// Run a script, the request body is passed on stdin
+    Script(String),
+    /// Write a heap snapshot of the Foundry process
+    HeapSnapshot,
+    /// Record a CPU profile of the Foundry process for the given seconds
+    CpuProfile(u64),
+}
+
+impl WebhookAction {
+    /// Parse `restart`, `backup`, `update`, `update:<version>`, `export:<world>`,
+    /// `script:<path>`, `heap-snapshot` or `cpu-profile:<seconds>`
+    pub fn parse(action: &str) -> Result<Self> {
+        match action.split_once(':') {
+            None if action == "restart" => Ok(Self::Restart),
+            None if action == "backup" => Ok(Self::Backup),
+            None if action == "update" => Ok(Self::Update(None)),
+            Some(("update", version)) if !version.is_empty() => {
+                Ok(Self::Update(Some(version.to_string())))
+            }
+            None if action == "heap-snapshot" => Ok(Self::HeapSnapshot),
+            Some(("cpu-profile", seconds)) => {
+                Ok(Self::CpuProfile(seconds.parse().map_err(|_| {
+                    anyhow!("Invalid profile duration '{}'", seconds)
+                })?))
+            }
+            Some(("export", world)) if !world.is_empty() => Ok(Self::Export(world.to_string())),
+            Some(("script", path)) if !path.is_empty() => Ok(Self::Script(path.to_string())),
+            _ => Err(anyhow!("Unknown webhook action '{}'", action)),
+        }
+    }
+
+    async fn run(self, name: &str, body: web::Bytes, config: &WebhookConfig) -> Result<()> {
+        match self {
+            Self::Restart => {
+                tokio::spawn(launch::restart_after_warning());
+                Ok(())
+            }
+            Self::Backup => {
+                let service = config.backup.clone();
+                let summary =
+                    tokio::task::spawn_blocking(move || service.create(BackupTrigger::Api))
+                        .await??;
+                info!(
+                    "Webhook {} backed up {} files to {}",
+                    name,
+                    summary.record.files,
+                    summary.path.display()
+                );
+                Ok(())
+            }
+            Self::Update(version) => {
+                // Like `POST /api/update`, a session is only interrupted when asked to
+                let force = serde_json::from_slice::<serde_json::Value>(&body)
+                    .is_ok_and(|body| body["force"].as_bool() == Some(true));
+                if !force
+                    && let Ok(server) = status::fetch_server_status(config.foundry_port).await
+                    && server.users > 0
+                {
+                    return Err(anyhow!(
+                        "{} players are connected, send {{\"force\": true}} to update anyway",
+                        server.users
+                    ));
+                }
+                let switched = match version {
+                    Some(version) => config.updater.install_version(&version).await?,
+                    None => config.updater.update().await?,
+                };
+                match switched {
+                    Some(version) => {
+                        info!("Webhook {} installed Foundry {}", name, version);
+                        launch::restart_after_warning().await;
+                    }
+                    None => info!("Webhook {}: the installed release is up to date", name),
+                }
+                Ok(())
+            }
+            Self::Export(world) => {
+                let target =
+                    tokio::task::spawn_blocking(move || ExportService::export_world(&world))
+                        .await??;
+                info!("Webhook {} exported to {}", name, target.display());
+                Ok(())
+            }
+            Self::HeapSnapshot => {
+                Inspector::attach().await?.heap_snapshot().await?;
+                Ok(())
+            }
+            Self::CpuProfile(seconds) => {
+                Inspector::attach()
+                    .await?
+                    .cpu_profile(Duration::from_secs(seconds))
+                    .await?;
+                Ok(())
+            }
+            Self::Script(path) => {
+                let result = CommandRunner::new(&path)
+                    .env("WEBHOOK_NAME", name)
+                    .current_dir(&*paths::DATA_DIR)
+                    .stdin(body)
+                    .timeout(SCRIPT_TIMEOUT)
+                    .run()
+                    .await
+                    .map_err(|e| match e {
+                        // A shebang ending in `\r` makes the interpreter lookup fail
+                        CommandError::Spawn { .. } => {
+                            if let Some(interpreter) = missing_interpreter(&path) {
+                                anyhow!(
+                                    "Failed to run {}: its interpreter {} does not exist in this image",
+                                    path,
+                                    interpreter
+                                )
+                            } else if has_crlf_shebang(&path) {
+                                anyhow!(
+                                    "Failed to run {}: it has Windows (CRLF) line endings, convert it with dos2unix",
+                                    path
+                                )
+                            } else {
+                                e.into()
+                            }
+                        }
+                        e => e.into(),
+                    })?;
+                for line in result.stdout.lines().filter(|l| !l.trim().is_empty()) {
+                    info!("Webhook {}: {}", name, line);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Interpreter named by the script's shebang when it is missing, e.g. `/bin/sh` in distroless
+fn missing_interpreter(path: &str) -> Option<String> {
+    let contents = std::fs::read(path).ok()?;
+    let line = contents
+        .strip_prefix(b"#!")?
+        .split(|b| *b == b'\n')
+        .next()?;
+    let line = String::from_utf8_lossy(line);
+    let interpreter = line.split_whitespace().next()?;
+    (!std::path::Path::new(interpreter).exists()).then(|| interpreter.to_string())
+}
+
+fn has_crlf_shebang(path: &str) -> bool {
+    std::fs::read(path).is_ok_and(|contents| {
+        contents.starts_with(b"#!")
+            && contents
+                .split(|b| *b == b'\n')
+                .next()
+                .is_some_and(|line| line.ends_with(b"\r"))
+    })
+}
+
+/// Configured webhooks and the secret their requests are signed with
+pub struct WebhookConfig {
+    pub secret: Option<String>,
+    pub hooks: BTreeMap<String, WebhookAction>,
+    pub backup: BackupService,
+    pub updater: Updater,
+    pub foundry_port: u16,
+}
+
+impl WebhookConfig {
+    /// The hooks of `WEBHOOKS` that parse, with what running them needs
+    pub fn from_config(config: &AppConfig) -> Self {
+        let mut hooks = BTreeMap::new();
+        for (name, action) in &config.webhooks {
+            match WebhookAction::parse(action) {
+                Ok(action) => {
+                    hooks.insert(name.clone(), action);
+                }
+                Err(e) => warn!("⚠️ Ignoring webhook {}: {}", name, e),
+            }
+        }
+        if !hooks.is_empty() && config.webhook_secret.is_none() {
+            warn!(
+                "⚠️ WEBHOOKS are configured but WEBHOOK_SECRET is not set, all hooks are rejected"
+            );
+        }
+
+        Self {
+            secret: config.webhook_secret.clone(),
+            hooks,
+            backup: BackupService::from_config(config),
+            updater: Updater::from_config(config),
+            foundry_port: config.server_port,
+        }
+    }
+}
+
+/// Verify the signature and start the hook configured in `WEBHOOKS` in the background
+#[utoipa::path(
+    post,
+    path = "/hooks/{name}",
+    operation_id = "triggerWebhook",
+    params(
+        ("name" = String, Path, description = "Name of the hook in WEBHOOKS"),
+        ("X-Webhook-Timestamp" = i64, Header, description = "Unix time the request was signed at, at most five minutes off"),
+        ("X-Hub-Signature-256" = String, Header, description = "`sha256=` and the hex HMAC-SHA256 of the hook name, the timestamp and the body, joined by `.`, with WEBHOOK_SECRET"),
+    ),
+    request_body(content = Vec<u8>, description = "Passed to the hook, e.g. the payload a CI system sends", content_type = "*/*"),
+    responses(
+        (status = 202, description = "The hook was started", body = Message),
+        (status = 401, description = "Invalid, stale or replayed signature", body = Message),
+        (status = 403, description = "WEBHOOK_SECRET is not set", body = Message),
+        (status = 404, description = "No hook of that name", body = Message),
+    )
+)]
+pub async fn handle_hook(
+    req: HttpRequest,
+    name: web::Path<String>,
+    body: web::Bytes,
+    config: web::Data<WebhookConfig>,
+) -> impl Responder {
+    let name = name.into_inner();
+
+    let Some(secret) = &config.secret else {
+        warn!("Rejected webhook {}: WEBHOOK_SECRET is not set", name);
+        return HttpResponse::Forbidden().json(Message::new("Webhooks are disabled"));
+    };
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+    };
+    if let Err(e) = verify_request(
+        secret,
+        &name,
+        header(TIMESTAMP_HEADER),
+        &body,
+        header(SIGNATURE_HEADER),
+        chrono::Utc::now().timestamp(),
+    ) {
+        warn!("Rejected webhook {}: {}", name, e);
+        return HttpResponse::Unauthorized().json(Message::new(e.to_string()));
+    }
+
+    let Some(action) = config.hooks.get(&name).cloned() else {
+        return HttpResponse::NotFound().json(Message::new(format!("No webhook named {}", name)));
+    };
+
+    info!("🪝 Webhook {} triggered {:?}", name, action);
+    let hook_name = name.clone();
+    // Downloads of updates are not Send, the action runs on the worker of the admin API
+    actix_web::rt::spawn(async move {
+        if let Err(e) = action.run(&hook_name, body, &config).await {
+            error!("❌ Webhook {} failed: {:#}", hook_name, e);
+        }
+    });
+
+    HttpResponse::Accepted().json(Message::new(format!("Webhook {} accepted", name)))
+}
+
+/// Check that the request to the hook `name` was signed with `secret` within `MAX_AGE` of
+/// `now` and not seen before, so a captured request cannot be sent again or to another hook
+fn verify_request(
+    secret: &str,
+    name: &str,
+    timestamp: &str,
+    body: &[u8],
+    signature: &str,
+    now: i64,
+) -> Result<()> {
+    let signed_at: i64 = timestamp
+        .parse()
+        .map_err(|_| anyhow!("Missing or invalid {} header", TIMESTAMP_HEADER))?;
+    let Some(mac) = signature.strip_prefix("sha256=").and_then(decode_hex) else {
+        return Err(anyhow!("Invalid signature"));
+    };
+    if !verify_mac(secret, name, timestamp, body, &mac) {
+        return Err(anyhow!("Invalid signature"));
+    }
+    if signed_at.abs_diff(now) > MAX_AGE.as_secs() {
+        return Err(anyhow!("The request was signed too long ago"));
+    }
+
+    let mut seen = SEEN_SIGNATURES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    seen.retain(|_, at| at.abs_diff(now) <= MAX_AGE.as_secs());
+    // Keyed by the decoded MAC, hex digits are accepted in either case
+    if seen.insert(mac, signed_at).is_some() {
+        return Err(anyhow!("The request was sent before"));
+    }
+    Ok(())
+}
+
+/// MACs of the requests accepted within `MAX_AGE`, with the time they were signed at
+static SEEN_SIGNATURES: Mutex<BTreeMap<Vec<u8>, i64>> = Mutex::new(BTreeMap::new());
+
+/// Check the HMAC `expected` of `<name>.<timestamp>.<body>` in constant time
+fn verify_mac(secret: &str, name: &str, timestamp: &str, body: &[u8], expected: &[u8]) -> bool {
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(name.as_bytes());
+    mac.update(b".");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac.verify_slice(expected).is_ok()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "It is a secret";
+    const BODY: &[u8] = br#"{"world":"w"}"#;
+    /// `printf 'nightly.1700000000.{"world":"w"}' | openssl dgst -sha256 -hmac 'It is a secret'`
+    const SIGNATURE: &str =
+        "sha256=ef96ba6ae501d9af4cc28d9e1764c64a7aa8b50780b243b2c1739b26bba98931";
+
+    fn mac() -> Vec<u8> {
+        decode_hex(SIGNATURE.strip_prefix("sha256=").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn signature_covers_name_timestamp_and_body() {
+        let mac = mac();
+        assert!(verify_mac(SECRET, "nightly", "1700000000", BODY, &mac));
+        assert!(!verify_mac(SECRET, "redeploy", "1700000000", BODY, &mac));
+        assert!(!verify_mac(SECRET, "nightly", "1700000001", BODY, &mac));
+        assert!(!verify_mac(SECRET, "nightly", "1700000000", b"{}", &mac));
+        assert!(!verify_mac("other", "nightly", "1700000000", BODY, &mac));
+        assert!(!verify_mac(
+            SECRET,
+            "nightly",
+            "1700000000",
+            BODY,
+            &mac[..4]
+        ));
+    }
+
+    #[test]
+    fn stale_and_replayed_requests_are_refused() {
+        let signed_at = 1_700_000_000;
+        let request = |name: &str, timestamp: &str, signature: &str, now: i64| {
+            verify_request(SECRET, name, timestamp, BODY, signature, now)
+        };
+        assert!(request("nightly", "", SIGNATURE, signed_at).is_err());
+        assert!(request("nightly", "1700000000", SIGNATURE, signed_at + 301).is_err());
+        assert!(request("nightly", "1700000000", SIGNATURE, signed_at - 301).is_err());
+        assert!(request("redeploy", "1700000000", SIGNATURE, signed_at + 5).is_err());
+        assert!(request("nightly", "1700000000", SIGNATURE, signed_at + 10).is_ok());
+        let replayed = request("nightly", "1700000000", SIGNATURE, signed_at + 20);
+        assert_eq!(
+            replayed.unwrap_err().to_string(),
+            "The request was sent before"
+        );
+        // The same MAC with its hex digits in upper case
+        let recased = format!("sha256={}", SIGNATURE["sha256=".len()..].to_uppercase());
+        let replayed = request("nightly", "1700000000", &recased, signed_at + 30);
+        assert_eq!(
+            replayed.unwrap_err().to_string(),
+            "The request was sent before"
+        );
+    }
+
+    #[test]
+    fn actions_parse() {
+        assert!(matches!(
+            WebhookAction::parse("backup"),
+            Ok(WebhookAction::Backup)
+        ));
+        assert!(matches!(
+            WebhookAction::parse("update"),
+            Ok(WebhookAction::Update(None))
+        ));
+        assert!(matches!(
+            WebhookAction::parse("update:12.331"),
+            Ok(WebhookAction::Update(Some(version))) if version == "12.331"
+        ));
+        assert!(WebhookAction::parse("update:").is_err());
+        assert!(WebhookAction::parse("backups").is_err());
+    }
+}
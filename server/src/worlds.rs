@@ -0,0 +1,401 @@
+use anyhow::{Context, Result, anyhow};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+use crate::db::Collection;
+use crate::i18n;
+use crate::permissions::FileKind;
+use crate::scan::ScanService;
+use crate::state::{WorldLock, WrapperState};
+use crate::status;
+use crate::storage::storage;
+use crate::utils::paths;
+
+/// Notice shown on the join screen of a locked world when no message is given
+pub fn default_lock_message() -> String {
+    i18n::tr("maintenance-locked")
+}
+
+/// A world of the data directory as `world list` shows it
+#[derive(Debug, Serialize)]
+pub struct WorldSummary {
+    pub id: String,
+    pub title: String,
+    pub system: Option<String>,
+    /// Foundry version the world was last verified with
+    pub core_version: Option<String>,
+    pub last_played: Option<String>,
+    pub bytes: u64,
+    pub locked: bool,
+}
+
+pub struct WorldService;
+
+impl WorldService {
+    /// Ids of all worlds in the data directory
+    pub fn ids() -> Result<Vec<String>> {
+        let mut ids = Vec::new();
+        let entries = match storage().list(&paths::WORLDS_DIR) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(ids),
+            Err(e) => return Err(e).context("Failed to list worlds"),
+        };
+        for entry in entries {
+            if storage().exists(&entry.path.join("world.json"))
+                && let Some(name) = entry.path.file_name()
+            {
+                ids.push(name.to_string_lossy().to_string());
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Every world of the data directory with the details of its manifest
+    pub fn list() -> Result<Vec<WorldSummary>> {
+        let locks = WrapperState::load()?.world_locks;
+        let mut worlds = Vec::new();
+        for id in Self::ids()? {
+            let manifest = match read_manifest(&id) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    warn!("Skipping world {}: {:#}", id, e);
+                    continue;
+                }
+            };
+            let bytes = ScanService::walk(&paths::WORLDS_DIR.join(&id), &[])?
+                .iter()
+                .map(|entry| entry.size)
+                .sum();
+            worlds.push(WorldSummary {
+                title: manifest["title"].as_str().unwrap_or(&id).to_string(),
+                system: manifest["system"].as_str().map(str::to_string),
+                core_version: manifest["compatibility"]["verified"]
+                    .as_str()
+                    .or(manifest["coreVersion"].as_str())
+                    .map(str::to_string),
+                last_played: manifest["lastPlayed"].as_str().map(str::to_string),
+                bytes,
+                locked: locks.contains_key(&id),
+                id,
+            });
+        }
+        Ok(worlds)
+    }
+
+    /// Lock a world, replacing its description on the join screen with `message`
+    pub fn lock(world_id: &str, message: &str) -> Result<()> {
+        validate_id(world_id)?;
+        let mut state = WrapperState::load()?;
+        let mut manifest = read_manifest(world_id)?;
+
+        // Keep the description from before the first lock when re-locking with a new message
+        let original_description = match state.world_locks.get(world_id) {
+            Some(lock) => lock.original_description.clone(),
+            None => manifest["description"].as_str().map(|d| d.to_string()),
+        };
+
+        manifest["description"] = Value::String(format!(
+            "<p><strong>🔒 {}</strong></p>",
+            escape_html(message)
+        ));
+        write_manifest(world_id, &manifest)?;
+
+        state.world_locks.insert(
+            world_id.to_string(),
+            WorldLock {
+                message: message.to_string(),
+                locked_at: chrono::Utc::now().to_rfc3339(),
+                original_description,
+            },
+        );
+        state.save()?;
+        forget_locks();
+
+        info!("🔒 Locked world {}: {}", world_id, message);
+        Ok(())
+    }
+
+    /// Remove the lock from a world and restore its original description
+    pub fn unlock(world_id: &str) -> Result<()> {
+        validate_id(world_id)?;
+        let mut state = WrapperState::load()?;
+        let lock = state
+            .world_locks
+            .remove(world_id)
+            .ok_or_else(|| anyhow!("World {} is not locked", world_id))?;
+
+        match read_manifest(world_id) {
+            Ok(mut manifest) => {
+                manifest["description"] = match lock.original_description {
+                    Some(description) => Value::String(description),
+                    None => Value::Null,
+                };
+                write_manifest(world_id, &manifest)?;
+            }
+            // The world may have been deleted while locked, the lock entry is stale either way
+            Err(e) => warn!("Could not restore description of world {}: {}", world_id, e),
+        }
+        state.save()?;
+        forget_locks();
+
+        info!("🔓 Unlocked world {}", world_id);
+        Ok(())
+    }
+
+    /// Copy a world to a new id, so it can be launched next to the original
+    pub fn duplicate(source_id: &str, target_id: &str, title: &str) -> Result<()> {
+        validate_id(source_id)?;
+        validate_id(target_id)?;
+        let source = paths::WORLDS_DIR.join(source_id);
+        let target = paths::WORLDS_DIR.join(target_id);
+        if !storage().exists(&source.join("world.json")) {
+            return Err(anyhow!(
+                "World {} not found at {}",
+                source_id,
+                source.display()
+            ));
+        }
+        if storage().exists(&target) {
+            return Err(anyhow!("World {} already exists", target_id));
+        }
+
+        for entry in ScanService::walk(&source, &[])? {
+            let destination = target.join(entry.path.strip_prefix(&source)?);
+            if let Some(parent) = destination.parent() {
+                storage()
+                    .create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            storage()
+                .copy(&entry.path, &destination)
+                .with_context(|| format!("Failed to copy {}", entry.path.display()))?;
+        }
+
+        // v10+ manifests identify the world by `id`, older ones by `name`
+        let mut manifest = read_manifest(target_id)?;
+        for key in ["id", "name"] {
+            if manifest.get(key).is_some() {
+                manifest[key] = Value::String(target_id.to_string());
+            }
+        }
+        manifest["title"] = Value::String(title.to_string());
+        write_manifest(target_id, &manifest)?;
+
+        info!("Duplicated world {} to {}", source_id, target_id);
+        Ok(())
+    }
+
+    /// Permanently delete a world directory
+    pub fn remove(world_id: &str) -> Result<()> {
+        validate_id(world_id)?;
+        let dir = paths::WORLDS_DIR.join(world_id);
+        storage()
+            .remove_dir_all(&dir)
+            .with_context(|| format!("Failed to remove {}", dir.display()))?;
+        info!("Removed world {}", world_id);
+        Ok(())
+    }
+
+    /// All currently locked worlds
+    pub fn locks() -> Result<Vec<(String, WorldLock)>> {
+        Ok(WrapperState::load()?.world_locks.into_iter().collect())
+    }
+}
+
+/// Ids of the locked worlds and the modification time of the state file they were read from.
+/// Management commands lock worlds from another process, a newer state file is read again.
+static LOCKED_WORLDS: Mutex<Option<(Option<SystemTime>, BTreeSet<String>)>> = Mutex::new(None);
+
+/// Whether any world is locked, joins only need checking then. Asked for every proxied
+/// request, so the state file is only read again when it changed.
+pub fn any_locked() -> bool {
+    let modified = fs::metadata(WrapperState::path())
+        .and_then(|metadata| metadata.modified())
+        .ok();
+    let mut cached = LOCKED_WORLDS.lock().unwrap_or_else(PoisonError::into_inner);
+    match &*cached {
+        Some((at, locked)) if *at == modified => !locked.is_empty(),
+        _ => {
+            let locked: BTreeSet<String> = WrapperState::load()
+                .map(|state| state.world_locks.into_keys().collect())
+                .unwrap_or_default();
+            let any = !locked.is_empty();
+            *cached = Some((modified, locked));
+            any
+        }
+    }
+}
+
+/// Drop the cached lock set after locking or unlocking a world
+fn forget_locks() {
+    *LOCKED_WORLDS.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+/// Foundry's role of assistant GMs, users with it or above may join a locked world to prepare it
+const PREPARING_ROLE: u64 = 3;
+
+/// The notice refusing `user_id` to join the active world while it is locked, none for GMs and
+/// assistants or when the active world is not locked
+pub async fn join_refusal(foundry_port: u16, user_id: &str) -> Option<String> {
+    let mut locks = WrapperState::load().ok()?.world_locks;
+    let world = status::fetch_server_status(foundry_port)
+        .await
+        .ok()?
+        .world
+        .filter(|world| locks.contains_key(world))?;
+    let lock = locks.remove(&world)?;
+
+    let lookup = (world.clone(), user_id.to_string());
+    let role = tokio::task::spawn_blocking(move || user_role(&lookup.0, &lookup.1))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|role| role);
+    match role {
+        Ok(Some(role)) if role >= PREPARING_ROLE => None,
+        Ok(_) => {
+            info!(
+                "🔒 Refused user {} joining the locked world {}",
+                user_id, world
+            );
+            Some(lock.message)
+        }
+        // Without the role the lock holds, a GM can still unlock the world
+        Err(e) => {
+            warn!("Could not read the users of world {}: {:#}", world, e);
+            Some(lock.message)
+        }
+    }
+}
+
+/// Role of the user `user_id` in the world, read from a copy of its database
+fn user_role(world_id: &str, user_id: &str) -> Result<Option<u64>> {
+    let mut users = Collection::open_snapshot(world_id, "users")?;
+    Ok(users
+        .documents()?
+        .iter()
+        .find(|user| user["_id"].as_str() == Some(user_id))
+        .and_then(|user| user["role"].as_u64()))
+}
+
+/// Names of the world's users by id, read from a copy of its database
+pub fn user_names(world_id: &str) -> Result<HashMap<String, String>> {
+    let mut users = Collection::open_snapshot(world_id, "users")?;
+    Ok(users
+        .documents()?
+        .iter()
+        .filter_map(|user| {
+            Some((
+                user["_id"].as_str()?.to_string(),
+                user["name"].as_str()?.to_string(),
+            ))
+        })
+        .collect())
+}
+
+/// Check that `manifest` is that of a world Foundry can load and return its id.
+///
+/// v10+ manifests identify the world by `id`, older ones by `name`. The id is the name of the
+/// world's directory, so it must be usable as one.
+pub fn validate_manifest(manifest: &Value) -> Result<String> {
+    if !manifest.is_object() {
+        return Err(anyhow!("world.json is not a JSON object"));
+    }
+    let id = manifest["id"]
+        .as_str()
+        .or(manifest["name"].as_str())
+        .ok_or_else(|| anyhow!("world.json has no id"))?;
+    validate_id(id)?;
+    for key in ["title", "system"] {
+        if manifest[key]
+            .as_str()
+            .is_none_or(|value| value.trim().is_empty())
+        {
+            return Err(anyhow!("world.json of {} has no {}", id, key));
+        }
+    }
+    Ok(id.to_string())
+}
+
+/// World ids become directory names below `Data/worlds`
+pub fn validate_id(id: &str) -> Result<()> {
+    let valid = !id.is_empty()
+        && id != "."
+        && id != ".."
+        && id
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(anyhow!(
+            "Invalid world id {:?}, use letters, digits, '-', '_' and '.'",
+            id
+        ));
+    }
+    Ok(())
+}
+
+/// The active world's database is open, copying or replacing it would miss what Foundry
+/// writes next
+pub async fn refuse_active(foundry_port: u16, world: &str, action: &str) -> Result<()> {
+    match status::fetch_server_status(foundry_port).await {
+        Ok(server) if server.active && server.world.as_deref() == Some(world) => Err(anyhow!(
+            "World {} is active, return to the setup screen before {}",
+            world,
+            action
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn manifest_path(world_id: &str) -> PathBuf {
+    paths::WORLDS_DIR.join(world_id).join("world.json")
+}
+
+fn read_manifest(world_id: &str) -> Result<Value> {
+    let path = manifest_path(world_id);
+    let contents = storage()
+        .read_to_string(&path)
+        .with_context(|| format!("World {} not found at {}", world_id, path.display()))?;
+    serde_json::from_str(&contents)
+        .with_context(|| format!("Invalid world manifest {}", path.display()))
+}
+
+fn write_manifest(world_id: &str, manifest: &Value) -> Result<()> {
+    let path = manifest_path(world_id);
+    // Foundry writes its manifests with two space indentation as well
+    storage()
+        .write(
+            &path,
+            serde_json::to_string_pretty(manifest)?.as_bytes(),
+            FileKind::Regular,
+        )
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_must_stay_below_the_worlds_directory() {
+        for id in ["my-world", "campaign_2", "v1.2", "Töwn"] {
+            assert!(validate_id(id).is_ok(), "{} was refused", id);
+        }
+        for id in ["", ".", "..", "../other", "a/b", "a\\b", "with space"] {
+            assert!(validate_id(id).is_err(), "{:?} was accepted", id);
+        }
+    }
+}
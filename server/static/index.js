@@ -2,6 +2,27 @@
 (() => {
   "use strict";
 
+  // Messages in the configured language, rendered into the page by the wrapper
+  const messages = (() => {
+    try {
+      return JSON.parse(document.getElementById("messages").textContent);
+    } catch {
+      return {};
+    }
+  })();
+
+  /**
+   * Looks up a translated message and fills in its `{$name}` placeholders.
+   * @param {string} id - The message id from the wrapper's catalog.
+   * @param {string} fallback - English text used when the message is missing.
+   * @param {object} args - Values for the placeholders.
+   */
+  const t = (id, fallback, args = {}) =>
+    Object.entries(args).reduce(
+      (text, [name, value]) => text.replaceAll(`{$${name}}`, value),
+      messages[id] || fallback,
+    );
+
   // Track global state
   const state = {
     eventSourceConnected: false,
@@ -24,6 +45,10 @@
    * Initializes tab switching functionality.
    */
   const initTabs = () => {
+    // Without JavaScript both forms are shown one below the other
+    const tabList = document.querySelector(".method-tabs");
+    if (tabList) tabList.hidden = false;
+
     const tabs = ["url", "file"];
     tabs.forEach((tab) => {
       const tabElement = document.getElementById(`${tab}-tab`);
@@ -44,7 +69,10 @@
   const switchTab = (tabName) => {
     document
       .querySelectorAll(".tab-button")
-      .forEach((tab) => tab.classList.remove("active"));
+      .forEach((tab) => {
+        tab.classList.remove("active");
+        tab.setAttribute("aria-selected", "false");
+      });
     document
       .querySelectorAll(".method-content")
       .forEach((content) => (content.style.display = "none"));
@@ -53,6 +81,7 @@
     const activeContent = document.getElementById(`${tabName}-method`);
     if (activeTab && activeContent) {
       activeTab.classList.add("active");
+      activeTab.setAttribute("aria-selected", "true");
       activeContent.style.display = "block";
     }
   };
@@ -79,12 +108,21 @@
         throw new Error(`Server responded with status ${response.status}`);
       }
       showToast(
-        "Process initiated. The server will exit once the operation is complete.",
+        t(
+          "setup-js-started",
+          "Process initiated. The server will exit once the operation is complete.",
+        ),
         "green",
       );
     } catch (error) {
       console.error(`Error with ${endpoint}:`, error);
-      showToast("Failed to process request. Please try again.", "red");
+      showToast(
+        t(
+          "setup-js-request-failed",
+          "Failed to process request. Please try again.",
+        ),
+        "red",
+      );
       showProcessingUI(false);
     }
   };
@@ -93,15 +131,16 @@
    * Initializes URL download functionality.
    */
   const initUrlDownload = () => {
-    const downloadButton = document.getElementById("download-button");
-    if (downloadButton) {
-      downloadButton.addEventListener("click", async () => {
+    const urlForm = document.getElementById("url-method");
+    if (urlForm) {
+      urlForm.addEventListener("submit", async (e) => {
+        e.preventDefault();
         const urlInput = document.getElementById("url-input");
         const url = urlInput ? urlInput.value : "";
         if (url) {
           await processRequest("/download", { url });
         } else {
-          showToast("Please enter a URL.", "red");
+          showToast(t("setup-js-enter-url", "Please enter a URL."), "red");
         }
       });
     }
@@ -115,13 +154,24 @@
     const fileInput = document.getElementById("file-input");
     const fileNameEl = document.getElementById("file-name");
     const uploadButton = document.getElementById("upload-button");
+    const uploadForm = document.getElementById("file-method");
     const dropArea = document.getElementById("drop-area");
 
-    if (!fileInput || !fileNameEl || !uploadButton || !dropArea) {
+    if (
+      !fileInput ||
+      !fileNameEl ||
+      !uploadButton ||
+      !uploadForm ||
+      !dropArea
+    ) {
       console.warn("Some file upload elements are missing in the DOM.");
       return;
     }
 
+    // The styled button and drag and drop replace the plain file input
+    fileInput.hidden = true;
+    uploadButton.disabled = true;
+
     // Update file selection display
     const updateFileSelection = (file) => {
       fileNameEl.textContent = file.name;
@@ -137,16 +187,21 @@
 
     const fileSelectButton = document.getElementById("file-select-button");
     if (fileSelectButton) {
+      fileSelectButton.hidden = false;
       fileSelectButton.addEventListener("click", () => fileInput.click());
     }
 
-    uploadButton.addEventListener("click", async () => {
+    uploadForm.addEventListener("submit", async (e) => {
+      e.preventDefault();
       if (selectedFile) {
         const formData = new FormData();
         formData.append("file", selectedFile);
         await processRequest("/upload", formData);
       } else {
-        showToast("Please select a file to upload.", "red");
+        showToast(
+          t("setup-js-select-file", "Please select a file to upload."),
+          "red",
+        );
       }
     });
 
@@ -205,11 +260,10 @@
     if (toast) {
       toast.textContent = message;
       toast.className = "toast";
-      toast.classList.add(color);
-      toast.style.display = "block";
+      toast.classList.add(color, "show");
       setTimeout(() => {
-        toast.style.display = "none";
-      }, 5000);
+        toast.classList.remove("show");
+      }, 3000);
     }
   };
 
@@ -269,6 +323,10 @@
 
         if (data.progress !== undefined && progressBar) {
           progressBar.style.width = `${data.progress}%`;
+          progressBar.parentElement.setAttribute(
+            "aria-valuenow",
+            Math.round(data.progress),
+          );
         }
         if (data.message && progressMessage) {
           progressMessage.textContent = data.message;
@@ -287,15 +345,20 @@
 
           if (isError) {
             showToast(
-              "An error occurred: " + (data.message || "Unknown error"),
+              t("setup-js-error", "An error occurred: {$message}", {
+                message:
+                  data.message || t("setup-js-unknown-error", "Unknown error"),
+              }),
               "red",
             );
             showProcessingUI(false);
           } else if (isComplete) {
             state.processingComplete = true;
             if (progressMessage) {
-              progressMessage.textContent =
-                "Installation complete! Waiting 30 seconds for server to shut down...";
+              progressMessage.textContent = t(
+                "setup-js-complete",
+                "Installation complete! Waiting 30 seconds for server to shut down...",
+              );
             }
             // Hard 20-second wait after complete event
             setTimeout(waitForServerShutdown, 30000);
@@ -305,7 +368,13 @@
         console.error("Error processing event:", error, event.data);
         state.eventSourceConnected = false;
         eventSource.close();
-        showToast("Error processing server event. Please try again.", "red");
+        showToast(
+          t(
+            "setup-js-event-error",
+            "Error processing server event. Please try again.",
+          ),
+          "red",
+        );
         showProcessingUI(false);
       }
     };
@@ -321,7 +390,13 @@
         );
         setTimeout(waitForServerShutdown, 10000);
       } else if (!state.processingComplete) {
-        showToast("Lost connection to server. Please try again.", "red");
+        showToast(
+          t(
+            "setup-js-connection-lost",
+            "Lost connection to server. Please try again.",
+          ),
+          "red",
+        );
         showProcessingUI(false);
       }
 
@@ -335,7 +410,13 @@
         console.warn("EventSource timeout - no activity detected");
         state.eventSourceConnected = false;
         eventSource.close();
-        showToast("Server did not respond in time. Please try again.", "red");
+        showToast(
+          t(
+            "setup-js-no-response",
+            "Server did not respond in time. Please try again.",
+          ),
+          "red",
+        );
         showProcessingUI(false);
       }
     }, 60000); // 1 minute timeout
@@ -361,7 +442,11 @@
     const tryRedirect = async () => {
       attempts++;
       if (redirectStatus) {
-        redirectStatus.textContent = `Checking for Foundry VTT (${attempts}/${maxAttempts})...`;
+        redirectStatus.textContent = t(
+          "setup-js-checking",
+          "Checking for Foundry VTT ({$attempts}/{$max})...",
+          { attempts, max: maxAttempts },
+        );
       }
 
       try {
@@ -382,27 +467,41 @@
 
         if (rootResponse.status === 200) {
           if (redirectStatus) {
-            redirectStatus.textContent = "Foundry VTT is ready! Redirecting...";
+            redirectStatus.textContent = t(
+              "setup-js-ready",
+              "Foundry VTT is ready! Redirecting...",
+            );
           }
           setTimeout(() => (window.location.href = "/"), 1000);
         } else if (licenseResponse.status === 200) {
           if (redirectStatus) {
-            redirectStatus.textContent =
-              "Foundry VTT license page is ready! Redirecting...";
+            redirectStatus.textContent = t(
+              "setup-js-license-ready",
+              "Foundry VTT license page is ready! Redirecting...",
+            );
           }
           setTimeout(() => (window.location.href = "/license"), 1000);
         } else if (attempts < maxAttempts) {
           if (redirectStatus) {
-            redirectStatus.textContent = `Foundry VTT not ready yet. Next check in ${delayBetweenAttempts / 1000} seconds...`;
+            redirectStatus.textContent = t(
+              "setup-js-not-ready",
+              "Foundry VTT not ready yet. Next check in {$seconds} seconds...",
+              { seconds: delayBetweenAttempts / 1000 },
+            );
           }
           setTimeout(tryRedirect, delayBetweenAttempts);
         } else {
           if (redirectStatus) {
-            redirectStatus.textContent =
-              "Max redirect attempts reached. Please try manually refreshing the page.";
+            redirectStatus.textContent = t(
+              "setup-js-max-attempts",
+              "Max redirect attempts reached. Please try manually refreshing the page.",
+            );
           }
           showToast(
-            "Foundry VTT might need more time to start. Try refreshing this page in a moment.",
+            t(
+              "setup-js-needs-time",
+              "Foundry VTT might need more time to start. Try refreshing this page in a moment.",
+            ),
             "orange",
           );
         }
@@ -410,16 +509,25 @@
         console.warn("Redirect check failed:", error);
         if (attempts < maxAttempts) {
           if (redirectStatus) {
-            redirectStatus.textContent = `Connection attempt failed. Retrying in ${delayBetweenAttempts / 1000} seconds...`;
+            redirectStatus.textContent = t(
+              "setup-js-retrying",
+              "Connection attempt failed. Retrying in {$seconds} seconds...",
+              { seconds: delayBetweenAttempts / 1000 },
+            );
           }
           setTimeout(tryRedirect, delayBetweenAttempts);
         } else {
           if (redirectStatus) {
-            redirectStatus.textContent =
-              "Unable to connect to Foundry VTT after multiple attempts. Please refresh manually.";
+            redirectStatus.textContent = t(
+              "setup-js-unreachable",
+              "Unable to connect to Foundry VTT after multiple attempts. Please refresh manually.",
+            );
           }
           showToast(
-            "Couldn't connect to Foundry VTT. Try refreshing this page in a moment.",
+            t(
+              "setup-js-couldnt-connect",
+              "Couldn't connect to Foundry VTT. Try refreshing this page in a moment.",
+            ),
             "red",
           );
         }
@@ -453,11 +561,16 @@
     }
 
     if (progressMessage) {
-      progressMessage.textContent =
-        "Server shutdown period complete. Starting redirect attempts...";
+      progressMessage.textContent = t(
+        "setup-js-shutdown-complete",
+        "Server shutdown period complete. Starting redirect attempts...",
+      );
     }
     if (shutdownStatus) {
-      shutdownStatus.textContent = "Beginning redirect attempts...";
+      shutdownStatus.textContent = t(
+        "setup-js-redirecting",
+        "Beginning redirect attempts...",
+      );
     }
 
     // Start redirect attempts immediately after the 20-second timeout